@@ -1,12 +1,20 @@
 //! Clerk JWT authentication
+//!
+//! Protected routes sit behind [`require_auth`], which validates the bearer
+//! token (signature, expiry and, when configured, audience) and stores the
+//! user in the request. Handlers take it with the [`AuthUser`] extractor.
 
 use axum::{
-    extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, HeaderMap, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Response},
 };
 use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::AppState;
 
 /// Authenticated user extracted from JWT
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,20 +36,37 @@ struct ClerkClaims {
     iat: usize,
 }
 
-/// Clerk authentication extractor
-pub struct ClerkAuth(pub AuthenticatedUser);
+/// Validates Clerk session tokens against the configured public key
+#[derive(Clone)]
+pub struct JwtValidator {
+    /// `None` when `CLERK_PUBLIC_KEY` is missing or not an RSA PEM
+    key: Option<Arc<DecodingKey>>,
+    validation: Validation,
+}
 
-#[axum::async_trait]
-impl<S> FromRequestParts<S> for ClerkAuth
-where
-    S: Send + Sync,
-{
-    type Rejection = AuthError;
+impl JwtValidator {
+    /// Validator for RS256 tokens signed by `public_key_pem`; tokens must
+    /// name `audience` when one is given
+    pub fn new(public_key_pem: &str, audience: Option<&str>) -> Self {
+        let key = DecodingKey::from_rsa_pem(public_key_pem.as_bytes()).ok().map(Arc::new);
+        if key.is_none() {
+            tracing::warn!("CLERK_PUBLIC_KEY is not a valid RSA public key; all tokens will be refused");
+        }
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        match audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        Self { key, validation }
+    }
+
+    /// Validate the request's bearer token
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<AuthenticatedUser, AuthError> {
         // Get Authorization header
-        let auth_header = parts
-            .headers
+        let auth_header = headers
             .get("Authorization")
             .and_then(|h| h.to_str().ok())
             .ok_or(AuthError::MissingToken)?;
@@ -51,40 +76,72 @@ where
             .strip_prefix("Bearer ")
             .ok_or(AuthError::InvalidToken)?;
 
+        self.validate(token)
+    }
+
+    fn validate(&self, token: &str) -> Result<AuthenticatedUser, AuthError> {
         // In development, allow a test token
         if cfg!(debug_assertions) && token == "dev-token" {
-            return Ok(ClerkAuth(AuthenticatedUser {
+            return Ok(AuthenticatedUser {
                 user_id: "dev-user".to_string(),
                 email: Some("dev@cinemaos.com".to_string()),
-            }));
+            });
         }
 
-        // Get public key from environment
-        let public_key = std::env::var("CLERK_PUBLIC_KEY")
-            .map_err(|_| AuthError::ConfigError)?;
+        let key = self.key.as_deref().ok_or(AuthError::ConfigError)?;
+        let token_data = decode::<ClerkClaims>(token, key, &self.validation).map_err(|e| {
+            tracing::debug!(error = %e, "Rejected bearer token");
+            AuthError::InvalidToken
+        })?;
 
-        // Decode and validate JWT
-        let token_data = decode::<ClerkClaims>(
-            token,
-            &DecodingKey::from_rsa_pem(public_key.as_bytes())
-                .map_err(|_| AuthError::InvalidKey)?,
-            &Validation::new(Algorithm::RS256),
-        )
-        .map_err(|_| AuthError::InvalidToken)?;
-
-        Ok(ClerkAuth(AuthenticatedUser {
+        Ok(AuthenticatedUser {
             user_id: token_data.claims.sub,
             email: token_data.claims.email,
-        }))
+        })
+    }
+}
+
+/// Middleware for protected routes: 401 unless the bearer token is valid
+pub async fn require_auth(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match state.auth.authenticate(request.headers()) {
+        Ok(user) => {
+            request.extensions_mut().insert(user);
+            next.run(request).await
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// The user [`require_auth`] authenticated
+pub struct AuthUser(pub AuthenticatedUser);
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // Missing on routes outside `require_auth`, which must not get a user
+        parts
+            .extensions
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .map(AuthUser)
+            .ok_or(AuthError::MissingToken)
     }
 }
 
 /// Authentication errors
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum AuthError {
     MissingToken,
     InvalidToken,
-    InvalidKey,
     ConfigError,
 }
 
@@ -93,10 +150,94 @@ impl IntoResponse for AuthError {
         let (status, message) = match self {
             AuthError::MissingToken => (StatusCode::UNAUTHORIZED, "Missing authorization token"),
             AuthError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
-            AuthError::InvalidKey => (StatusCode::INTERNAL_SERVER_ERROR, "Key configuration error"),
             AuthError::ConfigError => (StatusCode::INTERNAL_SERVER_ERROR, "Server configuration error"),
         };
 
         (status, message).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    const PRIVATE_KEY: &str = include_str!("testdata/test_key.pem");
+    const PUBLIC_KEY: &str = include_str!("testdata/test_key.pub.pem");
+
+    fn token(claims: serde_json::Value) -> String {
+        let key = EncodingKey::from_rsa_pem(PRIVATE_KEY.as_bytes()).unwrap();
+        encode(&Header::new(Algorithm::RS256), &claims, &key).unwrap()
+    }
+
+    fn claims(exp_offset: i64) -> serde_json::Value {
+        let now = chrono::Utc::now().timestamp();
+        serde_json::json!({
+            "sub": "user_2abc",
+            "email": "director@example.com",
+            "iat": now,
+            "exp": now + exp_offset,
+            "aud": "cinemaos",
+        })
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", format!("Bearer {}", token).parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_valid_token() {
+        let validator = JwtValidator::new(PUBLIC_KEY, Some("cinemaos"));
+        let user = validator.authenticate(&bearer(&token(claims(3600)))).unwrap();
+        assert_eq!(user.user_id, "user_2abc");
+        assert_eq!(user.email.as_deref(), Some("director@example.com"));
+    }
+
+    #[test]
+    fn test_expired_token() {
+        let validator = JwtValidator::new(PUBLIC_KEY, Some("cinemaos"));
+        // Past the default 60s leeway
+        assert_eq!(
+            validator.authenticate(&bearer(&token(claims(-3600)))).unwrap_err(),
+            AuthError::InvalidToken
+        );
+    }
+
+    #[test]
+    fn test_malformed_token() {
+        let validator = JwtValidator::new(PUBLIC_KEY, None);
+
+        assert_eq!(validator.authenticate(&HeaderMap::new()).unwrap_err(), AuthError::MissingToken);
+        assert_eq!(validator.authenticate(&bearer("not.a.jwt")).unwrap_err(), AuthError::InvalidToken);
+
+        let mut basic = HeaderMap::new();
+        basic.insert("Authorization", "Basic dXNlcjpwYXNz".parse().unwrap());
+        assert_eq!(validator.authenticate(&basic).unwrap_err(), AuthError::InvalidToken);
+
+        // Valid token with its payload swapped: the signature no longer matches
+        let valid = token(claims(3600));
+        let forged = token(serde_json::json!({ "sub": "user_other", "exp": 9_999_999_999i64, "iat": 0 }));
+        let parts: Vec<&str> = valid.split('.').collect();
+        let forged_payload = forged.split('.').nth(1).unwrap();
+        let tampered = format!("{}.{}.{}", parts[0], forged_payload, parts[2]);
+        assert_eq!(validator.authenticate(&bearer(&tampered)).unwrap_err(), AuthError::InvalidToken);
+    }
+
+    #[test]
+    fn test_audience() {
+        let validator = JwtValidator::new(PUBLIC_KEY, Some("another-app"));
+        assert_eq!(
+            validator.authenticate(&bearer(&token(claims(3600)))).unwrap_err(),
+            AuthError::InvalidToken
+        );
+
+        // Without a key nothing validates
+        let unconfigured = JwtValidator::new("", None);
+        assert_eq!(
+            unconfigured.authenticate(&bearer(&token(claims(3600)))).unwrap_err(),
+            AuthError::ConfigError
+        );
+    }
+}
@@ -2,4 +2,4 @@
 
 pub mod clerk;
 
-pub use clerk::ClerkAuth;
+pub use clerk::{require_auth, AuthUser, JwtValidator};
@@ -2,6 +2,10 @@
 
 use serde::Deserialize;
 
+use crate::pricing::PricingConfig;
+use crate::providers::fallback::FallbackConfig;
+use crate::ratelimit::RateLimitConfig;
+
 /// Application configuration loaded from environment variables
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
@@ -20,12 +24,28 @@ pub struct Config {
     /// Fal.ai API key
     pub fal_api_key: String,
 
+    /// Public base URL of this API (e.g. https://api.cinemaos.com) Fal
+    /// sends webhooks to; Fal jobs are only polled when unset
+    pub public_url: Option<String>,
+
     /// Clerk public key for JWT validation
     pub clerk_public_key: String,
 
+    /// Audience session tokens must carry (`aud` isn't checked when unset)
+    pub clerk_audience: Option<String>,
+
     /// Stripe secret key (optional, for payments)
     pub stripe_secret_key: Option<String>,
 
+    /// Credit conversion rate and provider markups
+    pub pricing: PricingConfig,
+
+    /// Per-user request limits
+    pub rate_limits: RateLimitConfig,
+
+    /// Alternate providers per capability when the primary is unavailable
+    pub fallbacks: FallbackConfig,
+
     /// Environment (development, staging, production)
     pub environment: Environment,
 }
@@ -54,8 +74,13 @@ impl Config {
             storage_bucket: std::env::var("STORAGE_BUCKET")
                 .unwrap_or_else(|_| "cinemaos-assets".to_string()),
             fal_api_key: std::env::var("FAL_API_KEY").expect("FAL_API_KEY must be set"),
+            public_url: std::env::var("PUBLIC_URL").ok().filter(|s| !s.is_empty()),
             clerk_public_key: std::env::var("CLERK_PUBLIC_KEY").unwrap_or_default(),
+            clerk_audience: std::env::var("CLERK_AUDIENCE").ok().filter(|s| !s.is_empty()),
             stripe_secret_key: std::env::var("STRIPE_SECRET_KEY").ok(),
+            pricing: PricingConfig::from_env(),
+            rate_limits: RateLimitConfig::from_env(),
+            fallbacks: FallbackConfig::from_env(),
             environment: match std::env::var("ENVIRONMENT").as_deref() {
                 Ok("production") => Environment::Production,
                 Ok("staging") => Environment::Staging,
@@ -1,6 +1,7 @@
-//! Firestore client for user data and credits
+//! Firestore client for user data, credits and generation jobs
 
 use crate::config::Config;
+use crate::queue::scheduler::QueuedJob;
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
 
@@ -10,20 +11,69 @@ pub struct User {
     pub id: String,
     pub email: Option<String>,
     pub credits: i64,
+    /// Has bought credits; paid users' jobs are scheduled first
+    #[serde(default)]
+    pub paid: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Attempts at a credit transaction before giving up on contention
+const CREDIT_TRANSACTION_ATTEMPTS: usize = 3;
+
+/// Longest accepted idempotency key
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 128;
+
 /// Credit transaction
+///
+/// Stored under `{user_id}_{idempotency key}`, so a transaction is applied
+/// at most once however often it is retried.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreditTransaction {
     pub id: String,
     pub user_id: String,
     pub amount: i64,
     pub reason: String,
+    /// Job the charge paid for, so a replayed request can be pointed at it
+    #[serde(default)]
+    pub job_id: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Why credits couldn't be moved
+#[derive(Debug, thiserror::Error)]
+pub enum CreditError {
+    #[error("Insufficient credits: {balance} available, {required} required")]
+    InsufficientCredits { balance: i64, required: i64 },
+    /// A transaction with this idempotency key was already applied
+    #[error("Credit transaction {0} was already applied")]
+    AlreadyApplied(String),
+    #[error("Invalid idempotency key")]
+    InvalidKey,
+    #[error("User not found")]
+    UserNotFound,
+    #[error(transparent)]
+    Firestore(#[from] anyhow::Error),
+}
+
+/// A generation job as stored, with the instance that queued it
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredJob {
+    pub job: QueuedJob,
+    /// Instance id of the API server whose scheduler holds the job
+    pub owner: String,
+}
+
+/// Document id of a user's credit transaction, if `key` is usable in one
+pub fn transaction_id(user_id: &str, key: &str) -> Option<String> {
+    let valid = !key.is_empty()
+        && key.len() <= MAX_IDEMPOTENCY_KEY_LEN
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    valid.then(|| format!("{}_{}", user_id, key))
+}
+
 /// Firestore client wrapper
 #[derive(Clone)]
 pub struct FirestoreClient {
@@ -42,6 +92,22 @@ impl FirestoreClient {
         })
     }
 
+    /// Check the database is reachable and readable
+    pub async fn ping(&self) -> Result<()> {
+        let url = format!(
+            "https://firestore.googleapis.com/v1/projects/{}/databases/{}/documents/users?pageSize=1",
+            self.project_id, self.database
+        );
+
+        self.http_client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
     /// Get or create a user
     pub async fn get_or_create_user(&self, user_id: &str, email: Option<&str>) -> Result<User> {
         // Try to get existing user
@@ -54,6 +120,7 @@ impl FirestoreClient {
             id: user_id.to_string(),
             email: email.map(String::from),
             credits: 100, // Free initial credits
+            paid: false,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
         };
@@ -101,80 +168,355 @@ impl FirestoreClient {
         Ok(())
     }
 
-    /// Deduct credits from user
-    pub async fn deduct_credits(&self, user_id: &str, amount: i64, reason: &str) -> Result<bool> {
-        let Some(mut user) = self.get_user(user_id).await? else {
-            return Ok(false);
-        };
+    /// Flag a user as paying, leaving every other field as it is
+    ///
+    /// Only `paid` is in the update mask, so a concurrent credit transaction
+    /// can't be overwritten with a stale balance.
+    pub async fn mark_paid(&self, user_id: &str) -> Result<()> {
+        let url = format!(
+            "{}/users/{}?updateMask.fieldPaths=paid&currentDocument.exists=true",
+            self.documents_url(),
+            user_id
+        );
 
-        if user.credits < amount {
-            return Ok(false);
-        }
+        self.http_client
+            .patch(&url)
+            .json(&serde_json::json!({
+                "fields": { "paid": { "booleanValue": true } }
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
 
-        user.credits -= amount;
-        user.updated_at = chrono::Utc::now();
-        self.save_user(&user).await?;
+        Ok(())
+    }
 
-        // Log transaction
-        let transaction = CreditTransaction {
-            id: uuid::Uuid::new_v4().to_string(),
-            user_id: user_id.to_string(),
-            amount: -amount,
-            reason: reason.to_string(),
-            created_at: chrono::Utc::now(),
-        };
-        self.save_transaction(&transaction).await?;
+    /// Deduct `amount` credits if the balance covers it
+    ///
+    /// The balance check and the decrement happen in one Firestore
+    /// transaction, so concurrent requests can't overdraw the account.
+    /// `job_id` is recorded with the charge. Returns the new balance.
+    pub async fn deduct_credits(
+        &self,
+        user_id: &str,
+        amount: i64,
+        reason: &str,
+        idempotency_key: &str,
+        job_id: Option<&str>,
+    ) -> Result<i64, CreditError> {
+        self.transact_credits(user_id, -amount, reason, idempotency_key, job_id).await
+    }
+
+    /// Job recorded with the transaction applied under `idempotency_key`
+    pub async fn charged_job(&self, user_id: &str, idempotency_key: &str) -> Result<Option<String>> {
+        let tx_id = transaction_id(user_id, idempotency_key)
+            .ok_or_else(|| anyhow::anyhow!("Invalid idempotency key"))?;
+        let url = format!("{}/transactions/{}", self.documents_url(), tx_id);
+
+        let response = self.http_client
+            .get(&url)
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
 
-        Ok(true)
+        let doc: serde_json::Value = response.error_for_status()?.json().await?;
+        Ok(doc["fields"]["job_id"]["stringValue"].as_str().map(String::from))
     }
 
     /// Add credits to user
+    ///
+    /// `reason` (e.g. `refund_<job id>`) doubles as the idempotency key, so
+    /// a topup or refund is applied once however often it is retried.
     pub async fn add_credits(&self, user_id: &str, amount: i64, reason: &str) -> Result<()> {
-        let Some(mut user) = self.get_user(user_id).await? else {
-            anyhow::bail!("User not found");
-        };
+        match self.transact_credits(user_id, amount, reason, reason, None).await {
+            Ok(_) => Ok(()),
+            Err(CreditError::AlreadyApplied(id)) => {
+                tracing::info!(transaction = %id, "Credit transaction already applied");
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Apply `amount` to the balance and record the transaction atomically,
+    /// retrying when Firestore aborts on contention
+    async fn transact_credits(
+        &self,
+        user_id: &str,
+        amount: i64,
+        reason: &str,
+        idempotency_key: &str,
+        job_id: Option<&str>,
+    ) -> Result<i64, CreditError> {
+        let tx_id = transaction_id(user_id, idempotency_key).ok_or(CreditError::InvalidKey)?;
+
+        for attempt in 1..=CREDIT_TRANSACTION_ATTEMPTS {
+            let transaction = self.begin_transaction().await?;
+            match self.try_transact(&transaction, user_id, amount, reason, &tx_id, job_id).await {
+                Ok(Some(balance)) => return Ok(balance),
+                Ok(None) => {
+                    tracing::debug!(transaction = %tx_id, attempt, "Credit transaction aborted, retrying");
+                }
+                Err(e) => {
+                    // Commit failures end the transaction on their own
+                    if let Err(rollback) = self.rollback(&transaction).await {
+                        tracing::debug!(error = %rollback, "Rollback after failed credit transaction");
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        Err(anyhow::anyhow!("Credit transaction {} kept conflicting", tx_id).into())
+    }
+
+    /// One attempt at a credit transaction; `None` if Firestore aborted it
+    async fn try_transact(
+        &self,
+        transaction: &str,
+        user_id: &str,
+        amount: i64,
+        reason: &str,
+        tx_id: &str,
+        job_id: Option<&str>,
+    ) -> Result<Option<i64>, CreditError> {
+        if self.get_document(&format!("transactions/{}", tx_id), transaction).await?.is_some() {
+            return Err(CreditError::AlreadyApplied(tx_id.to_string()));
+        }
+
+        let doc = self
+            .get_document(&format!("users/{}", user_id), transaction)
+            .await?
+            .ok_or(CreditError::UserNotFound)?;
+        let mut user = self.parse_user_doc(&doc)?;
+        if user.credits + amount < 0 {
+            return Err(CreditError::InsufficientCredits {
+                balance: user.credits,
+                required: -amount,
+            });
+        }
 
         user.credits += amount;
         user.updated_at = chrono::Utc::now();
-        self.save_user(&user).await?;
-
-        let transaction = CreditTransaction {
-            id: uuid::Uuid::new_v4().to_string(),
+        let record = CreditTransaction {
+            id: tx_id.to_string(),
             user_id: user_id.to_string(),
             amount,
             reason: reason.to_string(),
-            created_at: chrono::Utc::now(),
+            job_id: job_id.map(String::from),
+            created_at: user.updated_at,
         };
-        self.save_transaction(&transaction).await?;
 
+        let body = serde_json::json!({
+            "transaction": transaction,
+            "writes": [
+                {
+                    "update": {
+                        "name": self.document_name(&format!("users/{}", user_id)),
+                        "fields": {
+                            "credits": { "integerValue": user.credits.to_string() },
+                            "updated_at": { "timestampValue": user.updated_at.to_rfc3339() }
+                        }
+                    },
+                    "updateMask": { "fieldPaths": ["credits", "updated_at"] },
+                    "currentDocument": { "exists": true }
+                },
+                {
+                    "update": {
+                        "name": self.document_name(&format!("transactions/{}", tx_id)),
+                        "fields": transaction_fields(&record)
+                    },
+                    "currentDocument": { "exists": false }
+                }
+            ]
+        });
+
+        let response = self
+            .http_client
+            .post(format!("{}:commit", self.documents_url()))
+            .json(&body)
+            .send()
+            .await
+            .map_err(anyhow::Error::from)?;
+
+        if response.status().is_success() {
+            return Ok(Some(user.credits));
+        }
+        let status = response.status();
+        let error: serde_json::Value = response.json().await.unwrap_or_default();
+        match error["error"]["status"].as_str() {
+            Some("ABORTED") => Ok(None),
+            // Another request with the same key committed first
+            Some("ALREADY_EXISTS") => Err(CreditError::AlreadyApplied(tx_id.to_string())),
+            _ => Err(anyhow::anyhow!("Firestore commit failed ({}): {}", status, error).into()),
+        }
+    }
+
+    /// Store a job's current state (replacing what was stored)
+    pub async fn save_job(&self, job: &QueuedJob, owner: &str) -> Result<()> {
+        self.http_client
+            .patch(format!("{}/jobs/{}", self.documents_url(), job.id))
+            .json(&serde_json::json!({ "fields": job_fields(job, owner) }))
+            .send()
+            .await?
+            .error_for_status()?;
         Ok(())
     }
 
-    /// Save transaction
-    async fn save_transaction(&self, tx: &CreditTransaction) -> Result<()> {
-        let url = format!(
-            "https://firestore.googleapis.com/v1/projects/{}/databases/{}/documents/transactions/{}",
-            self.project_id, self.database, tx.id
-        );
+    /// A stored job by id
+    pub async fn get_job(&self, job_id: &str) -> Result<Option<StoredJob>> {
+        let response = self.http_client
+            .get(format!("{}/jobs/{}", self.documents_url(), job_id))
+            .send()
+            .await?;
 
-        let doc = serde_json::json!({
-            "fields": {
-                "user_id": { "stringValue": tx.user_id },
-                "amount": { "integerValue": tx.amount.to_string() },
-                "reason": { "stringValue": tx.reason },
-                "created_at": { "timestampValue": tx.created_at.to_rfc3339() }
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let doc: serde_json::Value = response.error_for_status()?.json().await?;
+        Ok(parse_job_doc(&doc))
+    }
+
+    /// The stored job `provider` knows as `request_id` (for webhooks)
+    pub async fn find_job_by_request(&self, provider: &str, request_id: &str) -> Result<Option<StoredJob>> {
+        let filter = serde_json::json!({
+            "compositeFilter": {
+                "op": "AND",
+                "filters": [
+                    field_filter("provider", "EQUAL", serde_json::json!({ "stringValue": provider })),
+                    field_filter("provider_request_id", "EQUAL", serde_json::json!({ "stringValue": request_id })),
+                ]
             }
         });
+        Ok(self.query_jobs(filter, Some(1)).await?.pop())
+    }
+
+    /// Jobs still waiting for or being submitted to a provider
+    pub async fn unsubmitted_jobs(&self) -> Result<Vec<StoredJob>> {
+        let states = serde_json::json!({
+            "arrayValue": { "values": [{ "stringValue": "queued" }, { "stringValue": "running" }] }
+        });
+        self.query_jobs(field_filter("state", "IN", states), None).await
+    }
+
+    async fn query_jobs(&self, filter: serde_json::Value, limit: Option<u32>) -> Result<Vec<StoredJob>> {
+        let mut query = serde_json::json!({
+            "from": [{ "collectionId": "jobs" }],
+            "where": filter
+        });
+        if let Some(limit) = limit {
+            query["limit"] = serde_json::json!(limit);
+        }
+
+        let rows: Vec<serde_json::Value> = self
+            .http_client
+            .post(format!("{}:runQuery", self.documents_url()))
+            .json(&serde_json::json!({ "structuredQuery": query }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
 
+        // Rows without a document only carry the read time
+        Ok(rows.iter().filter_map(|row| parse_job_doc(row.get("document")?)).collect())
+    }
+
+    /// Record that an API instance is alive
+    pub async fn heartbeat(&self, instance_id: &str) -> Result<()> {
         self.http_client
-            .patch(&url)
-            .json(&doc)
+            .patch(format!("{}/instances/{}", self.documents_url(), instance_id))
+            .json(&serde_json::json!({
+                "fields": { "seen_at": { "timestampValue": chrono::Utc::now().to_rfc3339() } }
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// When an API instance last sent a heartbeat
+    pub async fn instance_seen_at(&self, instance_id: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let response = self.http_client
+            .get(format!("{}/instances/{}", self.documents_url(), instance_id))
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let doc: serde_json::Value = response.error_for_status()?.json().await?;
+        Ok(parse_timestamp(&doc["fields"]["seen_at"]))
+    }
+
+    async fn begin_transaction(&self) -> Result<String> {
+        let response: serde_json::Value = self
+            .http_client
+            .post(format!("{}:beginTransaction", self.documents_url()))
+            .json(&serde_json::json!({}))
             .send()
+            .await?
+            .error_for_status()?
+            .json()
             .await?;
 
+        response["transaction"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| anyhow::anyhow!("No transaction in beginTransaction response"))
+    }
+
+    async fn rollback(&self, transaction: &str) -> Result<()> {
+        self.http_client
+            .post(format!("{}:rollback", self.documents_url()))
+            .json(&serde_json::json!({ "transaction": transaction }))
+            .send()
+            .await?
+            .error_for_status()?;
         Ok(())
     }
 
+    /// Read a document inside `transaction`, locking it until the commit
+    async fn get_document(&self, path: &str, transaction: &str) -> Result<Option<serde_json::Value>> {
+        let url = format!(
+            "{}/{}?transaction={}",
+            self.documents_url(),
+            path,
+            urlencoding::encode(transaction)
+        );
+
+        let response = self.http_client
+            .get(&url)
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+
+    fn documents_url(&self) -> String {
+        format!(
+            "https://firestore.googleapis.com/v1/projects/{}/databases/{}/documents",
+            self.project_id, self.database
+        )
+    }
+
+    /// Full resource name of a document, as commit writes expect
+    fn document_name(&self, path: &str) -> String {
+        format!(
+            "projects/{}/databases/{}/documents/{}",
+            self.project_id, self.database, path
+        )
+    }
+
     fn parse_user_doc(&self, doc: &serde_json::Value) -> Result<User> {
         let fields = doc.get("fields").ok_or_else(|| anyhow::anyhow!("Missing fields"))?;
         
@@ -185,16 +527,9 @@ impl FirestoreClient {
                 .as_str()
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(0),
-            created_at: fields["created_at"]["timestampValue"]
-                .as_str()
-                .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
-                .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(chrono::Utc::now),
-            updated_at: fields["updated_at"]["timestampValue"]
-                .as_str()
-                .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
-                .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(chrono::Utc::now),
+            paid: fields["paid"]["booleanValue"].as_bool().unwrap_or(false),
+            created_at: parse_timestamp(&fields["created_at"]).unwrap_or_else(chrono::Utc::now),
+            updated_at: parse_timestamp(&fields["updated_at"]).unwrap_or_else(chrono::Utc::now),
         })
     }
 
@@ -204,9 +539,190 @@ impl FirestoreClient {
                 "id": { "stringValue": user.id },
                 "email": { "stringValue": user.email.as_deref().unwrap_or("") },
                 "credits": { "integerValue": user.credits.to_string() },
+                "paid": { "booleanValue": user.paid },
                 "created_at": { "timestampValue": user.created_at.to_rfc3339() },
                 "updated_at": { "timestampValue": user.updated_at.to_rfc3339() }
             }
         })
     }
 }
+
+fn transaction_fields(tx: &CreditTransaction) -> serde_json::Value {
+    let mut fields = serde_json::json!({
+        "user_id": { "stringValue": tx.user_id },
+        "amount": { "integerValue": tx.amount.to_string() },
+        "reason": { "stringValue": tx.reason },
+        "created_at": { "timestampValue": tx.created_at.to_rfc3339() }
+    });
+    if let Some(job_id) = &tx.job_id {
+        fields["job_id"] = serde_json::json!({ "stringValue": job_id });
+    }
+    fields
+}
+
+fn parse_timestamp(value: &serde_json::Value) -> Option<chrono::DateTime<chrono::Utc>> {
+    value["timestampValue"]
+        .as_str()
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&chrono::Utc))
+}
+
+fn field_filter(path: &str, op: &str, value: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "fieldFilter": { "field": { "fieldPath": path }, "op": op, "value": value }
+    })
+}
+
+/// A serde enum's name ("queued", "paid", ...)
+fn enum_name(value: &impl Serialize) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+/// Document fields of a job; fields without a value are left out
+fn job_fields(job: &QueuedJob, owner: &str) -> serde_json::Value {
+    let mut fields = serde_json::json!({
+        "id": { "stringValue": job.id },
+        "user_id": { "stringValue": job.user_id },
+        "provider": { "stringValue": job.provider },
+        "model": { "stringValue": job.model },
+        "priority": { "stringValue": enum_name(&job.priority) },
+        "state": { "stringValue": enum_name(&job.state) },
+        "credits": { "integerValue": job.credits.to_string() },
+        "created_at": { "timestampValue": job.created_at.to_rfc3339() },
+        "owner": { "stringValue": owner }
+    });
+    let optional = [
+        ("provider_request_id", &job.provider_request_id),
+        ("provider_status", &job.provider_status),
+        ("url", &job.url),
+        ("error", &job.error),
+    ];
+    for (name, value) in optional {
+        if let Some(value) = value {
+            fields[name] = serde_json::json!({ "stringValue": value });
+        }
+    }
+    if let Some(position) = job.queue_position {
+        fields["queue_position"] = serde_json::json!({ "integerValue": position.to_string() });
+    }
+    if let Some(progress) = job.progress {
+        fields["progress"] = serde_json::json!({ "doubleValue": progress });
+    }
+    fields
+}
+
+fn enum_field<T: serde::de::DeserializeOwned>(fields: &serde_json::Value, name: &str) -> Option<T> {
+    serde_json::from_value(fields[name]["stringValue"].clone()).ok()
+}
+
+fn parse_job_doc(doc: &serde_json::Value) -> Option<StoredJob> {
+    let fields = doc.get("fields")?;
+    let string = |name: &str| fields[name]["stringValue"].as_str().map(String::from);
+    let integer = |name: &str| fields[name]["integerValue"].as_str().and_then(|v| v.parse::<i64>().ok());
+
+    Some(StoredJob {
+        job: QueuedJob {
+            id: string("id")?,
+            user_id: string("user_id")?,
+            provider: string("provider")?,
+            model: string("model")?,
+            priority: enum_field(fields, "priority")?,
+            state: enum_field(fields, "state")?,
+            provider_request_id: string("provider_request_id"),
+            provider_status: string("provider_status"),
+            queue_position: integer("queue_position").map(|p| p as u32),
+            progress: fields["progress"]["doubleValue"].as_f64().map(|p| p as f32),
+            url: string("url"),
+            error: string("error"),
+            credits: integer("credits")?,
+            created_at: parse_timestamp(&fields["created_at"])?,
+        },
+        owner: string("owner")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::scheduler::{JobState, Priority};
+
+    #[test]
+    fn test_transaction_id() {
+        assert_eq!(
+            transaction_id("user_2abc", "refund_3f2a-9c").as_deref(),
+            Some("user_2abc_refund_3f2a-9c")
+        );
+        assert_eq!(
+            transaction_id("user_2abc", "7d9e4b8e-1c2f-4a51-9a53-0f1e2d3c4b5a").as_deref(),
+            Some("user_2abc_7d9e4b8e-1c2f-4a51-9a53-0f1e2d3c4b5a")
+        );
+
+        // Would escape the transactions collection or isn't a valid id
+        assert_eq!(transaction_id("user_2abc", "../users/other"), None);
+        assert_eq!(transaction_id("user_2abc", ""), None);
+        assert_eq!(transaction_id("user_2abc", &"k".repeat(MAX_IDEMPOTENCY_KEY_LEN + 1)), None);
+    }
+
+    #[test]
+    fn test_transaction_fields_record_the_job() {
+        let mut tx = CreditTransaction {
+            id: "user_2abc_image_7d9e".into(),
+            user_id: "user_2abc".into(),
+            amount: -12,
+            reason: "image_generation".into(),
+            job_id: Some("3f2a-9c".into()),
+            created_at: chrono::Utc::now(),
+        };
+        let fields = transaction_fields(&tx);
+        assert_eq!(fields["job_id"]["stringValue"], "3f2a-9c");
+        assert_eq!(fields["amount"]["integerValue"], "-12");
+
+        // Topups and refunds pay for no job
+        tx.job_id = None;
+        assert!(transaction_fields(&tx).get("job_id").is_none());
+    }
+
+    #[test]
+    fn test_jobs_round_trip_through_documents() {
+        let created_at = chrono::DateTime::parse_from_rfc3339("2025-12-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let mut job = QueuedJob {
+            id: "3f2a-9c".into(),
+            user_id: "user_2abc".into(),
+            provider: "fal".into(),
+            model: "kling-pro".into(),
+            priority: Priority::Paid,
+            state: JobState::Queued,
+            provider_request_id: None,
+            provider_status: None,
+            queue_position: None,
+            progress: None,
+            url: None,
+            error: None,
+            credits: 40,
+            created_at,
+        };
+
+        let doc = serde_json::json!({ "fields": job_fields(&job, "instance-a") });
+        assert_eq!(doc["fields"]["state"]["stringValue"], "queued");
+        assert!(doc["fields"].get("provider_request_id").is_none());
+        assert_eq!(
+            parse_job_doc(&doc),
+            Some(StoredJob { job: job.clone(), owner: "instance-a".into() })
+        );
+
+        job.state = JobState::Submitted;
+        job.provider_request_id = Some("764cabcf".into());
+        job.provider_status = Some("IN_QUEUE".into());
+        job.queue_position = Some(3);
+        job.progress = Some(0.25);
+        let doc = serde_json::json!({ "fields": job_fields(&job, "instance-b") });
+        assert_eq!(parse_job_doc(&doc).unwrap().job, job);
+
+        assert_eq!(parse_job_doc(&serde_json::json!({ "name": "jobs/3f2a-9c" })), None);
+    }
+}
@@ -19,6 +19,19 @@ impl StorageClient {
         })
     }
 
+    /// Check the bucket is reachable
+    pub async fn ping(&self) -> Result<()> {
+        let url = format!("https://storage.googleapis.com/storage/v1/b/{}", self.bucket);
+
+        self.http_client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
     /// Generate a signed URL for uploading
     pub async fn get_upload_url(&self, object_name: &str, content_type: &str) -> Result<String> {
         // In production, use proper service account signing
@@ -5,19 +5,24 @@
 mod config;
 mod auth;
 mod db;
+mod payments;
+mod pricing;
 mod providers;
+mod ratelimit;
 mod routes;
 mod queue;
 mod observability;
 
 use axum::{
     Router,
+    middleware,
     routing::{get, post},
     http::Method,
 };
 use tower_http::cors::{CorsLayer, Any};
 use tower_http::trace::TraceLayer;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -35,27 +40,43 @@ async fn main() -> anyhow::Result<()> {
     // Load config
     let config = config::Config::from_env()?;
     let state = AppState::new(config).await?;
+    routes::jobs::spawn_job_recovery(state.firestore.clone(), state.instance_id.clone());
 
-    // Build router
-    let app = Router::new()
-        // Health check
-        .route("/health", get(routes::health::health_check))
+    let limiter = ratelimit::RateLimiter::new(state.config.rate_limits.clone(), state.auth.clone());
+    limiter.spawn_sweeper();
+
+    // Routes that need a valid Clerk token
+    let protected = Router::new()
         // Chat (streaming)
         .route("/api/chat", post(routes::chat::chat_handler))
         // Image generation
         .route("/api/generate/image", post(routes::generate::image_handler))
         // Video generation
         .route("/api/generate/video", post(routes::generate::video_handler))
+        // Queued generation jobs
+        .route("/api/jobs/:id", get(routes::jobs::get_job).delete(routes::jobs::cancel_job))
+        .route("/api/jobs/:id/events", get(routes::jobs::job_events_handler))
         // Credits
         .route("/api/credits", get(routes::credits::get_credits))
         .route("/api/credits/topup", post(routes::credits::topup_handler))
-        // Webhooks
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_auth));
+
+    // Build router
+    let app = Router::new()
+        // Health check
+        .route("/health", get(routes::health::health_check))
+        .route("/health/ready", get(routes::health::readiness_check))
+        // Queue depth per provider (no user data)
+        .route("/api/queue", get(routes::jobs::queue_depth))
+        // Webhooks (signed by Fal)
         .route("/api/webhook/fal", post(routes::webhooks::fal_webhook))
+        .merge(protected)
         // Middleware
+        .layer(middleware::from_fn_with_state(limiter, ratelimit::rate_limit))
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
-                .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+                .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::OPTIONS])
                 .allow_headers(Any),
         )
         .layer(TraceLayer::new_for_http())
@@ -70,7 +91,8 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Listening on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Peer addresses key the rate limits of unauthenticated requests
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
@@ -82,7 +104,18 @@ pub struct AppState {
     pub firestore: db::firestore::FirestoreClient,
     pub storage: db::storage::StorageClient,
     pub vertex: providers::vertex::VertexClient,
-    pub fal: providers::fal::FalClient,
+    /// Image/video vendors keyed by provider name
+    pub providers: providers::ProviderRegistry,
+    /// Rate-limited queue every provider submission goes through
+    pub scheduler: queue::scheduler::Scheduler,
+    /// Identifies this server's jobs in the job store
+    pub instance_id: String,
+    /// Validates bearer tokens on protected routes
+    pub auth: auth::JwtValidator,
+    /// Verifies topup payments; topups are refused when Stripe isn't configured
+    pub stripe: Option<payments::StripeClient>,
+    /// Checks Fal webhook signatures against Fal's published keys
+    pub fal_webhooks: routes::webhooks::FalWebhookVerifier,
 }
 
 impl AppState {
@@ -92,12 +125,36 @@ impl AppState {
         let vertex = providers::vertex::VertexClient::new(&config)?;
         let fal = providers::fal::FalClient::new(&config)?;
 
+        let mut registry = providers::ProviderRegistry::new();
+        registry.register(Arc::new(fal));
+        registry.register(Arc::new(vertex.clone()));
+
+        // Refund jobs that fail at submission or at the provider, and store
+        // every job so other instances (or this one, restarted) can serve it
+        let instance_id = uuid::Uuid::new_v4().to_string();
+        let scheduler = queue::scheduler::Scheduler::new()
+            .on_failure(routes::jobs::refund_failed(firestore.clone()))
+            .on_change(routes::jobs::persist_jobs(firestore.clone(), instance_id.clone()));
+
+        let stripe = config
+            .stripe_secret_key
+            .as_deref()
+            .filter(|key| !key.is_empty())
+            .map(payments::StripeClient::new);
+
+        let auth = auth::JwtValidator::new(&config.clerk_public_key, config.clerk_audience.as_deref());
+
         Ok(Self {
+            auth,
             config,
             firestore,
             storage,
             vertex,
-            fal,
+            providers: registry,
+            scheduler,
+            instance_id,
+            stripe,
+            fal_webhooks: routes::webhooks::FalWebhookVerifier::new(routes::webhooks::FAL_JWKS_URL),
         })
     }
 }
@@ -0,0 +1,126 @@
+//! Stripe payment verification for credit topups
+//!
+//! A topup only adds credits (and paid scheduling priority) for a
+//! PaymentIntent Stripe reports as succeeded. Intents are created with the
+//! buyer's id in `metadata[user_id]`, so one can't be claimed by another
+//! account, and the credits follow from the amount Stripe received rather
+//! than from anything the client sends.
+
+use crate::pricing::PricingConfig;
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const STRIPE_API_URL: &str = "https://api.stripe.com/v1";
+
+/// The parts of a Stripe PaymentIntent a topup is checked against
+#[derive(Debug, Clone, Deserialize)]
+pub struct PaymentIntent {
+    pub id: String,
+    /// "succeeded" once the money was captured
+    pub status: String,
+    /// In the currency's smallest unit (cents)
+    pub amount_received: i64,
+    pub currency: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+/// Why a payment doesn't pay for a topup
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum PaymentError {
+    #[error("Payment {0} has not succeeded")]
+    NotSucceeded(String),
+    #[error("Payment {0} was made by another user")]
+    OtherUser(String),
+    #[error("Payments in {0} are not supported")]
+    Currency(String),
+}
+
+impl PaymentIntent {
+    /// Credits this payment buys `user_id`
+    pub fn credits_for(&self, user_id: &str, pricing: &PricingConfig) -> Result<i64, PaymentError> {
+        if self.status != "succeeded" {
+            return Err(PaymentError::NotSucceeded(self.id.clone()));
+        }
+        if self.metadata.get("user_id").map(String::as_str) != Some(user_id) {
+            return Err(PaymentError::OtherUser(self.id.clone()));
+        }
+        if !self.currency.eq_ignore_ascii_case("usd") {
+            return Err(PaymentError::Currency(self.currency.clone()));
+        }
+
+        // Absorb float noise so e.g. $9.99 at 100 credits/USD buys 999
+        let usd = self.amount_received as f64 / 100.0;
+        Ok((usd * pricing.credits_per_usd + 1e-9).floor().max(0.0) as i64)
+    }
+}
+
+/// Stripe API client
+#[derive(Clone)]
+pub struct StripeClient {
+    secret_key: String,
+    http_client: reqwest::Client,
+}
+
+impl StripeClient {
+    pub fn new(secret_key: &str) -> Self {
+        Self {
+            secret_key: secret_key.to_string(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// A PaymentIntent by id, `None` if Stripe doesn't know it
+    pub async fn payment_intent(&self, id: &str) -> Result<Option<PaymentIntent>> {
+        let response = self.http_client
+            .get(format!("{}/payment_intents/{}", STRIPE_API_URL, urlencoding::encode(id)))
+            .bearer_auth(&self.secret_key)
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intent(status: &str, amount_received: i64, user_id: &str) -> PaymentIntent {
+        serde_json::from_value(serde_json::json!({
+            "id": "pi_3Pq",
+            "object": "payment_intent",
+            "status": status,
+            "amount": 999,
+            "amount_received": amount_received,
+            "currency": "usd",
+            "metadata": { "user_id": user_id }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_only_succeeded_payments_by_the_user_buy_credits() {
+        let pricing = PricingConfig::default();
+
+        assert_eq!(intent("succeeded", 999, "user_2abc").credits_for("user_2abc", &pricing), Ok(999));
+        assert_eq!(
+            intent("requires_payment_method", 0, "user_2abc").credits_for("user_2abc", &pricing),
+            Err(PaymentError::NotSucceeded("pi_3Pq".into()))
+        );
+        // Someone else's payment intent id
+        assert_eq!(
+            intent("succeeded", 999, "user_9xyz").credits_for("user_2abc", &pricing),
+            Err(PaymentError::OtherUser("pi_3Pq".into()))
+        );
+
+        let mut euros = intent("succeeded", 999, "user_2abc");
+        euros.currency = "eur".into();
+        assert_eq!(euros.credits_for("user_2abc", &pricing), Err(PaymentError::Currency("eur".into())));
+    }
+}
@@ -0,0 +1,137 @@
+//! Credit pricing shared with the desktop app
+//!
+//! Provider costs are defined in USD and converted to credits with a single
+//! rate plus a per-provider markup. The desktop app reads the same
+//! environment variables and receives this config from `/api/credits`, so
+//! its estimates match what the server charges.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Default conversion rate (must match the desktop app default)
+pub const DEFAULT_CREDITS_PER_USD: f64 = 100.0;
+
+/// Provider cost of a chat request (USD)
+pub const CHAT_USD: f64 = 0.01;
+
+/// Provider cost of one generated image (USD)
+pub const IMAGE_USD: f64 = 0.05;
+
+/// Provider cost of one second of generated video (USD)
+pub const VIDEO_USD_PER_SECOND: f64 = 0.10;
+
+/// Credit conversion and margins
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PricingConfig {
+    /// Credits charged per USD of provider cost (before markup)
+    pub credits_per_usd: f64,
+    /// Markup for providers without an explicit entry
+    pub default_markup: f64,
+    /// Per-provider markup multipliers keyed by provider ("fal", "vertex", ...)
+    pub provider_markups: HashMap<String, f64>,
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        Self {
+            credits_per_usd: DEFAULT_CREDITS_PER_USD,
+            default_markup: 1.0,
+            provider_markups: HashMap::new(),
+        }
+    }
+}
+
+impl PricingConfig {
+    /// Load from `CREDITS_PER_USD`, `PRICING_DEFAULT_MARKUP` and
+    /// `PRICING_MARKUPS` (e.g. `fal=1.2,vertex=1.1`)
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(rate) = std::env::var("CREDITS_PER_USD").ok().and_then(|v| v.parse().ok()) {
+            config.credits_per_usd = rate;
+        }
+        if let Some(markup) = std::env::var("PRICING_DEFAULT_MARKUP").ok().and_then(|v| v.parse().ok()) {
+            config.default_markup = markup;
+        }
+        if let Ok(markups) = std::env::var("PRICING_MARKUPS") {
+            config.provider_markups = markups
+                .split(',')
+                .filter_map(|pair| {
+                    let (provider, markup) = pair.split_once('=')?;
+                    Some((provider.trim().to_lowercase(), markup.trim().parse().ok()?))
+                })
+                .collect();
+        }
+
+        config
+    }
+
+    /// Markup multiplier for a provider
+    pub fn markup_for(&self, provider: &str) -> f64 {
+        self.provider_markups
+            .get(&provider.to_lowercase())
+            .copied()
+            .unwrap_or(self.default_markup)
+    }
+
+    /// Convert a provider cost in USD to credits
+    pub fn usd_to_credits(&self, usd: f64, provider: &str) -> f64 {
+        usd * self.markup_for(provider) * self.credits_per_usd
+    }
+
+    /// Whole credits to charge for a provider cost (rounded up)
+    pub fn charge(&self, usd: f64, provider: &str) -> i64 {
+        // Absorb float noise so e.g. 0.05 * 100 charges 5, not 6
+        (self.usd_to_credits(usd, provider) - 1e-9).ceil().max(0.0) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PricingConfig {
+        PricingConfig {
+            credits_per_usd: 100.0,
+            default_markup: 1.5,
+            provider_markups: HashMap::from([("fal".to_string(), 1.2), ("vertex".to_string(), 1.0)]),
+        }
+    }
+
+    #[test]
+    fn test_credit_conversion() {
+        let pricing = PricingConfig::default();
+        assert_eq!(pricing.charge(CHAT_USD, "openai"), 1);
+        assert_eq!(pricing.charge(IMAGE_USD, "fal"), 5);
+        assert_eq!(pricing.charge(VIDEO_USD_PER_SECOND * 8.0, "vertex"), 80);
+        assert_eq!(pricing.charge(0.0, "fal"), 0);
+
+        let cheap = PricingConfig { credits_per_usd: 10.0, ..PricingConfig::default() };
+        assert_eq!(cheap.charge(IMAGE_USD * 4.0, "fal"), 2);
+    }
+
+    #[test]
+    fn test_per_provider_markup() {
+        let pricing = config();
+        assert_eq!(pricing.markup_for("fal"), 1.2);
+        // Provider names are matched case-insensitively
+        assert_eq!(pricing.markup_for("Vertex"), 1.0);
+        assert_eq!(pricing.markup_for("replicate"), 1.5);
+
+        assert_eq!(pricing.charge(IMAGE_USD, "fal"), 6);
+        assert_eq!(pricing.charge(IMAGE_USD, "vertex"), 5);
+        assert_eq!(pricing.charge(IMAGE_USD, "replicate"), 8);
+    }
+
+    #[test]
+    fn test_charge_rounds_up() {
+        let pricing = config();
+        // 0.011 USD at 1.2x is 1.32 credits
+        assert_eq!(pricing.charge(0.011, "fal"), 2);
+        // 0.05 * 100 is 5.000000000000001 in floating point
+        assert_eq!(pricing.charge(0.05, "vertex"), 5);
+        assert_eq!(pricing.charge(0.0501, "vertex"), 6);
+        // Negative costs never turn into refunds
+        assert_eq!(pricing.charge(-1.0, "fal"), 0);
+    }
+}
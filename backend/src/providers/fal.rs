@@ -1,13 +1,26 @@
 //! Fal.ai client for Flux, Kling, and other models
+//!
+//! Everything goes through Fal's queue: [`FalClient::submit`] returns a
+//! request id at once and [`FalClient::poll_status`] follows the job. Images
+//! are quick, so `submit_image` waits for them; video jobs are handed back
+//! queued and finish through polling or the Fal webhook.
 
 use crate::config::Config;
+use super::{
+    fallback::{check_response, ProviderHttpError},
+    GenerationProvider, ImageJob, JobStatus, VideoJob,
+};
 use anyhow::Result;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// Fal.ai client
 #[derive(Clone)]
 pub struct FalClient {
     api_key: String,
+    /// Where Fal reports finished queue jobs; polled only when unset
+    webhook_url: Option<String>,
     http_client: reqwest::Client,
 }
 
@@ -29,12 +42,19 @@ pub struct FalVideoRequest {
     pub image_url: Option<String>, // For image-to-video
 }
 
-/// Generation response
+/// Queue status response (also returned when submitting)
 #[derive(Debug, Deserialize)]
-pub struct FalResponse {
-    pub request_id: String,
-    pub status: String,
-    pub output: Option<FalOutput>,
+struct FalQueueStatus {
+    request_id: Option<String>,
+    status: String,
+    queue_position: Option<u32>,
+    #[serde(default)]
+    logs: Option<Vec<FalLog>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FalLog {
+    message: String,
 }
 
 /// Generation output
@@ -44,11 +64,19 @@ pub struct FalOutput {
     pub video: Option<FalVideo>,
 }
 
+impl FalOutput {
+    /// First image or the video
+    pub fn url(&self) -> Option<String> {
+        self.images
+            .as_ref()
+            .and_then(|imgs| imgs.first().map(|i| i.url.clone()))
+            .or_else(|| self.video.as_ref().map(|v| v.url.clone()))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct FalImage {
     pub url: String,
-    pub width: u32,
-    pub height: u32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -56,55 +84,122 @@ pub struct FalVideo {
     pub url: String,
 }
 
+/// State of a Fal queue job
+#[derive(Debug)]
+pub enum FalJobStatus {
+    /// Waiting for a worker; `position` 0 is next
+    Queued { position: Option<u32> },
+    /// Running; `progress` (0.0-1.0) when the model logs it
+    InProgress { progress: Option<f32> },
+    Completed(FalOutput),
+    /// The model failed; Fal's error detail
+    Failed(String),
+}
+
+impl FalJobStatus {
+    /// The provider-neutral status of request `request_id`
+    pub fn into_job_status(self, request_id: &str) -> JobStatus {
+        let request_id = request_id.to_string();
+        match self {
+            Self::Queued { position } => JobStatus {
+                request_id,
+                status: "IN_QUEUE".to_string(),
+                queue_position: position,
+                ..Default::default()
+            },
+            Self::InProgress { progress } => JobStatus {
+                request_id,
+                status: "IN_PROGRESS".to_string(),
+                progress,
+                ..Default::default()
+            },
+            Self::Completed(output) => JobStatus {
+                request_id,
+                status: "COMPLETED".to_string(),
+                url: output.url(),
+                progress: Some(1.0),
+                ..Default::default()
+            },
+            Self::Failed(error) => JobStatus {
+                request_id,
+                status: "FAILED".to_string(),
+                error: Some(error),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// How long [`FalClient::submit_image`] waits before handing the job back
+/// still pending
+const IMAGE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Gap between status checks in [`FalClient::wait_for`]
+const WAIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 impl FalClient {
     /// Create a new Fal.ai client
     pub fn new(config: &Config) -> Result<Self> {
+        let webhook_url = config
+            .public_url
+            .as_ref()
+            .map(|base| format!("{}/api/webhook/fal", base.trim_end_matches('/')));
+
         Ok(Self {
             api_key: config.fal_api_key.clone(),
+            webhook_url,
             http_client: reqwest::Client::new(),
         })
     }
 
-    /// Generate image with Flux
-    pub async fn generate_image(&self, request: FalImageRequest) -> Result<FalResponse> {
-        let model_endpoint = match request.model.as_str() {
+    /// Fal endpoint for an image model
+    fn image_endpoint(model: &str) -> &'static str {
+        match model {
             "flux-pro" => "fal-ai/flux-pro/v1.1",
             "flux-dev" => "fal-ai/flux/dev",
             "flux-schnell" => "fal-ai/flux/schnell",
             _ => "fal-ai/flux/schnell", // Default to fast model
-        };
+        }
+    }
+
+    /// Fal endpoint for a video model
+    fn video_endpoint(model: &str) -> &'static str {
+        match model {
+            "kling-pro" => "fal-ai/kling-video/v1.6/pro/image-to-video",
+            "kling-standard" => "fal-ai/kling-video/v1.6/standard/image-to-video",
+            _ => "fal-ai/kling-video/v1.6/standard/image-to-video",
+        }
+    }
+
+    /// Fal endpoint serving `model`
+    fn endpoint(model: &str) -> &'static str {
+        if model.starts_with("kling") {
+            Self::video_endpoint(model)
+        } else {
+            Self::image_endpoint(model)
+        }
+    }
 
-        let url = format!("https://fal.run/{}", model_endpoint);
+    /// App id of `model`'s endpoint ("fal-ai/flux/dev" -> "fal-ai/flux");
+    /// queue status, result and cancel URLs leave out the endpoint path
+    fn app_id(model: &str) -> &'static str {
+        let endpoint = Self::endpoint(model);
+        match endpoint.match_indices('/').nth(1) {
+            Some((i, _)) => &endpoint[..i],
+            None => endpoint,
+        }
+    }
 
-        let body = serde_json::json!({
+    fn image_params(request: FalImageRequest) -> serde_json::Value {
+        serde_json::json!({
             "prompt": request.prompt,
             "image_size": request.image_size.unwrap_or_else(|| "landscape_16_9".to_string()),
             "num_images": request.num_images.unwrap_or(1),
             "enable_safety_checker": true
-        });
-
-        let response = self.http_client
-            .post(&url)
-            .header("Authorization", format!("Key {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
-
-        let result: FalResponse = response.json().await?;
-        Ok(result)
+        })
     }
 
-    /// Generate video with Kling
-    pub async fn generate_video(&self, request: FalVideoRequest) -> Result<FalResponse> {
-        let model_endpoint = match request.model.as_str() {
-            "kling-pro" => "fal-ai/kling-video/v1.6/pro/image-to-video",
-            "kling-standard" => "fal-ai/kling-video/v1.6/standard/image-to-video",
-            _ => "fal-ai/kling-video/v1.6/standard/image-to-video",
-        };
-
-        let url = format!("https://fal.run/{}", model_endpoint);
-
+    fn video_params(request: FalVideoRequest) -> serde_json::Value {
         let mut body = serde_json::json!({
             "prompt": request.prompt,
             "duration": request.duration.unwrap_or(5.0),
@@ -113,56 +208,95 @@ impl FalClient {
         if let Some(image_url) = &request.image_url {
             body["image_url"] = serde_json::json!(image_url);
         }
+        body
+    }
 
-        let response = self.http_client
+    /// Queue a job for `model` and return its request id at once
+    ///
+    /// Fal calls the webhook when the job finishes if one is configured;
+    /// [`Self::poll_status`] works either way.
+    pub async fn submit(&self, model: &str, params: serde_json::Value) -> Result<String> {
+        let url = format!("https://queue.fal.run/{}", Self::endpoint(model));
+
+        let mut request = self.http_client
             .post(&url)
             .header("Authorization", format!("Key {}", self.api_key))
             .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
+            .json(&params);
+        if let Some(webhook_url) = &self.webhook_url {
+            request = request.query(&[("fal_webhook", webhook_url)]);
+        }
+        let response = request.send().await?;
 
-        let result: FalResponse = response.json().await?;
-        Ok(result)
+        let queued: FalQueueStatus = check_response("fal", response).await?.json().await?;
+        queued.request_id.ok_or_else(|| anyhow::anyhow!("No request_id in response"))
     }
 
-    /// Submit async job (for long-running tasks)
-    pub async fn submit_async(&self, endpoint: &str, body: serde_json::Value) -> Result<String> {
-        let url = format!("https://queue.fal.run/{}", endpoint);
+    /// Current state of a queued job, with its output once completed
+    pub async fn poll_status(&self, model: &str, request_id: &str) -> Result<FalJobStatus> {
+        let url = format!("https://queue.fal.run/{}/requests/{}/status", Self::app_id(model), request_id);
 
         let response = self.http_client
-            .post(&url)
+            .get(&url)
+            .query(&[("logs", "1")])
             .header("Authorization", format!("Key {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&body)
             .send()
             .await?;
 
-        let result: serde_json::Value = response.json().await?;
-        let request_id = result["request_id"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("No request_id in response"))?;
+        let status: FalQueueStatus = check_response("fal", response).await?.json().await?;
+        match status.status.as_str() {
+            "IN_QUEUE" => Ok(FalJobStatus::Queued { position: status.queue_position }),
+            "IN_PROGRESS" => {
+                let logs = status.logs.unwrap_or_default();
+                Ok(FalJobStatus::InProgress { progress: logs.iter().rev().find_map(|l| log_progress(&l.message)) })
+            }
+            // Failed jobs complete too; their result is an error response
+            "COMPLETED" => match self.get_result(model, request_id).await {
+                Ok(output) => Ok(FalJobStatus::Completed(output)),
+                Err(e) => match e.downcast::<ProviderHttpError>() {
+                    Ok(http) => Ok(FalJobStatus::Failed(http.body)),
+                    Err(e) => Err(e),
+                },
+            },
+            other => anyhow::bail!("Unknown Fal queue status {} for {}", other, request_id),
+        }
+    }
 
-        Ok(request_id.to_string())
+    /// Poll until the job finishes or `timeout` passes, and return the last
+    /// status seen; a job still pending stays at Fal for polls and the webhook
+    pub async fn wait_for(&self, model: &str, request_id: &str, timeout: Duration) -> Result<FalJobStatus> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let status = self.poll_status(model, request_id).await?;
+            let finished = matches!(status, FalJobStatus::Completed(_) | FalJobStatus::Failed(_));
+            if finished || tokio::time::Instant::now() + WAIT_POLL_INTERVAL > deadline {
+                return Ok(status);
+            }
+            tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+        }
     }
 
-    /// Check job status
-    pub async fn get_status(&self, endpoint: &str, request_id: &str) -> Result<FalResponse> {
-        let url = format!("https://queue.fal.run/{}/requests/{}/status", endpoint, request_id);
+    /// Cancel a queued or running job
+    pub async fn cancel_request(&self, model: &str, request_id: &str) -> Result<()> {
+        let url = format!("https://queue.fal.run/{}/requests/{}/cancel", Self::app_id(model), request_id);
 
         let response = self.http_client
-            .get(&url)
+            .put(&url)
             .header("Authorization", format!("Key {}", self.api_key))
             .send()
             .await?;
 
-        let result: FalResponse = response.json().await?;
-        Ok(result)
+        // 400 means the job already completed
+        if !response.status().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("Fal refused to cancel {}: {}", request_id, body);
+        }
+        Ok(())
     }
 
     /// Get job result
-    pub async fn get_result(&self, endpoint: &str, request_id: &str) -> Result<FalResponse> {
-        let url = format!("https://queue.fal.run/{}/requests/{}", endpoint, request_id);
+    async fn get_result(&self, model: &str, request_id: &str) -> Result<FalOutput> {
+        let url = format!("https://queue.fal.run/{}/requests/{}", Self::app_id(model), request_id);
 
         let response = self.http_client
             .get(&url)
@@ -170,7 +304,111 @@ impl FalClient {
             .send()
             .await?;
 
-        let result: FalResponse = response.json().await?;
+        let result: FalOutput = check_response("fal", response).await?.json().await?;
         Ok(result)
     }
 }
+
+/// Progress from a progress-bar log line like ` 45%|████▌     | 9/20`
+fn log_progress(message: &str) -> Option<f32> {
+    let (before, _) = message.split_once('%')?;
+    let digits = before.trim_start();
+    let percent: f32 = digits.parse().ok()?;
+    (0.0..=100.0).contains(&percent).then_some(percent / 100.0)
+}
+
+#[async_trait]
+impl GenerationProvider for FalClient {
+    fn name(&self) -> &str {
+        "fal"
+    }
+
+    fn supports(&self, model: &str) -> bool {
+        model.starts_with("flux") || model.starts_with("kling")
+    }
+
+    async fn submit_image(&self, job: ImageJob) -> Result<JobStatus> {
+        let model = job.model.clone();
+        let params = Self::image_params(FalImageRequest {
+            prompt: job.prompt,
+            model: job.model,
+            image_size: job.size,
+            num_images: Some(1),
+        });
+        let request_id = self.submit(&model, params).await?;
+        // Fal has the job now: if we stop waiting it is handed back pending,
+        // for polls and the webhook to finish, rather than abandoned there
+        let status = match self.wait_for(&model, &request_id, IMAGE_TIMEOUT).await {
+            Ok(status) => status,
+            Err(e) => {
+                tracing::warn!(%request_id, error = %e, "Lost track of Fal image job; leaving it queued");
+                FalJobStatus::Queued { position: None }
+            }
+        };
+        Ok(status.into_job_status(&request_id))
+    }
+
+    async fn submit_video(&self, job: VideoJob) -> Result<JobStatus> {
+        let model = job.model.clone();
+        let params = Self::video_params(FalVideoRequest {
+            prompt: job.prompt,
+            model: job.model,
+            duration: Some(job.duration),
+            image_url: job.image_url,
+        });
+        let request_id = self.submit(&model, params).await?;
+        Ok(FalJobStatus::Queued { position: None }.into_job_status(&request_id))
+    }
+
+    async fn poll(&self, model: &str, request_id: &str) -> Result<JobStatus> {
+        Ok(self.poll_status(model, request_id).await?.into_job_status(request_id))
+    }
+
+    async fn cancel(&self, model: &str, request_id: &str) -> Result<()> {
+        self.cancel_request(model, request_id).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        // Any HTTP response means the queue is reachable
+        self.http_client.head("https://queue.fal.run/").send().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_app_id() {
+        assert_eq!(FalClient::app_id("flux-dev"), "fal-ai/flux");
+        assert_eq!(FalClient::app_id("flux-pro"), "fal-ai/flux-pro");
+        assert_eq!(FalClient::app_id("kling-pro"), "fal-ai/kling-video");
+    }
+
+    #[test]
+    fn test_log_progress() {
+        assert_eq!(log_progress(" 45%|████▌     | 9/20 [00:04<00:05]"), Some(0.45));
+        assert_eq!(log_progress("100%|██████████| 20/20"), Some(1.0));
+        assert_eq!(log_progress("Loading model"), None);
+        assert_eq!(log_progress("Using 150% guidance"), None);
+    }
+
+    #[test]
+    fn test_job_status() {
+        let queued = FalJobStatus::Queued { position: Some(3) }.into_job_status("req-1");
+        assert_eq!(queued.status, "IN_QUEUE");
+        assert_eq!(queued.queue_position, Some(3));
+
+        let output: FalOutput = serde_json::from_value(serde_json::json!({
+            "images": [{ "url": "https://fal.media/a.png", "width": 1024, "height": 576 }],
+        }))
+        .unwrap();
+        let completed = FalJobStatus::Completed(output).into_job_status("req-1");
+        assert_eq!(completed.status, "COMPLETED");
+        assert_eq!(completed.url.as_deref(), Some("https://fal.media/a.png"));
+
+        let failed = FalJobStatus::Failed("NSFW content detected".to_string()).into_job_status("req-1");
+        assert_eq!(failed.error.as_deref(), Some("NSFW content detected"));
+    }
+}
@@ -0,0 +1,301 @@
+//! Provider fallback when a vendor is unavailable
+//!
+//! A job is tried on the provider serving the requested model first, then
+//! on the alternates configured for its capability (e.g. Fal's Flux, then
+//! Vertex's Imagen for text-to-image). Only availability failures fall
+//! through: 5xx, 429, timeouts and connection errors. A request the vendor
+//! rejects (4xx) would fail the same way elsewhere and is returned as is.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+
+use super::{GenerationProvider, JobStatus, ProviderRegistry};
+
+/// Text-to-image alternate used when `FALLBACK_TEXT_TO_IMAGE` isn't set
+const DEFAULT_IMAGE_FALLBACK: &str = "vertex:imagen-3.0-fast-generate-001";
+
+/// What a job produces, independent of the vendor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    TextToImage,
+    TextToVideo,
+    ImageToVideo,
+}
+
+/// A provider and the model to ask it for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FallbackTarget {
+    pub provider: String,
+    pub model: String,
+}
+
+/// Alternates per capability, in the order they are tried
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FallbackConfig {
+    pub text_to_image: Vec<FallbackTarget>,
+    pub text_to_video: Vec<FallbackTarget>,
+    pub image_to_video: Vec<FallbackTarget>,
+}
+
+impl FallbackConfig {
+    /// Load from `FALLBACK_TEXT_TO_IMAGE`, `FALLBACK_TEXT_TO_VIDEO` and
+    /// `FALLBACK_IMAGE_TO_VIDEO`, each a list like
+    /// `vertex:imagen-3.0-fast-generate-001,fal:flux-dev` (empty disables)
+    pub fn from_env() -> Self {
+        let targets = |name: &str, default: &str| {
+            parse_targets(&std::env::var(name).unwrap_or_else(|_| default.to_string()))
+        };
+
+        Self {
+            text_to_image: targets("FALLBACK_TEXT_TO_IMAGE", DEFAULT_IMAGE_FALLBACK),
+            text_to_video: targets("FALLBACK_TEXT_TO_VIDEO", ""),
+            image_to_video: targets("FALLBACK_IMAGE_TO_VIDEO", ""),
+        }
+    }
+
+    /// Providers to try for a job: `primary` with `model`, then the
+    /// capability's alternates on other providers
+    pub fn chain(&self, capability: Capability, primary: &str, model: &str) -> Vec<FallbackTarget> {
+        let alternates = match capability {
+            Capability::TextToImage => &self.text_to_image,
+            Capability::TextToVideo => &self.text_to_video,
+            Capability::ImageToVideo => &self.image_to_video,
+        };
+
+        let primary = FallbackTarget { provider: primary.to_string(), model: model.to_string() };
+        std::iter::once(primary.clone())
+            .chain(alternates.iter().filter(|t| t.provider != primary.provider).cloned())
+            .collect()
+    }
+}
+
+fn parse_targets(list: &str) -> Vec<FallbackTarget> {
+    list.split(',')
+        .filter_map(|entry| {
+            let (provider, model) = entry.split_once(':')?;
+            let (provider, model) = (provider.trim(), model.trim());
+            (!provider.is_empty() && !model.is_empty()).then(|| FallbackTarget {
+                provider: provider.to_lowercase(),
+                model: model.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// A vendor answered with an HTTP error
+#[derive(Debug, thiserror::Error)]
+#[error("{provider} returned {status}: {body}")]
+pub struct ProviderHttpError {
+    pub provider: &'static str,
+    pub status: reqwest::StatusCode,
+    pub body: String,
+}
+
+/// `response`, or a [`ProviderHttpError`] if it isn't a success
+pub async fn check_response(provider: &'static str, response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(ProviderHttpError { provider, status, body }.into())
+}
+
+/// Whether another provider could succeed where this error failed
+pub fn is_retryable(error: &anyhow::Error) -> bool {
+    if let Some(http) = error.downcast_ref::<ProviderHttpError>() {
+        return http.status.is_server_error()
+            || http.status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            || http.status == reqwest::StatusCode::REQUEST_TIMEOUT;
+    }
+    if let Some(request) = error.downcast_ref::<reqwest::Error>() {
+        return request.is_timeout() || request.is_connect();
+    }
+    false
+}
+
+/// A job accepted by a provider
+#[derive(Debug, Clone)]
+pub struct Dispatched {
+    pub status: JobStatus,
+    /// Set when an alternate accepted the job instead of the provider it
+    /// was queued for
+    pub fallback: Option<FallbackTarget>,
+}
+
+impl From<JobStatus> for Dispatched {
+    fn from(status: JobStatus) -> Self {
+        Self { status, fallback: None }
+    }
+}
+
+/// Submit to each target of `chain` in turn until one accepts the job
+///
+/// Moves on only when [`is_retryable`] says the provider is unavailable;
+/// other errors are returned at once.
+pub async fn dispatch_with_fallback<F, Fut>(
+    registry: &ProviderRegistry,
+    chain: &[FallbackTarget],
+    submit: F,
+) -> Result<Dispatched>
+where
+    F: Fn(Arc<dyn GenerationProvider>, String) -> Fut,
+    Fut: Future<Output = Result<JobStatus>>,
+{
+    let mut last_error = None;
+
+    for (attempt, target) in chain.iter().enumerate() {
+        let Some(provider) = registry.get(&target.provider) else {
+            tracing::warn!(provider = %target.provider, "Fallback provider is not registered");
+            continue;
+        };
+
+        match submit(provider, target.model.clone()).await {
+            Ok(status) => {
+                if attempt > 0 {
+                    tracing::info!(provider = %target.provider, model = %target.model, "Served by fallback provider");
+                }
+                return Ok(Dispatched {
+                    status,
+                    fallback: (attempt > 0).then(|| target.clone()),
+                });
+            }
+            Err(e) if is_retryable(&e) => {
+                tracing::warn!(provider = %target.provider, error = %e, "Provider unavailable, trying the next one");
+                last_error = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No provider available for this job")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{ImageJob, VideoJob};
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Provider failing every submission with `status`, or accepting it
+    struct StubProvider {
+        name: &'static str,
+        status: Option<reqwest::StatusCode>,
+        submitted: Mutex<Vec<String>>,
+    }
+
+    impl StubProvider {
+        fn new(name: &'static str, status: Option<reqwest::StatusCode>) -> Arc<Self> {
+            Arc::new(Self { name, status, submitted: Mutex::new(Vec::new()) })
+        }
+    }
+
+    #[async_trait]
+    impl GenerationProvider for StubProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn supports(&self, _model: &str) -> bool {
+            true
+        }
+
+        async fn submit_image(&self, job: ImageJob) -> Result<JobStatus> {
+            self.submitted.lock().unwrap().push(job.model);
+            if let Some(status) = self.status {
+                return Err(ProviderHttpError { provider: "stub", status, body: String::new() }.into());
+            }
+            Ok(JobStatus {
+                request_id: format!("{}-1", self.name),
+                status: "COMPLETED".to_string(),
+                ..Default::default()
+            })
+        }
+
+        async fn submit_video(&self, _job: VideoJob) -> Result<JobStatus> {
+            unimplemented!()
+        }
+
+        async fn poll(&self, _model: &str, _request_id: &str) -> Result<JobStatus> {
+            unimplemented!()
+        }
+    }
+
+    fn job() -> ImageJob {
+        ImageJob {
+            prompt: "A neon alley in the rain".to_string(),
+            model: String::new(),
+            size: None,
+        }
+    }
+
+    fn config() -> FallbackConfig {
+        FallbackConfig {
+            text_to_image: parse_targets("fal:flux-dev, vertex:imagen-3.0-fast-generate-001"),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_when_unavailable() {
+        let mut registry = ProviderRegistry::new();
+        let fal = StubProvider::new("fal", Some(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+        let vertex = StubProvider::new("vertex", None);
+        registry.register(fal.clone());
+        registry.register(vertex.clone());
+
+        let chain = config().chain(Capability::TextToImage, "fal", "flux-schnell");
+        assert_eq!(chain.len(), 2);
+
+        let dispatched = dispatch_with_fallback(&registry, &chain, |provider, model| async move {
+            provider.submit_image(ImageJob { model, ..job() }).await
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(dispatched.status.request_id, "vertex-1");
+        assert_eq!(
+            dispatched.fallback,
+            Some(FallbackTarget {
+                provider: "vertex".to_string(),
+                model: "imagen-3.0-fast-generate-001".to_string(),
+            })
+        );
+        assert_eq!(*fal.submitted.lock().unwrap(), ["flux-schnell"]);
+    }
+
+    #[tokio::test]
+    async fn test_user_errors_do_not_fall_back() {
+        let mut registry = ProviderRegistry::new();
+        let fal = StubProvider::new("fal", Some(reqwest::StatusCode::UNPROCESSABLE_ENTITY));
+        let vertex = StubProvider::new("vertex", None);
+        registry.register(fal.clone());
+        registry.register(vertex.clone());
+
+        let chain = config().chain(Capability::TextToImage, "fal", "flux-schnell");
+        let error = dispatch_with_fallback(&registry, &chain, |provider, model| async move {
+            provider.submit_image(ImageJob { model, ..job() }).await
+        })
+        .await
+        .unwrap_err();
+
+        assert!(!is_retryable(&error));
+        assert!(vertex.submitted.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_chain() {
+        let config = config();
+        let chain = config.chain(Capability::TextToImage, "vertex", "imagen-3.0-generate-001");
+        let providers: Vec<_> = chain.iter().map(|t| (t.provider.as_str(), t.model.as_str())).collect();
+        assert_eq!(providers, [("vertex", "imagen-3.0-generate-001"), ("fal", "flux-dev")]);
+
+        // No alternates configured for video
+        assert_eq!(config.chain(Capability::ImageToVideo, "fal", "kling-pro").len(), 1);
+        assert!(parse_targets("").is_empty());
+        assert!(parse_targets("vertex, :model").is_empty());
+    }
+}
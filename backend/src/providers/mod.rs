@@ -1,7 +1,225 @@
 //! AI Provider modules
+//!
+//! Image/video vendors implement `GenerationProvider` and are registered in a
+//! `ProviderRegistry` keyed by provider name. Generate routes dispatch to the
+//! provider that serves the requested model, so adding a vendor means
+//! implementing the trait and registering it in `AppState::new`. When that
+//! provider is down, `dispatch_with_fallback` tries configured alternates.
 
 pub mod fal;
+pub mod fallback;
 pub mod vertex;
 
 pub use fal::FalClient;
+pub use fallback::{dispatch_with_fallback, Capability, Dispatched};
 pub use vertex::VertexClient;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::pricing;
+
+/// Image generation job
+#[derive(Debug, Clone)]
+pub struct ImageJob {
+    pub prompt: String,
+    pub model: String,
+    pub size: Option<String>,
+}
+
+/// Video generation job
+#[derive(Debug, Clone)]
+pub struct VideoJob {
+    pub prompt: String,
+    pub model: String,
+    pub duration: f32,
+    pub image_url: Option<String>, // For image-to-video
+}
+
+/// What a cost estimate is for
+#[derive(Debug, Clone, Copy)]
+pub enum JobKind {
+    Image,
+    Video { duration: f32 },
+}
+
+/// State of a submitted job
+#[derive(Debug, Clone, Default)]
+pub struct JobStatus {
+    pub request_id: String,
+    pub status: String,
+    /// Output URL once the job has completed
+    pub url: Option<String>,
+    /// Jobs ahead of this one in the vendor's queue, when it says
+    pub queue_position: Option<u32>,
+    /// Fraction done (0.0-1.0), when the vendor reports it
+    pub progress: Option<f32>,
+    /// Why the vendor failed the job
+    pub error: Option<String>,
+}
+
+/// An image/video generation vendor
+#[async_trait]
+pub trait GenerationProvider: Send + Sync {
+    /// Registry key, also used for pricing markups ("fal", "vertex", ...)
+    fn name(&self) -> &str;
+
+    /// Whether this provider serves `model`
+    fn supports(&self, model: &str) -> bool;
+
+    async fn submit_image(&self, job: ImageJob) -> Result<JobStatus>;
+
+    async fn submit_video(&self, job: VideoJob) -> Result<JobStatus>;
+
+    /// Current state of a previously submitted job
+    async fn poll(&self, model: &str, request_id: &str) -> Result<JobStatus>;
+
+    /// Cancel a submitted job; errors if the vendor can't (e.g. already completed)
+    async fn cancel(&self, _model: &str, request_id: &str) -> Result<()> {
+        anyhow::bail!("{} jobs can't be cancelled ({})", self.name(), request_id)
+    }
+
+    /// Light reachability check for readiness probes; doesn't submit work
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Provider cost in USD (before markup)
+    fn cost_estimate(&self, _model: &str, kind: JobKind) -> f64 {
+        match kind {
+            JobKind::Image => pricing::IMAGE_USD,
+            JobKind::Video { duration } => pricing::VIDEO_USD_PER_SECOND * duration as f64,
+        }
+    }
+}
+
+/// Generation providers keyed by name
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+    providers: HashMap<String, Arc<dyn GenerationProvider>>,
+    /// Registration order; the first provider supporting a model serves it
+    order: Vec<String>,
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a provider, replacing any provider with the same name
+    pub fn register(&mut self, provider: Arc<dyn GenerationProvider>) {
+        let name = provider.name().to_string();
+        if self.providers.insert(name.clone(), provider).is_none() {
+            self.order.push(name);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn GenerationProvider>> {
+        self.providers.get(name).cloned()
+    }
+
+    /// All providers, in registration order
+    pub fn all(&self) -> impl Iterator<Item = Arc<dyn GenerationProvider>> + '_ {
+        self.order.iter().filter_map(|name| self.providers.get(name)).cloned()
+    }
+
+    /// Provider serving `model`
+    pub fn for_model(&self, model: &str) -> Option<Arc<dyn GenerationProvider>> {
+        self.order
+            .iter()
+            .filter_map(|name| self.providers.get(name))
+            .find(|provider| provider.supports(model))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Provider that records submissions instead of calling a vendor
+    struct MockProvider {
+        name: &'static str,
+        prefix: &'static str,
+        submitted: Mutex<Vec<String>>,
+    }
+
+    impl MockProvider {
+        fn new(name: &'static str, prefix: &'static str) -> Arc<Self> {
+            Arc::new(Self { name, prefix, submitted: Mutex::new(Vec::new()) })
+        }
+    }
+
+    #[async_trait]
+    impl GenerationProvider for MockProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn supports(&self, model: &str) -> bool {
+            model.starts_with(self.prefix)
+        }
+
+        async fn submit_image(&self, job: ImageJob) -> Result<JobStatus> {
+            self.submitted.lock().unwrap().push(job.model);
+            Ok(JobStatus {
+                request_id: format!("{}-1", self.name),
+                status: "COMPLETED".to_string(),
+                url: Some(format!("https://{}.example/image.png", self.name)),
+                ..Default::default()
+            })
+        }
+
+        async fn submit_video(&self, job: VideoJob) -> Result<JobStatus> {
+            self.submitted.lock().unwrap().push(job.model);
+            Ok(JobStatus {
+                request_id: format!("{}-2", self.name),
+                status: "IN_QUEUE".to_string(),
+                ..Default::default()
+            })
+        }
+
+        async fn poll(&self, _model: &str, request_id: &str) -> Result<JobStatus> {
+            Ok(JobStatus {
+                request_id: request_id.to_string(),
+                status: "COMPLETED".to_string(),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_by_model() {
+        let fal = MockProvider::new("fal", "flux");
+        let replicate = MockProvider::new("replicate", "sdxl");
+
+        let mut registry = ProviderRegistry::new();
+        registry.register(fal.clone());
+        registry.register(replicate.clone());
+
+        let provider = registry.for_model("sdxl-lightning").unwrap();
+        assert_eq!(provider.name(), "replicate");
+
+        let status = provider
+            .submit_image(ImageJob {
+                prompt: "A lighthouse at dusk".to_string(),
+                model: "sdxl-lightning".to_string(),
+                size: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(status.request_id, "replicate-1");
+        assert_eq!(*replicate.submitted.lock().unwrap(), ["sdxl-lightning"]);
+        assert!(fal.submitted.lock().unwrap().is_empty());
+
+        assert_eq!(registry.for_model("flux-dev").unwrap().name(), "fal");
+        assert!(registry.for_model("unknown-model").is_none());
+        assert!(registry.get("replicate").is_some());
+
+        let cost = provider.cost_estimate("sdxl-lightning", JobKind::Video { duration: 5.0 });
+        assert!((cost - 0.5).abs() < 1e-9);
+    }
+}
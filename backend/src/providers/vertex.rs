@@ -1,11 +1,25 @@
 //! Vertex AI client for Gemini, Imagen, and Veo
 
 use crate::config::Config;
+use super::{fallback::check_response, GenerationProvider, ImageJob, JobStatus, VideoJob};
 use anyhow::Result;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use futures::Stream;
 use std::pin::Pin;
 
+/// Chat model used when the client doesn't pick one
+pub const DEFAULT_CHAT_MODEL: &str = "gemini-2.0-flash";
+
+/// Gemini models the chat route may be asked to use
+pub const SUPPORTED_CHAT_MODELS: &[&str] = &[
+    "gemini-2.0-flash",
+    "gemini-2.0-flash-lite",
+    "gemini-2.5-flash",
+    "gemini-2.5-flash-lite",
+    "gemini-2.5-pro",
+];
+
 /// Vertex AI client
 #[derive(Clone)]
 pub struct VertexClient {
@@ -35,6 +49,17 @@ pub struct ChatRequest {
 pub struct ChatChunk {
     pub content: String,
     pub done: bool,
+    /// Token counts, on the final (`done`) chunk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ChatUsage>,
+}
+
+/// Token counts for a chat, used for credit accounting
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
 }
 
 /// Image generation request
@@ -68,7 +93,7 @@ impl VertexClient {
     ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatChunk>> + Send>>> {
         let model = &request.model;
         let url = format!(
-            "https://{}-aiplatform.googleapis.com/v1/projects/{}/locations/{}/publishers/google/models/{}:streamGenerateContent",
+            "https://{}-aiplatform.googleapis.com/v1/projects/{}/locations/{}/publishers/google/models/{}:streamGenerateContent?alt=sse",
             self.region, self.project_id, self.region, model
         );
 
@@ -98,30 +123,43 @@ impl VertexClient {
             .send()
             .await?;
 
-        // Parse streaming response
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("Vertex AI returned {}: {}", status, body);
+        }
+
+        // The stream owns the response: dropping it (e.g. the client went
+        // away) closes the upstream connection
         let stream = async_stream::try_stream! {
-            let bytes = response.bytes().await?;
-            let text = String::from_utf8_lossy(&bytes);
-            
-            // Parse Vertex AI response format
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
-                if let Some(candidates) = json.get("candidates").and_then(|c| c.as_array()) {
-                    for candidate in candidates {
-                        if let Some(content) = candidate.get("content")
-                            .and_then(|c| c.get("parts"))
-                            .and_then(|p| p.as_array())
-                            .and_then(|a| a.first())
-                            .and_then(|p| p.get("text"))
-                            .and_then(|t| t.as_str())
-                        {
-                            yield ChatChunk {
-                                content: content.to_string(),
-                                done: true,
-                            };
-                        }
+            use futures::StreamExt;
+            let mut body = response.bytes_stream();
+            let mut buffer: Vec<u8> = Vec::new();
+            let mut usage = None;
+
+            loop {
+                let next = body.next().await;
+                match next {
+                    Some(bytes) => buffer.extend_from_slice(&bytes?),
+                    // Flush a last line that has no trailing newline
+                    None if !buffer.is_empty() => buffer.push(b'\n'),
+                    None => break,
+                }
+
+                while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    let (text, line_usage) = parse_stream_line(&String::from_utf8_lossy(&line))?;
+                    // Usage is cumulative; the last report wins
+                    if line_usage.is_some() {
+                        usage = line_usage;
+                    }
+                    if !text.is_empty() {
+                        yield ChatChunk { content: text, done: false, usage: None };
                     }
                 }
             }
+
+            yield ChatChunk { content: String::new(), done: true, usage };
         };
 
         Ok(Box::pin(stream))
@@ -149,7 +187,7 @@ impl VertexClient {
             .send()
             .await?;
 
-        let result: serde_json::Value = response.json().await?;
+        let result: serde_json::Value = check_response("vertex", response).await?.json().await?;
         
         // Extract image from response
         let image_data = result["predictions"][0]["bytesBase64Encoded"]
@@ -161,3 +199,112 @@ impl VertexClient {
         })
     }
 }
+
+/// Text and usage from one line of a `streamGenerateContent?alt=sse` body
+///
+/// Lines that aren't `data:` events yield nothing. Errors Vertex reports
+/// inside the stream (after the 200 status) become `Err`.
+fn parse_stream_line(line: &str) -> Result<(String, Option<ChatUsage>)> {
+    let Some(payload) = line.trim().strip_prefix("data:") else {
+        return Ok((String::new(), None));
+    };
+    let json: serde_json::Value = serde_json::from_str(payload.trim())?;
+
+    if let Some(error) = json.get("error") {
+        anyhow::bail!(
+            "Vertex AI stream error: {}",
+            error.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error")
+        );
+    }
+
+    let text = json["candidates"][0]["content"]["parts"]
+        .as_array()
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect::<String>()
+        })
+        .unwrap_or_default();
+
+    let usage = json.get("usageMetadata").map(|u| {
+        let count = |key: &str| u.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        ChatUsage {
+            prompt_tokens: count("promptTokenCount"),
+            completion_tokens: count("candidatesTokenCount"),
+            total_tokens: count("totalTokenCount"),
+        }
+    });
+
+    Ok((text, usage))
+}
+
+#[async_trait]
+impl GenerationProvider for VertexClient {
+    fn name(&self) -> &str {
+        "vertex"
+    }
+
+    fn supports(&self, model: &str) -> bool {
+        model.starts_with("imagen")
+    }
+
+    async fn submit_image(&self, job: ImageJob) -> Result<JobStatus> {
+        // Imagen responds synchronously with the image
+        let response = self.generate_image(ImageRequest {
+            prompt: job.prompt,
+            model: job.model,
+            size: job.size,
+        }).await?;
+
+        Ok(JobStatus {
+            request_id: uuid::Uuid::new_v4().to_string(),
+            status: "COMPLETED".to_string(),
+            url: Some(response.image_url),
+            ..Default::default()
+        })
+    }
+
+    async fn submit_video(&self, job: VideoJob) -> Result<JobStatus> {
+        anyhow::bail!("Vertex AI video generation is not supported yet (model {})", job.model)
+    }
+
+    async fn poll(&self, _model: &str, request_id: &str) -> Result<JobStatus> {
+        anyhow::bail!("Vertex AI jobs complete synchronously; nothing to poll for {}", request_id)
+    }
+
+    async fn ping(&self) -> Result<()> {
+        // Any HTTP response means the regional endpoint is reachable
+        let url = format!("https://{}-aiplatform.googleapis.com/", self.region);
+        self.http_client.head(&url).send().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stream_line() {
+        let (text, usage) = parse_stream_line(
+            r#"data: {"candidates":[{"content":{"role":"model","parts":[{"text":"INT. "},{"text":"ROOFTOP"}]}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(text, "INT. ROOFTOP");
+        assert_eq!(usage, None);
+
+        let (text, usage) = parse_stream_line(
+            r#"data: {"candidates":[{"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":12,"candidatesTokenCount":30,"totalTokenCount":42}}"#,
+        )
+        .unwrap();
+        assert_eq!(text, "");
+        assert_eq!(
+            usage,
+            Some(ChatUsage { prompt_tokens: 12, completion_tokens: 30, total_tokens: 42 })
+        );
+
+        assert_eq!(parse_stream_line("").unwrap().0, "");
+        assert!(parse_stream_line(r#"data: {"error":{"code":503,"message":"overloaded"}}"#).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 //! Queue modules for async processing
 
 pub mod pubsub;
+pub mod scheduler;
 pub mod tasks;
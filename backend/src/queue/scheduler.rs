@@ -0,0 +1,899 @@
+//! Provider scheduler - rate-limited, prioritized generation dispatch
+//!
+//! Generate routes don't call Fal/Vertex directly. They enqueue a job and
+//! return its id at once; one dispatcher per provider starts jobs in priority
+//! order without exceeding the vendor's concurrency cap or request rate, so
+//! bursts wait here instead of bouncing off provider 429s. A job keeps its
+//! slot until the provider finishes it or it is cancelled. Clients follow a
+//! job with `GET /api/jobs/:id` (or its `/events` stream) until the provider
+//! reports the result, and can cancel it with `DELETE /api/jobs/:id`.
+//!
+//! Every change to a job is passed to the [`Scheduler::on_change`] hook so
+//! it can be persisted; an instance that doesn't know a job (it was queued
+//! elsewhere, or before a restart) [`Scheduler::adopt`]s the stored copy.
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+
+use crate::providers::{Dispatched, JobStatus};
+
+/// How long finished jobs stay queryable
+const JOB_RETENTION: chrono::Duration = chrono::Duration::hours(1);
+
+/// Longest a submitted job holds its provider slot without a final status
+/// (nobody polled it and no webhook arrived)
+const MAX_JOB_RUNTIME: Duration = Duration::from_secs(30 * 60);
+
+/// Limits a provider's lane enforces
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ProviderLimits {
+    /// Jobs in flight at once
+    pub max_concurrent: usize,
+    /// Submissions started per minute
+    pub requests_per_minute: u32,
+}
+
+impl ProviderLimits {
+    /// The vendor's documented default limits
+    pub fn for_provider(name: &str) -> Self {
+        match name {
+            // Fal: 10 concurrent requests per API key
+            "fal" => Self { max_concurrent: 10, requests_per_minute: 600 },
+            // Vertex generative media: 60 requests/minute per project
+            "vertex" => Self { max_concurrent: 5, requests_per_minute: 60 },
+            _ => Self { max_concurrent: 4, requests_per_minute: 60 },
+        }
+    }
+
+    /// Minimum gap between two submissions
+    fn min_interval(&self) -> Duration {
+        Duration::from_secs(60) / self.requests_per_minute.max(1)
+    }
+}
+
+/// Dispatch order; paid users' jobs start first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Free,
+    Paid,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Waiting for a provider slot
+    Queued,
+    /// Being submitted to the provider
+    Running,
+    /// Accepted by the provider; poll for the result
+    Submitted,
+    Failed,
+    Cancelled,
+}
+
+/// A job as reported to clients
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueuedJob {
+    pub id: String,
+    #[serde(skip)]
+    pub user_id: String,
+    /// Provider serving the job; an alternate if the first was unavailable
+    pub provider: String,
+    pub model: String,
+    pub priority: Priority,
+    pub state: JobState,
+    /// Vendor request id once submitted
+    pub provider_request_id: Option<String>,
+    /// Vendor status ("IN_QUEUE", "COMPLETED", ...)
+    pub provider_status: Option<String>,
+    /// Jobs ahead of this one at the vendor, while it waits there
+    pub queue_position: Option<u32>,
+    /// Fraction done (0.0-1.0), when the vendor reports it
+    pub progress: Option<f32>,
+    pub url: Option<String>,
+    pub error: Option<String>,
+    /// Credits charged when the job was queued
+    pub credits: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl QueuedJob {
+    /// Record the provider's latest view of the job
+    pub fn apply_status(&mut self, status: JobStatus) {
+        self.state = if status.error.is_some() { JobState::Failed } else { JobState::Submitted };
+        self.provider_request_id = Some(status.request_id);
+        self.provider_status = Some(status.status);
+        self.queue_position = status.queue_position;
+        self.progress = status.progress.or(self.progress.take());
+        self.url = status.url.or(self.url.take());
+        self.error = status.error.or(self.error.take());
+    }
+
+    /// Whether the job will not change anymore
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, JobState::Failed | JobState::Cancelled)
+            || self.provider_status.as_deref() == Some("COMPLETED")
+    }
+}
+
+/// What cancelling a job takes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cancellation {
+    /// Removed from the queue before submission; refund `credits`
+    Dequeued { credits: i64 },
+    /// Already at the provider; cancel it there, then refund `credits`
+    AtProvider {
+        provider: String,
+        model: String,
+        request_id: String,
+        credits: i64,
+    },
+    /// Cancelled before; refund `credits` again in case that refund failed
+    Cancelled { credits: i64 },
+}
+
+/// Why a job can't be cancelled
+#[derive(Debug, Clone, PartialEq)]
+pub enum CancelError {
+    NotFound,
+    /// Completed or failed
+    Finished,
+    /// Being submitted right now; retry once it is submitted
+    Submitting,
+}
+
+/// Queue depth for one provider
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderDepth {
+    pub provider: String,
+    pub queued: usize,
+    pub running: usize,
+    pub limits: ProviderLimits,
+}
+
+/// Work that submits a job to a provider (or a fallback)
+pub type Submission = BoxFuture<'static, anyhow::Result<Dispatched>>;
+
+/// A job waiting in a lane
+struct Pending {
+    priority: Priority,
+    seq: u64,
+    id: String,
+    submission: Submission,
+}
+
+impl Ord for Pending {
+    /// Higher priority first, then first come first served
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+/// One provider's queue and its limits
+struct Lane {
+    limits: ProviderLimits,
+    permits: Arc<Semaphore>,
+    pending: Mutex<BinaryHeap<Pending>>,
+    wake: Notify,
+}
+
+type Jobs = Arc<Mutex<HashMap<String, QueuedJob>>>;
+
+/// Slots of submitted jobs the provider is still working on, by job id
+type Held = Arc<Mutex<HashMap<String, (OwnedSemaphorePermit, Instant)>>>;
+
+/// Called once with each job that fails, e.g. to refund its charge
+pub type FailureHook = Arc<dyn Fn(&QueuedJob) + Send + Sync>;
+
+/// Called with a job's new state after every change, e.g. to persist it
+pub type ChangeHook = Arc<dyn Fn(&QueuedJob) + Send + Sync>;
+
+/// Per-provider job queues
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    lanes: Arc<Mutex<HashMap<String, Arc<Lane>>>>,
+    jobs: Jobs,
+    held: Held,
+    seq: Arc<AtomicU64>,
+    /// Limits replacing `ProviderLimits::for_provider`
+    overrides: HashMap<String, ProviderLimits>,
+    on_failure: Option<FailureHook>,
+    on_change: Option<ChangeHook>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `limits` for `provider` instead of the vendor defaults
+    pub fn with_limits(mut self, provider: &str, limits: ProviderLimits) -> Self {
+        self.overrides.insert(provider.to_string(), limits);
+        self
+    }
+
+    /// Call `hook` whenever a job fails: at submission, or when a poll or
+    /// webhook reports it failed at the provider
+    pub fn on_failure(mut self, hook: impl Fn(&QueuedJob) + Send + Sync + 'static) -> Self {
+        self.on_failure = Some(Arc::new(hook));
+        self
+    }
+
+    /// Call `hook` with every job after it is queued or changes state
+    pub fn on_change(mut self, hook: impl Fn(&QueuedJob) + Send + Sync + 'static) -> Self {
+        self.on_change = Some(Arc::new(hook));
+        self
+    }
+
+    /// Queue a submission and return its job id without waiting.
+    ///
+    /// `credits` is what the job was charged, refunded if it is cancelled.
+    pub fn enqueue(
+        &self,
+        provider: &str,
+        model: &str,
+        user_id: &str,
+        priority: Priority,
+        credits: i64,
+        submission: Submission,
+    ) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.enqueue_as(id, provider, model, user_id, priority, credits, submission)
+    }
+
+    /// Queue a submission under an id chosen by the caller (one already
+    /// recorded with its charge)
+    #[allow(clippy::too_many_arguments)]
+    pub fn enqueue_as(
+        &self,
+        id: String,
+        provider: &str,
+        model: &str,
+        user_id: &str,
+        priority: Priority,
+        credits: i64,
+        submission: Submission,
+    ) -> String {
+        let now = chrono::Utc::now();
+        self.release_stale();
+
+        let job = QueuedJob {
+            id: id.clone(),
+            user_id: user_id.to_string(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            priority,
+            state: JobState::Queued,
+            provider_request_id: None,
+            provider_status: None,
+            queue_position: None,
+            progress: None,
+            url: None,
+            error: None,
+            credits,
+            created_at: now,
+        };
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.retain(|_, job| !job.is_finished() || now - job.created_at < JOB_RETENTION);
+            jobs.insert(id.clone(), job.clone());
+        }
+        report_change(self.on_change.as_ref(), Some(job));
+
+        let lane = self.lane(provider);
+        lane.pending.lock().unwrap().push(Pending {
+            priority,
+            seq: self.seq.fetch_add(1, AtomicOrdering::Relaxed),
+            id: id.clone(),
+            submission,
+        });
+        lane.wake.notify_one();
+
+        tracing::debug!(job_id = %id, provider, model, ?priority, "Queued generation job");
+        id
+    }
+
+    /// A job by id
+    pub fn job(&self, id: &str) -> Option<QueuedJob> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+
+    /// Track a job this instance didn't queue (loaded from the job store),
+    /// so polls, webhooks and cancellations can update it
+    ///
+    /// A job already tracked here is kept as it is.
+    pub fn adopt(&self, job: QueuedJob) {
+        self.jobs.lock().unwrap().entry(job.id.clone()).or_insert(job);
+    }
+
+    /// Apply a provider status update (poll or webhook) to the job
+    pub fn update(&self, id: &str, status: JobStatus) {
+        let (changed, failed) = self
+            .jobs
+            .lock()
+            .unwrap()
+            .get_mut(id)
+            .map(|job| {
+                if job.state == JobState::Cancelled {
+                    return (None, None);
+                }
+                let was_failed = job.state == JobState::Failed;
+                job.apply_status(status);
+                if job.is_finished() {
+                    self.held.lock().unwrap().remove(id);
+                }
+                let failed = (!was_failed && job.state == JobState::Failed).then(|| job.clone());
+                (Some(job.clone()), failed)
+            })
+            .unwrap_or_default();
+        report_change(self.on_change.as_ref(), changed);
+        report_failure(self.on_failure.as_ref(), failed);
+    }
+
+    /// Free the slots of jobs that ran past `MAX_JOB_RUNTIME`
+    fn release_stale(&self) {
+        self.held.lock().unwrap().retain(|id, (_, since)| {
+            let stale = since.elapsed() >= MAX_JOB_RUNTIME;
+            if stale {
+                tracing::warn!(job_id = %id, "No final status for generation job, freeing its slot");
+            }
+            !stale
+        });
+    }
+
+    /// Id of the job `provider` knows as `request_id` (for webhooks)
+    pub fn find_by_request(&self, provider: &str, request_id: &str) -> Option<String> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .values()
+            .find(|job| job.provider == provider && job.provider_request_id.as_deref() == Some(request_id))
+            .map(|job| job.id.clone())
+    }
+
+    /// Start cancelling a job; queued jobs are dropped right away.
+    ///
+    /// Jobs at the provider stay as they are until [`Self::finish_cancel`],
+    /// so a provider refusing the cancellation leaves them untouched.
+    pub fn begin_cancel(&self, id: &str) -> Result<Cancellation, CancelError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.get_mut(id).ok_or(CancelError::NotFound)?;
+        if job.state == JobState::Cancelled {
+            return Ok(Cancellation::Cancelled { credits: job.credits });
+        }
+        if job.is_finished() {
+            return Err(CancelError::Finished);
+        }
+
+        match job.state {
+            JobState::Queued => {
+                // The dispatcher may have popped it since; then it is starting
+                let lane = self.lanes.lock().unwrap().get(&job.provider).cloned();
+                let removed = lane.is_some_and(|lane| {
+                    let mut pending = lane.pending.lock().unwrap();
+                    let before = pending.len();
+                    pending.retain(|p| p.id != id);
+                    pending.len() < before
+                });
+                if !removed {
+                    return Err(CancelError::Submitting);
+                }
+                job.state = JobState::Cancelled;
+                let (cancelled, credits) = (job.clone(), job.credits);
+                drop(jobs);
+                report_change(self.on_change.as_ref(), Some(cancelled));
+                Ok(Cancellation::Dequeued { credits })
+            }
+            JobState::Submitted => Ok(Cancellation::AtProvider {
+                provider: job.provider.clone(),
+                model: job.model.clone(),
+                request_id: job.provider_request_id.clone().unwrap_or_default(),
+                credits: job.credits,
+            }),
+            _ => Err(CancelError::Submitting),
+        }
+    }
+
+    /// Mark a job cancelled after the provider accepted the cancellation
+    pub fn finish_cancel(&self, id: &str) {
+        let cancelled = self.jobs.lock().unwrap().get_mut(id).map(|job| {
+            job.state = JobState::Cancelled;
+            self.held.lock().unwrap().remove(id);
+            job.clone()
+        });
+        report_change(self.on_change.as_ref(), cancelled);
+    }
+
+    /// Queued and running jobs per provider
+    pub fn depth(&self) -> Vec<ProviderDepth> {
+        self.release_stale();
+        let lanes = self.lanes.lock().unwrap();
+        let mut depth: Vec<ProviderDepth> = lanes
+            .iter()
+            .map(|(provider, lane)| ProviderDepth {
+                provider: provider.clone(),
+                queued: lane.pending.lock().unwrap().len(),
+                running: lane.limits.max_concurrent - lane.permits.available_permits(),
+                limits: lane.limits,
+            })
+            .collect();
+        depth.sort_by(|a, b| a.provider.cmp(&b.provider));
+        depth
+    }
+
+    /// The provider's lane, starting its dispatcher on first use
+    fn lane(&self, provider: &str) -> Arc<Lane> {
+        let mut lanes = self.lanes.lock().unwrap();
+        if let Some(lane) = lanes.get(provider) {
+            return lane.clone();
+        }
+
+        let limits = self
+            .overrides
+            .get(provider)
+            .copied()
+            .unwrap_or_else(|| ProviderLimits::for_provider(provider));
+        let lane = Arc::new(Lane {
+            limits,
+            permits: Arc::new(Semaphore::new(limits.max_concurrent)),
+            pending: Mutex::new(BinaryHeap::new()),
+            wake: Notify::new(),
+        });
+        lanes.insert(provider.to_string(), lane.clone());
+        tokio::spawn(dispatch(
+            lane.clone(),
+            self.jobs.clone(),
+            self.held.clone(),
+            Hooks {
+                on_failure: self.on_failure.clone(),
+                on_change: self.on_change.clone(),
+            },
+        ));
+        lane
+    }
+}
+
+fn report_failure(hook: Option<&FailureHook>, failed: Option<QueuedJob>) {
+    if let (Some(hook), Some(job)) = (hook, failed) {
+        hook(&job);
+    }
+}
+
+fn report_change(hook: Option<&ChangeHook>, changed: Option<QueuedJob>) {
+    if let (Some(hook), Some(job)) = (hook, changed) {
+        hook(&job);
+    }
+}
+
+/// The hooks a lane's dispatcher reports to
+#[derive(Clone)]
+struct Hooks {
+    on_failure: Option<FailureHook>,
+    on_change: Option<ChangeHook>,
+}
+
+/// Start a lane's jobs as slots and the rate limit allow
+///
+/// A job accepted by the provider keeps its slot in `held` until a status
+/// update finishes it or it is cancelled.
+async fn dispatch(lane: Arc<Lane>, jobs: Jobs, held: Held, hooks: Hooks) {
+    let mut last_start: Option<Instant> = None;
+
+    loop {
+        while lane.pending.lock().unwrap().is_empty() {
+            lane.wake.notified().await;
+        }
+
+        let permit = lane
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("lane semaphore is never closed");
+        if let Some(last) = last_start {
+            tokio::time::sleep_until((last + lane.limits.min_interval()).into()).await;
+        }
+
+        // Pop only now, so a paid job queued while we waited goes first
+        let next = lane.pending.lock().unwrap().pop();
+        let Some(next) = next else {
+            continue;
+        };
+        last_start = Some(Instant::now());
+
+        let running = jobs.lock().unwrap().get_mut(&next.id).map(|job| {
+            job.state = JobState::Running;
+            job.clone()
+        });
+        report_change(hooks.on_change.as_ref(), running);
+
+        let jobs = jobs.clone();
+        let held = held.clone();
+        let hooks = hooks.clone();
+        tokio::spawn(async move {
+            let result = next.submission.await;
+            let mut jobs = jobs.lock().unwrap();
+            let (changed, failed) = jobs.get_mut(&next.id).map(|job| {
+                match result {
+                    Ok(dispatched) => {
+                        // Polls and cancellation go to whoever accepted it
+                        if let Some(fallback) = dispatched.fallback {
+                            job.provider = fallback.provider;
+                            job.model = fallback.model;
+                        }
+                        job.apply_status(dispatched.status);
+                    }
+                    Err(e) => {
+                        tracing::warn!(job_id = %next.id, error = %e, "Generation job failed");
+                        job.state = JobState::Failed;
+                        job.error = Some(e.to_string());
+                    }
+                }
+                if !job.is_finished() {
+                    // Under the jobs lock, so an update can't finish it first
+                    held.lock().unwrap().insert(next.id.clone(), (permit, Instant::now()));
+                }
+                (job.clone(), (job.state == JobState::Failed).then(|| job.clone()))
+            }).unzip();
+            drop(jobs);
+            report_change(hooks.on_change.as_ref(), changed);
+            report_failure(hooks.on_failure.as_ref(), failed.flatten());
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn fast_limits(max_concurrent: usize) -> ProviderLimits {
+        ProviderLimits { max_concurrent, requests_per_minute: 60_000 }
+    }
+
+    fn completed(id: usize) -> JobStatus {
+        JobStatus {
+            request_id: format!("req-{}", id),
+            status: "COMPLETED".to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn wait_until_done(scheduler: &Scheduler, ids: &[String]) {
+        for _ in 0..500 {
+            let done = ids.iter().all(|id| {
+                matches!(
+                    scheduler.job(id).unwrap().state,
+                    JobState::Submitted | JobState::Failed
+                )
+            });
+            if done {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("jobs did not finish");
+    }
+
+    #[tokio::test]
+    async fn test_never_exceeds_provider_concurrency() {
+        let scheduler = Scheduler::new().with_limits("fal", fast_limits(3));
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let ids: Vec<String> = (0..20)
+            .map(|i| {
+                let running = running.clone();
+                let peak = peak.clone();
+                scheduler.enqueue(
+                    "fal",
+                    "flux-dev",
+                    "user-1",
+                    Priority::Free,
+                    0,
+                    Box::pin(async move {
+                        let now = running.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+                        peak.fetch_max(now, AtomicOrdering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(15)).await;
+                        running.fetch_sub(1, AtomicOrdering::SeqCst);
+                        Ok(completed(i).into())
+                    }),
+                )
+            })
+            .collect();
+
+        let depth = scheduler.depth();
+        assert_eq!(depth[0].provider, "fal");
+        assert!(depth[0].running <= 3);
+
+        wait_until_done(&scheduler, &ids).await;
+        assert_eq!(peak.load(AtomicOrdering::SeqCst), 3);
+        assert_eq!(scheduler.job(&ids[7]).unwrap().provider_request_id.as_deref(), Some("req-7"));
+        assert_eq!(scheduler.depth()[0].queued, 0);
+    }
+
+    #[tokio::test]
+    async fn test_paid_jobs_start_first() {
+        let scheduler = Scheduler::new().with_limits("vertex", fast_limits(1));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (release, blocked) = tokio::sync::oneshot::channel::<()>();
+
+        // Hold the only slot while the other jobs queue up
+        let first = scheduler.enqueue(
+            "vertex",
+            "veo-3",
+            "user-0",
+            Priority::Free,
+            0,
+            Box::pin(async move {
+                let _ = blocked.await;
+                Ok(completed(0).into())
+            }),
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let mut ids = vec![first];
+        for (i, priority) in [Priority::Free, Priority::Paid, Priority::Free, Priority::Paid]
+            .into_iter()
+            .enumerate()
+        {
+            let order = order.clone();
+            ids.push(scheduler.enqueue(
+                "vertex",
+                "veo-3",
+                "user-1",
+                priority,
+                0,
+                Box::pin(async move {
+                    order.lock().unwrap().push((i + 1, priority));
+                    Ok(completed(i + 1).into())
+                }),
+            ));
+        }
+        assert_eq!(scheduler.depth()[0].queued, 4);
+
+        release.send(()).unwrap();
+        wait_until_done(&scheduler, &ids).await;
+
+        let order = order.lock().unwrap();
+        assert_eq!(
+            *order,
+            [
+                (2, Priority::Paid),
+                (4, Priority::Paid),
+                (1, Priority::Free),
+                (3, Priority::Free)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_reports_credits_to_refund() {
+        let scheduler = Scheduler::new().with_limits("fal", fast_limits(1));
+        let (release, blocked) = tokio::sync::oneshot::channel::<()>();
+        let started = Arc::new(AtomicUsize::new(0));
+
+        let submitted = scheduler.enqueue(
+            "fal",
+            "kling-pro",
+            "user-1",
+            Priority::Free,
+            30,
+            Box::pin(async move {
+                let _ = blocked.await;
+                Ok(JobStatus {
+                    request_id: "req-0".to_string(),
+                    status: "IN_PROGRESS".to_string(),
+                    ..Default::default()
+                }
+                .into())
+            }),
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(scheduler.begin_cancel(&submitted), Err(CancelError::Submitting));
+
+        let counter = started.clone();
+        let queued = scheduler.enqueue(
+            "fal",
+            "kling-pro",
+            "user-1",
+            Priority::Free,
+            50,
+            Box::pin(async move {
+                counter.fetch_add(1, AtomicOrdering::SeqCst);
+                Ok(completed(1).into())
+            }),
+        );
+
+        // Still waiting for a slot: dropped, never reaching the provider
+        assert_eq!(scheduler.begin_cancel(&queued), Ok(Cancellation::Dequeued { credits: 50 }));
+        assert_eq!(scheduler.job(&queued).unwrap().state, JobState::Cancelled);
+        assert_eq!(scheduler.depth()[0].queued, 0);
+
+        release.send(()).unwrap();
+        wait_until_done(&scheduler, std::slice::from_ref(&submitted)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(started.load(AtomicOrdering::SeqCst), 0);
+
+        // At the provider: cancel there, then refund what was charged
+        assert_eq!(
+            scheduler.begin_cancel(&submitted),
+            Ok(Cancellation::AtProvider {
+                provider: "fal".to_string(),
+                model: "kling-pro".to_string(),
+                request_id: "req-0".to_string(),
+                credits: 30,
+            })
+        );
+        scheduler.finish_cancel(&submitted);
+        scheduler.update(&submitted, completed(0));
+        assert_eq!(scheduler.job(&submitted).unwrap().state, JobState::Cancelled);
+        // Cancelling again only repeats the refund
+        assert_eq!(scheduler.begin_cancel(&submitted), Ok(Cancellation::Cancelled { credits: 30 }));
+        assert_eq!(scheduler.begin_cancel("missing"), Err(CancelError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn test_slot_is_held_until_the_provider_finishes() {
+        let scheduler = Scheduler::new().with_limits("fal", fast_limits(1));
+        let in_progress = |id: usize| -> Submission {
+            Box::pin(async move {
+                Ok(JobStatus {
+                    request_id: format!("req-{}", id),
+                    status: "IN_PROGRESS".to_string(),
+                    ..Default::default()
+                }
+                .into())
+            })
+        };
+        let enqueue =
+            |submission| scheduler.enqueue("fal", "kling-pro", "user-1", Priority::Free, 0, submission);
+
+        let first = enqueue(in_progress(0));
+        let second = enqueue(in_progress(1));
+        let third = enqueue(in_progress(2));
+        wait_until_done(&scheduler, std::slice::from_ref(&first)).await;
+
+        // Accepted but still rendering: the next job waits for the slot
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(scheduler.job(&second).unwrap().state, JobState::Queued);
+        assert_eq!(scheduler.depth()[0].running, 1);
+
+        scheduler.update(&first, completed(0));
+        wait_until_done(&scheduler, std::slice::from_ref(&second)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(scheduler.job(&third).unwrap().state, JobState::Queued);
+
+        // Cancelling at the provider frees the slot too
+        scheduler.finish_cancel(&second);
+        wait_until_done(&scheduler, std::slice::from_ref(&third)).await;
+    }
+
+    #[tokio::test]
+    async fn test_failed_jobs_are_reported_once() {
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let seen = failures.clone();
+        let scheduler = Scheduler::new()
+            .with_limits("fal", fast_limits(2))
+            .on_failure(move |job| seen.lock().unwrap().push((job.id.clone(), job.credits)));
+
+        let rejected = scheduler.enqueue(
+            "fal",
+            "flux-dev",
+            "user-1",
+            Priority::Free,
+            10,
+            Box::pin(async { Err(anyhow::anyhow!("422 Unprocessable Entity")) }),
+        );
+        let accepted = scheduler.enqueue(
+            "fal",
+            "kling-pro",
+            "user-1",
+            Priority::Free,
+            40,
+            Box::pin(async { Ok(completed(1).into()) }),
+        );
+        wait_until_done(&scheduler, &[rejected.clone(), accepted.clone()]).await;
+        assert_eq!(*failures.lock().unwrap(), [(rejected.clone(), 10)]);
+
+        // Failed at the provider, reported by a poll and again by the webhook
+        let failed = || JobStatus {
+            request_id: "req-1".to_string(),
+            status: "COMPLETED".to_string(),
+            error: Some("NSFW content detected".to_string()),
+            ..Default::default()
+        };
+        scheduler.update(&accepted, failed());
+        scheduler.update(&accepted, failed());
+        assert_eq!(scheduler.job(&accepted).unwrap().state, JobState::Failed);
+        assert_eq!(*failures.lock().unwrap(), [(rejected, 10), (accepted, 40)]);
+    }
+
+    #[tokio::test]
+    async fn test_every_change_is_reported() {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let seen = changes.clone();
+        let scheduler = Scheduler::new()
+            .with_limits("fal", fast_limits(1))
+            .on_change(move |job| seen.lock().unwrap().push(job.state.clone()));
+
+        let id = scheduler.enqueue(
+            "fal",
+            "kling-pro",
+            "user-1",
+            Priority::Free,
+            40,
+            Box::pin(async {
+                Ok(JobStatus {
+                    request_id: "req-1".to_string(),
+                    status: "IN_QUEUE".to_string(),
+                    ..Default::default()
+                }
+                .into())
+            }),
+        );
+        wait_until_done(&scheduler, std::slice::from_ref(&id)).await;
+        scheduler.update(&id, completed(1));
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            [JobState::Queued, JobState::Running, JobState::Submitted, JobState::Submitted]
+        );
+        assert!(scheduler.job(&id).unwrap().is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_adopted_jobs_follow_provider_updates() {
+        let scheduler = Scheduler::new();
+        let stored = QueuedJob {
+            id: "job-1".to_string(),
+            user_id: "user-1".to_string(),
+            provider: "fal".to_string(),
+            model: "kling-pro".to_string(),
+            priority: Priority::Paid,
+            state: JobState::Submitted,
+            provider_request_id: Some("req-1".to_string()),
+            provider_status: Some("IN_PROGRESS".to_string()),
+            queue_position: None,
+            progress: None,
+            url: None,
+            error: None,
+            credits: 40,
+            created_at: chrono::Utc::now(),
+        };
+
+        // Queued on another instance; its webhook lands here
+        scheduler.adopt(stored.clone());
+        assert_eq!(scheduler.find_by_request("fal", "req-1").as_deref(), Some("job-1"));
+        scheduler.update("job-1", completed(1));
+        assert!(scheduler.job("job-1").unwrap().is_finished());
+
+        // What this instance already tracks wins over a stale stored copy
+        scheduler.adopt(stored);
+        assert!(scheduler.job("job-1").unwrap().is_finished());
+        assert_eq!(scheduler.begin_cancel("job-1"), Err(CancelError::Finished));
+    }
+}
@@ -0,0 +1,331 @@
+//! Per-user request rate limiting
+//!
+//! Every request takes a token from its caller's bucket: the Clerk user for
+//! authenticated requests, the client IP otherwise (`/health`, requests
+//! with a bad token). Empty buckets get `429 Too Many Requests` with a
+//! `Retry-After` header. Buckets live in memory per instance and idle ones
+//! are swept periodically.
+//!
+//! Per-route limits (sustained rate, burst):
+//!
+//! - every route: 5/s, 20 (`RATE_LIMIT_RPS`, `RATE_LIMIT_BURST`)
+//! - `/api/generate/*`: additionally 0.2/s, 5 (`GENERATE_RATE_LIMIT_RPS`,
+//!   `GENERATE_RATE_LIMIT_BURST`), since every request costs provider money
+//! - `/api/webhook/*`: not limited; Fal signs its callbacks
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::auth::JwtValidator;
+
+/// How often idle buckets are dropped
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A bucket unused for this long is full again and can be dropped
+const IDLE_BUCKET_TTL: Duration = Duration::from_secs(600);
+
+/// Sustained rate and burst of one limit
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Limit {
+    /// Tokens refilled per second
+    pub requests_per_second: f64,
+    /// Bucket size: requests allowed at once after being idle
+    pub burst: u32,
+}
+
+/// Limits by route class
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Every limited request
+    pub default: Limit,
+    /// `/api/generate/*` on top of `default`; each request costs provider money
+    pub generate: Limit,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            default: Limit { requests_per_second: 5.0, burst: 20 },
+            generate: Limit { requests_per_second: 0.2, burst: 5 },
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Load from `RATE_LIMIT_RPS`, `RATE_LIMIT_BURST`,
+    /// `GENERATE_RATE_LIMIT_RPS` and `GENERATE_RATE_LIMIT_BURST`
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.parse().ok())
+        }
+
+        let mut config = Self::default();
+        if let Some(rps) = var("RATE_LIMIT_RPS") {
+            config.default.requests_per_second = rps;
+        }
+        if let Some(burst) = var("RATE_LIMIT_BURST") {
+            config.default.burst = burst;
+        }
+        if let Some(rps) = var("GENERATE_RATE_LIMIT_RPS") {
+            config.generate.requests_per_second = rps;
+        }
+        if let Some(burst) = var("GENERATE_RATE_LIMIT_BURST") {
+            config.generate.burst = burst;
+        }
+        config
+    }
+
+    /// Limits a request to `path` is checked against
+    fn limits_for(&self, path: &str) -> Vec<(RouteClass, Limit)> {
+        if path.starts_with("/api/webhook/") {
+            Vec::new()
+        } else if path.starts_with("/api/generate/") {
+            vec![(RouteClass::Default, self.default), (RouteClass::Generate, self.generate)]
+        } else {
+            vec![(RouteClass::Default, self.default)]
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum RouteClass {
+    Default,
+    Generate,
+}
+
+/// Token bucket
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: Limit, now: Instant) -> Self {
+        Self { tokens: limit.burst as f64, updated: now }
+    }
+
+    fn refill(&mut self, limit: Limit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.requests_per_second).min(limit.burst as f64);
+        self.updated = now;
+    }
+
+    /// How long until a token is available; zero if one is now
+    fn wait(&self, limit: Limit) -> Duration {
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else if limit.requests_per_second <= 0.0 {
+            Duration::MAX
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / limit.requests_per_second)
+        }
+    }
+}
+
+/// In-memory buckets keyed by caller and route class
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    /// Tells users apart; requests without a valid token are keyed by IP
+    auth: JwtValidator,
+    buckets: Arc<Mutex<HashMap<(String, RouteClass), Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig, auth: JwtValidator) -> Self {
+        Self {
+            config,
+            auth,
+            buckets: Arc::default(),
+        }
+    }
+
+    /// Take a token from each of the caller's buckets for `path`, or say
+    /// how long to wait
+    ///
+    /// Nothing is taken unless every bucket has a token, so a request
+    /// rejected by the generate limit doesn't use up the general one.
+    fn check(&self, caller: &str, path: &str, now: Instant) -> Result<(), Duration> {
+        let limits = self.config.limits_for(path);
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let mut wait = Duration::ZERO;
+        for &(class, limit) in &limits {
+            let bucket = buckets
+                .entry((caller.to_string(), class))
+                .or_insert_with(|| Bucket::full(limit, now));
+            bucket.refill(limit, now);
+            wait = wait.max(bucket.wait(limit));
+        }
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+
+        for (class, _) in limits {
+            if let Some(bucket) = buckets.get_mut(&(caller.to_string(), class)) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop buckets nobody used for a while (they would be full anyway)
+    fn sweep(&self, now: Instant) {
+        self.buckets
+            .lock()
+            .unwrap()
+            .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < IDLE_BUCKET_TTL);
+    }
+
+    /// Sweep idle buckets every [`SWEEP_INTERVAL`] in the background
+    pub fn spawn_sweeper(&self) {
+        let limiter = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                limiter.sweep(Instant::now());
+            }
+        });
+    }
+}
+
+/// Bucket key: the authenticated user, else the client IP
+fn caller_key(auth: &JwtValidator, headers: &HeaderMap, peer: SocketAddr) -> String {
+    if let Ok(user) = auth.authenticate(headers) {
+        return format!("user:{}", user.user_id);
+    }
+
+    // Cloud Run's front end appends the client address last; earlier
+    // entries come from the client and can't be trusted
+    let forwarded = headers
+        .get("X-Forwarded-For")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit(',').next())
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty());
+
+    format!("ip:{}", forwarded.map(String::from).unwrap_or_else(|| peer.ip().to_string()))
+}
+
+#[derive(Serialize)]
+struct RateLimitedResponse {
+    error: String,
+}
+
+/// Middleware enforcing the limits; see the module docs
+pub async fn rate_limit(
+    State(limiter): State<RateLimiter>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let caller = caller_key(&limiter.auth, request.headers(), peer);
+
+    match limiter.check(&caller, request.uri().path(), Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            // Whole seconds, rounded up so a retry right on time succeeds
+            let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            tracing::warn!(caller, path = %request.uri().path(), retry_after, "Rate limited");
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(RateLimitedResponse { error: "Too many requests".to_string() }),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        let config = RateLimitConfig {
+            default: Limit { requests_per_second: 10.0, burst: 3 },
+            generate: Limit { requests_per_second: 0.5, burst: 1 },
+        };
+        RateLimiter::new(config, JwtValidator::new("", None))
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = limiter();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.check("user:a", "/api/credits", start), Ok(()));
+        }
+        assert_eq!(
+            limiter.check("user:a", "/api/credits", start),
+            Err(Duration::from_millis(100))
+        );
+        // Other callers have their own buckets
+        assert_eq!(limiter.check("ip:10.0.0.1", "/api/credits", start), Ok(()));
+
+        // One token back after 100ms at 10/s
+        let later = start + Duration::from_millis(100);
+        assert_eq!(limiter.check("user:a", "/api/credits", later), Ok(()));
+    }
+
+    #[test]
+    fn test_generate_limit() {
+        let limiter = limiter();
+        let start = Instant::now();
+
+        assert_eq!(limiter.check("user:a", "/api/generate/video", start), Ok(()));
+        assert_eq!(
+            limiter.check("user:a", "/api/generate/image", start),
+            Err(Duration::from_secs(2))
+        );
+        // The rejected request didn't use up the general bucket
+        assert_eq!(limiter.check("user:a", "/api/jobs/1", start), Ok(()));
+        assert_eq!(limiter.check("user:a", "/api/jobs/1", start), Ok(()));
+        assert!(limiter.check("user:a", "/api/jobs/1", start).is_err());
+
+        // Signed webhooks aren't limited
+        assert_eq!(limiter.check("ip:fal", "/api/webhook/fal", start), Ok(()));
+        assert!(limiter.buckets.lock().unwrap().keys().all(|(caller, _)| caller != "ip:fal"));
+    }
+
+    #[test]
+    fn test_sweep_drops_idle_buckets() {
+        let limiter = limiter();
+        let start = Instant::now();
+        limiter.check("user:idle", "/health", start).unwrap();
+        limiter.check("user:active", "/health", start + IDLE_BUCKET_TTL).unwrap();
+
+        limiter.sweep(start + IDLE_BUCKET_TTL);
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert!(buckets.contains_key(&("user:active".to_string(), RouteClass::Default)));
+    }
+
+    #[test]
+    fn test_caller_key() {
+        let auth = JwtValidator::new("", None);
+        let peer: SocketAddr = "10.0.0.7:5000".parse().unwrap();
+        assert_eq!(caller_key(&auth, &HeaderMap::new(), peer), "ip:10.0.0.7");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Forwarded-For", "1.2.3.4, 203.0.113.9".parse().unwrap());
+        assert_eq!(caller_key(&auth, &headers, peer), "ip:203.0.113.9");
+
+        // An invalid token doesn't pick a bucket
+        headers.insert("Authorization", "Bearer forged".parse().unwrap());
+        assert_eq!(caller_key(&auth, &headers, peer), "ip:203.0.113.9");
+    }
+}
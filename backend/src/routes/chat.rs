@@ -1,9 +1,13 @@
 //! Chat endpoint with streaming
+//!
+//! The response is an SSE stream: a `delta` event per chunk of text, then
+//! either a `done` event with token usage or an `error` event.
 
-use crate::{AppState, auth::ClerkAuth, providers::vertex::{ChatMessage, ChatRequest}};
+use crate::{AppState, auth::AuthUser, db::firestore::CreditError, pricing, providers::vertex::{ChatMessage, ChatRequest, ChatUsage, DEFAULT_CHAT_MODEL, SUPPORTED_CHAT_MODELS}, routes::credits::idempotency_key};
 use axum::{
     extract::State,
-    response::sse::{Event, Sse},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
     Json,
 };
 use futures::stream::Stream;
@@ -25,13 +29,52 @@ pub struct ChatResponse {
     pub credits_used: i64,
 }
 
+/// Payload of the `delta` event
+#[derive(Debug, Serialize)]
+struct DeltaEvent<'a> {
+    text: &'a str,
+}
+
+/// Payload of the terminal `done` event
+#[derive(Debug, Serialize)]
+struct DoneEvent {
+    usage: Option<ChatUsage>,
+    credits_used: i64,
+}
+
+/// Payload of the terminal `error` event
+#[derive(Debug, Serialize)]
+struct ErrorEvent {
+    message: String,
+}
+
+fn sse_event<T: Serialize>(name: &str, payload: &T) -> Event {
+    Event::default()
+        .event(name)
+        .data(serde_json::to_string(payload).unwrap_or_default())
+}
+
+/// Requested model, or the default when omitted; 400 for models outside the allowlist
+fn resolve_model(requested: Option<String>) -> Result<String, axum::http::StatusCode> {
+    match requested {
+        None => Ok(DEFAULT_CHAT_MODEL.to_string()),
+        Some(model) if SUPPORTED_CHAT_MODELS.contains(&model.as_str()) => Ok(model),
+        Some(model) => {
+            tracing::warn!("Rejected chat request for unsupported model {}", model);
+            Err(axum::http::StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
 /// Chat handler with SSE streaming
 pub async fn chat_handler(
     State(state): State<AppState>,
-    auth: ClerkAuth,
+    auth: AuthUser,
+    headers: HeaderMap,
     Json(request): Json<ClientChatRequest>,
 ) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, axum::http::StatusCode> {
     let user = auth.0;
+    let model = resolve_model(request.model)?;
     
     // Get or create user
     let db_user = state.firestore
@@ -40,19 +83,23 @@ pub async fn chat_handler(
         .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Check credits
-    let cost = 1; // 1 credit per chat
+    let cost = state.config.pricing.charge(pricing::CHAT_USD, "vertex");
     if db_user.credits < cost {
         return Err(axum::http::StatusCode::PAYMENT_REQUIRED);
     }
 
-    // Deduct credits
+    // Deduct credits; a replayed key can't replay the stream it paid for
     state.firestore
-        .deduct_credits(&user.user_id, cost, "chat")
+        .deduct_credits(&user.user_id, cost, "chat", &idempotency_key(&headers, "chat"), None)
         .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| match e {
+            CreditError::InsufficientCredits { .. } => axum::http::StatusCode::PAYMENT_REQUIRED,
+            CreditError::AlreadyApplied(_) => axum::http::StatusCode::CONFLICT,
+            CreditError::InvalidKey => axum::http::StatusCode::BAD_REQUEST,
+            _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        })?;
 
     // Build request for Vertex AI
-    let model = request.model.unwrap_or_else(|| "gemini-2.0-flash".to_string());
     let vertex_request = ChatRequest {
         messages: request.messages,
         model,
@@ -64,27 +111,62 @@ pub async fn chat_handler(
     let stream = state.vertex
         .chat_stream(vertex_request)
         .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| {
+            tracing::error!("Vertex AI chat request failed: {}", e);
+            axum::http::StatusCode::BAD_GATEWAY
+        })?;
 
-    // Convert to SSE
+    // Convert to SSE. Axum drops this stream when the client disconnects,
+    // which drops the Vertex stream and aborts the upstream request.
+    let user_id = user.user_id;
     let sse_stream = async_stream::stream! {
         use futures::StreamExt;
         let mut stream = stream;
-        
+
         while let Some(result) = stream.next().await {
             match result {
+                Ok(chunk) if chunk.done => {
+                    if let Some(usage) = &chunk.usage {
+                        tracing::info!(
+                            user_id = %user_id,
+                            prompt_tokens = usage.prompt_tokens,
+                            completion_tokens = usage.completion_tokens,
+                            credits = cost,
+                            "Chat completed"
+                        );
+                    }
+                    yield Ok(sse_event("done", &DoneEvent { usage: chunk.usage, credits_used: cost }));
+                    return;
+                }
                 Ok(chunk) => {
-                    let event = Event::default()
-                        .data(serde_json::to_string(&chunk).unwrap_or_default());
-                    yield Ok(event);
+                    yield Ok(sse_event("delta", &DeltaEvent { text: &chunk.content }));
+                }
+                Err(e) => {
+                    tracing::warn!("Vertex AI chat stream failed: {}", e);
+                    yield Ok(sse_event("error", &ErrorEvent { message: e.to_string() }));
+                    return;
                 }
-                Err(_) => break,
             }
         }
-        
-        // Send done event
-        yield Ok(Event::default().data("[DONE]"));
+
+        // Upstream ended without its final chunk
+        yield Ok(sse_event("error", &ErrorEvent { message: "Chat stream ended unexpectedly".to_string() }));
     };
 
-    Ok(Sse::new(sse_stream))
+    Ok(Sse::new(sse_stream).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_model() {
+        assert_eq!(resolve_model(Some("gemini-2.5-pro".to_string())).unwrap(), "gemini-2.5-pro");
+        assert_eq!(resolve_model(None).unwrap(), DEFAULT_CHAT_MODEL);
+        assert_eq!(
+            resolve_model(Some("gpt-4o".to_string())).unwrap_err(),
+            axum::http::StatusCode::BAD_REQUEST
+        );
+    }
 }
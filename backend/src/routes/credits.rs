@@ -1,7 +1,7 @@
 //! Credits management endpoints
 
-use crate::{AppState, auth::ClerkAuth};
-use axum::{extract::State, Json};
+use crate::{AppState, auth::AuthUser, pricing::PricingConfig};
+use axum::{extract::State, http::HeaderMap, Json};
 use serde::{Deserialize, Serialize};
 
 /// Credits balance response
@@ -9,12 +9,13 @@ use serde::{Deserialize, Serialize};
 pub struct CreditsResponse {
     pub credits: i64,
     pub user_id: String,
+    /// Rate used to convert provider costs to credits
+    pub pricing: PricingConfig,
 }
 
-/// Topup request
+/// Topup request; the credits follow from what the payment received
 #[derive(Debug, Deserialize)]
 pub struct TopupRequest {
-    pub amount: i64,
     pub payment_intent_id: String,
 }
 
@@ -25,10 +26,27 @@ pub struct TopupResponse {
     pub new_balance: i64,
 }
 
+/// Header a client sets so a retried request isn't charged twice
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// The request's idempotency key scoped to `route`, or a fresh one if it
+/// didn't send any
+///
+/// Scoping keeps one key reused across endpoints from matching the other
+/// endpoint's charge.
+pub fn idempotency_key(headers: &HeaderMap, route: &str) -> String {
+    let key = headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    format!("{}_{}", route, key)
+}
+
 /// Get current credits
 pub async fn get_credits(
     State(state): State<AppState>,
-    auth: ClerkAuth,
+    auth: AuthUser,
 ) -> Result<Json<CreditsResponse>, axum::http::StatusCode> {
     let user = auth.0;
     
@@ -40,22 +58,41 @@ pub async fn get_credits(
     Ok(Json(CreditsResponse {
         credits: db_user.credits,
         user_id: db_user.id,
+        pricing: state.config.pricing.clone(),
     }))
 }
 
 /// Handle credit topup (after Stripe payment)
+///
+/// Credits and paid priority are only granted for a payment Stripe reports
+/// as succeeded for this user.
 pub async fn topup_handler(
     State(state): State<AppState>,
-    auth: ClerkAuth,
+    auth: AuthUser,
     Json(request): Json<TopupRequest>,
 ) -> Result<Json<TopupResponse>, axum::http::StatusCode> {
     let user = auth.0;
-    
-    // TODO: Verify payment_intent_id with Stripe
-    // For now, just add credits
-    
+    let stripe = state.stripe.as_ref().ok_or(axum::http::StatusCode::SERVICE_UNAVAILABLE)?;
+
+    let intent = stripe
+        .payment_intent(&request.payment_intent_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(payment_intent = %request.payment_intent_id, error = %e, "Failed to fetch payment");
+            axum::http::StatusCode::BAD_GATEWAY
+        })?
+        .ok_or(axum::http::StatusCode::BAD_REQUEST)?;
+
+    let credits = intent.credits_for(&user.user_id, &state.config.pricing).map_err(|e| {
+        tracing::warn!(user_id = %user.user_id, error = %e, "Rejected topup");
+        axum::http::StatusCode::PAYMENT_REQUIRED
+    })?;
+    if credits <= 0 {
+        return Err(axum::http::StatusCode::BAD_REQUEST);
+    }
+
     state.firestore
-        .add_credits(&user.user_id, request.amount, &format!("topup_{}", request.payment_intent_id))
+        .add_credits(&user.user_id, credits, &format!("topup_{}", intent.id))
         .await
         .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -65,8 +102,32 @@ pub async fn topup_handler(
         .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(axum::http::StatusCode::NOT_FOUND)?;
 
+    if !db_user.paid {
+        state.firestore
+            .mark_paid(&user.user_id)
+            .await
+            .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
     Ok(Json(TopupResponse {
         success: true,
         new_balance: db_user.credits,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idempotency_key_is_scoped_per_route() {
+        let mut headers = HeaderMap::new();
+        headers.insert(IDEMPOTENCY_KEY_HEADER, "7d9e4b8e".parse().unwrap());
+        assert_eq!(idempotency_key(&headers, "image"), "image_7d9e4b8e");
+        assert_eq!(idempotency_key(&headers, "video"), "video_7d9e4b8e");
+
+        // Requests without a key are never treated as retries
+        let fresh = HeaderMap::new();
+        assert_ne!(idempotency_key(&fresh, "chat"), idempotency_key(&fresh, "chat"));
+    }
+}
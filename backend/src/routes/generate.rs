@@ -1,8 +1,22 @@
 //! Generation endpoints for image and video
 
-use crate::{AppState, auth::ClerkAuth, providers::fal::{FalImageRequest, FalVideoRequest}};
-use axum::{extract::State, Json};
+use crate::{
+    AppState,
+    auth::AuthUser,
+    db::firestore::{CreditError, FirestoreClient, User},
+    providers::{dispatch_with_fallback, Capability, GenerationProvider, ImageJob, JobKind, VideoJob},
+    queue::scheduler::{Priority, Submission},
+    routes::credits::idempotency_key,
+};
+use axum::{extract::State, http::{HeaderMap, StatusCode}, Json};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Model used when an image request doesn't name one
+const DEFAULT_IMAGE_MODEL: &str = "flux-schnell";
+
+/// Model used when a video request doesn't name one
+const DEFAULT_VIDEO_MODEL: &str = "kling-standard";
 
 /// Image generation request
 #[derive(Debug, Deserialize)]
@@ -24,7 +38,11 @@ pub struct VideoGenRequest {
 /// Generation response
 #[derive(Debug, Serialize)]
 pub struct GenerationResponse {
+    /// Queued job id; follow it at `/api/jobs/:id`, which also names the
+    /// provider that served it if a fallback did
     pub request_id: String,
+    /// Provider the job is queued for
+    pub provider: String,
     pub status: String,
     pub url: Option<String>,
     pub credits_used: i64,
@@ -36,13 +54,71 @@ pub struct ErrorResponse {
     pub error: String,
 }
 
+/// Provider serving `model`, or 400 if no registered provider does
+fn provider_for(
+    state: &AppState,
+    model: &str,
+) -> Result<Arc<dyn GenerationProvider>, (axum::http::StatusCode, Json<ErrorResponse>)> {
+    state.providers.for_model(model).ok_or_else(|| (
+        axum::http::StatusCode::BAD_REQUEST,
+        Json(ErrorResponse { error: format!("No provider available for model {}", model) })
+    ))
+}
+
+/// Charge `cost` up front for `job_id`
+///
+/// A key that was already charged means a retry: the job that charge paid
+/// for is returned (`Some`) and nothing is charged or queued again.
+async fn charge(
+    firestore: &FirestoreClient,
+    user_id: &str,
+    cost: i64,
+    reason: &str,
+    key: &str,
+    job_id: &str,
+) -> Result<Option<String>, (StatusCode, Json<ErrorResponse>)> {
+    let error = |status: StatusCode, e: CreditError| (status, Json(ErrorResponse { error: e.to_string() }));
+
+    match firestore.deduct_credits(user_id, cost, reason, key, Some(job_id)).await {
+        Ok(_) => Ok(None),
+        Err(e @ CreditError::AlreadyApplied(_)) => match firestore.charged_job(user_id, key).await {
+            Ok(Some(original)) => Ok(Some(original)),
+            Ok(None) => Err(error(StatusCode::CONFLICT, e)),
+            Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.into())),
+        },
+        Err(e @ CreditError::InsufficientCredits { .. }) => Err(error(StatusCode::PAYMENT_REQUIRED, e)),
+        Err(e @ CreditError::InvalidKey) => Err(error(StatusCode::BAD_REQUEST, e)),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+/// Response for a job queued on `provider`, also replayed for retries
+fn queued(job_id: String, provider: String, cost: i64) -> Json<GenerationResponse> {
+    Json(GenerationResponse {
+        request_id: job_id,
+        provider,
+        status: "queued".to_string(),
+        url: None,
+        credits_used: cost,
+    })
+}
+
+/// Scheduling priority for a user's jobs
+fn priority_for(user: &User) -> Priority {
+    if user.paid { Priority::Paid } else { Priority::Free }
+}
+
 /// Image generation handler
 pub async fn image_handler(
     State(state): State<AppState>,
-    auth: ClerkAuth,
+    auth: AuthUser,
+    headers: HeaderMap,
     Json(request): Json<ImageGenRequest>,
 ) -> Result<Json<GenerationResponse>, (axum::http::StatusCode, Json<ErrorResponse>)> {
     let user = auth.0;
+    let key = idempotency_key(&headers, "image");
+    let model = request.model.clone().unwrap_or_else(|| DEFAULT_IMAGE_MODEL.to_string());
+    let provider = provider_for(&state, &model)?;
     
     // Get user and check credits
     let db_user = state.firestore
@@ -53,7 +129,7 @@ pub async fn image_handler(
             Json(ErrorResponse { error: e.to_string() })
         ))?;
 
-    let cost = 5; // 5 credits per image
+    let cost = state.config.pricing.charge(provider.cost_estimate(&model, JobKind::Image), provider.name());
     if db_user.credits < cost {
         return Err((
             axum::http::StatusCode::PAYMENT_REQUIRED,
@@ -61,51 +137,48 @@ pub async fn image_handler(
         ));
     }
 
-    // Generate image
-    let fal_request = FalImageRequest {
+    // Charge before queueing; the scheduler refunds jobs that fail
+    let job_id = uuid::Uuid::new_v4().to_string();
+    if let Some(original) = charge(&state.firestore, &user.user_id, cost, "image_generation", &key, &job_id).await? {
+        return Ok(queued(original, provider.name().to_string(), cost));
+    }
+
+    let job = ImageJob {
         prompt: request.prompt,
-        model: request.model.unwrap_or_else(|| "flux-schnell".to_string()),
-        image_size: request.size,
-        num_images: Some(1),
+        model: model.clone(),
+        size: request.size,
     };
 
-    let result = state.fal
-        .generate_image(fal_request)
-        .await
-        .map_err(|e| (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e.to_string() })
-        ))?;
-
-    // Deduct credits on success
-    state.firestore
-        .deduct_credits(&user.user_id, cost, "image_generation")
+    let chain = state.config.fallbacks.chain(Capability::TextToImage, provider.name(), &model);
+    let providers = state.providers.clone();
+    let provider_name = provider.name().to_string();
+    let submission: Submission = Box::pin(async move {
+        dispatch_with_fallback(&providers, &chain, |provider, model| {
+            let job = ImageJob { model, ..job.clone() };
+            async move { provider.submit_image(job).await }
+        })
         .await
-        .map_err(|e| (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e.to_string() })
-        ))?;
-
-    // Extract URL from result
-    let url = result.output
-        .and_then(|o| o.images)
-        .and_then(|imgs| imgs.first().map(|i| i.url.clone()));
+    });
+    state.scheduler.enqueue_as(job_id.clone(), &provider_name, &model, &user.user_id, priority_for(&db_user), cost, submission);
 
-    Ok(Json(GenerationResponse {
-        request_id: result.request_id,
-        status: result.status,
-        url,
-        credits_used: cost,
-    }))
+    Ok(queued(job_id, provider_name, cost))
 }
 
 /// Video generation handler
+///
+/// Returns as soon as the job is queued; the provider's request id, queue
+/// position and progress follow on `/api/jobs/:id` as polls and webhooks
+/// report them.
 pub async fn video_handler(
     State(state): State<AppState>,
-    auth: ClerkAuth,
+    auth: AuthUser,
+    headers: HeaderMap,
     Json(request): Json<VideoGenRequest>,
 ) -> Result<Json<GenerationResponse>, (axum::http::StatusCode, Json<ErrorResponse>)> {
     let user = auth.0;
+    let key = idempotency_key(&headers, "video");
+    let model = request.model.clone().unwrap_or_else(|| DEFAULT_VIDEO_MODEL.to_string());
+    let provider = provider_for(&state, &model)?;
     
     // Get user and check credits
     let db_user = state.firestore
@@ -117,7 +190,7 @@ pub async fn video_handler(
         ))?;
 
     let duration = request.duration.unwrap_or(5.0);
-    let cost = (duration * 10.0) as i64; // 10 credits per second
+    let cost = state.config.pricing.charge(provider.cost_estimate(&model, JobKind::Video { duration }), provider.name());
     
     if db_user.credits < cost {
         return Err((
@@ -126,40 +199,31 @@ pub async fn video_handler(
         ));
     }
 
-    // Generate video
-    let fal_request = FalVideoRequest {
+    // Charge before queueing; the scheduler refunds jobs that fail
+    let job_id = uuid::Uuid::new_v4().to_string();
+    if let Some(original) = charge(&state.firestore, &user.user_id, cost, "video_generation", &key, &job_id).await? {
+        return Ok(queued(original, provider.name().to_string(), cost));
+    }
+
+    let capability = if request.image_url.is_some() { Capability::ImageToVideo } else { Capability::TextToVideo };
+    let job = VideoJob {
         prompt: request.prompt,
-        model: request.model.unwrap_or_else(|| "kling-standard".to_string()),
-        duration: Some(duration),
+        model: model.clone(),
+        duration,
         image_url: request.image_url,
     };
 
-    let result = state.fal
-        .generate_video(fal_request)
-        .await
-        .map_err(|e| (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e.to_string() })
-        ))?;
-
-    // Deduct credits on success
-    state.firestore
-        .deduct_credits(&user.user_id, cost, "video_generation")
+    let chain = state.config.fallbacks.chain(capability, provider.name(), &model);
+    let providers = state.providers.clone();
+    let provider_name = provider.name().to_string();
+    let submission: Submission = Box::pin(async move {
+        dispatch_with_fallback(&providers, &chain, |provider, model| {
+            let job = VideoJob { model, ..job.clone() };
+            async move { provider.submit_video(job).await }
+        })
         .await
-        .map_err(|e| (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse { error: e.to_string() })
-        ))?;
+    });
+    state.scheduler.enqueue_as(job_id.clone(), &provider_name, &model, &user.user_id, priority_for(&db_user), cost, submission);
 
-    // Extract URL from result
-    let url = result.output
-        .and_then(|o| o.video)
-        .map(|v| v.url);
-
-    Ok(Json(GenerationResponse {
-        request_id: result.request_id,
-        status: result.status,
-        url,
-        credits_used: cost,
-    }))
+    Ok(queued(job_id, provider_name, cost))
 }
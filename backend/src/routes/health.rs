@@ -1,7 +1,19 @@
-//! Health check endpoint
+//! Health check endpoints
+//!
+//! - `/health`: liveness, always 200 while the process is serving
+//! - `/health/ready`: readiness, 503 unless Firestore, storage and the
+//!   generation providers are all reachable
 
-use axum::Json;
+use axum::{extract::State, http::StatusCode, Json};
+use futures::future::{join_all, BoxFuture};
 use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::AppState;
+
+/// Longest a single dependency check may take before it counts as down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
 
 #[derive(Serialize)]
 pub struct HealthResponse {
@@ -9,6 +21,25 @@ pub struct HealthResponse {
     pub version: String,
 }
 
+/// State of one downstream dependency
+#[derive(Debug, Serialize)]
+pub struct DependencyStatus {
+    pub healthy: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    pub status: String,
+    pub version: String,
+    pub dependencies: BTreeMap<String, DependencyStatus>,
+}
+
+/// A named dependency check
+pub type DependencyCheck = (String, BoxFuture<'static, anyhow::Result<()>>);
+
 /// Health check handler
 pub async fn health_check() -> Json<HealthResponse> {
     Json(HealthResponse {
@@ -16,3 +47,102 @@ pub async fn health_check() -> Json<HealthResponse> {
         version: env!("CARGO_PKG_VERSION").to_string(),
     })
 }
+
+/// Readiness probe handler
+pub async fn readiness_check(
+    State(state): State<AppState>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let mut checks: Vec<DependencyCheck> = Vec::new();
+
+    let firestore = state.firestore.clone();
+    checks.push(("firestore".to_string(), Box::pin(async move { firestore.ping().await })));
+
+    let storage = state.storage.clone();
+    checks.push(("storage".to_string(), Box::pin(async move { storage.ping().await })));
+
+    for provider in state.providers.all() {
+        let name = provider.name().to_string();
+        checks.push((name, Box::pin(async move { provider.ping().await })));
+    }
+
+    let (status, response) = check_dependencies(checks).await;
+    (status, Json(response))
+}
+
+/// Run all checks concurrently; 503 if any fails or times out
+pub async fn check_dependencies(checks: Vec<DependencyCheck>) -> (StatusCode, ReadinessResponse) {
+    let results = join_all(checks.into_iter().map(|(name, check)| async move {
+        let started = Instant::now();
+        let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("timed out after {:?}", CHECK_TIMEOUT)),
+        };
+        let status = DependencyStatus {
+            healthy: result.is_ok(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            error: result.err().map(|e| e.to_string()),
+        };
+        (name, status)
+    }))
+    .await;
+
+    let dependencies: BTreeMap<String, DependencyStatus> = results.into_iter().collect();
+    let ready = dependencies.values().all(|d| d.healthy);
+    if !ready {
+        tracing::warn!(
+            down = ?dependencies.iter().filter(|(_, d)| !d.healthy).map(|(n, _)| n).collect::<Vec<_>>(),
+            "Readiness check failed"
+        );
+    }
+
+    let response = ReadinessResponse {
+        status: if ready { "ready" } else { "unavailable" }.to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        dependencies,
+    };
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &str, up: bool) -> DependencyCheck {
+        let future: BoxFuture<'static, anyhow::Result<()>> = if up {
+            Box::pin(async { Ok(()) })
+        } else {
+            Box::pin(async { anyhow::bail!("connection refused") })
+        };
+        (name.to_string(), future)
+    }
+
+    #[tokio::test]
+    async fn test_down_dependency_returns_503() {
+        let (status, response) = check_dependencies(vec![
+            check("firestore", false),
+            check("storage", true),
+            check("fal", true),
+        ])
+        .await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.status, "unavailable");
+        assert!(!response.dependencies["firestore"].healthy);
+        assert_eq!(
+            response.dependencies["firestore"].error.as_deref(),
+            Some("connection refused")
+        );
+        assert!(response.dependencies["storage"].healthy);
+        assert!(response.dependencies["fal"].error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_all_dependencies_up() {
+        let (status, response) =
+            check_dependencies(vec![check("firestore", true), check("storage", true)]).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.status, "ready");
+    }
+}
@@ -0,0 +1,510 @@
+//! Queued generation jobs and queue metrics
+//!
+//! Jobs are stored in Firestore as they change, so any instance can serve
+//! them. Each instance sends a heartbeat; jobs still waiting for a provider
+//! on an instance that stopped sending them (restarted or scaled in) were
+//! charged but will never run, so they are failed and refunded.
+
+use crate::{
+    AppState,
+    auth::AuthUser,
+    db::firestore::{FirestoreClient, StoredJob},
+    providers::ProviderRegistry,
+    queue::scheduler::{CancelError, Cancellation, JobState, ProviderDepth, QueuedJob, Scheduler},
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    Json,
+};
+use futures::stream::{Stream, StreamExt};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::time::Duration;
+
+/// How often an event stream checks the provider for progress
+const EVENT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often an instance records that it is alive and looks for orphaned jobs
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Silence after which an instance counts as gone
+const INSTANCE_TIMEOUT: chrono::Duration = chrono::Duration::minutes(10);
+
+/// Queue depth response
+#[derive(Debug, Serialize)]
+pub struct QueueDepthResponse {
+    pub providers: Vec<ProviderDepth>,
+}
+
+/// Progress event sent on `/api/jobs/:id/events`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobEvent {
+    pub job_id: String,
+    pub state: JobState,
+    /// Vendor status ("IN_QUEUE", "IN_PROGRESS", "COMPLETED", ...)
+    pub provider_status: Option<String>,
+    /// Jobs ahead at the vendor while `IN_QUEUE`
+    pub queue_position: Option<u32>,
+    /// Fraction done (0.0-1.0), when the vendor reports it
+    pub progress: Option<f32>,
+    pub url: Option<String>,
+    pub error: Option<String>,
+    /// Last event of the stream
+    pub done: bool,
+}
+
+impl From<&QueuedJob> for JobEvent {
+    fn from(job: &QueuedJob) -> Self {
+        Self {
+            job_id: job.id.clone(),
+            state: job.state.clone(),
+            provider_status: job.provider_status.clone(),
+            queue_position: job.queue_position,
+            progress: job.progress,
+            url: job.url.clone(),
+            error: job.error.clone(),
+            done: job.is_finished(),
+        }
+    }
+}
+
+/// Cancel response
+#[derive(Debug, Serialize)]
+pub struct CancelResponse {
+    pub job_id: String,
+    pub state: JobState,
+    pub credits_refunded: i64,
+}
+
+/// A job by id, loaded from the job store if another instance queued it
+pub async fn load_job(scheduler: &Scheduler, firestore: &FirestoreClient, id: &str) -> Option<QueuedJob> {
+    if let Some(job) = scheduler.job(id) {
+        return Some(job);
+    }
+    match firestore.get_job(id).await {
+        Ok(stored) => {
+            scheduler.adopt(stored?.job);
+            scheduler.job(id)
+        }
+        Err(e) => {
+            tracing::warn!(job_id = %id, error = %e, "Failed to load job");
+            None
+        }
+    }
+}
+
+/// The user's job, or 404 (also for other users' jobs)
+async fn owned_job(state: &AppState, auth: &AuthUser, id: &str) -> Result<QueuedJob, StatusCode> {
+    load_job(&state.scheduler, &state.firestore, id)
+        .await
+        .filter(|job| job.user_id == auth.0.user_id)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// The job, refreshed from the provider while its result is pending
+async fn refresh(scheduler: &Scheduler, providers: &ProviderRegistry, id: &str) -> Option<QueuedJob> {
+    let job = scheduler.job(id)?;
+
+    let pending_result = job.state == JobState::Submitted && !job.is_finished();
+    if let (true, Some(request_id)) = (pending_result, job.provider_request_id.as_deref()) {
+        if let Some(provider) = providers.get(&job.provider) {
+            match provider.poll(&job.model, request_id).await {
+                Ok(status) => scheduler.update(id, status),
+                Err(e) => tracing::warn!(job_id = %id, error = %e, "Failed to poll provider"),
+            }
+        }
+    }
+
+    scheduler.job(id)
+}
+
+/// Events for every change of a job, ending once it is finished
+pub fn job_events(
+    scheduler: Scheduler,
+    providers: ProviderRegistry,
+    id: String,
+    interval: Duration,
+) -> impl Stream<Item = JobEvent> {
+    async_stream::stream! {
+        let mut last: Option<JobEvent> = None;
+
+        while let Some(job) = refresh(&scheduler, &providers, &id).await {
+            let event = JobEvent::from(&job);
+            let done = event.done;
+            if last.as_ref() != Some(&event) {
+                last = Some(event.clone());
+                yield event;
+            }
+            if done {
+                break;
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+/// Get a job; submitted jobs are refreshed from the provider
+pub async fn get_job(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<QueuedJob>, StatusCode> {
+    owned_job(&state, &auth, &id).await?;
+    refresh(&state.scheduler, &state.providers, &id)
+        .await
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Stream a job's progress as server-sent `job` events
+pub async fn job_events_handler(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    owned_job(&state, &auth, &id).await?;
+
+    let events = job_events(state.scheduler.clone(), state.providers.clone(), id, EVENT_POLL_INTERVAL)
+        .map(|event| Ok(Event::default().event("job").json_data(&event).unwrap_or_default()));
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Cancel a job and refund the credits it was charged
+pub async fn cancel_job(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<CancelResponse>, StatusCode> {
+    owned_job(&state, &auth, &id).await?;
+
+    let refund_key = format!("refund_{}", id);
+    let credits_refunded = cancel_and_refund(&state.scheduler, &state.providers, &id, |credits| {
+        state.firestore.add_credits(&auth.0.user_id, credits, &refund_key)
+    })
+    .await?;
+
+    Ok(Json(CancelResponse {
+        job_id: id,
+        state: JobState::Cancelled,
+        credits_refunded,
+    }))
+}
+
+/// Cancel a job, then refund it with `refund`
+///
+/// A failed refund leaves the job cancelled and answers 500; cancelling it
+/// again repeats the refund, which is idempotent under `refund_{id}`, so a
+/// retry can't refund twice or be turned away unrefunded.
+async fn cancel_and_refund<F, Fut>(
+    scheduler: &Scheduler,
+    providers: &ProviderRegistry,
+    id: &str,
+    refund: F,
+) -> Result<i64, StatusCode>
+where
+    F: FnOnce(i64) -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<()>>,
+{
+    let credits = match scheduler.begin_cancel(id) {
+        Ok(Cancellation::Dequeued { credits } | Cancellation::Cancelled { credits }) => credits,
+        Ok(Cancellation::AtProvider { provider, model, request_id, credits }) => {
+            let provider = providers.get(&provider).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+            provider.cancel(&model, &request_id).await.map_err(|e| {
+                tracing::warn!(job_id = %id, error = %e, "Provider refused cancellation");
+                StatusCode::CONFLICT
+            })?;
+            scheduler.finish_cancel(id);
+            credits
+        }
+        Err(CancelError::NotFound) => return Err(StatusCode::NOT_FOUND),
+        Err(CancelError::Finished | CancelError::Submitting) => return Err(StatusCode::CONFLICT),
+    };
+
+    refund(credits).await.map_err(|e| {
+        tracing::error!(job_id = %id, error = %e, "Failed to refund a cancelled job");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(credits)
+}
+
+/// Scheduler hook refunding a failed job's charge
+///
+/// Uses the key a cancellation refunds under, so a job is refunded at most
+/// once however it ends.
+pub fn refund_failed(firestore: FirestoreClient) -> impl Fn(&QueuedJob) + Send + Sync + 'static {
+    move |job| {
+        if job.credits <= 0 {
+            return;
+        }
+        let firestore = firestore.clone();
+        let (job_id, user_id, credits) = (job.id.clone(), job.user_id.clone(), job.credits);
+        tokio::spawn(async move {
+            if let Err(e) = firestore.add_credits(&user_id, credits, &format!("refund_{}", job_id)).await {
+                tracing::error!(%job_id, %user_id, error = %e, "Failed to refund a failed generation");
+            }
+        });
+    }
+}
+
+/// Scheduler hook storing every job change, in the order they happen
+pub fn persist_jobs(firestore: FirestoreClient, instance_id: String) -> impl Fn(&QueuedJob) + Send + Sync + 'static {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<QueuedJob>();
+    tokio::spawn(async move {
+        while let Some(job) = rx.recv().await {
+            if let Err(e) = firestore.save_job(&job, &instance_id).await {
+                tracing::error!(job_id = %job.id, error = %e, "Failed to store generation job");
+            }
+        }
+    });
+    move |job| {
+        let _ = tx.send(job.clone());
+    }
+}
+
+/// Whether a job's owner has gone silent; queueing the job counts as a
+/// sign of life, in case it came before the owner's first heartbeat
+fn is_orphaned(
+    job: &QueuedJob,
+    owner_seen_at: Option<chrono::DateTime<chrono::Utc>>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> bool {
+    let last_alive = owner_seen_at.map_or(job.created_at, |seen_at| seen_at.max(job.created_at));
+    now - last_alive > INSTANCE_TIMEOUT
+}
+
+/// The job as failed by a lost instance
+fn orphan_failed(mut job: QueuedJob) -> QueuedJob {
+    job.state = JobState::Failed;
+    job.error = Some("The server handling this job restarted before it reached the provider".to_string());
+    job
+}
+
+/// Fail and refund jobs whose instance went away before submitting them
+///
+/// The refund uses the key every other refund of the job does, and the job
+/// is only marked failed once it went through, so a failed write is retried
+/// on the next run.
+pub async fn recover_orphaned_jobs(firestore: &FirestoreClient, instance_id: &str) -> anyhow::Result<usize> {
+    let now = chrono::Utc::now();
+    let mut seen_at = std::collections::HashMap::new();
+    let mut recovered = 0;
+
+    for StoredJob { job, owner } in firestore.unsubmitted_jobs().await? {
+        if owner == instance_id {
+            continue;
+        }
+        if !seen_at.contains_key(&owner) {
+            seen_at.insert(owner.clone(), firestore.instance_seen_at(&owner).await?);
+        }
+        if !is_orphaned(&job, seen_at[&owner], now) {
+            continue;
+        }
+
+        tracing::warn!(job_id = %job.id, %owner, "Refunding a job its instance never submitted");
+        if job.credits > 0 {
+            firestore.add_credits(&job.user_id, job.credits, &format!("refund_{}", job.id)).await?;
+        }
+        firestore.save_job(&orphan_failed(job), &owner).await?;
+        recovered += 1;
+    }
+
+    Ok(recovered)
+}
+
+/// Keep this instance's heartbeat fresh and recover other instances'
+/// orphaned jobs, now and every [`HEARTBEAT_INTERVAL`]
+pub fn spawn_job_recovery(firestore: FirestoreClient, instance_id: String) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = firestore.heartbeat(&instance_id).await {
+                tracing::warn!(error = %e, "Failed to record instance heartbeat");
+            }
+            match recover_orphaned_jobs(&firestore, &instance_id).await {
+                Ok(0) => {}
+                Ok(recovered) => tracing::info!(recovered, "Refunded orphaned generation jobs"),
+                Err(e) => tracing::warn!(error = %e, "Failed to recover orphaned jobs"),
+            }
+        }
+    });
+}
+
+/// Queued and running jobs per provider
+pub async fn queue_depth(State(state): State<AppState>) -> Json<QueueDepthResponse> {
+    Json(QueueDepthResponse {
+        providers: state.scheduler.depth(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{GenerationProvider, ImageJob, JobStatus, VideoJob};
+    use crate::queue::scheduler::Priority;
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+
+    /// Provider reporting a fixed sequence of statuses when polled
+    struct ScriptedProvider {
+        statuses: Mutex<Vec<&'static str>>,
+    }
+
+    fn status(request_id: &str, status: &str) -> JobStatus {
+        JobStatus {
+            request_id: request_id.to_string(),
+            status: status.to_string(),
+            url: (status == "COMPLETED").then(|| "https://fal.media/veo.mp4".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[async_trait]
+    impl GenerationProvider for ScriptedProvider {
+        fn name(&self) -> &str {
+            "fal"
+        }
+
+        fn supports(&self, _model: &str) -> bool {
+            true
+        }
+
+        async fn submit_image(&self, _job: ImageJob) -> anyhow::Result<JobStatus> {
+            unimplemented!()
+        }
+
+        async fn submit_video(&self, _job: VideoJob) -> anyhow::Result<JobStatus> {
+            unimplemented!()
+        }
+
+        async fn poll(&self, _model: &str, request_id: &str) -> anyhow::Result<JobStatus> {
+            let next = self.statuses.lock().unwrap().remove(0);
+            Ok(status(request_id, next))
+        }
+
+        async fn cancel(&self, _model: &str, _request_id: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_jobs_of_silent_instances_are_orphaned() {
+        let now = chrono::Utc::now();
+        let job = QueuedJob {
+            id: "job-1".to_string(),
+            user_id: "user-1".to_string(),
+            provider: "fal".to_string(),
+            model: "kling-pro".to_string(),
+            priority: Priority::Free,
+            state: JobState::Queued,
+            provider_request_id: None,
+            provider_status: None,
+            queue_position: None,
+            progress: None,
+            url: None,
+            error: None,
+            credits: 40,
+            created_at: now - chrono::Duration::minutes(30),
+        };
+
+        assert!(!is_orphaned(&job, Some(now - chrono::Duration::seconds(90)), now));
+        assert!(is_orphaned(&job, Some(now - chrono::Duration::minutes(15)), now));
+        assert!(is_orphaned(&job, None, now));
+
+        // Queued just now, by an instance that hasn't sent a heartbeat yet
+        let fresh = QueuedJob { created_at: now, ..job.clone() };
+        assert!(!is_orphaned(&fresh, None, now));
+
+        let failed = orphan_failed(job);
+        assert_eq!(failed.state, JobState::Failed);
+        assert!(failed.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_retry_repeats_a_failed_refund() {
+        let scheduler = Scheduler::new();
+        let mut providers = ProviderRegistry::new();
+        providers.register(Arc::new(ScriptedProvider { statuses: Mutex::new(Vec::new()) }));
+        scheduler.adopt(QueuedJob {
+            id: "job-1".to_string(),
+            user_id: "user-1".to_string(),
+            provider: "fal".to_string(),
+            model: "kling-pro".to_string(),
+            priority: Priority::Free,
+            state: JobState::Submitted,
+            provider_request_id: Some("req-1".to_string()),
+            provider_status: Some("IN_PROGRESS".to_string()),
+            queue_position: None,
+            progress: None,
+            url: None,
+            error: None,
+            credits: 40,
+            created_at: chrono::Utc::now(),
+        });
+
+        // The refund write fails after the provider cancelled the job
+        let failed = cancel_and_refund(&scheduler, &providers, "job-1", |_| async {
+            Err(anyhow::anyhow!("Firestore unavailable"))
+        })
+        .await;
+        assert_eq!(failed, Err(StatusCode::INTERNAL_SERVER_ERROR));
+        assert_eq!(scheduler.job("job-1").unwrap().state, JobState::Cancelled);
+
+        // The client retries: the refund is written instead of a 409
+        let refunded = Mutex::new(Vec::new());
+        let retried = cancel_and_refund(&scheduler, &providers, "job-1", |credits| {
+            refunded.lock().unwrap().push(credits);
+            async { Ok(()) }
+        })
+        .await;
+        assert_eq!(retried, Ok(40));
+        assert_eq!(*refunded.lock().unwrap(), [40]);
+    }
+
+    #[tokio::test]
+    async fn test_events_relay_each_provider_change() {
+        let scheduler = Scheduler::new();
+        let mut providers = ProviderRegistry::new();
+        providers.register(Arc::new(ScriptedProvider {
+            statuses: Mutex::new(vec!["IN_PROGRESS", "IN_PROGRESS", "COMPLETED"]),
+        }));
+
+        let id = scheduler.enqueue(
+            "fal",
+            "kling-pro",
+            "user-1",
+            Priority::Free,
+            40,
+            Box::pin(async { Ok(status("req-1", "IN_QUEUE").into()) }),
+        );
+        while scheduler.job(&id).unwrap().state != JobState::Submitted {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let events: Vec<JobEvent> = job_events(scheduler, providers, id.clone(), Duration::from_millis(1))
+            .collect()
+            .await;
+
+        // The repeated IN_PROGRESS poll is not sent twice
+        let statuses: Vec<_> = events.iter().map(|e| e.provider_status.as_deref()).collect();
+        assert_eq!(statuses, [Some("IN_PROGRESS"), Some("COMPLETED")]);
+        assert!(!events[0].done);
+
+        assert_eq!(
+            serde_json::to_value(&events[1]).unwrap(),
+            serde_json::json!({
+                "job_id": id,
+                "state": "submitted",
+                "provider_status": "COMPLETED",
+                "queue_position": null,
+                "progress": null,
+                "url": "https://fal.media/veo.mp4",
+                "error": null,
+                "done": true,
+            })
+        );
+    }
+}
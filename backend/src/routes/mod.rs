@@ -4,4 +4,5 @@ pub mod chat;
 pub mod credits;
 pub mod generate;
 pub mod health;
+pub mod jobs;
 pub mod webhooks;
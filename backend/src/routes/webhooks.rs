@@ -1,15 +1,87 @@
 //! Webhook handlers for Fal.ai callbacks
+//!
+//! Fal signs every callback with ED25519: `X-Fal-Webhook-Signature` is the
+//! hex signature of the request id, user id, timestamp (the matching
+//! `X-Fal-Webhook-*` headers) and hex SHA-256 of the body, joined by
+//! newlines. The public keys come from Fal's JWKS and are cached. Unsigned,
+//! tampered or stale callbacks are refused with 401, and a request id seen
+//! within the freshness window is acknowledged without being applied again.
+//! Verified callbacks finish the queued job Fal ran, so clients see the
+//! result without it being polled.
 
-use axum::Json;
+use crate::{
+    providers::{
+        fal::{FalJobStatus, FalOutput},
+        JobStatus,
+    },
+    AppState,
+};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{
+    digest,
+    signature::{UnparsedPublicKey, ED25519},
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Where Fal publishes the keys webhooks are signed with
+pub const FAL_JWKS_URL: &str = "https://rest.alpha.fal.ai/.well-known/jwks.json";
+
+const REQUEST_ID_HEADER: &str = "X-Fal-Webhook-Request-Id";
+const USER_ID_HEADER: &str = "X-Fal-Webhook-User-Id";
+/// Unix time the webhook was signed at
+const TIMESTAMP_HEADER: &str = "X-Fal-Webhook-Timestamp";
+/// Hex ED25519 signature
+const SIGNATURE_HEADER: &str = "X-Fal-Webhook-Signature";
+
+/// How far a webhook's timestamp may be from now, in seconds, before it
+/// counts as a replay
+const TIMESTAMP_TOLERANCE_SECS: i64 = 300;
+
+/// How long fetched keys are used before being fetched again
+const JWKS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Least time between refetches forced by a signature no key matches
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(60);
+
+const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Fal.ai webhook payload
 #[derive(Debug, Deserialize)]
 pub struct FalWebhookPayload {
     pub request_id: String,
+    /// "OK" or "ERROR"
     pub status: String,
-    pub logs: Option<Vec<String>>,
+    /// Model output, or the error detail
+    #[serde(alias = "payload")]
     pub output: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+impl FalWebhookPayload {
+    /// The finished job's status
+    fn job_status(self) -> JobStatus {
+        let status = match (self.status.as_str(), self.output) {
+            ("OK", Some(output)) => match serde_json::from_value::<FalOutput>(output) {
+                Ok(output) => FalJobStatus::Completed(output),
+                Err(e) => FalJobStatus::Failed(format!("Unreadable Fal output: {}", e)),
+            },
+            (_, output) => FalJobStatus::Failed(
+                self.error
+                    .or_else(|| output.map(|o| o.to_string()))
+                    .unwrap_or_else(|| "Fal job failed".to_string()),
+            ),
+        };
+        status.into_job_status(&self.request_id)
+    }
 }
 
 /// Webhook response
@@ -18,20 +90,346 @@ pub struct WebhookResponse {
     pub received: bool,
 }
 
+/// Why a webhook signature was rejected
+#[derive(Debug, PartialEq)]
+enum SignatureError {
+    Missing,
+    Malformed,
+    Stale,
+    Mismatch,
+}
+
+/// The signed `X-Fal-Webhook-*` headers of a callback
+#[derive(Debug)]
+struct SignedHeaders {
+    request_id: String,
+    user_id: String,
+    timestamp: String,
+    signature: String,
+}
+
+impl SignedHeaders {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| Some(headers.get(name)?.to_str().ok()?.to_string());
+        Some(Self {
+            request_id: header(REQUEST_ID_HEADER)?,
+            user_id: header(USER_ID_HEADER)?,
+            timestamp: header(TIMESTAMP_HEADER)?,
+            signature: header(SIGNATURE_HEADER)?,
+        })
+    }
+}
+
+/// Check one of `keys` signed the headers and `body`, and the timestamp is
+/// within the tolerance of `now`
+fn verify_signature(
+    keys: &[Vec<u8>],
+    signed: &SignedHeaders,
+    body: &[u8],
+    now: i64,
+) -> Result<(), SignatureError> {
+    let signed_at: i64 = signed.timestamp.parse().map_err(|_| SignatureError::Malformed)?;
+    if (now - signed_at).abs() > TIMESTAMP_TOLERANCE_SECS {
+        return Err(SignatureError::Stale);
+    }
+    let signature = decode_hex(&signed.signature).ok_or(SignatureError::Malformed)?;
+
+    let body_hash = encode_hex(digest::digest(&digest::SHA256, body).as_ref());
+    let message = [
+        signed.request_id.as_str(),
+        signed.user_id.as_str(),
+        signed.timestamp.as_str(),
+        body_hash.as_str(),
+    ]
+    .join("\n");
+
+    let valid = keys
+        .iter()
+        .any(|key| UnparsedPublicKey::new(&ED25519, key).verify(message.as_bytes(), &signature).is_ok());
+    if valid { Ok(()) } else { Err(SignatureError::Mismatch) }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct Jwk {
+    #[serde(default)]
+    kty: String,
+    #[serde(default)]
+    crv: String,
+    #[serde(default)]
+    x: String,
+}
+
+/// The ED25519 public keys in a JWKS document; other keys are skipped
+fn parse_jwks(body: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let jwks: Jwks = serde_json::from_slice(body).map_err(|e| format!("Unreadable JWKS: {}", e))?;
+    let keys: Vec<Vec<u8>> = jwks
+        .keys
+        .into_iter()
+        .filter(|key| key.kty == "OKP" && key.crv == "Ed25519")
+        .filter_map(|key| URL_SAFE_NO_PAD.decode(key.x).ok())
+        .filter(|key| key.len() == 32)
+        .collect();
+    if keys.is_empty() {
+        return Err("JWKS has no Ed25519 keys".to_string());
+    }
+    Ok(keys)
+}
+
+struct CachedKeys {
+    keys: Vec<Vec<u8>>,
+    fetched_at: Instant,
+}
+
+/// Fal's webhook keys, and the callbacks already handled
+#[derive(Clone)]
+pub struct FalWebhookVerifier {
+    jwks_url: String,
+    http_client: reqwest::Client,
+    keys: Arc<tokio::sync::Mutex<Option<CachedKeys>>>,
+    /// Signing time of each request id handled within the freshness window
+    seen: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl FalWebhookVerifier {
+    pub fn new(jwks_url: &str) -> Self {
+        Self {
+            jwks_url: jwks_url.to_string(),
+            http_client: reqwest::Client::builder()
+                .timeout(JWKS_FETCH_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            keys: Arc::new(tokio::sync::Mutex::new(None)),
+            seen: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The cached keys, fetched when missing or expired; `refresh` also
+    /// refetches keys older than [`JWKS_MIN_REFRESH`] in case Fal rotated them
+    async fn keys(&self, refresh: bool) -> Result<Vec<Vec<u8>>, String> {
+        let mut cached = self.keys.lock().await;
+        let due = match cached.as_ref() {
+            None => true,
+            Some(cached) => {
+                let age = cached.fetched_at.elapsed();
+                age > JWKS_TTL || (refresh && age > JWKS_MIN_REFRESH)
+            }
+        };
+        if due {
+            match self.fetch_keys().await {
+                Ok(keys) => {
+                    *cached = Some(CachedKeys {
+                        keys,
+                        fetched_at: Instant::now(),
+                    })
+                }
+                // Keys rotate rarely; better the old ones than refusing everything
+                Err(e) if cached.is_some() => tracing::warn!("Keeping cached Fal JWKS: {}", e),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(cached.as_ref().map(|cached| cached.keys.clone()).unwrap_or_default())
+    }
+
+    async fn fetch_keys(&self) -> Result<Vec<Vec<u8>>, String> {
+        let response = self
+            .http_client
+            .get(&self.jwks_url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Failed to fetch Fal JWKS: {}", e))?;
+        let body = response.bytes().await.map_err(|e| e.to_string())?;
+        parse_jwks(&body)
+    }
+
+    /// Verify against the cached keys, refetching once if none match
+    async fn verify(&self, signed: &SignedHeaders, body: &[u8], now: i64) -> Result<(), String> {
+        let mut verified = verify_signature(&self.keys(false).await?, signed, body, now);
+        if verified == Err(SignatureError::Mismatch) {
+            verified = verify_signature(&self.keys(true).await?, signed, body, now);
+        }
+        verified.map_err(|e| format!("{:?}", e))
+    }
+
+    /// Record a verified callback; false if its request id was already
+    /// handled within the freshness window
+    fn first_delivery(&self, request_id: &str, signed_at: i64, now: i64) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        // Older entries can't be replayed: their timestamps are stale
+        seen.retain(|_, at| now - *at <= TIMESTAMP_TOLERANCE_SECS);
+        if seen.contains_key(request_id) {
+            return false;
+        }
+        seen.insert(request_id.to_string(), signed_at);
+        true
+    }
+}
+
 /// Handle Fal.ai webhook
 pub async fn fal_webhook(
-    Json(payload): Json<FalWebhookPayload>,
-) -> Json<WebhookResponse> {
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<WebhookResponse>, StatusCode> {
+    let Some(signed) = SignedHeaders::from_headers(&headers) else {
+        tracing::warn!(reason = ?SignatureError::Missing, "Rejected Fal.ai webhook signature");
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let now = chrono::Utc::now().timestamp();
+    if let Err(e) = state.fal_webhooks.verify(&signed, &body, now).await {
+        tracing::warn!(reason = %e, "Rejected Fal.ai webhook signature");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let payload: FalWebhookPayload = serde_json::from_slice(&body).map_err(|_| StatusCode::BAD_REQUEST)?;
+
     tracing::info!(
         request_id = %payload.request_id,
         status = %payload.status,
         "Received Fal.ai webhook"
     );
 
-    // TODO: 
-    // 1. Look up the original request in database
-    // 2. Update the generation status
-    // 3. Notify the user via Pub/Sub or WebSocket
+    // The timestamp parsed during verification
+    let signed_at = signed.timestamp.parse().unwrap_or(now);
+    if !state.fal_webhooks.first_delivery(&signed.request_id, signed_at, now) {
+        tracing::info!(request_id = %signed.request_id, "Ignoring repeated Fal.ai webhook");
+        return Ok(Json(WebhookResponse { received: true }));
+    }
+
+    // Jobs queued on another instance (or before a restart) are in the store
+    let job_id = match state.scheduler.find_by_request("fal", &payload.request_id) {
+        Some(job_id) => Some(job_id),
+        None => state
+            .firestore
+            .find_job_by_request("fal", &payload.request_id)
+            .await
+            .map_err(|e| {
+                tracing::error!(request_id = %payload.request_id, error = %e, "Failed to look up job");
+                // Fal retries failed deliveries
+                StatusCode::SERVICE_UNAVAILABLE
+            })?
+            .map(|stored| {
+                let id = stored.job.id.clone();
+                state.scheduler.adopt(stored.job);
+                id
+            }),
+    };
+    match job_id {
+        Some(job_id) => state.scheduler.update(&job_id, payload.job_status()),
+        None => tracing::warn!(request_id = %payload.request_id, "Fal.ai webhook for an unknown job"),
+    }
+
+    Ok(Json(WebhookResponse { received: true }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JWKS: &str = include_str!("testdata/fal_jwks.json");
+    const BODY: &[u8] = include_bytes!("testdata/fal_webhook_body.json");
+    const SIGNED_AT: i64 = 1_760_000_000;
+
+    /// Headers Fal sent with `BODY`, signed by the key in `JWKS`
+    fn fixture() -> SignedHeaders {
+        SignedHeaders {
+            request_id: "764cabcf-b745-4b3e-ae38-1200304cf45b".into(),
+            user_id: "user_2abc".into(),
+            timestamp: SIGNED_AT.to_string(),
+            signature: "fd49bdd823839e7f9bf2ee51f77ecd9795e1bb39edf777404c01ca2e073ccb84\
+                        65cf6e55ceb53d3ad930087d9c460baca4f8fc78770843eacf9837360644e70d"
+                .into(),
+        }
+    }
+
+    #[test]
+    fn test_job_status() {
+        let payload: FalWebhookPayload = serde_json::from_str(
+            r#"{"request_id":"req-1","status":"OK","payload":{"video":{"url":"https://fal.media/v.mp4"}}}"#,
+        )
+        .unwrap();
+        let status = payload.job_status();
+        assert_eq!(status.status, "COMPLETED");
+        assert_eq!(status.url.as_deref(), Some("https://fal.media/v.mp4"));
+        assert!(status.error.is_none());
+
+        let payload: FalWebhookPayload = serde_json::from_str(
+            r#"{"request_id":"req-2","status":"ERROR","error":"Invalid status code: 422","payload":{"detail":[]}}"#,
+        )
+        .unwrap();
+        let status = payload.job_status();
+        assert_eq!(status.status, "FAILED");
+        assert_eq!(status.error.as_deref(), Some("Invalid status code: 422"));
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let keys = parse_jwks(JWKS.as_bytes()).unwrap();
+        let signed = fixture();
+
+        assert_eq!(verify_signature(&keys, &signed, BODY, SIGNED_AT), Ok(()));
+        // Allowed clock skew
+        assert_eq!(verify_signature(&keys, &signed, BODY, SIGNED_AT + 60), Ok(()));
+
+        let tampered = String::from_utf8(BODY.to_vec()).unwrap().replace("\"OK\"", "\"ERROR\"");
+        assert_eq!(
+            verify_signature(&keys, &signed, tampered.as_bytes(), SIGNED_AT),
+            Err(SignatureError::Mismatch)
+        );
+        let other_job = SignedHeaders { request_id: "another-request".into(), ..fixture() };
+        assert_eq!(verify_signature(&keys, &other_job, BODY, SIGNED_AT), Err(SignatureError::Mismatch));
+        let other_key = vec![vec![7u8; 32]];
+        assert_eq!(verify_signature(&other_key, &signed, BODY, SIGNED_AT), Err(SignatureError::Mismatch));
+
+        // A captured webhook replayed later
+        assert_eq!(
+            verify_signature(&keys, &signed, BODY, SIGNED_AT + TIMESTAMP_TOLERANCE_SECS + 1),
+            Err(SignatureError::Stale)
+        );
+        let undated = SignedHeaders { timestamp: "yesterday".into(), ..fixture() };
+        assert_eq!(verify_signature(&keys, &undated, BODY, SIGNED_AT), Err(SignatureError::Malformed));
+        let unsigned = SignedHeaders { signature: "not-hex".into(), ..fixture() };
+        assert_eq!(verify_signature(&keys, &unsigned, BODY, SIGNED_AT), Err(SignatureError::Malformed));
+    }
+
+    #[test]
+    fn test_parse_jwks_keeps_ed25519_keys() {
+        let keys = parse_jwks(
+            br#"{"keys":[{"kty":"RSA","n":"abc","e":"AQAB"},{"kty":"OKP","crv":"Ed25519","x":"tZauSt3dfDUSyeIiNFD96Kr6SfNAhcb9QEnYDsC989A"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(keys.len(), 1);
+        assert!(parse_jwks(br#"{"keys":[]}"#).is_err());
+    }
+
+    #[test]
+    fn test_repeated_request_id_is_ignored_within_window() {
+        let verifier = FalWebhookVerifier::new(FAL_JWKS_URL);
+        assert!(verifier.first_delivery("req-1", SIGNED_AT, SIGNED_AT));
+        assert!(!verifier.first_delivery("req-1", SIGNED_AT + 30, SIGNED_AT + 30));
+        assert!(verifier.first_delivery("req-2", SIGNED_AT, SIGNED_AT + 30));
 
-    Json(WebhookResponse { received: true })
+        // Once the window has passed the entry is forgotten
+        let later = SIGNED_AT + TIMESTAMP_TOLERANCE_SECS + 1;
+        assert!(verifier.first_delivery("req-1", later, later));
+    }
 }
@@ -2,10 +2,35 @@
 //!
 //! Defines executable actions and the action executor.
 
+use base64::{engine::general_purpose::STANDARD, Engine as _};
 use serde::{Deserialize, Serialize};
 use specta::Type;
-
-use crate::ai::workflow_generator::{generate_workflow, WorkflowRequest, WorkflowType};
+use std::time::Instant;
+
+use crate::ai::comfyui::{determine_execution_path, ExecutionPath, WorkflowTarget};
+use crate::ai::comfyui_client::{get_client, MAX_UPLOAD_BYTES};
+use crate::ai::context::UserPreferences;
+use crate::ai::cost::{estimate_image_cost, estimate_video_cost, usd_to_credits, CostCalculator};
+use crate::ai::elevenlabs_client::{ElevenLabsClient, TtsRequest};
+use crate::ai::generation_queue::{generation_queue, GenerationLane, DEFAULT_PRIORITY};
+use crate::ai::model_schema::validate_request_for_model;
+use crate::ai::model_selection::resolve_model;
+use crate::ai::models::ModelCapability;
+use crate::ai::resolution::{
+    image_size, validate_dimensions, validate_mask_size, ResolutionPreset,
+};
+use crate::ai::workflow_generator::{
+    default_batch_size, generate_edit_workflow, generate_workflow, ControlNetConfig, EditRequest,
+    GeneratedWorkflow, WorkflowRequest, WorkflowType, DEFAULT_EDIT_STRENGTH,
+};
+use crate::ai::AgentError;
+use crate::comfyui::output::execute_and_ingest;
+use crate::request_log::{self, RequestLogEntry};
+use crate::telemetry::{self, GenerationEvent};
+use crate::vault::script_patch::ScriptPatch;
+
+/// Output size for video generation
+const DEFAULT_VIDEO_PRESET: ResolutionPreset = ResolutionPreset::Hd720;
 
 // ═══════════════════════════════════════════════════════════════════════════════
 // ACTION TYPES
@@ -21,8 +46,58 @@ pub enum AgentAction {
         model: String,
         width: u32,
         height: u32,
+        /// Named output size; overrides `width`/`height` when set
+        #[serde(default)]
+        preset: Option<ResolutionPreset>,
         /// Token IDs to include for consistency
         token_ids: Vec<String>,
+        /// ControlNet conditioning from a reference image
+        #[serde(default)]
+        control: Option<ControlNetConfig>,
+        /// Fixed seed to reproduce an earlier image; random when omitted
+        #[serde(default)]
+        #[specta(type = Option<f64>)]
+        seed: Option<i64>,
+        /// Images generated in one execution
+        #[serde(default = "default_batch_size")]
+        batch_size: u32,
+        /// Starting image: a file in ComfyUI's input folder, a URL, or base64
+        #[serde(default)]
+        input_image: Option<String>,
+    },
+
+    /// Edit an existing image (inpainting with a mask, or a whole-image instruction)
+    EditImage {
+        prompt: String,
+        /// Image to edit (file name in ComfyUI's input folder)
+        source_image: String,
+        /// White areas are repainted; must match the source size
+        #[serde(default)]
+        mask: Option<String>,
+        /// Denoise strength, 0.0-1.0 (defaults to a full repaint)
+        #[serde(default)]
+        strength: Option<f32>,
+        /// "flux-fill" (local, masked) or "flux-kontext" (cloud, whole image)
+        model: String,
+        /// Project the edited image is ingested into
+        #[serde(default)]
+        project_id: Option<String>,
+    },
+
+    /// Remove something from an image (mask) or extend its borders (no mask)
+    FillImage {
+        prompt: String,
+        /// Image to fill: a project asset file, or a file in ComfyUI's input folder
+        source_image: String,
+        /// White areas are repainted; must match the source size and be the
+        /// same kind of file as the source
+        #[serde(default)]
+        mask: Option<String>,
+        /// Pixels added on each side when outpainting
+        #[serde(default)]
+        expand_pixels: Option<u32>,
+        /// "flux-fill" (cloud) or a local checkpoint such as "sdxl"
+        model: String,
     },
 
     /// Generate a video
@@ -30,7 +105,7 @@ pub enum AgentAction {
         prompt: String,
         model: String,
         duration_seconds: f32,
-        /// Reference image path
+        /// Reference image: a file in ComfyUI's input folder, or base64 to upload
         reference_image: Option<String>,
         token_ids: Vec<String>,
     },
@@ -40,6 +115,9 @@ pub enum AgentAction {
         audio_type: AudioActionType,
         model: String,
         duration_seconds: Option<f32>,
+        /// ElevenLabs voice for `Voice` audio (defaults to `ELEVENLABS_VOICE_ID`)
+        #[serde(default)]
+        voice_id: Option<String>,
     },
 
     /// Generate 3D asset
@@ -101,6 +179,28 @@ pub enum AgentAction {
     ExecuteWorkflow { workflow_json: String },
 }
 
+impl AgentAction {
+    /// Snake-case name used as `ActionResult::action_type`
+    pub fn action_type(&self) -> &'static str {
+        match self {
+            AgentAction::GenerateImage { .. } => "generate_image",
+            AgentAction::EditImage { .. } => "edit_image",
+            AgentAction::FillImage { .. } => "fill_image",
+            AgentAction::GenerateVideo { .. } => "generate_video",
+            AgentAction::GenerateAudio { .. } => "generate_audio",
+            AgentAction::Generate3D { .. } => "generate_3d",
+            AgentAction::SegmentAsset { .. } => "segment_asset",
+            AgentAction::ApplyColorGrade { .. } => "apply_color_grade",
+            AgentAction::UpdateScript { .. } => "update_script",
+            AgentAction::AddToCanvas { .. } => "add_to_canvas",
+            AgentAction::UpdateVault { .. } => "update_vault",
+            AgentAction::Delegate { .. } => "delegate",
+            AgentAction::ShowMessage { .. } => "show_message",
+            AgentAction::ExecuteWorkflow { .. } => "execute_workflow",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
 pub enum AudioActionType {
     Voice,
@@ -149,6 +249,15 @@ pub struct ActionResult {
     pub error: Option<String>,
     /// Estimated cost in credits
     pub credits_used: Option<f32>,
+    /// Credits the action was estimated to cost before running
+    #[serde(default)]
+    pub estimated_credits: Option<f32>,
+    /// The user's `max_credits_per_request` at execution time
+    #[serde(default)]
+    pub credit_cap: Option<f32>,
+    /// Blocked by the credit cap; re-run with confirmation to proceed
+    #[serde(default)]
+    pub requires_confirmation: bool,
 }
 
 impl ActionResult {
@@ -160,6 +269,9 @@ impl ActionResult {
             data: None,
             error: None,
             credits_used: None,
+            estimated_credits: None,
+            credit_cap: None,
+            requires_confirmation: false,
         }
     }
 
@@ -171,6 +283,25 @@ impl ActionResult {
             data: None,
             error: Some(error.into()),
             credits_used: None,
+            estimated_credits: None,
+            credit_cap: None,
+            requires_confirmation: false,
+        }
+    }
+
+    /// An action refused because its estimate exceeds the user's credit cap
+    pub fn over_credit_cap(action_type: &str, estimated: f32, cap: f32) -> Self {
+        Self {
+            estimated_credits: Some(estimated),
+            credit_cap: Some(cap),
+            requires_confirmation: true,
+            ..Self::error(
+                action_type,
+                &format!(
+                    "Insufficient credit limit: this action needs ~{:.1} credits but your limit is {:.1} per request",
+                    estimated, cap
+                ),
+            )
         }
     }
 
@@ -198,16 +329,244 @@ impl ActionResult {
 pub struct ActionExecutor;
 
 impl ActionExecutor {
+    /// Estimated credits for an action (0 for free/local actions)
+    pub fn estimate_credits(action: &AgentAction) -> f32 {
+        match action {
+            AgentAction::GenerateImage {
+                model,
+                width,
+                height,
+                preset,
+                batch_size,
+                ..
+            } => {
+                let (width, height) = preset.map_or((*width, *height), |p| p.dimensions());
+                estimate_image_cost(model, width, height) * *batch_size as f32
+            }
+            AgentAction::EditImage { model, .. } | AgentAction::FillImage { model, .. } => {
+                estimate_image_cost(model, 1024, 1024)
+            }
+            AgentAction::GenerateVideo {
+                model,
+                duration_seconds,
+                ..
+            } => estimate_video_cost(model, *duration_seconds),
+            AgentAction::GenerateAudio {
+                prompt,
+                audio_type: AudioActionType::Voice,
+                model,
+                ..
+            } if model.starts_with("eleven") => {
+                CostCalculator::estimate_tts(model, prompt.chars().count()).credits
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Block the action if its estimate exceeds `max_credits` (a cap of 0 means no cap)
+    pub fn check_credit_cap(
+        action: &AgentAction,
+        max_credits: Option<f32>,
+    ) -> Option<ActionResult> {
+        let cap = max_credits.filter(|cap| *cap > 0.0)?;
+        let estimated = Self::estimate_credits(action);
+
+        (estimated > cap).then(|| {
+            tracing::warn!(
+                "Blocked {} action: ~{:.1} credits exceeds cap of {:.1}",
+                action.action_type(),
+                estimated,
+                cap
+            );
+            ActionResult::over_credit_cap(action.action_type(), estimated, cap)
+        })
+    }
+
+    /// Replace an "auto" model with the best one for the action within `prefs`
+    pub fn select_model(
+        mut action: AgentAction,
+        prefs: &UserPreferences,
+    ) -> Result<AgentAction, AgentError> {
+        let selected = match &mut action {
+            AgentAction::GenerateImage {
+                model, input_image, ..
+            } => Some((
+                model,
+                if input_image.is_some() {
+                    ModelCapability::ImageToImage
+                } else {
+                    ModelCapability::TextToImage
+                },
+            )),
+            AgentAction::EditImage { model, .. } | AgentAction::FillImage { model, .. } => {
+                Some((model, ModelCapability::ImageToImage))
+            }
+            AgentAction::GenerateVideo {
+                model,
+                reference_image,
+                ..
+            } => Some((
+                model,
+                if reference_image.is_some() {
+                    ModelCapability::ImageToVideo
+                } else {
+                    ModelCapability::TextToVideo
+                },
+            )),
+            _ => None,
+        };
+        let Some((model, capability)) = selected else {
+            return Ok(action);
+        };
+
+        *model = resolve_model(Some(model.as_str()), model, capability, prefs)?;
+        Ok(action)
+    }
+
+    /// Execute an action unless it exceeds the user's `max_credits_per_request`
+    ///
+    /// `confirmed` lets the user explicitly run an action that was blocked.
+    /// An "auto" model is picked within the cap, and the action is blocked if
+    /// none fits.
+    pub async fn execute_with_cap(
+        action: AgentAction,
+        max_credits: Option<f32>,
+        confirmed: bool,
+    ) -> ActionResult {
+        let action_type = action.action_type();
+        let prefs = UserPreferences {
+            max_credits_per_request: max_credits.filter(|_| !confirmed).unwrap_or(0.0),
+            ..UserPreferences::default()
+        };
+        let action = match Self::select_model(action, &prefs) {
+            Ok(action) => action,
+            Err(AgentError::InsufficientCredits { needed, available }) => {
+                return ActionResult::over_credit_cap(action_type, needed, available)
+            }
+            Err(e) => return ActionResult::error(action_type, &e.to_string()),
+        };
+
+        if !confirmed {
+            if let Some(blocked) = Self::check_credit_cap(&action, max_credits) {
+                return blocked;
+            }
+        }
+
+        let estimated = Self::estimate_credits(&action);
+        let mut result = Self::execute(action).await;
+        result.estimated_credits = Some(estimated);
+        result.credit_cap = max_credits.filter(|cap| *cap > 0.0);
+        result
+    }
+
     /// Execute an action and return the result
     pub async fn execute(action: AgentAction) -> ActionResult {
+        let action_type = action.action_type();
+        let action = match Self::select_model(action, &UserPreferences::default()) {
+            Ok(action) => action,
+            Err(e) => return ActionResult::error(action_type, &e.to_string()),
+        };
+        let estimated = Self::estimate_credits(&action);
+
         match action {
             AgentAction::GenerateImage {
                 prompt,
                 model,
                 width,
                 height,
-                token_ids,
-            } => Self::execute_generate_image(prompt, model, width, height, token_ids).await,
+                preset,
+                token_ids: _,
+                control,
+                seed,
+                batch_size,
+                input_image,
+            } => {
+                let (width, height) = preset.map_or((width, height), |p| p.dimensions());
+                // Check the size the workflow will actually use, not the raw request
+                let size = validate_dimensions(&model, width, height);
+                let params = serde_json::json!({
+                    "prompt": prompt,
+                    "width": size.width,
+                    "height": size.height,
+                });
+                if let Err(e) = validate_request_for_model(&model, &params) {
+                    return ActionResult::error("generate_image", &e);
+                }
+                let local = runs_locally("image", &model);
+                let input_image = match stage_input_image(input_image, local).await {
+                    Ok(image) => image,
+                    Err(e) => return ActionResult::error("generate_image", &e),
+                };
+
+                let request = WorkflowRequest {
+                    workflow_type: if input_image.is_some() {
+                        WorkflowType::ImageToImage
+                    } else {
+                        WorkflowType::TextToImage
+                    },
+                    prompt,
+                    negative_prompt: None,
+                    model: model.clone(),
+                    width,
+                    height,
+                    steps: None,
+                    seed,
+                    batch_size,
+                    input_image,
+                    force_local: Some(local),
+                    control,
+                    mask_image: None,
+                    expand_pixels: None,
+                    params: None,
+                };
+
+                let started = Instant::now();
+                let result = Self::execute_image_workflow(&request).await;
+                Self::record_outcome(model, estimated, started, &result).await;
+                result
+            }
+
+            AgentAction::EditImage {
+                prompt,
+                source_image,
+                mask,
+                strength,
+                model,
+                project_id,
+            } => {
+                let started = Instant::now();
+                let result = Self::execute_edit_image(
+                    prompt,
+                    source_image,
+                    mask,
+                    strength.unwrap_or(DEFAULT_EDIT_STRENGTH),
+                    model.clone(),
+                    project_id,
+                )
+                .await;
+                Self::record_outcome(model, estimated, started, &result).await;
+                result
+            }
+
+            AgentAction::FillImage {
+                prompt,
+                source_image,
+                mask,
+                expand_pixels,
+                model,
+            } => {
+                let started = Instant::now();
+                let result = Self::execute_fill_image(
+                    prompt,
+                    source_image,
+                    mask,
+                    expand_pixels,
+                    model.clone(),
+                )
+                .await;
+                Self::record_outcome(model, estimated, started, &result).await;
+                result
+            }
 
             AgentAction::GenerateVideo {
                 prompt,
@@ -216,14 +575,31 @@ impl ActionExecutor {
                 reference_image,
                 token_ids,
             } => {
-                Self::execute_generate_video(
+                let params = serde_json::json!({
+                    "prompt": prompt,
+                    "duration_seconds": duration_seconds,
+                    "image": reference_image,
+                });
+                if let Err(e) = validate_request_for_model(&model, &params) {
+                    return ActionResult::error("generate_video", &e);
+                }
+                // Video always runs in the cloud, which takes the image inline
+                let reference_image = match stage_input_image(reference_image, false).await {
+                    Ok(image) => image,
+                    Err(e) => return ActionResult::error("generate_video", &e),
+                };
+
+                let started = Instant::now();
+                let result = Self::execute_generate_video(
                     prompt,
-                    model,
+                    model.clone(),
                     duration_seconds,
                     reference_image,
                     token_ids,
                 )
-                .await
+                .await;
+                Self::record_outcome(model, estimated, started, &result).await;
+                result
             }
 
             AgentAction::UpdateScript {
@@ -232,6 +608,14 @@ impl ActionExecutor {
                 line_start,
                 line_end,
             } => {
+                // Patches are applied server-side via `apply_script_patch`;
+                // reject malformed ranges before they reach the frontend
+                if matches!(mode, ScriptUpdateMode::Patch) {
+                    if let Err(e) = ScriptPatch::from_range(line_start, line_end, &content) {
+                        return ActionResult::error("update_script", &e);
+                    }
+                }
+
                 // Script updates are handled by the frontend
                 // Return the data for the frontend to apply
                 ActionResult::success("update_script").with_data(serde_json::json!({
@@ -292,11 +676,25 @@ impl ActionExecutor {
                 Self::execute_comfyui_workflow(workflow_json).await
             }
 
+            AgentAction::GenerateAudio {
+                prompt,
+                audio_type: AudioActionType::Voice,
+                model,
+                voice_id,
+                ..
+            } if model.starts_with("eleven") => {
+                let started = Instant::now();
+                let result = Self::execute_generate_voice(prompt, model.clone(), voice_id).await;
+                Self::record_outcome(model, estimated, started, &result).await;
+                result
+            }
+
             AgentAction::GenerateAudio {
                 prompt,
                 audio_type,
                 model,
                 duration_seconds,
+                ..
             } => {
                 // Audio generation placeholder
                 ActionResult::success("generate_audio").with_data(serde_json::json!({
@@ -332,28 +730,35 @@ impl ActionExecutor {
         }
     }
 
-    async fn execute_generate_image(
-        prompt: String,
+    /// Record the generation outcome and its cost for opt-in local telemetry
+    async fn record_outcome(
         model: String,
-        width: u32,
-        height: u32,
-        token_ids: Vec<String>,
-    ) -> ActionResult {
-        // Create workflow request
-        let request = WorkflowRequest {
-            workflow_type: WorkflowType::TextToImage,
-            prompt: prompt.clone(),
-            negative_prompt: None,
-            model: model.clone(),
-            width,
-            height,
-            steps: None,
-            seed: None,
-            input_image: None,
-            force_local: Some(false),
-        };
+        estimated: f32,
+        started: Instant,
+        result: &ActionResult,
+    ) {
+        request_log::record(RequestLogEntry::generation(
+            &model,
+            estimated,
+            result.credits_used,
+            result.success,
+            started.elapsed(),
+        ))
+        .await;
+        telemetry::record_generation(GenerationEvent::new(
+            model,
+            result.success,
+            started.elapsed().as_millis() as u64,
+            false,
+        ))
+        .await;
+    }
 
-        let workflow = match generate_workflow(&request) {
+    /// Generate an image workflow and queue it locally, or hand it off for cloud execution
+    pub async fn execute_image_workflow(request: &WorkflowRequest) -> ActionResult {
+        let model = &request.model;
+
+        let workflow = match generate_workflow(request) {
             Ok(w) => w,
             Err(e) => {
                 return ActionResult::error(
@@ -363,12 +768,40 @@ impl ActionExecutor {
             }
         };
 
+        // ControlNet workflows need custom nodes and weights a plain
+        // ComfyUI install lacks
+        if workflow.is_local && request.control.is_some() {
+            let checked = match serde_json::from_str(&workflow.workflow_json) {
+                Ok(prompt) => get_client().check_requirements(&prompt).await,
+                Err(e) => Err(format!("Invalid generated workflow JSON: {}", e)),
+            };
+            if let Err(e) = checked {
+                return ActionResult::error("generate_image", &e);
+            }
+        }
+
+        let lane = if workflow.is_local {
+            GenerationLane::Local
+        } else {
+            GenerationLane::Cloud
+        };
+        generation_queue()
+            .run(
+                lane,
+                model,
+                DEFAULT_PRIORITY,
+                Self::submit_workflow(&workflow, model),
+            )
+            .await
+            .unwrap_or_else(|e| ActionResult::error("generate_image", &e))
+    }
+
+    async fn submit_workflow(workflow: &GeneratedWorkflow, model: &str) -> ActionResult {
         // If local workflow, execute via ComfyUI
         if workflow.is_local {
             use crate::comfyui::client::ComfyUIClient;
 
-            // TODO: Get host/port from config
-            let client = ComfyUIClient::new("127.0.0.1", 8188);
+            let client = ComfyUIClient::active();
 
             let workflow_json: serde_json::Value =
                 match serde_json::from_str(&workflow.workflow_json) {
@@ -384,10 +817,14 @@ impl ActionExecutor {
             match client.queue_prompt(workflow_json).await {
                 Ok(response) => ActionResult::success("generate_image")
                     .with_execution_id(response.prompt_id.clone())
-                    .with_credits(workflow.estimated_cost as f32)
+                    .with_credits(usd_to_credits(workflow.estimated_cost as f32, model))
                     .with_data(serde_json::json!({
                         "is_local": true,
                         "workflow": workflow.workflow_json,
+                        "width": workflow.width,
+                        "height": workflow.height,
+                        "seed": workflow.seed,
+                        "warnings": workflow.warnings,
                         "status": "queued",
                         "prompt_id": response.prompt_id,
                         "number": response.number
@@ -401,15 +838,202 @@ impl ActionExecutor {
             // Cloud workflow (Fal.ai / etc) - Logic pending Phase A5
             ActionResult::success("generate_image")
                 .with_execution_id(uuid::Uuid::new_v4().to_string())
-                .with_credits(workflow.estimated_cost as f32)
+                .with_credits(usd_to_credits(workflow.estimated_cost as f32, model))
                 .with_data(serde_json::json!({
                     "is_local": false,
                     "workflow": workflow.workflow_json,
+                    "width": workflow.width,
+                    "height": workflow.height,
+                    "seed": workflow.seed,
+                    "warnings": workflow.warnings,
                     "status": "pending_cloud_execution"
                 }))
         }
     }
 
+    /// Validate the inputs, build the edit workflow and run it
+    ///
+    /// Local edits with a project are run to completion and their output is
+    /// ingested as a project asset; without one they are only queued.
+    async fn execute_edit_image(
+        prompt: String,
+        source_image: String,
+        mask: Option<String>,
+        strength: f32,
+        model: String,
+        project_id: Option<String>,
+    ) -> ActionResult {
+        let (width, height) = match source_and_mask_size(&source_image, mask.as_deref()) {
+            Ok(size) => size,
+            Err(e) => return ActionResult::error("edit_image", &e),
+        };
+
+        let request = EditRequest {
+            prompt,
+            source_image,
+            mask,
+            strength,
+            model: model.clone(),
+            width,
+            height,
+            seed: None,
+        };
+        let workflow = match generate_edit_workflow(&request) {
+            Ok(w) => w,
+            Err(e) => {
+                return ActionResult::error(
+                    "edit_image",
+                    &format!("Workflow Generation Failed: {}", e),
+                )
+            }
+        };
+
+        let Some(project_id) = project_id.filter(|_| workflow.is_local) else {
+            let lane = if workflow.is_local {
+                GenerationLane::Local
+            } else {
+                GenerationLane::Cloud
+            };
+            let mut result = generation_queue()
+                .run(
+                    lane,
+                    &model,
+                    DEFAULT_PRIORITY,
+                    Self::submit_workflow(&workflow, &model),
+                )
+                .await
+                .unwrap_or_else(|e| ActionResult::error("edit_image", &e));
+            result.action_type = "edit_image".into();
+            return result;
+        };
+
+        let prompt_json: serde_json::Value = match serde_json::from_str(&workflow.workflow_json) {
+            Ok(v) => v,
+            Err(e) => {
+                return ActionResult::error(
+                    "edit_image",
+                    &format!("Invalid generated workflow JSON: {}", e),
+                )
+            }
+        };
+        let client = crate::ai::comfyui_client::get_client();
+        let ingested = generation_queue()
+            .run(
+                GenerationLane::Local,
+                &model,
+                DEFAULT_PRIORITY,
+                execute_and_ingest(&client, &project_id, prompt_json, None),
+            )
+            .await
+            .and_then(|result| result);
+
+        match ingested {
+            Ok(ingested) if ingested.result.success => ActionResult::success("edit_image")
+                .with_execution_id(ingested.result.execution_id.clone())
+                .with_credits(usd_to_credits(workflow.estimated_cost as f32, &model))
+                .with_data(serde_json::json!({
+                    "is_local": true,
+                    "width": workflow.width,
+                    "height": workflow.height,
+                    "seed": workflow.seed,
+                    "status": "completed",
+                    "assets": ingested.assets
+                })),
+            Ok(ingested) => ActionResult::error(
+                "edit_image",
+                ingested
+                    .result
+                    .error
+                    .as_deref()
+                    .unwrap_or("Edit workflow failed"),
+            ),
+            Err(e) => ActionResult::error("edit_image", &e),
+        }
+    }
+
+    async fn execute_generate_voice(
+        prompt: String,
+        model: String,
+        voice_id: Option<String>,
+    ) -> ActionResult {
+        let client = match ElevenLabsClient::new() {
+            Ok(client) => client,
+            Err(e) => return ActionResult::error("generate_audio", &e),
+        };
+
+        let request = TtsRequest::new(&prompt, voice_id, &model);
+        let synthesized = generation_queue()
+            .run(
+                GenerationLane::Cloud,
+                &model,
+                DEFAULT_PRIORITY,
+                client.synthesize(&request),
+            )
+            .await
+            .and_then(|result| result);
+        match synthesized {
+            Ok(result) => ActionResult::success("generate_audio")
+                // Cache hits are not billed again
+                .with_credits(if result.cached {
+                    0.0
+                } else {
+                    CostCalculator::estimate_tts(&model, prompt.chars().count()).credits
+                })
+                .with_data(serde_json::json!({
+                    "audio_type": AudioActionType::Voice,
+                    "model": model,
+                    "voice_id": request.voice_id,
+                    "path": result.path,
+                    "cached": result.cached
+                })),
+            Err(e) => ActionResult::error("generate_audio", &e),
+        }
+    }
+
+    /// Inpaint the masked area, or outpaint the borders when there is no mask
+    ///
+    /// Only "flux-fill" runs in the cloud; other models fill locally.
+    async fn execute_fill_image(
+        prompt: String,
+        source_image: String,
+        mask: Option<String>,
+        expand_pixels: Option<u32>,
+        model: String,
+    ) -> ActionResult {
+        let local = model != "flux-fill";
+        let (source_image, mask, (width, height)) =
+            match stage_fill_inputs(source_image, mask, local).await {
+                Ok(staged) => staged,
+                Err(e) => return ActionResult::error("fill_image", &e),
+            };
+
+        let request = WorkflowRequest {
+            workflow_type: if mask.is_some() {
+                WorkflowType::Inpaint
+            } else {
+                WorkflowType::Outpaint
+            },
+            prompt,
+            negative_prompt: None,
+            force_local: Some(local),
+            model,
+            width,
+            height,
+            steps: None,
+            seed: None,
+            batch_size: 1,
+            input_image: Some(source_image),
+            control: None,
+            mask_image: mask,
+            expand_pixels,
+            params: None,
+        };
+
+        let mut result = Self::execute_image_workflow(&request).await;
+        result.action_type = "fill_image".into();
+        result
+    }
+
     async fn execute_generate_video(
         prompt: String,
         model: String,
@@ -423,17 +1047,23 @@ impl ActionExecutor {
             WorkflowType::TextToVideo
         };
 
+        let (video_width, video_height) = DEFAULT_VIDEO_PRESET.dimensions();
         let request = WorkflowRequest {
             workflow_type,
             prompt: prompt.clone(),
             negative_prompt: None,
             model: model.clone(),
-            width: 1280,
-            height: 720,
+            width: video_width,
+            height: video_height,
             steps: None,
             seed: None,
+            batch_size: 1,
             input_image: reference_image,
             force_local: Some(false),
+            control: None,
+            mask_image: None,
+            expand_pixels: None,
+            params: None,
         };
 
         let workflow = match generate_workflow(&request) {
@@ -450,7 +1080,7 @@ impl ActionExecutor {
         if workflow.is_local {
             use crate::comfyui::client::ComfyUIClient;
 
-            let client = ComfyUIClient::new("127.0.0.1", 8188);
+            let client = ComfyUIClient::active();
 
             let workflow_json: serde_json::Value =
                 match serde_json::from_str(&workflow.workflow_json) {
@@ -466,10 +1096,14 @@ impl ActionExecutor {
             match client.queue_prompt(workflow_json).await {
                 Ok(response) => ActionResult::success("generate_video")
                     .with_execution_id(response.prompt_id.clone())
-                    .with_credits(workflow.estimated_cost as f32)
+                    .with_credits(usd_to_credits(workflow.estimated_cost as f32, &model))
                     .with_data(serde_json::json!({
                         "is_local": true,
                         "workflow": workflow.workflow_json,
+                        "width": workflow.width,
+                        "height": workflow.height,
+                        "seed": workflow.seed,
+                        "warnings": workflow.warnings,
                         "status": "queued",
                         "prompt_id": response.prompt_id,
                         "number": response.number
@@ -483,10 +1117,14 @@ impl ActionExecutor {
             // Cloud workflow
             ActionResult::success("generate_video")
                 .with_execution_id(uuid::Uuid::new_v4().to_string())
-                .with_credits(workflow.estimated_cost as f32)
+                .with_credits(usd_to_credits(workflow.estimated_cost as f32, &model))
                 .with_data(serde_json::json!({
                     "is_local": false,
                     "workflow": workflow.workflow_json,
+                    "width": workflow.width,
+                    "height": workflow.height,
+                    "seed": workflow.seed,
+                    "warnings": workflow.warnings,
                     "status": "pending_cloud_execution"
                 }))
         }
@@ -517,7 +1155,7 @@ impl ActionExecutor {
             }
         };
 
-        let client = ComfyUIClient::new("127.0.0.1", 8188);
+        let client = ComfyUIClient::active();
 
         match client.queue_prompt(workflow_value).await {
             Ok(response) => ActionResult::success("execute_workflow")
@@ -535,6 +1173,136 @@ impl ActionExecutor {
     }
 }
 
+/// Image bytes when `value` is inline base64 rather than a file name
+///
+/// Data URLs are always inline; bare strings only when they are long and
+/// decode cleanly (file names contain characters base64 doesn't use).
+fn inline_image(value: &str) -> Result<Option<Vec<u8>>, String> {
+    let encoded = match value.strip_prefix("data:") {
+        Some(rest) => rest
+            .split_once(";base64,")
+            .map(|(_, data)| data)
+            .ok_or("input image data URL is not base64")?,
+        None if value.len() > 256 => value,
+        None => return Ok(None),
+    };
+    // Base64 is 4/3 the size of the bytes it encodes
+    if encoded.len() / 4 * 3 > MAX_UPLOAD_BYTES {
+        return Err(format!(
+            "input image is larger than the {} MB upload limit",
+            MAX_UPLOAD_BYTES / (1024 * 1024)
+        ));
+    }
+
+    match STANDARD.decode(encoded.trim()) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(_) if value.starts_with("data:") => Err("input image is not valid base64".into()),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Whether a generation of `task_type` with `model` runs in local ComfyUI
+fn runs_locally(task_type: &str, model: &str) -> bool {
+    matches!(
+        determine_execution_path(task_type, model, true),
+        ExecutionPath::WorkflowPath {
+            execution_target: WorkflowTarget::Local,
+            ..
+        }
+    )
+}
+
+/// Make an input image referenceable by the workflow that runs it
+///
+/// Local runs upload inline base64 to ComfyUI and reference the stored name;
+/// cloud runs take it as a data URL. File names and URLs pass through unchanged.
+async fn stage_input_image(image: Option<String>, local: bool) -> Result<Option<String>, String> {
+    let Some(image) = image else {
+        return Ok(None);
+    };
+    let Some(bytes) = inline_image(&image)? else {
+        return Ok(Some(image));
+    };
+    stage_image_bytes(bytes, local).await.map(Some)
+}
+
+/// Upload image bytes to ComfyUI for local runs, or inline them as a data URL
+async fn stage_image_bytes(bytes: Vec<u8>, local: bool) -> Result<String, String> {
+    let (extension, mime) = if bytes.starts_with(&[0xFF, 0xD8]) {
+        ("jpg", "image/jpeg")
+    } else {
+        ("png", "image/png")
+    };
+    if !local {
+        return Ok(format!("data:{};base64,{}", mime, STANDARD.encode(&bytes)));
+    }
+
+    let filename = format!("cinemaos_input_{}.{}", uuid::Uuid::new_v4(), extension);
+    let uploaded = crate::ai::comfyui_client::get_client()
+        .upload_image(bytes, &filename, false)
+        .await?;
+    Ok(uploaded.workflow_path())
+}
+
+/// Bytes of a project asset file (`None` for names in ComfyUI's input folder)
+fn asset_file(image: &str) -> Result<Option<Vec<u8>>, String> {
+    if !std::path::Path::new(image).is_absolute() {
+        return Ok(None);
+    }
+    std::fs::read(image)
+        .map(Some)
+        .map_err(|e| format!("Cannot read image {}: {}", image, e))
+}
+
+/// Make a fill's source and mask referenceable by its workflow, with the source size
+///
+/// Asset files are staged like inline images; names in ComfyUI's input
+/// folder are used as they are.
+async fn stage_fill_inputs(
+    source: String,
+    mask: Option<String>,
+    local: bool,
+) -> Result<(String, Option<String>, (u32, u32)), String> {
+    let Some(source_bytes) = asset_file(&source)? else {
+        let size = source_and_mask_size(&source, mask.as_deref())?;
+        return Ok((source, mask, size));
+    };
+    let size = image_size(&source_bytes)
+        .ok_or_else(|| format!("{} is not a PNG or JPEG image", source))?;
+
+    let mask = match mask {
+        Some(mask) => {
+            let bytes = asset_file(&mask)?
+                .ok_or_else(|| format!("Mask {} is not a project asset like its image", mask))?;
+            let mask_size =
+                image_size(&bytes).ok_or_else(|| format!("{} is not a PNG or JPEG image", mask))?;
+            validate_mask_size(size, mask_size)?;
+            Some(stage_image_bytes(bytes, local).await?)
+        }
+        None => None,
+    };
+    Ok((stage_image_bytes(source_bytes, local).await?, mask, size))
+}
+
+/// Size of an image in ComfyUI's input folder
+fn input_image_size(file_name: &str) -> Result<(u32, u32), String> {
+    let path = crate::installer::get_comfyui_dir()
+        .join("input")
+        .join(file_name);
+    let bytes =
+        std::fs::read(&path).map_err(|e| format!("Cannot read image {}: {}", file_name, e))?;
+    image_size(&bytes).ok_or_else(|| format!("{} is not a PNG or JPEG image", file_name))
+}
+
+/// Size of a source image, checking its mask (if any) matches
+fn source_and_mask_size(source: &str, mask: Option<&str>) -> Result<(u32, u32), String> {
+    let size = input_image_size(source)?;
+    if let Some(mask) = mask {
+        validate_mask_size(size, input_image_size(mask)?)?;
+    }
+    Ok(size)
+}
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // PARSE ACTIONS FROM LLM RESPONSE
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -564,7 +1332,12 @@ pub fn parse_actions_from_response(response: &str) -> Vec<AgentAction> {
                 model: "flux-schnell".into(),
                 width: 1024,
                 height: 1024,
+                preset: None,
                 token_ids: Vec::new(),
+                control: None,
+                seed: None,
+                batch_size: 1,
+                input_image: None,
             });
         }
     }
@@ -622,6 +1395,51 @@ fn extract_script_block(text: &str) -> Option<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_credit_cap_blocks_expensive_video() {
+        let veo = AgentAction::GenerateVideo {
+            prompt: "A chase across rooftops".into(),
+            model: "veo-3.1".into(),
+            duration_seconds: 8.0,
+            reference_image: None,
+            token_ids: vec![],
+        };
+
+        let blocked = ActionExecutor::check_credit_cap(&veo, Some(50.0)).unwrap();
+        assert!(!blocked.success);
+        assert!(blocked.requires_confirmation);
+        assert_eq!(blocked.credit_cap, Some(50.0));
+        assert!(blocked.estimated_credits.unwrap() > 50.0);
+
+        // Generous or disabled caps let it through
+        assert!(ActionExecutor::check_credit_cap(&veo, Some(10_000.0)).is_none());
+        assert!(ActionExecutor::check_credit_cap(&veo, Some(0.0)).is_none());
+        assert!(ActionExecutor::check_credit_cap(&veo, None).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_auto_model_over_budget_is_blocked() {
+        let auto = AgentAction::GenerateVideo {
+            prompt: "A chase across rooftops".into(),
+            model: "auto".into(),
+            duration_seconds: 5.0,
+            reference_image: None,
+            token_ids: vec![],
+        };
+
+        // Nothing fits, so the action is refused before it is run
+        let blocked = ActionExecutor::execute_with_cap(auto.clone(), Some(0.01), false).await;
+        assert!(blocked.requires_confirmation);
+        assert_eq!(blocked.credit_cap, Some(0.01));
+        assert!(blocked.estimated_credits.unwrap() > 0.01);
+
+        let uncapped = ActionExecutor::select_model(auto, &UserPreferences::default()).unwrap();
+        match uncapped {
+            AgentAction::GenerateVideo { model, .. } => assert_ne!(model, "auto"),
+            other => panic!("unexpected action {:?}", other),
+        }
+    }
+
     #[test]
     fn test_action_result_builder() {
         let result = ActionResult::success("test")
@@ -645,4 +1463,60 @@ mod tests {
             panic!("Expected GenerateImage action");
         }
     }
+
+    #[test]
+    fn test_inline_input_image() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\x04\0\0\0\x03\0";
+        let data_url = format!("data:image/png;base64,{}", STANDARD.encode(png));
+        assert_eq!(inline_image(&data_url), Ok(Some(png.to_vec())));
+
+        // File names are left for LoadImage to resolve
+        assert_eq!(inline_image("shot_012.png"), Ok(None));
+        assert_eq!(inline_image("cinemaos/ref (1).png"), Ok(None));
+
+        assert!(inline_image("data:image/png;base64,@@@").is_err());
+        assert!(inline_image("data:image/png,rawpixels").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cloud_input_image_is_not_uploaded() {
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0].repeat(100);
+        let raw = STANDARD.encode(&jpeg);
+        let staged = stage_input_image(Some(raw.clone()), false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(staged, format!("data:image/jpeg;base64,{}", raw));
+
+        let named = stage_input_image(Some("shot_012.png".into()), false).await;
+        assert_eq!(named, Ok(Some("shot_012.png".into())));
+        assert_eq!(stage_input_image(None, false).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_cloud_fill_inlines_asset_files() {
+        let dir = std::env::temp_dir().join(format!("cinemaos_fill_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\x04\0\0\0\x03\0";
+        let source = dir.join("shot_012.png");
+        let mask = dir.join("shot_012_car.png");
+        std::fs::write(&source, png).unwrap();
+        std::fs::write(&mask, png).unwrap();
+        let path = |p: &std::path::Path| p.to_string_lossy().into_owned();
+
+        let (staged, staged_mask, size) =
+            stage_fill_inputs(path(&source), Some(path(&mask)), false)
+                .await
+                .unwrap();
+        let data_url = format!("data:image/png;base64,{}", STANDARD.encode(png));
+        assert_eq!(staged, data_url);
+        assert_eq!(staged_mask, Some(data_url));
+        assert_eq!(size, (1024, 768));
+
+        // A mask must come from the same place as its image
+        let mixed = stage_fill_inputs(path(&source), Some("mask.png".into()), false).await;
+        assert!(mixed.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,389 @@
+//! Agent Memory - What each agent remembers of past conversations
+//!
+//! Every `agent_chat_full` turn is stored in the Vault per project and agent,
+//! so the next session picks up where the last one left off. Only the last
+//! messages are kept verbatim; older ones are folded into a rolling summary
+//! (written by the LLM when a key is set, clipped excerpts otherwise).
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::sync::RwLock;
+
+use crate::ai::agent_executor::ChatMessage;
+use crate::ai::llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest};
+use crate::ai::token_extractor::llm_available;
+use crate::settings::{load_json, save_json};
+use crate::vault;
+
+/// SurrealDB table holding one record per project and agent
+const MEMORY_TABLE: &str = "agent_memory";
+
+/// Cheap model for folding old turns into the summary
+const SUMMARY_MODEL: &str = "gemini-2.5-flash-lite";
+
+/// Characters of each message kept by the offline summary
+const EXCERPT_CHARS: usize = 200;
+
+const SUMMARY_SYSTEM_PROMPT: &str =
+    "You keep the running notes of a film production conversation. \
+Merge the new messages into the existing notes. Keep decisions, established facts, names and open \
+questions; drop greetings and repetition. Reply with the notes only, as short bullet points.";
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// CONFIG
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// How much of a conversation is replayed to the agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct AgentMemoryConfig {
+    /// Messages kept verbatim; past this, the oldest half is summarized
+    pub window: u32,
+    /// Longest the rolling summary may grow, in characters
+    pub summary_max_chars: u32,
+}
+
+impl Default for AgentMemoryConfig {
+    fn default() -> Self {
+        Self {
+            window: 20,
+            summary_max_chars: 4000,
+        }
+    }
+}
+
+/// Where the memory window settings are persisted
+const MEMORY_CONFIG_FILE: &str = "agent_memory.json";
+
+static MEMORY_CONFIG: Lazy<RwLock<AgentMemoryConfig>> =
+    Lazy::new(|| RwLock::new(load_json(MEMORY_CONFIG_FILE)));
+
+/// Current memory window settings
+pub fn memory_config() -> AgentMemoryConfig {
+    MEMORY_CONFIG.read().map(|c| c.clone()).unwrap_or_default()
+}
+
+/// Replace the memory window settings (persisted across restarts)
+pub fn set_memory_config(config: AgentMemoryConfig) -> Result<(), String> {
+    if config.window < 2 {
+        return Err("The memory window must keep at least one exchange".into());
+    }
+    let mut current = MEMORY_CONFIG.write().map_err(|e| e.to_string())?;
+    save_json(MEMORY_CONFIG_FILE, &config)?;
+    *current = config;
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// One agent's memory of a project
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct AgentMemory {
+    /// Rolling summary of the messages that left the window
+    #[serde(default)]
+    pub summary: String,
+    /// Most recent messages, oldest first
+    #[serde(default)]
+    pub messages: Vec<ChatMessage>,
+}
+
+impl AgentMemory {
+    pub fn is_empty(&self) -> bool {
+        self.summary.is_empty() && self.messages.is_empty()
+    }
+
+    /// Record one exchange
+    pub fn push_turn(&mut self, user: &str, assistant: &str) {
+        self.messages.push(ChatMessage {
+            role: "user".into(),
+            content: user.to_string(),
+        });
+        self.messages.push(ChatMessage {
+            role: "assistant".into(),
+            content: assistant.to_string(),
+        });
+    }
+
+    /// Messages to fold into the summary once the window overflows
+    ///
+    /// Half the window goes at once (whole exchanges), so the summary isn't
+    /// rewritten on every turn.
+    pub fn take_overflow(&mut self, window: usize) -> Vec<ChatMessage> {
+        if self.messages.len() <= window {
+            return Vec::new();
+        }
+        let keep = (window / 2).max(2) & !1;
+        let overflow = self.messages.len().saturating_sub(keep);
+        self.messages.drain(..overflow).collect()
+    }
+
+    /// Markdown section for the agent's prompt (empty without a summary)
+    pub fn to_prompt_section(&self) -> String {
+        if self.summary.is_empty() {
+            return String::new();
+        }
+        format!("## Earlier in this conversation\n{}", self.summary)
+    }
+}
+
+/// Stored record (the memory plus its owner)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MemoryRecord {
+    project_id: String,
+    role: String,
+    summary: String,
+    messages: Vec<ChatMessage>,
+    updated_at: String,
+}
+
+/// Roles are stored as the frontend spells them, case-insensitively
+fn role_key(role: &str) -> String {
+    role.trim().to_lowercase()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// SUMMARIES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// `summary` with `messages` folded in
+async fn summarize(summary: &str, messages: &[ChatMessage], max_chars: usize) -> String {
+    if llm_available() {
+        match summarize_with_llm(summary, messages).await {
+            Ok(updated) => return clip_front(&updated, max_chars),
+            Err(e) => tracing::warn!("Memory summary failed, keeping excerpts: {}", e),
+        }
+    }
+    offline_summary(summary, messages, max_chars)
+}
+
+async fn summarize_with_llm(summary: &str, messages: &[ChatMessage]) -> Result<String, String> {
+    let transcript: Vec<String> = messages
+        .iter()
+        .map(|m| format!("{}: {}", m.role, m.content))
+        .collect();
+    let content = format!(
+        "Existing notes:\n{}\n\nNew messages:\n{}",
+        if summary.is_empty() {
+            "(none)"
+        } else {
+            summary
+        },
+        transcript.join("\n\n")
+    );
+
+    let response = get_llm_client()
+        .chat(LLMRequest {
+            provider: LLMProvider::Gemini,
+            model: SUMMARY_MODEL.to_string(),
+            messages: vec![LLMMessage {
+                role: "user".to_string(),
+                content,
+                images: Vec::new(),
+            }],
+            temperature: Some(0.2),
+            max_tokens: Some(1000),
+            system_prompt: Some(SUMMARY_SYSTEM_PROMPT.to_string()),
+        })
+        .await?;
+
+    let notes = response.content.trim();
+    if notes.is_empty() {
+        return Err("The model returned an empty summary".to_string());
+    }
+    Ok(notes.to_string())
+}
+
+/// One clipped line per message, appended to `summary`
+fn offline_summary(summary: &str, messages: &[ChatMessage], max_chars: usize) -> String {
+    let mut lines: Vec<String> = summary.lines().map(str::to_string).collect();
+    lines.extend(messages.iter().map(|m| {
+        let text = m.content.split_whitespace().collect::<Vec<_>>().join(" ");
+        let mut excerpt: String = text.chars().take(EXCERPT_CHARS).collect();
+        if text.chars().count() > EXCERPT_CHARS {
+            excerpt.push('…');
+        }
+        format!("- {}: {}", m.role, excerpt)
+    }));
+    clip_front(&lines.join("\n"), max_chars)
+}
+
+/// The last `max_chars` of `text`, cut at a line break
+fn clip_front(text: &str, max_chars: usize) -> String {
+    let count = text.chars().count();
+    if count <= max_chars {
+        return text.to_string();
+    }
+    let start = count - max_chars;
+    let tail: String = text.chars().skip(start).collect();
+    if text.chars().nth(start - 1) == Some('\n') {
+        return tail;
+    }
+    match tail.split_once('\n') {
+        Some((_, rest)) if !rest.is_empty() => rest.to_string(),
+        _ => tail,
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// STORE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// What `role` remembers of `project_id`
+pub async fn recall(project_id: &str, role: &str) -> Result<AgentMemory, String> {
+    let db = vault::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    let mut result = db
+        .query("SELECT * FROM type::thing($table, [$pid, $role])")
+        .bind(("table", MEMORY_TABLE))
+        .bind(("pid", project_id.to_string()))
+        .bind(("role", role_key(role)))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let record: Option<MemoryRecord> = result.take(0).map_err(|e| e.to_string())?;
+    Ok(record
+        .map(|r| AgentMemory {
+            summary: r.summary,
+            messages: r.messages,
+        })
+        .unwrap_or_default())
+}
+
+async fn save(project_id: &str, role: &str, memory: &AgentMemory) -> Result<(), String> {
+    let db = vault::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    db.query("UPSERT type::thing($table, [$pid, $role]) CONTENT $content")
+        .bind(("table", MEMORY_TABLE))
+        .bind(("pid", project_id.to_string()))
+        .bind(("role", role_key(role)))
+        .bind((
+            "content",
+            MemoryRecord {
+                project_id: project_id.to_string(),
+                role: role_key(role),
+                summary: memory.summary.clone(),
+                messages: memory.messages.clone(),
+                updated_at: chrono::Utc::now().to_rfc3339(),
+            },
+        ))
+        .await
+        .map_err(|e| e.to_string())?
+        .check()
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Append one exchange, summarizing whatever leaves the window
+pub async fn record_turn(
+    project_id: &str,
+    role: &str,
+    user: &str,
+    assistant: &str,
+) -> Result<AgentMemory, String> {
+    let config = memory_config();
+    let mut memory = recall(project_id, role).await?;
+    memory.push_turn(user, assistant);
+
+    let overflow = memory.take_overflow(config.window as usize);
+    if !overflow.is_empty() {
+        memory.summary = summarize(
+            &memory.summary,
+            &overflow,
+            config.summary_max_chars as usize,
+        )
+        .await;
+    }
+
+    save(project_id, role, &memory).await?;
+    Ok(memory)
+}
+
+/// Forget everything `role` remembers of `project_id`
+pub async fn clear(project_id: &str, role: &str) -> Result<(), String> {
+    let db = vault::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    db.query("DELETE type::thing($table, [$pid, $role])")
+        .bind(("table", MEMORY_TABLE))
+        .bind(("pid", project_id.to_string()))
+        .bind(("role", role_key(role)))
+        .await
+        .map_err(|e| e.to_string())?
+        .check()
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_with_turns(turns: usize) -> AgentMemory {
+        let mut memory = AgentMemory::default();
+        for i in 0..turns {
+            memory.push_turn(&format!("question {}", i), &format!("answer {}", i));
+        }
+        memory
+    }
+
+    #[test]
+    fn test_overflow_drops_oldest_exchanges() {
+        let mut memory = memory_with_turns(5);
+        assert!(memory.take_overflow(10).is_empty());
+
+        memory.push_turn("question 5", "answer 5");
+        let overflow = memory.take_overflow(10);
+        // Down to half the window, whole exchanges only
+        assert_eq!(overflow.len(), 8);
+        assert_eq!(overflow[0].content, "question 0");
+        assert_eq!(memory.messages.len(), 4);
+        assert_eq!(memory.messages[0].content, "question 4");
+        assert_eq!(memory.messages[0].role, "user");
+    }
+
+    #[test]
+    fn test_offline_summary_clips_to_the_newest() {
+        let messages = memory_with_turns(2).messages;
+        let summary = offline_summary("- user: set in 1920s Chicago", &messages, 1000);
+        assert_eq!(
+            summary,
+            "- user: set in 1920s Chicago\n- user: question 0\n- assistant: answer 0\n\
+             - user: question 1\n- assistant: answer 1"
+        );
+
+        let clipped = offline_summary(&summary, &[], 40);
+        assert_eq!(clipped, "- user: question 1\n- assistant: answer 1");
+
+        let long = ChatMessage {
+            role: "assistant".into(),
+            content: "word ".repeat(100),
+        };
+        let summary = offline_summary("", &[long], 1000);
+        assert_eq!(
+            summary.chars().count(),
+            "- assistant: ".len() + EXCERPT_CHARS + 1
+        );
+        assert!(summary.ends_with('…'));
+    }
+
+    #[test]
+    fn test_prompt_section() {
+        let mut memory = memory_with_turns(1);
+        assert!(memory.to_prompt_section().is_empty());
+        memory.summary = "- The film is a noir".into();
+        assert_eq!(
+            memory.to_prompt_section(),
+            "## Earlier in this conversation\n- The film is a noir"
+        );
+    }
+}
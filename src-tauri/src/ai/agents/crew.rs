@@ -5,6 +5,7 @@
 
 use crate::ai::agents::{
     prompts::get_system_prompt,
+    routing::{classify_with_llm, route_message, RoutingTable, MIN_CONFIDENCE},
     traits::{Agent, AgentInput, AgentOutput, AgentRole},
 };
 use std::collections::HashMap;
@@ -161,68 +162,34 @@ impl VirtualCrew {
         Ok(agent.process(input).await)
     }
 
-    /// Route a request to the best agent based on intent
-    /// This is a simple implementation - the Showrunner can do more complex routing
+    /// Route a request to the best agent using the shared routing table
     pub fn route_by_intent(&self, intent: &str) -> AgentRole {
-        let intent_lower = intent.to_lowercase();
-
-        if intent_lower.contains("image")
-            || intent_lower.contains("photo")
-            || intent_lower.contains("picture")
-            || intent_lower.contains("concept art")
-        {
-            AgentRole::PhotographyDirector
-        } else if intent_lower.contains("video")
-            || intent_lower.contains("shot")
-            || intent_lower.contains("sequence")
-            || intent_lower.contains("footage")
-        {
-            AgentRole::CameraDirector
-        } else if intent_lower.contains("script")
-            || intent_lower.contains("dialogue")
-            || intent_lower.contains("scene")
-            || intent_lower.contains("write")
-        {
-            AgentRole::Scriptwriter
-        } else if intent_lower.contains("voice")
-            || intent_lower.contains("speak")
-            || intent_lower.contains("say")
-        {
-            AgentRole::VoiceActors
-        } else if intent_lower.contains("music")
-            || intent_lower.contains("sound")
-            || intent_lower.contains("audio")
-            || intent_lower.contains("sfx")
-        {
-            AgentRole::MusicSfxDirector
-        } else if intent_lower.contains("character")
-            || intent_lower.contains("cast")
-            || intent_lower.contains("actor")
-        {
-            AgentRole::CastingDirector
-        } else if intent_lower.contains("location")
-            || intent_lower.contains("set")
-            || intent_lower.contains("prop")
-        {
-            AgentRole::ArtDirector
-        } else if intent_lower.contains("camera")
-            || intent_lower.contains("lens")
-            || intent_lower.contains("lighting")
-        {
-            AgentRole::Cinematographer
-        } else if intent_lower.contains("edit")
-            || intent_lower.contains("cut")
-            || intent_lower.contains("montage")
-        {
-            AgentRole::Editor
-        } else if intent_lower.contains("color")
-            || intent_lower.contains("grade")
-            || intent_lower.contains("lut")
-        {
-            AgentRole::Colorist
-        } else {
-            // Default to Showrunner for general/unclear requests
-            AgentRole::Showrunner
+        // Default to Showrunner for general/unclear requests
+        route_message(intent).unwrap_or(AgentRole::Showrunner)
+    }
+
+    /// `route_by_intent` with the given table instead of the shared one
+    pub fn route_with(&self, table: &RoutingTable, intent: &str) -> AgentRole {
+        table.route(intent).unwrap_or(AgentRole::Showrunner)
+    }
+
+    /// Route a request with the LLM router, falling back to the keyword table
+    /// when it is unavailable; unsure classifications go to the Showrunner
+    pub async fn route_by_intent_llm(&self, intent: &str) -> AgentRole {
+        match classify_with_llm(intent).await {
+            Ok((role, confidence)) if confidence >= MIN_CONFIDENCE => role,
+            Ok((role, confidence)) => {
+                tracing::debug!(
+                    "Low routing confidence ({:.2} for {:?}), asking the Showrunner",
+                    confidence,
+                    role
+                );
+                AgentRole::Showrunner
+            }
+            Err(e) => {
+                tracing::debug!("LLM routing unavailable, using keywords: {}", e);
+                self.route_by_intent(intent)
+            }
         }
     }
 }
@@ -253,21 +220,22 @@ mod tests {
     #[test]
     fn test_intent_routing() {
         let crew = VirtualCrew::new();
+        let table = RoutingTable::default();
 
         assert_eq!(
-            crew.route_by_intent("Generate an image of a sunset"),
+            crew.route_with(&table, "Generate an image of a sunset"),
             AgentRole::PhotographyDirector
         );
         assert_eq!(
-            crew.route_by_intent("Create a video shot of the hero walking"),
+            crew.route_with(&table, "Create a video shot of the hero walking"),
             AgentRole::CameraDirector
         );
         assert_eq!(
-            crew.route_by_intent("Write the dialogue for this scene"),
+            crew.route_with(&table, "Write the dialogue for this scene"),
             AgentRole::Scriptwriter
         );
         assert_eq!(
-            crew.route_by_intent("What should we do next?"),
+            crew.route_with(&table, "What should we do next?"),
             AgentRole::Showrunner
         );
     }
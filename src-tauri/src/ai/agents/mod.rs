@@ -12,6 +12,7 @@
 pub mod traits;
 pub mod crew;
 pub mod prompts;
+pub mod routing;
 
 pub use traits::*;
 pub use crew::*;
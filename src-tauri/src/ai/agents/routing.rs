@@ -0,0 +1,262 @@
+//! Routing Table - Keyword → agent mapping shared by every router
+//!
+//! Both `MainAgent` and `VirtualCrew::route_by_intent` classify requests with
+//! this table, so they can't disagree. Users can add domain-specific terms
+//! (e.g. "matte painting" → ArtDirector); the table is persisted to
+//! `routing_table.json` in the CinemaOS data directory.
+//!
+//! With an API key, `classify_with_llm` reads the request instead; the table
+//! stays the offline fallback and what tests route with.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::sync::RwLock;
+
+use super::traits::AgentRole;
+use crate::ai::llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest};
+use crate::ai::structured_output::extract_json;
+use crate::ai::token_extractor::llm_available;
+use crate::settings::{load_json, save_json};
+
+/// Small and fast: routing runs before every agent call
+const ROUTER_MODEL: &str = "gemini-2.5-flash-lite";
+
+/// Below this the request goes to the Showrunner, who can ask or delegate
+pub const MIN_CONFIDENCE: f32 = 0.6;
+
+const ROUTER_SYSTEM_PROMPT: &str = r#"You route requests to one agent of a film production crew:
+- Showrunner: project overview, consistency, planning, anything unclear
+- Scriptwriter: screenplay, dialogue, plot, characters' arcs
+- Cinematographer: lenses, lighting, camera angles, shot composition
+- CastingDirector: character looks and consistency, casting
+- ArtDirector: locations, set design, props, world-building
+- VoiceActors: text-to-speech, dialogue performance, voice acting
+- MusicSfxDirector: score, foley, sound design, ambience
+- PhotographyDirector: generating images (concept art, stills, keyframes)
+- CameraDirector: generating video (shots, sequences)
+- Editor: montage, pacing, assembly, timeline
+- Colorist: color grading, LUTs, visual style
+
+Reply with a single JSON object and nothing else:
+{"role": "<one agent name exactly as above>", "confidence": <0.0 to 1.0>}"#;
+
+/// Keywords that send a request to one agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct RoutingRule {
+    pub role: AgentRole,
+    /// Matched case-insensitively as substrings of the request
+    pub keywords: Vec<String>,
+}
+
+/// Ordered routing rules; the first rule with a matching keyword wins
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct RoutingTable {
+    pub rules: Vec<RoutingRule>,
+}
+
+impl Default for RoutingTable {
+    fn default() -> Self {
+        let rule = |role, keywords: &[&str]| RoutingRule {
+            role,
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+        };
+
+        Self {
+            rules: vec![
+                rule(
+                    AgentRole::PhotographyDirector,
+                    &["image", "photo", "picture", "concept art", "storyboard"],
+                ),
+                rule(
+                    AgentRole::CameraDirector,
+                    &["video", "shot", "sequence", "footage", "clip"],
+                ),
+                rule(
+                    AgentRole::Scriptwriter,
+                    &["script", "dialogue", "scene", "write"],
+                ),
+                rule(
+                    AgentRole::VoiceActors,
+                    &["voice", "speak", "say", "tts", "narration"],
+                ),
+                rule(
+                    AgentRole::MusicSfxDirector,
+                    &["music", "sound", "audio", "sfx"],
+                ),
+                rule(
+                    AgentRole::CastingDirector,
+                    &["character", "cast", "actor", "face"],
+                ),
+                rule(
+                    AgentRole::ArtDirector,
+                    &["location", "set", "prop", "environment"],
+                ),
+                rule(
+                    AgentRole::Cinematographer,
+                    &["camera", "lens", "lighting", "composition"],
+                ),
+                rule(AgentRole::Editor, &["edit", "cut", "montage", "transition"]),
+                rule(AgentRole::Colorist, &["color", "grade", "lut"]),
+                rule(
+                    AgentRole::Showrunner,
+                    &["overview", "consistency", "vault", "project"],
+                ),
+            ],
+        }
+    }
+}
+
+impl RoutingTable {
+    /// Agent for a request, or `None` if no keyword matches
+    pub fn route(&self, message: &str) -> Option<AgentRole> {
+        let message = message.to_lowercase();
+
+        self.rules
+            .iter()
+            .find(|rule| {
+                rule.keywords
+                    .iter()
+                    .any(|k| !k.is_empty() && message.contains(&k.to_lowercase()))
+            })
+            .map(|rule| rule.role)
+    }
+
+    /// Add keywords to a role's rule (appending a new rule if the role has none)
+    pub fn add_keywords(&mut self, role: AgentRole, keywords: &[&str]) {
+        let keywords = keywords.iter().map(|k| k.to_lowercase());
+
+        match self.rules.iter_mut().find(|r| r.role == role) {
+            Some(rule) => rule.keywords.extend(keywords),
+            None => self.rules.push(RoutingRule {
+                role,
+                keywords: keywords.collect(),
+            }),
+        }
+    }
+}
+
+/// Where the routing table is persisted
+const ROUTING_FILE: &str = "routing_table.json";
+
+static ROUTING_TABLE: Lazy<RwLock<RoutingTable>> =
+    Lazy::new(|| RwLock::new(load_json(ROUTING_FILE)));
+
+/// Current routing table
+pub fn routing_table() -> RoutingTable {
+    ROUTING_TABLE.read().map(|t| t.clone()).unwrap_or_default()
+}
+
+/// Replace the routing table (persisted across restarts)
+pub fn set_routing_table(table: RoutingTable) -> Result<(), String> {
+    let mut current = ROUTING_TABLE.write().map_err(|e| e.to_string())?;
+    save_json(ROUTING_FILE, &table)?;
+    *current = table;
+    Ok(())
+}
+
+/// Route a request with the current routing table
+pub fn route_message(message: &str) -> Option<AgentRole> {
+    ROUTING_TABLE
+        .read()
+        .ok()
+        .and_then(|table| table.route(message))
+}
+
+#[derive(Debug, Deserialize)]
+struct LlmRoute {
+    role: AgentRole,
+    confidence: f32,
+}
+
+/// Agent for a request as classified by the LLM, with the model's confidence
+///
+/// Errors when no key is set or the reply isn't a known role, so callers can
+/// fall back to `route_message`.
+pub async fn classify_with_llm(message: &str) -> Result<(AgentRole, f32), String> {
+    if !llm_available() {
+        return Err("No API key for the intent router".to_string());
+    }
+
+    let response = get_llm_client()
+        .chat(LLMRequest {
+            provider: LLMProvider::Gemini,
+            model: ROUTER_MODEL.to_string(),
+            messages: vec![LLMMessage {
+                role: "user".to_string(),
+                content: message.to_string(),
+                images: Vec::new(),
+            }],
+            temperature: Some(0.0),
+            max_tokens: Some(50),
+            system_prompt: Some(ROUTER_SYSTEM_PROMPT.to_string()),
+        })
+        .await?;
+
+    parse_route(&response.content)
+        .ok_or_else(|| format!("Unusable routing reply: {}", response.content))
+}
+
+/// Role and confidence (clamped to 0..=1) from the router's completion
+fn parse_route(raw: &str) -> Option<(AgentRole, f32)> {
+    let route: LlmRoute = serde_json::from_str(extract_json(raw)?).ok()?;
+    if !route.confidence.is_finite() {
+        return None;
+    }
+    Some((route.role, route.confidence.clamp(0.0, 1.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let table = RoutingTable::default();
+        // "sunset" contains "set", but images are matched first
+        assert_eq!(
+            table.route("Generate an image of a sunset"),
+            Some(AgentRole::PhotographyDirector)
+        );
+        assert_eq!(
+            table.route("Give me a project overview"),
+            Some(AgentRole::Showrunner)
+        );
+        assert_eq!(table.route("Hello there"), None);
+    }
+
+    #[test]
+    fn test_custom_keywords() {
+        let mut table = RoutingTable::default();
+        assert_eq!(table.route("Plan the Matte Painting"), None);
+
+        table.add_keywords(AgentRole::ArtDirector, &["Matte Painting"]);
+        assert_eq!(
+            table.route("Plan the Matte Painting"),
+            Some(AgentRole::ArtDirector)
+        );
+
+        let json = serde_json::to_string(&table).unwrap();
+        let restored: RoutingTable = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, table);
+    }
+
+    #[test]
+    fn test_parse_route() {
+        assert_eq!(
+            parse_route(r#"{"role": "PhotographyDirector", "confidence": 0.92}"#),
+            Some((AgentRole::PhotographyDirector, 0.92))
+        );
+        assert_eq!(
+            parse_route("```json\n{\"role\": \"Editor\", \"confidence\": 1.4}\n```"),
+            Some((AgentRole::Editor, 1.0))
+        );
+        // Only the exact enum names are accepted
+        assert_eq!(
+            parse_route(r#"{"role": "Photography Director", "confidence": 0.9}"#),
+            None
+        );
+        assert_eq!(parse_route(r#"{"role": "Editor"}"#), None);
+        assert_eq!(parse_route("Editor"), None);
+    }
+}
@@ -34,6 +34,7 @@ pub struct AssetDefinition {
     pub filename: String,
     pub url: String,
     pub expected_sha256: Option<String>,
+    #[specta(type = f64)]
     pub size_bytes: u64,
     pub target_path: String, // Relative to app_data_dir/models/
 }
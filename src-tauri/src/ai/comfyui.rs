@@ -19,10 +19,20 @@
 //!
 //! For low-latency tasks (chat, quick LLM responses), use the Fast Path
 //! which bypasses ComfyUI and calls providers directly.
+//!
+//! Local models only run when the largest GPU has the VRAM for them; otherwise
+//! routing falls back to a smaller local model or the cloud and says why.
 
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+use crate::installer::hardware::{detect_hardware, HardwareInfo};
+
+use crate::ai::model_params::{
+    BeatovenParams, ElevenLabsParams, FluxParams, KlingParams, ModelParams, VeoParams,
+};
+
 /// Execution path for AI requests
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
 pub enum ExecutionPath {
@@ -76,6 +86,16 @@ pub enum CinemaOSNode {
         reference_images: Vec<String>,
     },
 
+    /// FLUX Fill Pro - Inpainting (mask) or outpainting (expand_pixels)
+    FalFluxFill {
+        prompt: String,
+        image_url: String,
+        mask_url: Option<String>,
+        expand_pixels: Option<u32>,
+        #[specta(type = f64)]
+        seed: i64,
+    },
+
     // ══════════════════════════════════════════════════════════════════════════
     // VIDEO GENERATION (Fal.ai Cloud) - All support native audio
     // ══════════════════════════════════════════════════════════════════════════
@@ -256,43 +276,110 @@ pub struct NodeConnection {
     pub to_input: String,
 }
 
-/// Determine which execution path to use
-pub fn determine_execution_path(
-    task_type: &str,
-    model_id: &str,
-    prefer_local: bool,
-) -> ExecutionPath {
-    // Fast Path: LLM chat and quick text generation
-    let is_fast_path_task = matches!(
-        task_type,
-        "chat" | "quick_text" | "translate" | "summarize" | "completion" | "script"
-    );
+// ═══════════════════════════════════════════════════════════════════════════════
+// EXECUTION ROUTING
+// ═══════════════════════════════════════════════════════════════════════════════
 
-    if is_fast_path_task {
-        let provider = crate::ai::providers::get_provider_for_model(model_id);
-        return ExecutionPath::FastPath {
-            provider: format!("{:?}", provider),
-            model_id: model_id.to_string(),
-        };
-    }
+/// A local model: the VRAM it needs and the smaller model to try instead
+struct LocalModel {
+    /// Matched as a substring of the lowercased model id
+    id: &'static str,
+    min_vram_gb: u32,
+    smaller: Option<&'static str>,
+}
 
-    // Workflow Path: All generation tasks
-    let target = if prefer_local {
-        // Check if model supports local execution
-        let local_models = ["flux.2-schnell", "ltx-video", "wan-2.2", "flux-dev"];
-        if local_models
-            .iter()
-            .any(|m| model_id.to_lowercase().contains(m))
-        {
-            WorkflowTarget::Local
-        } else {
-            WorkflowTarget::Hybrid // Cloud gen, local post-processing
+/// Models that can run on the user's machine, largest first within a family
+const LOCAL_MODELS: &[LocalModel] = &[
+    LocalModel {
+        id: "llama-4-70b",
+        min_vram_gb: 24,
+        smaller: Some("llama-4-8b"),
+    },
+    LocalModel {
+        id: "llama-4-8b",
+        min_vram_gb: 6,
+        smaller: None,
+    },
+    LocalModel {
+        id: "flux-dev",
+        min_vram_gb: 24,
+        smaller: Some("flux.2-schnell"),
+    },
+    LocalModel {
+        id: "flux.2-schnell",
+        min_vram_gb: 12,
+        smaller: None,
+    },
+    LocalModel {
+        id: "wan-2.2",
+        min_vram_gb: 24,
+        smaller: Some("ltx-video"),
+    },
+    LocalModel {
+        id: "ltx-video",
+        min_vram_gb: 12,
+        smaller: None,
+    },
+];
+
+/// Cloud model answering Fast Path tasks the machine can't run locally
+const CLOUD_CHAT_FALLBACK: &str = "gemini-2.5-flash";
+
+/// Cloud models running workflow tasks the machine can't run locally
+const CLOUD_IMAGE_FALLBACK: &str = "flux-pro-2.0";
+const CLOUD_VIDEO_FALLBACK: &str = "veo-3.1";
+
+/// Detected once; GPUs don't change while the app runs
+static HARDWARE: Lazy<HardwareInfo> = Lazy::new(detect_hardware);
+
+/// Chosen execution path, the model that runs on it and why
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
+pub struct RoutingDecision {
+    pub path: ExecutionPath,
+    /// The requested model, or the one substituted for it
+    pub model_id: String,
+    pub reason: String,
+}
+
+fn local_model(model_id: &str) -> Option<&'static LocalModel> {
+    let model_id = model_id.to_lowercase();
+    LOCAL_MODELS.iter().find(|m| model_id.contains(m.id))
+}
+
+/// The requested local model, or the largest smaller one, that fits in `vram_gb`
+///
+/// Returns the model id and, when it had to be swapped, why.
+fn fit_local_model(model_id: &str, vram_gb: u32) -> Result<(String, Option<String>), String> {
+    let requested =
+        local_model(model_id).ok_or_else(|| format!("{} has no local build", model_id))?;
+    let mut candidate = requested;
+    loop {
+        if candidate.min_vram_gb <= vram_gb {
+            if std::ptr::eq(candidate, requested) {
+                return Ok((model_id.to_string(), None));
+            }
+            return Ok((
+                candidate.id.to_string(),
+                Some(format!(
+                    "{} needs {} GB VRAM but the GPU has {} GB; using {} ({} GB) instead",
+                    model_id, requested.min_vram_gb, vram_gb, candidate.id, candidate.min_vram_gb
+                )),
+            ));
         }
-    } else {
-        WorkflowTarget::Cloud
-    };
+        match candidate.smaller.and_then(local_model) {
+            Some(smaller) => candidate = smaller,
+            None => {
+                return Err(format!(
+                    "{} needs {} GB VRAM but the GPU has {} GB",
+                    model_id, requested.min_vram_gb, vram_gb
+                ))
+            }
+        }
+    }
+}
 
-    let workflow_id = match task_type {
+fn workflow_for(task_type: &str) -> &'static str {
+    match task_type {
         // Image workflows
         "image" | "concept_art" => "flux2_turbo_v1",
         "image_edit" | "inpaint" => "image_edit_kontext_v1",
@@ -316,14 +403,115 @@ pub fn determine_execution_path(
         "segment" | "mask" => "sam3_segment_v1",
 
         _ => "generic_v1",
+    }
+}
+
+/// Cloud model for a workflow task whose local model doesn't fit; local
+/// model ids mean nothing to the cloud
+fn cloud_fallback(task_type: &str) -> &'static str {
+    match task_type {
+        "video" | "shot" | "video_fast" | "image_to_video" | "i2v" => CLOUD_VIDEO_FALLBACK,
+        _ => CLOUD_IMAGE_FALLBACK,
+    }
+}
+
+/// Route a request, checking local models against `hardware`
+pub fn route_execution(
+    task_type: &str,
+    model_id: &str,
+    prefer_local: bool,
+    hardware: &HardwareInfo,
+) -> RoutingDecision {
+    // Fast Path: LLM chat and quick text generation
+    let is_fast_path_task = matches!(
+        task_type,
+        "chat" | "quick_text" | "translate" | "summarize" | "completion" | "script"
+    );
+
+    if is_fast_path_task {
+        let fast_path = |model_id: &str, reason: String| {
+            let provider = crate::ai::providers::get_provider_for_model(model_id);
+            RoutingDecision {
+                path: ExecutionPath::FastPath {
+                    provider: format!("{:?}", provider),
+                    model_id: model_id.to_string(),
+                },
+                model_id: model_id.to_string(),
+                reason,
+            }
+        };
+
+        if local_model(model_id).is_none() {
+            return fast_path(model_id, "Text task on the Fast Path".into());
+        }
+        return match fit_local_model(model_id, hardware.vram_gb) {
+            Ok((model, None)) => fast_path(&model, "Local model fits in VRAM".into()),
+            Ok((model, Some(reason))) => fast_path(&model, reason),
+            Err(reason) => fast_path(
+                CLOUD_CHAT_FALLBACK,
+                format!("{}; answering in the cloud", reason),
+            ),
+        };
+    }
+
+    // Workflow Path: All generation tasks
+    let workflow = |target: WorkflowTarget, model_id: &str, reason: String| RoutingDecision {
+        path: ExecutionPath::WorkflowPath {
+            workflow_id: workflow_for(task_type).to_string(),
+            execution_target: target,
+        },
+        model_id: model_id.to_string(),
+        reason,
     };
 
-    ExecutionPath::WorkflowPath {
-        workflow_id: workflow_id.to_string(),
-        execution_target: target,
+    if !prefer_local {
+        return workflow(WorkflowTarget::Cloud, model_id, "Cloud preferred".into());
+    }
+    if local_model(model_id).is_none() {
+        // Cloud gen, local post-processing
+        return workflow(
+            WorkflowTarget::Hybrid,
+            model_id,
+            format!("{} has no local build", model_id),
+        );
+    }
+    match fit_local_model(model_id, hardware.vram_gb) {
+        Ok((model, None)) => workflow(
+            WorkflowTarget::Local,
+            &model,
+            "Local model fits in VRAM".into(),
+        ),
+        Ok((model, Some(reason))) => workflow(WorkflowTarget::Local, &model, reason),
+        Err(reason) => {
+            let cloud_model = cloud_fallback(task_type);
+            workflow(
+                WorkflowTarget::Cloud,
+                cloud_model,
+                format!("{}; running {} in the cloud", reason, cloud_model),
+            )
+        }
     }
 }
 
+/// Determine which execution path to use on this machine
+pub fn determine_execution_path(
+    task_type: &str,
+    model_id: &str,
+    prefer_local: bool,
+) -> ExecutionPath {
+    route_execution(task_type, model_id, prefer_local, machine_hardware()).path
+}
+
+/// This machine's hardware, detected on first use
+pub fn machine_hardware() -> &'static HardwareInfo {
+    &HARDWARE
+}
+
+/// Serialize template params; they are constants checked by the tests
+fn node_params(params: ModelParams) -> String {
+    params.to_params_json().unwrap_or_default()
+}
+
 /// Get predefined workflow templates - Updated December 2025
 pub fn get_workflow_template(workflow_id: &str) -> Option<Workflow> {
     match workflow_id {
@@ -337,7 +525,10 @@ pub fn get_workflow_template(workflow_id: &str) -> Option<Workflow> {
             nodes: vec![WorkflowNode {
                 id: "flux2".into(),
                 node_type: "FalFlux2".into(),
-                params_json: r#"{"num_inference_steps": 4, "guidance_scale": 1.0}"#.into(),
+                params_json: node_params(ModelParams::Flux(FluxParams {
+                    num_inference_steps: 4,
+                    guidance_scale: 1.0,
+                })),
                 position_x: 0.0,
                 position_y: 0.0,
             }],
@@ -354,7 +545,7 @@ pub fn get_workflow_template(workflow_id: &str) -> Option<Workflow> {
             nodes: vec![WorkflowNode {
                 id: "flux2".into(),
                 node_type: "FalFlux2".into(),
-                params_json: r#"{"num_inference_steps": 28, "guidance_scale": 3.5}"#.into(),
+                params_json: node_params(ModelParams::Flux(FluxParams::default())),
                 position_x: 0.0,
                 position_y: 0.0,
             }],
@@ -374,14 +565,14 @@ pub fn get_workflow_template(workflow_id: &str) -> Option<Workflow> {
             nodes: vec![WorkflowNode {
                 id: "veo31".into(),
                 node_type: "FalVeo31".into(),
-                params_json: r#"{"duration_seconds": 5.0, "with_audio": true}"#.into(),
+                params_json: node_params(ModelParams::Veo(VeoParams::default())),
                 position_x: 0.0,
                 position_y: 0.0,
             }],
             connections: vec![],
             local_compatible: false,
             requires_credits: true,
-            estimated_cost: 0.25, // ~$0.05/sec * 5 sec
+            estimated_cost: 0.40, // ~$0.05/sec * 8 sec
         }),
 
         "kling_turbo_v1" => Some(Workflow {
@@ -391,7 +582,10 @@ pub fn get_workflow_template(workflow_id: &str) -> Option<Workflow> {
             nodes: vec![WorkflowNode {
                 id: "kling".into(),
                 node_type: "FalKlingV25Turbo".into(),
-                params_json: r#"{}"#.into(),
+                params_json: node_params(ModelParams::Kling(KlingParams {
+                    with_audio: false,
+                    ..KlingParams::default()
+                })),
                 position_x: 0.0,
                 position_y: 0.0,
             }],
@@ -408,7 +602,7 @@ pub fn get_workflow_template(workflow_id: &str) -> Option<Workflow> {
             nodes: vec![WorkflowNode {
                 id: "kling26".into(),
                 node_type: "FalKlingV26".into(),
-                params_json: r#"{"duration_seconds": 5.0, "with_audio": true}"#.into(),
+                params_json: node_params(ModelParams::Kling(KlingParams::default())),
                 position_x: 0.0,
                 position_y: 0.0,
             }],
@@ -428,7 +622,7 @@ pub fn get_workflow_template(workflow_id: &str) -> Option<Workflow> {
             nodes: vec![WorkflowNode {
                 id: "music".into(),
                 node_type: "FalBeatovenMusic".into(),
-                params_json: r#"{"genre": "cinematic", "duration_seconds": 30.0}"#.into(),
+                params_json: node_params(ModelParams::Beatoven(BeatovenParams::default())),
                 position_x: 0.0,
                 position_y: 0.0,
             }],
@@ -445,7 +639,7 @@ pub fn get_workflow_template(workflow_id: &str) -> Option<Workflow> {
             nodes: vec![WorkflowNode {
                 id: "tts".into(),
                 node_type: "ElevenLabsTts".into(),
-                params_json: r#"{"model": "v3"}"#.into(),
+                params_json: node_params(ModelParams::ElevenLabs(ElevenLabsParams::default())),
                 position_x: 0.0,
                 position_y: 0.0,
             }],
@@ -501,35 +695,78 @@ pub fn get_all_workflow_templates() -> Vec<Workflow> {
 mod tests {
     use super::*;
 
+    fn gpu(vram_gb: u32) -> HardwareInfo {
+        HardwareInfo {
+            vram_gb,
+            ..HardwareInfo::default()
+        }
+    }
+
+    fn target(decision: &RoutingDecision) -> Option<&WorkflowTarget> {
+        match &decision.path {
+            ExecutionPath::WorkflowPath {
+                execution_target, ..
+            } => Some(execution_target),
+            ExecutionPath::FastPath { .. } => None,
+        }
+    }
+
     #[test]
     fn test_execution_path_routing() {
+        let rtx_4090 = gpu(24);
+
         // Chat should use Fast Path
-        let path = determine_execution_path("chat", "gemini-3-pro", false);
-        assert!(matches!(path, ExecutionPath::FastPath { .. }));
+        let decision = route_execution("chat", "gemini-3-pro", false, &rtx_4090);
+        assert!(matches!(decision.path, ExecutionPath::FastPath { .. }));
 
         // Image generation should use Workflow Path
-        let path = determine_execution_path("image", "flux.2", false);
-        assert!(matches!(path, ExecutionPath::WorkflowPath { .. }));
+        let decision = route_execution("image", "flux.2", false, &rtx_4090);
+        assert_eq!(target(&decision), Some(&WorkflowTarget::Cloud));
 
         // Video generation with prefer_local should use Hybrid (cloud model)
-        let path = determine_execution_path("video", "veo-3.1", true);
-        assert!(matches!(
-            path,
-            ExecutionPath::WorkflowPath {
-                execution_target: WorkflowTarget::Hybrid,
-                ..
-            }
-        ));
+        let decision = route_execution("video", "veo-3.1", true, &rtx_4090);
+        assert_eq!(target(&decision), Some(&WorkflowTarget::Hybrid));
 
         // Local model should use Local target
-        let path = determine_execution_path("image", "flux.2-schnell", true);
+        let decision = route_execution("image", "flux.2-schnell", true, &rtx_4090);
+        assert_eq!(target(&decision), Some(&WorkflowTarget::Local));
+        assert_eq!(decision.model_id, "flux.2-schnell");
+    }
+
+    #[test]
+    fn test_small_gpu_falls_back() {
+        let gtx_1060 = gpu(6);
+
+        // A 24 GB LLM steps down to the 8B model, which fits in 6 GB
+        let decision = route_execution("chat", "llama-4-70b", true, &gtx_1060);
+        assert_eq!(decision.model_id, "llama-4-8b");
         assert!(matches!(
-            path,
-            ExecutionPath::WorkflowPath {
-                execution_target: WorkflowTarget::Local,
-                ..
-            }
+            decision.path,
+            ExecutionPath::FastPath { ref model_id, .. } if model_id == "llama-4-8b"
         ));
+        assert!(decision
+            .reason
+            .contains("needs 24 GB VRAM but the GPU has 6 GB"));
+
+        // No local video model fits, so a cloud video model runs it
+        let decision = route_execution("video", "wan-2.2", true, &gtx_1060);
+        assert_eq!(target(&decision), Some(&WorkflowTarget::Cloud));
+        assert_eq!(decision.model_id, CLOUD_VIDEO_FALLBACK);
+        assert!(decision.reason.contains("running veo-3.1 in the cloud"));
+
+        // Likewise for images, with a cloud image model
+        let decision = route_execution("image", "flux-dev", true, &gtx_1060);
+        assert_eq!(target(&decision), Some(&WorkflowTarget::Cloud));
+        assert_eq!(decision.model_id, CLOUD_IMAGE_FALLBACK);
+
+        // Without a GPU even the small LLM goes to the cloud
+        let decision = route_execution("chat", "llama-4-8b", true, &gpu(0));
+        assert_eq!(decision.model_id, CLOUD_CHAT_FALLBACK);
+
+        // A 16 GB card gets the smaller image model
+        let decision = route_execution("image", "flux-dev", true, &gpu(16));
+        assert_eq!(target(&decision), Some(&WorkflowTarget::Local));
+        assert_eq!(decision.model_id, "flux.2-schnell");
     }
 
     #[test]
@@ -552,4 +789,16 @@ mod tests {
         let kling = get_workflow_template("i2v_kling_v1").unwrap();
         assert!(kling.nodes[0].params_json.contains("with_audio"));
     }
+
+    #[test]
+    fn test_template_params_are_valid() {
+        for workflow in get_all_workflow_templates() {
+            for node in &workflow.nodes {
+                if ModelParams::for_node_type(&node.node_type).is_some() {
+                    ModelParams::from_params_json(&node.node_type, &node.params_json)
+                        .unwrap_or_else(|e| panic!("{}: {}", workflow.id, e));
+                }
+            }
+        }
+    }
 }
@@ -9,9 +9,14 @@ use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 use tokio::sync::{mpsc, RwLock};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::ai::resolution::image_size;
+use crate::comfyui::client::SystemStats;
+use crate::comfyui::output::{parse_outputs, OutputFile};
 
 // ═══════════════════════════════════════════════════════════════════════════════
 // CONNECTION STATE
@@ -64,14 +69,30 @@ impl ComfyUIConfig {
 // ═══════════════════════════════════════════════════════════════════════════════
 
 /// Progress update from ComfyUI
+///
+/// `execution_id` is the prompt id, so updates from concurrent executions
+/// can be told apart.
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
 pub struct ProgressUpdate {
     pub execution_id: String,
     pub node_id: String,
+    /// Progress of the running node, 0.0 to 1.0
     pub progress: f32,
+    /// "queued", "running", "completed", "failed" or "cancelled"
     pub status: String,
 }
 
+impl ProgressUpdate {
+    fn new(execution_id: &str, status: &str, progress: f32) -> Self {
+        Self {
+            execution_id: execution_id.to_string(),
+            node_id: String::new(),
+            progress,
+            status: status.to_string(),
+        }
+    }
+}
+
 /// Execution result
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
 pub struct ExecutionResult {
@@ -79,9 +100,31 @@ pub struct ExecutionResult {
     pub success: bool,
     /// Output data as JSON string (for specta compatibility)
     pub outputs_json: String,
+    /// Every saved file, all images of a batch included
+    #[serde(default)]
+    pub output_files: Vec<OutputFile>,
     pub error: Option<String>,
 }
 
+/// Error of an `ExecutionResult` for a prompt that was interrupted or dequeued
+pub const CANCELLED_ERROR: &str = "cancelled";
+
+/// Reconnect attempts after the WebSocket drops mid-execution
+const WS_RECONNECT_ATTEMPTS: u32 = 5;
+
+/// Wait before the first reconnect attempt, doubled for each one after
+const WS_RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Interval between `/history` checks while waiting on a prompt
+const HISTORY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Failed `/history` checks in a row after which a wait gives up
+const HISTORY_POLL_FAILURES: u32 = 5;
+
+/// Receiving half of the execution WebSocket
+type WsReader =
+    futures_util::stream::SplitStream<WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>>;
+
 /// Output data per node (internal use)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutputData {
@@ -90,6 +133,141 @@ pub struct OutputData {
     pub data: serde_json::Value,
 }
 
+// ═══════════════════════════════════════════════════════════════════════════════
+// MODEL INVENTORY
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Loader node and the input listing its files
+const CHECKPOINT_LOADER: (&str, &str) = ("CheckpointLoaderSimple", "ckpt_name");
+const LORA_LOADER: (&str, &str) = ("LoraLoader", "lora_name");
+const VAE_LOADER: (&str, &str) = ("VAELoader", "vae_name");
+const CONTROLNET_LOADER: (&str, &str) = ("ControlNetLoader", "control_net_name");
+
+/// Model files installed in ComfyUI, by kind
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct ModelInventory {
+    pub checkpoints: Vec<String>,
+    pub loras: Vec<String>,
+    pub vaes: Vec<String>,
+    pub controlnets: Vec<String>,
+}
+
+impl ModelInventory {
+    fn from_object_info(info: &serde_json::Value) -> Self {
+        let options = |(loader, field): (&str, &str)| loader_options(info, loader, field);
+        Self {
+            checkpoints: options(CHECKPOINT_LOADER),
+            loras: options(LORA_LOADER),
+            vaes: options(VAE_LOADER),
+            controlnets: options(CONTROLNET_LOADER),
+        }
+    }
+}
+
+/// Choices of a loader's combo input in an `/object_info` response
+///
+/// Older ComfyUI lists them as `[[choices...], {..}]`, newer as
+/// `["COMBO", {"options": [choices...]}]`. Missing nodes give an empty list.
+fn loader_options(info: &serde_json::Value, loader: &str, field: &str) -> Vec<String> {
+    let input = &info[loader]["input"]["required"][field];
+    let choices = match &input[0] {
+        serde_json::Value::Array(choices) => Some(choices),
+        _ => input[1]["options"].as_array(),
+    };
+
+    choices
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Custom node pack a node type comes from, for types ComfyUI doesn't ship
+fn node_package(class_type: &str) -> Option<&'static str> {
+    match class_type {
+        "DepthAnythingV2Preprocessor" | "DWPreprocessor" | "OpenposePreprocessor" => {
+            Some("comfyui_controlnet_aux")
+        }
+        _ => None,
+    }
+}
+
+/// What ComfyUI lacks to run `workflow`, given its `/object_info`: node
+/// types it doesn't know and ControlNet weights it doesn't list
+fn missing_requirements(workflow: &serde_json::Value, info: &serde_json::Value) -> Vec<String> {
+    let controlnets = loader_options(info, CONTROLNET_LOADER.0, CONTROLNET_LOADER.1);
+    let mut missing = Vec::new();
+
+    for node in workflow.as_object().into_iter().flat_map(|n| n.values()) {
+        let class_type = node["class_type"].as_str().unwrap_or_default();
+        let problem = if info.get(class_type).is_none() {
+            Some(match node_package(class_type) {
+                Some(package) => format!("node {} (install {})", class_type, package),
+                None => format!("node {}", class_type),
+            })
+        } else if class_type == CONTROLNET_LOADER.0 {
+            let weights = node["inputs"][CONTROLNET_LOADER.1]
+                .as_str()
+                .unwrap_or_default();
+            (!controlnets.iter().any(|c| c == weights))
+                .then(|| format!("ControlNet {} (add it to models/controlnet)", weights))
+        } else {
+            None
+        };
+
+        if let Some(problem) = problem {
+            if !missing.contains(&problem) {
+                missing.push(problem);
+            }
+        }
+    }
+    missing
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// UPLOADS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Largest input image accepted for upload
+pub const MAX_UPLOAD_BYTES: usize = 25 * 1024 * 1024;
+
+/// An image stored in ComfyUI's input folder
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct UploadedImage {
+    pub name: String,
+    pub subfolder: String,
+    #[serde(rename = "type")]
+    pub folder_type: String,
+}
+
+impl UploadedImage {
+    /// Value for a LoadImage node's `image` input
+    pub fn workflow_path(&self) -> String {
+        if self.subfolder.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}/{}", self.subfolder, self.name)
+        }
+    }
+}
+
+/// Reject uploads that are too large or aren't PNG/JPEG images
+pub fn check_upload(bytes: &[u8]) -> Result<(), String> {
+    if bytes.len() > MAX_UPLOAD_BYTES {
+        return Err(format!(
+            "Image is {:.1} MB; the upload limit is {} MB",
+            bytes.len() as f64 / (1024.0 * 1024.0),
+            MAX_UPLOAD_BYTES / (1024 * 1024)
+        ));
+    }
+    if image_size(bytes).is_none() {
+        return Err("Upload is not a PNG or JPEG image".into());
+    }
+    Ok(())
+}
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // COMFYUI CLIENT
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -99,6 +277,8 @@ pub struct ComfyUIClient {
     config: ComfyUIConfig,
     status: Arc<RwLock<ConnectionStatus>>,
     http_client: reqwest::Client,
+    /// Running `execute` calls by prompt id, signalled by `interrupt`
+    active: Mutex<HashMap<String, mpsc::Sender<()>>>,
 }
 
 impl ComfyUIClient {
@@ -108,6 +288,7 @@ impl ComfyUIClient {
             config,
             status: Arc::new(RwLock::new(ConnectionStatus::Disconnected)),
             http_client: reqwest::Client::new(),
+            active: Mutex::new(HashMap::new()),
         }
     }
 
@@ -116,6 +297,10 @@ impl ComfyUIClient {
         Self::new(ComfyUIConfig::default())
     }
 
+    pub fn config(&self) -> &ComfyUIConfig {
+        &self.config
+    }
+
     /// Get current connection status
     pub async fn status(&self) -> ConnectionStatus {
         self.status.read().await.clone()
@@ -131,9 +316,28 @@ impl ComfyUIClient {
         }
     }
 
-    /// Get available models from ComfyUI
-    pub async fn get_models(&self) -> Result<Vec<String>, String> {
-        let url = format!("{}/object_info", self.config.http_url());
+    /// Get typed system stats (GPU, VRAM, torch/python versions)
+    pub async fn get_system_stats(&self) -> Result<SystemStats, String> {
+        let url = format!("{}/system_stats", self.config.http_url());
+
+        let resp = self
+            .http_client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to get system stats: {}", e))?;
+
+        resp.json()
+            .await
+            .map_err(|e| format!("Failed to parse system stats: {}", e))
+    }
+
+    /// Fetch `/object_info`, or a single node's entry when `node` is given
+    async fn object_info(&self, node: Option<&str>) -> Result<serde_json::Value, String> {
+        let url = match node {
+            Some(node) => format!("{}/object_info/{}", self.config.http_url(), node),
+            None => format!("{}/object_info", self.config.http_url()),
+        };
 
         let resp = self
             .http_client
@@ -142,34 +346,101 @@ impl ComfyUIClient {
             .await
             .map_err(|e| format!("Failed to get models: {}", e))?;
 
-        let data: serde_json::Value = resp
-            .json()
+        resp.json()
             .await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
-
-        // Extract checkpoint names from CheckpointLoaderSimple
-        let models = data
-            .get("CheckpointLoaderSimple")
-            .and_then(|n| n.get("input"))
-            .and_then(|n| n.get("required"))
-            .and_then(|n| n.get("ckpt_name"))
-            .and_then(|n| n.get(0))
-            .and_then(|n| n.as_array())
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|v| v.as_str().map(String::from))
-                    .collect()
-            })
-            .unwrap_or_default();
+            .map_err(|e| format!("Failed to parse response: {}", e))
+    }
+
+    /// Files a loader node can choose from, e.g. ("LoraLoader", "lora_name")
+    pub async fn get_models_of(&self, loader: &str, field: &str) -> Result<Vec<String>, String> {
+        let info = self.object_info(Some(loader)).await?;
+        Ok(loader_options(&info, loader, field))
+    }
+
+    pub async fn get_checkpoints(&self) -> Result<Vec<String>, String> {
+        self.get_models_of(CHECKPOINT_LOADER.0, CHECKPOINT_LOADER.1)
+            .await
+    }
+
+    pub async fn get_loras(&self) -> Result<Vec<String>, String> {
+        self.get_models_of(LORA_LOADER.0, LORA_LOADER.1).await
+    }
 
-        Ok(models)
+    pub async fn get_vaes(&self) -> Result<Vec<String>, String> {
+        self.get_models_of(VAE_LOADER.0, VAE_LOADER.1).await
+    }
+
+    pub async fn get_controlnets(&self) -> Result<Vec<String>, String> {
+        self.get_models_of(CONTROLNET_LOADER.0, CONTROLNET_LOADER.1)
+            .await
+    }
+
+    /// Every model list in one `/object_info` request
+    pub async fn get_all_model_types(&self) -> Result<ModelInventory, String> {
+        let info = self.object_info(None).await?;
+        Ok(ModelInventory::from_object_info(&info))
+    }
+
+    /// Fail before queueing if ComfyUI lacks a node type or the ControlNet
+    /// weights `workflow` needs, instead of letting the prompt fail
+    pub async fn check_requirements(&self, workflow: &serde_json::Value) -> Result<(), String> {
+        let info = self.object_info(None).await?;
+        let missing = missing_requirements(workflow, &info);
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("ComfyUI is missing {}", missing.join(", ")))
+        }
+    }
+
+    /// Store an image in ComfyUI's input folder for LoadImage nodes
+    ///
+    /// Without `overwrite`, ComfyUI renames the file if the name is taken;
+    /// the returned name is the one to reference.
+    pub async fn upload_image(
+        &self,
+        bytes: Vec<u8>,
+        filename: &str,
+        overwrite: bool,
+    ) -> Result<UploadedImage, String> {
+        check_upload(&bytes)?;
+
+        let url = format!("{}/upload/image", self.config.http_url());
+        let form = reqwest::multipart::Form::new()
+            .part(
+                "image",
+                reqwest::multipart::Part::bytes(bytes).file_name(filename.to_string()),
+            )
+            .text("type", "input")
+            .text("overwrite", overwrite.to_string());
+
+        let resp = self
+            .http_client
+            .post(&url)
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to upload image: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("ComfyUI rejected the upload: {}", resp.status()));
+        }
+
+        resp.json()
+            .await
+            .map_err(|e| format!("Failed to parse upload response: {}", e))
     }
 
     /// Execute a workflow and return results
+    ///
+    /// Sending on `cancel` (or calling [`Self::interrupt`] with the prompt id)
+    /// stops the prompt and returns an unsuccessful result with the
+    /// [`CANCELLED_ERROR`] error. A dropped WebSocket is reconnected (see
+    /// [`Self::recover`]) rather than failing the execution.
     pub async fn execute(
         &self,
         prompt: serde_json::Value,
         progress_tx: Option<mpsc::Sender<ProgressUpdate>>,
+        mut cancel: Option<mpsc::Receiver<()>>,
     ) -> Result<ExecutionResult, String> {
         let client_id = uuid::Uuid::new_v4().to_string();
 
@@ -213,36 +484,50 @@ impl ComfyUIClient {
             .ok_or("No prompt_id in response")?
             .to_string();
 
+        let (interrupt_tx, mut interrupted) = mpsc::channel(1);
+        self.active().insert(prompt_id.clone(), interrupt_tx);
+
+        if let Some(tx) = &progress_tx {
+            let _ = tx
+                .send(ProgressUpdate::new(&prompt_id, "queued", 0.0))
+                .await;
+        }
+
         // Listen for progress and completion
         let mut outputs: HashMap<String, OutputData> = HashMap::new();
         let mut error: Option<String> = None;
 
-        while let Some(msg) = read.next().await {
-            match msg {
-                Ok(Message::Text(text)) => {
+        loop {
+            let msg = tokio::select! {
+                msg = read.next() => msg,
+                // `interrupt` already stopped the prompt
+                Some(()) = interrupted.recv() => {
+                    error = Some(CANCELLED_ERROR.into());
+                    break;
+                }
+                () = cancel_requested(&mut cancel) => {
+                    if let Err(e) = self.interrupt(&prompt_id).await {
+                        tracing::warn!("Failed to interrupt ComfyUI prompt {}: {}", prompt_id, e);
+                    }
+                    error = Some(CANCELLED_ERROR.into());
+                    break;
+                }
+            };
+
+            let dropped = match msg {
+                Some(Ok(Message::Text(text))) => {
                     if let Ok(data) = serde_json::from_str::<serde_json::Value>(&text) {
                         let msg_type = data.get("type").and_then(|v| v.as_str()).unwrap_or("");
 
+                        // Messages about other prompts aren't this execution's
+                        if !is_for_prompt(&data, &prompt_id) {
+                            continue;
+                        }
+
                         match msg_type {
                             "progress" => {
                                 if let Some(tx) = &progress_tx {
-                                    let update = ProgressUpdate {
-                                        execution_id: prompt_id.clone(),
-                                        node_id: data
-                                            .get("data")
-                                            .and_then(|d| d.get("node"))
-                                            .and_then(|v| v.as_str())
-                                            .unwrap_or("")
-                                            .to_string(),
-                                        progress: data
-                                            .get("data")
-                                            .and_then(|d| d.get("value"))
-                                            .and_then(|v| v.as_f64())
-                                            .unwrap_or(0.0)
-                                            as f32,
-                                        status: "running".into(),
-                                    };
-                                    let _ = tx.send(update).await;
+                                    let _ = tx.send(node_progress(&prompt_id, &data)).await;
                                 }
                             }
                             "executed" => {
@@ -288,30 +573,236 @@ impl ComfyUIClient {
                                     break;
                                 }
                             }
+                            // Interrupted from elsewhere, e.g. the ComfyUI web UI
+                            "execution_interrupted" => {
+                                let interrupted_id = data
+                                    .get("data")
+                                    .and_then(|d| d.get("prompt_id"))
+                                    .and_then(|v| v.as_str())
+                                    .unwrap_or("");
+
+                                if interrupted_id == prompt_id {
+                                    error = Some(CANCELLED_ERROR.into());
+                                    break;
+                                }
+                            }
                             _ => {}
                         }
                     }
+                    None
+                }
+                // The socket idled out or the server closed it before the
+                // prompt finished
+                Some(Ok(Message::Close(_))) | None => Some("connection closed".to_string()),
+                Some(Err(e)) => Some(e.to_string()),
+                Some(Ok(_)) => None,
+            };
+
+            if let Some(reason) = dropped {
+                match self.recover(&ws_url, &prompt_id, &reason).await {
+                    Ok(Recovery::Reconnected(reader)) => read = reader,
+                    Ok(Recovery::Finished(outcome)) => {
+                        outputs.extend(outcome.outputs);
+                        error = outcome.error;
+                        break;
+                    }
+                    Err(e) => {
+                        error = Some(e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Other executions may still be listening
+        let idle = {
+            let mut active = self.active();
+            active.remove(&prompt_id);
+            active.is_empty()
+        };
+        if idle {
+            *self.status.write().await = ConnectionStatus::Disconnected;
+        }
+
+        if let Some(tx) = &progress_tx {
+            let update = match error.as_deref() {
+                None => ProgressUpdate::new(&prompt_id, "completed", 1.0),
+                Some(CANCELLED_ERROR) => ProgressUpdate::new(&prompt_id, "cancelled", 0.0),
+                Some(_) => ProgressUpdate::new(&prompt_id, "failed", 0.0),
+            };
+            let _ = tx.send(update).await;
+        }
+
+        Ok(execution_result(prompt_id, &outputs, error))
+    }
+
+    /// Wait for a prompt queued without `execute` (e.g. through
+    /// `queue_prompt`) to finish
+    ///
+    /// Polls `/history` until ComfyUI records the prompt. Calling
+    /// [`Self::interrupt`] with the prompt id ends the wait with a
+    /// [`CANCELLED_ERROR`] result; only fails if the history stays
+    /// unreachable.
+    pub async fn wait_for(&self, prompt_id: &str) -> Result<ExecutionResult, String> {
+        let (interrupt_tx, mut interrupted) = mpsc::channel(1);
+        self.active().insert(prompt_id.to_string(), interrupt_tx);
+
+        let mut failures = 0;
+        let outcome = loop {
+            match self.get_history(prompt_id).await {
+                Ok(history) => {
+                    failures = 0;
+                    if let Some(outcome) = history_outcome(&history, prompt_id) {
+                        break Ok(outcome);
+                    }
                 }
-                Ok(Message::Close(_)) => break,
                 Err(e) => {
-                    error = Some(format!("WebSocket error: {}", e));
-                    break;
+                    failures += 1;
+                    if failures >= HISTORY_POLL_FAILURES {
+                        break Err(e);
+                    }
+                }
+            }
+
+            tokio::select! {
+                Some(()) = interrupted.recv() => {
+                    break Ok(HistoryOutcome {
+                        outputs: HashMap::new(),
+                        error: Some(CANCELLED_ERROR.into()),
+                    });
                 }
-                _ => {}
+                () = tokio::time::sleep(HISTORY_POLL_INTERVAL) => {}
+            }
+        };
+
+        self.active().remove(prompt_id);
+        let outcome = outcome?;
+        Ok(execution_result(
+            prompt_id.to_string(),
+            &outcome.outputs,
+            outcome.error,
+        ))
+    }
+
+    /// Reconnect after the WebSocket dropped, with exponential backoff
+    ///
+    /// ComfyUI keeps running the prompt while nobody listens, so `/history`
+    /// is checked after every attempt: a prompt that finished in the
+    /// meantime is reported from there instead of waiting for messages that
+    /// were already sent. Only fails when every attempt did and the prompt
+    /// still hasn't finished.
+    async fn recover(
+        &self,
+        ws_url: &str,
+        prompt_id: &str,
+        reason: &str,
+    ) -> Result<Recovery, String> {
+        tracing::warn!(
+            "ComfyUI WebSocket dropped during prompt {}: {}",
+            prompt_id,
+            reason
+        );
+        *self.status.write().await = ConnectionStatus::Connecting;
+
+        for attempt in 0..WS_RECONNECT_ATTEMPTS {
+            tokio::time::sleep(reconnect_delay(attempt)).await;
+
+            // Reconnect before checking history, so a prompt finishing in
+            // between still reaches the new socket
+            let reader = connect_async(ws_url).await.ok().map(|(ws, _)| ws.split().1);
+            if let Some(outcome) = self.finished_outcome(prompt_id).await {
+                *self.status.write().await = ConnectionStatus::Connected;
+                return Ok(Recovery::Finished(outcome));
+            }
+            if let Some(reader) = reader {
+                tracing::info!("Reconnected to ComfyUI for prompt {}", prompt_id);
+                *self.status.write().await = ConnectionStatus::Connected;
+                return Ok(Recovery::Reconnected(reader));
             }
         }
 
-        *self.status.write().await = ConnectionStatus::Disconnected;
+        Err(format!(
+            "WebSocket error: {} (no reconnect after {} attempts)",
+            reason, WS_RECONNECT_ATTEMPTS
+        ))
+    }
 
-        // Convert outputs to JSON string for specta compatibility
-        let outputs_json = serde_json::to_string(&outputs).unwrap_or_default();
+    /// Outcome of `prompt_id` if ComfyUI's history says it finished
+    async fn finished_outcome(&self, prompt_id: &str) -> Option<HistoryOutcome> {
+        match self.get_history(prompt_id).await {
+            Ok(history) => history_outcome(&history, prompt_id),
+            Err(e) => {
+                tracing::debug!("History unavailable while reconnecting: {}", e);
+                None
+            }
+        }
+    }
 
-        Ok(ExecutionResult {
-            execution_id: prompt_id,
-            success: error.is_none(),
-            outputs_json,
-            error,
-        })
+    /// Stop a prompt: remove it from the queue if it hasn't started, or
+    /// interrupt it if it is running
+    ///
+    /// An `execute` waiting on the prompt returns a cancelled result.
+    pub async fn interrupt(&self, prompt_id: &str) -> Result<(), String> {
+        let queue = self.raw_queue().await?;
+
+        let request = match queue_position(&queue, prompt_id) {
+            QueuePosition::Pending => Some((
+                format!("{}/queue", self.config.http_url()),
+                serde_json::json!({ "delete": [prompt_id] }),
+            )),
+            // Servers that know `prompt_id` only interrupt that prompt
+            QueuePosition::Running => Some((
+                format!("{}/interrupt", self.config.http_url()),
+                serde_json::json!({ "prompt_id": prompt_id }),
+            )),
+            QueuePosition::Absent => None,
+        };
+
+        if let Some((url, body)) = request {
+            let resp = self
+                .http_client
+                .post(&url)
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| format!("Failed to interrupt prompt: {}", e))?;
+            if !resp.status().is_success() {
+                return Err(format!("ComfyUI refused to interrupt: {}", resp.status()));
+            }
+        }
+
+        if let Some(tx) = self.active().get(prompt_id) {
+            let _ = tx.try_send(());
+        }
+        Ok(())
+    }
+
+    /// Running and pending prompts, including ones queued by other clients
+    pub async fn get_queue(&self) -> Result<QueueStatus, String> {
+        let queue = self.raw_queue().await?;
+        let active = self.active();
+        Ok(queue_status(&queue, |prompt_id| {
+            active.contains_key(prompt_id)
+        }))
+    }
+
+    async fn raw_queue(&self) -> Result<serde_json::Value, String> {
+        let url = format!("{}/queue", self.config.http_url());
+        self.http_client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to get queue: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse queue: {}", e))
+    }
+
+    fn active(&self) -> MutexGuard<'_, HashMap<String, mpsc::Sender<()>>> {
+        match self.active.lock() {
+            Ok(active) => active,
+            Err(poisoned) => poisoned.into_inner(),
+        }
     }
 
     /// Get history of executions
@@ -359,28 +850,328 @@ impl ComfyUIClient {
     }
 }
 
+/// Resolves when the caller asks to cancel; never if it can't
+async fn cancel_requested(cancel: &mut Option<mpsc::Receiver<()>>) {
+    if let Some(rx) = cancel {
+        if rx.recv().await.is_some() {
+            return;
+        }
+    }
+    std::future::pending().await
+}
+
+/// Whether a WebSocket message concerns `prompt_id`; messages that name no
+/// prompt (e.g. queue status) count
+fn is_for_prompt(message: &serde_json::Value, prompt_id: &str) -> bool {
+    message["data"]["prompt_id"]
+        .as_str()
+        .is_none_or(|id| id == prompt_id)
+}
+
+/// Update for a `progress` message, as a fraction of the node's steps
+fn node_progress(prompt_id: &str, message: &serde_json::Value) -> ProgressUpdate {
+    let data = &message["data"];
+    let value = data["value"].as_f64().unwrap_or(0.0);
+    let max = data["max"].as_f64().filter(|max| *max > 0.0).unwrap_or(1.0);
+
+    ProgressUpdate {
+        execution_id: prompt_id.to_string(),
+        node_id: data["node"].as_str().unwrap_or("").to_string(),
+        progress: (value / max).clamp(0.0, 1.0) as f32,
+        status: "running".into(),
+    }
+}
+
+/// Delay before reconnect attempt `attempt` (counting from 0)
+fn reconnect_delay(attempt: u32) -> Duration {
+    WS_RECONNECT_BASE_DELAY * 2u32.pow(attempt)
+}
+
+/// How `ComfyUIClient::recover` got an execution going again
+enum Recovery {
+    /// Listening again; the prompt hasn't finished
+    Reconnected(WsReader),
+    /// The prompt finished while the socket was down
+    Finished(HistoryOutcome),
+}
+
+/// Result of a finished prompt, outputs converted to a JSON string for
+/// specta compatibility
+fn execution_result(
+    prompt_id: String,
+    outputs: &HashMap<String, OutputData>,
+    error: Option<String>,
+) -> ExecutionResult {
+    let outputs_json = serde_json::to_string(outputs).unwrap_or_default();
+    let output_files = parse_outputs(&outputs_json).unwrap_or_default();
+
+    ExecutionResult {
+        execution_id: prompt_id,
+        success: error.is_none(),
+        outputs_json,
+        output_files,
+        error,
+    }
+}
+
+/// What `/history/{prompt_id}` records about a finished prompt
+#[derive(Debug)]
+struct HistoryOutcome {
+    outputs: HashMap<String, OutputData>,
+    error: Option<String>,
+}
+
+/// Outputs and error of a finished prompt, or `None` while it is still
+/// queued or running (ComfyUI only adds prompts to history once done)
+fn history_outcome(history: &serde_json::Value, prompt_id: &str) -> Option<HistoryOutcome> {
+    let entry = history.get(prompt_id)?;
+    let status = &entry["status"];
+
+    // Messages are `[event, data]` pairs
+    let message = |event: &str| {
+        status["messages"]
+            .as_array()
+            .and_then(|messages| messages.iter().find(|m| m[0] == event))
+            .map(|m| &m[1])
+    };
+
+    let error = if message("execution_interrupted").is_some() {
+        Some(CANCELLED_ERROR.to_string())
+    } else if let Some(data) = message("execution_error") {
+        Some(
+            data.get("exception_message")
+                .and_then(|v| v.as_str())
+                .unwrap_or("Unknown error")
+                .to_string(),
+        )
+    } else if status["status_str"] == "error" {
+        Some("Unknown error".to_string())
+    } else {
+        None
+    };
+
+    let outputs = entry["outputs"]
+        .as_object()
+        .map(|nodes| {
+            nodes
+                .iter()
+                .map(|(node_id, output)| {
+                    (
+                        node_id.clone(),
+                        OutputData {
+                            node_id: node_id.clone(),
+                            output_type: "image".into(),
+                            data: output.clone(),
+                        },
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(HistoryOutcome { outputs, error })
+}
+
+/// Where a prompt is in ComfyUI's `/queue` listing
+#[derive(Debug, PartialEq)]
+enum QueuePosition {
+    Running,
+    Pending,
+    Absent,
+}
+
+/// A prompt in ComfyUI's queue
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct QueuedPrompt {
+    pub prompt_id: String,
+    /// ComfyUI's queue number; lower numbers run first
+    #[specta(type = f64)]
+    pub number: i64,
+    /// Queued by an execution on this client, so it reports progress
+    pub tracked: bool,
+}
+
+/// Snapshot of ComfyUI's `/queue`
+///
+/// Named apart from the generation queue's `QueueStatus` in the bindings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename = "ComfyUIQueueStatus")]
+pub struct QueueStatus {
+    pub running: Vec<QueuedPrompt>,
+    /// In the order they will run
+    pub pending: Vec<QueuedPrompt>,
+}
+
+fn queue_status(queue: &serde_json::Value, tracked: impl Fn(&str) -> bool) -> QueueStatus {
+    // Entries are `[number, prompt_id, prompt, extra_data, outputs]`
+    let entries = |key: &str| {
+        let mut prompts: Vec<QueuedPrompt> = queue[key]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let prompt_id = entry[1].as_str()?;
+                Some(QueuedPrompt {
+                    prompt_id: prompt_id.to_string(),
+                    number: entry[0].as_i64().unwrap_or_default(),
+                    tracked: tracked(prompt_id),
+                })
+            })
+            .collect();
+        prompts.sort_by_key(|prompt| prompt.number);
+        prompts
+    };
+
+    QueueStatus {
+        running: entries("queue_running"),
+        pending: entries("queue_pending"),
+    }
+}
+
+fn queue_position(queue: &serde_json::Value, prompt_id: &str) -> QueuePosition {
+    // Entries are `[number, prompt_id, prompt, extra_data, outputs]`
+    let listed = |key: &str| {
+        queue[key]
+            .as_array()
+            .is_some_and(|entries| entries.iter().any(|entry| entry[1] == prompt_id))
+    };
+
+    if listed("queue_running") {
+        QueuePosition::Running
+    } else if listed("queue_pending") {
+        QueuePosition::Pending
+    } else {
+        QueuePosition::Absent
+    }
+}
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // GLOBAL CLIENT (Singleton)
 // ═══════════════════════════════════════════════════════════════════════════════
 
-use std::sync::OnceLock;
+use once_cell::sync::Lazy;
 
-static COMFYUI_CLIENT: OnceLock<ComfyUIClient> = OnceLock::new();
+static COMFYUI_CLIENT: Lazy<std::sync::RwLock<Arc<ComfyUIClient>>> =
+    Lazy::new(|| std::sync::RwLock::new(Arc::new(ComfyUIClient::default_local())));
 
-/// Get or create the global ComfyUI client
-pub fn get_client() -> &'static ComfyUIClient {
-    COMFYUI_CLIENT.get_or_init(ComfyUIClient::default_local)
+/// Get the global ComfyUI client.
+///
+/// Callers keep the returned client for a whole execution, so a later
+/// [`reconfigure_client`] only affects calls made after it.
+pub fn get_client() -> Arc<ComfyUIClient> {
+    match COMFYUI_CLIENT.read() {
+        Ok(client) => client.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
 }
 
-/// Initialize with custom config
-pub fn init_client(config: ComfyUIConfig) -> &'static ComfyUIClient {
-    COMFYUI_CLIENT.get_or_init(|| ComfyUIClient::new(config))
+/// Replace the global client (e.g. after the host or port changed)
+pub fn reconfigure_client(config: ComfyUIConfig) -> Arc<ComfyUIClient> {
+    let client = Arc::new(ComfyUIClient::new(config));
+    match COMFYUI_CLIENT.write() {
+        Ok(mut current) => *current = client.clone(),
+        Err(poisoned) => *poisoned.into_inner() = client.clone(),
+    }
+    client
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_loader_options() {
+        let info = serde_json::json!({
+            "CheckpointLoaderSimple": {
+                "input": { "required": { "ckpt_name": [["flux1-dev.safetensors", "sd_xl_base_1.0.safetensors"]] } }
+            },
+            "LoraLoader": {
+                "input": { "required": {
+                    "model": ["MODEL"],
+                    "lora_name": ["COMBO", { "options": ["anna_v2.safetensors"] }]
+                } }
+            },
+            "VAELoader": {
+                "input": { "required": { "vae_name": [[]] } }
+            }
+        });
+
+        assert_eq!(
+            ModelInventory::from_object_info(&info),
+            ModelInventory {
+                checkpoints: vec![
+                    "flux1-dev.safetensors".into(),
+                    "sd_xl_base_1.0.safetensors".into()
+                ],
+                loras: vec!["anna_v2.safetensors".into()],
+                vaes: vec![],
+                controlnets: vec![],
+            }
+        );
+        assert!(loader_options(&info, "LoraLoader", "model").is_empty());
+    }
+
+    #[test]
+    fn test_missing_requirements() {
+        let info = serde_json::json!({
+            "LoadImage": {},
+            "Canny": {},
+            "KSampler": {},
+            "ControlNetLoader": {
+                "input": { "required": {
+                    "control_net_name": [["controlnet-canny-sdxl-1.0.safetensors"]]
+                } }
+            }
+        });
+        let workflow = |preprocessor: &str, controlnet: &str| {
+            serde_json::json!({
+                "3": { "class_type": "KSampler", "inputs": {} },
+                "20": { "class_type": "LoadImage", "inputs": {} },
+                "21": { "class_type": preprocessor, "inputs": {} },
+                "22": {
+                    "class_type": "ControlNetLoader",
+                    "inputs": { "control_net_name": controlnet }
+                }
+            })
+        };
+
+        assert_eq!(
+            missing_requirements(
+                &workflow("DWPreprocessor", "controlnet-openpose-sdxl-1.0.safetensors"),
+                &info
+            ),
+            [
+                "node DWPreprocessor (install comfyui_controlnet_aux)",
+                "ControlNet controlnet-openpose-sdxl-1.0.safetensors (add it to models/controlnet)"
+            ]
+        );
+        assert!(missing_requirements(
+            &workflow("Canny", "controlnet-canny-sdxl-1.0.safetensors"),
+            &info
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_check_upload() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\x04\0\0\0\x03\0";
+        assert!(check_upload(png).is_ok());
+        assert_eq!(
+            check_upload(b"<html>not found</html>"),
+            Err("Upload is not a PNG or JPEG image".to_string())
+        );
+
+        let mut huge = png.to_vec();
+        huge.resize(MAX_UPLOAD_BYTES + 1, 0);
+        assert!(check_upload(&huge).unwrap_err().contains("upload limit"));
+
+        let uploaded: UploadedImage = serde_json::from_str(
+            r#"{"name": "ref (1).png", "subfolder": "cinemaos", "type": "input"}"#,
+        )
+        .unwrap();
+        assert_eq!(uploaded.workflow_path(), "cinemaos/ref (1).png");
+    }
+
     #[test]
     fn test_config_urls() {
         let config = ComfyUIConfig::default();
@@ -398,4 +1189,122 @@ mod tests {
         assert_eq!(config.ws_url(), "wss://comfy.cloud:443/ws");
         assert_eq!(config.http_url(), "https://comfy.cloud:443");
     }
+
+    #[test]
+    fn test_reconfigure_swaps_client() {
+        let before = get_client();
+
+        reconfigure_client(ComfyUIConfig {
+            port: 8190,
+            ..ComfyUIConfig::default()
+        });
+        assert_eq!(get_client().config().http_url(), "http://127.0.0.1:8190");
+        // Clients already handed out keep their config
+        assert_eq!(before.config().http_url(), "http://127.0.0.1:8188");
+
+        reconfigure_client(ComfyUIConfig::default());
+        assert_eq!(get_client().config().http_url(), "http://127.0.0.1:8188");
+    }
+
+    #[test]
+    fn test_queue_position() {
+        let queue = serde_json::json!({
+            "queue_running": [[3, "running-id", {}, {}, []]],
+            "queue_pending": [[4, "pending-id", {}, {}, []], [5, "other-id", {}, {}, []]]
+        });
+        assert_eq!(queue_position(&queue, "running-id"), QueuePosition::Running);
+        assert_eq!(queue_position(&queue, "other-id"), QueuePosition::Pending);
+        assert_eq!(queue_position(&queue, "done-id"), QueuePosition::Absent);
+        assert_eq!(
+            queue_position(&serde_json::json!({}), "running-id"),
+            QueuePosition::Absent
+        );
+    }
+
+    #[test]
+    fn test_queue_status() {
+        let queue = serde_json::json!({
+            "queue_running": [[3, "running-id", {}, {}, []]],
+            "queue_pending": [[7, "later-id", {}, {}, []], [4, "next-id", {}, {}, []]]
+        });
+        let status = queue_status(&queue, |id| id != "later-id");
+
+        assert_eq!(status.running.len(), 1);
+        assert!(status.running[0].tracked);
+        let pending: Vec<_> = status
+            .pending
+            .iter()
+            .map(|p| (p.prompt_id.as_str(), p.tracked))
+            .collect();
+        assert_eq!(pending, [("next-id", true), ("later-id", false)]);
+
+        assert_eq!(
+            queue_status(&serde_json::json!({}), |_| true),
+            QueueStatus {
+                running: vec![],
+                pending: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_progress_routing() {
+        let progress = serde_json::json!({
+            "type": "progress",
+            "data": {"value": 5, "max": 20, "prompt_id": "a", "node": "3"}
+        });
+        assert!(is_for_prompt(&progress, "a"));
+        assert!(!is_for_prompt(&progress, "b"));
+        // Queue status messages name no prompt
+        assert!(is_for_prompt(
+            &serde_json::json!({"type": "status", "data": {}}),
+            "b"
+        ));
+
+        let update = node_progress("a", &progress);
+        assert_eq!(update.execution_id, "a");
+        assert_eq!(update.node_id, "3");
+        assert_eq!(update.progress, 0.25);
+    }
+
+    #[test]
+    fn test_history_outcome() {
+        assert_eq!(reconnect_delay(0), Duration::from_millis(500));
+        assert_eq!(reconnect_delay(3), Duration::from_secs(4));
+
+        let history = serde_json::json!({
+            "done-id": {
+                "outputs": {"9": {"images": [{"filename": "shot_00001_.png", "subfolder": "", "type": "output"}]}},
+                "status": {"status_str": "success", "completed": true, "messages": [["execution_start", {}]]}
+            },
+            "failed-id": {
+                "outputs": {},
+                "status": {
+                    "status_str": "error",
+                    "completed": false,
+                    "messages": [["execution_error", {"exception_message": "CUDA out of memory"}]]
+                }
+            },
+            "stopped-id": {
+                "outputs": {},
+                "status": {"status_str": "error", "completed": false, "messages": [["execution_interrupted", {}]]}
+            }
+        });
+
+        let done = history_outcome(&history, "done-id").unwrap();
+        assert_eq!(done.error, None);
+        assert_eq!(
+            done.outputs["9"].data["images"][0]["filename"],
+            "shot_00001_.png"
+        );
+
+        let failed = history_outcome(&history, "failed-id").unwrap();
+        assert_eq!(failed.error.as_deref(), Some("CUDA out of memory"));
+
+        let stopped = history_outcome(&history, "stopped-id").unwrap();
+        assert_eq!(stopped.error.as_deref(), Some(CANCELLED_ERROR));
+
+        // Still running: not in history yet
+        assert!(history_outcome(&serde_json::json!({}), "done-id").is_none());
+    }
 }
@@ -1,14 +1,142 @@
 //! Cost calculation for AI operations
 //!
-//! Transparent credit-based pricing
+//! Transparent credit-based pricing. Provider prices are tracked in USD and
+//! converted to credits through a single [`PricingConfig`], which mirrors the
+//! rate the backend exposes on `/api/credits`.
 
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::ai::elevenlabs_client::elevenlabs_model_id;
+use crate::ai::models::{get_all_models, ModelPricing};
+use crate::ai::providers::get_provider_for_model;
+use crate::comfyui::models::CloudModels;
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PRICING
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Default conversion rate (must match the backend default)
+pub const DEFAULT_CREDITS_PER_USD: f32 = 100.0;
+
+/// Credit conversion and margins
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct PricingConfig {
+    /// Credits charged per USD of provider cost (before markup)
+    pub credits_per_usd: f32,
+    /// Markup for providers without an explicit entry
+    pub default_markup: f32,
+    /// Per-provider markup multipliers keyed by provider ("fal", "vertex", ...)
+    pub provider_markups: HashMap<String, f32>,
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        Self {
+            credits_per_usd: DEFAULT_CREDITS_PER_USD,
+            default_markup: 1.0,
+            provider_markups: HashMap::new(),
+        }
+    }
+}
+
+impl PricingConfig {
+    /// Load from `CREDITS_PER_USD`, `PRICING_DEFAULT_MARKUP` and
+    /// `PRICING_MARKUPS` (e.g. `fal=1.2,vertex=1.1`), the same variables the backend reads
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(rate) = std::env::var("CREDITS_PER_USD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            config.credits_per_usd = rate;
+        }
+        if let Some(markup) = std::env::var("PRICING_DEFAULT_MARKUP")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            config.default_markup = markup;
+        }
+        if let Ok(markups) = std::env::var("PRICING_MARKUPS") {
+            config.provider_markups = parse_markups(&markups);
+        }
+
+        config
+    }
+
+    /// Markup multiplier for a provider
+    pub fn markup_for(&self, provider: &str) -> f32 {
+        self.provider_markups
+            .get(&provider.to_lowercase())
+            .copied()
+            .unwrap_or(self.default_markup)
+    }
+
+    /// Convert a provider cost in USD to the credits charged to the user
+    pub fn usd_to_credits(&self, usd: f32, provider: &str) -> f32 {
+        usd * self.markup_for(provider) * self.credits_per_usd
+    }
+
+    /// Convert charged credits back to the provider cost in USD
+    pub fn credits_to_usd(&self, credits: f32, provider: &str) -> f32 {
+        let rate = self.markup_for(provider) * self.credits_per_usd;
+        if rate > 0.0 {
+            credits / rate
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Parse `provider=multiplier` pairs separated by commas
+fn parse_markups(value: &str) -> HashMap<String, f32> {
+    value
+        .split(',')
+        .filter_map(|pair| {
+            let (provider, markup) = pair.split_once('=')?;
+            Some((provider.trim().to_lowercase(), markup.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+static PRICING: Lazy<RwLock<PricingConfig>> = Lazy::new(|| RwLock::new(PricingConfig::from_env()));
+
+/// Current pricing config
+pub fn pricing() -> PricingConfig {
+    PRICING.read().map(|p| p.clone()).unwrap_or_default()
+}
+
+/// Replace the pricing config (e.g. with the rate reported by `/api/credits`)
+pub fn set_pricing(config: PricingConfig) {
+    if let Ok(mut pricing) = PRICING.write() {
+        *pricing = config;
+    }
+}
+
+/// Convert a USD cost for `model_id` to credits using the current pricing
+pub fn usd_to_credits(usd: f32, model_id: &str) -> f32 {
+    pricing().usd_to_credits(usd, get_provider_for_model(model_id).pricing_key())
+}
+
+/// Convert credits charged for `model_id` back to USD using the current pricing
+pub fn credits_to_usd(credits: f32, model_id: &str) -> f32 {
+    pricing().credits_to_usd(credits, get_provider_for_model(model_id).pricing_key())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// ESTIMATES
+// ═══════════════════════════════════════════════════════════════════════════════
 
 /// Credit costs per operation
 #[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
 pub struct CostEstimate {
     /// Estimated credits
     pub credits: f32,
+    /// Underlying provider cost (USD)
+    pub usd: f32,
     /// Breakdown by service
     pub breakdown: Vec<CostItem>,
 }
@@ -25,6 +153,20 @@ pub struct CostItem {
 pub struct CostCalculator;
 
 impl CostCalculator {
+    fn estimate(model: &str, usd: f32, service: &str, operation: String) -> CostEstimate {
+        let credits = usd_to_credits(usd, model);
+
+        CostEstimate {
+            credits,
+            usd,
+            breakdown: vec![CostItem {
+                service: service.to_string(),
+                operation,
+                credits,
+            }],
+        }
+    }
+
     /// Estimate cost for image generation
     pub fn estimate_image_generation(
         model: &str,
@@ -32,7 +174,7 @@ impl CostCalculator {
         height: u32,
         steps: u32,
     ) -> CostEstimate {
-        let credits = match model {
+        let usd = match model {
             // Local models (free)
             "flux-schnell" => 0.0,
 
@@ -50,17 +192,12 @@ impl CostCalculator {
                 // Google Imagen 4 pricing
                 0.04
             }
+            // FLUX Kontext edit on Fal.ai
+            "flux-kontext" => 0.04,
             _ => 0.0,
         };
 
-        CostEstimate {
-            credits,
-            breakdown: vec![CostItem {
-                service: "Image Generation".to_string(),
-                operation: model.to_string(),
-                credits,
-            }],
-        }
+        Self::estimate(model, usd, "Image Generation", model.to_string())
     }
 
     /// Estimate cost for video generation
@@ -69,7 +206,7 @@ impl CostCalculator {
         duration_secs: f32,
         resolution: VideoResolution,
     ) -> CostEstimate {
-        let credits = match model {
+        let usd = match model {
             // Kling Video pricing: $0.112 per second
             "kling-o1" | "kling-video-2.6" => 0.112 * duration_secs,
             // Veo 3.1 (estimate)
@@ -79,34 +216,38 @@ impl CostCalculator {
             _ => 0.0,
         };
 
-        CostEstimate {
-            credits,
-            breakdown: vec![CostItem {
-                service: "Video Generation".to_string(),
-                operation: format!("{} ({} seconds, {:?})", model, duration_secs, resolution),
-                credits,
-            }],
-        }
+        Self::estimate(
+            model,
+            usd,
+            "Video Generation",
+            format!("{} ({} seconds, {:?})", model, duration_secs, resolution),
+        )
     }
 
     /// Estimate cost for TTS
+    ///
+    /// ElevenLabs ids are priced by the model they resolve to, so
+    /// `eleven-flash` costs what Flash costs rather than what v3 costs.
     pub fn estimate_tts(service: &str, characters: usize) -> CostEstimate {
-        let credits = match service {
-            // ElevenLabs v3: ~$0.30 per 1000 chars
-            "elevenlabs-v3" => (characters as f32 / 1000.0) * 0.30,
+        let usd_per_1k_chars = match service {
+            id if id.starts_with("eleven") => match elevenlabs_model_id(id) {
+                // ElevenLabs Flash / Turbo: ~$0.15 per 1000 chars
+                CloudModels::ELEVENLABS_FLASH | CloudModels::ELEVENLABS_TURBO => 0.15,
+                // ElevenLabs v3 / Multilingual v2: ~$0.30 per 1000 chars
+                _ => 0.30,
+            },
             // Kling native audio (included in video)
             "kling-native" => 0.0,
             _ => 0.0,
         };
+        let usd = (characters as f32 / 1000.0) * usd_per_1k_chars;
 
-        CostEstimate {
-            credits,
-            breakdown: vec![CostItem {
-                service: "Text-to-Speech".to_string(),
-                operation: format!("{} ({} chars)", service, characters),
-                credits,
-            }],
-        }
+        Self::estimate(
+            service,
+            usd,
+            "Text-to-Speech",
+            format!("{} ({} chars)", service, characters),
+        )
     }
 
     /// Estimate cost for LLM inference
@@ -136,23 +277,99 @@ impl CostCalculator {
 
         let prompt_cost = (prompt_tokens as f32 / 1000.0) * input_cost;
         let completion_cost = (max_completion as f32 / 1000.0) * output_cost;
-        let credits = prompt_cost + completion_cost;
+        let usd = prompt_cost + completion_cost;
 
-        CostEstimate {
-            credits,
-            breakdown: vec![CostItem {
-                service: "LLM Inference".to_string(),
-                operation: format!(
-                    "{} (~{}K tokens)",
-                    model,
-                    (prompt_tokens + max_completion) / 1000
-                ),
-                credits,
-            }],
+        Self::estimate(
+            model,
+            usd,
+            "LLM Inference",
+            format!(
+                "{} (~{}K tokens)",
+                model,
+                (prompt_tokens + max_completion) / 1000
+            ),
+        )
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// MODEL PRICING
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// IDs agents and workflows use for models listed under another ID in `models.rs`
+const PRICING_ALIASES: &[(&str, &str)] = &[
+    ("veo-3.1", "veo-3.1-vid"),
+    ("kling-video-2.6", "kling-v2.6"),
+    ("flux-2-pro", "flux-pro-2.0"),
+    ("gemini-3-pro", "gemini-3.0-pro-001"),
+    ("claude-opus-4.5", "claude-4.5-opus"),
+    ("claude-sonnet-4.5", "claude-4.5-sonnet"),
+];
+
+/// Pricing for `model_id` from the model table, if it is listed
+pub fn model_pricing(model_id: &str) -> Option<ModelPricing> {
+    let id = PRICING_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == model_id)
+        .map_or(model_id, |(_, id)| id);
+
+    get_all_models()
+        .into_iter()
+        .find(|m| m.id == id)
+        .map(|m| m.pricing)
+}
+
+/// Credits for `duration_seconds` of video from `model_id`
+///
+/// Priced per second from the model table; models not listed there use the
+/// `CostCalculator` estimate.
+pub fn estimate_video_cost(model_id: &str, duration_seconds: f32) -> f32 {
+    match model_pricing(model_id) {
+        Some(pricing) if pricing.unit_type == "second" => usd_to_credits(
+            pricing.output_cost as f32 * duration_seconds.max(0.0),
+            model_id,
+        ),
+        _ => {
+            CostCalculator::estimate_video_generation(
+                model_id,
+                duration_seconds,
+                VideoResolution::FullHD,
+            )
+            .credits
         }
     }
 }
 
+/// Credits for one image from `model_id`
+///
+/// Priced per image from the model table; models not listed there use the
+/// size-based `CostCalculator` estimate.
+pub fn estimate_image_cost(model_id: &str, width: u32, height: u32) -> f32 {
+    match model_pricing(model_id) {
+        Some(pricing) if pricing.unit_type == "image" => {
+            usd_to_credits(pricing.output_cost as f32, model_id)
+        }
+        _ => CostCalculator::estimate_image_generation(model_id, width, height, 20).credits,
+    }
+}
+
+/// Credits for an LLM call with `model_id`
+///
+/// Priced per million prompt and completion tokens from the model table;
+/// models not listed there use the `CostCalculator` estimate.
+pub fn estimate_llm_cost(model_id: &str, prompt_tokens: u32, completion_tokens: u32) -> f32 {
+    match model_pricing(model_id) {
+        Some(pricing) if pricing.unit_type == "free" => 0.0,
+        Some(pricing) if pricing.unit_type == "1M tokens" => {
+            let usd = (prompt_tokens as f64 * pricing.input_cost
+                + completion_tokens as f64 * pricing.output_cost)
+                / 1_000_000.0;
+            usd_to_credits(usd as f32, model_id)
+        }
+        _ => CostCalculator::estimate_llm(model_id, prompt_tokens, completion_tokens).credits,
+    }
+}
+
 /// Video resolution enum
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, specta::Type)]
 pub enum VideoResolution {
@@ -173,13 +390,104 @@ mod tests {
         assert_eq!(cost.credits, 0.0); // Local is free
 
         let cost = CostCalculator::estimate_image_generation("kling-image-o1", 1024, 1024, 4);
-        assert_eq!(cost.credits, 0.028); // Kling pricing
+        assert_eq!(cost.usd, 0.028); // Kling pricing
+        assert_eq!(cost.credits, usd_to_credits(0.028, "kling-image-o1"));
     }
 
     #[test]
     fn test_video_cost() {
         let cost =
             CostCalculator::estimate_video_generation("kling-o1", 5.0, VideoResolution::FullHD);
-        assert_eq!(cost.credits, 0.56); // 5s * $0.112
+        assert_eq!(cost.usd, 0.56); // 5s * $0.112
+        assert_eq!(cost.credits, usd_to_credits(0.56, "kling-o1"));
+    }
+
+    #[test]
+    fn test_tts_cost_follows_the_model() {
+        let v3 = CostCalculator::estimate_tts("elevenlabs-v3", 2000);
+        assert!((v3.usd - 0.6).abs() < 1e-6);
+        assert_eq!(v3.credits, usd_to_credits(0.6, "elevenlabs-v3"));
+
+        let flash = CostCalculator::estimate_tts("eleven-flash", 2000);
+        assert!((flash.usd - 0.3).abs() < 1e-6);
+        assert_eq!(flash.credits, usd_to_credits(0.3, "elevenlabs-flash"));
+        assert_eq!(
+            CostCalculator::estimate_tts(CloudModels::ELEVENLABS_TURBO, 2000).usd,
+            flash.usd
+        );
+        assert_eq!(CostCalculator::estimate_tts("kling-native", 2000).usd, 0.0);
+    }
+
+    #[test]
+    fn test_usd_credits_round_trip() {
+        let pricing = PricingConfig::default();
+        let credits = pricing.usd_to_credits(0.25, "fal");
+        assert!((credits - 25.0).abs() < 1e-4);
+        assert!((pricing.credits_to_usd(credits, "fal") - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_provider_markup() {
+        let pricing = PricingConfig {
+            credits_per_usd: 100.0,
+            default_markup: 1.1,
+            provider_markups: parse_markups("fal=1.5, Vertex = 1.2,broken"),
+        };
+
+        assert_eq!(pricing.provider_markups.len(), 2);
+        assert!((pricing.usd_to_credits(1.0, "fal") - 150.0).abs() < 1e-3);
+        assert!((pricing.usd_to_credits(1.0, "vertex") - 120.0).abs() < 1e-3);
+        // Unknown providers use the default markup
+        assert!((pricing.usd_to_credits(1.0, "openai") - 110.0).abs() < 1e-3);
+        // Markup survives the round trip
+        let credits = pricing.usd_to_credits(0.5, "fal");
+        assert!((pricing.credits_to_usd(credits, "fal") - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_video_cost_from_pricing_table() {
+        // Kling O1 is listed at $0.112 per second
+        let credits = estimate_video_cost("kling-o1", 5.0);
+        assert!((credits - usd_to_credits(0.56, "kling-o1")).abs() < 1e-4);
+        // Veo 3.1 is listed as veo-3.1-vid at $0.20 per second
+        let credits = estimate_video_cost("veo-3.1", 8.0);
+        assert!((credits - usd_to_credits(1.6, "veo-3.1")).abs() < 1e-4);
+        // Cost scales with duration
+        assert!(
+            (estimate_video_cost("kling-v2.6", 10.0)
+                - 2.0 * estimate_video_cost("kling-v2.6", 5.0))
+            .abs()
+                < 1e-4
+        );
+        // Unlisted models fall back to the calculator
+        assert_eq!(
+            estimate_video_cost("sora-2-pro", 4.0),
+            CostCalculator::estimate_video_generation("sora-2-pro", 4.0, VideoResolution::FullHD)
+                .credits
+        );
+    }
+
+    #[test]
+    fn test_image_cost_from_pricing_table() {
+        let credits = estimate_image_cost("flux-pro-1.1-ultra", 1024, 1024);
+        assert!((credits - usd_to_credits(0.06, "flux-pro-1.1-ultra")).abs() < 1e-4);
+        // Per image, whatever the size
+        assert_eq!(
+            estimate_image_cost("kling-image-o1", 512, 512),
+            estimate_image_cost("kling-image-o1", 2048, 2048)
+        );
+        assert_eq!(estimate_image_cost("flux-schnell", 1024, 1024), 0.0);
+    }
+
+    #[test]
+    fn test_llm_cost_from_pricing_table() {
+        // Claude 4.5 Sonnet: $3 in / $12 out per 1M tokens
+        let credits = estimate_llm_cost("claude-4.5-sonnet", 1_000_000, 500_000);
+        assert!((credits - usd_to_credits(9.0, "claude-4.5-sonnet")).abs() < 1e-2);
+        assert_eq!(estimate_llm_cost("llama-4-70b", 10_000, 10_000), 0.0);
+        assert_eq!(
+            estimate_llm_cost("gemini-3-pro", 2000, 800),
+            estimate_llm_cost("gemini-3.0-pro-001", 2000, 800)
+        );
     }
 }
@@ -5,6 +5,7 @@
 
 use crate::ai::{
     llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest},
+    structured_output::{parse_structured_output, with_structured_output},
     templates::inject_context,
     Agent, AgentAction, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
     ProcessingLocation,
@@ -129,7 +130,8 @@ impl Agent for ArtDirector {
         let start_time = Instant::now();
         let llm = get_llm_client();
 
-        let system_prompt = inject_context(ART_DIRECTOR_SYSTEM_PROMPT, &context);
+        let system_prompt =
+            with_structured_output(inject_context(ART_DIRECTOR_SYSTEM_PROMPT, &context));
 
         let request = LLMRequest {
             provider: self.llm_provider.clone(),
@@ -137,6 +139,7 @@ impl Agent for ArtDirector {
             messages: vec![LLMMessage {
                 role: "user".to_string(),
                 content: message.to_string(),
+                images: Vec::new(),
             }],
             temperature: Some(0.7),
             max_tokens: Some(1200),
@@ -148,15 +151,18 @@ impl Agent for ArtDirector {
             .await
             .map_err(AgentError::ProcessingFailed)?;
 
-        let actions = vec![AgentAction::Generate3D {
+        let mut output = parse_structured_output(&response.content, &context);
+
+        let actions = output.take_actions_or(vec![AgentAction::Generate3D {
             prompt: message.to_string(),
             model: "meshy".to_string(),
-        }];
+        }]);
 
         Ok(AgentResponse {
             agent: self.name().to_string(),
-            content: format!("**🎨 Art Direction**\n\n{}", response.content),
+            content: format!("**🎨 Art Direction**\n\n{}", output.message),
             actions,
+            citations: output.citations,
             cost: Some(0.005),
             metadata: AgentMetadata {
                 model: self.get_model_name(),
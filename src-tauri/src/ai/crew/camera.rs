@@ -4,8 +4,11 @@
 //! Supports Veo 3.1, Sora 2 Pro, and Kling v2.6 (all with native audio).
 
 use crate::ai::{
+    cost::{estimate_llm_cost, estimate_video_cost},
     llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest},
+    structured_output::{parse_structured_output, with_structured_output},
     templates::inject_context,
+    token_budget::estimate_tokens,
     Agent, AgentAction, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
     ProcessingLocation,
 };
@@ -51,6 +54,13 @@ Help create compelling video content by providing:
 - Model-specific optimizations
 "#;
 
+/// Model and length of the shot suggested when the LLM picks none
+const DEFAULT_VIDEO_MODEL: &str = "veo-3.1";
+const DEFAULT_SHOT_SECONDS: f32 = 5.0;
+
+/// Completion budget for the shot breakdown
+const MAX_RESPONSE_TOKENS: u32 = 800;
+
 pub struct CameraDirector {
     llm_provider: LLMProvider,
     llm_model: Option<String>,
@@ -97,7 +107,7 @@ impl Agent for CameraDirector {
         "Camera Director"
     }
     fn capability(&self) -> AgentCapability {
-        AgentCapability::VideoDirection
+        AgentCapability::Videography
     }
     fn description(&self) -> &str {
         "Video generation and camera movement specialist - Veo 3.1, Sora 2, Kling v2.6"
@@ -111,7 +121,8 @@ impl Agent for CameraDirector {
         let start_time = Instant::now();
         let llm = get_llm_client();
 
-        let system_prompt = inject_context(CAMERA_DIRECTOR_SYSTEM_PROMPT, &context);
+        let system_prompt =
+            with_structured_output(inject_context(CAMERA_DIRECTOR_SYSTEM_PROMPT, &context));
 
         let user_message = format!(
             "User wants to generate a video:\n\n\"{}\"\n\n\
@@ -126,15 +137,19 @@ impl Agent for CameraDirector {
             message
         );
 
+        // Fallback for providers that don't report usage
+        let prompt_tokens = estimate_tokens(&system_prompt) + estimate_tokens(&user_message);
+
         let request = LLMRequest {
             provider: self.llm_provider.clone(),
             model: self.llm_model.clone().unwrap_or_default(),
             messages: vec![LLMMessage {
                 role: "user".to_string(),
                 content: user_message,
+                images: Vec::new(),
             }],
             temperature: Some(0.7),
-            max_tokens: Some(800),
+            max_tokens: Some(MAX_RESPONSE_TOKENS),
             system_prompt: Some(system_prompt),
         };
 
@@ -143,34 +158,44 @@ impl Agent for CameraDirector {
             .await
             .map_err(AgentError::ProcessingFailed)?;
 
+        let mut output = parse_structured_output(&response.content, &context);
+        let shot_prompt = output.message.clone();
+
         // Suggest video generation action with best model
-        let actions = vec![
+        let actions = output.take_actions_or(vec![
             AgentAction::GenerateVideo {
-                prompt: response.content.clone(),
-                model: "veo-3.1".to_string(),
-                duration_seconds: 5.0,
+                prompt: shot_prompt.clone(),
+                model: DEFAULT_VIDEO_MODEL.to_string(),
+                duration_seconds: DEFAULT_SHOT_SECONDS,
                 reference_image: None,
                 token_ids: vec![],
             },
             AgentAction::GenerateVideo {
-                prompt: response.content.clone(),
+                prompt: shot_prompt,
                 model: "kling-v2.5-turbo".to_string(),
-                duration_seconds: 5.0,
+                duration_seconds: DEFAULT_SHOT_SECONDS,
                 reference_image: None,
                 token_ids: vec![],
             },
-        ];
+        ]);
+
+        let model = self.get_model_name();
+        let cost = match &response.usage {
+            Some(u) => estimate_llm_cost(&model, u.prompt_tokens, u.completion_tokens),
+            None => estimate_llm_cost(&model, prompt_tokens, estimate_tokens(&response.content)),
+        };
 
         Ok(AgentResponse {
             agent: self.name().to_string(),
             content: format!(
                 "**🎬 Video Direction**\n\n{}\n\n---\n*Ready to generate with Veo 3.1 (quality) or Kling v2.5 Turbo (fast preview)*",
-                response.content
+                output.message
             ),
             actions,
-            cost: Some(0.005),
+            citations: output.citations,
+            cost: Some(cost),
             metadata: AgentMetadata {
-                model: self.get_model_name(),
+                model,
                 processing_time_ms: start_time.elapsed().as_millis() as u64,
                 tokens: response.usage.map(|u| crate::ai::TokenUsage {
                     prompt: u.prompt_tokens,
@@ -186,15 +211,12 @@ impl Agent for CameraDirector {
         })
     }
 
-    async fn estimate_cost(&self, _message: &str) -> f32 {
-        match self.llm_provider {
-            LLMProvider::Ollama => 0.0,
-            LLMProvider::LlamaStack => 0.0,
-            LLMProvider::Gemini => 0.005,   // Gemini 3 Pro
-            LLMProvider::OpenAI => 0.015,   // GPT-4o
-            LLMProvider::Anthropic => 0.02, // Claude
-            LLMProvider::VertexAI => 0.005, // Vertex AI
-        }
+    async fn estimate_cost(&self, message: &str) -> f32 {
+        // The shot breakdown plus the default shot it suggests
+        let prompt_tokens =
+            estimate_tokens(CAMERA_DIRECTOR_SYSTEM_PROMPT) + estimate_tokens(message);
+        estimate_llm_cost(&self.get_model_name(), prompt_tokens, MAX_RESPONSE_TOKENS)
+            + estimate_video_cost(DEFAULT_VIDEO_MODEL, DEFAULT_SHOT_SECONDS)
     }
 }
 
@@ -206,7 +228,7 @@ mod tests {
     fn test_agent_creation() {
         let agent = CameraDirector::new();
         assert_eq!(agent.name(), "Camera Director");
-        assert_eq!(agent.capability(), AgentCapability::VideoDirection);
+        assert_eq!(agent.capability(), AgentCapability::Videography);
     }
 
     #[test]
@@ -214,4 +236,16 @@ mod tests {
         let agent = CameraDirector::new();
         assert_eq!(agent.get_model_name(), "gemini-3-pro");
     }
+
+    #[tokio::test]
+    async fn test_estimate_includes_the_suggested_shot() {
+        let agent = CameraDirector::new();
+        let estimate = agent.estimate_cost("A slow push-in on the detective").await;
+        let shot = estimate_video_cost(DEFAULT_VIDEO_MODEL, DEFAULT_SHOT_SECONDS);
+        assert!(shot > 0.0);
+        assert!(estimate > shot);
+
+        let local = CameraDirector::new().with_llm(LLMProvider::Ollama, None);
+        assert_eq!(local.estimate_cost("A slow push-in").await, shot);
+    }
 }
@@ -5,6 +5,7 @@
 
 use crate::ai::{
     llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest},
+    structured_output::{parse_structured_output, with_structured_output},
     templates::inject_context,
     Agent, AgentAction, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
     ProcessingLocation,
@@ -132,7 +133,8 @@ impl Agent for CastingDirector {
         let start_time = Instant::now();
         let llm = get_llm_client();
 
-        let system_prompt = inject_context(CASTING_DIRECTOR_SYSTEM_PROMPT, &context);
+        let system_prompt =
+            with_structured_output(inject_context(CASTING_DIRECTOR_SYSTEM_PROMPT, &context));
 
         let request = LLMRequest {
             provider: self.llm_provider.clone(),
@@ -140,6 +142,7 @@ impl Agent for CastingDirector {
             messages: vec![LLMMessage {
                 role: "user".to_string(),
                 content: message.to_string(),
+                images: Vec::new(),
             }],
             temperature: Some(0.3), // Lower for consistency
             max_tokens: Some(1000),
@@ -151,17 +154,20 @@ impl Agent for CastingDirector {
             .await
             .map_err(AgentError::ProcessingFailed)?;
 
+        let mut output = parse_structured_output(&response.content, &context);
+
         // Actions for character consistency tools
-        let actions = vec![AgentAction::SegmentAsset {
+        let actions = output.take_actions_or(vec![AgentAction::SegmentAsset {
             prompt: "character".to_string(), // Inferred prompt
             model: "sam-3".to_string(),
             mode: "auto".to_string(),
-        }];
+        }]);
 
         Ok(AgentResponse {
             agent: self.name().to_string(),
-            content: format!("**🎭 Casting Direction**\n\n{}", response.content),
+            content: format!("**🎭 Casting Direction**\n\n{}", output.message),
             actions,
+            citations: output.citations,
             cost: Some(0.005),
             metadata: AgentMetadata {
                 model: self.get_model_name(),
@@ -4,6 +4,7 @@
 
 use crate::ai::{
     llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest},
+    structured_output::{parse_structured_output, with_structured_output},
     templates::inject_context,
     Agent, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
     ProcessingLocation,
@@ -106,7 +107,8 @@ impl Agent for Cinematographer {
         let start_time = Instant::now();
         let llm = get_llm_client();
 
-        let system_prompt = inject_context(CINEMATOGRAPHER_SYSTEM_PROMPT, &context);
+        let system_prompt =
+            with_structured_output(inject_context(CINEMATOGRAPHER_SYSTEM_PROMPT, &context));
 
         let request = LLMRequest {
             provider: self.llm_provider.clone(),
@@ -114,6 +116,7 @@ impl Agent for Cinematographer {
             messages: vec![LLMMessage {
                 role: "user".to_string(),
                 content: message.to_string(),
+                images: Vec::new(),
             }],
             temperature: Some(0.7),
             max_tokens: Some(1000),
@@ -125,10 +128,13 @@ impl Agent for Cinematographer {
             .await
             .map_err(AgentError::ProcessingFailed)?;
 
+        let output = parse_structured_output(&response.content, &context);
+
         Ok(AgentResponse {
             agent: self.name().to_string(),
-            content: format!("**📷 Cinematography**\n\n{}", response.content),
-            actions: vec![],
+            content: format!("**📷 Cinematography**\n\n{}", output.message),
+            actions: output.actions,
+            citations: output.citations,
             cost: Some(0.005),
             metadata: AgentMetadata {
                 model: self.get_model_name(),
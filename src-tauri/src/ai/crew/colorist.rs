@@ -5,6 +5,7 @@
 
 use crate::ai::{
     llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest},
+    structured_output::{parse_structured_output, with_structured_output},
     templates::inject_context,
     Agent, AgentAction, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
     ProcessingLocation,
@@ -120,7 +121,8 @@ impl Agent for Colorist {
         let start_time = Instant::now();
         let llm = get_llm_client();
 
-        let system_prompt = inject_context(COLORIST_SYSTEM_PROMPT, &context);
+        let system_prompt =
+            with_structured_output(inject_context(COLORIST_SYSTEM_PROMPT, &context));
 
         let request = LLMRequest {
             provider: self.llm_provider.clone(),
@@ -128,6 +130,7 @@ impl Agent for Colorist {
             messages: vec![LLMMessage {
                 role: "user".to_string(),
                 content: message.to_string(),
+                images: Vec::new(),
             }],
             temperature: Some(0.5), // Precise
             max_tokens: Some(800),
@@ -139,15 +142,18 @@ impl Agent for Colorist {
             .await
             .map_err(AgentError::ProcessingFailed)?;
 
-        let actions = vec![AgentAction::ApplyColorGrade {
+        let mut output = parse_structured_output(&response.content, &context);
+
+        let actions = output.take_actions_or(vec![AgentAction::ApplyColorGrade {
             model: "kling-ai-colourist".to_string(),
             style: "cinematic".to_string(),
-        }];
+        }]);
 
         Ok(AgentResponse {
             agent: self.name().to_string(),
-            content: format!("**🎨 Color Grading**\n\n{}", response.content),
+            content: format!("**🎨 Color Grading**\n\n{}", output.message),
             actions,
+            citations: output.citations,
             cost: Some(0.003),
             metadata: AgentMetadata {
                 model: self.get_model_name(),
@@ -1,16 +1,27 @@
 //! Main Agent - The Orchestrator
 //!
-//! Routes user requests to specialized agents based on intent
+//! Routes user requests to specialized agents based on intent, then follows
+//! `Delegate` actions so one agent can pull in another department.
 
 use super::{
     ArtDirector, CameraDirector, CastingDirector, Cinematographer, Colorist, Editor,
     MusicSFXDirector, PhotographyDirector, Scriptwriter, Showrunner, VoiceActors,
 };
+use crate::ai::agent_executor::get_agent_executor;
+use crate::ai::agents::{
+    routing::{route_message, RoutingTable},
+    AgentRole,
+};
 use crate::ai::{
-    Agent, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
-    ProcessingLocation,
+    Agent, AgentAction, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
+    ProcessingLocation, TokenUsage,
 };
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// Most agents one request is handed on to, so two agents can't ping-pong
+pub const MAX_DELEGATIONS: usize = 3;
 
 /// Main Agent - Orchestrates the Virtual Crew
 pub struct MainAgent {
@@ -45,135 +56,56 @@ impl MainAgent {
         }
     }
 
-    /// Route message to appropriate agent(s)
+    /// Route message to appropriate agent(s), one combined response for the chain
     pub async fn route(
         &self,
         message: &str,
         context: AgentContext,
     ) -> Result<AgentResponse, AgentError> {
-        let intent = self.parse_intent(message);
-
-        match intent {
-            Intent::Script => self.scriptwriter.process(message, context).await,
-            Intent::Visual => self.photography.process(message, context).await,
-            Intent::Video => self.camera.process(message, context).await,
-            Intent::Character => self.casting_director.process(message, context).await,
-            Intent::Location => self.art_director.process(message, context).await,
-            Intent::Audio => self.music_sfx.process(message, context).await,
-            Intent::Voice => self.voice_actors.process(message, context).await,
-            Intent::Composition => self.cinematographer.process(message, context).await,
-            Intent::Editing => self.editor.process(message, context).await,
-            Intent::ColorGrading => self.colorist.process(message, context).await,
-            Intent::ProjectOverview => self.showrunner.process(message, context).await,
-            Intent::Unknown => {
-                // Default to Main Agent response
-                self.process(message, context).await
-            }
-        }
+        Ok(self.route_chain(message, context).await?.combined())
     }
 
-    /// Parse user intent from message
-    fn parse_intent(&self, message: &str) -> Intent {
-        let msg_lower = message.to_lowercase();
-
-        // Keyword-based intent classification (TODO: Use LLM for better accuracy)
-
-        // Script/Writing
-        if msg_lower.contains("script")
-            || msg_lower.contains("dialogue")
-            || msg_lower.contains("scene")
-            || msg_lower.contains("write")
-        {
-            return Intent::Script;
-        }
-
-        // Visual (Images)
-        if msg_lower.contains("image")
-            || msg_lower.contains("picture")
-            || msg_lower.contains("photo")
-            || msg_lower.contains("concept art")
-            || msg_lower.contains("storyboard")
-        {
-            return Intent::Visual;
-        }
-
-        // Video
-        if msg_lower.contains("video")
-            || msg_lower.contains("shot")
-            || msg_lower.contains("footage")
-            || msg_lower.contains("clip")
-        {
-            return Intent::Video;
-        }
-
-        // Character
-        if msg_lower.contains("character")
-            || msg_lower.contains("actor")
-            || msg_lower.contains("casting")
-            || msg_lower.contains("face")
-        {
-            return Intent::Character;
-        }
-
-        // Location/Props
-        if msg_lower.contains("location")
-            || msg_lower.contains("set")
-            || msg_lower.contains("prop")
-            || msg_lower.contains("environment")
-        {
-            return Intent::Location;
-        }
-
-        // Audio/Music
-        if msg_lower.contains("music")
-            || msg_lower.contains("sound")
-            || msg_lower.contains("audio")
-            || msg_lower.contains("sfx")
-        {
-            return Intent::Audio;
-        }
-
-        // Voice
-        if msg_lower.contains("voice")
-            || msg_lower.contains("tts")
-            || msg_lower.contains("narration")
-        {
-            return Intent::Voice;
-        }
-
-        // Camera/Composition
-        if msg_lower.contains("camera")
-            || msg_lower.contains("lens")
-            || msg_lower.contains("lighting")
-            || msg_lower.contains("composition")
-        {
-            return Intent::Composition;
-        }
-
-        // Editing
-        if msg_lower.contains("edit")
-            || msg_lower.contains("cut")
-            || msg_lower.contains("montage")
-            || msg_lower.contains("transition")
-        {
-            return Intent::Editing;
-        }
+    /// Route message and follow delegations, keeping every agent's response
+    pub async fn route_chain(
+        &self,
+        message: &str,
+        context: AgentContext,
+    ) -> Result<DelegationChain, AgentError> {
+        // The LLM router, or the keyword table when it is offline
+        let role = get_agent_executor().route_request_llm(message).await;
+        follow_delegations(self.agent(role), message, context, |role| self.agent(role)).await
+    }
 
-        // Color grading
-        if msg_lower.contains("color") || msg_lower.contains("grade") || msg_lower.contains("lut") {
-            return Intent::ColorGrading;
+    /// The specialist for a role
+    pub fn agent(&self, role: AgentRole) -> &dyn Agent {
+        match role {
+            AgentRole::Showrunner => &self.showrunner,
+            AgentRole::Scriptwriter => &self.scriptwriter,
+            AgentRole::Cinematographer => &self.cinematographer,
+            AgentRole::CastingDirector => &self.casting_director,
+            AgentRole::ArtDirector => &self.art_director,
+            AgentRole::VoiceActors => &self.voice_actors,
+            AgentRole::MusicSfxDirector => &self.music_sfx,
+            AgentRole::PhotographyDirector => &self.photography,
+            AgentRole::CameraDirector => &self.camera,
+            AgentRole::Editor => &self.editor,
+            AgentRole::Colorist => &self.colorist,
         }
+    }
 
-        // Project overview
-        if msg_lower.contains("overview")
-            || msg_lower.contains("consistency")
-            || msg_lower.contains("vault")
-            || msg_lower.contains("project")
-        {
-            return Intent::ProjectOverview;
-        }
+    /// Parse user intent from message using the shared routing table
+    pub fn parse_intent(&self, message: &str) -> Intent {
+        route_message(message)
+            .map(Intent::from)
+            .unwrap_or(Intent::Unknown)
+    }
 
-        Intent::Unknown
+    /// `parse_intent` with the given table instead of the shared one
+    pub fn parse_intent_with(&self, table: &RoutingTable, message: &str) -> Intent {
+        table
+            .route(message)
+            .map(Intent::from)
+            .unwrap_or(Intent::Unknown)
     }
 
     /// Get all available agents
@@ -236,6 +168,7 @@ impl Agent for MainAgent {
             agent: self.name().to_string(),
             content,
             actions,
+            citations: vec![],
             cost: Some(0.0),
             metadata: AgentMetadata {
                 model: "keyword-based-router".to_string(),
@@ -247,9 +180,177 @@ impl Agent for MainAgent {
     }
 }
 
+/// Responses of every agent that worked on one request, in order
+#[derive(Debug, Clone)]
+pub struct DelegationChain {
+    pub responses: Vec<AgentResponse>,
+}
+
+impl DelegationChain {
+    /// Agents that contributed, in order
+    pub fn contributors(&self) -> Vec<&str> {
+        self.responses.iter().map(|r| r.agent.as_str()).collect()
+    }
+
+    /// Credits spent across the chain
+    pub fn total_cost(&self) -> f32 {
+        self.responses.iter().filter_map(|r| r.cost).sum()
+    }
+
+    /// One response crediting each contributor, with everyone's actions and
+    /// citations (the delegations already followed are dropped)
+    pub fn combined(mut self) -> AgentResponse {
+        if self.responses.len() == 1 {
+            return self.responses.remove(0);
+        }
+
+        let agent = self.contributors().join(" → ");
+        let cost = self
+            .responses
+            .iter()
+            .any(|r| r.cost.is_some())
+            .then(|| self.total_cost());
+        let last = self.responses.len() - 1;
+
+        let mut content = Vec::new();
+        let mut actions = Vec::new();
+        let mut citations = Vec::new();
+        let mut models = Vec::new();
+        let mut processing_time_ms = 0;
+        let mut tokens: Option<TokenUsage> = None;
+        let mut location = ProcessingLocation::Local;
+
+        for (i, response) in self.responses.into_iter().enumerate() {
+            content.push(format!("**{}:**\n{}", response.agent, response.content));
+
+            let mut delegated = i < last;
+            for action in response.actions {
+                // Only the first delegation was followed
+                if delegated && matches!(action, AgentAction::Delegate { .. }) {
+                    delegated = false;
+                    continue;
+                }
+                actions.push(action);
+            }
+            for citation in response.citations {
+                if !citations.contains(&citation) {
+                    citations.push(citation);
+                }
+            }
+
+            if !models.contains(&response.metadata.model) {
+                models.push(response.metadata.model);
+            }
+            processing_time_ms += response.metadata.processing_time_ms;
+            if let Some(usage) = response.metadata.tokens {
+                let total = tokens.get_or_insert(TokenUsage {
+                    prompt: 0,
+                    completion: 0,
+                    total: 0,
+                });
+                total.prompt += usage.prompt;
+                total.completion += usage.completion;
+                total.total += usage.total;
+            }
+            if matches!(response.metadata.location, ProcessingLocation::Cloud) {
+                location = ProcessingLocation::Cloud;
+            }
+        }
+
+        AgentResponse {
+            agent,
+            content: content.join("\n\n"),
+            actions,
+            citations,
+            cost,
+            metadata: AgentMetadata {
+                model: models.join(", "),
+                processing_time_ms,
+                tokens,
+                location,
+            },
+        }
+    }
+}
+
+/// Run `first`, then hand the request on while agents return `Delegate`
+/// actions, up to `MAX_DELEGATIONS` times and never back to an agent that
+/// already answered
+async fn follow_delegations<'a>(
+    first: &'a dyn Agent,
+    message: &str,
+    context: AgentContext,
+    agent_for: impl Fn(AgentRole) -> &'a dyn Agent,
+) -> Result<DelegationChain, AgentError> {
+    let mut responses: Vec<AgentResponse> = Vec::new();
+    let mut agent = first;
+    let mut message = message.to_string();
+
+    loop {
+        let response = agent.process(&message, context.clone()).await?;
+        let delegation = response.actions.iter().find_map(|action| match action {
+            AgentAction::Delegate {
+                target_agent,
+                message,
+            } => Some((target_agent.clone(), message.clone())),
+            _ => None,
+        });
+        responses.push(response);
+
+        let Some((target, next_message)) = delegation else {
+            break;
+        };
+        if responses.len() > MAX_DELEGATIONS {
+            tracing::warn!("Delegation limit reached, not forwarding to {}", target);
+            break;
+        }
+        let Some(role) = parse_agent_name(&target) else {
+            tracing::warn!("Cannot delegate to unknown agent: {}", target);
+            break;
+        };
+        let next = agent_for(role);
+        if responses.iter().any(|r| r.agent == next.name()) {
+            tracing::debug!("{} already answered, not delegating back", next.name());
+            break;
+        }
+
+        tracing::info!("{} delegated to {}", agent.name(), next.name());
+        agent = next;
+        message = next_message;
+    }
+
+    Ok(DelegationChain { responses })
+}
+
+/// Role for an agent name as models write it ("CastingDirector",
+/// "casting_director", "The Casting Director", "casting")
+fn parse_agent_name(name: &str) -> Option<AgentRole> {
+    let normalize = |s: &str| -> String {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    };
+    let name = normalize(
+        name.trim()
+            .trim_start_matches("The ")
+            .trim_start_matches("the "),
+    );
+    // Short prefixes are ambiguous ("c" could be four agents)
+    if name.len() < 3 {
+        return None;
+    }
+
+    AgentRole::all().iter().copied().find(|role| {
+        let variant = normalize(&format!("{:?}", role));
+        let display = normalize(role.display_name().trim_start_matches("The "));
+        name == variant || name == display || variant.starts_with(&name)
+    })
+}
+
 /// User intent classification
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Intent {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub enum Intent {
     Script,          // Scriptwriter
     Visual,          // Photography Director
     Video,           // Camera Director
@@ -264,24 +365,227 @@ enum Intent {
     Unknown,         // Main Agent (ask for clarification)
 }
 
+impl From<AgentRole> for Intent {
+    fn from(role: AgentRole) -> Self {
+        match role {
+            AgentRole::Scriptwriter => Intent::Script,
+            AgentRole::PhotographyDirector => Intent::Visual,
+            AgentRole::CameraDirector => Intent::Video,
+            AgentRole::CastingDirector => Intent::Character,
+            AgentRole::ArtDirector => Intent::Location,
+            AgentRole::MusicSfxDirector => Intent::Audio,
+            AgentRole::VoiceActors => Intent::Voice,
+            AgentRole::Cinematographer => Intent::Composition,
+            AgentRole::Editor => Intent::Editing,
+            AgentRole::Colorist => Intent::ColorGrading,
+            AgentRole::Showrunner => Intent::ProjectOverview,
+        }
+    }
+}
+
+impl Intent {
+    /// Agent that handles this intent (`None` for `Unknown`)
+    pub fn role(self) -> Option<AgentRole> {
+        match self {
+            Intent::Script => Some(AgentRole::Scriptwriter),
+            Intent::Visual => Some(AgentRole::PhotographyDirector),
+            Intent::Video => Some(AgentRole::CameraDirector),
+            Intent::Character => Some(AgentRole::CastingDirector),
+            Intent::Location => Some(AgentRole::ArtDirector),
+            Intent::Audio => Some(AgentRole::MusicSfxDirector),
+            Intent::Voice => Some(AgentRole::VoiceActors),
+            Intent::Composition => Some(AgentRole::Cinematographer),
+            Intent::Editing => Some(AgentRole::Editor),
+            Intent::ColorGrading => Some(AgentRole::Colorist),
+            Intent::ProjectOverview => Some(AgentRole::Showrunner),
+            Intent::Unknown => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ai::agents::VirtualCrew;
 
     #[test]
     fn test_intent_parsing() {
         let agent = MainAgent::new();
+        let table = RoutingTable::default();
+        let intent = |message: &str| agent.parse_intent_with(&table, message);
+
+        assert_eq!(intent("Write a scene"), Intent::Script);
+        assert_eq!(intent("Generate an image of John"), Intent::Visual);
+        assert_eq!(intent("Create a video shot"), Intent::Video);
+        assert_eq!(intent("Tell me about my characters"), Intent::Character);
+        assert_eq!(intent("Add background music"), Intent::Audio);
+    }
+
+    #[test]
+    fn test_routers_agree() {
+        let agent = MainAgent::new();
+        let crew = VirtualCrew::new();
+
+        // A user-tuned table, not whatever is saved on this machine
+        let mut table = RoutingTable::default();
+        table.add_keywords(AgentRole::ArtDirector, &["matte painting"]);
+
+        let mut messages: Vec<String> = table
+            .rules
+            .iter()
+            .flat_map(|rule| rule.keywords.iter().map(|k| format!("Help with the {}", k)))
+            .collect();
+        messages.push("What should we do next?".into());
+
+        for message in &messages {
+            let from_main = agent
+                .parse_intent_with(&table, message)
+                .role()
+                .unwrap_or(AgentRole::Showrunner);
+            assert_eq!(from_main, crew.route_with(&table, message), "{}", message);
+        }
+        assert_eq!(
+            crew.route_with(&table, "Help with the matte painting"),
+            AgentRole::ArtDirector
+        );
+    }
+
+    struct MockAgent {
+        name: &'static str,
+        delegate_to: Option<&'static str>,
+        cost: f32,
+    }
+
+    #[async_trait]
+    impl Agent for MockAgent {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn capability(&self) -> AgentCapability {
+            AgentCapability::Showrunning
+        }
+
+        fn description(&self) -> &str {
+            "Test agent"
+        }
+
+        async fn process(
+            &self,
+            message: &str,
+            _context: AgentContext,
+        ) -> Result<AgentResponse, AgentError> {
+            let actions = self
+                .delegate_to
+                .map(|target| AgentAction::Delegate {
+                    target_agent: target.to_string(),
+                    message: format!("Describe the look of {}", message),
+                })
+                .into_iter()
+                .collect();
+            Ok(AgentResponse {
+                agent: self.name.to_string(),
+                content: format!("{} on: {}", self.name, message),
+                actions,
+                citations: vec![],
+                cost: Some(self.cost),
+                metadata: AgentMetadata {
+                    model: "mock".to_string(),
+                    processing_time_ms: 10,
+                    tokens: None,
+                    location: ProcessingLocation::Cloud,
+                },
+            })
+        }
+    }
 
-        assert_eq!(agent.parse_intent("Write a scene"), Intent::Script);
+    fn context() -> AgentContext {
+        AgentContext {
+            script: None,
+            canvas: None,
+            timeline: None,
+            vault: None,
+            mode: "writer".into(),
+            project_name: None,
+            project_id: None,
+            project_memory: None,
+            preferences: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scriptwriter_delegates_to_casting_director() {
+        let scriptwriter = MockAgent {
+            name: "Scriptwriter",
+            delegate_to: Some("casting_director"),
+            cost: 0.5,
+        };
+        let casting = MockAgent {
+            name: "Casting Director",
+            delegate_to: None,
+            cost: 0.25,
+        };
+        let agent_for = |role: AgentRole| match role {
+            AgentRole::CastingDirector => &casting as &dyn Agent,
+            _ => &scriptwriter as &dyn Agent,
+        };
+
+        let chain = follow_delegations(&scriptwriter, "ANNA", context(), agent_for)
+            .await
+            .unwrap();
+        assert_eq!(chain.contributors(), ["Scriptwriter", "Casting Director"]);
+        assert_eq!(chain.total_cost(), 0.75);
+        assert_eq!(
+            chain.responses[1].content,
+            "Casting Director on: Describe the look of ANNA"
+        );
+
+        let combined = chain.combined();
+        assert_eq!(combined.agent, "Scriptwriter → Casting Director");
+        assert_eq!(combined.cost, Some(0.75));
+        assert!(combined.content.starts_with("**Scriptwriter:**"));
+        assert!(combined.content.contains("**Casting Director:**"));
+        assert!(combined.actions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delegation_does_not_loop() {
+        let scriptwriter = MockAgent {
+            name: "Scriptwriter",
+            delegate_to: Some("Casting Director"),
+            cost: 0.0,
+        };
+        let casting = MockAgent {
+            name: "Casting Director",
+            delegate_to: Some("Scriptwriter"),
+            cost: 0.0,
+        };
+        let agent_for = |role: AgentRole| match role {
+            AgentRole::CastingDirector => &casting as &dyn Agent,
+            _ => &scriptwriter as &dyn Agent,
+        };
+
+        let chain = follow_delegations(&scriptwriter, "ANNA", context(), agent_for)
+            .await
+            .unwrap();
+        assert_eq!(chain.contributors(), ["Scriptwriter", "Casting Director"]);
+        // The unfollowed delegation stays for the user to act on
+        assert_eq!(chain.combined().actions.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_agent_name() {
         assert_eq!(
-            agent.parse_intent("Generate an image of John"),
-            Intent::Visual
+            parse_agent_name("casting_director"),
+            Some(AgentRole::CastingDirector)
         );
-        assert_eq!(agent.parse_intent("Create a video shot"), Intent::Video);
         assert_eq!(
-            agent.parse_intent("Tell me about my characters"),
-            Intent::Character
+            parse_agent_name("The Art Director"),
+            Some(AgentRole::ArtDirector)
         );
-        assert_eq!(agent.parse_intent("Add background music"), Intent::Audio);
+        assert_eq!(parse_agent_name("camera"), Some(AgentRole::CameraDirector));
+        assert_eq!(parse_agent_name("Showrunner"), Some(AgentRole::Showrunner));
+        assert_eq!(parse_agent_name("gaffer"), None);
+        assert_eq!(parse_agent_name("c"), None);
     }
 }
@@ -6,6 +6,7 @@
 use crate::ai::actions::AudioActionType;
 use crate::ai::{
     llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest},
+    structured_output::{parse_structured_output, with_structured_output},
     templates::inject_context,
     Agent, AgentAction, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
     ProcessingLocation,
@@ -99,7 +100,7 @@ impl Agent for MusicSFXDirector {
         "Music & SFX Director"
     }
     fn capability(&self) -> AgentCapability {
-        AgentCapability::MusicSFX
+        AgentCapability::AudioProduction
     }
     fn description(&self) -> &str {
         "Score, foley, and sound design - Lyria 2, Suno, Beatoven"
@@ -113,7 +114,8 @@ impl Agent for MusicSFXDirector {
         let start_time = Instant::now();
         let llm = get_llm_client();
 
-        let system_prompt = inject_context(MUSIC_SFX_SYSTEM_PROMPT, &context);
+        let system_prompt =
+            with_structured_output(inject_context(MUSIC_SFX_SYSTEM_PROMPT, &context));
 
         let user_message = format!(
             "User needs audio/music for:\n\n\"{}\"\n\n\
@@ -133,6 +135,7 @@ impl Agent for MusicSFXDirector {
             messages: vec![LLMMessage {
                 role: "user".to_string(),
                 content: user_message,
+                images: Vec::new(),
             }],
             temperature: Some(0.8), // Creative
             max_tokens: Some(1000),
@@ -144,29 +147,34 @@ impl Agent for MusicSFXDirector {
             .await
             .map_err(AgentError::ProcessingFailed)?;
 
+        let mut output = parse_structured_output(&response.content, &context);
+
         // Suggest audio generation actions
-        let actions = vec![
+        let actions = output.take_actions_or(vec![
             AgentAction::GenerateAudio {
                 prompt: message.to_string(),
                 audio_type: AudioActionType::Music,
                 model: "beatoven".to_string(),
                 duration_seconds: Some(30.0),
+                voice_id: None,
             },
             AgentAction::GenerateAudio {
                 prompt: message.to_string(),
                 audio_type: AudioActionType::SoundEffect,
                 model: "beatoven-sfx".to_string(),
                 duration_seconds: None,
+                voice_id: None,
             },
-        ];
+        ]);
 
         Ok(AgentResponse {
             agent: self.name().to_string(),
             content: format!(
                 "**🎵 Sound Design Direction**\n\n{}\n\n---\n*Ready to generate with Beatoven (royalty-free) or Suno (full songs)*",
-                response.content
+                output.message
             ),
             actions,
+            citations: output.citations,
             cost: Some(0.015),
             metadata: AgentMetadata {
                 model: self.get_model_name(),
@@ -205,7 +213,7 @@ mod tests {
     fn test_agent_creation() {
         let agent = MusicSFXDirector::new();
         assert_eq!(agent.name(), "Music & SFX Director");
-        assert_eq!(agent.capability(), AgentCapability::MusicSFX);
+        assert_eq!(agent.capability(), AgentCapability::AudioProduction);
     }
 
     #[test]
@@ -1,15 +1,82 @@
 //! Photography Director - Image generation specialist
 //!
-//! Enhances user prompts with cinematic details and generates images via ComfyUI
+//! Enhances user prompts with cinematic details and generates images via ComfyUI.
+//! Removal and "extend the frame" requests on a selected canvas image become
+//! inpaint/outpaint fills instead.
 
 use crate::ai::{
-    llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest},
+    context::CanvasContext,
+    cost::{estimate_image_cost, estimate_llm_cost},
+    llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest, LLMResponse},
+    structured_output::detect_citations,
     templates::{inject_context, PHOTOGRAPHY_SYSTEM_PROMPT},
+    token_budget::estimate_tokens,
     Agent, AgentAction, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
     ProcessingLocation,
 };
+use crate::vault::{self, assets::get_asset};
 use async_trait::async_trait;
 use std::time::Instant;
+use surrealdb::engine::any::Any;
+use surrealdb::Surreal;
+
+/// Image model and size suggested for the enhanced prompt
+const DEFAULT_IMAGE_MODEL: &str = "auto";
+const DEFAULT_IMAGE_SIZE: u32 = 1024;
+
+/// Completion budget for the enhanced prompt
+const MAX_RESPONSE_TOKENS: u32 = 500;
+
+/// Model for inpainting/outpainting a selected image
+const FILL_MODEL: &str = "flux-fill";
+
+/// Border added on each side when extending a shot
+const DEFAULT_EXPAND_PIXELS: u32 = 256;
+
+/// Requests that change part of an existing image rather than make a new one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FillIntent {
+    /// "remove the car from this shot"
+    Inpaint,
+    /// "extend the frame to the left"
+    Outpaint,
+}
+
+fn fill_intent(message: &str) -> Option<FillIntent> {
+    let message = message.to_lowercase();
+    let mentions = |words: &[&str]| words.iter().any(|w| message.contains(w));
+    if mentions(&["remove ", "erase ", "get rid of ", "paint out "]) {
+        Some(FillIntent::Inpaint)
+    } else if mentions(&["extend ", "outpaint", "widen ", "zoom out"]) {
+        Some(FillIntent::Outpaint)
+    } else {
+        None
+    }
+}
+
+/// First selected canvas node of the given type ("image", "mask")
+fn selected_node(canvas: &CanvasContext, node_type: &str) -> Option<String> {
+    canvas
+        .selected_nodes
+        .iter()
+        .zip(&canvas.selected_types)
+        .find(|(_, t)| t.eq_ignore_ascii_case(node_type))
+        .map(|(id, _)| id.clone())
+}
+
+/// Asset files behind the selected image node and its mask node, if any
+async fn fill_files(
+    db: &Surreal<Any>,
+    image_id: &str,
+    mask_id: Option<&str>,
+) -> Result<(String, Option<String>), String> {
+    let source_image = get_asset(db, image_id).await?.path;
+    let mask = match mask_id {
+        Some(mask_id) => Some(get_asset(db, mask_id).await?.path),
+        None => None,
+    };
+    Ok((source_image, mask))
+}
 
 pub struct PhotographyDirector {
     /// User-selected LLM provider (default: Gemini)
@@ -38,7 +105,7 @@ impl PhotographyDirector {
         &self,
         user_prompt: &str,
         context: &AgentContext,
-    ) -> Result<String, String> {
+    ) -> Result<LLMResponse, String> {
         let llm = get_llm_client();
 
         // Build system prompt with context
@@ -62,14 +129,88 @@ impl PhotographyDirector {
             messages: vec![LLMMessage {
                 role: "user".to_string(),
                 content: user_message,
+                images: Vec::new(),
             }],
             temperature: Some(0.7),
-            max_tokens: Some(500),
+            max_tokens: Some(MAX_RESPONSE_TOKENS),
             system_prompt: Some(system_prompt),
         };
 
-        let response = llm.chat(request).await?;
-        Ok(response.content.trim().to_string())
+        llm.chat(request).await
+    }
+
+    /// Answer a removal/extension request on the selected image with a fill
+    ///
+    /// Inpainting needs a mask selected alongside the image; without one the
+    /// user is asked to paint it.
+    fn fill_response(
+        &self,
+        message: &str,
+        intent: FillIntent,
+        source_image: String,
+        mask: Option<String>,
+        start_time: Instant,
+    ) -> AgentResponse {
+        let (content, actions) = match (intent, mask) {
+            (FillIntent::Inpaint, None) => (
+                "Paint a mask over what should change, select it with the image, \
+                 and ask again."
+                    .to_string(),
+                vec![],
+            ),
+            (FillIntent::Inpaint, Some(mask)) => (
+                "I'll repaint the masked area with FLUX Fill.".to_string(),
+                vec![AgentAction::FillImage {
+                    prompt: message.to_string(),
+                    source_image,
+                    mask: Some(mask),
+                    expand_pixels: None,
+                    model: FILL_MODEL.to_string(),
+                }],
+            ),
+            (FillIntent::Outpaint, _) => (
+                format!(
+                    "I'll extend the frame by {}px on each side with FLUX Fill.",
+                    DEFAULT_EXPAND_PIXELS
+                ),
+                vec![AgentAction::FillImage {
+                    prompt: message.to_string(),
+                    source_image,
+                    mask: None,
+                    expand_pixels: Some(DEFAULT_EXPAND_PIXELS),
+                    model: FILL_MODEL.to_string(),
+                }],
+            ),
+        };
+
+        AgentResponse {
+            agent: self.name().to_string(),
+            content,
+            actions,
+            citations: vec![],
+            // No LLM call; the fill itself is priced when it runs
+            cost: None,
+            metadata: AgentMetadata {
+                model: FILL_MODEL.to_string(),
+                processing_time_ms: start_time.elapsed().as_millis() as u64,
+                tokens: None,
+                location: ProcessingLocation::Cloud,
+            },
+        }
+    }
+
+    /// Model answering for the current provider (December 2025)
+    fn get_model_name(&self) -> String {
+        self.llm_model
+            .clone()
+            .unwrap_or_else(|| match self.llm_provider {
+                LLMProvider::Gemini => "gemini-2.5-flash".to_string(),
+                LLMProvider::OpenAI => "gpt-4o".to_string(),
+                LLMProvider::Anthropic => "claude-sonnet-4-5".to_string(),
+                LLMProvider::Ollama => "llama3.1:8b".to_string(),
+                LLMProvider::LlamaStack => "llama3.2-3b".to_string(),
+                LLMProvider::VertexAI => "gemini-1.5-pro-001".to_string(),
+            })
     }
 }
 
@@ -100,10 +241,24 @@ impl Agent for PhotographyDirector {
     ) -> Result<AgentResponse, AgentError> {
         let start_time = Instant::now();
 
+        if let (Some(intent), Some(canvas)) = (fill_intent(message), &context.canvas) {
+            if let Some(image_id) = selected_node(canvas, "image") {
+                let db = vault::get_db()
+                    .await
+                    .ok_or_else(|| AgentError::ContextMissing("Vault not initialized".into()))?;
+                let mask_id = selected_node(canvas, "mask");
+                let (source_image, mask) = fill_files(&db, &image_id, mask_id.as_deref())
+                    .await
+                    .map_err(AgentError::ContextMissing)?;
+                return Ok(self.fill_response(message, intent, source_image, mask, start_time));
+            }
+        }
+
         // Enhance prompt using LLM
-        let enhanced_prompt = self.enhance_prompt(message, &context).await.map_err(|e| {
+        let response = self.enhance_prompt(message, &context).await.map_err(|e| {
             AgentError::ProcessingFailed(format!("Failed to enhance prompt: {}", e))
         })?;
+        let enhanced_prompt = response.content.trim().to_string();
 
         // Build response with enhanced prompt and action
         let response_content = format!(
@@ -117,40 +272,28 @@ impl Agent for PhotographyDirector {
         // Suggest action to generate image
         let actions = vec![AgentAction::GenerateImage {
             prompt: enhanced_prompt,
-            model: "auto".to_string(),
-            width: 1024,
-            height: 1024,
+            model: DEFAULT_IMAGE_MODEL.to_string(),
+            width: DEFAULT_IMAGE_SIZE,
+            height: DEFAULT_IMAGE_SIZE,
+            preset: None,
             token_ids: vec![],
+            control: None,
+            seed: None,
+            batch_size: 1,
+            input_image: None,
         }];
 
         let processing_time = start_time.elapsed().as_millis() as u64;
 
-        // Determine model used (December 2025)
-        let model_name = match self.llm_provider {
-            LLMProvider::Gemini => self
-                .llm_model
-                .clone()
-                .unwrap_or_else(|| "gemini-2.5-flash".to_string()),
-            LLMProvider::OpenAI => self
-                .llm_model
-                .clone()
-                .unwrap_or_else(|| "gpt-4o".to_string()),
-            LLMProvider::Anthropic => self
-                .llm_model
-                .clone()
-                .unwrap_or_else(|| "claude-sonnet-4-5".to_string()),
-            LLMProvider::Ollama => self
-                .llm_model
-                .clone()
-                .unwrap_or_else(|| "llama3.1:8b".to_string()),
-            LLMProvider::LlamaStack => self
-                .llm_model
-                .clone()
-                .unwrap_or_else(|| "llama3.2-3b".to_string()),
-            LLMProvider::VertexAI => self
-                .llm_model
-                .clone()
-                .unwrap_or_else(|| "gemini-1.5-pro-001".to_string()),
+        let model_name = self.get_model_name();
+        let cost = match &response.usage {
+            Some(u) => estimate_llm_cost(&model_name, u.prompt_tokens, u.completion_tokens),
+            // Providers that don't report usage
+            None => estimate_llm_cost(
+                &model_name,
+                estimate_tokens(PHOTOGRAPHY_SYSTEM_PROMPT) + estimate_tokens(message),
+                estimate_tokens(&response.content),
+            ),
         };
 
         let location = match self.llm_provider {
@@ -158,36 +301,39 @@ impl Agent for PhotographyDirector {
             _ => ProcessingLocation::Cloud,
         };
 
+        let citations = detect_citations(&response_content, &context);
+
         Ok(AgentResponse {
             agent: self.name().to_string(),
             content: response_content,
             actions,
-            cost: Some(0.001), // Approximate cost for prompt enhancement
+            citations,
+            cost: Some(cost),
             metadata: AgentMetadata {
                 model: model_name,
                 processing_time_ms: processing_time,
-                tokens: None,
+                tokens: response.usage.map(|u| crate::ai::TokenUsage {
+                    prompt: u.prompt_tokens,
+                    completion: u.completion_tokens,
+                    total: u.total_tokens,
+                }),
                 location,
             },
         })
     }
 
-    async fn estimate_cost(&self, _message: &str) -> f32 {
-        // LLM call + potential image generation
-        match self.llm_provider {
-            LLMProvider::Ollama => 0.0, // Local is free
-            LLMProvider::LlamaStack => 0.0,
-            LLMProvider::Gemini => 0.001, // Gemini Flash is very cheap
-            LLMProvider::OpenAI => 0.01,  // GPT-4o
-            LLMProvider::Anthropic => 0.015, // Claude
-            LLMProvider::VertexAI => 0.005, // Vertex AI
-        }
+    async fn estimate_cost(&self, message: &str) -> f32 {
+        // Prompt enhancement plus the image it suggests
+        let prompt_tokens = estimate_tokens(PHOTOGRAPHY_SYSTEM_PROMPT) + estimate_tokens(message);
+        estimate_llm_cost(&self.get_model_name(), prompt_tokens, MAX_RESPONSE_TOKENS)
+            + estimate_image_cost(DEFAULT_IMAGE_MODEL, DEFAULT_IMAGE_SIZE, DEFAULT_IMAGE_SIZE)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vault::assets::{Asset, AssetKind};
 
     #[test]
     fn test_agent_creation() {
@@ -204,4 +350,72 @@ mod tests {
         assert_eq!(agent.llm_provider, LLMProvider::OpenAI);
         assert_eq!(agent.llm_model, Some("gpt-4o".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_remove_request_becomes_inpaint() {
+        assert_eq!(
+            fill_intent("Remove the car from this shot"),
+            Some(FillIntent::Inpaint)
+        );
+        assert_eq!(
+            fill_intent("extend the frame to the left"),
+            Some(FillIntent::Outpaint)
+        );
+        assert_eq!(fill_intent("Anna on the rooftop at dawn"), None);
+
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+        for (id, file_name) in [("shot12", "shot_012.png"), ("car12", "shot_012_car.png")] {
+            let asset = Asset {
+                id: None,
+                project_id: "project:p1".into(),
+                kind: AssetKind::Image,
+                file_name: file_name.into(),
+                path: format!("/projects/p1/assets/{}", file_name),
+                source: "import".into(),
+                execution_id: None,
+                node_id: None,
+                token_ids: Vec::new(),
+                created_at: "2025-12-01T10:00:00Z".into(),
+            };
+            let _: Option<Asset> = db.create(("asset", id)).content(asset).await.unwrap();
+        }
+
+        // Canvas nodes are named by asset id, not by file
+        let canvas = CanvasContext {
+            selected_nodes: vec!["asset:shot12".into(), "asset:car12".into()],
+            selected_types: vec!["image".into(), "mask".into()],
+            ..CanvasContext::empty()
+        };
+        let image_id = selected_node(&canvas, "image").unwrap();
+        let mask_id = selected_node(&canvas, "mask");
+        let (source_image, mask) = fill_files(&db, &image_id, mask_id.as_deref())
+            .await
+            .unwrap();
+        assert!(fill_files(&db, "asset:missing", None).await.is_err());
+
+        let response = PhotographyDirector::new().fill_response(
+            "Remove the car from this shot",
+            FillIntent::Inpaint,
+            source_image,
+            mask,
+            Instant::now(),
+        );
+        match &response.actions[..] {
+            [AgentAction::FillImage {
+                source_image,
+                mask,
+                model,
+                ..
+            }] => {
+                assert_eq!(source_image, "/projects/p1/assets/shot_012.png");
+                assert_eq!(
+                    mask.as_deref(),
+                    Some("/projects/p1/assets/shot_012_car.png")
+                );
+                assert_eq!(model, FILL_MODEL);
+            }
+            other => panic!("expected a FillImage action, got {:?}", other),
+        }
+    }
 }
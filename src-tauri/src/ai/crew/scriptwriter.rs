@@ -5,6 +5,7 @@
 
 use crate::ai::{
     llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest},
+    structured_output::{parse_structured_output, with_structured_output},
     templates::inject_context,
     Agent, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
     ProcessingLocation,
@@ -104,7 +105,8 @@ impl Agent for Scriptwriter {
         let start_time = Instant::now();
         let llm = get_llm_client();
 
-        let system_prompt = inject_context(SCRIPTWRITER_SYSTEM_PROMPT, &context);
+        let system_prompt =
+            with_structured_output(inject_context(SCRIPTWRITER_SYSTEM_PROMPT, &context));
 
         let request = LLMRequest {
             provider: self.llm_provider.clone(),
@@ -112,6 +114,7 @@ impl Agent for Scriptwriter {
             messages: vec![LLMMessage {
                 role: "user".to_string(),
                 content: message.to_string(),
+                images: Vec::new(),
             }],
             temperature: Some(0.8), // Higher for creativity
             max_tokens: Some(2000),
@@ -123,10 +126,13 @@ impl Agent for Scriptwriter {
             .await
             .map_err(AgentError::ProcessingFailed)?;
 
+        let output = parse_structured_output(&response.content, &context);
+
         Ok(AgentResponse {
             agent: self.name().to_string(),
-            content: format!("**✍️ Screenplay**\n\n{}", response.content),
-            actions: vec![],
+            content: format!("**✍️ Screenplay**\n\n{}", output.message),
+            actions: output.actions,
+            citations: output.citations,
             cost: Some(0.02), // Claude Opus 4.5 pricing
             metadata: AgentMetadata {
                 model: self.get_model_name(),
@@ -4,7 +4,10 @@
 //! The Showrunner maintains project-wide coherence across all agents.
 
 use crate::ai::{
-    llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest},
+    llm_client::{LLMMessage, LLMProvider, LLMRequest},
+    mcp::registry::chat_with_tools,
+    project_memory,
+    structured_output::{parse_structured_output, with_structured_output},
     templates::inject_context,
     Agent, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
     ProcessingLocation,
@@ -48,6 +51,11 @@ The Vault contains:
 - **Timeline** - Chronological events
 - **Style Guide** - Visual and tonal rules
 
+# The Project Bible
+Facts the team has already established appear under "Project Bible". Treat
+them as canon: cite them when checking consistency and flag any request that
+contradicts them.
+
 # Communication Style
 - Big-picture thinking with attention to detail
 - Reference established canon from Vault tokens
@@ -110,12 +118,15 @@ impl Agent for Showrunner {
     async fn process(
         &self,
         message: &str,
-        context: AgentContext,
+        mut context: AgentContext,
     ) -> Result<AgentResponse, AgentError> {
         let start_time = Instant::now();
-        let llm = get_llm_client();
 
-        let system_prompt = inject_context(SHOWRUNNER_SYSTEM_PROMPT, &context);
+        // The Bible is loaded on every call so canon persists across sessions
+        project_memory::attach(&mut context).await;
+
+        let system_prompt =
+            with_structured_output(inject_context(SHOWRUNNER_SYSTEM_PROMPT, &context));
 
         let request = LLMRequest {
             provider: self.llm_provider.clone(),
@@ -123,21 +134,25 @@ impl Agent for Showrunner {
             messages: vec![LLMMessage {
                 role: "user".to_string(),
                 content: message.to_string(),
+                images: Vec::new(),
             }],
             temperature: Some(0.4), // Conservative for consistency
             max_tokens: Some(1500),
             system_prompt: Some(system_prompt),
         };
 
-        let response = llm
-            .chat(request)
+        // External tools (search, files, ...) from any configured MCP servers
+        let response = chat_with_tools(request)
             .await
             .map_err(AgentError::ProcessingFailed)?;
 
+        let output = parse_structured_output(&response.content, &context);
+
         Ok(AgentResponse {
             agent: self.name().to_string(),
-            content: format!("**🎬 Showrunner**\n\n{}", response.content),
-            actions: vec![],
+            content: format!("**🎬 Showrunner**\n\n{}", output.message),
+            actions: output.actions,
+            citations: output.citations,
             cost: Some(0.008),
             metadata: AgentMetadata {
                 model: self.get_model_name(),
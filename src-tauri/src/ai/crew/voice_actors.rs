@@ -6,6 +6,7 @@
 use crate::ai::actions::AudioActionType;
 use crate::ai::{
     llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest},
+    structured_output::{parse_structured_output, with_structured_output},
     templates::inject_context,
     Agent, AgentAction, AgentCapability, AgentContext, AgentError, AgentMetadata, AgentResponse,
     ProcessingLocation,
@@ -112,7 +113,8 @@ impl Agent for VoiceActors {
         let start_time = Instant::now();
         let llm = get_llm_client();
 
-        let system_prompt = inject_context(VOICE_ACTORS_SYSTEM_PROMPT, &context);
+        let system_prompt =
+            with_structured_output(inject_context(VOICE_ACTORS_SYSTEM_PROMPT, &context));
 
         let user_message = format!(
             "User needs voice/TTS for:\n\n\"{}\"\n\n\
@@ -131,6 +133,7 @@ impl Agent for VoiceActors {
             messages: vec![LLMMessage {
                 role: "user".to_string(),
                 content: user_message,
+                images: Vec::new(),
             }],
             temperature: Some(0.7),
             max_tokens: Some(800),
@@ -142,29 +145,34 @@ impl Agent for VoiceActors {
             .await
             .map_err(AgentError::ProcessingFailed)?;
 
+        let mut output = parse_structured_output(&response.content, &context);
+
         // Suggest TTS actions with different models
-        let actions = vec![
+        let actions = output.take_actions_or(vec![
             AgentAction::GenerateAudio {
                 prompt: message.to_string(),
                 audio_type: AudioActionType::Voice,
                 model: "eleven-v3".to_string(),
                 duration_seconds: None,
+                voice_id: None,
             },
             AgentAction::GenerateAudio {
                 prompt: message.to_string(),
                 audio_type: AudioActionType::Voice,
                 model: "gemini-flash".to_string(),
                 duration_seconds: None,
+                voice_id: None,
             },
-        ];
+        ]);
 
         Ok(AgentResponse {
             agent: self.name().to_string(),
             content: format!(
                 "**🎙️ Voice Direction**\n\n{}\n\n---\n*Ready to generate with ElevenLabs v3 (quality) or Flash v2.5 (low latency)*",
-                response.content
+                output.message
             ),
             actions,
+            citations: output.citations,
             cost: Some(0.002),
             metadata: AgentMetadata {
                 model: self.get_model_name(),
@@ -0,0 +1,329 @@
+//! Dialogue Assembly - Multi-speaker TTS for a whole scene
+//!
+//! Each line is synthesized with its character's voice (through the cached
+//! ElevenLabs client), then ffmpeg concatenates the clips into one track with
+//! silence between lines. Per-line timings are returned for the timeline.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::ai::elevenlabs_client::{ElevenLabsClient, TtsRequest};
+use crate::installer::get_cinema_os_dir;
+use crate::vault::tokens::{Token, TokenType};
+
+/// Default silence between lines
+pub const DEFAULT_GAP_MS: u32 = 300;
+
+/// Default TTS model for dialogue
+const DEFAULT_MODEL: &str = "eleven-v3";
+
+/// Sample rate all clips are resampled to before concatenation
+const SAMPLE_RATE: u32 = 44_100;
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// One line of dialogue
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct DialogueLine {
+    /// Character name (matched against Vault Character tokens)
+    pub character: String,
+    /// Explicit voice; falls back to the character token's voice
+    #[serde(default)]
+    pub voice_id: Option<String>,
+    pub text: String,
+}
+
+/// Dialogue synthesis options
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct DialogueOptions {
+    /// Project whose Character tokens provide the voices
+    pub project_id: Option<String>,
+    /// Silence between lines in milliseconds
+    pub gap_ms: u32,
+    /// TTS model id from the Model Matrix
+    pub model: String,
+}
+
+impl Default for DialogueOptions {
+    fn default() -> Self {
+        Self {
+            project_id: None,
+            gap_ms: DEFAULT_GAP_MS,
+            model: DEFAULT_MODEL.to_string(),
+        }
+    }
+}
+
+/// Where a line sits in the assembled track
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct LineTiming {
+    pub index: u32,
+    pub character: String,
+    pub voice_id: String,
+    pub start_ms: u32,
+    pub end_ms: u32,
+}
+
+/// The assembled dialogue track
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct DialogueTrack {
+    pub path: String,
+    pub duration_ms: u32,
+    pub lines: Vec<LineTiming>,
+    /// Lines served from the TTS cache
+    pub cached_lines: u32,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// SYNTHESIS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Synthesize every line and assemble them into a single track
+pub async fn synthesize_dialogue(
+    lines: Vec<DialogueLine>,
+    options: DialogueOptions,
+) -> Result<DialogueTrack, String> {
+    if lines.is_empty() {
+        return Err("No dialogue lines to synthesize".into());
+    }
+
+    let tokens = match &options.project_id {
+        Some(project_id) => character_tokens(project_id).await?,
+        None => Vec::new(),
+    };
+    let voices = resolve_voices(&lines, &tokens)?;
+
+    let client = ElevenLabsClient::new()?;
+    let mut clips = Vec::with_capacity(lines.len());
+    let mut durations = Vec::with_capacity(lines.len());
+    let mut cached_lines = 0;
+
+    for (line, voice_id) in lines.iter().zip(&voices) {
+        let request = TtsRequest::new(&line.text, Some(voice_id.clone()), &options.model);
+        let result = client.synthesize(&request).await?;
+        if result.cached {
+            cached_lines += 1;
+        }
+
+        let clip = PathBuf::from(result.path);
+        durations.push(probe_duration_ms(&clip).await?);
+        clips.push(clip);
+    }
+
+    let output_dir = get_cinema_os_dir().join("dialogue");
+    std::fs::create_dir_all(&output_dir).map_err(|e| e.to_string())?;
+    let output = output_dir.join(format!("{}.mp3", uuid::Uuid::new_v4()));
+
+    let status = Command::new(ffmpeg_binary("FFMPEG_PATH", "ffmpeg"))
+        .args(concat_args(&clips, options.gap_ms, &output))
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !status.status.success() {
+        return Err(format!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&status.stderr)
+        ));
+    }
+
+    let timings = compute_timings(&lines, &voices, &durations, options.gap_ms);
+    Ok(DialogueTrack {
+        path: output.to_string_lossy().to_string(),
+        duration_ms: timings.last().map(|t| t.end_ms).unwrap_or(0),
+        lines: timings,
+        cached_lines,
+    })
+}
+
+async fn character_tokens(project_id: &str) -> Result<Vec<Token>, String> {
+    let db = crate::vault::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    let mut result = db
+        .query("SELECT * FROM token WHERE project_id = $pid AND token_type = $ttype")
+        .bind(("pid", project_id.to_string()))
+        .bind(("ttype", format!("{:?}", TokenType::Character)))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    result.take(0).map_err(|e| e.to_string())
+}
+
+/// Voice for every line: the explicit voice, else the character token's voice
+pub fn resolve_voices(lines: &[DialogueLine], tokens: &[Token]) -> Result<Vec<String>, String> {
+    lines
+        .iter()
+        .map(|line| {
+            if let Some(voice) = &line.voice_id {
+                return Ok(voice.clone());
+            }
+
+            let name = line.character.trim().trim_start_matches('@');
+            tokens
+                .iter()
+                .filter(|t| t.token_type == TokenType::Character)
+                .find(|t| {
+                    t.name.eq_ignore_ascii_case(name)
+                        || t.slug.trim_start_matches('@').eq_ignore_ascii_case(name)
+                })
+                .and_then(|t| t.voice_id.clone())
+                .ok_or_else(|| format!("No voice mapped for character {}", line.character))
+        })
+        .collect()
+}
+
+/// Start/end of each line when clips are separated by `gap_ms` of silence
+pub fn compute_timings(
+    lines: &[DialogueLine],
+    voices: &[String],
+    durations_ms: &[u32],
+    gap_ms: u32,
+) -> Vec<LineTiming> {
+    let mut cursor = 0;
+
+    lines
+        .iter()
+        .zip(voices)
+        .zip(durations_ms)
+        .enumerate()
+        .map(|(index, ((line, voice_id), duration))| {
+            if index > 0 {
+                cursor += gap_ms;
+            }
+            let timing = LineTiming {
+                index: index as u32,
+                character: line.character.clone(),
+                voice_id: voice_id.clone(),
+                start_ms: cursor,
+                end_ms: cursor + duration,
+            };
+            cursor += duration;
+            timing
+        })
+        .collect()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// FFMPEG
+// ═══════════════════════════════════════════════════════════════════════════════
+
+pub(crate) fn ffmpeg_binary(env_var: &str, default: &str) -> String {
+    std::env::var(env_var).unwrap_or_else(|_| default.to_string())
+}
+
+/// Duration of an audio file via ffprobe
+async fn probe_duration_ms(path: &Path) -> Result<u32, String> {
+    let output = Command::new(ffmpeg_binary("FFPROBE_PATH", "ffprobe"))
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+
+    let seconds: f64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map_err(|_| format!("Could not read duration of {}", path.display()))?;
+    Ok((seconds * 1000.0).round() as u32)
+}
+
+/// ffmpeg arguments that pad every clip but the last with silence and concatenate them
+pub fn concat_args(clips: &[PathBuf], gap_ms: u32, output: &Path) -> Vec<String> {
+    let mut args = vec!["-y".to_string()];
+    for clip in clips {
+        args.push("-i".into());
+        args.push(clip.to_string_lossy().to_string());
+    }
+
+    let last = clips.len().saturating_sub(1);
+    let mut filter = String::new();
+    for i in 0..clips.len() {
+        filter.push_str(&format!(
+            "[{i}:a]aresample={SAMPLE_RATE},aformat=channel_layouts=mono"
+        ));
+        if i < last && gap_ms > 0 {
+            filter.push_str(&format!(",apad=pad_dur={:.3}", gap_ms as f64 / 1000.0));
+        }
+        filter.push_str(&format!("[a{i}];"));
+    }
+    for i in 0..clips.len() {
+        filter.push_str(&format!("[a{i}]"));
+    }
+    filter.push_str(&format!("concat=n={}:v=0:a=1[out]", clips.len()));
+
+    args.extend([
+        "-filter_complex".into(),
+        filter,
+        "-map".into(),
+        "[out]".into(),
+        output.to_string_lossy().to_string(),
+    ]);
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(character: &str, voice_id: Option<&str>) -> DialogueLine {
+        DialogueLine {
+            character: character.into(),
+            voice_id: voice_id.map(String::from),
+            text: "Line".into(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_voices_from_tokens() {
+        let mut anna = Token::new(
+            "p1".into(),
+            TokenType::Character,
+            "Anna".into(),
+            String::new(),
+        );
+        anna.voice_id = Some("voice-anna".into());
+
+        let lines = vec![line("@anna", None), line("Marc", Some("voice-marc"))];
+        let voices = resolve_voices(&lines, &[anna]).unwrap();
+        assert_eq!(voices, ["voice-anna", "voice-marc"]);
+
+        let err = resolve_voices(&[line("Nobody", None)], &[]).unwrap_err();
+        assert!(err.contains("Nobody"));
+    }
+
+    #[test]
+    fn test_timings_include_gaps() {
+        let lines = vec![line("Anna", None), line("Marc", None)];
+        let voices = vec!["a".to_string(), "m".to_string()];
+        let timings = compute_timings(&lines, &voices, &[1200, 800], 300);
+
+        assert_eq!((timings[0].start_ms, timings[0].end_ms), (0, 1200));
+        assert_eq!((timings[1].start_ms, timings[1].end_ms), (1500, 2300));
+        assert_eq!(timings[1].character, "Marc");
+    }
+
+    #[test]
+    fn test_concat_pads_all_but_last_clip() {
+        let clips = vec![PathBuf::from("a.mp3"), PathBuf::from("b.mp3")];
+        let args = concat_args(&clips, 250, Path::new("out.mp3"));
+        let filter = &args[args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
+
+        assert!(filter
+            .contains("[0:a]aresample=44100,aformat=channel_layouts=mono,apad=pad_dur=0.250[a0];"));
+        assert!(filter.contains("[1:a]aresample=44100,aformat=channel_layouts=mono[a1];"));
+        assert!(filter.ends_with("[a0][a1]concat=n=2:v=0:a=1[out]"));
+        assert_eq!(args.last().unwrap(), "out.mp3");
+    }
+}
@@ -1,12 +1,265 @@
+//! ElevenLabs Client - Text-to-speech with a content-addressed cache
+//!
+//! Synthesized audio is stored under `cache/tts`, keyed by a hash of
+//! (text, voice, model, settings), so regenerating the same line with the same
+//! voice is served from disk instead of being billed again.
+
 use bytes::Bytes;
-use futures_util::Stream;
+use futures_util::{stream, Stream, TryStreamExt};
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use sha2::{Digest, Sha256};
+use specta::Type;
 use std::env;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::comfyui::models::CloudModels;
+use crate::installer::get_cinema_os_dir;
+
+/// Default cache size cap (overridable with `TTS_CACHE_MAX_MB`)
+const DEFAULT_CACHE_MAX_MB: u64 = 500;
+
+/// Voice used when an action doesn't specify one (overridable with `ELEVENLABS_VOICE_ID`)
+const DEFAULT_VOICE_ID: &str = "21m00Tcm4TlvDq8ikWAM";
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct VoiceSettings {
+    pub stability: f32,
+    pub similarity_boost: f32,
+}
+
+impl Default for VoiceSettings {
+    fn default() -> Self {
+        Self {
+            stability: 0.5,
+            similarity_boost: 0.5,
+        }
+    }
+}
+
+/// A text-to-speech request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct TtsRequest {
+    pub text: String,
+    pub voice_id: String,
+    /// ElevenLabs model id (e.g. "eleven_v3")
+    pub model_id: String,
+    pub settings: VoiceSettings,
+}
+
+impl TtsRequest {
+    /// Build a request from a Model Matrix id ("eleven-v3", "eleven-flash", ...)
+    pub fn new(text: &str, voice_id: Option<String>, model: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            voice_id: voice_id
+                .or_else(|| env::var("ELEVENLABS_VOICE_ID").ok())
+                .unwrap_or_else(|| DEFAULT_VOICE_ID.to_string()),
+            model_id: elevenlabs_model_id(model).to_string(),
+            settings: VoiceSettings::default(),
+        }
+    }
+
+    /// Content hash identifying the audio this request produces
+    pub fn cache_key(&self) -> String {
+        let mut hasher = Sha256::new();
+        for part in [&self.text, &self.voice_id, &self.model_id] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update(self.settings.stability.to_le_bytes());
+        hasher.update(self.settings.similarity_boost.to_le_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// Synthesized audio on disk
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct TtsResult {
+    pub path: String,
+    /// Served from the TTS cache (not billed)
+    pub cached: bool,
+}
+
+/// An ElevenLabs voice, for the voice picker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct Voice {
+    pub voice_id: String,
+    pub name: String,
+    /// "premade", "cloned", "generated", ...
+    pub category: Option<String>,
+    /// Sample clip of the voice
+    pub preview_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VoicesResponse {
+    voices: Vec<Voice>,
+}
+
+/// `tts-stream` event payload emitted by the `stream_tts` command
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct TtsStreamChunk {
+    pub stream_id: String,
+    /// Base64 MP3 bytes; empty on the final event
+    pub audio: String,
+    pub done: bool,
+}
+
+/// Map Model Matrix ids to ElevenLabs model ids
+pub fn elevenlabs_model_id(model: &str) -> &str {
+    match model {
+        "eleven-v3" | "elevenlabs-v3" => "eleven_v3",
+        "eleven-flash" | "elevenlabs-flash" => CloudModels::ELEVENLABS_FLASH,
+        "eleven-turbo" | "elevenlabs-turbo" => CloudModels::ELEVENLABS_TURBO,
+        id if id.starts_with("eleven_") => id,
+        _ => CloudModels::ELEVENLABS_V2,
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TTS CACHE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// On-disk audio cache with a size cap and least-recently-used eviction
+pub struct TtsCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl TtsCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self { dir, max_bytes }
+    }
+
+    /// The shared cache under the CinemaOS data directory
+    pub fn default_cache() -> Self {
+        let max_mb = env::var("TTS_CACHE_MAX_MB")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CACHE_MAX_MB);
+        Self::new(
+            get_cinema_os_dir().join("cache").join("tts"),
+            max_mb * 1024 * 1024,
+        )
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.mp3", key))
+    }
+
+    /// Cached audio for `key`, marking it as recently used
+    pub fn get(&self, key: &str) -> Option<PathBuf> {
+        let path = self.path_for(key);
+        let file = std::fs::File::options().write(true).open(&path).ok()?;
+        // The modification time doubles as the LRU timestamp
+        let _ = file.set_modified(SystemTime::now());
+        Some(path)
+    }
+
+    /// Store audio for `key`, evicting the least recently used files over the cap
+    pub fn insert(&self, key: &str, audio: &[u8]) -> Result<PathBuf, String> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        let path = self.path_for(key);
+        std::fs::write(&path, audio).map_err(|e| e.to_string())?;
+        self.evict(&path);
+        Ok(path)
+    }
+
+    fn entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let Ok(dir) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        dir.filter_map(|entry| {
+            let entry = entry.ok()?;
+            let meta = entry.metadata().ok()?;
+            if !meta.is_file() {
+                return None;
+            }
+            Some((entry.path(), meta.len(), meta.modified().ok()?))
+        })
+        .collect()
+    }
+
+    fn evict(&self, keep: &Path) {
+        let mut entries = self.entries();
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        entries.sort_by_key(|(_, _, modified)| *modified);
+
+        for (path, size, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if path != keep && std::fs::remove_file(&path).is_ok() {
+                total -= size;
+            }
+        }
+    }
+
+    /// Total size of cached audio in bytes
+    pub fn size(&self) -> u64 {
+        self.entries().iter().map(|(_, size, _)| size).sum()
+    }
+
+    /// Delete all cached audio
+    pub fn clear(&self) -> Result<(), String> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Return cached audio for `request`, or call `fetch` and cache its output
+    pub async fn get_or_fetch<F, Fut>(
+        &self,
+        request: &TtsRequest,
+        fetch: F,
+    ) -> Result<TtsResult, String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Bytes, String>>,
+    {
+        let key = request.cache_key();
+
+        if let Some(path) = self.get(&key) {
+            tracing::debug!("TTS cache hit for voice {}", request.voice_id);
+            return Ok(TtsResult {
+                path: path.to_string_lossy().to_string(),
+                cached: true,
+            });
+        }
+
+        let audio = fetch().await?;
+        let path = self.insert(&key, &audio)?;
+        Ok(TtsResult {
+            path: path.to_string_lossy().to_string(),
+            cached: false,
+        })
+    }
+}
+
+/// Delete all cached TTS audio
+pub fn clear_tts_cache() -> Result<(), String> {
+    TtsCache::default_cache().clear()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// CLIENT
+// ═══════════════════════════════════════════════════════════════════════════════
 
 pub struct ElevenLabsClient {
     http: Client,
     api_key: String,
+    cache: TtsCache,
 }
 
 impl ElevenLabsClient {
@@ -17,26 +270,27 @@ impl ElevenLabsClient {
         Ok(Self {
             http: Client::new(),
             api_key,
+            cache: TtsCache::default_cache(),
         })
     }
 
-    pub async fn stream_speech(
-        &self,
-        text: &str,
-        voice_id: &str,
-    ) -> Result<impl Stream<Item = reqwest::Result<Bytes>>, String> {
+    /// Synthesize speech to a file, reusing cached audio for identical requests
+    pub async fn synthesize(&self, request: &TtsRequest) -> Result<TtsResult, String> {
+        self.cache
+            .get_or_fetch(request, || self.fetch_speech(request))
+            .await
+    }
+
+    async fn fetch_speech(&self, request: &TtsRequest) -> Result<Bytes, String> {
         let url = format!(
-            "https://api.elevenlabs.io/v1/text-to-speech/{}/stream",
-            voice_id
+            "https://api.elevenlabs.io/v1/text-to-speech/{}",
+            request.voice_id
         );
 
         let body = json!({
-            "text": text,
-            "model_id": "eleven_turbo_v2", // Low latency model
-            "voice_settings": {
-                "stability": 0.5,
-                "similarity_boost": 0.5
-            }
+            "text": request.text,
+            "model_id": request.model_id,
+            "voice_settings": request.settings
         });
 
         let response = self
@@ -53,6 +307,175 @@ impl ElevenLabsClient {
             return Err(format!("ElevenLabs API Error: {}", error_text));
         }
 
-        Ok(response.bytes_stream())
+        response
+            .bytes()
+            .await
+            .map_err(|e| format!("ElevenLabs response failed: {}", e))
+    }
+
+    /// Stream speech as it is generated, so playback can start before the
+    /// whole line is synthesized
+    ///
+    /// `model` is a Model Matrix or ElevenLabs id; Flash v2.5
+    /// ([`CloudModels::ELEVENLABS_FLASH`]) has the lowest latency. Request
+    /// errors arrive as the stream's first item.
+    pub fn stream_tts<'a>(
+        &'a self,
+        text: &'a str,
+        voice_id: &'a str,
+        model: &'a str,
+    ) -> impl Stream<Item = Result<Bytes, String>> + 'a {
+        let request = async move {
+            let url = format!(
+                "https://api.elevenlabs.io/v1/text-to-speech/{}/stream",
+                voice_id
+            );
+
+            let body = json!({
+                "text": text,
+                "model_id": elevenlabs_model_id(model),
+                "voice_settings": VoiceSettings::default()
+            });
+
+            let response = self
+                .http
+                .post(&url)
+                .header("xi-api-key", &self.api_key)
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| format!("ElevenLabs request failed: {}", e))?;
+
+            if !response.status().is_success() {
+                let error_text = response.text().await.unwrap_or_default();
+                return Err(format!("ElevenLabs API Error: {}", error_text));
+            }
+
+            Ok(response
+                .bytes_stream()
+                .map_err(|e| format!("ElevenLabs stream failed: {}", e)))
+        };
+
+        stream::once(request).try_flatten()
+    }
+
+    /// Voices available to the account (premade, cloned and library voices)
+    pub async fn list_voices(&self) -> Result<Vec<Voice>, String> {
+        let response = self
+            .http
+            .get("https://api.elevenlabs.io/v1/voices")
+            .header("xi-api-key", &self.api_key)
+            .send()
+            .await
+            .map_err(|e| format!("ElevenLabs request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("ElevenLabs API Error: {}", error_text));
+        }
+
+        let voices: VoicesResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("ElevenLabs response failed: {}", e))?;
+        Ok(voices.voices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn temp_cache(name: &str, max_bytes: u64) -> TtsCache {
+        let dir =
+            std::env::temp_dir().join(format!("cinemaos-tts-{}-{}", name, std::process::id()));
+        let cache = TtsCache::new(dir, max_bytes);
+        cache.clear().unwrap();
+        cache
+    }
+
+    #[tokio::test]
+    async fn test_second_identical_request_hits_cache() {
+        let cache = temp_cache("hit", 1024 * 1024);
+        let request = TtsRequest::new("We're not alone.", Some("voice-1".into()), "eleven-v3");
+        let calls = AtomicUsize::new(0);
+        let fetch = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Bytes::from_static(b"mp3-bytes"))
+        };
+
+        let first = cache.get_or_fetch(&request, fetch).await.unwrap();
+        let second = cache.get_or_fetch(&request, fetch).await.unwrap();
+
+        assert!(!first.cached);
+        assert!(second.cached);
+        assert_eq!(first.path, second.path);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // A different voice is a different line
+        let other = TtsRequest::new("We're not alone.", Some("voice-2".into()), "eleven-v3");
+        assert!(!cache.get_or_fetch(&other, fetch).await.unwrap().cached);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_model_ids() {
+        assert_eq!(
+            elevenlabs_model_id("eleven-flash"),
+            CloudModels::ELEVENLABS_FLASH
+        );
+        assert_eq!(
+            elevenlabs_model_id(CloudModels::ELEVENLABS_TURBO),
+            "eleven_turbo_v2_5"
+        );
+        assert_eq!(elevenlabs_model_id("eleven-v3"), "eleven_v3");
+        assert_eq!(elevenlabs_model_id("unknown"), CloudModels::ELEVENLABS_V2);
+    }
+
+    #[test]
+    fn test_parse_voices() {
+        let response: VoicesResponse = serde_json::from_value(json!({
+            "voices": [{
+                "voice_id": "21m00Tcm4TlvDq8ikWAM",
+                "name": "Rachel",
+                "category": "premade",
+                "labels": { "accent": "american" },
+                "preview_url": "https://storage.googleapis.com/eleven-public-prod/rachel.mp3"
+            }, {
+                "voice_id": "abc123",
+                "name": "Narrator (clone)",
+                "category": "cloned"
+            }]
+        }))
+        .unwrap();
+
+        assert_eq!(response.voices.len(), 2);
+        assert_eq!(response.voices[0].name, "Rachel");
+        assert_eq!(response.voices[1].category.as_deref(), Some("cloned"));
+        assert!(response.voices[1].preview_url.is_none());
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = temp_cache("lru", 10);
+        cache.insert("a", b"12345").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.insert("b", b"12345").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        // Touch "a" so "b" becomes the oldest
+        assert!(cache.get("a").is_some());
+        cache.insert("c", b"12345").unwrap();
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+        assert!(cache.size() <= 10);
+
+        cache.clear().unwrap();
+        assert_eq!(cache.size(), 0);
     }
 }
@@ -102,6 +102,16 @@ impl FalClient {
 
     /// Poll for results with exponential backoff
     pub async fn poll(&self, request_id: &str, timeout_secs: u64) -> Result<FalResult, String> {
+        let output = self.poll_output(request_id, timeout_secs).await?;
+        serde_json::from_value(output).map_err(|e| format!("Failed to parse result: {}", e))
+    }
+
+    /// Poll like [`Self::poll`], returning the raw output (e.g. trainer results)
+    pub async fn poll_output(
+        &self,
+        request_id: &str,
+        timeout_secs: u64,
+    ) -> Result<serde_json::Value, String> {
         let status_url = format!("https://queue.fal.run/requests/{}/status", request_id);
         let result_url = format!("https://queue.fal.run/requests/{}", request_id);
 
@@ -154,7 +164,7 @@ impl FalClient {
             .map_err(|e| format!("Result Fetch Failed: {}", e))?;
 
         result_resp
-            .json::<FalResult>()
+            .json::<serde_json::Value>()
             .await
             .map_err(|e| format!("Failed to parse result: {}", e))
     }
@@ -0,0 +1,616 @@
+//! Unified Generation - One entry point for every generation task
+//!
+//! The UI used to pick between agent chat, local ComfyUI and Fal.ai itself.
+//! `generate` does that routing: it resolves a model, runs
+//! `route_execution`, enforces the user's credit cap, then either
+//! runs the task (Fast Path chat, local ComfyUI workflows) or submits a cloud
+//! job and returns its id.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use specta::Type;
+
+use crate::ai::actions::{ActionExecutor, AgentAction};
+use crate::ai::comfyui::{machine_hardware, route_execution, ExecutionPath, WorkflowTarget};
+use crate::ai::context::UserPreferences;
+use crate::ai::fal_client::FalClient;
+use crate::ai::generation_queue::{generation_queue, GenerationLane, DEFAULT_PRIORITY};
+use crate::ai::hybrid::fal_endpoint_for;
+use crate::ai::llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest};
+use crate::ai::model_schema::validate_request_for_model;
+use crate::ai::model_selection::resolve_model;
+use crate::ai::models::ModelCapability;
+use crate::ai::resolution::{validate_dimensions, ResolutionPreset};
+use crate::ai::workflow_generator::{WorkflowRequest, WorkflowType};
+use crate::installer::hardware::HardwareInfo;
+
+const DEFAULT_TEXT_MODEL: &str = "gemini-2.5-flash";
+const DEFAULT_LOCAL_IMAGE_MODEL: &str = "flux-dev";
+const DEFAULT_CLOUD_IMAGE_MODEL: &str = "flux-pro-2.0";
+const DEFAULT_LOCAL_VIDEO_MODEL: &str = "ltx-video";
+const DEFAULT_CLOUD_VIDEO_MODEL: &str = "veo-3.1";
+const DEFAULT_VIDEO_SECONDS: f32 = 5.0;
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// A generation request from the UI
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct GenerateRequest {
+    /// Task type as understood by `determine_execution_path` ("chat", "image", "video", ...)
+    pub task_type: String,
+    pub prompt: String,
+    /// Model Matrix id; a default for the task is used when omitted
+    #[serde(default)]
+    pub model_id: Option<String>,
+    /// Vault tokens to keep consistent
+    #[serde(default)]
+    pub token_ids: Vec<String>,
+    #[serde(default)]
+    pub preferences: Option<UserPreferences>,
+    /// Output size for image tasks (default `Square1024`)
+    #[serde(default)]
+    pub preset: Option<ResolutionPreset>,
+    #[serde(default)]
+    pub duration_seconds: Option<f32>,
+    /// Source image for image-to-video
+    #[serde(default)]
+    pub reference_image: Option<String>,
+    /// Run even when the estimate exceeds `max_credits_per_request`
+    #[serde(default)]
+    pub confirmed: bool,
+}
+
+/// Where a generation ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationRoute {
+    /// Direct LLM call
+    Fast,
+    /// Local ComfyUI
+    Local,
+    /// Fal.ai queue
+    Cloud,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationStatus {
+    /// Submitted; poll ComfyUI or Fal.ai with `job_id`
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    /// Over the credit cap; resend with `confirmed` to run it
+    NeedsConfirmation,
+}
+
+/// Handle returned by `generate`
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct GenerationHandle {
+    pub id: String,
+    pub task_type: String,
+    pub model_id: String,
+    pub route: GenerationRoute,
+    pub status: GenerationStatus,
+    /// ComfyUI prompt id (local) or Fal.ai request id (cloud)
+    pub job_id: Option<String>,
+    /// Response text for Fast Path tasks
+    pub output: Option<String>,
+    pub estimated_credits: f32,
+    /// Adjustments made to the request (e.g. snapped dimensions)
+    pub warnings: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Progress event for a `generate` call
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct GenerationProgress {
+    pub handle_id: String,
+    pub status: GenerationStatus,
+    /// 0.0 - 1.0
+    pub progress: f32,
+    pub message: String,
+}
+
+/// What kind of output a task produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TaskKind {
+    Text,
+    Image,
+    Video,
+}
+
+/// A routed request, before anything has run
+struct GenerationPlan {
+    kind: TaskKind,
+    model_id: String,
+    route: GenerationRoute,
+    width: u32,
+    height: u32,
+    duration_seconds: f32,
+    estimated_credits: f32,
+    warnings: Vec<String>,
+    /// Why the request needs confirmation
+    blocked: Option<String>,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// ROUTING
+// ═══════════════════════════════════════════════════════════════════════════════
+
+fn task_kind(task_type: &str) -> Result<TaskKind, String> {
+    match task_type {
+        "chat" | "quick_text" | "translate" | "summarize" | "completion" | "script" => {
+            Ok(TaskKind::Text)
+        }
+        "image" | "concept_art" => Ok(TaskKind::Image),
+        "video" | "shot" | "video_fast" | "image_to_video" | "i2v" => Ok(TaskKind::Video),
+        other => Err(format!(
+            "Task type '{}' is not supported by generate",
+            other
+        )),
+    }
+}
+
+fn default_model(kind: TaskKind, prefer_local: bool) -> &'static str {
+    match (kind, prefer_local) {
+        (TaskKind::Text, _) => DEFAULT_TEXT_MODEL,
+        (TaskKind::Image, true) => DEFAULT_LOCAL_IMAGE_MODEL,
+        (TaskKind::Image, false) => DEFAULT_CLOUD_IMAGE_MODEL,
+        (TaskKind::Video, true) => DEFAULT_LOCAL_VIDEO_MODEL,
+        (TaskKind::Video, false) => DEFAULT_CLOUD_VIDEO_MODEL,
+    }
+}
+
+fn capability_for(kind: TaskKind, has_reference: bool) -> ModelCapability {
+    match (kind, has_reference) {
+        (TaskKind::Text, _) => ModelCapability::TextGeneration,
+        (TaskKind::Image, _) => ModelCapability::TextToImage,
+        (TaskKind::Video, true) => ModelCapability::ImageToVideo,
+        (TaskKind::Video, false) => ModelCapability::TextToVideo,
+    }
+}
+
+fn plan(request: &GenerateRequest, hardware: &HardwareInfo) -> Result<GenerationPlan, String> {
+    let kind = task_kind(&request.task_type)?;
+    let preferences = request.preferences.clone().unwrap_or_default();
+    let default = default_model(kind, preferences.prefer_local);
+    // Local compute is free, so only cloud defaults are checked against the budget
+    let requested_model = if preferences.prefer_local {
+        request
+            .model_id
+            .clone()
+            .unwrap_or_else(|| default.to_string())
+    } else {
+        resolve_model(
+            request.model_id.as_deref(),
+            default,
+            capability_for(kind, request.reference_image.is_some()),
+            &preferences,
+        )
+        .map_err(|e| e.to_string())?
+    };
+
+    // The GPU may not fit the model, in which case a smaller one or the cloud runs it
+    let decision = route_execution(
+        &request.task_type,
+        &requested_model,
+        preferences.prefer_local,
+        hardware,
+    );
+    let fell_back = decision.model_id != requested_model
+        || (preferences.prefer_local
+            && matches!(
+                decision.path,
+                ExecutionPath::WorkflowPath {
+                    execution_target: WorkflowTarget::Cloud,
+                    ..
+                }
+            ));
+    let model_id = decision.model_id;
+
+    // Hybrid targets have nothing to post-process here, so they run as cloud jobs
+    let route = match decision.path {
+        ExecutionPath::FastPath { .. } => GenerationRoute::Fast,
+        ExecutionPath::WorkflowPath {
+            execution_target: WorkflowTarget::Local,
+            ..
+        } => GenerationRoute::Local,
+        ExecutionPath::WorkflowPath { .. } => GenerationRoute::Cloud,
+    };
+
+    let (width, height) = match kind {
+        TaskKind::Video => ResolutionPreset::Hd720.dimensions(),
+        _ => request
+            .preset
+            .unwrap_or(ResolutionPreset::Square1024)
+            .dimensions(),
+    };
+    let mut size = validate_dimensions(&model_id, width, height);
+    if fell_back {
+        size.warnings.insert(0, decision.reason);
+    }
+    let duration_seconds = request.duration_seconds.unwrap_or(DEFAULT_VIDEO_SECONDS);
+
+    let action = match kind {
+        TaskKind::Text => None,
+        TaskKind::Image => Some(AgentAction::GenerateImage {
+            prompt: request.prompt.clone(),
+            model: model_id.clone(),
+            width: size.width,
+            height: size.height,
+            preset: None,
+            token_ids: request.token_ids.clone(),
+            control: None,
+            seed: None,
+            batch_size: 1,
+            input_image: None,
+        }),
+        TaskKind::Video => Some(AgentAction::GenerateVideo {
+            prompt: request.prompt.clone(),
+            model: model_id.clone(),
+            duration_seconds,
+            reference_image: request.reference_image.clone(),
+            token_ids: request.token_ids.clone(),
+        }),
+    };
+
+    // Local compute is free
+    let (estimated_credits, blocked) = match (&action, route) {
+        (Some(action), GenerationRoute::Cloud) => {
+            let blocked = if request.confirmed {
+                None
+            } else {
+                ActionExecutor::check_credit_cap(action, Some(preferences.max_credits_per_request))
+                    .and_then(|result| result.error)
+            };
+            (ActionExecutor::estimate_credits(action), blocked)
+        }
+        _ => (0.0, None),
+    };
+
+    Ok(GenerationPlan {
+        kind,
+        model_id,
+        route,
+        width: size.width,
+        height: size.height,
+        duration_seconds,
+        estimated_credits,
+        warnings: size.warnings,
+        blocked,
+    })
+}
+
+/// LLM provider for a Fast Path model
+fn llm_provider_for(model_id: &str) -> LLMProvider {
+    let model = model_id.to_lowercase();
+    if model.starts_with("claude") {
+        LLMProvider::Anthropic
+    } else if model.starts_with("gpt") || model.starts_with("o3") {
+        LLMProvider::OpenAI
+    } else if model.starts_with("llama") || model.starts_with("qwen") {
+        LLMProvider::Ollama
+    } else {
+        LLMProvider::Gemini
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// EXECUTION
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Route and run a generation request.
+///
+/// Fast Path tasks complete before returning; workflow tasks return a
+/// `Queued` handle carrying the ComfyUI or Fal.ai job id.
+pub async fn generate(
+    request: GenerateRequest,
+    on_progress: impl Fn(GenerationProgress),
+) -> Result<GenerationHandle, String> {
+    let plan = plan(&request, machine_hardware())?;
+    let mut handle = GenerationHandle {
+        id: uuid::Uuid::new_v4().to_string(),
+        task_type: request.task_type.clone(),
+        model_id: plan.model_id.clone(),
+        route: plan.route,
+        status: GenerationStatus::Running,
+        job_id: None,
+        output: None,
+        estimated_credits: plan.estimated_credits,
+        warnings: plan.warnings.clone(),
+        error: None,
+    };
+
+    if let Some(reason) = plan.blocked.clone() {
+        handle.status = GenerationStatus::NeedsConfirmation;
+        handle.error = Some(reason);
+        report(&on_progress, &handle, 1.0, "Waiting for confirmation");
+        return Ok(handle);
+    }
+
+    report(
+        &on_progress,
+        &handle,
+        0.0,
+        &format!("Routing {} to {:?}", plan.model_id, plan.route),
+    );
+
+    let result = match plan.route {
+        GenerationRoute::Fast => run_fast(&request, &plan).await.map(|text| {
+            handle.output = Some(text);
+            GenerationStatus::Completed
+        }),
+        GenerationRoute::Local => queue_local(&request, &plan).await.map(|prompt_id| {
+            handle.job_id = Some(prompt_id);
+            GenerationStatus::Queued
+        }),
+        GenerationRoute::Cloud => submit_cloud(&request, &plan).await.map(|request_id| {
+            handle.job_id = Some(request_id);
+            GenerationStatus::Queued
+        }),
+    };
+
+    match result {
+        Ok(status) => {
+            handle.status = status;
+            let message = match status {
+                GenerationStatus::Completed => "Generation complete",
+                _ => "Job submitted",
+            };
+            report(&on_progress, &handle, 1.0, message);
+        }
+        Err(e) => {
+            tracing::warn!("generate {} failed: {}", request.task_type, e);
+            handle.status = GenerationStatus::Failed;
+            handle.error = Some(e.clone());
+            report(&on_progress, &handle, 1.0, &e);
+        }
+    }
+
+    Ok(handle)
+}
+
+async fn run_fast(request: &GenerateRequest, plan: &GenerationPlan) -> Result<String, String> {
+    let response = get_llm_client()
+        .chat(LLMRequest {
+            provider: llm_provider_for(&plan.model_id),
+            model: plan.model_id.clone(),
+            messages: vec![LLMMessage {
+                role: "user".into(),
+                content: request.prompt.clone(),
+                images: Vec::new(),
+            }],
+            temperature: None,
+            max_tokens: None,
+            system_prompt: None,
+        })
+        .await?;
+    Ok(response.content)
+}
+
+async fn queue_local(request: &GenerateRequest, plan: &GenerationPlan) -> Result<String, String> {
+    let workflow_type = match (plan.kind, &request.reference_image) {
+        (TaskKind::Video, Some(_)) => WorkflowType::ImageToVideo,
+        (TaskKind::Video, None) => WorkflowType::TextToVideo,
+        _ => WorkflowType::TextToImage,
+    };
+
+    let result = ActionExecutor::execute_image_workflow(&WorkflowRequest {
+        workflow_type,
+        prompt: request.prompt.clone(),
+        negative_prompt: None,
+        model: plan.model_id.clone(),
+        width: plan.width,
+        height: plan.height,
+        steps: None,
+        seed: None,
+        batch_size: 1,
+        input_image: request.reference_image.clone(),
+        force_local: Some(true),
+        control: None,
+        mask_image: None,
+        expand_pixels: None,
+        params: None,
+    })
+    .await;
+
+    match (result.success, result.execution_id) {
+        (true, Some(prompt_id)) => Ok(prompt_id),
+        _ => Err(result
+            .error
+            .unwrap_or_else(|| "Local ComfyUI did not queue the workflow".to_string())),
+    }
+}
+
+async fn submit_cloud(request: &GenerateRequest, plan: &GenerationPlan) -> Result<String, String> {
+    let params = match plan.kind {
+        TaskKind::Video => json!({
+            "prompt": request.prompt,
+            "duration_seconds": plan.duration_seconds,
+            "image": request.reference_image,
+        }),
+        _ => json!({
+            "prompt": request.prompt,
+            "width": plan.width,
+            "height": plan.height,
+        }),
+    };
+    validate_request_for_model(&plan.model_id, &params)?;
+
+    // Fal.ai names some fields differently from the model schemas
+
+    let api_key = std::env::var("FAL_KEY").map_err(|_| "FAL_KEY not set".to_string())?;
+    let body = match plan.kind {
+        TaskKind::Video => json!({
+            "prompt": request.prompt,
+            "duration": plan.duration_seconds,
+            "image_url": request.reference_image,
+        }),
+        _ => json!({
+            "prompt": request.prompt,
+            "image_size": { "width": plan.width, "height": plan.height },
+        }),
+    };
+
+    let client = FalClient::new(api_key);
+    let queued = generation_queue()
+        .run(
+            GenerationLane::Cloud,
+            &plan.model_id,
+            DEFAULT_PRIORITY,
+            client.submit(&cloud_endpoint(plan, request), body),
+        )
+        .await??;
+    Ok(queued.request_id)
+}
+
+/// Fal endpoint for a cloud plan; video with a reference frame runs image-to-video
+fn cloud_endpoint(plan: &GenerationPlan, request: &GenerateRequest) -> String {
+    let from_image = plan.kind == TaskKind::Video && request.reference_image.is_some();
+    fal_endpoint_for(&plan.model_id, from_image)
+}
+
+fn report(
+    on_progress: &impl Fn(GenerationProgress),
+    handle: &GenerationHandle,
+    progress: f32,
+    message: &str,
+) {
+    on_progress(GenerationProgress {
+        handle_id: handle.id.clone(),
+        status: handle.status,
+        progress,
+        message: message.to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comfyui::models::CloudModels;
+
+    fn request(task_type: &str, prefer_local: bool, max_credits: f32) -> GenerateRequest {
+        GenerateRequest {
+            task_type: task_type.into(),
+            prompt: "A lighthouse at dusk".into(),
+            model_id: None,
+            token_ids: vec![],
+            preferences: Some(UserPreferences {
+                prefer_local,
+                max_credits_per_request: max_credits,
+                preferred_models: vec![],
+            }),
+            preset: None,
+            duration_seconds: None,
+            reference_image: None,
+            confirmed: false,
+        }
+    }
+
+    fn gpu(vram_gb: u32) -> HardwareInfo {
+        HardwareInfo {
+            vram_gb,
+            ..HardwareInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_routes_by_task_and_preference() {
+        let chat = plan(&request("chat", false, 0.0), &gpu(24)).unwrap();
+        assert_eq!(chat.route, GenerationRoute::Fast);
+        assert_eq!(chat.model_id, DEFAULT_TEXT_MODEL);
+
+        let local = plan(&request("image", true, 0.0), &gpu(24)).unwrap();
+        assert_eq!(local.route, GenerationRoute::Local);
+        assert_eq!(local.model_id, DEFAULT_LOCAL_IMAGE_MODEL);
+        assert_eq!(local.estimated_credits, 0.0);
+
+        let cloud = plan(&request("video", false, 0.0), &gpu(24)).unwrap();
+        assert_eq!(cloud.route, GenerationRoute::Cloud);
+        assert_eq!((cloud.width, cloud.height), (1280, 720));
+        assert!(cloud.estimated_credits > 0.0);
+
+        assert!(plan(&request("3d", false, 0.0), &gpu(24)).is_err());
+    }
+
+    #[test]
+    fn test_small_gpu_falls_back_to_cloud() {
+        let fallback = plan(&request("video", true, 0.0), &gpu(6)).unwrap();
+        assert_eq!(fallback.route, GenerationRoute::Cloud);
+        // The local model's id means nothing to Fal; a cloud video model runs it
+        assert_eq!(fallback.model_id, DEFAULT_CLOUD_VIDEO_MODEL);
+        assert_eq!(
+            fal_endpoint_for(&fallback.model_id, false),
+            CloudModels::VEO_31
+        );
+        assert!(fallback.warnings[0].contains("in the cloud"));
+        assert!(fallback.estimated_credits > 0.0);
+    }
+
+    #[test]
+    fn test_video_with_reference_runs_image_to_video() {
+        let text = request("video", false, 0.0);
+        let text_plan = plan(&text, &gpu(24)).unwrap();
+        assert_eq!(cloud_endpoint(&text_plan, &text), CloudModels::VEO_31);
+
+        let mut image = text.clone();
+        image.reference_image = Some("https://example.com/frame.png".into());
+        let image_plan = plan(&image, &gpu(24)).unwrap();
+        assert_eq!(cloud_endpoint(&image_plan, &image), CloudModels::VEO_31_I2V);
+    }
+
+    #[test]
+    fn test_credit_cap_requires_confirmation() {
+        let mut named = request("video", false, 1.0);
+        named.model_id = Some(DEFAULT_CLOUD_VIDEO_MODEL.into());
+        let blocked = plan(&named, &gpu(24)).unwrap();
+        assert!(blocked
+            .blocked
+            .unwrap()
+            .starts_with("Insufficient credit limit"));
+
+        let mut confirmed = named.clone();
+        confirmed.confirmed = true;
+        assert!(plan(&confirmed, &gpu(24)).unwrap().blocked.is_none());
+
+        // Local runs are never blocked
+        assert!(plan(&request("image", true, 0.01), &gpu(24))
+            .unwrap()
+            .blocked
+            .is_none());
+    }
+
+    #[test]
+    fn test_over_budget_request_is_rejected() {
+        // No model was named and none fits the cap
+        let error = plan(&request("video", false, 0.01), &gpu(24)).unwrap_err();
+        assert!(error.starts_with("Insufficient credits"), "{}", error);
+
+        // A cap the default fits keeps it
+        let cost = plan(&request("image", false, 0.0), &gpu(24))
+            .unwrap()
+            .estimated_credits;
+        let capped = plan(&request("image", false, cost), &gpu(24)).unwrap();
+        assert_eq!(capped.model_id, DEFAULT_CLOUD_IMAGE_MODEL);
+        assert!(capped.blocked.is_none());
+    }
+
+    #[test]
+    fn test_llm_provider_for_model() {
+        assert!(matches!(
+            llm_provider_for("claude-4.5-sonnet"),
+            LLMProvider::Anthropic
+        ));
+        assert!(matches!(
+            llm_provider_for("gpt-5.2-turbo"),
+            LLMProvider::OpenAI
+        ));
+        assert!(matches!(
+            llm_provider_for("gemini-2.5-flash"),
+            LLMProvider::Gemini
+        ));
+    }
+}
@@ -0,0 +1,538 @@
+//! Generation Queue - Global concurrency cap for every generation
+//!
+//! Local ComfyUI workflows, Fal.ai submissions and TTS all run through one
+//! `GenerationQueue` so a storyboard run cannot saturate the GPU or exceed the
+//! cloud provider's concurrency limit. Local and cloud jobs have separate caps,
+//! each backed by a semaphore. Waiting jobs start highest priority first (FIFO
+//! within a priority) and can be reprioritized or cancelled until they start.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+
+use crate::settings::{load_json, save_json};
+
+/// Local generations running at once by default (one GPU)
+pub const DEFAULT_MAX_LOCAL: u32 = 1;
+
+/// Cloud generations running at once by default
+pub const DEFAULT_MAX_CLOUD: u32 = 4;
+
+/// Priority of jobs scheduled without an explicit one
+pub const DEFAULT_PRIORITY: i32 = 0;
+
+/// Where the caps are persisted
+const LIMITS_FILE: &str = "generation_queue.json";
+
+static GENERATION_QUEUE: Lazy<GenerationQueue> =
+    Lazy::new(|| GenerationQueue::new(load_json(LIMITS_FILE)));
+
+/// The app-wide generation queue
+pub fn generation_queue() -> &'static GenerationQueue {
+    &GENERATION_QUEUE
+}
+
+/// Change the app-wide caps (persisted across restarts)
+pub fn set_queue_limits(limits: QueueLimits) -> Result<QueueStatus, String> {
+    save_json(LIMITS_FILE, &limits)?;
+    Ok(generation_queue().set_limits(limits))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Which cap a job counts against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationLane {
+    /// Local ComfyUI / GPU
+    Local,
+    /// Fal.ai, ElevenLabs and other cloud providers
+    Cloud,
+}
+
+/// Concurrency caps (values below 1 are treated as 1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct QueueLimits {
+    pub max_local: u32,
+    pub max_cloud: u32,
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        Self {
+            max_local: DEFAULT_MAX_LOCAL,
+            max_cloud: DEFAULT_MAX_CLOUD,
+        }
+    }
+}
+
+impl QueueLimits {
+    fn for_lane(&self, lane: GenerationLane) -> usize {
+        let limit = match lane {
+            GenerationLane::Local => self.max_local,
+            GenerationLane::Cloud => self.max_cloud,
+        };
+        limit.max(1) as usize
+    }
+}
+
+/// A job waiting for a slot
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct PendingGeneration {
+    pub id: String,
+    /// What is being generated (usually the model id)
+    pub label: String,
+    pub lane: GenerationLane,
+    /// Higher runs first
+    pub priority: i32,
+}
+
+/// Snapshot of the queue for the UI
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct QueueStatus {
+    pub limits: QueueLimits,
+    pub running_local: u32,
+    pub running_cloud: u32,
+    /// Waiting jobs in the order they will start
+    pub pending: Vec<PendingGeneration>,
+}
+
+struct Lane {
+    semaphore: Arc<Semaphore>,
+    running: usize,
+    /// Permits still to be retired after the cap was lowered while jobs ran
+    owed: usize,
+}
+
+impl Lane {
+    fn new(limit: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            running: 0,
+            owed: 0,
+        }
+    }
+
+    fn resize(&mut self, from: usize, to: usize) {
+        if to > from {
+            let added = to - from;
+            let repaid = added.min(self.owed);
+            self.owed -= repaid;
+            self.semaphore.add_permits(added - repaid);
+        } else if to < from {
+            let removed = from - to;
+            self.owed += removed - self.semaphore.forget_permits(removed);
+        }
+    }
+}
+
+struct Pending {
+    job: PendingGeneration,
+    seq: u64,
+}
+
+struct QueueState {
+    limits: QueueLimits,
+    local: Lane,
+    cloud: Lane,
+    /// Kept in start order
+    pending: Vec<Pending>,
+    next_seq: u64,
+}
+
+impl QueueState {
+    fn lane(&mut self, lane: GenerationLane) -> &mut Lane {
+        match lane {
+            GenerationLane::Local => &mut self.local,
+            GenerationLane::Cloud => &mut self.cloud,
+        }
+    }
+
+    fn sort_pending(&mut self) {
+        self.pending
+            .sort_by_key(|p| (std::cmp::Reverse(p.job.priority), p.seq));
+    }
+
+    fn remove_pending(&mut self, id: &str) -> bool {
+        let before = self.pending.len();
+        self.pending.retain(|p| p.job.id != id);
+        self.pending.len() != before
+    }
+}
+
+enum Start {
+    Started(OwnedSemaphorePermit),
+    Waiting,
+    Cancelled,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// QUEUE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+pub struct GenerationQueue {
+    state: Mutex<QueueState>,
+    changed: Notify,
+}
+
+impl GenerationQueue {
+    pub fn new(limits: QueueLimits) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                limits,
+                local: Lane::new(limits.for_lane(GenerationLane::Local)),
+                cloud: Lane::new(limits.for_lane(GenerationLane::Cloud)),
+                pending: Vec::new(),
+                next_seq: 0,
+            }),
+            changed: Notify::new(),
+        }
+    }
+
+    /// Run `job` once a `lane` slot is free
+    ///
+    /// Returns an error without running the job if it is cancelled while waiting.
+    pub async fn run<T>(
+        &self,
+        lane: GenerationLane,
+        label: &str,
+        priority: i32,
+        job: impl Future<Output = T>,
+    ) -> Result<T, String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.enqueue(&id, lane, label, priority);
+
+        let slot = {
+            // Drops the pending entry if the caller gives up while waiting
+            let _waiting = PendingGuard {
+                queue: self,
+                id: &id,
+            };
+            self.wait_for_slot(&id, lane).await?
+        };
+
+        let output = job.await;
+        drop(slot);
+        Ok(output)
+    }
+
+    /// Current caps, running counts and waiting jobs
+    pub fn status(&self) -> QueueStatus {
+        let state = self.lock();
+        QueueStatus {
+            limits: state.limits,
+            running_local: state.local.running as u32,
+            running_cloud: state.cloud.running as u32,
+            pending: state.pending.iter().map(|p| p.job.clone()).collect(),
+        }
+    }
+
+    /// Change the caps; running jobs finish, new ones respect the new caps
+    pub fn set_limits(&self, limits: QueueLimits) -> QueueStatus {
+        {
+            let mut state = self.lock();
+            for lane in [GenerationLane::Local, GenerationLane::Cloud] {
+                let from = state.limits.for_lane(lane);
+                state.lane(lane).resize(from, limits.for_lane(lane));
+            }
+            state.limits = limits;
+        }
+        self.changed.notify_waiters();
+        self.status()
+    }
+
+    /// Change a waiting job's priority; false if it is not waiting
+    pub fn reprioritize(&self, id: &str, priority: i32) -> bool {
+        let found = {
+            let mut state = self.lock();
+            let found = match state.pending.iter_mut().find(|p| p.job.id == id) {
+                Some(pending) => {
+                    pending.job.priority = priority;
+                    true
+                }
+                None => false,
+            };
+            state.sort_pending();
+            found
+        };
+        if found {
+            self.changed.notify_waiters();
+        }
+        found
+    }
+
+    /// Cancel a waiting job; false if it is not waiting (running jobs are not interrupted)
+    pub fn cancel(&self, id: &str) -> bool {
+        let removed = self.lock().remove_pending(id);
+        if removed {
+            tracing::info!("Cancelled queued generation {}", id);
+            self.changed.notify_waiters();
+        }
+        removed
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        // A panic while holding the lock leaves the counters consistent
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn enqueue(&self, id: &str, lane: GenerationLane, label: &str, priority: i32) {
+        let mut state = self.lock();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.pending.push(Pending {
+            job: PendingGeneration {
+                id: id.to_string(),
+                label: label.to_string(),
+                lane,
+                priority,
+            },
+            seq,
+        });
+        state.sort_pending();
+    }
+
+    async fn wait_for_slot(&self, id: &str, lane: GenerationLane) -> Result<Slot<'_>, String> {
+        loop {
+            // Register before checking so a release between the check and the await is not missed
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+
+            match self.try_start(id, lane) {
+                Start::Started(permit) => {
+                    return Ok(Slot {
+                        queue: self,
+                        lane,
+                        permit: Some(permit),
+                    })
+                }
+                Start::Cancelled => return Err("Generation cancelled while queued".into()),
+                Start::Waiting => changed.await,
+            }
+        }
+    }
+
+    fn try_start(&self, id: &str, lane: GenerationLane) -> Start {
+        let mut state = self.lock();
+        let next = state
+            .pending
+            .iter()
+            .find(|p| p.job.lane == lane)
+            .map(|p| p.job.id.as_str());
+
+        match next {
+            Some(next) if next == id => {}
+            _ if !state.pending.iter().any(|p| p.job.id == id) => return Start::Cancelled,
+            _ => return Start::Waiting,
+        }
+
+        match state.lane(lane).semaphore.clone().try_acquire_owned() {
+            Ok(permit) => {
+                state.remove_pending(id);
+                state.lane(lane).running += 1;
+                Start::Started(permit)
+            }
+            Err(_) => Start::Waiting,
+        }
+    }
+
+    fn release(&self, lane: GenerationLane, permit: OwnedSemaphorePermit) {
+        {
+            let mut state = self.lock();
+            let lane = state.lane(lane);
+            lane.running -= 1;
+            if lane.owed > 0 {
+                lane.owed -= 1;
+                permit.forget();
+            } else {
+                drop(permit);
+            }
+        }
+        self.changed.notify_waiters();
+    }
+}
+
+/// A held slot; frees it on drop
+struct Slot<'a> {
+    queue: &'a GenerationQueue,
+    lane: GenerationLane,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        if let Some(permit) = self.permit.take() {
+            self.queue.release(self.lane, permit);
+        }
+    }
+}
+
+struct PendingGuard<'a> {
+    queue: &'a GenerationQueue,
+    id: &'a str,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        if self.queue.lock().remove_pending(self.id) {
+            self.queue.changed.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Run `count` jobs on `lane`, returning the most that ran at once
+    async fn peak_concurrency(
+        queue: Arc<GenerationQueue>,
+        lane: GenerationLane,
+        count: usize,
+    ) -> usize {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..count)
+            .map(|i| {
+                let (queue, running, peak) = (queue.clone(), running.clone(), peak.clone());
+                tokio::spawn(async move {
+                    queue
+                        .run(lane, &format!("job-{}", i), DEFAULT_PRIORITY, async {
+                            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                            peak.fetch_max(now, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            running.fetch_sub(1, Ordering::SeqCst);
+                        })
+                        .await
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+        peak.load(Ordering::SeqCst)
+    }
+
+    async fn wait_for_pending(queue: &GenerationQueue, count: usize) {
+        while queue.status().pending.len() < count {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_caps_concurrency_per_lane() {
+        let queue = Arc::new(GenerationQueue::new(QueueLimits {
+            max_local: 2,
+            max_cloud: 3,
+        }));
+
+        assert_eq!(
+            peak_concurrency(queue.clone(), GenerationLane::Local, 6).await,
+            2
+        );
+        assert_eq!(
+            peak_concurrency(queue.clone(), GenerationLane::Cloud, 6).await,
+            3
+        );
+
+        let status = queue.status();
+        assert_eq!((status.running_local, status.running_cloud), (0, 0));
+        assert!(status.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lowered_cap_applies_to_new_jobs() {
+        let queue = Arc::new(GenerationQueue::new(QueueLimits {
+            max_local: 4,
+            max_cloud: 1,
+        }));
+        queue.set_limits(QueueLimits {
+            max_local: 1,
+            max_cloud: 1,
+        });
+
+        assert_eq!(
+            peak_concurrency(queue.clone(), GenerationLane::Local, 4).await,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_priority_and_cancel() {
+        let queue = Arc::new(GenerationQueue::new(QueueLimits {
+            max_local: 1,
+            max_cloud: 1,
+        }));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (release, blocked) = tokio::sync::oneshot::channel::<()>();
+
+        let blocker = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                queue
+                    .run(GenerationLane::Local, "blocker", 0, async {
+                        blocked.await.ok();
+                    })
+                    .await
+            })
+        };
+        while queue.status().running_local == 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        let spawn = |label: &'static str, priority: i32| {
+            let (queue, order) = (queue.clone(), order.clone());
+            tokio::spawn(async move {
+                queue
+                    .run(GenerationLane::Local, label, priority, async {
+                        order.lock().unwrap().push(label);
+                    })
+                    .await
+            })
+        };
+
+        let low = spawn("low", 0);
+        wait_for_pending(&queue, 1).await;
+        let dropped = spawn("dropped", 0);
+        wait_for_pending(&queue, 2).await;
+        let high = spawn("high", 0);
+        wait_for_pending(&queue, 3).await;
+
+        let pending = queue.status().pending;
+        let high_id = pending
+            .iter()
+            .find(|p| p.label == "high")
+            .unwrap()
+            .id
+            .clone();
+        let dropped_id = pending
+            .iter()
+            .find(|p| p.label == "dropped")
+            .unwrap()
+            .id
+            .clone();
+        assert!(queue.reprioritize(&high_id, 10));
+        assert!(queue.cancel(&dropped_id));
+        assert_eq!(queue.status().pending[0].label, "high");
+
+        release.send(()).unwrap();
+        blocker.await.unwrap().unwrap();
+        high.await.unwrap().unwrap();
+        low.await.unwrap().unwrap();
+        assert!(dropped.await.unwrap().is_err());
+
+        assert_eq!(*order.lock().unwrap(), vec!["high", "low"]);
+        assert!(!queue.cancel(&high_id));
+    }
+}
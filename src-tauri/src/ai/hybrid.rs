@@ -0,0 +1,527 @@
+//! Hybrid Execution - Cloud generation, local post-processing
+//!
+//! Implements `WorkflowTarget::Hybrid`: the heavy generation step runs on
+//! Fal.ai, then the cloud output is copied into the local ComfyUI input folder
+//! and piped through a small post-processing graph (upscale, sharpen).
+//! If local ComfyUI isn't running, the cloud output is returned as-is.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use specta::Type;
+use std::time::{Duration, Instant};
+
+use crate::ai::comfyui::{determine_execution_path, get_workflow_template, ExecutionPath};
+use crate::ai::cost::usd_to_credits;
+use crate::ai::fal_client::{FalClient, FalResult};
+use crate::comfyui::client::{ComfyUIClient, ImageOutput};
+use crate::comfyui::models::CloudModels;
+use crate::comfyui::ComfyUIConfig;
+
+/// Maximum time to wait for the cloud generation
+const CLOUD_TIMEOUT_SECS: u64 = 600;
+
+/// Maximum time to wait for the local post-processing graph
+const LOCAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+const LOCAL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Share of the combined progress bar taken by the cloud stage
+const CLOUD_WEIGHT: f32 = 0.7;
+
+/// Progress reached once the cloud output is staged locally
+const TRANSFER_DONE: f32 = 0.8;
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// A local post-processing step (core ComfyUI nodes only)
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PostProcess {
+    /// Lanczos resize (`ImageScaleBy`)
+    Upscale { scale: f32 },
+    /// Unsharp mask (`ImageSharpen`)
+    Sharpen { radius: u32, sigma: f32, alpha: f32 },
+}
+
+/// Hybrid generation request
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct HybridRequest {
+    /// Task type as understood by `determine_execution_path` ("image", "video", ...)
+    pub task_type: String,
+    pub model_id: String,
+    pub prompt: String,
+    pub width: u32,
+    pub height: u32,
+    /// Local steps, applied in order
+    pub post_process: Vec<PostProcess>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum HybridStage {
+    Cloud,
+    Transfer,
+    Local,
+    Complete,
+}
+
+/// Combined progress across the cloud and local stages
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct HybridProgress {
+    pub stage: HybridStage,
+    /// 0.0 - 1.0 over the whole pipeline
+    pub progress: f32,
+    pub message: String,
+}
+
+/// Result of a hybrid run
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct HybridResult {
+    /// Final output: the local ComfyUI view URL, or the cloud URL on fallback
+    pub output_url: String,
+    pub cloud_output_url: String,
+    /// Whether the local post-processing graph ran
+    pub post_processed: bool,
+    /// Why local post-processing was skipped
+    pub fallback_reason: Option<String>,
+    pub cloud_cost: f32,
+    /// Local compute is free
+    pub local_cost: f32,
+    pub total_cost: f32,
+    /// `total_cost` converted with the shared pricing config
+    pub total_credits: f32,
+    #[specta(type = f64)]
+    pub duration_ms: u64,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// EXECUTOR
+// ═══════════════════════════════════════════════════════════════════════════════
+
+pub struct HybridExecutor {
+    fal: FalClient,
+    comfyui: ComfyUIConfig,
+}
+
+impl HybridExecutor {
+    pub fn new(fal_api_key: String, comfyui: ComfyUIConfig) -> Self {
+        Self {
+            fal: FalClient::new(fal_api_key),
+            comfyui,
+        }
+    }
+
+    /// Create an executor using `FAL_KEY` and the default local ComfyUI
+    pub fn from_env() -> Result<Self, String> {
+        let api_key = std::env::var("FAL_KEY").map_err(|_| "FAL_KEY not set".to_string())?;
+        Ok(Self::new(api_key, ComfyUIConfig::default()))
+    }
+
+    /// Generate on Fal.ai, then post-process on local ComfyUI.
+    ///
+    /// Local failures never fail the request: the cloud output is returned
+    /// with `fallback_reason` set instead.
+    pub async fn execute(
+        &self,
+        request: HybridRequest,
+        on_progress: impl Fn(HybridProgress),
+    ) -> Result<HybridResult, String> {
+        let started = Instant::now();
+        let cloud_cost = estimated_cloud_cost(&request);
+
+        // 1. Heavy generation on the cloud
+        report(
+            &on_progress,
+            HybridStage::Cloud,
+            0.0,
+            format!("Generating with {} on Fal.ai", request.model_id),
+        );
+
+        let endpoint = fal_endpoint_for(&request.model_id, false);
+        let queued = self
+            .fal
+            .submit(
+                &endpoint,
+                json!({
+                    "prompt": request.prompt,
+                    "image_size": { "width": request.width, "height": request.height },
+                }),
+            )
+            .await?;
+        let result = self
+            .fal
+            .poll(&queued.request_id, CLOUD_TIMEOUT_SECS)
+            .await?;
+
+        let (cloud_output_url, is_image) =
+            cloud_output(&result).ok_or_else(|| "Fal.ai returned no output".to_string())?;
+
+        report(
+            &on_progress,
+            HybridStage::Cloud,
+            CLOUD_WEIGHT,
+            "Cloud generation finished".to_string(),
+        );
+
+        // 2. Local post-processing, falling back to the cloud output
+        let local = if request.post_process.is_empty() {
+            Err("No post-processing requested".to_string())
+        } else if !is_image {
+            Err("Local post-processing only supports image outputs".to_string())
+        } else {
+            self.post_process_locally(&cloud_output_url, &request.post_process, &on_progress)
+                .await
+        };
+
+        let (output_url, fallback_reason) = match local {
+            Ok(url) => (url, None),
+            Err(reason) => {
+                tracing::warn!(
+                    "Hybrid post-processing skipped, using cloud output: {}",
+                    reason
+                );
+                (cloud_output_url.clone(), Some(reason))
+            }
+        };
+
+        report(
+            &on_progress,
+            HybridStage::Complete,
+            1.0,
+            "Generation complete".to_string(),
+        );
+
+        Ok(HybridResult {
+            output_url,
+            cloud_output_url,
+            post_processed: fallback_reason.is_none(),
+            fallback_reason,
+            cloud_cost,
+            local_cost: 0.0,
+            total_cost: cloud_cost,
+            total_credits: usd_to_credits(cloud_cost, &request.model_id),
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+
+    async fn post_process_locally(
+        &self,
+        cloud_url: &str,
+        steps: &[PostProcess],
+        on_progress: &impl Fn(HybridProgress),
+    ) -> Result<String, String> {
+        let client = ComfyUIClient::new(&self.comfyui.host, self.comfyui.port);
+        client
+            .get_system_stats()
+            .await
+            .map_err(|_| "Local ComfyUI is not running".to_string())?;
+
+        report(
+            on_progress,
+            HybridStage::Transfer,
+            CLOUD_WEIGHT,
+            "Transferring cloud output to local ComfyUI".to_string(),
+        );
+        let filename = self.stage_input(cloud_url).await?;
+
+        report(
+            on_progress,
+            HybridStage::Local,
+            TRANSFER_DONE,
+            "Post-processing locally".to_string(),
+        );
+        let workflow = build_post_process_workflow(&filename, steps);
+        let queued = client
+            .queue_prompt(workflow)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let image = wait_for_image(&client, &queued.prompt_id).await?;
+        Ok(format!(
+            "http://{}:{}/view?filename={}&subfolder={}&type={}",
+            self.comfyui.host,
+            self.comfyui.port,
+            image.filename,
+            image.subfolder,
+            image.output_type
+        ))
+    }
+
+    /// Download the cloud output into ComfyUI's `input` folder for `LoadImage`
+    async fn stage_input(&self, url: &str) -> Result<String, String> {
+        let bytes = reqwest::get(url)
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Failed to download cloud output: {}", e))?
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to download cloud output: {}", e))?;
+
+        let extension = url
+            .split('?')
+            .next()
+            .and_then(|path| path.rsplit('/').next())
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, ext)| ext.to_lowercase())
+            .unwrap_or_else(|| "png".to_string());
+
+        let filename = format!("cinemaos_hybrid_{}.{}", uuid::Uuid::new_v4(), extension);
+        let input_dir = self.comfyui.install_path.join("input");
+        std::fs::create_dir_all(&input_dir).map_err(|e| e.to_string())?;
+        std::fs::write(input_dir.join(&filename), &bytes).map_err(|e| e.to_string())?;
+
+        Ok(filename)
+    }
+}
+
+fn report(
+    on_progress: &impl Fn(HybridProgress),
+    stage: HybridStage,
+    progress: f32,
+    message: String,
+) {
+    on_progress(HybridProgress {
+        stage,
+        progress,
+        message,
+    });
+}
+
+async fn wait_for_image(client: &ComfyUIClient, prompt_id: &str) -> Result<ImageOutput, String> {
+    let started = Instant::now();
+
+    while started.elapsed() < LOCAL_TIMEOUT {
+        let mut history = client
+            .get_history(prompt_id)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(entry) = history.data.remove(prompt_id) {
+            return entry
+                .outputs
+                .into_values()
+                .filter_map(|node| node.images)
+                .flatten()
+                .next()
+                .ok_or_else(|| "Local post-processing produced no image".to_string());
+        }
+
+        tokio::time::sleep(LOCAL_POLL_INTERVAL).await;
+    }
+
+    Err("Local post-processing timed out".to_string())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// HELPERS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Map a Model Matrix id to its Fal.ai endpoint (full endpoints pass through)
+///
+/// Video models go to their image-to-video endpoint when `from_image` is set.
+pub fn fal_endpoint_for(model_id: &str, from_image: bool) -> String {
+    if model_id.contains('/') {
+        return model_id.to_string();
+    }
+
+    let (text, image) = match model_id.to_lowercase().as_str() {
+        "nano-banana-pro" => (CloudModels::NANO_BANANA_PRO, CloudModels::NANO_BANANA_PRO),
+        "flux-pro-2.0" => (CloudModels::FLUX_2_PRO_FAL, CloudModels::FLUX_2_PRO_FAL),
+        "veo-3.1" | "veo3.1" => (CloudModels::VEO_31, CloudModels::VEO_31_I2V),
+        "veo-3.1-fast" => (CloudModels::VEO_31_FAST, CloudModels::VEO_31_FAST_I2V),
+        "sora-2" => (CloudModels::SORA_2, CloudModels::SORA_2_I2V),
+        "sora-2-pro" => (CloudModels::SORA_2_PRO, CloudModels::SORA_2_PRO_I2V),
+        "kling-v2.6" => (CloudModels::KLING_V26_T2V, CloudModels::KLING_V26_I2V),
+        "kling-v2.5-turbo" => (
+            CloudModels::KLING_V25_TURBO,
+            CloudModels::KLING_V25_TURBO_I2V,
+        ),
+        _ => (CloudModels::FLUX_2_FLEX, CloudModels::FLUX_2_FLEX),
+    };
+    if from_image { image } else { text }.to_string()
+}
+
+/// Estimated cloud cost from the workflow template the Hybrid path resolves to
+fn estimated_cloud_cost(request: &HybridRequest) -> f32 {
+    match determine_execution_path(&request.task_type, &request.model_id, true) {
+        ExecutionPath::WorkflowPath { workflow_id, .. } => get_workflow_template(&workflow_id)
+            .map(|w| w.estimated_cost)
+            .unwrap_or(0.0),
+        ExecutionPath::FastPath { .. } => 0.0,
+    }
+}
+
+/// First output URL of a Fal result, and whether it is an image
+fn cloud_output(result: &FalResult) -> Option<(String, bool)> {
+    if let Some(image) = result.images.as_ref().and_then(|images| images.first()) {
+        return Some((image.url.clone(), true));
+    }
+    result
+        .video
+        .as_ref()
+        .map(|v| v.url.clone())
+        .or_else(|| result.audio.as_ref().map(|a| a.url.clone()))
+        .map(|url| (url, false))
+}
+
+/// Build an API-format ComfyUI graph: LoadImage -> steps... -> SaveImage
+pub fn build_post_process_workflow(input_filename: &str, steps: &[PostProcess]) -> Value {
+    let mut graph = serde_json::Map::new();
+    graph.insert(
+        "1".into(),
+        json!({ "class_type": "LoadImage", "inputs": { "image": input_filename } }),
+    );
+
+    let mut previous = 1;
+    for (index, step) in steps.iter().enumerate() {
+        let id = index + 2;
+        let node = match step {
+            PostProcess::Upscale { scale } => json!({
+                "class_type": "ImageScaleBy",
+                "inputs": {
+                    "image": [previous.to_string(), 0],
+                    "upscale_method": "lanczos",
+                    "scale_by": scale,
+                },
+            }),
+            PostProcess::Sharpen {
+                radius,
+                sigma,
+                alpha,
+            } => json!({
+                "class_type": "ImageSharpen",
+                "inputs": {
+                    "image": [previous.to_string(), 0],
+                    "sharpen_radius": radius,
+                    "sigma": sigma,
+                    "alpha": alpha,
+                },
+            }),
+        };
+        graph.insert(id.to_string(), node);
+        previous = id;
+    }
+
+    graph.insert(
+        (previous + 1).to_string(),
+        json!({
+            "class_type": "SaveImage",
+            "inputs": {
+                "images": [previous.to_string(), 0],
+                "filename_prefix": "CinemaOS_hybrid",
+            },
+        }),
+    );
+
+    Value::Object(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::fal_client::{FalImage, FalVideo};
+
+    #[test]
+    fn test_post_process_graph_chains_steps() {
+        let graph = build_post_process_workflow(
+            "cloud.png",
+            &[
+                PostProcess::Upscale { scale: 2.0 },
+                PostProcess::Sharpen {
+                    radius: 1,
+                    sigma: 1.0,
+                    alpha: 0.5,
+                },
+            ],
+        );
+
+        assert_eq!(graph["1"]["class_type"], "LoadImage");
+        assert_eq!(graph["1"]["inputs"]["image"], "cloud.png");
+        assert_eq!(graph["2"]["class_type"], "ImageScaleBy");
+        assert_eq!(graph["2"]["inputs"]["image"], json!(["1", 0]));
+        assert_eq!(graph["3"]["class_type"], "ImageSharpen");
+        assert_eq!(graph["3"]["inputs"]["image"], json!(["2", 0]));
+        assert_eq!(graph["4"]["class_type"], "SaveImage");
+        assert_eq!(graph["4"]["inputs"]["images"], json!(["3", 0]));
+    }
+
+    #[test]
+    fn test_fal_endpoint_mapping() {
+        assert_eq!(fal_endpoint_for("veo-3.1", false), CloudModels::VEO_31);
+        assert_eq!(
+            fal_endpoint_for("fal-ai/recraft/v3/text-to-image", false),
+            CloudModels::RECRAFT_V3
+        );
+        assert_eq!(
+            fal_endpoint_for("flux-pro-2.0", false),
+            CloudModels::FLUX_2_PRO_FAL
+        );
+        assert_eq!(fal_endpoint_for("flux.2", false), CloudModels::FLUX_2_FLEX);
+    }
+
+    #[test]
+    fn test_video_endpoints_follow_the_input() {
+        assert_eq!(fal_endpoint_for("veo-3.1", true), CloudModels::VEO_31_I2V);
+        assert_eq!(fal_endpoint_for("sora-2", false), CloudModels::SORA_2);
+        assert_eq!(fal_endpoint_for("sora-2", true), CloudModels::SORA_2_I2V);
+        assert_eq!(
+            fal_endpoint_for("kling-v2.6", false),
+            CloudModels::KLING_V26_T2V
+        );
+        assert_eq!(
+            fal_endpoint_for("kling-v2.6", true),
+            CloudModels::KLING_V26_I2V
+        );
+        // Image models ignore it
+        assert_eq!(
+            fal_endpoint_for("flux-pro-2.0", true),
+            CloudModels::FLUX_2_PRO_FAL
+        );
+    }
+
+    #[test]
+    fn test_cloud_output_prefers_images() {
+        let image = FalResult {
+            images: Some(vec![FalImage {
+                url: "https://fal.media/a.png".into(),
+                width: None,
+                height: None,
+                content_type: None,
+            }]),
+            video: None,
+            audio: None,
+        };
+        assert_eq!(
+            cloud_output(&image),
+            Some(("https://fal.media/a.png".to_string(), true))
+        );
+
+        let video = FalResult {
+            images: None,
+            video: Some(FalVideo {
+                url: "https://fal.media/a.mp4".into(),
+                content_type: None,
+            }),
+            audio: None,
+        };
+        assert_eq!(
+            cloud_output(&video),
+            Some(("https://fal.media/a.mp4".to_string(), false))
+        );
+    }
+
+    #[test]
+    fn test_hybrid_cost_comes_from_template() {
+        let request = HybridRequest {
+            task_type: "video".into(),
+            model_id: "veo-3.1".into(),
+            prompt: "A rainy street".into(),
+            width: 1280,
+            height: 720,
+            post_process: vec![],
+        };
+        assert!((estimated_cloud_cost(&request) - 0.25).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,267 @@
+//! LLM Response Cache - Short-lived LRU cache for identical prompts
+//!
+//! Agents frequently resend identical prompts (e.g. re-opening the same chat).
+//! Caching deterministic requests avoids re-billing the user for the same answer.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::llm_client::{LLMRequest, LLMResponse};
+
+/// Cache configuration
+#[derive(Debug, Clone)]
+pub struct LLMCacheConfig {
+    /// Maximum number of cached responses (0 disables the cache)
+    pub capacity: usize,
+    /// How long a cached response stays valid
+    pub ttl: Duration,
+    /// Cache requests with temperature > 0 (non-deterministic) as well
+    pub allow_nondeterministic: bool,
+}
+
+impl Default for LLMCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            ttl: Duration::from_secs(300),
+            allow_nondeterministic: false,
+        }
+    }
+}
+
+struct CacheEntry {
+    response: LLMResponse,
+    inserted_at: Instant,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    entries: HashMap<u64, CacheEntry>,
+    /// Keys ordered from least to most recently used
+    order: VecDeque<u64>,
+}
+
+impl CacheInner {
+    fn touch(&mut self, key: u64) {
+        self.order.retain(|k| *k != key);
+        self.order.push_back(key);
+    }
+
+    fn remove(&mut self, key: u64) {
+        self.entries.remove(&key);
+        self.order.retain(|k| *k != key);
+    }
+}
+
+/// In-memory LRU cache of LLM responses keyed by request hash
+pub struct LLMCache {
+    config: LLMCacheConfig,
+    inner: Mutex<CacheInner>,
+}
+
+impl LLMCache {
+    pub fn new(config: LLMCacheConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(CacheInner::default()),
+        }
+    }
+
+    /// Compute the cache key for a request, or `None` if it must not be cached
+    pub fn key_for(&self, request: &LLMRequest) -> Option<u64> {
+        if self.config.capacity == 0 {
+            return None;
+        }
+
+        // Providers default to 0.7 when unset, so only an explicit 0.0 is deterministic
+        let deterministic = request.temperature.is_some_and(|t| t <= 0.0);
+        if !deterministic && !self.config.allow_nondeterministic {
+            return None;
+        }
+
+        let key_material = serde_json::to_string(&(
+            &request.provider,
+            &request.model,
+            &request.messages,
+            &request.system_prompt,
+            request.temperature,
+        ))
+        .ok()?;
+
+        let mut hasher = DefaultHasher::new();
+        key_material.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    /// Look up a cached response, marking it as `cached`
+    pub fn get(&self, key: u64) -> Option<LLMResponse> {
+        let mut inner = self.inner.lock().ok()?;
+
+        let expired = inner
+            .entries
+            .get(&key)
+            .map(|e| e.inserted_at.elapsed() > self.config.ttl)?;
+
+        if expired {
+            inner.remove(key);
+            return None;
+        }
+
+        inner.touch(key);
+        inner.entries.get(&key).map(|e| {
+            let mut response = e.response.clone();
+            response.cached = true;
+            response
+        })
+    }
+
+    /// Store a response, evicting the least recently used entry when full
+    pub fn insert(&self, key: u64, response: LLMResponse) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+
+        while inner.entries.len() >= self.config.capacity && !inner.entries.contains_key(&key) {
+            match inner.order.pop_front() {
+                Some(oldest) => {
+                    inner.entries.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+
+        inner.entries.insert(
+            key,
+            CacheEntry {
+                response,
+                inserted_at: Instant::now(),
+            },
+        );
+        inner.touch(key);
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().map(|i| i.entries.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.entries.clear();
+            inner.order.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::llm_client::{LLMMessage, LLMProvider};
+
+    fn request(content: &str, temperature: Option<f32>) -> LLMRequest {
+        LLMRequest {
+            provider: LLMProvider::Gemini,
+            model: "gemini-2.0-flash".into(),
+            messages: vec![LLMMessage {
+                role: "user".into(),
+                content: content.into(),
+                images: Vec::new(),
+            }],
+            temperature,
+            max_tokens: Some(1024),
+            system_prompt: Some("You are helpful".into()),
+        }
+    }
+
+    fn response(content: &str) -> LLMResponse {
+        LLMResponse {
+            content: content.into(),
+            model: "gemini-2.0-flash".into(),
+            usage: None,
+            finish_reason: Some("STOP".into()),
+            cached: false,
+        }
+    }
+
+    #[test]
+    fn test_cache_hit_and_miss() {
+        let cache = LLMCache::new(LLMCacheConfig::default());
+
+        let key = cache.key_for(&request("Hello", Some(0.0))).unwrap();
+        assert!(cache.get(key).is_none());
+
+        cache.insert(key, response("Hi there"));
+        let hit = cache.get(key).unwrap();
+        assert_eq!(hit.content, "Hi there");
+        assert!(hit.cached);
+
+        let other = cache.key_for(&request("Goodbye", Some(0.0))).unwrap();
+        assert_ne!(key, other);
+        assert!(cache.get(other).is_none());
+    }
+
+    #[test]
+    fn test_temperature_gate() {
+        let cache = LLMCache::new(LLMCacheConfig::default());
+        assert!(cache.key_for(&request("Hello", Some(0.7))).is_none());
+        assert!(cache.key_for(&request("Hello", None)).is_none());
+
+        let permissive = LLMCache::new(LLMCacheConfig {
+            allow_nondeterministic: true,
+            ..Default::default()
+        });
+        assert!(permissive.key_for(&request("Hello", Some(0.7))).is_some());
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = LLMCache::new(LLMCacheConfig {
+            capacity: 2,
+            ..Default::default()
+        });
+
+        let a = cache.key_for(&request("a", Some(0.0))).unwrap();
+        let b = cache.key_for(&request("b", Some(0.0))).unwrap();
+        let c = cache.key_for(&request("c", Some(0.0))).unwrap();
+
+        cache.insert(a, response("a"));
+        cache.insert(b, response("b"));
+        // Touch `a` so `b` becomes least recently used
+        assert!(cache.get(a).is_some());
+        cache.insert(c, response("c"));
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(a).is_some());
+        assert!(cache.get(b).is_none());
+        assert!(cache.get(c).is_some());
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let cache = LLMCache::new(LLMCacheConfig {
+            ttl: Duration::ZERO,
+            ..Default::default()
+        });
+
+        let key = cache.key_for(&request("Hello", Some(0.0))).unwrap();
+        cache.insert(key, response("Hi"));
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(cache.get(key).is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_cache() {
+        let cache = LLMCache::new(LLMCacheConfig {
+            capacity: 0,
+            ..Default::default()
+        });
+        assert!(cache.key_for(&request("Hello", Some(0.0))).is_none());
+    }
+}
@@ -1,11 +1,24 @@
 //! LLM Client - API calls to Gemini, OpenAI, Anthropic
 //!
-//! Provides unified interface for LLM inference across providers.
+//! Provides unified interface for LLM inference across providers. Every
+//! provider is called in streaming mode; `chat` collects the stream.
 
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use futures_util::stream::{BoxStream, Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use specta::Type;
+use std::collections::VecDeque;
 use std::env;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use super::llm_cache::{LLMCache, LLMCacheConfig};
+use super::llm_stream::{LLMStreamEvent, StreamCollector, StreamDecoder, StreamFormat};
+use super::local_models::{self, LocalRuntime};
+use crate::errors::LLMError;
+use crate::request_log::{self, RequestLogEntry};
 
 // ═══════════════════════════════════════════════════════════════════════════════
 // LLM PROVIDER TYPES
@@ -25,6 +38,29 @@ pub enum LLMProvider {
 pub struct LLMMessage {
     pub role: String, // "user", "assistant", "system"
     pub content: String,
+    /// Images sent along with `content` (vision models)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageInput>,
+}
+
+/// An image attached to a message
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageInput {
+    /// Raw base64 (no `data:` prefix)
+    Base64 { mime_type: String, data: String },
+    /// An image the provider fetches itself
+    Url { url: String },
+}
+
+impl ImageInput {
+    /// A URL OpenAI-style APIs accept: the URL itself or a `data:` URL
+    fn as_url(&self) -> String {
+        match self {
+            ImageInput::Base64 { mime_type, data } => format!("data:{};base64,{}", mime_type, data),
+            ImageInput::Url { url } => url.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -43,6 +79,9 @@ pub struct LLMResponse {
     pub model: String,
     pub usage: Option<TokenUsage>,
     pub finish_reason: Option<String>,
+    /// Served from the in-memory response cache (not re-billed)
+    #[serde(default)]
+    pub cached: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -56,41 +95,281 @@ pub struct TokenUsage {
 // LLM CLIENT
 // ═══════════════════════════════════════════════════════════════════════════════
 
+/// Retries after a rate limit, timeout, network error or 5xx
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Longer waits (e.g. a `Retry-After` of an hour) fail the request instead
+const MAX_RETRY_WAIT_SECS: u64 = 60;
+
+/// Rate limit wait when the provider sends no `Retry-After`
+const DEFAULT_RETRY_AFTER_SECS: u64 = 5;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
 pub struct LLMClient {
     http: Client,
+    cache: Option<LLMCache>,
+    max_retries: u32,
 }
 
 impl LLMClient {
     pub fn new() -> Self {
+        let mut config = LLMCacheConfig::default();
+        if let Some(capacity) = env::var("LLM_CACHE_SIZE").ok().and_then(|v| v.parse().ok()) {
+            config.capacity = capacity;
+        }
+        let client = Self::with_cache(Some(config));
+        match env::var("LLM_MAX_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            Some(retries) => client.with_max_retries(retries),
+            None => client,
+        }
+    }
+
+    /// Create a client with a custom response cache (`None` disables caching)
+    pub fn with_cache(config: Option<LLMCacheConfig>) -> Self {
         Self {
-            http: Client::new(),
+            http: Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            cache: config.map(LLMCache::new),
+            max_retries: DEFAULT_MAX_RETRIES,
+        }
+    }
+
+    /// Retry failed requests up to `max_retries` times (0 disables retries)
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Drop all cached responses
+    pub fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
         }
     }
 
     /// Send a request to an LLM provider
+    ///
+    /// Identical deterministic requests are served from the response cache.
     pub async fn chat(&self, request: LLMRequest) -> Result<LLMResponse, String> {
-        match request.provider {
-            LLMProvider::Gemini => self.chat_gemini(request).await,
-            LLMProvider::OpenAI => self.chat_openai(request).await,
-            LLMProvider::Anthropic => self.chat_anthropic(request).await,
-            LLMProvider::Ollama => self.chat_ollama(request).await,
-            LLMProvider::LlamaStack => self.chat_llama_stack(request).await,
-            LLMProvider::VertexAI => self.chat_vertex_ai(request).await,
+        self.chat_as(request, None).await.map_err(String::from)
+    }
+
+    /// Send a request on behalf of an agent, attributing it in the request log
+    ///
+    /// Errors stay typed so callers can tell transient failures from ones
+    /// that would fail again.
+    pub async fn chat_as(
+        &self,
+        request: LLMRequest,
+        agent_role: Option<&str>,
+    ) -> Result<LLMResponse, LLMError> {
+        let started = Instant::now();
+        let provider = request.provider.clone();
+        let model = request.model.clone();
+
+        let result = self.chat_cached(request).await;
+
+        request_log::record(RequestLogEntry::llm(
+            &provider,
+            &model,
+            agent_role,
+            result.as_ref().ok(),
+            started.elapsed(),
+        ))
+        .await;
+
+        result
+    }
+
+    async fn chat_cached(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        let cache_key = self.cache.as_ref().and_then(|c| c.key_for(&request));
+
+        if let (Some(cache), Some(key)) = (&self.cache, cache_key) {
+            if let Some(hit) = cache.get(key) {
+                tracing::debug!("LLM cache hit for model {}", hit.model);
+                return Ok(hit);
+            }
+        }
+
+        let response = self.dispatch(request).await?;
+
+        if let (Some(cache), Some(key)) = (&self.cache, cache_key) {
+            cache.insert(key, response.clone());
+        }
+
+        Ok(response)
+    }
+
+    async fn dispatch(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        let provider = format!("{:?}", request.provider);
+        let mut events = std::pin::pin!(self.stream_events(request));
+        let mut collector = StreamCollector::default();
+        while let Some(event) = events.next().await {
+            collector.push(&event?);
         }
+        collector
+            .into_response()
+            .map_err(|message| LLMError::InvalidResponse { provider, message })
+    }
+
+    /// Stream a response as text deltas, ending with a `Done` event that
+    /// carries the finish reason and token usage
+    ///
+    /// Rate limits and transient failures are retried before the first delta.
+    /// Streams bypass the response cache and the request log; `chat` is built
+    /// on top of this and adds both.
+    pub fn chat_stream(
+        &self,
+        request: LLMRequest,
+    ) -> impl Stream<Item = Result<LLMStreamEvent, String>> + '_ {
+        self.stream_events(request)
+            .map(|event| event.map_err(String::from))
+    }
+
+    fn stream_events(
+        &self,
+        request: LLMRequest,
+    ) -> impl Stream<Item = Result<LLMStreamEvent, LLMError>> + '_ {
+        enum State<'a> {
+            Start(&'a LLMClient, LLMRequest),
+            Reading {
+                body: BoxStream<'static, reqwest::Result<Bytes>>,
+                decoder: StreamDecoder,
+                pending: VecDeque<LLMStreamEvent>,
+                label: &'static str,
+            },
+            Draining(VecDeque<LLMStreamEvent>),
+            Finished,
+        }
+
+        futures_util::stream::unfold(State::Start(self, request), |state| async move {
+            let mut state = state;
+            loop {
+                state = match state {
+                    State::Start(client, request) => match client.open_stream(request).await {
+                        Ok((body, decoder, label)) => State::Reading {
+                            body,
+                            decoder,
+                            pending: VecDeque::new(),
+                            label,
+                        },
+                        Err(e) => return Some((Err(e), State::Finished)),
+                    },
+                    State::Reading {
+                        mut body,
+                        mut decoder,
+                        mut pending,
+                        label,
+                    } => {
+                        if let Some(event) = pending.pop_front() {
+                            return Some((
+                                Ok(event),
+                                State::Reading {
+                                    body,
+                                    decoder,
+                                    pending,
+                                    label,
+                                },
+                            ));
+                        }
+                        let invalid = |message| LLMError::InvalidResponse {
+                            provider: label.to_string(),
+                            message,
+                        };
+                        let decoded = match body.next().await {
+                            Some(Ok(chunk)) => decoder
+                                .feed(&chunk)
+                                .map(|events| {
+                                    pending.extend(events);
+                                    State::Reading {
+                                        body,
+                                        decoder,
+                                        pending,
+                                        label,
+                                    }
+                                })
+                                .map_err(invalid),
+                            Some(Err(e)) => Err(LLMError::NetworkError(e)),
+                            None => decoder
+                                .finish()
+                                .map(|events| {
+                                    pending.extend(events);
+                                    State::Draining(pending)
+                                })
+                                .map_err(invalid),
+                        };
+                        match decoded {
+                            Ok(next) => next,
+                            Err(e) => return Some((Err(e), State::Finished)),
+                        }
+                    }
+                    State::Draining(mut pending) => {
+                        return pending
+                            .pop_front()
+                            .map(|event| (Ok(event), State::Draining(pending)))
+                    }
+                    State::Finished => return None,
+                };
+            }
+        })
+    }
+
+    /// Send a streaming request; returns the body, a decoder for its format
+    /// and the provider's name
+    async fn open_stream(
+        &self,
+        request: LLMRequest,
+    ) -> Result<
+        (
+            BoxStream<'static, reqwest::Result<Bytes>>,
+            StreamDecoder,
+            &'static str,
+        ),
+        LLMError,
+    > {
+        let prepared = match request.provider {
+            LLMProvider::Gemini => self.prepare_gemini(&request)?,
+            LLMProvider::OpenAI => self.prepare_openai(&request)?,
+            LLMProvider::Anthropic => self.prepare_anthropic(&request)?,
+            LLMProvider::Ollama => self.prepare_ollama(&request).await?,
+            LLMProvider::LlamaStack => self.prepare_llama_stack(&request).await,
+            LLMProvider::VertexAI => self.prepare_vertex_ai(&request)?,
+        };
+
+        // Only opening the stream is retried; a stream that breaks midway has
+        // already delivered deltas
+        let response = with_retries(self.max_retries, || {
+            send_checked(prepared.builder.try_clone(), prepared.label)
+        })
+        .await?;
+
+        Ok((
+            response.bytes_stream().boxed(),
+            StreamDecoder::new(prepared.format, prepared.model),
+            prepared.label,
+        ))
     }
 
     // ─────────────────────────────────────────────────────────────────────────
     // LLAMA STACK (Local)
     // ─────────────────────────────────────────────────────────────────────────
 
-    async fn chat_llama_stack(&self, request: LLMRequest) -> Result<LLMResponse, String> {
-        let base_url =
-            env::var("LLAMA_STACK_PORT").unwrap_or_else(|_| "http://localhost:5000".to_string());
+    async fn prepare_llama_stack(&self, request: &LLMRequest) -> PreparedStream {
+        let base_url = local_models::llama_stack_base_url();
 
+        // Use what the stack actually serves rather than guessing a name
         let model = if request.model.is_empty() {
-            "llama3.2-3b"
+            local_models::default_model(LocalRuntime::LlamaStack)
+                .await
+                .unwrap_or_else(|| "llama3.2-3b".to_string())
         } else {
-            &request.model
+            request.model.clone()
         };
 
         let mut messages: Vec<serde_json::Value> = Vec::new();
@@ -102,71 +381,35 @@ impl LLMClient {
             }));
         }
 
-        for m in &request.messages {
-            messages.push(serde_json::json!({
-                "role": m.role,
-                "content": m.content
-            }));
-        }
+        messages.extend(request.messages.iter().map(openai_message));
 
         let body = serde_json::json!({
             "model": model,
             "messages": messages,
             "temperature": request.temperature.unwrap_or(0.7),
             "max_tokens": request.max_tokens.unwrap_or(4096),
-            "stream": false
+            "stream": true
         });
 
         // Llama Stack usually exposes OpenAI-compatible /v1/chat/completions
         let url = format!("{}/v1/chat/completions", base_url);
 
-        let response = self
-            .http
-            .post(&url)
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Llama Stack request failed: {}", e))?;
-
-        let status = response.status();
-        let text = response.text().await.map_err(|e| e.to_string())?;
-
-        if !status.is_success() {
-            return Err(format!("Llama Stack error {}: {}", status, text));
+        PreparedStream {
+            builder: self.http.post(&url).json(&body),
+            format: StreamFormat::OpenAI,
+            model,
+            label: "Llama Stack",
         }
-
-        let json: serde_json::Value =
-            serde_json::from_str(&text).map_err(|e| format!("Parse error: {}", e))?;
-
-        let content = json["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
-
-        let usage = json.get("usage").map(|u| TokenUsage {
-            prompt_tokens: u["prompt_tokens"].as_u64().unwrap_or(0) as u32,
-            completion_tokens: u["completion_tokens"].as_u64().unwrap_or(0) as u32,
-            total_tokens: u["total_tokens"].as_u64().unwrap_or(0) as u32,
-        });
-
-        Ok(LLMResponse {
-            content,
-            model: model.to_string(),
-            usage,
-            finish_reason: json["choices"][0]["finish_reason"]
-                .as_str()
-                .map(String::from),
-        })
     }
 
     // ─────────────────────────────────────────────────────────────────────────
     // GEMINI
     // ─────────────────────────────────────────────────────────────────────────
 
-    async fn chat_gemini(&self, request: LLMRequest) -> Result<LLMResponse, String> {
+    fn prepare_gemini(&self, request: &LLMRequest) -> Result<PreparedStream, LLMError> {
         let api_key = env::var("GOOGLE_API_KEY")
             .or_else(|_| env::var("GEMINI_API_KEY"))
-            .map_err(|_| "GOOGLE_API_KEY not set")?;
+            .map_err(|_| missing_key("Gemini", "GOOGLE_API_KEY"))?;
 
         let model = if request.model.is_empty() {
             "gemini-2.0-flash"
@@ -176,75 +419,15 @@ impl LLMClient {
 
         // Use v1beta for latest features, but consider moving to v1 for production stability
         let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?alt=sse&key={}",
             model, api_key
         );
 
-        // Build Gemini request format
-        let contents: Vec<serde_json::Value> = request
-            .messages
-            .iter()
-            .map(|m| {
-                serde_json::json!({
-                    "role": if m.role == "assistant" { "model" } else { "user" },
-                    "parts": [{"text": m.content}]
-                })
-            })
-            .collect();
-
-        let mut body = serde_json::json!({
-            "contents": contents
-        });
-
-        // Add system instruction if provided
-        if let Some(system) = &request.system_prompt {
-            body["systemInstruction"] = serde_json::json!({
-                "parts": [{"text": system}]
-            });
-        }
-
-        // Add generation config
-        body["generationConfig"] = serde_json::json!({
-            "temperature": request.temperature.unwrap_or(0.7),
-            "maxOutputTokens": request.max_tokens.unwrap_or(8192)
-        });
-
-        let response = self
-            .http
-            .post(&url)
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Gemini request failed: {}", e))?;
-
-        let status = response.status();
-        let text = response.text().await.map_err(|e| e.to_string())?;
-
-        if !status.is_success() {
-            return Err(format!("Gemini error {}: {}", status, text));
-        }
-
-        let json: serde_json::Value =
-            serde_json::from_str(&text).map_err(|e| format!("Parse error: {}", e))?;
-
-        let content = json["candidates"][0]["content"]["parts"][0]["text"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
-
-        let usage = json.get("usageMetadata").map(|u| TokenUsage {
-            prompt_tokens: u["promptTokenCount"].as_u64().unwrap_or(0) as u32,
-            completion_tokens: u["candidatesTokenCount"].as_u64().unwrap_or(0) as u32,
-            total_tokens: u["totalTokenCount"].as_u64().unwrap_or(0) as u32,
-        });
-
-        Ok(LLMResponse {
-            content,
+        Ok(PreparedStream {
+            builder: self.http.post(&url).json(&gemini_body(request)),
+            format: StreamFormat::Gemini,
             model: model.to_string(),
-            usage,
-            finish_reason: json["candidates"][0]["finishReason"]
-                .as_str()
-                .map(String::from),
+            label: "Gemini",
         })
     }
 
@@ -252,8 +435,9 @@ impl LLMClient {
     // OPENAI
     // ─────────────────────────────────────────────────────────────────────────
 
-    async fn chat_openai(&self, request: LLMRequest) -> Result<LLMResponse, String> {
-        let api_key = env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY not set")?;
+    fn prepare_openai(&self, request: &LLMRequest) -> Result<PreparedStream, LLMError> {
+        let api_key =
+            env::var("OPENAI_API_KEY").map_err(|_| missing_key("OpenAI", "OPENAI_API_KEY"))?;
 
         let model = if request.model.is_empty() {
             "gpt-4o"
@@ -272,57 +456,27 @@ impl LLMClient {
         }
 
         // Add conversation messages
-        for m in &request.messages {
-            messages.push(serde_json::json!({
-                "role": m.role,
-                "content": m.content
-            }));
-        }
+        messages.extend(request.messages.iter().map(openai_message));
 
         let body = serde_json::json!({
             "model": model,
             "messages": messages,
             "temperature": request.temperature.unwrap_or(0.7),
-            "max_tokens": request.max_tokens.unwrap_or(4096)
-        });
-
-        let response = self
-            .http
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("OpenAI request failed: {}", e))?;
-
-        let status = response.status();
-        let text = response.text().await.map_err(|e| e.to_string())?;
-
-        if !status.is_success() {
-            return Err(format!("OpenAI error {}: {}", status, text));
-        }
-
-        let json: serde_json::Value =
-            serde_json::from_str(&text).map_err(|e| format!("Parse error: {}", e))?;
-
-        let content = json["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
-
-        let usage = json.get("usage").map(|u| TokenUsage {
-            prompt_tokens: u["prompt_tokens"].as_u64().unwrap_or(0) as u32,
-            completion_tokens: u["completion_tokens"].as_u64().unwrap_or(0) as u32,
-            total_tokens: u["total_tokens"].as_u64().unwrap_or(0) as u32,
+            "max_tokens": request.max_tokens.unwrap_or(4096),
+            "stream": true,
+            // Usage arrives in a final chunk only when asked for
+            "stream_options": { "include_usage": true }
         });
 
-        Ok(LLMResponse {
-            content,
+        Ok(PreparedStream {
+            builder: self
+                .http
+                .post("https://api.openai.com/v1/chat/completions")
+                .header("Authorization", format!("Bearer {}", api_key))
+                .json(&body),
+            format: StreamFormat::OpenAI,
             model: model.to_string(),
-            usage,
-            finish_reason: json["choices"][0]["finish_reason"]
-                .as_str()
-                .map(String::from),
+            label: "OpenAI",
         })
     }
 
@@ -330,8 +484,9 @@ impl LLMClient {
     // ANTHROPIC
     // ─────────────────────────────────────────────────────────────────────────
 
-    async fn chat_anthropic(&self, request: LLMRequest) -> Result<LLMResponse, String> {
-        let api_key = env::var("ANTHROPIC_API_KEY").map_err(|_| "ANTHROPIC_API_KEY not set")?;
+    fn prepare_anthropic(&self, request: &LLMRequest) -> Result<PreparedStream, LLMError> {
+        let api_key = env::var("ANTHROPIC_API_KEY")
+            .map_err(|_| missing_key("Anthropic", "ANTHROPIC_API_KEY"))?;
 
         let model = if request.model.is_empty() {
             "claude-sonnet-4-20250514"
@@ -339,64 +494,30 @@ impl LLMClient {
             &request.model
         };
 
-        let messages: Vec<serde_json::Value> = request
-            .messages
-            .iter()
-            .map(|m| {
-                serde_json::json!({
-                    "role": m.role,
-                    "content": m.content
-                })
-            })
-            .collect();
+        let messages: Vec<serde_json::Value> =
+            request.messages.iter().map(anthropic_message).collect();
 
         let mut body = serde_json::json!({
             "model": model,
             "messages": messages,
-            "max_tokens": request.max_tokens.unwrap_or(4096)
+            "max_tokens": request.max_tokens.unwrap_or(4096),
+            "stream": true
         });
 
         if let Some(system) = &request.system_prompt {
             body["system"] = serde_json::json!(system);
         }
 
-        let response = self
-            .http
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &api_key)
-            .header("anthropic-version", "2023-06-01")
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Anthropic request failed: {}", e))?;
-
-        let status = response.status();
-        let text = response.text().await.map_err(|e| e.to_string())?;
-
-        if !status.is_success() {
-            return Err(format!("Anthropic error {}: {}", status, text));
-        }
-
-        let json: serde_json::Value =
-            serde_json::from_str(&text).map_err(|e| format!("Parse error: {}", e))?;
-
-        let content = json["content"][0]["text"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
-
-        let usage = json.get("usage").map(|u| TokenUsage {
-            prompt_tokens: u["input_tokens"].as_u64().unwrap_or(0) as u32,
-            completion_tokens: u["output_tokens"].as_u64().unwrap_or(0) as u32,
-            total_tokens: (u["input_tokens"].as_u64().unwrap_or(0)
-                + u["output_tokens"].as_u64().unwrap_or(0)) as u32,
-        });
-
-        Ok(LLMResponse {
-            content,
+        Ok(PreparedStream {
+            builder: self
+                .http
+                .post("https://api.anthropic.com/v1/messages")
+                .header("x-api-key", &api_key)
+                .header("anthropic-version", "2023-06-01")
+                .json(&body),
+            format: StreamFormat::Anthropic,
             model: model.to_string(),
-            usage,
-            finish_reason: json["stop_reason"].as_str().map(String::from),
+            label: "Anthropic",
         })
     }
 
@@ -404,64 +525,41 @@ impl LLMClient {
     // OLLAMA (Local)
     // ─────────────────────────────────────────────────────────────────────────
 
-    async fn chat_ollama(&self, request: LLMRequest) -> Result<LLMResponse, String> {
-        let base_url =
-            env::var("OLLAMA_HOST").unwrap_or_else(|_| "http://localhost:11434".to_string());
+    async fn prepare_ollama(&self, request: &LLMRequest) -> Result<PreparedStream, LLMError> {
+        let base_url = local_models::ollama_base_url();
 
         let model = if request.model.is_empty() {
-            "llama3.1:8b"
+            local_models::default_model(LocalRuntime::Ollama)
+                .await
+                .unwrap_or_else(|| "llama3.1:8b".to_string())
         } else {
-            &request.model
+            request.model.clone()
         };
 
-        let messages: Vec<serde_json::Value> = request
+        let messages = request
             .messages
             .iter()
-            .map(|m| {
-                serde_json::json!({
-                    "role": m.role,
-                    "content": m.content
-                })
-            })
-            .collect();
+            .map(ollama_message)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|message| LLMError::InvalidRequest {
+                provider: "Ollama".to_string(),
+                message,
+            })?;
 
         let body = serde_json::json!({
             "model": model,
             "messages": messages,
-            "stream": false,
+            "stream": true,
             "options": {
                 "temperature": request.temperature.unwrap_or(0.7)
             }
         });
 
-        let response = self
-            .http
-            .post(format!("{}/api/chat", base_url))
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Ollama request failed: {}", e))?;
-
-        let status = response.status();
-        let text = response.text().await.map_err(|e| e.to_string())?;
-
-        if !status.is_success() {
-            return Err(format!("Ollama error {}: {}", status, text));
-        }
-
-        let json: serde_json::Value =
-            serde_json::from_str(&text).map_err(|e| format!("Parse error: {}", e))?;
-
-        let content = json["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
-
-        Ok(LLMResponse {
-            content,
-            model: model.to_string(),
-            usage: None,
-            finish_reason: Some("stop".to_string()),
+        Ok(PreparedStream {
+            builder: self.http.post(format!("{}/api/chat", base_url)).json(&body),
+            format: StreamFormat::Ollama,
+            model,
+            label: "Ollama",
         })
     }
 
@@ -469,9 +567,11 @@ impl LLMClient {
     // VERTEX AI (GCP)
     // ─────────────────────────────────────────────────────────────────────────
 
-    async fn chat_vertex_ai(&self, request: LLMRequest) -> Result<LLMResponse, String> {
-        let access_token = env::var("GCP_ACCESS_TOKEN").map_err(|_| "GCP_ACCESS_TOKEN not set")?;
-        let project_id = env::var("GCP_PROJECT_ID").map_err(|_| "GCP_PROJECT_ID not set")?;
+    fn prepare_vertex_ai(&self, request: &LLMRequest) -> Result<PreparedStream, LLMError> {
+        let access_token = env::var("GCP_ACCESS_TOKEN")
+            .map_err(|_| missing_key("Vertex AI", "GCP_ACCESS_TOKEN"))?;
+        let project_id =
+            env::var("GCP_PROJECT_ID").map_err(|_| missing_key("Vertex AI", "GCP_PROJECT_ID"))?;
         let region = env::var("GCP_REGION").unwrap_or_else(|_| "us-central1".to_string());
 
         let model = if request.model.is_empty() {
@@ -481,75 +581,297 @@ impl LLMClient {
         };
 
         let url = format!(
-            "https://{}-aiplatform.googleapis.com/v1/projects/{}/locations/{}/publishers/google/models/{}:generateContent",
+            "https://{}-aiplatform.googleapis.com/v1/projects/{}/locations/{}/publishers/google/models/{}:streamGenerateContent?alt=sse",
             region, project_id, region, model
         );
 
         // Vertex AI mimics Gemini format but auth is different
-        let contents: Vec<serde_json::Value> = request
-            .messages
-            .iter()
-            .map(|m| {
-                serde_json::json!({
-                    "role": if m.role == "assistant" { "model" } else { "user" },
-                    "parts": [{"text": m.content}]
-                })
-            })
-            .collect();
+        Ok(PreparedStream {
+            builder: self
+                .http
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", access_token))
+                .json(&gemini_body(request)),
+            format: StreamFormat::Gemini,
+            model: model.to_string(),
+            label: "Vertex AI",
+        })
+    }
+}
 
-        let mut body = serde_json::json!({
-            "contents": contents
-        });
+// ═══════════════════════════════════════════════════════════════════════════════
+// RETRIES
+// ═══════════════════════════════════════════════════════════════════════════════
 
-        if let Some(system) = &request.system_prompt {
-            body["systemInstruction"] = serde_json::json!({
-                "parts": [{"text": system}]
-            });
+fn missing_key(provider: &str, env_var: &str) -> LLMError {
+    LLMError::MissingApiKey {
+        provider: provider.to_string(),
+        env_var: env_var.to_string(),
+    }
+}
+
+/// Send a request, mapping failures and error statuses to `LLMError`
+async fn send_checked(
+    builder: Option<reqwest::RequestBuilder>,
+    provider: &str,
+) -> Result<reqwest::Response, LLMError> {
+    // JSON bodies can always be cloned; streaming bodies could not be resent
+    let builder = builder.ok_or_else(|| LLMError::InvalidResponse {
+        provider: provider.to_string(),
+        message: "request cannot be retried".to_string(),
+    })?;
+
+    let response = builder.send().await.map_err(|e| {
+        if e.is_timeout() {
+            LLMError::Timeout {
+                timeout_secs: CONNECT_TIMEOUT.as_secs(),
+            }
+        } else {
+            LLMError::NetworkError(e)
         }
+    })?;
 
-        body["generationConfig"] = serde_json::json!({
-            "temperature": request.temperature.unwrap_or(0.7),
-            "maxOutputTokens": request.max_tokens.unwrap_or(8192)
-        });
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
 
-        let response = self
-            .http
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", access_token))
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Vertex AI request failed: {}", e))?;
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_retry_after(v, Utc::now()));
+    let body = response.text().await.unwrap_or_default();
+    Err(status_error(provider, status.as_u16(), retry_after, body))
+}
 
-        let status = response.status();
-        let text = response.text().await.map_err(|e| e.to_string())?;
+/// `LLMError` for an unsuccessful HTTP status
+pub fn status_error(
+    provider: &str,
+    status: u16,
+    retry_after_secs: Option<u64>,
+    body: String,
+) -> LLMError {
+    let provider = provider.to_string();
+    match status {
+        429 => LLMError::RateLimited {
+            provider,
+            retry_after_secs: retry_after_secs.unwrap_or(DEFAULT_RETRY_AFTER_SECS),
+        },
+        401 | 403 => LLMError::AuthenticationFailed {
+            provider,
+            message: body,
+        },
+        408 => LLMError::Timeout {
+            timeout_secs: CONNECT_TIMEOUT.as_secs(),
+        },
+        _ => LLMError::ProviderError {
+            provider,
+            status_code: status,
+            message: body,
+        },
+    }
+}
 
-        if !status.is_success() {
-            return Err(format!("Vertex AI error {}: {}", status, text));
-        }
+/// Seconds to wait from a `Retry-After` value: delay-seconds or an HTTP date
+///
+/// Dates in the past mean "now".
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(secs);
+    }
+
+    // IMF-fixdate ("Wed, 21 Oct 2015 07:28:00 GMT"), then the obsolete
+    // RFC 850 and asctime forms HTTP/1.1 still allows
+    let date = DateTime::parse_from_rfc2822(value)
+        .map(|d| d.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(value, "%A, %d-%b-%y %H:%M:%S GMT").map(|d| d.and_utc())
+        })
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(value, "%a %b %e %H:%M:%S %Y").map(|d| d.and_utc())
+        })
+        .ok()?;
+
+    Some((date - now).num_seconds().max(0) as u64)
+}
+
+/// How long to wait before retry number `retries + 1`, if the error is worth retrying
+fn retry_wait(error: &LLMError, retries: u32) -> Option<Duration> {
+    if !error.is_retryable() {
+        return None;
+    }
+    let secs = match error {
+        // The provider said when; don't second-guess it
+        LLMError::RateLimited {
+            retry_after_secs, ..
+        } => *retry_after_secs,
+        _ => error.retry_delay()? << retries.min(5),
+    };
+    (secs <= MAX_RETRY_WAIT_SECS).then(|| Duration::from_secs(secs))
+}
+
+/// Run `attempt` until it succeeds, fails for good, or `max_retries` is used up
+async fn with_retries<T, F, Fut>(max_retries: u32, mut attempt: F) -> Result<T, LLMError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, LLMError>>,
+{
+    let mut retries = 0;
+    loop {
+        let error = match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        let wait = match retry_wait(&error, retries) {
+            Some(wait) if retries < max_retries => wait,
+            _ => return Err(error),
+        };
 
-        let json: serde_json::Value =
-            serde_json::from_str(&text).map_err(|e| format!("Parse error: {}", e))?;
+        retries += 1;
+        tracing::warn!(
+            "LLM request failed ({}); retry {}/{} in {:?}",
+            error,
+            retries,
+            max_retries,
+            wait
+        );
+        tokio::time::sleep(wait).await;
+    }
+}
 
-        let content = json["candidates"][0]["content"]["parts"][0]["text"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
+/// A streaming provider request, ready to send
+struct PreparedStream {
+    builder: reqwest::RequestBuilder,
+    format: StreamFormat,
+    /// Model actually requested (defaults resolved)
+    model: String,
+    /// Provider name for error messages
+    label: &'static str,
+}
 
-        let usage = json.get("usageMetadata").map(|u| TokenUsage {
-            prompt_tokens: u["promptTokenCount"].as_u64().unwrap_or(0) as u32,
-            completion_tokens: u["candidatesTokenCount"].as_u64().unwrap_or(0) as u32,
-            total_tokens: u["totalTokenCount"].as_u64().unwrap_or(0) as u32,
+/// Request body shared by Gemini and Vertex AI
+fn gemini_body(request: &LLMRequest) -> serde_json::Value {
+    let contents: Vec<serde_json::Value> = request
+        .messages
+        .iter()
+        .map(|m| {
+            serde_json::json!({
+                "role": if m.role == "assistant" { "model" } else { "user" },
+                "parts": gemini_parts(m)
+            })
+        })
+        .collect();
+
+    let mut body = serde_json::json!({
+        "contents": contents
+    });
+
+    // Add system instruction if provided
+    if let Some(system) = &request.system_prompt {
+        body["systemInstruction"] = serde_json::json!({
+            "parts": [{"text": system}]
         });
+    }
 
-        Ok(LLMResponse {
-            content,
-            model: model.to_string(),
-            usage,
-            finish_reason: json["candidates"][0]["finishReason"]
-                .as_str()
-                .map(String::from),
+    // Add generation config
+    body["generationConfig"] = serde_json::json!({
+        "temperature": request.temperature.unwrap_or(0.7),
+        "maxOutputTokens": request.max_tokens.unwrap_or(8192)
+    });
+
+    body
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// MESSAGE SERIALIZATION
+// ═══════════════════════════════════════════════════════════════════════════════
+//
+// Messages without images keep the plain `{"role", "content": "..."}` shape
+// every provider accepted before vision support.
+
+/// Gemini/Vertex parts: the text, then `inlineData` or `fileData` per image
+fn gemini_parts(message: &LLMMessage) -> Vec<serde_json::Value> {
+    let mut parts = vec![serde_json::json!({"text": message.content})];
+    parts.extend(message.images.iter().map(|image| match image {
+        ImageInput::Base64 { mime_type, data } => serde_json::json!({
+            "inlineData": {"mimeType": mime_type, "data": data}
+        }),
+        ImageInput::Url { url } => serde_json::json!({
+            "fileData": {"mimeType": image_mime_from_url(url), "fileUri": url}
+        }),
+    }));
+    parts
+}
+
+/// OpenAI (and Llama Stack) message; images turn `content` into typed blocks
+fn openai_message(message: &LLMMessage) -> serde_json::Value {
+    if message.images.is_empty() {
+        return serde_json::json!({"role": message.role, "content": message.content});
+    }
+
+    let mut content = vec![serde_json::json!({"type": "text", "text": message.content})];
+    content.extend(message.images.iter().map(
+        |image| serde_json::json!({"type": "image_url", "image_url": {"url": image.as_url()}}),
+    ));
+    serde_json::json!({"role": message.role, "content": content})
+}
+
+/// Anthropic message; images go before the text, as Anthropic recommends
+fn anthropic_message(message: &LLMMessage) -> serde_json::Value {
+    if message.images.is_empty() {
+        return serde_json::json!({"role": message.role, "content": message.content});
+    }
+
+    let mut content: Vec<serde_json::Value> = message
+        .images
+        .iter()
+        .map(|image| {
+            let source = match image {
+                ImageInput::Base64 { mime_type, data } => serde_json::json!({
+                    "type": "base64", "media_type": mime_type, "data": data
+                }),
+                ImageInput::Url { url } => serde_json::json!({"type": "url", "url": url}),
+            };
+            serde_json::json!({"type": "image", "source": source})
         })
+        .collect();
+    content.push(serde_json::json!({"type": "text", "text": message.content}));
+    serde_json::json!({"role": message.role, "content": content})
+}
+
+/// Ollama message; `/api/chat` only takes base64 `images`
+fn ollama_message(message: &LLMMessage) -> Result<serde_json::Value, String> {
+    let mut value = serde_json::json!({"role": message.role, "content": message.content});
+    if message.images.is_empty() {
+        return Ok(value);
+    }
+
+    let images = message
+        .images
+        .iter()
+        .map(|image| match image {
+            ImageInput::Base64 { data, .. } => Ok(data.clone()),
+            ImageInput::Url { url } => Err(format!(
+                "Ollama only accepts base64 images; download {} first",
+                url
+            )),
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    value["images"] = serde_json::json!(images);
+    Ok(value)
+}
+
+/// Gemini needs a MIME type for `fileData`; guess it from the extension
+fn image_mime_from_url(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+    match path.rsplit('.').next() {
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        Some("heic") => "image/heic",
+        _ => "image/jpeg",
     }
 }
 
@@ -571,16 +893,216 @@ pub fn get_llm_client() -> &'static LLMClient {
     &LLM_CLIENT
 }
 
+static FALLBACK_LLM_CLIENT: Lazy<LLMClient> = Lazy::new(|| LLMClient::new().with_max_retries(0));
+
+/// Client for calls made through a fallback chain
+///
+/// It never retries: waiting out a `Retry-After` on the primary model would
+/// use up the agent's deadline before the next model is tried.
+pub fn get_fallback_llm_client() -> &'static LLMClient {
+    &FALLBACK_LLM_CLIENT
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_retry_after_seconds_and_dates() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:27:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(parse_retry_after("120", now), Some(120));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(60)
+        );
+        assert_eq!(
+            parse_retry_after("Wednesday, 21-Oct-15 07:27:30 GMT", now),
+            Some(30)
+        );
+        assert_eq!(parse_retry_after("Wed Oct 21 07:27:10 2015", now), Some(10));
+        // Already passed: retry right away
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(0)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_status_errors() {
+        let error = status_error("Gemini", 429, Some(30), String::new());
+        assert!(matches!(
+            error,
+            LLMError::RateLimited {
+                retry_after_secs: 30,
+                ..
+            }
+        ));
+        assert_eq!(retry_wait(&error, 2), Some(Duration::from_secs(30)));
+
+        // Waits beyond the cap fail instead of stalling the agent
+        let error = status_error("Gemini", 429, Some(3600), String::new());
+        assert_eq!(retry_wait(&error, 0), None);
+
+        // Server errors back off exponentially
+        let error = status_error("OpenAI", 503, None, "overloaded".into());
+        assert_eq!(retry_wait(&error, 0), Some(Duration::from_secs(2)));
+        assert_eq!(retry_wait(&error, 2), Some(Duration::from_secs(8)));
+
+        let error = status_error("OpenAI", 401, None, "bad key".into());
+        assert!(matches!(error, LLMError::AuthenticationFailed { .. }));
+        assert_eq!(retry_wait(&error, 0), None);
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success_or_budget_spent() {
+        let rate_limited = || status_error("Gemini", 429, Some(0), String::new());
+
+        let calls = AtomicU32::new(0);
+        let result = with_retries(3, || async {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(rate_limited()),
+                _ => Ok("response"),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), "response");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = with_retries(2, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(rate_limited())
+        })
+        .await;
+        assert!(matches!(result, Err(LLMError::RateLimited { .. })));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Not retryable: one attempt only
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = with_retries(3, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(status_error("Gemini", 400, None, String::new()))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_primary_reaches_fallback_before_timeout() {
+        use crate::ai::agent_executor::{run_bounded, LLM_TIMEOUT};
+        use crate::ai::model_selection::{run_with_fallback, FallbackStep, RETRIES_PER_MODEL};
+
+        let chain = [
+            FallbackStep::new(LLMProvider::Gemini, "gemini-3-pro"),
+            FallbackStep::new(LLMProvider::OpenAI, "gpt-5"),
+        ];
+        let max_retries = get_fallback_llm_client().max_retries;
+        let started = Instant::now();
+
+        // The primary answers every request with a 429 and `Retry-After: 30`
+        let call = run_with_fallback(&chain, RETRIES_PER_MODEL, |step| {
+            with_retries(max_retries, move || {
+                let step = step.clone();
+                async move {
+                    match step.provider {
+                        LLMProvider::Gemini => {
+                            Err(status_error("Gemini", 429, Some(30), String::new()))
+                        }
+                        _ => Ok(step.model),
+                    }
+                }
+            })
+        });
+        let (model, substitution) = run_bounded(call, LLM_TIMEOUT, None).await.unwrap();
+
+        assert_eq!(model, "gpt-5");
+        assert_eq!(substitution.unwrap().requested.model, "gemini-3-pro");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
 
     #[test]
     fn test_message_creation() {
         let msg = LLMMessage {
             role: "user".into(),
             content: "Hello".into(),
+            images: Vec::new(),
         };
         assert_eq!(msg.role, "user");
     }
+
+    #[test]
+    fn test_text_messages_serialize_as_before() {
+        let msg = LLMMessage {
+            role: "user".into(),
+            content: "Hello".into(),
+            images: Vec::new(),
+        };
+        let plain = serde_json::json!({"role": "user", "content": "Hello"});
+
+        assert_eq!(serde_json::to_value(&msg).unwrap(), plain);
+        assert_eq!(openai_message(&msg), plain);
+        assert_eq!(anthropic_message(&msg), plain);
+        assert_eq!(ollama_message(&msg).unwrap(), plain);
+        assert_eq!(gemini_parts(&msg), [serde_json::json!({"text": "Hello"})]);
+    }
+
+    #[test]
+    fn test_image_messages_per_provider() {
+        let msg = LLMMessage {
+            role: "user".into(),
+            content: "Describe the lighting".into(),
+            images: vec![ImageInput::Base64 {
+                mime_type: "image/png".into(),
+                data: "iVBORw0K".into(),
+            }],
+        };
+
+        assert_eq!(
+            openai_message(&msg)["content"][1],
+            serde_json::json!({
+                "type": "image_url",
+                "image_url": {"url": "data:image/png;base64,iVBORw0K"}
+            })
+        );
+        assert_eq!(
+            anthropic_message(&msg)["content"],
+            serde_json::json!([
+                {
+                    "type": "image",
+                    "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0K"}
+                },
+                {"type": "text", "text": "Describe the lighting"}
+            ])
+        );
+        assert_eq!(
+            gemini_parts(&msg)[1],
+            serde_json::json!({"inlineData": {"mimeType": "image/png", "data": "iVBORw0K"}})
+        );
+        assert_eq!(
+            ollama_message(&msg).unwrap()["images"],
+            serde_json::json!(["iVBORw0K"])
+        );
+
+        let by_url = LLMMessage {
+            images: vec![ImageInput::Url {
+                url: "https://example.com/still.webp?v=2".into(),
+            }],
+            ..msg
+        };
+        assert_eq!(
+            gemini_parts(&by_url)[1]["fileData"]["mimeType"],
+            "image/webp"
+        );
+        assert_eq!(
+            anthropic_message(&by_url)["content"][0]["source"],
+            serde_json::json!({"type": "url", "url": "https://example.com/still.webp?v=2"})
+        );
+        assert!(ollama_message(&by_url).is_err());
+    }
 }
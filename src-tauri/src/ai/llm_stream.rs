@@ -0,0 +1,379 @@
+//! LLM Streaming - Incremental decoding of provider response streams
+//!
+//! Providers stream either server-sent events (Gemini, OpenAI, Anthropic,
+//! Llama Stack, Vertex AI) or JSON lines (Ollama). `StreamDecoder` turns raw
+//! body chunks into text deltas and keeps the finish reason and token usage
+//! for the terminal `Done` event.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::llm_client::{LLMResponse, TokenUsage};
+
+/// Wire format of a provider's streaming response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// SSE with `GenerateContentResponse` chunks (Gemini, Vertex AI)
+    Gemini,
+    /// SSE with chat completion chunks, ending in `[DONE]` (OpenAI, Llama Stack)
+    OpenAI,
+    /// SSE with typed message events
+    Anthropic,
+    /// One JSON object per line
+    Ollama,
+}
+
+/// One event of a streamed chat response
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LLMStreamEvent {
+    /// Text generated since the previous event
+    Delta { text: String },
+    /// Last event of a successful stream
+    Done {
+        model: String,
+        finish_reason: Option<String>,
+        usage: Option<TokenUsage>,
+    },
+}
+
+/// `llm-stream` event payload emitted by the `chat_stream` command
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ChatStreamChunk {
+    pub stream_id: String,
+    pub event: LLMStreamEvent,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// DECODER
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Splits a response body into lines and decodes each provider chunk
+pub struct StreamDecoder {
+    format: StreamFormat,
+    model: String,
+    /// Bytes after the last newline; chunks may split lines and UTF-8 sequences
+    buffer: Vec<u8>,
+    finish_reason: Option<String>,
+    prompt_tokens: Option<u32>,
+    completion_tokens: Option<u32>,
+    total_tokens: Option<u32>,
+}
+
+fn tokens(value: &serde_json::Value) -> Option<u32> {
+    value.as_u64().map(|n| n as u32)
+}
+
+impl StreamDecoder {
+    pub fn new(format: StreamFormat, model: impl Into<String>) -> Self {
+        Self {
+            format,
+            model: model.into(),
+            buffer: Vec::new(),
+            finish_reason: None,
+            prompt_tokens: None,
+            completion_tokens: None,
+            total_tokens: None,
+        }
+    }
+
+    /// `Delta` events for the lines completed by `chunk`
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<LLMStreamEvent>, String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut deltas = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(text) = self.decode_line(line.trim())? {
+                deltas.push(LLMStreamEvent::Delta { text });
+            }
+        }
+        Ok(deltas)
+    }
+
+    /// Deltas from an unterminated last line, then the terminal `Done` event
+    pub fn finish(mut self) -> Result<Vec<LLMStreamEvent>, String> {
+        let mut events = if self.buffer.is_empty() {
+            Vec::new()
+        } else {
+            self.feed(b"\n")?
+        };
+
+        let usage = match (self.prompt_tokens, self.completion_tokens) {
+            (None, None) => None,
+            (prompt, completion) => {
+                let prompt = prompt.unwrap_or(0);
+                let completion = completion.unwrap_or(0);
+                Some(TokenUsage {
+                    prompt_tokens: prompt,
+                    completion_tokens: completion,
+                    total_tokens: self.total_tokens.unwrap_or(prompt + completion),
+                })
+            }
+        };
+
+        events.push(LLMStreamEvent::Done {
+            model: self.model,
+            finish_reason: self.finish_reason,
+            usage,
+        });
+        Ok(events)
+    }
+
+    fn decode_line(&mut self, line: &str) -> Result<Option<String>, String> {
+        let payload = match self.format {
+            StreamFormat::Ollama => line,
+            // `event:`, `id:` and comment lines carry nothing the data doesn't
+            _ => match line.strip_prefix("data:") {
+                Some(data) => data.trim_start(),
+                None => return Ok(None),
+            },
+        };
+        if payload.is_empty() || payload == "[DONE]" {
+            return Ok(None);
+        }
+
+        let json: serde_json::Value = serde_json::from_str(payload)
+            .map_err(|e| format!("Invalid stream chunk: {} ({})", e, payload))?;
+
+        let text = match self.format {
+            StreamFormat::Gemini => self.decode_gemini(&json),
+            StreamFormat::OpenAI => self.decode_openai(&json)?,
+            StreamFormat::Anthropic => self.decode_anthropic(&json)?,
+            StreamFormat::Ollama => self.decode_ollama(&json)?,
+        };
+        Ok(text.filter(|t| !t.is_empty()))
+    }
+
+    fn decode_gemini(&mut self, json: &serde_json::Value) -> Option<String> {
+        let candidate = &json["candidates"][0];
+        if let Some(reason) = candidate["finishReason"].as_str() {
+            self.finish_reason = Some(reason.to_string());
+        }
+        // Every chunk carries the running totals
+        if let Some(usage) = json.get("usageMetadata") {
+            self.prompt_tokens = tokens(&usage["promptTokenCount"]);
+            self.completion_tokens = tokens(&usage["candidatesTokenCount"]);
+            self.total_tokens = tokens(&usage["totalTokenCount"]);
+        }
+
+        let parts = candidate["content"]["parts"].as_array()?;
+        Some(parts.iter().filter_map(|p| p["text"].as_str()).collect())
+    }
+
+    fn decode_openai(&mut self, json: &serde_json::Value) -> Result<Option<String>, String> {
+        if let Some(error) = json.get("error") {
+            return Err(format!("Stream error: {}", error));
+        }
+
+        let choice = &json["choices"][0];
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            self.finish_reason = Some(reason.to_string());
+        }
+        // Sent in a final chunk without choices when `include_usage` is set
+        if let Some(usage) = json.get("usage").filter(|u| u.is_object()) {
+            self.prompt_tokens = tokens(&usage["prompt_tokens"]);
+            self.completion_tokens = tokens(&usage["completion_tokens"]);
+            self.total_tokens = tokens(&usage["total_tokens"]);
+        }
+
+        Ok(choice["delta"]["content"].as_str().map(String::from))
+    }
+
+    fn decode_anthropic(&mut self, json: &serde_json::Value) -> Result<Option<String>, String> {
+        match json["type"].as_str() {
+            Some("message_start") => {
+                let usage = &json["message"]["usage"];
+                self.prompt_tokens = tokens(&usage["input_tokens"]);
+                self.completion_tokens = tokens(&usage["output_tokens"]);
+                Ok(None)
+            }
+            Some("content_block_delta") => Ok(json["delta"]["text"].as_str().map(String::from)),
+            Some("message_delta") => {
+                if let Some(reason) = json["delta"]["stop_reason"].as_str() {
+                    self.finish_reason = Some(reason.to_string());
+                }
+                if let Some(output) = tokens(&json["usage"]["output_tokens"]) {
+                    self.completion_tokens = Some(output);
+                }
+                Ok(None)
+            }
+            Some("error") => Err(format!(
+                "Anthropic stream error: {}",
+                json["error"]["message"].as_str().unwrap_or("unknown error")
+            )),
+            _ => Ok(None),
+        }
+    }
+
+    fn decode_ollama(&mut self, json: &serde_json::Value) -> Result<Option<String>, String> {
+        if let Some(error) = json["error"].as_str() {
+            return Err(format!("Ollama error: {}", error));
+        }
+
+        if json["done"].as_bool() == Some(true) {
+            self.finish_reason = Some(json["done_reason"].as_str().unwrap_or("stop").to_string());
+            self.prompt_tokens = tokens(&json["prompt_eval_count"]);
+            self.completion_tokens = tokens(&json["eval_count"]);
+        }
+
+        Ok(json["message"]["content"].as_str().map(String::from))
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// COLLECTING
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Accumulates stream events into the equivalent non-streaming response
+#[derive(Debug, Default)]
+pub struct StreamCollector {
+    content: String,
+    done: Option<LLMStreamEvent>,
+}
+
+impl StreamCollector {
+    pub fn push(&mut self, event: &LLMStreamEvent) {
+        match event {
+            LLMStreamEvent::Delta { text } => self.content.push_str(text),
+            LLMStreamEvent::Done { .. } => self.done = Some(event.clone()),
+        }
+    }
+
+    /// The full response, or an error if the stream ended without `Done`
+    pub fn into_response(self) -> Result<LLMResponse, String> {
+        match self.done {
+            Some(LLMStreamEvent::Done {
+                model,
+                finish_reason,
+                usage,
+            }) => Ok(LLMResponse {
+                content: self.content,
+                model,
+                usage,
+                finish_reason,
+                cached: false,
+            }),
+            _ => Err("LLM stream ended before completing".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `body` split into `size`-byte chunks, as the network might deliver it
+    fn decode(
+        format: StreamFormat,
+        body: &str,
+        size: usize,
+    ) -> Result<(String, LLMStreamEvent), String> {
+        let mut decoder = StreamDecoder::new(format, "model");
+        let mut collector = StreamCollector::default();
+        let mut events = Vec::new();
+        for chunk in body.as_bytes().chunks(size) {
+            events.extend(decoder.feed(chunk)?);
+        }
+        events.extend(decoder.finish()?);
+
+        events.iter().for_each(|event| collector.push(event));
+        let done = events.pop().unwrap();
+        Ok((collector.into_response()?.content, done))
+    }
+
+    fn usage(done: &LLMStreamEvent) -> (Option<&str>, Option<(u32, u32, u32)>) {
+        match done {
+            LLMStreamEvent::Done {
+                finish_reason,
+                usage,
+                ..
+            } => (
+                finish_reason.as_deref(),
+                usage
+                    .as_ref()
+                    .map(|u| (u.prompt_tokens, u.completion_tokens, u.total_tokens)),
+            ),
+            _ => panic!("not a Done event"),
+        }
+    }
+
+    #[test]
+    fn test_openai_sse() {
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"INT. DINER \"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"— NIGHT\"},\"finish_reason\":null}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":5,\"total_tokens\":17}}\n\n",
+            "data: [DONE]\n\n",
+        );
+
+        // Small chunks split lines and the multi-byte dash
+        for size in [1, 7, body.len()] {
+            let (content, done) = decode(StreamFormat::OpenAI, body, size).unwrap();
+            assert_eq!(content, "INT. DINER — NIGHT");
+            assert_eq!(usage(&done), (Some("stop"), Some((12, 5, 17))));
+        }
+    }
+
+    #[test]
+    fn test_anthropic_sse() {
+        let body = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":20,\"output_tokens\":1}}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"FADE \"}}\n\n",
+            "event: ping\n",
+            "data: {\"type\":\"ping\"}\n\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"IN:\"}}\n\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":9}}\n\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        );
+
+        let (content, done) = decode(StreamFormat::Anthropic, body, 5).unwrap();
+        assert_eq!(content, "FADE IN:");
+        assert_eq!(usage(&done), (Some("end_turn"), Some((20, 9, 29))));
+
+        let error = "data: {\"type\":\"error\",\"error\":{\"message\":\"Overloaded\"}}\n";
+        assert!(decode(StreamFormat::Anthropic, error, 64)
+            .unwrap_err()
+            .contains("Overloaded"));
+    }
+
+    #[test]
+    fn test_gemini_sse() {
+        let body = concat!(
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Anna \"}]}}],\"usageMetadata\":{\"promptTokenCount\":8,\"candidatesTokenCount\":1,\"totalTokenCount\":9}}\r\n\r\n",
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"waits.\"}]},\"finishReason\":\"STOP\"}],\"usageMetadata\":{\"promptTokenCount\":8,\"candidatesTokenCount\":3,\"totalTokenCount\":11}}",
+        );
+
+        // The last chunk has no trailing newline
+        let (content, done) = decode(StreamFormat::Gemini, body, 16).unwrap();
+        assert_eq!(content, "Anna waits.");
+        assert_eq!(usage(&done), (Some("STOP"), Some((8, 3, 11))));
+    }
+
+    #[test]
+    fn test_ollama_json_lines() {
+        let body = concat!(
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"CUT \"},\"done\":false}\n",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"TO:\"},\"done\":false}\n",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"done_reason\":\"length\",\"prompt_eval_count\":30,\"eval_count\":2}\n",
+        );
+
+        let (content, done) = decode(StreamFormat::Ollama, body, 3).unwrap();
+        assert_eq!(content, "CUT TO:");
+        assert_eq!(usage(&done), (Some("length"), Some((30, 2, 32))));
+    }
+
+    #[test]
+    fn test_stream_without_done_is_an_error() {
+        let mut collector = StreamCollector::default();
+        collector.push(&LLMStreamEvent::Delta {
+            text: "partial".into(),
+        });
+        assert!(collector.into_response().is_err());
+    }
+}
@@ -0,0 +1,292 @@
+//! Local Model Discovery - Ask running runtimes what they actually serve
+//!
+//! Queries Ollama (`/api/tags`, `/api/show`) and Llama Stack (`/v1/models`)
+//! for their installed models instead of guessing model names. Results are
+//! cached briefly so the model picker can poll without hammering the runtimes.
+
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a discovery result stays valid
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Per-request timeout (runtimes that are not running should fail fast)
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Local runtime serving a model
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Type)]
+pub enum LocalRuntime {
+    Ollama,
+    LlamaStack,
+}
+
+/// A model reported by a running local runtime
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
+pub struct LocalModelInfo {
+    pub runtime: LocalRuntime,
+    /// Model id to pass back in `LLMRequest::model`
+    pub id: String,
+    /// Size on disk in bytes, when reported
+    #[specta(type = Option<f64>)]
+    pub size_bytes: Option<u64>,
+    /// Context window in tokens, when reported
+    pub context_window: Option<u32>,
+    pub family: Option<String>,
+    pub parameter_size: Option<String>,
+    pub quantization: Option<String>,
+}
+
+/// Models discovered across all local runtimes
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct LocalModelDiscovery {
+    pub models: Vec<LocalModelInfo>,
+    pub ollama_running: bool,
+    pub llama_stack_running: bool,
+}
+
+static CACHE: Lazy<Mutex<Option<(Instant, LocalModelDiscovery)>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn ollama_base_url() -> String {
+    env::var("OLLAMA_HOST").unwrap_or_else(|_| "http://localhost:11434".to_string())
+}
+
+pub fn llama_stack_base_url() -> String {
+    env::var("LLAMA_STACK_PORT").unwrap_or_else(|_| "http://localhost:5000".to_string())
+}
+
+fn http_client() -> Client {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// DISCOVERY
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Discover models from every running local runtime (cached for [`CACHE_TTL`])
+pub async fn discover_local_models(refresh: bool) -> LocalModelDiscovery {
+    if !refresh {
+        if let Some(cached) = cached_discovery(CACHE_TTL) {
+            return cached;
+        }
+    }
+
+    let (ollama, llama_stack) =
+        futures_util::future::join(list_ollama_models(), list_llama_stack_models()).await;
+
+    let mut discovery = LocalModelDiscovery {
+        ollama_running: ollama.is_ok(),
+        llama_stack_running: llama_stack.is_ok(),
+        ..Default::default()
+    };
+    for result in [ollama, llama_stack] {
+        match result {
+            Ok(models) => discovery.models.extend(models),
+            Err(e) => tracing::debug!("Local runtime unavailable: {}", e),
+        }
+    }
+
+    if let Ok(mut cache) = CACHE.lock() {
+        *cache = Some((Instant::now(), discovery.clone()));
+    }
+    discovery
+}
+
+fn cached_discovery(ttl: Duration) -> Option<LocalModelDiscovery> {
+    let cache = CACHE.lock().ok()?;
+    cache
+        .as_ref()
+        .filter(|(at, _)| at.elapsed() < ttl)
+        .map(|(_, discovery)| discovery.clone())
+}
+
+/// First model served by `runtime`, used when a request leaves the model empty
+pub async fn default_model(runtime: LocalRuntime) -> Option<String> {
+    discover_local_models(false)
+        .await
+        .models
+        .into_iter()
+        .find(|m| m.runtime == runtime)
+        .map(|m| m.id)
+}
+
+/// List models installed in Ollama, with context windows from `/api/show`
+pub async fn list_ollama_models() -> Result<Vec<LocalModelInfo>, String> {
+    let http = http_client();
+    let base_url = ollama_base_url();
+
+    let json: serde_json::Value = http
+        .get(format!("{}/api/tags", base_url))
+        .send()
+        .await
+        .map_err(|e| format!("Ollama not reachable: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid Ollama response: {}", e))?;
+
+    let mut models = parse_ollama_tags(&json);
+
+    for model in &mut models {
+        let show = http
+            .post(format!("{}/api/show", base_url))
+            .json(&serde_json::json!({ "model": model.id }))
+            .send()
+            .await;
+
+        if let Ok(response) = show {
+            if let Ok(details) = response.json::<serde_json::Value>().await {
+                model.context_window = parse_ollama_context_window(&details);
+            }
+        }
+    }
+
+    Ok(models)
+}
+
+/// List LLMs registered with Llama Stack
+pub async fn list_llama_stack_models() -> Result<Vec<LocalModelInfo>, String> {
+    let json: serde_json::Value = http_client()
+        .get(format!("{}/v1/models", llama_stack_base_url()))
+        .send()
+        .await
+        .map_err(|e| format!("Llama Stack not reachable: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid Llama Stack response: {}", e))?;
+
+    Ok(parse_llama_stack_models(&json))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// RESPONSE PARSING
+// ═══════════════════════════════════════════════════════════════════════════════
+
+fn parse_ollama_tags(json: &serde_json::Value) -> Vec<LocalModelInfo> {
+    let Some(models) = json["models"].as_array() else {
+        return Vec::new();
+    };
+
+    models
+        .iter()
+        .filter_map(|m| {
+            let id = m["name"].as_str().or_else(|| m["model"].as_str())?;
+            let details = &m["details"];
+            Some(LocalModelInfo {
+                runtime: LocalRuntime::Ollama,
+                id: id.to_string(),
+                size_bytes: m["size"].as_u64(),
+                context_window: None,
+                family: details["family"].as_str().map(String::from),
+                parameter_size: details["parameter_size"].as_str().map(String::from),
+                quantization: details["quantization_level"].as_str().map(String::from),
+            })
+        })
+        .collect()
+}
+
+/// Ollama reports the window as `<architecture>.context_length` in `model_info`
+fn parse_ollama_context_window(json: &serde_json::Value) -> Option<u32> {
+    json["model_info"]
+        .as_object()?
+        .iter()
+        .find(|(key, _)| key.ends_with(".context_length"))
+        .and_then(|(_, value)| value.as_u64())
+        .map(|v| v as u32)
+}
+
+/// Accepts both the native Llama Stack shape (`identifier`, `model_type`) and
+/// the OpenAI-compatible one (`id`)
+fn parse_llama_stack_models(json: &serde_json::Value) -> Vec<LocalModelInfo> {
+    let Some(models) = json["data"].as_array() else {
+        return Vec::new();
+    };
+
+    models
+        .iter()
+        .filter(|m| m["model_type"].as_str().is_none_or(|t| t == "llm"))
+        .filter_map(|m| {
+            let id = m["identifier"].as_str().or_else(|| m["id"].as_str())?;
+            let metadata = &m["metadata"];
+            Some(LocalModelInfo {
+                runtime: LocalRuntime::LlamaStack,
+                id: id.to_string(),
+                size_bytes: None,
+                context_window: metadata["context_length"]
+                    .as_u64()
+                    .or_else(|| m["context_length"].as_u64())
+                    .map(|v| v as u32),
+                family: None,
+                parameter_size: None,
+                quantization: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ollama_tags() {
+        let json = serde_json::json!({
+            "models": [{
+                "name": "llama3.1:8b",
+                "model": "llama3.1:8b",
+                "size": 4_920_753_328u64,
+                "details": {
+                    "family": "llama",
+                    "parameter_size": "8.0B",
+                    "quantization_level": "Q4_K_M"
+                }
+            }]
+        });
+
+        let models = parse_ollama_tags(&json);
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].id, "llama3.1:8b");
+        assert_eq!(models[0].size_bytes, Some(4_920_753_328));
+        assert_eq!(models[0].parameter_size.as_deref(), Some("8.0B"));
+        assert!(parse_ollama_tags(&serde_json::json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_parse_ollama_context_window() {
+        let json = serde_json::json!({
+            "model_info": {
+                "general.architecture": "llama",
+                "llama.context_length": 131072
+            }
+        });
+        assert_eq!(parse_ollama_context_window(&json), Some(131_072));
+        assert_eq!(parse_ollama_context_window(&serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn test_parse_llama_stack_models() {
+        let json = serde_json::json!({
+            "data": [
+                {
+                    "identifier": "meta-llama/Llama-3.2-3B-Instruct",
+                    "model_type": "llm",
+                    "metadata": { "context_length": 128000 }
+                },
+                { "identifier": "all-MiniLM-L6-v2", "model_type": "embedding" },
+                { "id": "llama3.2:3b" }
+            ]
+        });
+
+        let models = parse_llama_stack_models(&json);
+        let ids: Vec<&str> = models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["meta-llama/Llama-3.2-3B-Instruct", "llama3.2:3b"]);
+        assert_eq!(models[0].context_window, Some(128_000));
+        assert_eq!(models[1].runtime, LocalRuntime::LlamaStack);
+    }
+}
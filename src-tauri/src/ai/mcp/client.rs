@@ -1,38 +1,120 @@
+//! MCP Client - Calls tools on external MCP servers
+//!
+//! Talks JSON-RPC to a server spawned over stdio (one message per line) or
+//! reached over WebSocket (one message per text frame). Responses are matched
+//! to requests by id, and a request that gets no answer in time fails instead
+//! of waiting forever.
+
 use super::protocol::*;
+use super::{MCPCapability, MCPMessage};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use specta::Type;
 use std::collections::HashMap;
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::{mpsc, oneshot, Mutex};
-use tokio_tungstenite::tungstenite::error::Error as WsError;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+/// How long a request waits for its response by default
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+type PendingRequests = Arc<Mutex<HashMap<u64, oneshot::Sender<JsonRpcResponse>>>>;
+
+/// How to reach an MCP server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum McpTransport {
+    /// Spawn `command` and speak over its stdin/stdout
+    Stdio { command: String, args: Vec<String> },
+    /// Connect to a `ws://` or `wss://` endpoint
+    WebSocket { url: String },
+}
 
 pub struct McpClient {
-    command: String,
-    args: Vec<String>,
-    pending_requests: Arc<Mutex<HashMap<u64, oneshot::Sender<JsonRpcResponse>>>>,
+    transport: McpTransport,
+    timeout: Duration,
+    pending_requests: PendingRequests,
     request_tx: Option<mpsc::Sender<JsonRpcRequest>>,
-    next_id: Arc<Mutex<u64>>,
+    next_id: AtomicU64,
+    /// Set once the server hangs up
+    closed: Arc<AtomicBool>,
+    /// Kept so the server process lives (and dies) with the client
+    _child: Option<Child>,
+    server_info: Option<ClientInfo>,
 }
 
 impl McpClient {
+    /// Client for a server spawned as `command args...`
     pub fn new(command: &str, args: &[&str]) -> Self {
-        Self {
+        Self::with_transport(McpTransport::Stdio {
             command: command.to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
+        })
+    }
+
+    /// Client for a server listening on `url`
+    pub fn websocket(url: &str) -> Self {
+        Self::with_transport(McpTransport::WebSocket {
+            url: url.to_string(),
+        })
+    }
+
+    pub fn with_transport(transport: McpTransport) -> Self {
+        Self {
+            transport,
+            timeout: DEFAULT_REQUEST_TIMEOUT,
             pending_requests: Arc::new(Mutex::new(HashMap::new())),
             request_tx: None,
-            next_id: Arc::new(Mutex::new(1)),
+            next_id: AtomicU64::new(1),
+            closed: Arc::new(AtomicBool::new(false)),
+            _child: None,
+            server_info: None,
         }
     }
 
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Whether the transport is open and the server has not hung up
+    pub fn is_connected(&self) -> bool {
+        self.request_tx.as_ref().is_some_and(|tx| !tx.is_closed())
+            && !self.closed.load(Ordering::Relaxed)
+    }
+
+    /// Server name and version, once initialized
+    pub fn server_info(&self) -> Option<&ClientInfo> {
+        self.server_info.as_ref()
+    }
+
+    /// Start the transport and run the initialize handshake
+    pub async fn connect(&mut self) -> Result<(), String> {
+        self.start().await?;
+        self.initialize().await
+    }
+
+    /// Open the transport without initializing
     pub async fn start(&mut self) -> Result<(), String> {
-        let mut child = Command::new(&self.command)
-            .args(&self.args)
+        match self.transport.clone() {
+            McpTransport::Stdio { command, args } => self.start_stdio(&command, &args),
+            McpTransport::WebSocket { url } => self.start_websocket(&url).await,
+        }
+    }
+
+    fn start_stdio(&mut self, command: &str, args: &[String]) -> Result<(), String> {
+        let mut child = Command::new(command)
+            .args(args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::inherit())
+            .kill_on_drop(true)
             .spawn()
             .map_err(|e| format!("Failed to spawn MCP server: {}", e))?;
 
@@ -47,8 +129,7 @@ impl McpClient {
 
         let (tx, mut rx) = mpsc::channel::<JsonRpcRequest>(32);
         self.request_tx = Some(tx);
-
-        let pending_requests = self.pending_requests.clone();
+        self._child = Some(child);
 
         // Writer Task
         tokio::spawn(async move {
@@ -63,83 +144,159 @@ impl McpClient {
         });
 
         // Reader Task
+        let pending_requests = self.pending_requests.clone();
+        let closed = self.closed.clone();
         tokio::spawn(async move {
-            let reader = BufReader::new(stdout);
-            let mut lines = reader.lines();
-
+            let mut lines = BufReader::new(stdout).lines();
             while let Ok(Some(line)) = lines.next_line().await {
-                if line.trim().is_empty() {
-                    continue;
-                }
+                dispatch(&pending_requests, &line).await;
+            }
+            hang_up(&pending_requests, &closed).await;
+        });
+
+        Ok(())
+    }
+
+    async fn start_websocket(&mut self, url: &str) -> Result<(), String> {
+        let (ws_stream, _) = connect_async(url)
+            .await
+            .map_err(|e| format!("Failed to connect to MCP server: {}", e))?;
+        let (mut sink, mut stream) = ws_stream.split();
+
+        let (tx, mut rx) = mpsc::channel::<JsonRpcRequest>(32);
+        self.request_tx = Some(tx);
 
-                // Try to parse as Response
-                if let Ok(response) = serde_json::from_str::<JsonRpcResponse>(&line) {
-                    if let Some(id) = response.id {
-                        let mut map = pending_requests.lock().await;
-                        if let Some(sender) = map.remove(&id) {
-                            let _ = sender.send(response);
-                        }
+        // Writer Task
+        tokio::spawn(async move {
+            while let Some(req) = rx.recv().await {
+                if let Ok(json) = serde_json::to_string(&req) {
+                    if sink.send(Message::Text(json.into())).await.is_err() {
+                        break;
                     }
-                } else {
-                    // Try to parse as Notification or Request (if server calls client)
-                    // For now, just log or ignore
-                    eprintln!("MCP Reader received non-response: {}", line);
                 }
             }
         });
 
+        // Reader Task
+        let pending_requests = self.pending_requests.clone();
+        let closed = self.closed.clone();
+        tokio::spawn(async move {
+            while let Some(message) = stream.next().await {
+                match message {
+                    Ok(Message::Text(text)) => dispatch(&pending_requests, &text).await,
+                    Ok(Message::Close(_)) | Err(_) => break,
+                    Ok(_) => {}
+                }
+            }
+            hang_up(&pending_requests, &closed).await;
+        });
+
         Ok(())
     }
 
+    /// The `initialize` request followed by the `initialized` notification
+    pub async fn initialize(&mut self) -> Result<(), String> {
+        let params = McpInitializeParams {
+            protocol_version: MCP_PROTOCOL_VERSION.to_string(),
+            capabilities: serde_json::json!({}),
+            client_info: ClientInfo {
+                name: "CinemaOS".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+        };
+        let params = serde_json::to_value(params).map_err(|e| e.to_string())?;
+
+        let result: McpInitializeResult = self.request("initialize", Some(params)).await?;
+        self.server_info = result.server_info;
+
+        self.notify("notifications/initialized", None).await
+    }
+
+    async fn notify(&self, method: &str, params: Option<Value>) -> Result<(), String> {
+        self.sender()?
+            .send(JsonRpcRequest::notification(method, params))
+            .await
+            .map_err(|e| format!("Failed to send notification: {}", e))
+    }
+
+    fn sender(&self) -> Result<&mpsc::Sender<JsonRpcRequest>, String> {
+        self.request_tx
+            .as_ref()
+            .ok_or("Client not started".to_string())
+    }
+
     async fn send_request(
         &self,
         method: &str,
         params: Option<Value>,
     ) -> Result<JsonRpcResponse, String> {
-        let tx = self
-            .request_tx
-            .as_ref()
-            .ok_or("Client not started".to_string())?;
-
-        let id = {
-            let mut lock = self.next_id.lock().await;
-            let id = *lock;
-            *lock += 1;
-            id
-        };
+        let tx = self.sender()?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
 
         let request = JsonRpcRequest::new(method, params, id);
         let (resp_tx, resp_rx) = oneshot::channel();
+        self.pending_requests.lock().await.insert(id, resp_tx);
 
-        {
-            let mut map = self.pending_requests.lock().await;
-            map.insert(id, resp_tx);
+        // Checked after registering, so a hang-up can't strand the request
+        if self.closed.load(Ordering::Relaxed) {
+            self.pending_requests.lock().await.remove(&id);
+            return Err("MCP server closed the connection".to_string());
         }
 
-        tx.send(request)
-            .await
-            .map_err(|e| format!("Failed to send request: {}", e))?;
+        if let Err(e) = tx.send(request).await {
+            self.pending_requests.lock().await.remove(&id);
+            return Err(format!("Failed to send request: {}", e));
+        }
 
-        // Timeout logic could be added here
-        resp_rx
-            .await
-            .map_err(|e| format!("Failed to receive response: {}", e))
+        match tokio::time::timeout(self.timeout, resp_rx).await {
+            Ok(response) => response.map_err(|e| format!("Failed to receive response: {}", e)),
+            Err(_) => {
+                self.pending_requests.lock().await.remove(&id);
+                Err(format!(
+                    "MCP server did not answer {} within {}s",
+                    method,
+                    self.timeout.as_secs_f32()
+                ))
+            }
+        }
     }
 
-    pub async fn list_tools(&self) -> Result<Vec<McpTool>, String> {
-        let response = self.send_request("tools/list", None).await?;
+    /// Send a request and decode its result
+    async fn request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Option<Value>,
+    ) -> Result<T, String> {
+        let response = self.send_request(method, params).await?;
 
         if let Some(err) = response.error {
             return Err(format!("MCP Error {}: {}", err.code, err.message));
         }
 
-        if let Some(result) = response.result {
-            let list: McpListToolsResult = serde_json::from_value(result)
-                .map_err(|e| format!("Failed to parse tools/list result: {}", e))?;
-            Ok(list.tools)
-        } else {
-            Err("No result in tools/list response".to_string())
-        }
+        let result = response
+            .result
+            .ok_or_else(|| format!("No result in {} response", method))?;
+        serde_json::from_value(result)
+            .map_err(|e| format!("Failed to parse {} result: {}", method, e))
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<McpTool>, String> {
+        let list: McpListToolsResult = self.request("tools/list", None).await?;
+        Ok(list.tools)
+    }
+
+    /// The server's tools as capabilities
+    pub async fn capabilities(&self) -> Result<Vec<MCPCapability>, String> {
+        Ok(self
+            .list_tools()
+            .await?
+            .into_iter()
+            .map(|tool| MCPCapability {
+                description: tool.description.unwrap_or_default(),
+                name: tool.name,
+                parameters: tool.input_schema,
+            })
+            .collect())
     }
 
     pub async fn call_tool(
@@ -151,19 +308,152 @@ impl McpClient {
             "name": tool_name,
             "arguments": arguments
         });
+        self.request("tools/call", Some(params)).await
+    }
 
-        let response = self.send_request("tools/call", Some(params)).await?;
+    /// Answer a `ToolCall` with a `ToolResponse`
+    ///
+    /// Tool failures come back as the response's error; only transport and
+    /// protocol failures are `Err`.
+    pub async fn call(&self, message: MCPMessage) -> Result<MCPMessage, String> {
+        let MCPMessage::ToolCall { tool, params } = message else {
+            return Err("Invalid message type".to_string());
+        };
 
-        if let Some(err) = response.error {
-            return Err(format!("MCP Error {}: {}", err.code, err.message));
+        let result = self.call_tool(&tool, params).await?;
+        let error = result.is_error.unwrap_or(false).then(|| result.text());
+        Ok(MCPMessage::ToolResponse {
+            result: serde_json::to_value(&result.content).map_err(|e| e.to_string())?,
+            error,
+        })
+    }
+}
+
+/// Mark the connection closed and fail the requests still waiting on it
+async fn hang_up(pending_requests: &PendingRequests, closed: &AtomicBool) {
+    closed.store(true, Ordering::Relaxed);
+    pending_requests.lock().await.clear();
+}
+
+/// Hand a response to the request waiting for its id
+async fn dispatch(pending_requests: &PendingRequests, raw: &str) {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return;
+    }
+
+    // Requests and notifications from the server carry a method; none are handled yet
+    let message: Value = match serde_json::from_str(raw) {
+        Ok(message) => message,
+        Err(_) => {
+            tracing::warn!("MCP server sent invalid JSON: {}", raw);
+            return;
         }
+    };
+    if message.get("method").is_some() {
+        tracing::debug!("Ignoring MCP server message: {}", raw);
+        return;
+    }
 
-        if let Some(result) = response.result {
-            let call_result: McpCallToolResult = serde_json::from_value(result)
-                .map_err(|e| format!("Failed to parse tools/call result: {}", e))?;
-            Ok(call_result)
-        } else {
-            Err("No result in tools/call response".to_string())
+    let response = match serde_json::from_value::<JsonRpcResponse>(message) {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::warn!("Unreadable MCP response: {}", e);
+            return;
+        }
+    };
+    let Some(id) = response.id else {
+        return;
+    };
+    let sender = pending_requests.lock().await.remove(&id);
+    match sender {
+        Some(sender) => {
+            let _ = sender.send(response);
         }
+        None => tracing::debug!("MCP response {} arrived after its request gave up", id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(id: u64, result: Value) -> String {
+        serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_responses_match_requests_by_id() {
+        let pending: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let (first_tx, first_rx) = oneshot::channel();
+        let (second_tx, second_rx) = oneshot::channel();
+        pending.lock().await.insert(1, first_tx);
+        pending.lock().await.insert(2, second_tx);
+
+        // Out of order, with a server notification in between
+        dispatch(&pending, &response(2, serde_json::json!("second"))).await;
+        dispatch(
+            &pending,
+            r#"{"jsonrpc":"2.0","method":"notifications/progress","params":{}}"#,
+        )
+        .await;
+        dispatch(&pending, &response(1, serde_json::json!("first"))).await;
+
+        assert_eq!(first_rx.await.unwrap().result.unwrap(), "first");
+        assert_eq!(second_rx.await.unwrap().result.unwrap(), "second");
+        assert!(pending.lock().await.is_empty());
+    }
+
+    #[test]
+    fn test_tool_result_wire_format() {
+        let result: McpCallToolResult = serde_json::from_str(
+            r#"{"content":[{"type":"text","text":"3 results"},{"type":"image","data":"aGk=","mimeType":"image/png"}],"isError":false}"#,
+        )
+        .unwrap();
+        assert_eq!(result.text(), "3 results");
+        assert_eq!(result.content[1].mime_type.as_deref(), Some("image/png"));
+
+        let tools: McpListToolsResult = serde_json::from_str(
+            r#"{"tools":[{"name":"web_search","description":"Search the web","inputSchema":{"type":"object"}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(tools.tools[0].input_schema["type"], "object");
+
+        // Notifications go out without an id
+        let notification = serde_json::to_value(JsonRpcRequest::notification(
+            "notifications/initialized",
+            None,
+        ))
+        .unwrap();
+        assert!(notification.get("id").is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unanswered_request_times_out() {
+        // Reads nothing and never replies
+        let mut client = McpClient::new("sleep", &["5"]).with_timeout(Duration::from_millis(100));
+        client.start().await.unwrap();
+
+        let error = client.list_tools().await.unwrap_err();
+        assert!(error.contains("did not answer tools/list"), "{}", error);
+        assert!(client.pending_requests.lock().await.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_exited_server_fails_fast() {
+        let mut client = McpClient::new("true", &[]).with_timeout(Duration::from_secs(30));
+        client.start().await.unwrap();
+        for _ in 0..100 {
+            if !client.is_connected() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!client.is_connected());
+
+        let error = client.list_tools().await.unwrap_err();
+        assert!(error.contains("closed the connection"), "{}", error);
     }
 }
@@ -4,6 +4,7 @@
 
 pub mod client;
 pub mod protocol;
+pub mod registry;
 pub mod server;
 
 use serde::{Deserialize, Serialize};
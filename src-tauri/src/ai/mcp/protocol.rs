@@ -1,11 +1,19 @@
+//! JSON-RPC 2.0 messages as MCP puts them on the wire (camelCase fields)
+
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+/// MCP revision the client speaks
+pub const MCP_PROTOCOL_VERSION: &str = "2024-11-05";
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JsonRpcRequest {
     pub jsonrpc: String,
     pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub params: Option<Value>,
+    /// `None` for notifications, which get no response
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<u64>,
 }
 
@@ -25,12 +33,23 @@ pub struct JsonRpcError {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct McpInitializeParams {
     pub protocol_version: String,
     pub capabilities: Value,
     pub client_info: ClientInfo,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct McpInitializeResult {
+    pub protocol_version: String,
+    #[serde(default)]
+    pub capabilities: Value,
+    pub server_info: Option<ClientInfo>,
+}
+
+/// Name and version of either end of the connection
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ClientInfo {
     pub name: String,
@@ -38,9 +57,11 @@ pub struct ClientInfo {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct McpTool {
     pub name: String,
     pub description: Option<String>,
+    #[serde(default)]
     pub input_schema: Value,
 }
 
@@ -50,12 +71,15 @@ pub struct McpListToolsResult {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct McpCallToolResult {
+    #[serde(default)]
     pub content: Vec<McpContent>,
     pub is_error: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct McpContent {
     pub r#type: String,
     pub text: Option<String>,
@@ -72,4 +96,24 @@ impl JsonRpcRequest {
             id: Some(id),
         }
     }
+
+    pub fn notification(method: &str, params: Option<Value>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params,
+            id: None,
+        }
+    }
+}
+
+impl McpCallToolResult {
+    /// The text parts of the result, one per line
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|c| c.text.as_deref())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
@@ -0,0 +1,416 @@
+//! MCP Registry - External tool servers the agents may call
+//!
+//! Servers are configured in `mcp_servers.json` and connected on first use;
+//! a server that fails to connect is retried with backoff, and one that hangs
+//! up is reconnected.
+//! [`chat_with_tools`] lists their tools in the system prompt and runs the
+//! calls the model asks for, feeding each result back until it answers.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use super::client::{McpClient, McpTransport};
+use super::protocol::McpTool;
+use crate::ai::llm_client::{get_llm_client, LLMMessage, LLMRequest, LLMResponse, TokenUsage};
+use crate::ai::structured_output::extract_json;
+use crate::settings::{load_json, save_json};
+
+/// Tool calls allowed before the model must answer
+const MAX_TOOL_ROUNDS: usize = 3;
+
+/// Longest tool result passed back to the model, in characters
+const MAX_TOOL_RESULT_CHARS: usize = 8000;
+
+/// Wait before retrying a server that failed to connect, doubled per failure
+const RETRY_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(300);
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// SETTINGS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct McpServerConfig {
+    pub name: String,
+    pub transport: McpTransport,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct McpSettings {
+    pub servers: Vec<McpServerConfig>,
+}
+
+/// Where the MCP servers are persisted
+const MCP_SETTINGS_FILE: &str = "mcp_servers.json";
+
+static MCP_SETTINGS: Lazy<RwLock<McpSettings>> =
+    Lazy::new(|| RwLock::new(load_json(MCP_SETTINGS_FILE)));
+
+static CONNECTIONS: Lazy<tokio::sync::Mutex<Connections>> =
+    Lazy::new(|| tokio::sync::Mutex::new(Connections::default()));
+
+/// Configured MCP servers
+pub fn mcp_settings() -> McpSettings {
+    MCP_SETTINGS.read().map(|s| s.clone()).unwrap_or_default()
+}
+
+/// Replace the MCP servers (persisted across restarts); open connections are dropped
+pub async fn set_mcp_settings(settings: McpSettings) -> Result<(), String> {
+    let mut names = std::collections::HashSet::new();
+    if let Some(server) = settings.servers.iter().find(|s| !names.insert(&s.name)) {
+        return Err(format!("Two MCP servers are named {}", server.name));
+    }
+    {
+        let mut current = MCP_SETTINGS.write().map_err(|e| e.to_string())?;
+        save_json(MCP_SETTINGS_FILE, &settings)?;
+        *current = settings;
+    }
+    CONNECTIONS.lock().await.reset();
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TOOLS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// A tool and the server that provides it
+#[derive(Debug, Clone)]
+pub struct RegisteredTool {
+    pub server: String,
+    pub tool: McpTool,
+}
+
+/// A connected server and the tools it offered when it connected
+struct Connection {
+    client: McpClient,
+    tools: Vec<McpTool>,
+}
+
+/// When a server that failed to connect may be tried again
+struct Failure {
+    failures: u32,
+    retry_at: Instant,
+}
+
+#[derive(Default)]
+struct Connections {
+    /// Bumped when the settings change, so connects begun before are dropped
+    generation: u64,
+    live: HashMap<String, Arc<Connection>>,
+    failed: HashMap<String, Failure>,
+}
+
+impl Connections {
+    fn reset(&mut self) {
+        self.generation += 1;
+        self.live.clear();
+        self.failed.clear();
+    }
+
+    /// Whether `server` should be connected now
+    fn due(&self, server: &str, now: Instant) -> bool {
+        !self.live.contains_key(server) && self.failed.get(server).is_none_or(|f| f.retry_at <= now)
+    }
+
+    fn record_failure(&mut self, server: String, now: Instant) {
+        let failures = self.failed.get(&server).map_or(1, |f| f.failures + 1);
+        let retry_at = now + retry_delay(failures);
+        self.failed.insert(server, Failure { failures, retry_at });
+    }
+}
+
+/// Backoff after `failures` failed connects in a row
+fn retry_delay(failures: u32) -> Duration {
+    RETRY_BACKOFF
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(MAX_RETRY_BACKOFF)
+}
+
+async fn connect(transport: McpTransport) -> Result<Connection, String> {
+    let mut client = McpClient::with_transport(transport);
+    client.connect().await?;
+    let tools = client.list_tools().await?;
+    Ok(Connection { client, tools })
+}
+
+/// Connections to every enabled server that is reachable
+///
+/// Servers that hung up are reconnected. Connecting happens outside the lock,
+/// so one slow server doesn't hold up callers that only need the others.
+async fn connections() -> Vec<(String, Arc<Connection>)> {
+    let (generation, pending) = {
+        let mut state = CONNECTIONS.lock().await;
+        state.live.retain(|_, c| c.client.is_connected());
+        let now = Instant::now();
+        let pending: Vec<McpServerConfig> = mcp_settings()
+            .servers
+            .into_iter()
+            .filter(|s| s.enabled && state.due(&s.name, now))
+            .collect();
+        (state.generation, pending)
+    };
+
+    let attempts = futures_util::future::join_all(
+        pending
+            .into_iter()
+            .map(|server| async move { (server.name, connect(server.transport).await) }),
+    )
+    .await;
+
+    let mut state = CONNECTIONS.lock().await;
+    if state.generation == generation {
+        for (name, result) in attempts {
+            match result {
+                Ok(connection) => {
+                    state.failed.remove(&name);
+                    // Another caller may have connected it meanwhile; keep theirs
+                    state
+                        .live
+                        .entry(name)
+                        .or_insert_with(|| Arc::new(connection));
+                }
+                Err(e) => {
+                    tracing::warn!("MCP server {} unavailable: {}", name, e);
+                    state.record_failure(name, Instant::now());
+                }
+            }
+        }
+    }
+    state
+        .live
+        .iter()
+        .map(|(name, connection)| (name.clone(), connection.clone()))
+        .collect()
+}
+
+/// A tool as listed in settings
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct McpToolInfo {
+    pub server: String,
+    pub name: String,
+    pub description: String,
+}
+
+impl From<RegisteredTool> for McpToolInfo {
+    fn from(registered: RegisteredTool) -> Self {
+        Self {
+            server: registered.server,
+            name: registered.tool.name,
+            description: registered.tool.description.unwrap_or_default(),
+        }
+    }
+}
+
+/// Every tool the enabled servers offer
+pub async fn available_tools() -> Vec<RegisteredTool> {
+    connections()
+        .await
+        .into_iter()
+        .flat_map(|(server, connection)| {
+            connection
+                .tools
+                .iter()
+                .map(|tool| RegisteredTool {
+                    server: server.clone(),
+                    tool: tool.clone(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Run `tool` on whichever server offers it, returning its text output
+///
+/// If the server hangs up during the call, it is reconnected and the call is
+/// made once more.
+pub async fn call_tool(tool: &str, arguments: serde_json::Value) -> Result<String, String> {
+    let mut retried = false;
+    loop {
+        let (server, connection) = connections()
+            .await
+            .into_iter()
+            .find(|(_, c)| c.tools.iter().any(|t| t.name == tool))
+            .ok_or_else(|| format!("No MCP server offers {}", tool))?;
+
+        let result = match connection.client.call_tool(tool, arguments.clone()).await {
+            Ok(result) => result,
+            Err(e) if retried || connection.client.is_connected() => return Err(e),
+            Err(e) => {
+                tracing::warn!("MCP server {} hung up, reconnecting: {}", server, e);
+                retried = true;
+                continue;
+            }
+        };
+        if result.is_error.unwrap_or(false) {
+            return Err(format!("{} on {} failed: {}", tool, server, result.text()));
+        }
+        return Ok(result.text());
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TOOL USE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[derive(Debug, Deserialize)]
+struct ToolCallReply {
+    tool_call: ToolCallRequest,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolCallRequest {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
+}
+
+/// System prompt section listing `tools` and how to call them
+fn tools_section(tools: &[RegisteredTool]) -> String {
+    let mut section = String::from(
+        "\n\n# Tools\nYou can call these tools. To call one, reply with only \
+         {\"tool_call\": {\"name\": \"...\", \"arguments\": {...}}} and nothing else; \
+         the result comes back in the next message. Otherwise answer normally.\n",
+    );
+    for RegisteredTool { tool, .. } in tools {
+        section.push_str(&format!(
+            "- {}: {} Arguments: {}\n",
+            tool.name,
+            tool.description.as_deref().unwrap_or(""),
+            tool.input_schema
+        ));
+    }
+    section
+}
+
+/// The tool the model asked for, if its reply is a tool call
+fn parse_tool_call(raw: &str) -> Option<(String, serde_json::Value)> {
+    let reply: ToolCallReply = serde_json::from_str(extract_json(raw)?).ok()?;
+    Some((reply.tool_call.name, reply.tool_call.arguments))
+}
+
+/// `chat` that lets the model call MCP tools before answering
+///
+/// Without configured servers this is a single plain `chat`.
+pub async fn chat_with_tools(mut request: LLMRequest) -> Result<LLMResponse, String> {
+    let llm = get_llm_client();
+    let tools = available_tools().await;
+    if tools.is_empty() {
+        return llm.chat(request).await;
+    }
+
+    let prompt = request.system_prompt.take().unwrap_or_default();
+    request.system_prompt = Some(prompt + &tools_section(&tools));
+
+    let mut usage = None;
+    let mut rounds = 0;
+    loop {
+        let mut response = llm.chat(request.clone()).await?;
+        usage = add_usage(usage, response.usage.take());
+
+        let call = (rounds < MAX_TOOL_ROUNDS)
+            .then(|| parse_tool_call(&response.content))
+            .flatten();
+        let Some((name, arguments)) = call else {
+            response.usage = usage;
+            return Ok(response);
+        };
+
+        let result = match call_tool(&name, arguments).await {
+            Ok(output) => output.chars().take(MAX_TOOL_RESULT_CHARS).collect(),
+            Err(e) => format!("Error: {}", e),
+        };
+        request.messages.push(LLMMessage {
+            role: "assistant".to_string(),
+            content: response.content,
+            images: Vec::new(),
+        });
+        request.messages.push(LLMMessage {
+            role: "user".to_string(),
+            content: format!("Result of {}:\n{}", name, result),
+            images: Vec::new(),
+        });
+        rounds += 1;
+    }
+}
+
+fn add_usage(total: Option<TokenUsage>, more: Option<TokenUsage>) -> Option<TokenUsage> {
+    match (total, more) {
+        (Some(mut total), Some(more)) => {
+            total.prompt_tokens += more.prompt_tokens;
+            total.completion_tokens += more.completion_tokens;
+            total.total_tokens += more.total_tokens;
+            Some(total)
+        }
+        (total, more) => total.or(more),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tool_call() {
+        let raw = r#"```json
+{"tool_call": {"name": "web_search", "arguments": {"query": "1940s LA diner interiors"}}}
+```"#;
+        let (name, arguments) = parse_tool_call(raw).unwrap();
+        assert_eq!(name, "web_search");
+        assert_eq!(arguments["query"], "1940s LA diner interiors");
+
+        assert!(parse_tool_call("The diner should feel cramped and warm.").is_none());
+        assert!(parse_tool_call(r#"{"message": "no tools needed"}"#).is_none());
+    }
+
+    #[test]
+    fn test_failed_servers_back_off() {
+        let mut state = Connections::default();
+        let now = Instant::now();
+        assert!(state.due("search", now));
+
+        state.record_failure("search".into(), now);
+        assert!(!state.due("search", now));
+        assert!(state.due("search", now + RETRY_BACKOFF));
+
+        state.record_failure("search".into(), now);
+        assert!(!state.due("search", now + RETRY_BACKOFF));
+        assert!(state.due("search", now + RETRY_BACKOFF * 2));
+
+        assert_eq!(retry_delay(30), MAX_RETRY_BACKOFF);
+
+        // New settings start over
+        state.reset();
+        assert!(state.due("search", now));
+        assert_eq!(state.generation, 1);
+    }
+
+    #[test]
+    fn test_settings_wire_format() {
+        let settings: McpSettings = serde_json::from_str(
+            r#"{"servers": [
+                {"name": "search", "transport": {"type": "stdio", "command": "npx", "args": ["-y", "search-mcp"]}},
+                {"name": "files", "transport": {"type": "web_socket", "url": "ws://127.0.0.1:9000"}, "enabled": false}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(settings.servers[0].enabled);
+        assert!(!settings.servers[1].enabled);
+        assert_eq!(
+            settings.servers[1].transport,
+            McpTransport::WebSocket {
+                url: "ws://127.0.0.1:9000".into()
+            }
+        );
+    }
+}
@@ -1,13 +1,73 @@
 //! MCP Server - Exposes CinemaOS capabilities to agents
+//!
+//! Each tool is an existing Tauri command: its parameter schema is generated
+//! from the command's argument types and calls go straight to the command.
+//! External agents and IDEs reach the server over WebSocket, one JSON-RPC
+//! message per text frame. Clients must present the session's bearer secret;
+//! browsers (anything sending an `Origin`) are turned away so a web page
+//! can't drive the Vault through localhost.
 
+use super::protocol::*;
 use super::{MCPCapability, MCPMessage};
-use serde_json::Value;
+use futures_util::future::BoxFuture;
+use futures_util::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use specta::datatype::{DataType, EnumVariants, Field, Function, PrimitiveType, StructFields};
+use specta::function::fn_datatype;
+use specta::{Type, TypeCollection};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::Arc;
+use surrealdb::engine::any::Any;
+use surrealdb::Surreal;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_tungstenite::accept_hdr_async;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{header, HeaderMap, StatusCode};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::ai::generate::{self, GenerateRequest};
+use crate::commands::{self, tokens};
+use crate::vault::{self, tokens::Token};
+
+/// JSON-RPC error codes
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const INTERNAL_ERROR: i32 = -32603;
+
+/// Length of the per-session bearer secret
+const SECRET_LEN: usize = 40;
+
+/// What tools run against
+#[derive(Clone, Default)]
+pub struct ToolContext {
+    db: Option<Surreal<Any>>,
+}
+
+impl ToolContext {
+    /// The injected database, else the Vault's
+    async fn db(&self) -> Result<Surreal<Any>, String> {
+        match &self.db {
+            Some(db) => Ok(db.clone()),
+            None => vault::wait_for_db(vault::DB_WAIT_TIMEOUT)
+                .await
+                .ok_or_else(|| "Vault not initialized".to_string()),
+        }
+    }
+}
 
 /// MCP Server
 pub struct MCPServer {
     capabilities: Vec<MCPCapability>,
     tools: HashMap<String, Box<dyn MCPTool>>,
+    context: ToolContext,
 }
 
 impl MCPServer {
@@ -15,13 +75,44 @@ impl MCPServer {
         let mut server = Self {
             capabilities: Vec::new(),
             tools: HashMap::new(),
+            context: ToolContext::default(),
         };
 
-        // Register built-in tools
-        server.register_tool(Box::new(GenerateImageTool));
-        server.register_tool(Box::new(QueryVaultTool));
-        server.register_tool(Box::new(CreateTokenTool));
+        // Built-in tools, one per command
+        let types = &mut TypeCollection::default();
+        server.register_command(
+            fn_datatype!(tokens::get_tokens)(types),
+            types,
+            call_get_tokens,
+        );
+        server.register_command(
+            fn_datatype!(tokens::search_tokens)(types),
+            types,
+            call_search_tokens,
+        );
+        server.register_command(
+            fn_datatype!(tokens::create_token)(types),
+            types,
+            call_create_token,
+        );
+        server.register_command(
+            fn_datatype!(commands::search_script)(types),
+            types,
+            call_search_script,
+        );
+        server.register_command(
+            fn_datatype!(commands::ai::generate)(types),
+            types,
+            call_generate,
+        );
+
+        server
+    }
 
+    /// A server whose tools use `db` instead of the Vault's
+    pub fn with_db(db: Surreal<Any>) -> Self {
+        let mut server = Self::new();
+        server.context.db = Some(db);
         server
     }
 
@@ -31,6 +122,13 @@ impl MCPServer {
         self.tools.insert(tool.name().to_string(), tool);
     }
 
+    fn register_command(&mut self, function: Function, types: &TypeCollection, handler: Handler) {
+        self.register_tool(Box::new(CommandTool {
+            capability: command_capability(&function, types),
+            handler,
+        }));
+    }
+
     pub fn capabilities(&self) -> &[MCPCapability] {
         &self.capabilities
     }
@@ -40,7 +138,7 @@ impl MCPServer {
         match message {
             MCPMessage::ToolCall { tool, params } => {
                 if let Some(tool_impl) = self.tools.get(&tool) {
-                    tool_impl.execute(params).await
+                    tool_impl.execute(&self.context, params).await
                 } else {
                     Err(format!("Tool not found: {}", tool))
                 }
@@ -48,6 +146,99 @@ impl MCPServer {
             _ => Err("Invalid message type".to_string()),
         }
     }
+
+    /// Answer a `ToolCall` with a `ToolResponse`
+    pub async fn handle(&self, message: MCPMessage) -> MCPMessage {
+        match self.execute(message).await {
+            Ok(result) => MCPMessage::ToolResponse {
+                result,
+                error: None,
+            },
+            Err(e) => MCPMessage::ToolResponse {
+                result: Value::Null,
+                error: Some(e),
+            },
+        }
+    }
+
+    /// Answer one JSON-RPC message; notifications get no response
+    pub async fn handle_rpc(&self, request: JsonRpcRequest) -> Option<JsonRpcResponse> {
+        let id = request.id?;
+        let params = request.params.unwrap_or(Value::Null);
+
+        let result = match request.method.as_str() {
+            "initialize" => Ok(json!({
+                "protocolVersion": MCP_PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "CinemaOS", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.list_tools() })),
+            "tools/call" => self.call_tool(params).await,
+            other => Err(JsonRpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Unknown method: {}", other),
+                data: None,
+            }),
+        };
+
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Some(JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            result,
+            error,
+            id: Some(id),
+        })
+    }
+
+    fn list_tools(&self) -> Vec<McpTool> {
+        self.capabilities
+            .iter()
+            .map(|cap| McpTool {
+                name: cap.name.clone(),
+                description: Some(cap.description.clone()),
+                input_schema: cap.parameters.clone(),
+            })
+            .collect()
+    }
+
+    /// `tools/call`: failures of the tool itself are reported in the result
+    async fn call_tool(&self, params: Value) -> Result<Value, JsonRpcError> {
+        let name = params["name"].as_str().ok_or_else(|| JsonRpcError {
+            code: INVALID_PARAMS,
+            message: "tools/call requires a tool name".to_string(),
+            data: None,
+        })?;
+        let call = MCPMessage::ToolCall {
+            tool: name.to_string(),
+            params: params.get("arguments").cloned().unwrap_or(json!({})),
+        };
+
+        let (text, is_error) = match self.handle(call).await {
+            MCPMessage::ToolResponse {
+                error: Some(error), ..
+            } => (error, true),
+            MCPMessage::ToolResponse { result, .. } => (result.to_string(), false),
+            _ => unreachable!("handle always returns a ToolResponse"),
+        };
+        let result = McpCallToolResult {
+            content: vec![McpContent {
+                r#type: "text".to_string(),
+                text: Some(text),
+                data: None,
+                mime_type: None,
+            }],
+            is_error: Some(is_error),
+        };
+        serde_json::to_value(result).map_err(|e| JsonRpcError {
+            code: INTERNAL_ERROR,
+            message: e.to_string(),
+            data: None,
+        })
+    }
 }
 
 impl Default for MCPServer {
@@ -61,106 +252,497 @@ impl Default for MCPServer {
 pub trait MCPTool: Send + Sync {
     fn name(&self) -> &str;
     fn capability(&self) -> MCPCapability;
-    async fn execute(&self, params: Value) -> Result<Value, String>;
+    async fn execute(&self, context: &ToolContext, params: Value) -> Result<Value, String>;
 }
 
-/// Generate Image Tool
-struct GenerateImageTool;
+// ═══════════════════════════════════════════════════════════════════════════════
+// COMMAND TOOLS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+type Handler = fn(ToolContext, Value) -> BoxFuture<'static, Result<Value, String>>;
+
+/// A Tauri command called as a tool
+struct CommandTool {
+    capability: MCPCapability,
+    handler: Handler,
+}
 
 #[async_trait::async_trait]
-impl MCPTool for GenerateImageTool {
+impl MCPTool for CommandTool {
     fn name(&self) -> &str {
-        "generate_image"
+        &self.capability.name
     }
 
     fn capability(&self) -> MCPCapability {
-        MCPCapability {
-            name: "generate_image".to_string(),
-            description: "Generate image via ComfyUI".to_string(),
-            parameters: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "prompt": {"type": "string"},
-                    "width": {"type": "number", "default": 1024},
-                    "height": {"type": "number", "default": 1024},
-                    "seed": {"type": "number", "optional": true}
-                },
-                "required": ["prompt"]
-            }),
-        }
+        self.capability.clone()
     }
 
-    async fn execute(&self, params: Value) -> Result<Value, String> {
-        // TODO: Call actual ComfyUI integration
-        Ok(serde_json::json!({
-            "status": "queued",
-            "prompt_id": "placeholder_id"
-        }))
+    async fn execute(&self, context: &ToolContext, params: Value) -> Result<Value, String> {
+        (self.handler)(context.clone(), params).await
     }
 }
 
-/// Query Vault Tool
-struct QueryVaultTool;
+/// Argument `name` of a call; a missing argument reads as `null`
+fn arg<T: DeserializeOwned>(params: &Value, name: &str) -> Result<T, String> {
+    let value = params.get(name).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|e| format!("Invalid argument {}: {}", name, e))
+}
 
-#[async_trait::async_trait]
-impl MCPTool for QueryVaultTool {
-    fn name(&self) -> &str {
-        "query_vault"
+fn to_json<T: Serialize>(value: T) -> Result<Value, String> {
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
+
+fn call_get_tokens(
+    context: ToolContext,
+    params: Value,
+) -> BoxFuture<'static, Result<Value, String>> {
+    Box::pin(async move {
+        let project_id: String = arg(&params, "project_id")?;
+        let db = context.db().await?;
+        to_json(vault::tokens::project_tokens(&db, &project_id).await?)
+    })
+}
+
+fn call_search_tokens(
+    context: ToolContext,
+    params: Value,
+) -> BoxFuture<'static, Result<Value, String>> {
+    Box::pin(async move {
+        let project_id: String = arg(&params, "project_id")?;
+        let query: String = arg(&params, "query")?;
+        let db = context.db().await?;
+        to_json(
+            vault::tokens::search_tokens(&db, &project_id, &query, arg(&params, "token_type")?)
+                .await?,
+        )
+    })
+}
+
+fn call_create_token(
+    context: ToolContext,
+    params: Value,
+) -> BoxFuture<'static, Result<Value, String>> {
+    Box::pin(async move {
+        let token = Token::new(
+            arg(&params, "project_id")?,
+            arg(&params, "token_type")?,
+            arg(&params, "name")?,
+            arg(&params, "description")?,
+        );
+        let db = context.db().await?;
+        to_json(vault::tokens::insert_token(&db, token).await?)
+    })
+}
+
+fn call_search_script(_: ToolContext, params: Value) -> BoxFuture<'static, Result<Value, String>> {
+    Box::pin(async move {
+        to_json(commands::search_script(arg(&params, "project_id")?, arg(&params, "query")?).await?)
+    })
+}
+
+/// `generate` without a window to report progress to
+fn call_generate(_: ToolContext, params: Value) -> BoxFuture<'static, Result<Value, String>> {
+    Box::pin(async move {
+        let request: GenerateRequest = arg(&params, "request")?;
+        to_json(generate::generate(request, |_| {}).await?)
+    })
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PARAMETER SCHEMAS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// A property name and its type; skipped fields have no type
+type SchemaField<'a> = (&'a str, Option<&'a DataType>);
+
+/// Tool description and JSON Schema for a command's arguments
+fn command_capability(function: &Function, types: &TypeCollection) -> MCPCapability {
+    let fields = function
+        .args()
+        .map(|(name, ty)| (name.as_ref(), Some(ty)))
+        .collect();
+
+    MCPCapability {
+        name: function.name().to_string(),
+        description: function.docs().trim().to_string(),
+        parameters: object_schema(fields, types),
     }
+}
 
-    fn capability(&self) -> MCPCapability {
-        MCPCapability {
-            name: "query_vault".to_string(),
-            description: "Query Vault for tokens (characters, locations, props)".to_string(),
-            parameters: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "token_type": {"type": "string", "enum": ["character", "location", "prop"]},
-                    "name": {"type": "string", "optional": true}
-                },
-                "required": ["token_type"]
-            }),
+/// `{"type": "object"}` with one property per field; nullable fields are optional
+fn object_schema(fields: Vec<SchemaField<'_>>, types: &TypeCollection) -> Value {
+    let mut properties = serde_json::Map::new();
+    let mut required = Vec::new();
+    for (name, ty) in fields {
+        let Some(ty) = ty else { continue };
+        if !matches!(ty, DataType::Nullable(_)) {
+            required.push(name);
+        }
+        properties.insert(name.to_string(), json_schema(ty, types));
+    }
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn named_fields<'a>(fields: &'a [(Cow<'static, str>, Field)]) -> Vec<SchemaField<'a>> {
+    fields
+        .iter()
+        .map(|(name, field)| (name.as_ref(), field.ty()))
+        .collect()
+}
+
+/// JSON Schema for a Specta type; anything without a JSON equivalent is unconstrained
+fn json_schema(ty: &DataType, types: &TypeCollection) -> Value {
+    match ty {
+        DataType::Primitive(primitive) => {
+            let json_type = match primitive {
+                PrimitiveType::String | PrimitiveType::char => "string",
+                PrimitiveType::bool => "boolean",
+                PrimitiveType::f32 | PrimitiveType::f64 => "number",
+                _ => "integer",
+            };
+            json!({ "type": json_type })
         }
+        DataType::Nullable(inner) => json_schema(inner, types),
+        DataType::List(list) => json!({ "type": "array", "items": json_schema(list.ty(), types) }),
+        DataType::Map(map) => json!({
+            "type": "object",
+            "additionalProperties": json_schema(map.value_ty(), types),
+        }),
+        DataType::Struct(strukt) => match strukt.fields() {
+            StructFields::Named(fields) => object_schema(named_fields(fields.fields()), types),
+            _ => json!({}),
+        },
+        DataType::Enum(enumeration) => {
+            let variants = enumeration
+                .variants()
+                .iter()
+                .filter(|(_, variant)| !variant.skip());
+            if variants
+                .clone()
+                .all(|(_, variant)| matches!(variant.inner(), EnumVariants::Unit))
+            {
+                let names: Vec<&str> = variants.map(|(name, _)| name.as_ref()).collect();
+                json!({ "type": "string", "enum": names })
+            } else {
+                json!({})
+            }
+        }
+        DataType::Reference(reference) => types
+            .get(reference.sid())
+            .map(|named| json_schema(&named.inner, types))
+            .unwrap_or_else(|| json!({})),
+        _ => json!({}),
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TRANSPORT
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Where a running server listens and the secret clients must present
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct McpServerInfo {
+    pub url: String,
+    /// Sent by clients as `Authorization: Bearer <token>`
+    pub token: String,
+}
+
+struct RunningServer {
+    info: McpServerInfo,
+    task: JoinHandle<()>,
+}
+
+/// The server started by `start`, if any
+static RUNNING: Lazy<Mutex<Option<RunningServer>>> = Lazy::new(|| Mutex::new(None));
+
+/// Serve on `ws://127.0.0.1:<port>` with a fresh secret; one server at a time
+pub async fn start(port: u16) -> Result<McpServerInfo, String> {
+    let mut running = RUNNING.lock().await;
+    if let Some(server) = running.as_ref().filter(|server| !server.task.is_finished()) {
+        return Err(format!("MCP server already running on {}", server.info.url));
     }
 
-    async fn execute(&self, params: Value) -> Result<Value, String> {
-        // TODO: Call actual Vault (SurrealDB) query
-        Ok(serde_json::json!({
-            "tokens": []
-        }))
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("Failed to bind: {}", e))?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let info = McpServerInfo {
+        url: format!("ws://{}", addr),
+        token: new_secret(),
+    };
+    tracing::info!("MCP server listening on {}", info.url);
+
+    let secret = info.token.clone();
+    let task = tokio::spawn(async move {
+        if let Err(e) = serve(listener, secret).await {
+            tracing::error!("MCP server error: {}", e);
+        }
+    });
+    *running = Some(RunningServer {
+        info: info.clone(),
+        task,
+    });
+    Ok(info)
+}
+
+/// Stop the running server and drop its clients; false if none was running
+pub async fn stop() -> bool {
+    match RUNNING.lock().await.take() {
+        Some(server) => {
+            server.task.abort();
+            tracing::info!("MCP server on {} stopped", server.info.url);
+            true
+        }
+        None => false,
     }
 }
 
-/// Create Token Tool
-struct CreateTokenTool;
+fn new_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LEN)
+        .map(char::from)
+        .collect()
+}
 
-#[async_trait::async_trait]
-impl MCPTool for CreateTokenTool {
-    fn name(&self) -> &str {
-        "create_token"
+/// Accept MCP clients presenting `secret` on `listener` until it fails
+///
+/// Connections live in a `JoinSet`, so aborting this task closes them too.
+pub async fn serve(listener: TcpListener, secret: String) -> Result<(), String> {
+    let server = Arc::new(MCPServer::new());
+    let secret: Arc<str> = secret.into();
+    let mut connections = JoinSet::new();
+    loop {
+        let (stream, peer) = listener
+            .accept()
+            .await
+            .map_err(|e| format!("MCP server stopped accepting: {}", e))?;
+        while connections.try_join_next().is_some() {}
+        tracing::info!("MCP client connected from {}", peer);
+
+        let server = server.clone();
+        let secret = secret.clone();
+        connections.spawn(async move {
+            if let Err(e) = serve_connection(&server, stream, &secret).await {
+                tracing::warn!("MCP connection from {} closed: {}", peer, e);
+            }
+        });
     }
+}
 
-    fn capability(&self) -> MCPCapability {
-        MCPCapability {
-            name: "create_token".to_string(),
-            description: "Create a new token in the Vault".to_string(),
-            parameters: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "token_type": {"type": "string"},
-                    "name": {"type": "string"},
-                    "description": {"type": "string"}
-                },
-                "required": ["token_type", "name", "description"]
-            }),
+/// Admit a WebSocket upgrade only with the bearer secret and no browser `Origin`
+fn check_handshake(headers: &HeaderMap, secret: &str) -> Result<(), StatusCode> {
+    if headers.contains_key(header::ORIGIN) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if secrets_match(presented.as_bytes(), secret.as_bytes()) {
+        Ok(())
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Compare without stopping at the first differing byte
+fn secrets_match(presented: &[u8], secret: &[u8]) -> bool {
+    presented.len() == secret.len()
+        && presented
+            .iter()
+            .zip(secret)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn serve_connection(
+    server: &MCPServer,
+    stream: TcpStream,
+    secret: &str,
+) -> Result<(), String> {
+    let authorize = |request: &Request, response: Response| {
+        check_handshake(request.headers(), secret)
+            .map(|()| response)
+            .map_err(|status| {
+                let mut rejection = ErrorResponse::new(Some(status.to_string()));
+                *rejection.status_mut() = status;
+                rejection
+            })
+    };
+    let ws_stream = accept_hdr_async(stream, authorize)
+        .await
+        .map_err(|e| e.to_string())?;
+    let (mut sink, mut stream) = ws_stream.split();
+
+    while let Some(message) = stream.next().await {
+        let text = match message.map_err(|e| e.to_string())? {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let request: JsonRpcRequest = match serde_json::from_str(&text) {
+            Ok(request) => request,
+            Err(e) => {
+                tracing::warn!("Unreadable MCP request: {}", e);
+                continue;
+            }
+        };
+        if let Some(response) = server.handle_rpc(request).await {
+            let json = serde_json::to_string(&response).map_err(|e| e.to_string())?;
+            sink.send(Message::Text(json.into()))
+                .await
+                .map_err(|e| e.to_string())?;
         }
     }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::connect_async;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+    #[test]
+    fn test_schemas_come_from_command_arguments() {
+        let server = MCPServer::new();
+        let schema = |name: &str| {
+            server
+                .capabilities()
+                .iter()
+                .find(|cap| cap.name == name)
+                .unwrap()
+                .parameters
+                .clone()
+        };
+
+        let search = schema("search_tokens");
+        assert_eq!(search["properties"]["query"]["type"], "string");
+        assert_eq!(
+            search["properties"]["token_type"]["enum"],
+            json!(["Character", "Location", "Prop", "Scene"])
+        );
+        assert_eq!(search["required"], json!(["project_id", "query"]));
+
+        // The window argument isn't the caller's to give
+        let generate = schema("generate");
+        assert_eq!(generate["required"], json!(["request"]));
+        assert_eq!(
+            generate["properties"]["request"]["properties"]["prompt"]["type"],
+            "string"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_tokens_tool_call() {
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        db.use_ns(vault::NAMESPACE)
+            .use_db(vault::DATABASE)
+            .await
+            .unwrap();
+        let token = Token::new(
+            "project:noir".into(),
+            vault::tokens::TokenType::Character,
+            "Marlowe".into(),
+            "A tired detective".into(),
+        );
+        let _: Option<Token> = db.create("token").content(token).await.unwrap();
+        let server = MCPServer::with_db(db);
+
+        let response = server
+            .handle(MCPMessage::ToolCall {
+                tool: "get_tokens".into(),
+                params: json!({ "project_id": "project:noir" }),
+            })
+            .await;
+
+        let MCPMessage::ToolResponse { result, error } = response else {
+            panic!("expected a ToolResponse");
+        };
+        assert_eq!(error, None);
+        assert_eq!(result[0]["name"], "Marlowe");
+        assert_eq!(result[0]["token_type"], "Character");
+        assert_eq!(result[0]["slug"], "@marlowe");
+
+        let rpc = server
+            .handle_rpc(JsonRpcRequest::new(
+                "tools/call",
+                Some(json!({ "name": "get_tokens", "arguments": {} })),
+                7,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(rpc.id, Some(7));
+        assert_eq!(rpc.result.unwrap()["isError"], true);
+    }
+
+    #[test]
+    fn test_handshake_requires_secret_and_no_origin() {
+        let headers = |pairs: &[(header::HeaderName, &str)]| {
+            let mut map = HeaderMap::new();
+            for (name, value) in pairs {
+                map.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+            }
+            map
+        };
+
+        assert_eq!(
+            check_handshake(&headers(&[]), "s3cret"),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            check_handshake(
+                &headers(&[(header::AUTHORIZATION, "Bearer wrong!")]),
+                "s3cret"
+            ),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            check_handshake(
+                &headers(&[
+                    (header::AUTHORIZATION, "Bearer s3cret"),
+                    (header::ORIGIN, "https://evil.example"),
+                ]),
+                "s3cret"
+            ),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            check_handshake(
+                &headers(&[(header::AUTHORIZATION, "Bearer s3cret")]),
+                "s3cret"
+            ),
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_server_admits_only_its_secret_and_runs_once() {
+        let info = start(0).await.unwrap();
+        assert!(start(0).await.is_err());
+
+        assert!(connect_async(info.url.as_str()).await.is_err());
+
+        let mut request = info.url.as_str().into_client_request().unwrap();
+        request.headers_mut().insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", info.token)).unwrap(),
+        );
+        let (mut client, _) = connect_async(request).await.unwrap();
+        let ping = serde_json::to_string(&JsonRpcRequest::new("ping", None, 1)).unwrap();
+        client.send(Message::Text(ping.into())).await.unwrap();
+        let Some(Ok(Message::Text(reply))) = client.next().await else {
+            panic!("expected a reply to ping");
+        };
+        let reply: JsonRpcResponse = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply.id, Some(1));
 
-    async fn execute(&self, params: Value) -> Result<Value, String> {
-        // TODO: Call actual Vault create
-        Ok(serde_json::json!({
-            "id": "token_123",
-            "created": true
-        }))
+        assert!(stop().await);
+        assert!(!stop().await);
+        assert!(start(0).await.is_ok());
+        assert!(stop().await);
     }
 }
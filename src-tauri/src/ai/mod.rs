@@ -22,17 +22,37 @@ pub mod templates;
 // LEGACY: Existing AI modules (preserved)
 pub mod actions;
 pub mod agent_executor;
+pub mod agent_memory;
 pub mod agents;
+pub mod comfyui;
 pub mod comfyui_client;
 pub mod context;
+pub mod dialogue;
 pub mod elevenlabs_client;
 pub mod fal_client;
+pub mod generate;
+pub mod generation_queue;
+pub mod hybrid;
 pub mod keygen_client;
+pub mod llm_cache;
 pub mod llm_client;
+pub mod llm_stream;
 pub mod local;
+pub mod local_models;
+pub mod model_params;
+pub mod model_schema;
 pub mod models;
+pub mod project_memory;
+pub mod prompt_enhancer;
+pub mod prompt_guard;
 pub mod providers;
+pub mod resolution;
 pub mod router;
+pub mod storyboard;
+pub mod structured_output;
+pub mod token_budget;
+pub mod token_extractor;
+pub mod transcription;
 pub mod uv_manager;
 pub mod workflow;
 pub mod workflow_generator;
@@ -54,16 +74,14 @@ pub enum AgentCapability {
     ArtDirection,
     /// Voice generation & TTS
     VoiceActing,
-    /// Music & sound effects
+    /// Music & sound effects (formerly also reported as `MusicSFX`)
+    #[serde(alias = "music_s_f_x", alias = "music_sfx")]
     AudioProduction,
-    /// Music & SFX (alias)
-    MusicSFX,
     /// Image generation
     Photography,
-    /// Video generation
+    /// Video generation & direction (formerly also reported as `VideoDirection`)
+    #[serde(alias = "video_direction")]
     Videography,
-    /// Video direction (alias)
-    VideoDirection,
     /// Editing & montage
     Editing,
     /// Color grading
@@ -72,6 +90,36 @@ pub enum AgentCapability {
     Showrunning,
 }
 
+impl AgentCapability {
+    /// Normalize a reported capability name to its canonical variant.
+    ///
+    /// Accepts the serialized snake_case names, the Rust variant names and the
+    /// legacy aliases (`MusicSFX`, `VideoDirection`) so routing and capability
+    /// filters treat them as one.
+    pub fn canonical(name: &str) -> Option<Self> {
+        let normalized: String = name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        match normalized.as_str() {
+            "scriptwriting" => Some(Self::Scriptwriting),
+            "cinematography" => Some(Self::Cinematography),
+            "casting" => Some(Self::Casting),
+            "artdirection" => Some(Self::ArtDirection),
+            "voiceacting" => Some(Self::VoiceActing),
+            "audioproduction" | "musicsfx" => Some(Self::AudioProduction),
+            "photography" => Some(Self::Photography),
+            "videography" | "videodirection" => Some(Self::Videography),
+            "editing" => Some(Self::Editing),
+            "colorgrading" => Some(Self::ColorGrading),
+            "showrunning" => Some(Self::Showrunning),
+            _ => None,
+        }
+    }
+}
+
 /// Agent response
 #[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
 #[serde(rename_all = "camelCase")]
@@ -82,6 +130,9 @@ pub struct AgentResponse {
     pub content: String,
     /// Suggested actions (optional)
     pub actions: Vec<AgentAction>,
+    /// Vault tokens the response relies on
+    #[serde(default)]
+    pub citations: Vec<TokenReference>,
     /// Cost in credits (if cloud)
     pub cost: Option<f32>,
     /// Metadata (model used, tokens, etc.)
@@ -90,6 +141,7 @@ pub struct AgentResponse {
 
 /// Agent action (executable operation)
 pub use actions::AgentAction;
+pub use structured_output::TokenReference;
 
 /// Agent metadata
 #[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
@@ -98,6 +150,7 @@ pub struct AgentMetadata {
     /// Model used (e.g., "gemini-3-pro", "llama-4")
     pub model: String,
     /// Processing time in ms
+    #[specta(type = f64)]
     pub processing_time_ms: u64,
     /// Tokens used (if applicable)
     pub tokens: Option<TokenUsage>,
@@ -187,3 +240,39 @@ impl From<AgentError> for String {
 pub use workflow::{
     generate_workflow, parse_agent_request, GeneratedWorkflow, WorkflowRequest, WorkflowType,
 };
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_capability_names_deserialize_to_canonical() {
+        let audio: AgentCapability = serde_json::from_str("\"music_s_f_x\"").unwrap();
+        assert_eq!(audio, AgentCapability::AudioProduction);
+
+        let video: AgentCapability = serde_json::from_str("\"video_direction\"").unwrap();
+        assert_eq!(video, AgentCapability::Videography);
+
+        assert_eq!(
+            serde_json::to_string(&AgentCapability::AudioProduction).unwrap(),
+            "\"audio_production\""
+        );
+    }
+
+    #[test]
+    fn test_canonical_normalization() {
+        assert_eq!(
+            AgentCapability::canonical("MusicSFX"),
+            Some(AgentCapability::AudioProduction)
+        );
+        assert_eq!(
+            AgentCapability::canonical("video_direction"),
+            Some(AgentCapability::Videography)
+        );
+        assert_eq!(
+            AgentCapability::canonical("color_grading"),
+            Some(AgentCapability::ColorGrading)
+        );
+        assert_eq!(AgentCapability::canonical("juggling"), None);
+    }
+}
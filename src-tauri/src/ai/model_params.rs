@@ -0,0 +1,317 @@
+//! Model Params - Typed per-model generation parameters
+//!
+//! `WorkflowRequest` and the workflow nodes only carry the knobs every model
+//! shares (steps, size, seed). Each model also has its own: FLUX guidance,
+//! Kling motion strength, Veo aspect ratio and audio. `ModelParams` carries
+//! those with their real names and ranges, so the UI and agents can offer the
+//! right controls per model. Params are validated before being serialized into
+//! a node's `params_json`; omitted fields take the model's defaults.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// Output aspect ratio for video models
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Type)]
+pub enum AspectRatio {
+    #[default]
+    #[serde(rename = "16:9")]
+    Landscape,
+    #[serde(rename = "9:16")]
+    Portrait,
+}
+
+/// FLUX.2 image generation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
+#[serde(default)]
+pub struct FluxParams {
+    pub num_inference_steps: u32,
+    pub guidance_scale: f32,
+}
+
+impl Default for FluxParams {
+    fn default() -> Self {
+        Self {
+            num_inference_steps: 28,
+            guidance_scale: 3.5,
+        }
+    }
+}
+
+/// Veo 3.1 video generation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
+#[serde(default)]
+pub struct VeoParams {
+    /// 4, 6 or 8 seconds
+    pub duration_seconds: u32,
+    pub aspect_ratio: AspectRatio,
+    pub with_audio: bool,
+}
+
+impl Default for VeoParams {
+    fn default() -> Self {
+        Self {
+            duration_seconds: 8,
+            aspect_ratio: AspectRatio::Landscape,
+            with_audio: true,
+        }
+    }
+}
+
+/// Sora 2 video generation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
+#[serde(default)]
+pub struct SoraParams {
+    /// 4, 8 or 12 seconds
+    pub duration_seconds: u32,
+    pub aspect_ratio: AspectRatio,
+    pub with_audio: bool,
+}
+
+impl Default for SoraParams {
+    fn default() -> Self {
+        Self {
+            duration_seconds: 4,
+            aspect_ratio: AspectRatio::Landscape,
+            with_audio: true,
+        }
+    }
+}
+
+/// Kling video generation (v2.5 Turbo and v2.6)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
+#[serde(default)]
+pub struct KlingParams {
+    /// 5 or 10 seconds
+    pub duration_seconds: u32,
+    /// How far the video may move away from the prompt/start frame, 0.0-1.0
+    pub motion_strength: f32,
+    pub with_audio: bool,
+    pub negative_prompt: Option<String>,
+}
+
+impl Default for KlingParams {
+    fn default() -> Self {
+        Self {
+            duration_seconds: 5,
+            motion_strength: 0.5,
+            with_audio: true,
+            negative_prompt: None,
+        }
+    }
+}
+
+/// Beatoven music generation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
+#[serde(default)]
+pub struct BeatovenParams {
+    pub genre: String,
+    pub duration_seconds: f32,
+}
+
+impl Default for BeatovenParams {
+    fn default() -> Self {
+        Self {
+            genre: "cinematic".into(),
+            duration_seconds: 30.0,
+        }
+    }
+}
+
+/// ElevenLabs voice synthesis
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
+#[serde(default)]
+pub struct ElevenLabsParams {
+    /// v3, flash_v2.5 or turbo_v2.5
+    pub model: String,
+    pub stability: f32,
+    pub similarity_boost: f32,
+}
+
+impl Default for ElevenLabsParams {
+    fn default() -> Self {
+        Self {
+            model: "v3".into(),
+            stability: 0.5,
+            similarity_boost: 0.75,
+        }
+    }
+}
+
+/// Model-specific parameters, tagged with the model family
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
+#[serde(tag = "model", content = "params", rename_all = "snake_case")]
+pub enum ModelParams {
+    Flux(FluxParams),
+    Veo(VeoParams),
+    Sora(SoraParams),
+    Kling(KlingParams),
+    Beatoven(BeatovenParams),
+    ElevenLabs(ElevenLabsParams),
+}
+
+impl ModelParams {
+    /// Default params for a workflow node type, if it has model-specific ones
+    pub fn for_node_type(node_type: &str) -> Option<Self> {
+        Some(match node_type {
+            "FalFlux2" | "LocalFlux2Schnell" => Self::Flux(FluxParams::default()),
+            "FalVeo31" => Self::Veo(VeoParams::default()),
+            "FalSora2Pro" => Self::Sora(SoraParams::default()),
+            "FalKlingV26" | "FalKlingV25Turbo" => Self::Kling(KlingParams::default()),
+            "FalBeatovenMusic" => Self::Beatoven(BeatovenParams::default()),
+            "ElevenLabsTts" => Self::ElevenLabs(ElevenLabsParams::default()),
+            _ => return None,
+        })
+    }
+
+    /// Read a node's `params_json`, filling omitted fields with defaults
+    pub fn from_params_json(node_type: &str, params_json: &str) -> Result<Self, String> {
+        let defaults = Self::for_node_type(node_type)
+            .ok_or_else(|| format!("{} has no model-specific params", node_type))?;
+        let json = if params_json.trim().is_empty() {
+            "{}"
+        } else {
+            params_json
+        };
+        let parse_error = |e: serde_json::Error| format!("Invalid {} params: {}", node_type, e);
+
+        let params = match defaults {
+            Self::Flux(_) => Self::Flux(serde_json::from_str(json).map_err(parse_error)?),
+            Self::Veo(_) => Self::Veo(serde_json::from_str(json).map_err(parse_error)?),
+            Self::Sora(_) => Self::Sora(serde_json::from_str(json).map_err(parse_error)?),
+            Self::Kling(_) => Self::Kling(serde_json::from_str(json).map_err(parse_error)?),
+            Self::Beatoven(_) => Self::Beatoven(serde_json::from_str(json).map_err(parse_error)?),
+            Self::ElevenLabs(_) => {
+                Self::ElevenLabs(serde_json::from_str(json).map_err(parse_error)?)
+            }
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Check every parameter is within what the model accepts
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Flux(p) => {
+                check_range(
+                    "num_inference_steps",
+                    p.num_inference_steps as f32,
+                    1.0,
+                    50.0,
+                )?;
+                check_range("guidance_scale", p.guidance_scale, 0.0, 20.0)
+            }
+            Self::Veo(p) => check_one_of("duration_seconds", p.duration_seconds, &[4, 6, 8]),
+            Self::Sora(p) => check_one_of("duration_seconds", p.duration_seconds, &[4, 8, 12]),
+            Self::Kling(p) => {
+                check_one_of("duration_seconds", p.duration_seconds, &[5, 10])?;
+                check_range("motion_strength", p.motion_strength, 0.0, 1.0)
+            }
+            Self::Beatoven(p) => {
+                if p.genre.trim().is_empty() {
+                    return Err("genre cannot be empty".into());
+                }
+                check_range("duration_seconds", p.duration_seconds, 1.0, 150.0)
+            }
+            Self::ElevenLabs(p) => {
+                if !["v3", "flash_v2.5", "turbo_v2.5"].contains(&p.model.as_str()) {
+                    return Err(format!("Unknown ElevenLabs model: {}", p.model));
+                }
+                check_range("stability", p.stability, 0.0, 1.0)?;
+                check_range("similarity_boost", p.similarity_boost, 0.0, 1.0)
+            }
+        }
+    }
+
+    /// Validate and serialize into a node's `params_json`
+    pub fn to_params_json(&self) -> Result<String, String> {
+        self.validate()?;
+        let json = match self {
+            Self::Flux(p) => serde_json::to_string(p),
+            Self::Veo(p) => serde_json::to_string(p),
+            Self::Sora(p) => serde_json::to_string(p),
+            Self::Kling(p) => serde_json::to_string(p),
+            Self::Beatoven(p) => serde_json::to_string(p),
+            Self::ElevenLabs(p) => serde_json::to_string(p),
+        };
+        json.map_err(|e| e.to_string())
+    }
+}
+
+fn check_range(name: &str, value: f32, min: f32, max: f32) -> Result<(), String> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} must be between {} and {}, got {}",
+            name, min, max, value
+        ))
+    }
+}
+
+fn check_one_of(name: &str, value: u32, allowed: &[u32]) -> Result<(), String> {
+    if allowed.contains(&value) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} must be one of {:?}, got {}",
+            name, allowed, value
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kling_round_trip() {
+        let params = ModelParams::Kling(KlingParams {
+            duration_seconds: 10,
+            motion_strength: 0.8,
+            with_audio: false,
+            negative_prompt: Some("blur".into()),
+        });
+
+        let json = serde_json::to_string(&params).unwrap();
+        assert!(json.contains(r#""model":"kling""#));
+        assert_eq!(serde_json::from_str::<ModelParams>(&json).unwrap(), params);
+
+        let params_json = params.to_params_json().unwrap();
+        assert_eq!(
+            ModelParams::from_params_json("FalKlingV26", &params_json).unwrap(),
+            params
+        );
+    }
+
+    #[test]
+    fn test_veo_round_trip_with_defaults() {
+        let params =
+            ModelParams::from_params_json("FalVeo31", r#"{"aspect_ratio": "9:16"}"#).unwrap();
+        assert_eq!(
+            params,
+            ModelParams::Veo(VeoParams {
+                aspect_ratio: AspectRatio::Portrait,
+                ..VeoParams::default()
+            })
+        );
+
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(serde_json::from_str::<ModelParams>(&json).unwrap(), params);
+        assert!(params
+            .to_params_json()
+            .unwrap()
+            .contains(r#""aspect_ratio":"9:16""#));
+    }
+
+    #[test]
+    fn test_invalid_params_rejected() {
+        assert!(ModelParams::from_params_json("FalVeo31", r#"{"duration_seconds": 5}"#).is_err());
+        assert!(ModelParams::Flux(FluxParams {
+            num_inference_steps: 0,
+            guidance_scale: 3.5,
+        })
+        .to_params_json()
+        .is_err());
+        assert!(ModelParams::from_params_json("FalBriaRemoveBg", "{}").is_err());
+    }
+}
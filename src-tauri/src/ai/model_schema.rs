@@ -0,0 +1,245 @@
+//! Model Schemas - Validate generation parameters before dispatch
+//!
+//! Models in the Model Matrix may carry a JSON Schema for their request
+//! parameters in `api_schema`. Requests are checked against it locally so a
+//! bad parameter fails immediately instead of after a billed round-trip.
+//! Only the subset of JSON Schema the bundled schemas use is supported:
+//! `type`, `properties`, `required`, `enum`, `minimum`/`maximum`,
+//! `minLength`/`maxLength`, `multipleOf` and `additionalProperties: false`.
+
+use serde_json::Value;
+
+use crate::ai::models::get_all_models;
+
+/// Parameters accepted by FLUX image models
+pub const FLUX_IMAGE_SCHEMA: &str = r#"{
+  "type": "object",
+  "required": ["prompt"],
+  "properties": {
+    "prompt": { "type": "string", "minLength": 1, "maxLength": 4000 },
+    "width": { "type": "integer", "minimum": 256, "maximum": 2048, "multipleOf": 16 },
+    "height": { "type": "integer", "minimum": 256, "maximum": 2048, "multipleOf": 16 },
+    "steps": { "type": "integer", "minimum": 1, "maximum": 50 },
+    "guidance": { "type": "number", "minimum": 1.5, "maximum": 5 },
+    "seed": { "type": "integer", "minimum": 0 },
+    "safety_tolerance": { "type": "integer", "minimum": 0, "maximum": 6 },
+    "output_format": { "type": "string", "enum": ["jpeg", "png"] }
+  }
+}"#;
+
+/// Parameters accepted by Veo video models
+pub const VEO_VIDEO_SCHEMA: &str = r#"{
+  "type": "object",
+  "required": ["prompt"],
+  "properties": {
+    "prompt": { "type": "string", "minLength": 1, "maxLength": 4000 },
+    "negative_prompt": { "type": "string" },
+    "duration_seconds": { "type": "integer", "enum": [4, 6, 8] },
+    "aspect_ratio": { "type": "string", "enum": ["16:9", "9:16"] },
+    "resolution": { "type": "string", "enum": ["720p", "1080p"] },
+    "generate_audio": { "type": "boolean" },
+    "image": { "type": "string", "minLength": 1 },
+    "seed": { "type": "integer", "minimum": 0 }
+  }
+}"#;
+
+/// Check `params` against the JSON Schema of `model_id`.
+///
+/// Models without a schema (or with a non-schema hint such as
+/// `"prompt_caching"`) accept any parameters.
+pub fn validate_request_for_model(model_id: &str, params: &Value) -> Result<(), String> {
+    let Some(schema) = get_all_models()
+        .into_iter()
+        .find(|m| m.id == model_id)
+        .and_then(|m| m.api_schema)
+        .filter(|s| s.trim_start().starts_with('{'))
+    else {
+        return Ok(());
+    };
+
+    let schema: Value = serde_json::from_str(&schema)
+        .map_err(|e| format!("Invalid schema for {}: {}", model_id, e))?;
+    validate(&schema, params, "params").map_err(|e| format!("{}: {}", model_id, e))
+}
+
+/// Validate `value` against `schema`; `path` names the value in errors
+pub fn validate(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    if let Some(expected) = schema["type"].as_str() {
+        if !has_type(value, expected) {
+            return Err(format!(
+                "{} must be of type {}, got {}",
+                path,
+                expected,
+                type_name(value)
+            ));
+        }
+    }
+
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.iter().any(|a| same_value(a, value)) {
+            let allowed: Vec<_> = allowed.iter().map(Value::to_string).collect();
+            return Err(format!(
+                "{} must be one of {}, got {}",
+                path,
+                allowed.join(", "),
+                value
+            ));
+        }
+    }
+
+    if let Some(n) = value.as_f64() {
+        if let Some(min) = schema["minimum"].as_f64().filter(|min| n < *min) {
+            return Err(format!("{} must be at least {}, got {}", path, min, value));
+        }
+        if let Some(max) = schema["maximum"].as_f64().filter(|max| n > *max) {
+            return Err(format!("{} must be at most {}, got {}", path, max, value));
+        }
+        if let Some(step) = schema["multipleOf"].as_f64().filter(|s| *s > 0.0) {
+            if (n / step).fract() != 0.0 {
+                return Err(format!(
+                    "{} must be a multiple of {}, got {}",
+                    path, step, value
+                ));
+            }
+        }
+    }
+
+    if let Some(s) = value.as_str() {
+        let len = s.chars().count() as u64;
+        if let Some(min) = schema["minLength"].as_u64().filter(|min| len < *min) {
+            return Err(format!("{} must be at least {} characters", path, min));
+        }
+        if let Some(max) = schema["maxLength"].as_u64().filter(|max| len > *max) {
+            return Err(format!("{} must be at most {} characters", path, max));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema["required"].as_array() {
+            for field in required.iter().filter_map(Value::as_str) {
+                if object.get(field).is_none_or(Value::is_null) {
+                    return Err(format!("{}.{} is required", path, field));
+                }
+            }
+        }
+
+        let properties = schema["properties"].as_object();
+        for (key, field) in object {
+            match properties.and_then(|p| p.get(key)) {
+                // Unset optional fields are fine
+                Some(_) if field.is_null() => {}
+                Some(field_schema) => validate(field_schema, field, &format!("{}.{}", path, key))?,
+                None if schema["additionalProperties"] == Value::Bool(false) => {
+                    return Err(format!("{}.{} is not a supported parameter", path, key));
+                }
+                None => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "number" => value.is_number(),
+        // 8.0 is an integer in JSON Schema
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// JSON equality that treats 8 and 8.0 as the same number
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => x == y,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_flux_params() {
+        assert!(validate_request_for_model(
+            "flux-pro-2.0",
+            &json!({"prompt": "A rain-soaked alley", "width": 1344, "height": 768, "steps": 28})
+        )
+        .is_ok());
+
+        let err = validate_request_for_model(
+            "flux-pro-2.0",
+            &json!({"prompt": "A rain-soaked alley", "width": 1000, "height": 768}),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "flux-pro-2.0: params.width must be a multiple of 16, got 1000"
+        );
+
+        let err =
+            validate_request_for_model("flux-pro-1.1-ultra", &json!({"steps": 10})).unwrap_err();
+        assert!(err.contains("params.prompt is required"));
+
+        let err = validate_request_for_model(
+            "flux-pro-2.0",
+            &json!({"prompt": "x", "output_format": "gif"}),
+        )
+        .unwrap_err();
+        assert!(err.contains("must be one of \"jpeg\", \"png\""));
+    }
+
+    #[test]
+    fn test_veo_params() {
+        assert!(validate_request_for_model(
+            "veo-3.1-vid",
+            &json!({"prompt": "A chase across rooftops", "duration_seconds": 8.0, "image": null})
+        )
+        .is_ok());
+
+        let err = validate_request_for_model(
+            "veo-3.1-vid",
+            &json!({"prompt": "A chase", "duration_seconds": 12}),
+        )
+        .unwrap_err();
+        assert!(err.contains("params.duration_seconds must be one of 4, 6, 8, got 12"));
+
+        let err = validate_request_for_model(
+            "veo-3.1-vid",
+            &json!({"prompt": "A chase", "generate_audio": "yes"}),
+        )
+        .unwrap_err();
+        assert!(err.contains("must be of type boolean, got string"));
+    }
+
+    #[test]
+    fn test_models_without_schema_accept_anything() {
+        // Hint strings are not schemas
+        assert!(validate_request_for_model("claude-4.5-sonnet", &json!({"x": 1})).is_ok());
+        assert!(validate_request_for_model("not-a-model", &json!(null)).is_ok());
+
+        let strict = json!({"type": "object", "additionalProperties": false, "properties": {}});
+        assert!(validate(&strict, &json!({"extra": 1}), "params")
+            .unwrap_err()
+            .contains("params.extra is not a supported parameter"));
+    }
+}
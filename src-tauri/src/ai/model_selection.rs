@@ -1,7 +1,20 @@
-//! Model selection types for user control
+//! Model selection types for user control, budget-aware picking of a model
+//! for a capability, and fallback chains used when a provider fails
 
+use crate::ai::context::UserPreferences;
+use crate::ai::cost::{estimate_image_cost, estimate_llm_cost, estimate_video_cost};
 use crate::ai::llm_client::LLMProvider;
+use crate::ai::models::{
+    get_models_by_capability, ModelCapability, ModelDefinition, ModelLocation, SpeedTier,
+};
+use crate::ai::AgentError;
+use crate::errors::LLMError;
+use crate::settings::{load_json, save_json};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::RwLock;
+use std::time::Duration;
 
 /// User's model selection for an agent
 #[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
@@ -31,3 +44,605 @@ impl Default for ModelSelection {
         }
     }
 }
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// BUDGET-AWARE SELECTION
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Size of the request a model is priced on when choosing between models
+const TYPICAL_PROMPT_TOKENS: u32 = 2_000;
+const TYPICAL_COMPLETION_TOKENS: u32 = 1_000;
+const TYPICAL_VIDEO_SECONDS: f32 = 5.0;
+const TYPICAL_IMAGE_SIZE: u32 = 1024;
+
+/// Estimated credits for one typical request to `model`
+pub fn typical_request_cost(model: &ModelDefinition) -> f32 {
+    match model.pricing.unit_type.as_str() {
+        "free" => 0.0,
+        "second" => estimate_video_cost(&model.id, TYPICAL_VIDEO_SECONDS),
+        "image" => estimate_image_cost(&model.id, TYPICAL_IMAGE_SIZE, TYPICAL_IMAGE_SIZE),
+        _ => estimate_llm_cost(&model.id, TYPICAL_PROMPT_TOKENS, TYPICAL_COMPLETION_TOKENS),
+    }
+}
+
+/// Higher is better: quality tier first, local models ahead when preferred
+fn score(model: &ModelDefinition, prefer_local: bool) -> u32 {
+    let tier = match model.speed_tier {
+        SpeedTier::Quality => 3,
+        SpeedTier::Standard => 2,
+        SpeedTier::Fast => 1,
+    };
+    let local = prefer_local && model.location == ModelLocation::Local;
+    tier + if local { 10 } else { 0 }
+}
+
+/// Whether a `preferred_models` entry names `model` ("flux-pro-2.0", or
+/// "gemini:auto" for any model of a provider)
+fn matches_preference(preference: &str, model: &ModelDefinition) -> bool {
+    match preference.split_once(':') {
+        Some((provider, "auto")) => {
+            let provider = match provider.to_lowercase().as_str() {
+                "gemini" | "vertex" => "google".to_string(),
+                other => other.to_string(),
+            };
+            model.provider.eq_ignore_ascii_case(&provider)
+        }
+        _ => model.id.eq_ignore_ascii_case(preference),
+    }
+}
+
+/// Pick from `candidates` within the user's budget (`max_credits_per_request`
+/// of 0 means no cap)
+///
+/// The first `preferred_models` entry that fits wins; otherwise the best
+/// scoring model, the cheaper one on ties.
+pub fn select_from(
+    candidates: Vec<ModelDefinition>,
+    prefs: &UserPreferences,
+) -> Option<ModelDefinition> {
+    let budget = prefs.max_credits_per_request;
+    let mut affordable: Vec<(ModelDefinition, f32)> = candidates
+        .into_iter()
+        .map(|m| {
+            let cost = typical_request_cost(&m);
+            (m, cost)
+        })
+        .filter(|(_, cost)| budget <= 0.0 || *cost <= budget)
+        .collect();
+
+    affordable.sort_by(|(a, a_cost), (b, b_cost)| {
+        score(b, prefs.prefer_local)
+            .cmp(&score(a, prefs.prefer_local))
+            .then(a_cost.total_cmp(b_cost))
+    });
+
+    let preferred = prefs.preferred_models.iter().find_map(|preference| {
+        affordable
+            .iter()
+            .position(|(m, _)| matches_preference(preference, m))
+    });
+    let index = preferred.unwrap_or(0);
+    (index < affordable.len()).then(|| affordable.swap_remove(index).0)
+}
+
+/// Best model for `capability` that fits the user's budget and preferences
+pub fn select_model(
+    capability: ModelCapability,
+    prefs: &UserPreferences,
+) -> Option<ModelDefinition> {
+    select_from(get_models_by_capability(capability), prefs)
+}
+
+/// `select_model`, or `InsufficientCredits` with the cheapest option's cost
+/// when nothing fits the budget
+pub fn require_model(
+    capability: ModelCapability,
+    prefs: &UserPreferences,
+) -> Result<ModelDefinition, AgentError> {
+    let candidates = get_models_by_capability(capability.clone());
+    if let Some(model) = select_from(candidates.clone(), prefs) {
+        return Ok(model);
+    }
+
+    let needed = candidates
+        .iter()
+        .map(typical_request_cost)
+        .min_by(f32::total_cmp)
+        .ok_or_else(|| AgentError::ModelUnavailable(format!("No model for {:?}", capability)))?;
+    Err(AgentError::InsufficientCredits {
+        needed,
+        available: prefs.max_credits_per_request,
+    })
+}
+
+/// Model for a request that named `requested` (`None` or "auto" to choose)
+///
+/// With no model named, `default` is kept if it fits the budget and the user
+/// has no preferred models; "auto", or a default over budget, goes through
+/// [`require_model`].
+pub fn resolve_model(
+    requested: Option<&str>,
+    default: &str,
+    capability: ModelCapability,
+    prefs: &UserPreferences,
+) -> Result<String, AgentError> {
+    match requested {
+        Some(model) if !model.eq_ignore_ascii_case("auto") => return Ok(model.to_string()),
+        None if prefs.preferred_models.is_empty() => {
+            let budget = prefs.max_credits_per_request;
+            if budget <= 0.0 || typical_cost_of(default, &capability) <= budget {
+                return Ok(default.to_string());
+            }
+        }
+        _ => {}
+    }
+    require_model(capability, prefs).map(|model| model.id)
+}
+
+/// `typical_request_cost` for a model id that may not be in the registry
+fn typical_cost_of(model_id: &str, capability: &ModelCapability) -> f32 {
+    match capability {
+        ModelCapability::TextToVideo | ModelCapability::ImageToVideo => {
+            estimate_video_cost(model_id, TYPICAL_VIDEO_SECONDS)
+        }
+        ModelCapability::TextToImage | ModelCapability::ImageToImage => {
+            estimate_image_cost(model_id, TYPICAL_IMAGE_SIZE, TYPICAL_IMAGE_SIZE)
+        }
+        _ => estimate_llm_cost(model_id, TYPICAL_PROMPT_TOKENS, TYPICAL_COMPLETION_TOKENS),
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// FALLBACK CHAINS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Retries per model before moving down the chain
+///
+/// Chains run on a client that does not retry by itself, so this is the only
+/// retry layer between a failing model and the next one.
+pub const RETRIES_PER_MODEL: u32 = 1;
+
+/// Delay before retrying the same model (multiplied by the attempt number)
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// One model in a fallback chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct FallbackStep {
+    pub provider: LLMProvider,
+    /// Empty means the runtime's default (used for local models)
+    pub model: String,
+}
+
+impl FallbackStep {
+    pub fn new(provider: LLMProvider, model: &str) -> Self {
+        Self {
+            provider,
+            model: model.to_string(),
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        matches!(self.provider, LLMProvider::Ollama | LLMProvider::LlamaStack)
+    }
+}
+
+/// Models to try, in order, when the chosen model's provider fails
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct FallbackChain {
+    pub capability: ModelCapability,
+    pub steps: Vec<FallbackStep>,
+}
+
+/// Fallback chains for every capability that has one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct FallbackConfig {
+    pub chains: Vec<FallbackChain>,
+}
+
+impl Default for FallbackConfig {
+    fn default() -> Self {
+        Self {
+            chains: vec![FallbackChain {
+                capability: ModelCapability::TextGeneration,
+                steps: vec![
+                    FallbackStep::new(LLMProvider::Gemini, "gemini-3-pro"),
+                    FallbackStep::new(LLMProvider::Anthropic, "claude-sonnet-4-5"),
+                    FallbackStep::new(LLMProvider::Ollama, ""),
+                ],
+            }],
+        }
+    }
+}
+
+impl FallbackConfig {
+    /// Models to try for `capability`, starting with `primary`
+    ///
+    /// With `prefer_local`, cloud models are never substituted in, so a
+    /// local-only user is not billed by a fallback.
+    pub fn chain_for(
+        &self,
+        capability: &ModelCapability,
+        primary: FallbackStep,
+        prefer_local: bool,
+    ) -> Vec<FallbackStep> {
+        let fallbacks = self
+            .chains
+            .iter()
+            .find(|c| &c.capability == capability)
+            .map(|c| c.steps.as_slice())
+            .unwrap_or_default();
+
+        let mut chain = vec![primary];
+        for step in fallbacks {
+            if (!prefer_local || step.is_local()) && !chain.contains(step) {
+                chain.push(step.clone());
+            }
+        }
+        chain
+    }
+}
+
+/// Where the fallback chains are persisted
+const FALLBACK_FILE: &str = "fallback_chains.json";
+
+static FALLBACK_CONFIG: Lazy<RwLock<FallbackConfig>> =
+    Lazy::new(|| RwLock::new(load_json(FALLBACK_FILE)));
+
+/// Current fallback chains
+pub fn fallback_config() -> FallbackConfig {
+    FALLBACK_CONFIG
+        .read()
+        .map(|c| c.clone())
+        .unwrap_or_default()
+}
+
+/// Replace the fallback chains (persisted across restarts)
+pub fn set_fallback_config(config: FallbackConfig) -> Result<(), String> {
+    let mut current = FALLBACK_CONFIG.write().map_err(|e| e.to_string())?;
+    save_json(FALLBACK_FILE, &config)?;
+    *current = config;
+    Ok(())
+}
+
+/// A model substituted for the requested one after provider failures
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct ModelSubstitution {
+    pub requested: FallbackStep,
+    pub used: FallbackStep,
+    /// Last error from each model that was skipped
+    pub errors: Vec<String>,
+}
+
+/// Whether an LLM error is worth trying the next model in the chain for
+///
+/// Retryable errors are, once retries run out, and so are ones tied to the
+/// provider (missing or rejected API key, unknown model). Malformed requests
+/// and unreadable responses would fail on any model.
+pub fn should_fall_back(error: &LLMError) -> bool {
+    error.is_retryable()
+        || matches!(
+            error,
+            LLMError::MissingApiKey { .. }
+                | LLMError::AuthenticationFailed { .. }
+                | LLMError::ModelNotFound { .. }
+        )
+}
+
+/// Run `call` against each model in `chain` until one succeeds
+///
+/// Each model is retried `retries` times on retryable errors before the next
+/// one is tried; see [`should_fall_back`] for errors that fail immediately.
+/// Returns the value and, if a fallback was used, the substitution.
+pub async fn run_with_fallback<T, F, Fut>(
+    chain: &[FallbackStep],
+    retries: u32,
+    mut call: F,
+) -> Result<(T, Option<ModelSubstitution>), String>
+where
+    F: FnMut(FallbackStep) -> Fut,
+    Fut: Future<Output = Result<T, LLMError>>,
+{
+    let mut errors = Vec::new();
+
+    for step in chain {
+        let mut attempt = 0;
+        let error = loop {
+            match call(step.clone()).await {
+                Ok(value) => {
+                    let substitution = (!errors.is_empty()).then(|| ModelSubstitution {
+                        requested: chain[0].clone(),
+                        used: step.clone(),
+                        errors,
+                    });
+                    return Ok((value, substitution));
+                }
+                Err(e) if !should_fall_back(&e) => return Err(e.to_string()),
+                Err(e) if !e.is_retryable() || attempt >= retries => break e,
+                Err(e) => {
+                    attempt += 1;
+                    tracing::debug!("Retrying {} ({}): {}", step.model, attempt, e);
+                    tokio::time::sleep(RETRY_BACKOFF * attempt).await;
+                }
+            }
+        };
+
+        tracing::warn!(
+            "{:?} model {} failed, trying next fallback: {}",
+            step.provider,
+            step.model,
+            error
+        );
+        errors.push(error.to_string());
+    }
+
+    Err(errors
+        .pop()
+        .unwrap_or_else(|| "No models in fallback chain".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_falls_back_when_primary_provider_fails() {
+        let chain = FallbackConfig::default().chain_for(
+            &ModelCapability::TextGeneration,
+            FallbackStep::new(LLMProvider::Gemini, "gemini-3-pro"),
+            false,
+        );
+        let calls = Mutex::new(Vec::new());
+
+        let (reply, substitution) = run_with_fallback(&chain, 1, |step| {
+            calls.lock().unwrap().push(step.model.clone());
+            async move {
+                match step.provider {
+                    LLMProvider::Gemini => Err(provider_error(503)),
+                    _ => Ok(format!("reply from {}", step.model)),
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(reply, "reply from claude-sonnet-4-5");
+        // The primary is retried once before falling back
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["gemini-3-pro", "gemini-3-pro", "claude-sonnet-4-5"]
+        );
+
+        let substitution = substitution.unwrap();
+        assert_eq!(substitution.requested.model, "gemini-3-pro");
+        assert_eq!(substitution.used.provider, LLMProvider::Anthropic);
+        assert_eq!(substitution.errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_error_does_not_fall_back() {
+        let chain = FallbackConfig::default().chain_for(
+            &ModelCapability::TextGeneration,
+            FallbackStep::new(LLMProvider::OpenAI, "gpt-5-mini"),
+            false,
+        );
+
+        let calls = Mutex::new(0);
+        let result: Result<(String, _), _> = run_with_fallback(&chain, 1, |_| {
+            *calls.lock().unwrap() += 1;
+            async { Err(provider_error(400)) }
+        })
+        .await;
+        assert!(result.unwrap_err().contains("400"));
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_missing_key_falls_back_without_retrying() {
+        let chain = FallbackConfig::default().chain_for(
+            &ModelCapability::TextGeneration,
+            FallbackStep::new(LLMProvider::Gemini, "gemini-3-pro"),
+            false,
+        );
+        let calls = Mutex::new(Vec::new());
+
+        let (_, substitution) = run_with_fallback(&chain, 2, |step| {
+            calls.lock().unwrap().push(step.model.clone());
+            async move {
+                match step.provider {
+                    LLMProvider::Gemini => Err(LLMError::MissingApiKey {
+                        provider: "Gemini".into(),
+                        env_var: "GOOGLE_API_KEY".into(),
+                    }),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["gemini-3-pro", "claude-sonnet-4-5"]
+        );
+        assert!(substitution.unwrap().errors[0].contains("GOOGLE_API_KEY"));
+    }
+
+    fn provider_error(status_code: u16) -> LLMError {
+        LLMError::ProviderError {
+            provider: "Gemini".into(),
+            status_code,
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_should_fall_back_for_each_error() {
+        let network = reqwest::Client::new().get("not a url").build().unwrap_err();
+        let cases = [
+            (
+                LLMError::MissingApiKey {
+                    provider: "OpenAI".into(),
+                    env_var: "OPENAI_API_KEY".into(),
+                },
+                false,
+                true,
+            ),
+            (
+                LLMError::RateLimited {
+                    provider: "OpenAI".into(),
+                    retry_after_secs: 1,
+                },
+                true,
+                true,
+            ),
+            (
+                LLMError::AuthenticationFailed {
+                    provider: "OpenAI".into(),
+                    message: "invalid key".into(),
+                },
+                false,
+                true,
+            ),
+            (LLMError::NetworkError(network), true, true),
+            (
+                LLMError::InvalidRequest {
+                    provider: "Ollama".into(),
+                    message: "URL image".into(),
+                },
+                false,
+                false,
+            ),
+            (
+                LLMError::InvalidResponse {
+                    provider: "OpenAI".into(),
+                    message: "truncated".into(),
+                },
+                false,
+                false,
+            ),
+            (
+                LLMError::ModelNotFound {
+                    model_id: "gpt-9".into(),
+                },
+                false,
+                true,
+            ),
+            (LLMError::Timeout { timeout_secs: 30 }, true, true),
+            (provider_error(503), true, true),
+            (provider_error(400), false, false),
+        ];
+
+        for (error, retryable, falls_back) in cases {
+            assert_eq!(error.is_retryable(), retryable, "{}", error);
+            assert_eq!(should_fall_back(&error), falls_back, "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_prefer_local_skips_cloud_fallbacks() {
+        let chain = FallbackConfig::default().chain_for(
+            &ModelCapability::TextGeneration,
+            FallbackStep::new(LLMProvider::Gemini, "gemini-3-pro"),
+            true,
+        );
+        assert_eq!(
+            chain,
+            [
+                FallbackStep::new(LLMProvider::Gemini, "gemini-3-pro"),
+                FallbackStep::new(LLMProvider::Ollama, ""),
+            ]
+        );
+    }
+
+    fn prefs(max_credits: f32, prefer_local: bool, preferred: &[&str]) -> UserPreferences {
+        UserPreferences {
+            prefer_local,
+            max_credits_per_request: max_credits,
+            preferred_models: preferred.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_select_model_by_quality_within_budget() {
+        // No cap: the best quality text model, cheapest among equals
+        let model = select_model(ModelCapability::TextGeneration, &prefs(0.0, false, &[])).unwrap();
+        assert_eq!(model.speed_tier, SpeedTier::Quality);
+        assert!(get_models_by_capability(ModelCapability::TextGeneration)
+            .iter()
+            .filter(|m| m.speed_tier == SpeedTier::Quality)
+            .all(|m| typical_request_cost(m) >= typical_request_cost(&model)));
+
+        // A tight budget drops the expensive models
+        let budget = typical_request_cost(&model) / 2.0;
+        assert!(budget > 0.0);
+        let cheap =
+            select_model(ModelCapability::TextGeneration, &prefs(budget, false, &[])).unwrap();
+        assert!(typical_request_cost(&cheap) <= budget);
+    }
+
+    #[test]
+    fn test_prefer_local_and_preferred_models() {
+        let local = select_model(ModelCapability::TextGeneration, &prefs(0.0, true, &[])).unwrap();
+        assert_eq!(local.location, ModelLocation::Local);
+
+        let preferred = select_model(
+            ModelCapability::TextGeneration,
+            &prefs(0.0, true, &["not-a-model", "anthropic:auto"]),
+        )
+        .unwrap();
+        assert_eq!(preferred.provider, "anthropic");
+
+        // A preferred model over budget is skipped
+        let opus = select_model(
+            ModelCapability::TextGeneration,
+            &prefs(0.01, false, &["claude-4.5-opus"]),
+        );
+        assert!(opus.is_none_or(|m| m.id != "claude-4.5-opus"));
+    }
+
+    #[test]
+    fn test_resolve_model_keeps_named_and_affordable_defaults() {
+        let tiny = prefs(0.0001, false, &[]);
+        let capability = || ModelCapability::TextToVideo;
+
+        // A named model is the caller's choice; the credit cap confirms it later
+        assert_eq!(
+            resolve_model(Some("veo-3.1"), "kling-v2.6", capability(), &tiny).unwrap(),
+            "veo-3.1"
+        );
+        assert_eq!(
+            resolve_model(None, "veo-3.1", capability(), &prefs(0.0, false, &[])).unwrap(),
+            "veo-3.1"
+        );
+        assert!(matches!(
+            resolve_model(None, "veo-3.1", capability(), &tiny),
+            Err(AgentError::InsufficientCredits { .. })
+        ));
+
+        let auto = resolve_model(
+            Some("auto"),
+            "veo-3.1",
+            capability(),
+            &prefs(0.0, false, &["kling:auto"]),
+        )
+        .unwrap();
+        assert!(auto.starts_with("kling"));
+    }
+
+    #[test]
+    fn test_nothing_fits_the_budget() {
+        let tiny = prefs(0.0001, false, &[]);
+        assert!(select_model(ModelCapability::TextToVideo, &tiny).is_none());
+        match require_model(ModelCapability::TextToVideo, &tiny) {
+            Err(AgentError::InsufficientCredits { needed, available }) => {
+                assert!(needed > available);
+                assert_eq!(available, 0.0001);
+            }
+            other => panic!(
+                "expected InsufficientCredits, got {:?}",
+                other.map(|m| m.id)
+            ),
+        }
+    }
+}
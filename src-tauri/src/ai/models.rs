@@ -6,6 +6,8 @@
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+use crate::ai::model_schema::{FLUX_IMAGE_SCHEMA, VEO_VIDEO_SCHEMA};
+
 /// Where the model runs
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
 pub enum ModelLocation {
@@ -155,7 +157,7 @@ pub fn get_all_models() -> Vec<ModelDefinition> {
             is_new: true,
             speed_tier: Quality,
             local_download_id: None,
-            api_schema: None,
+            api_schema: Some(VEO_VIDEO_SCHEMA.into()),
         },
         ModelDefinition {
             id: "imagen-3-pro".into(),
@@ -347,7 +349,7 @@ pub fn get_all_models() -> Vec<ModelDefinition> {
             is_new: true,
             speed_tier: Quality,
             local_download_id: None,
-            api_schema: None,
+            api_schema: Some(FLUX_IMAGE_SCHEMA.into()),
         },
         ModelDefinition {
             id: "flux-pro-2.0".into(),
@@ -367,7 +369,7 @@ pub fn get_all_models() -> Vec<ModelDefinition> {
             is_new: true,
             speed_tier: Quality,
             local_download_id: None,
-            api_schema: None,
+            api_schema: Some(FLUX_IMAGE_SCHEMA.into()),
         },
         ModelDefinition {
             id: "flux-fill".into(),
@@ -0,0 +1,264 @@
+//! Project Memory - The Showrunner's "Bible"
+//!
+//! Established facts about a project ("the film is set in 1920s Chicago") are
+//! stored in the Vault as key/value entries, so they survive across sessions.
+//! The Showrunner loads them on every call and they travel in `AgentContext`,
+//! so any agent can read them. Memory is capped to keep prompts bounded.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::ai::context::AgentContext;
+use crate::vault;
+
+/// SurrealDB table holding one memory record per project
+const MEMORY_TABLE: &str = "project_memory";
+
+/// Maximum facts kept per project; the least recently updated are dropped first
+pub const MAX_ENTRIES: usize = 50;
+
+/// Maximum length of a key in characters
+pub const MAX_KEY_CHARS: usize = 80;
+
+/// Maximum length of a value in characters
+pub const MAX_VALUE_CHARS: usize = 500;
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// One established fact
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct MemoryEntry {
+    pub key: String,
+    pub value: String,
+    /// RFC 3339 timestamp of the last change
+    pub updated_at: String,
+}
+
+/// All facts for a project, oldest update first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct ProjectMemory {
+    pub entries: Vec<MemoryEntry>,
+}
+
+impl ProjectMemory {
+    /// Set `key` to `value`, replacing any entry with the same key (case-insensitive).
+    ///
+    /// Returns the keys evicted to stay under [`MAX_ENTRIES`].
+    pub fn remember(&mut self, key: &str, value: &str) -> Result<Vec<String>, String> {
+        let key = key.trim();
+        let value = value.trim();
+
+        if key.is_empty() {
+            return Err("Memory key cannot be empty".into());
+        }
+        if value.is_empty() {
+            return Err(format!("Memory value for '{}' cannot be empty", key));
+        }
+        if key.chars().count() > MAX_KEY_CHARS {
+            return Err(format!(
+                "Memory key is longer than {} characters",
+                MAX_KEY_CHARS
+            ));
+        }
+        if value.chars().count() > MAX_VALUE_CHARS {
+            return Err(format!(
+                "Memory value for '{}' is longer than {} characters",
+                key, MAX_VALUE_CHARS
+            ));
+        }
+
+        self.entries.retain(|e| !e.key.eq_ignore_ascii_case(key));
+        self.entries.push(MemoryEntry {
+            key: key.to_string(),
+            value: value.to_string(),
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        });
+
+        let overflow = self.entries.len().saturating_sub(MAX_ENTRIES);
+        Ok(self.entries.drain(..overflow).map(|e| e.key).collect())
+    }
+
+    /// Remove `key`; returns false if it wasn't remembered
+    pub fn forget(&mut self, key: &str) -> bool {
+        let before = self.entries.len();
+        self.entries
+            .retain(|e| !e.key.eq_ignore_ascii_case(key.trim()));
+        self.entries.len() != before
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|e| e.key.eq_ignore_ascii_case(key.trim()))
+            .map(|e| e.value.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Markdown section for agent prompts (empty when nothing is remembered)
+    pub fn to_prompt_section(&self) -> String {
+        if self.entries.is_empty() {
+            return String::new();
+        }
+
+        let facts: Vec<_> = self
+            .entries
+            .iter()
+            .map(|e| format!("- {}: {}", e.key, e.value))
+            .collect();
+        format!("## Project Bible (established facts)\n{}", facts.join("\n"))
+    }
+}
+
+/// Stored record (the memory plus its owner)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MemoryRecord {
+    project_id: String,
+    entries: Vec<MemoryEntry>,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// STORE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// All facts remembered for `project_id`
+pub async fn recall(project_id: &str) -> Result<ProjectMemory, String> {
+    let db = vault::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    let mut result = db
+        .query("SELECT project_id, entries FROM type::thing($table, $pid)")
+        .bind(("table", MEMORY_TABLE))
+        .bind(("pid", project_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let record: Option<MemoryRecord> = result.take(0).map_err(|e| e.to_string())?;
+    Ok(ProjectMemory {
+        entries: record.map(|r| r.entries).unwrap_or_default(),
+    })
+}
+
+/// Replace the memory for `project_id`
+pub async fn save(project_id: &str, memory: &ProjectMemory) -> Result<(), String> {
+    let db = vault::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    db.query("UPSERT type::thing($table, $pid) CONTENT $content")
+        .bind(("table", MEMORY_TABLE))
+        .bind(("pid", project_id.to_string()))
+        .bind((
+            "content",
+            MemoryRecord {
+                project_id: project_id.to_string(),
+                entries: memory.entries.clone(),
+            },
+        ))
+        .await
+        .map_err(|e| e.to_string())?
+        .check()
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Remember a fact for `project_id` and return the updated memory
+pub async fn remember(project_id: &str, key: &str, value: &str) -> Result<ProjectMemory, String> {
+    let mut memory = recall(project_id).await?;
+    let evicted = memory.remember(key, value)?;
+    if !evicted.is_empty() {
+        tracing::info!(
+            "Project memory for {} is full; dropped {:?}",
+            project_id,
+            evicted
+        );
+    }
+
+    save(project_id, &memory).await?;
+    Ok(memory)
+}
+
+/// Forget a fact for `project_id` and return the updated memory
+pub async fn forget(project_id: &str, key: &str) -> Result<ProjectMemory, String> {
+    let mut memory = recall(project_id).await?;
+    if memory.forget(key) {
+        save(project_id, &memory).await?;
+    }
+    Ok(memory)
+}
+
+/// Load the project's memory into `context` unless the caller already supplied it.
+///
+/// Failures are logged; a missing memory must never block a chat.
+pub async fn attach(context: &mut AgentContext) {
+    if context.project_memory.is_some() {
+        return;
+    }
+    let Some(project_id) = context.project_id.clone() else {
+        return;
+    };
+
+    match recall(&project_id).await {
+        Ok(memory) => context.project_memory = Some(memory),
+        Err(e) => tracing::warn!("Could not load project memory for {}: {}", project_id, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remember_replaces_same_key() {
+        let mut memory = ProjectMemory::default();
+        memory.remember("Setting", "1920s Chicago").unwrap();
+        memory.remember("Tone", "Noir").unwrap();
+        memory.remember("setting", "1930s Chicago").unwrap();
+
+        assert_eq!(memory.entries.len(), 2);
+        assert_eq!(memory.get("SETTING"), Some("1930s Chicago"));
+        // The updated fact moves to the end (most recent)
+        assert_eq!(memory.entries.last().unwrap().key, "setting");
+
+        assert!(memory.forget("tone"));
+        assert!(!memory.forget("tone"));
+        assert_eq!(
+            memory.to_prompt_section(),
+            "## Project Bible (established facts)\n- setting: 1930s Chicago"
+        );
+    }
+
+    #[test]
+    fn test_size_cap_evicts_oldest() {
+        let mut memory = ProjectMemory::default();
+        for i in 0..MAX_ENTRIES {
+            assert!(memory
+                .remember(&format!("fact {}", i), "value")
+                .unwrap()
+                .is_empty());
+        }
+
+        let evicted = memory.remember("one more", "value").unwrap();
+        assert_eq!(evicted, ["fact 0"]);
+        assert_eq!(memory.entries.len(), MAX_ENTRIES);
+        assert!(memory.get("fact 0").is_none());
+        assert!(memory.get("one more").is_some());
+    }
+
+    #[test]
+    fn test_rejects_invalid_entries() {
+        let mut memory = ProjectMemory::default();
+        assert!(memory.remember("  ", "value").is_err());
+        assert!(memory.remember("key", "").is_err());
+        assert!(memory
+            .remember("key", &"x".repeat(MAX_VALUE_CHARS + 1))
+            .is_err());
+        assert!(memory.is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+//! Prompt Enhancer - Expand a terse idea into a structured generation prompt
+//!
+//! The same expansion the Photography and Camera Directors do in chat, as a
+//! standalone call the UI can offer next to any prompt box. A fast LLM fills
+//! in subject, setting, lighting, camera and style; the fields are then
+//! composed into a model-ready prompt with Vault tokens injected.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::ai::llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest};
+use crate::ai::structured_output::extract_json;
+use crate::vault::tokens::{build_generation_prompt, TokenContext};
+
+/// Fast, cheap model used for enhancement
+const ENHANCER_MODEL: &str = "gemini-2.5-flash";
+
+const ENHANCER_SYSTEM_PROMPT: &str = r#"You are a prompt engineer for cinematic image and video models.
+Expand the user's idea into a precise generation prompt. Be concrete and visual; never add story or dialogue.
+
+Reply with a single JSON object and nothing else:
+{
+  "subject": "who or what is in frame, with appearance and action",
+  "setting": "where and when",
+  "lighting": "light sources, quality, color temperature",
+  "camera": "shot size, lens, angle (and movement for video)",
+  "style": "film stock, color grade, artistic references"
+}"#;
+
+/// What the prompt will generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub enum PromptKind {
+    Image,
+    Video,
+}
+
+/// The parts of an enhanced prompt
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct PromptFields {
+    #[serde(default)]
+    pub subject: String,
+    #[serde(default)]
+    pub setting: String,
+    #[serde(default)]
+    pub lighting: String,
+    #[serde(default)]
+    pub camera: String,
+    #[serde(default)]
+    pub style: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct EnhancedPrompt {
+    /// Model-ready prompt, with Vault tokens injected
+    pub prompt: String,
+    pub fields: PromptFields,
+    /// Whether the model returned the structured fields (otherwise the raw
+    /// completion is used as the subject)
+    pub structured: bool,
+    pub model_used: String,
+}
+
+/// Expand `idea` into a structured prompt for `kind`, in the given `style`
+pub async fn enhance_prompt(
+    kind: PromptKind,
+    idea: &str,
+    tokens: &[TokenContext],
+    style: Option<&str>,
+) -> Result<EnhancedPrompt, String> {
+    if idea.trim().is_empty() {
+        return Err("Nothing to enhance: the idea is empty".to_string());
+    }
+
+    let response = get_llm_client()
+        .chat(LLMRequest {
+            provider: LLMProvider::Gemini,
+            model: ENHANCER_MODEL.to_string(),
+            messages: vec![LLMMessage {
+                role: "user".to_string(),
+                content: user_message(kind, idea, tokens, style),
+                images: Vec::new(),
+            }],
+            temperature: Some(0.7),
+            max_tokens: Some(600),
+            system_prompt: Some(ENHANCER_SYSTEM_PROMPT.to_string()),
+        })
+        .await?;
+
+    let (fields, structured) = parse_fields(&response.content);
+    Ok(EnhancedPrompt {
+        prompt: build_generation_prompt(&compose_prompt(&fields), tokens),
+        fields,
+        structured,
+        model_used: response.model,
+    })
+}
+
+fn user_message(
+    kind: PromptKind,
+    idea: &str,
+    tokens: &[TokenContext],
+    style: Option<&str>,
+) -> String {
+    let target = match kind {
+        PromptKind::Image => "a still image",
+        PromptKind::Video => "a video shot (describe camera movement in \"camera\")",
+    };
+
+    let mut message = format!("Idea for {}:\n\"{}\"", target, idea.trim());
+    if let Some(style) = style.filter(|s| !s.trim().is_empty()) {
+        message.push_str(&format!("\n\nRequired style: {}", style.trim()));
+    }
+    if !tokens.is_empty() {
+        message.push_str("\n\nThese Vault elements appear in the shot; keep them recognizable:");
+        for token in tokens {
+            message.push_str(&format!(
+                "\n- {}: {}",
+                token.display_name, token.description
+            ));
+        }
+    }
+    message
+}
+
+/// Structured fields from the completion, or the raw text as the subject
+fn parse_fields(raw: &str) -> (PromptFields, bool) {
+    match extract_json(raw).and_then(|json| serde_json::from_str::<PromptFields>(json).ok()) {
+        Some(fields) if !fields.subject.trim().is_empty() => (fields, true),
+        _ => (
+            PromptFields {
+                subject: raw.trim().to_string(),
+                ..Default::default()
+            },
+            false,
+        ),
+    }
+}
+
+/// Join the fields in the order diffusion models weight them
+fn compose_prompt(fields: &PromptFields) -> String {
+    [
+        &fields.subject,
+        &fields.setting,
+        &fields.lighting,
+        &fields.camera,
+        &fields.style,
+    ]
+    .iter()
+    .map(|part| part.trim().trim_end_matches('.'))
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_structured_fields() {
+        let raw = r#"```json
+{"subject": "A detective lighting a cigarette", "setting": "Rain-soaked alley at night",
+ "lighting": "Neon rim light.", "camera": "35mm, low angle", "style": "Kodak Vision3 500T"}
+```"#;
+        let (fields, structured) = parse_fields(raw);
+        assert!(structured);
+        assert_eq!(fields.setting, "Rain-soaked alley at night");
+        assert_eq!(
+            compose_prompt(&fields),
+            "A detective lighting a cigarette, Rain-soaked alley at night, Neon rim light, \
+             35mm, low angle, Kodak Vision3 500T"
+        );
+    }
+
+    #[test]
+    fn test_unstructured_reply_becomes_subject() {
+        let (fields, structured) = parse_fields("  A lighthouse at dusk, storm rolling in ");
+        assert!(!structured);
+        assert_eq!(fields.subject, "A lighthouse at dusk, storm rolling in");
+        assert_eq!(
+            compose_prompt(&fields),
+            "A lighthouse at dusk, storm rolling in"
+        );
+    }
+
+    #[test]
+    fn test_user_message_includes_style_and_tokens() {
+        let anna = TokenContext {
+            token_id: "token:1".into(),
+            display_name: "@Anna".into(),
+            description: "A tired detective".into(),
+            visual_prompt: None,
+            lora_trigger: None,
+        };
+        let message = user_message(PromptKind::Video, "anna runs", &[anna], Some("noir"));
+        assert!(message.contains("a video shot"));
+        assert!(message.contains("Required style: noir"));
+        assert!(message.contains("- @Anna: A tired detective"));
+    }
+}
@@ -52,6 +52,24 @@ impl CloudProvider {
         }
     }
 
+    /// Key used for per-provider markups in the pricing config
+    pub fn pricing_key(&self) -> &'static str {
+        match self {
+            CloudProvider::VertexAI => "vertex",
+            CloudProvider::FalAI => "fal",
+            CloudProvider::OpenAI => "openai",
+            CloudProvider::Anthropic => "anthropic",
+            CloudProvider::ElevenLabs => "elevenlabs",
+            CloudProvider::XAI => "xai",
+            CloudProvider::Runway => "runway",
+            CloudProvider::Kling => "kling",
+            CloudProvider::ByteDance => "bytedance",
+            CloudProvider::Meshy => "meshy",
+            CloudProvider::Suno => "suno",
+            CloudProvider::Lightricks => "lightricks",
+        }
+    }
+
     /// Get the secret key name for this provider
     pub fn secret_key_name(&self) -> &'static str {
         match self {
@@ -93,7 +111,7 @@ pub fn get_provider_for_model(model_id: &str) -> CloudProvider {
         id if id.starts_with("grok") => CloudProvider::XAI,
 
         // ── ElevenLabs Direct ──
-        id if id.starts_with("elevenlabs") => CloudProvider::ElevenLabs,
+        id if id.starts_with("eleven") => CloudProvider::ElevenLabs,
 
         // ── Runway Direct ──
         id if id.starts_with("gen-") => CloudProvider::Runway,
@@ -145,6 +163,12 @@ mod tests {
         // xAI
         assert_eq!(get_provider_for_model("grok-3"), CloudProvider::XAI);
 
+        // ElevenLabs
+        assert_eq!(
+            get_provider_for_model("eleven-flash"),
+            CloudProvider::ElevenLabs
+        );
+
         // Runway
         assert_eq!(get_provider_for_model("gen-4.5"), CloudProvider::Runway);
 
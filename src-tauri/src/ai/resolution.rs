@@ -0,0 +1,261 @@
+//! Output Resolution - Presets and model-valid dimension snapping
+//!
+//! Diffusion models only accept sizes on a grid (FLUX works in multiples of
+//! 16, SDXL in multiples of 64) and degrade badly at extreme aspect ratios.
+//! Requested sizes are snapped to the nearest valid size before a workflow is
+//! generated, with warnings instead of a silent failure at the provider.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// Smallest edge any model accepts
+pub const MIN_DIMENSION: u32 = 256;
+
+/// Largest edge any model accepts
+pub const MAX_DIMENSION: u32 = 2048;
+
+/// Widest (or tallest) aspect ratio models handle without artifacts
+pub const MAX_ASPECT_RATIO: f32 = 2.5;
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PRESETS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Named output sizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub enum ResolutionPreset {
+    /// 1:1
+    Square1024,
+    /// Vertical, close to 9:16
+    Portrait,
+    /// Horizontal, close to 16:9
+    Landscape,
+    /// 1280×720 HD video
+    Hd720,
+    /// 1.85:1 theatrical flat
+    Cinema1_85,
+    /// 2.39:1 anamorphic scope
+    Cinema2_39,
+}
+
+impl ResolutionPreset {
+    /// (width, height) in pixels
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            ResolutionPreset::Square1024 => (1024, 1024),
+            ResolutionPreset::Portrait => (768, 1344),
+            ResolutionPreset::Landscape => (1344, 768),
+            ResolutionPreset::Hd720 => (1280, 720),
+            ResolutionPreset::Cinema1_85 => (1536, 832),
+            ResolutionPreset::Cinema2_39 => (1536, 640),
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// VALIDATION
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Dimensions a model will accept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct ValidatedSize {
+    pub width: u32,
+    pub height: u32,
+    /// Adjustments the user should know about
+    pub warnings: Vec<String>,
+}
+
+/// Grid the model's dimensions must sit on
+pub fn dimension_step(model: &str) -> u32 {
+    let model = model.to_lowercase();
+    if model.starts_with("sdxl") {
+        64
+    } else if model.starts_with("flux") {
+        16
+    } else {
+        // Latent VAE downsampling factor
+        8
+    }
+}
+
+/// Snap `width`×`height` to the nearest size valid for `model`
+pub fn validate_dimensions(model: &str, width: u32, height: u32) -> ValidatedSize {
+    let mut warnings = Vec::new();
+
+    if width == 0 || height == 0 {
+        let (default_width, default_height) = ResolutionPreset::Square1024.dimensions();
+        warnings.push(format!(
+            "Invalid size {}x{}; using {}x{}",
+            width, height, default_width, default_height
+        ));
+        return ValidatedSize {
+            width: default_width,
+            height: default_height,
+            warnings,
+        };
+    }
+
+    let ratio = width as f32 / height as f32;
+    if !(1.0 / MAX_ASPECT_RATIO..=MAX_ASPECT_RATIO).contains(&ratio) {
+        warnings.push(format!(
+            "Aspect ratio {:.2}:1 is not supported by {} (max {}:1); expect cropping or artifacts",
+            ratio, model, MAX_ASPECT_RATIO
+        ));
+    }
+
+    let step = dimension_step(model);
+    let mut snap = |edge: &str, value: u32| {
+        if !(MIN_DIMENSION..=MAX_DIMENSION).contains(&value) {
+            warnings.push(format!(
+                "{} {} is outside {}-{}",
+                edge, value, MIN_DIMENSION, MAX_DIMENSION
+            ));
+        }
+        let clamped = value.clamp(MIN_DIMENSION, MAX_DIMENSION);
+        ((clamped + step / 2) / step * step).clamp(MIN_DIMENSION, MAX_DIMENSION)
+    };
+    let snapped_width = snap("Width", width);
+    let snapped_height = snap("Height", height);
+
+    if (snapped_width, snapped_height) != (width, height) {
+        tracing::debug!(
+            "Snapped {}x{} to {}x{} for {}",
+            width,
+            height,
+            snapped_width,
+            snapped_height,
+            model
+        );
+    }
+
+    ValidatedSize {
+        width: snapped_width,
+        height: snapped_height,
+        warnings,
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// IMAGE SIZE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Width and height from a PNG or JPEG header
+pub fn image_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    if bytes.starts_with(PNG_SIGNATURE) {
+        // IHDR is always the first chunk
+        let width = u32::from_be_bytes(bytes.get(16..20)?.try_into().ok()?);
+        let height = u32::from_be_bytes(bytes.get(20..24)?.try_into().ok()?);
+        return Some((width, height));
+    }
+
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        // Walk the segments to the first start-of-frame marker
+        let mut pos = 2;
+        while pos + 4 <= bytes.len() {
+            if bytes[pos] != 0xFF {
+                return None;
+            }
+            let marker = bytes[pos + 1];
+            let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+            let is_frame = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+            if is_frame {
+                let frame = bytes.get(pos + 5..pos + 9)?;
+                let height = u16::from_be_bytes([frame[0], frame[1]]) as u32;
+                let width = u16::from_be_bytes([frame[2], frame[3]]) as u32;
+                return Some((width, height));
+            }
+            pos += 2 + length;
+        }
+    }
+
+    None
+}
+
+/// Check an inpainting mask covers exactly the source image
+pub fn validate_mask_size(source: (u32, u32), mask: (u32, u32)) -> Result<(), String> {
+    if source == mask {
+        Ok(())
+    } else {
+        Err(format!(
+            "Mask is {}x{} but the source image is {}x{}; they must match",
+            mask.0, mask.1, source.0, source.1
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_dimensions() {
+        assert_eq!(ResolutionPreset::Square1024.dimensions(), (1024, 1024));
+        assert_eq!(ResolutionPreset::Portrait.dimensions(), (768, 1344));
+        assert_eq!(ResolutionPreset::Hd720.dimensions(), (1280, 720));
+
+        let (w, h) = ResolutionPreset::Cinema2_39.dimensions();
+        assert!((w as f32 / h as f32 - 2.39).abs() < 0.02);
+
+        // Every preset is already valid for FLUX
+        for preset in [
+            ResolutionPreset::Square1024,
+            ResolutionPreset::Portrait,
+            ResolutionPreset::Landscape,
+            ResolutionPreset::Hd720,
+            ResolutionPreset::Cinema1_85,
+            ResolutionPreset::Cinema2_39,
+        ] {
+            let (w, h) = preset.dimensions();
+            let size = validate_dimensions("flux-dev", w, h);
+            assert_eq!((size.width, size.height), (w, h));
+            assert!(size.warnings.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_snaps_to_model_grid() {
+        let flux = validate_dimensions("flux-schnell", 1000, 700);
+        assert_eq!((flux.width, flux.height), (1008, 704));
+        assert!(flux.warnings.is_empty());
+
+        let sdxl = validate_dimensions("sdxl", 1280, 720);
+        assert_eq!((sdxl.width, sdxl.height), (1280, 704));
+
+        let other = validate_dimensions("kling-v2.6", 1282, 719);
+        assert_eq!((other.width, other.height), (1280, 720));
+    }
+
+    #[test]
+    fn test_clamps_and_warns() {
+        let size = validate_dimensions("flux-dev", 4000, 1000);
+        assert_eq!((size.width, size.height), (2048, 1008));
+        assert_eq!(size.warnings.len(), 2);
+        assert!(size.warnings[0].contains("Aspect ratio 4.00:1"));
+        assert!(size.warnings[1].contains("Width 4000"));
+
+        let zero = validate_dimensions("flux-dev", 0, 512);
+        assert_eq!((zero.width, zero.height), (1024, 1024));
+        assert_eq!(zero.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_image_size_from_headers() {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend(1280u32.to_be_bytes());
+        png.extend(720u32.to_be_bytes());
+        assert_eq!(image_size(&png), Some((1280, 720)));
+
+        // SOI, an APP0 segment, then a baseline SOF0 frame
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x02,
+            0xD0, 0x05, 0x00,
+        ];
+        assert_eq!(image_size(&jpeg), Some((1280, 720)));
+
+        assert_eq!(image_size(b"GIF89a"), None);
+        assert!(validate_mask_size((1280, 720), (1280, 720)).is_ok());
+        assert!(validate_mask_size((1280, 720), (1024, 1024)).is_err());
+    }
+}
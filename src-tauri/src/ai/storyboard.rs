@@ -0,0 +1,739 @@
+//! Storyboard Pipeline - One image per scene for a whole script
+//!
+//! Splits the project's Lexical script into scenes, enriches each scene with
+//! its Vault tokens (descriptions and LoRA triggers), and generates a frame
+//! per scene through the image workflow path with a concurrency cap.
+//! A local scene holds its slot until ComfyUI finished rendering it, not just
+//! until the prompt was queued. Progress is reported per finished scene and
+//! the run can be cancelled: prompts already queued are interrupted and
+//! scenes that have not started yet are skipped.
+
+use futures_util::StreamExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::ai::actions::{ActionExecutor, ActionResult};
+use crate::ai::comfyui_client::{get_client, ExecutionResult, CANCELLED_ERROR};
+use crate::ai::workflow_generator::{WorkflowRequest, WorkflowType};
+use crate::pagination::ScriptElement;
+use crate::scenes;
+use crate::vault::models::Script;
+use crate::vault::tokens::{build_generation_prompt, Token, TokenContext, TokenType};
+
+/// Scenes generated at the same time by default
+pub const DEFAULT_MAX_CONCURRENT: usize = 3;
+
+/// Default storyboard image model (local, free)
+const DEFAULT_MODEL: &str = "flux-schnell";
+
+/// Longest action excerpt included in a frame prompt
+const MAX_ACTION_CHARS: usize = 400;
+
+/// Running storyboards, keyed by storyboard id
+static ACTIVE_STORYBOARDS: Lazy<Mutex<HashMap<String, Arc<StoryboardRun>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Cancel state of a running storyboard
+#[derive(Default)]
+struct StoryboardRun {
+    cancelled: AtomicBool,
+    /// ComfyUI prompts queued and not finished yet
+    prompt_ids: Mutex<HashSet<String>>,
+}
+
+impl StoryboardRun {
+    /// Track a queued prompt; false if the run was cancelled meanwhile
+    fn track(&self, prompt_id: &str) -> bool {
+        if let Ok(mut prompt_ids) = self.prompt_ids.lock() {
+            prompt_ids.insert(prompt_id.to_string());
+        }
+        !self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn untrack(&self, prompt_id: &str) {
+        if let Ok(mut prompt_ids) = self.prompt_ids.lock() {
+            prompt_ids.remove(prompt_id);
+        }
+    }
+
+    /// Set the flag and take the prompts to interrupt
+    fn cancel(&self) -> Vec<String> {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.prompt_ids
+            .lock()
+            .map(|prompt_ids| prompt_ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// A scene extracted from the script
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct ScriptScene {
+    /// 1-based scene number
+    pub number: u32,
+    pub heading: String,
+    /// Action lines of the scene, joined
+    pub action: String,
+    /// Ids of tokens mentioned in the scene
+    pub token_ids: Vec<String>,
+    /// Speaking characters (cue names without extensions)
+    pub characters: Vec<String>,
+}
+
+/// Storyboard generation options (missing fields use the defaults)
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct StoryboardOptions {
+    /// Client-chosen id used to cancel the run via `cancel_storyboard`
+    pub storyboard_id: String,
+    pub model: String,
+    pub width: u32,
+    pub height: u32,
+    pub max_concurrent: u32,
+}
+
+impl Default for StoryboardOptions {
+    fn default() -> Self {
+        Self {
+            storyboard_id: uuid::Uuid::new_v4().to_string(),
+            model: DEFAULT_MODEL.to_string(),
+            width: 1344,
+            height: 768,
+            max_concurrent: DEFAULT_MAX_CONCURRENT as u32,
+        }
+    }
+}
+
+/// The generated frame for one scene
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct StoryboardFrame {
+    pub scene_number: u32,
+    pub heading: String,
+    pub prompt: String,
+    pub success: bool,
+    /// ComfyUI prompt id (local) or pending cloud execution id
+    pub execution_id: Option<String>,
+    /// Workflow/asset details as JSON string (parse on frontend)
+    pub data: Option<String>,
+    pub credits: f32,
+    pub error: Option<String>,
+}
+
+/// Aggregate progress, reported after each scene
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct StoryboardProgress {
+    pub storyboard_id: String,
+    pub completed: u32,
+    pub failed: u32,
+    pub total: u32,
+    pub credits: f32,
+}
+
+/// Result of a storyboard run, frames in scene order
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct StoryboardResult {
+    pub storyboard_id: String,
+    pub frames: Vec<StoryboardFrame>,
+    pub total_credits: f32,
+    pub cancelled: bool,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// SCRIPT PARSING
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Split a Lexical script document into scenes
+///
+/// Each `scene-heading` node starts a scene; omitted scenes are skipped and
+/// content before the first heading is ignored.
+pub fn parse_scenes(lexical_json: &str) -> Result<Vec<ScriptScene>, String> {
+    let doc: serde_json::Value =
+        serde_json::from_str(lexical_json).map_err(|e| format!("Invalid script JSON: {}", e))?;
+    let Some(nodes) = doc["root"]["children"].as_array() else {
+        return Ok(Vec::new());
+    };
+
+    let elements: Vec<ScriptElement> = nodes
+        .iter()
+        .map(|node| ScriptElement {
+            r#type: node["type"].as_str().unwrap_or_default().to_string(),
+            text: node_text(node),
+            scene_number: None,
+        })
+        .collect();
+
+    let scenes = scenes::parse_scenes(&elements)
+        .into_iter()
+        .filter(|scene| {
+            !nodes[scene.start_index]["isOmitted"]
+                .as_bool()
+                .unwrap_or(false)
+        })
+        .enumerate()
+        .map(|(i, scene)| {
+            let mut token_ids = Vec::new();
+            for node in &nodes[scene.start_index..scene.end_index] {
+                collect_token_ids(node, &mut token_ids);
+            }
+            ScriptScene {
+                number: i as u32 + 1,
+                heading: scene.heading,
+                action: scene.action_lines.join(" "),
+                token_ids,
+                characters: scene.characters,
+            }
+        })
+        .collect();
+
+    Ok(scenes)
+}
+
+/// Concatenated text of a node and its descendants
+fn node_text(node: &serde_json::Value) -> String {
+    let mut text = node["text"].as_str().unwrap_or_default().to_string();
+    if let Some(children) = node["children"].as_array() {
+        for child in children {
+            text.push_str(&node_text(child));
+        }
+    }
+    text
+}
+
+fn collect_token_ids(node: &serde_json::Value, ids: &mut Vec<String>) {
+    if node["type"] == "token" {
+        if let Some(id) = node["data"].as_str() {
+            if !ids.iter().any(|existing| existing == id) {
+                ids.push(id.to_string());
+            }
+        }
+    }
+    if let Some(children) = node["children"].as_array() {
+        for child in children {
+            collect_token_ids(child, ids);
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PROMPTS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Tokens relevant to a scene: mentioned tokens, speaking characters and
+/// locations named in the heading
+pub fn scene_tokens<'a>(scene: &ScriptScene, tokens: &'a [Token]) -> Vec<&'a Token> {
+    let heading = scene.heading.to_lowercase();
+
+    tokens
+        .iter()
+        .filter(|t| {
+            t.id.as_ref().is_some_and(|id| scene.token_ids.contains(id))
+                || (t.token_type == TokenType::Character
+                    && scene
+                        .characters
+                        .iter()
+                        .any(|c| c.eq_ignore_ascii_case(&t.name)))
+                || (t.token_type == TokenType::Location && heading.contains(&t.name.to_lowercase()))
+        })
+        .collect()
+}
+
+/// Image workflow request for a scene, with token descriptions and LoRA triggers
+pub fn build_scene_request(
+    scene: &ScriptScene,
+    tokens: &[&Token],
+    options: &StoryboardOptions,
+) -> WorkflowRequest {
+    let mut parts = vec![format!("Cinematic storyboard frame, {}", scene.heading)];
+    if !scene.action.is_empty() {
+        parts.push(scene.action.chars().take(MAX_ACTION_CHARS).collect());
+    }
+
+    let contexts: Vec<TokenContext> = tokens
+        .iter()
+        .map(|token| TokenContext::from((*token).clone()))
+        .collect();
+    let prompt = build_generation_prompt(&parts.join(". "), &contexts);
+
+    WorkflowRequest {
+        workflow_type: WorkflowType::TextToImage,
+        prompt,
+        negative_prompt: None,
+        model: options.model.clone(),
+        width: options.width,
+        height: options.height,
+        steps: None,
+        seed: None,
+        batch_size: 1,
+        input_image: None,
+        force_local: None,
+        control: None,
+        mask_image: None,
+        expand_pixels: None,
+        params: None,
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PIPELINE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Generate a storyboard frame for every scene of the project's script
+pub async fn generate_storyboard(
+    project_id: &str,
+    options: StoryboardOptions,
+    on_progress: impl Fn(StoryboardProgress),
+) -> Result<StoryboardResult, String> {
+    let (script, tokens) = load_project(project_id).await?;
+    let scenes = parse_scenes(&script.content)?;
+    if scenes.is_empty() {
+        return Err("The script has no scene headings".into());
+    }
+
+    let requests: Vec<(ScriptScene, WorkflowRequest)> = scenes
+        .into_iter()
+        .map(|scene| {
+            let request = build_scene_request(&scene, &scene_tokens(&scene, &tokens), &options);
+            (scene, request)
+        })
+        .collect();
+
+    let run = Arc::new(StoryboardRun::default());
+    if let Ok(mut active) = ACTIVE_STORYBOARDS.lock() {
+        active.insert(options.storyboard_id.clone(), run.clone());
+    }
+
+    let frames = run_scenes(
+        &options.storyboard_id,
+        requests,
+        options.max_concurrent.max(1) as usize,
+        &run.cancelled,
+        |request| render_scene(request, &run),
+        on_progress,
+    )
+    .await;
+
+    if let Ok(mut active) = ACTIVE_STORYBOARDS.lock() {
+        active.remove(&options.storyboard_id);
+    }
+
+    Ok(StoryboardResult {
+        storyboard_id: options.storyboard_id,
+        total_credits: frames.iter().map(|f| f.credits).sum(),
+        cancelled: run.cancelled.load(Ordering::SeqCst),
+        frames,
+    })
+}
+
+/// Cancel a running storyboard; returns false if it is not running
+///
+/// Scenes not started yet are skipped and the prompts already queued are
+/// interrupted (or removed from ComfyUI's queue).
+pub fn cancel_storyboard(storyboard_id: &str) -> bool {
+    let Some(run) = ACTIVE_STORYBOARDS
+        .lock()
+        .ok()
+        .and_then(|active| active.get(storyboard_id).cloned())
+    else {
+        return false;
+    };
+
+    let prompt_ids = run.cancel();
+    tauri::async_runtime::spawn(async move {
+        for prompt_id in prompt_ids {
+            interrupt_prompt(&prompt_id).await;
+        }
+    });
+    true
+}
+
+async fn interrupt_prompt(prompt_id: &str) {
+    if let Err(e) = get_client().interrupt(prompt_id).await {
+        tracing::warn!("Failed to interrupt storyboard prompt {}: {}", prompt_id, e);
+    }
+}
+
+/// Queue a scene and wait until its frame is rendered
+async fn render_scene(request: WorkflowRequest, run: &StoryboardRun) -> ActionResult {
+    let queued = ActionExecutor::execute_image_workflow(&request).await;
+    await_render(queued, |prompt_id| async move {
+        // Cancelled while the scene was being queued
+        if !run.track(&prompt_id) {
+            interrupt_prompt(&prompt_id).await;
+        }
+        let result = get_client().wait_for(&prompt_id).await;
+        run.untrack(&prompt_id);
+        result
+    })
+    .await
+}
+
+/// Finish a scene result once its queued ComfyUI prompt completed
+///
+/// `execute_image_workflow` returns as soon as ComfyUI accepted a local
+/// prompt; `wait` resolves when it finished. Cloud and failed results are
+/// returned unchanged.
+async fn await_render<W, Fut>(mut result: ActionResult, wait: W) -> ActionResult
+where
+    W: FnOnce(String) -> Fut,
+    Fut: std::future::Future<Output = Result<ExecutionResult, String>>,
+{
+    let mut data: serde_json::Value = result
+        .data
+        .as_deref()
+        .and_then(|data| serde_json::from_str(data).ok())
+        .unwrap_or_default();
+    let prompt_id = match &result.execution_id {
+        Some(id) if result.success && data["is_local"] == true => id.clone(),
+        _ => return result,
+    };
+
+    match wait(prompt_id).await {
+        Ok(execution) if execution.success => {
+            data["status"] = "completed".into();
+            data["outputs"] = serde_json::to_value(&execution.output_files).unwrap_or_default();
+            result.data = Some(data.to_string());
+        }
+        Ok(execution) => {
+            result.success = false;
+            result.error = match execution.error.as_deref() {
+                Some(CANCELLED_ERROR) => Some("Cancelled".into()),
+                _ => execution.error,
+            };
+        }
+        Err(e) => {
+            result.success = false;
+            result.error = Some(e);
+        }
+    }
+    result
+}
+
+/// Run scene requests with at most `max_concurrent` in flight, returning
+/// frames in scene order
+async fn run_scenes<F, Fut>(
+    storyboard_id: &str,
+    requests: Vec<(ScriptScene, WorkflowRequest)>,
+    max_concurrent: usize,
+    cancel: &AtomicBool,
+    execute: F,
+    on_progress: impl Fn(StoryboardProgress),
+) -> Vec<StoryboardFrame>
+where
+    F: Fn(WorkflowRequest) -> Fut,
+    Fut: std::future::Future<Output = ActionResult>,
+{
+    let total = requests.len() as u32;
+    let progress = Mutex::new(StoryboardProgress {
+        storyboard_id: storyboard_id.to_string(),
+        completed: 0,
+        failed: 0,
+        total,
+        credits: 0.0,
+    });
+
+    let mut frames: Vec<StoryboardFrame> = futures_util::stream::iter(requests)
+        .map(|(scene, request)| {
+            let prompt = request.prompt.clone();
+            let run = (!cancel.load(Ordering::SeqCst)).then(|| execute(request));
+            let progress = &progress;
+            let on_progress = &on_progress;
+
+            async move {
+                let frame = match run {
+                    Some(run) => {
+                        let result = run.await;
+                        StoryboardFrame {
+                            scene_number: scene.number,
+                            heading: scene.heading,
+                            prompt,
+                            success: result.success,
+                            execution_id: result.execution_id,
+                            data: result.data,
+                            credits: result.credits_used.unwrap_or(0.0),
+                            error: result.error,
+                        }
+                    }
+                    None => StoryboardFrame {
+                        scene_number: scene.number,
+                        heading: scene.heading,
+                        prompt,
+                        success: false,
+                        execution_id: None,
+                        data: None,
+                        credits: 0.0,
+                        error: Some("Cancelled".into()),
+                    },
+                };
+
+                if let Ok(mut progress) = progress.lock() {
+                    progress.completed += 1;
+                    if !frame.success {
+                        progress.failed += 1;
+                    }
+                    progress.credits += frame.credits;
+                    on_progress(progress.clone());
+                }
+                frame
+            }
+        })
+        .buffer_unordered(max_concurrent)
+        .collect()
+        .await;
+
+    frames.sort_by_key(|f| f.scene_number);
+    frames
+}
+
+async fn load_project(project_id: &str) -> Result<(Script, Vec<Token>), String> {
+    let db = crate::vault::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    let mut result = db
+        .query("SELECT * FROM script WHERE project_id = type::thing($pid)")
+        .bind(("pid", project_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+    let script: Option<Script> = result.take(0).map_err(|e| e.to_string())?;
+    let script = script.ok_or_else(|| format!("No script found for project {}", project_id))?;
+
+    let mut result = db
+        .query("SELECT * FROM token WHERE project_id = $pid")
+        .bind(("pid", project_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+    let tokens: Vec<Token> = result.take(0).map_err(|e| e.to_string())?;
+
+    Ok((script, tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = r#"{"root":{"children":[
+        {"type":"action","children":[{"type":"text","text":"FADE IN:"}]},
+        {"type":"scene-heading","isOmitted":false,"children":[{"type":"text","text":"INT. BAR DE LA CIUTAT - NIGHT"}]},
+        {"type":"action","children":[
+            {"type":"token","text":"@Anna","data":"token:anna"},
+            {"type":"text","text":" nurses a whiskey."}
+        ]},
+        {"type":"character","children":[{"type":"text","text":"MARC (V.O.)"}]},
+        {"type":"dialogue","children":[{"type":"text","text":"You came back."}]},
+        {"type":"scene-heading","isOmitted":true,"children":[{"type":"text","text":"EXT. ROOF - NIGHT"}]},
+        {"type":"action","children":[{"type":"text","text":"Cut for time."}]},
+        {"type":"scene-heading","children":[{"type":"text","text":"EXT. BEACH - DAWN"}]}
+    ]}}"#;
+
+    fn token(id: &str, token_type: TokenType, name: &str, lora: Option<&str>) -> Token {
+        let mut token = Token::new(
+            "project:1".into(),
+            token_type,
+            name.into(),
+            format!("{} look", name),
+        );
+        token.id = Some(id.into());
+        token.lora_id = lora.map(String::from);
+        token
+    }
+
+    #[test]
+    fn test_parse_scenes() {
+        let scenes = parse_scenes(SCRIPT).unwrap();
+        assert_eq!(scenes.len(), 2);
+
+        assert_eq!(scenes[0].number, 1);
+        assert_eq!(scenes[0].heading, "INT. BAR DE LA CIUTAT - NIGHT");
+        assert_eq!(scenes[0].action, "@Anna nurses a whiskey.");
+        assert_eq!(scenes[0].token_ids, ["token:anna"]);
+        assert_eq!(scenes[0].characters, ["MARC"]);
+
+        // The omitted scene is skipped entirely
+        assert_eq!(scenes[1].heading, "EXT. BEACH - DAWN");
+        assert_eq!(scenes[1].number, 2);
+        assert!(scenes[1].action.is_empty());
+    }
+
+    #[test]
+    fn test_scene_request_includes_tokens_and_loras() {
+        let tokens = vec![
+            token("token:anna", TokenType::Character, "Anna", Some("anna_v2")),
+            token("token:marc", TokenType::Character, "Marc", None),
+            token(
+                "token:bar",
+                TokenType::Location,
+                "Bar de la Ciutat",
+                Some("bar_v1"),
+            ),
+            token("token:beach", TokenType::Location, "Beach", None),
+        ];
+        let scene = &parse_scenes(SCRIPT).unwrap()[0];
+
+        let relevant = scene_tokens(scene, &tokens);
+        let ids: Vec<&str> = relevant.iter().filter_map(|t| t.id.as_deref()).collect();
+        assert_eq!(ids, ["token:anna", "token:marc", "token:bar"]);
+
+        let request = build_scene_request(scene, &relevant, &StoryboardOptions::default());
+        assert!(request
+            .prompt
+            .starts_with("Cinematic storyboard frame, INT. BAR"));
+        assert!(request.prompt.contains("Anna: Anna look"));
+        assert!(request.prompt.ends_with("<lora:anna_v2> <lora:bar_v1>"));
+        assert_eq!(request.model, DEFAULT_MODEL);
+    }
+
+    #[tokio::test]
+    async fn test_run_scenes_respects_cap_and_cancel() {
+        let options = StoryboardOptions::default();
+        let requests: Vec<_> = (1..=5)
+            .map(|n| {
+                let scene = ScriptScene {
+                    number: n,
+                    heading: format!("SCENE {}", n),
+                    action: String::new(),
+                    token_ids: Vec::new(),
+                    characters: Vec::new(),
+                };
+                let request = build_scene_request(&scene, &[], &options);
+                (scene, request)
+            })
+            .collect();
+
+        let in_flight = std::sync::atomic::AtomicUsize::new(0);
+        let peak = std::sync::atomic::AtomicUsize::new(0);
+        let cancel = AtomicBool::new(false);
+        let updates = Mutex::new(Vec::new());
+
+        let frames = run_scenes(
+            "sb-1",
+            requests,
+            2,
+            &cancel,
+            |request| {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                // The user cancels as scene 3 starts
+                if request.prompt.contains("SCENE 3") {
+                    cancel.store(true, Ordering::SeqCst);
+                }
+                let in_flight = &in_flight;
+                async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    ActionResult::success("generate_image").with_credits(1.5)
+                }
+            },
+            |progress| updates.lock().unwrap().push(progress),
+        )
+        .await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        let numbers: Vec<u32> = frames.iter().map(|f| f.scene_number).collect();
+        assert_eq!(numbers, [1, 2, 3, 4, 5]);
+
+        // Scenes already started finish; the rest are skipped
+        let generated = frames.iter().filter(|f| f.success).count();
+        assert_eq!(generated, 3);
+        assert_eq!(frames[4].error.as_deref(), Some("Cancelled"));
+
+        let last = updates.lock().unwrap().last().cloned().unwrap();
+        assert_eq!((last.completed, last.failed, last.total), (5, 2, 5));
+        assert!((last.credits - 4.5).abs() < f32::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_local_scenes_hold_their_slot_until_rendered() {
+        let options = StoryboardOptions::default();
+        let requests: Vec<_> = (1..=4)
+            .map(|n| {
+                let scene = ScriptScene {
+                    number: n,
+                    heading: format!("SCENE {}", n),
+                    action: String::new(),
+                    token_ids: Vec::new(),
+                    characters: Vec::new(),
+                };
+                let request = build_scene_request(&scene, &[], &options);
+                (scene, request)
+            })
+            .collect();
+
+        // Prompts ComfyUI accepted and hasn't finished rendering
+        let rendering = std::sync::atomic::AtomicUsize::new(0);
+        let peak = std::sync::atomic::AtomicUsize::new(0);
+        let run = StoryboardRun::default();
+
+        let frames = run_scenes(
+            "sb-2",
+            requests,
+            2,
+            &run.cancelled,
+            |request| {
+                let (rendering, peak, run) = (&rendering, &peak, &run);
+                async move {
+                    // Queueing returns at once, like `submit_workflow`
+                    let prompt_id = request.prompt.clone();
+                    let now = rendering.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    let queued = ActionResult::success("generate_image")
+                        .with_execution_id(prompt_id)
+                        .with_data(serde_json::json!({ "is_local": true, "status": "queued" }));
+
+                    await_render(queued, |prompt_id| async move {
+                        assert!(run.track(&prompt_id));
+                        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                        run.untrack(&prompt_id);
+                        rendering.fetch_sub(1, Ordering::SeqCst);
+                        Ok(ExecutionResult {
+                            execution_id: prompt_id,
+                            success: true,
+                            outputs_json: "{}".into(),
+                            output_files: Vec::new(),
+                            error: None,
+                        })
+                    })
+                    .await
+                }
+            },
+            |_| {},
+        )
+        .await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert!(frames.iter().all(|f| f.success));
+        let data: serde_json::Value =
+            serde_json::from_str(frames[0].data.as_deref().unwrap()).unwrap();
+        assert_eq!(data["status"], "completed");
+
+        // Cancelling hands out the prompts still rendering and refuses new ones
+        assert!(run.track("prompt-a"));
+        assert_eq!(run.cancel(), ["prompt-a"]);
+        assert!(!run.track("prompt-b"));
+
+        let interrupted = await_render(
+            ActionResult::success("generate_image")
+                .with_execution_id("prompt-b".into())
+                .with_data(serde_json::json!({ "is_local": true })),
+            |prompt_id| async move {
+                Ok(ExecutionResult {
+                    execution_id: prompt_id,
+                    success: false,
+                    outputs_json: "{}".into(),
+                    output_files: Vec::new(),
+                    error: Some(CANCELLED_ERROR.into()),
+                })
+            },
+        )
+        .await;
+        assert!(!interrupted.success);
+        assert_eq!(interrupted.error.as_deref(), Some("Cancelled"));
+    }
+}
@@ -0,0 +1,228 @@
+//! Structured Agent Output - message + actions + Vault citations
+//!
+//! Agents ask the LLM for a JSON-structured completion so the frontend gets
+//! the prose, executable actions and cited Vault tokens as separate fields.
+//! When the model ignores the format, we fall back to plain text plus the
+//! heuristic action parser.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::ai::actions::{parse_actions_from_response, AgentAction};
+use crate::ai::context::{AgentContext, TokenSummary};
+
+/// Instructions appended to agent system prompts
+pub const STRUCTURED_OUTPUT_INSTRUCTIONS: &str = r#"
+
+# Response Format
+Reply with a single JSON object and nothing else:
+{
+  "message": "Your reply to the user (Markdown allowed)",
+  "actions": [],
+  "citations": [{ "name": "Anna", "token_type": "Character" }]
+}
+- "actions": optional executable actions, each an object tagged by "type"
+  (GenerateImage, GenerateVideo, GenerateAudio, UpdateScript, AddToCanvas, UpdateVault, Delegate, ShowMessage)
+- "citations": the Vault tokens (Character, Location, Prop) your answer relies on
+"#;
+
+/// A Vault token the agent relied on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct TokenReference {
+    /// Vault ID, when the token could be resolved
+    #[serde(default)]
+    pub token_id: Option<String>,
+    pub name: String,
+    /// "Character", "Location" or "Prop"
+    #[serde(default)]
+    pub token_type: String,
+}
+
+/// Parsed agent output
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct StructuredAgentOutput {
+    pub message: String,
+    pub actions: Vec<AgentAction>,
+    pub citations: Vec<TokenReference>,
+    /// Whether the model followed the JSON format
+    pub structured: bool,
+}
+
+impl StructuredAgentOutput {
+    /// Take the parsed actions, or `fallback` if the model suggested none
+    pub fn take_actions_or(&mut self, fallback: Vec<AgentAction>) -> Vec<AgentAction> {
+        if self.actions.is_empty() {
+            fallback
+        } else {
+            std::mem::take(&mut self.actions)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawOutput {
+    message: String,
+    #[serde(default)]
+    actions: Vec<serde_json::Value>,
+    #[serde(default)]
+    citations: Vec<TokenReference>,
+}
+
+/// Append the structured-output instructions to a system prompt
+pub fn with_structured_output(system_prompt: String) -> String {
+    system_prompt + STRUCTURED_OUTPUT_INSTRUCTIONS
+}
+
+/// Parse an LLM completion into structured output, falling back to plain text
+pub fn parse_structured_output(raw: &str, context: &AgentContext) -> StructuredAgentOutput {
+    let tokens = vault_tokens(context);
+
+    match extract_json(raw).and_then(|json| serde_json::from_str::<RawOutput>(json).ok()) {
+        Some(parsed) => StructuredAgentOutput {
+            message: parsed.message,
+            // Skip malformed actions rather than rejecting the whole reply
+            actions: parsed
+                .actions
+                .into_iter()
+                .filter_map(|a| serde_json::from_value(a).ok())
+                .collect(),
+            citations: parsed
+                .citations
+                .into_iter()
+                .map(|c| resolve_citation(c, &tokens))
+                .collect(),
+            structured: true,
+        },
+        None => StructuredAgentOutput {
+            message: raw.trim().to_string(),
+            actions: parse_actions_from_response(raw),
+            citations: cite_tokens(raw, &tokens),
+            structured: false,
+        },
+    }
+}
+
+/// Find the JSON object in a completion (tolerates code fences and stray prose)
+pub fn extract_json(raw: &str) -> Option<&str> {
+    let start = raw.find('{')?;
+    let end = raw.rfind('}')?;
+    (end > start).then(|| &raw[start..=end])
+}
+
+fn vault_tokens(context: &AgentContext) -> Vec<(&TokenSummary, &'static str)> {
+    let Some(vault) = &context.vault else {
+        return Vec::new();
+    };
+
+    vault
+        .characters
+        .iter()
+        .map(|t| (t, "Character"))
+        .chain(vault.locations.iter().map(|t| (t, "Location")))
+        .chain(vault.props.iter().map(|t| (t, "Prop")))
+        .collect()
+}
+
+fn resolve_citation(
+    mut citation: TokenReference,
+    tokens: &[(&TokenSummary, &'static str)],
+) -> TokenReference {
+    let name = citation.name.trim_start_matches(['@', '/', '#']);
+
+    if let Some((token, token_type)) = tokens
+        .iter()
+        .find(|(t, _)| t.name.eq_ignore_ascii_case(name))
+    {
+        citation.token_id = Some(token.id.clone());
+        citation.name = token.name.clone();
+        citation.token_type = token_type.to_string();
+    }
+    citation
+}
+
+/// Cite every Vault token from the context that is mentioned by name in `text`
+pub fn detect_citations(text: &str, context: &AgentContext) -> Vec<TokenReference> {
+    cite_tokens(text, &vault_tokens(context))
+}
+
+fn cite_tokens(text: &str, tokens: &[(&TokenSummary, &'static str)]) -> Vec<TokenReference> {
+    let lower = text.to_lowercase();
+
+    tokens
+        .iter()
+        .filter(|(t, _)| !t.name.is_empty() && lower.contains(&t.name.to_lowercase()))
+        .map(|(t, token_type)| TokenReference {
+            token_id: Some(t.id.clone()),
+            name: t.name.clone(),
+            token_type: token_type.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::context::VaultTokenContext;
+
+    fn context_with_anna() -> AgentContext {
+        let mut ctx = AgentContext::empty();
+        ctx.vault = Some(VaultTokenContext {
+            characters: vec![TokenSummary {
+                id: "token:anna".into(),
+                name: "Anna".into(),
+                description: "A tired detective".into(),
+                has_reference_images: false,
+                has_lora: false,
+            }],
+            locations: vec![],
+            props: vec![],
+            style_notes: None,
+        });
+        ctx
+    }
+
+    #[test]
+    fn test_parse_structured_json() {
+        let raw = r#"```json
+{
+  "message": "Anna should enter from the rain.",
+  "actions": [
+    {"type": "GenerateImage", "prompt": "Anna in the rain", "model": "flux-schnell",
+     "width": 1024, "height": 1024, "token_ids": ["token:anna"]},
+    {"type": "NotARealAction"}
+  ],
+  "citations": [{"name": "@anna", "token_type": "Character"}]
+}
+```"#;
+
+        let output = parse_structured_output(raw, &context_with_anna());
+        assert!(output.structured);
+        assert_eq!(output.message, "Anna should enter from the rain.");
+        assert_eq!(output.actions.len(), 1);
+        assert_eq!(output.citations.len(), 1);
+        assert_eq!(output.citations[0].token_id.as_deref(), Some("token:anna"));
+        assert_eq!(output.citations[0].name, "Anna");
+    }
+
+    #[test]
+    fn test_fallback_to_plain_text() {
+        let raw = "Anna lights a cigarette and stares at the door.";
+        let output = parse_structured_output(raw, &context_with_anna());
+
+        assert!(!output.structured);
+        assert_eq!(output.message, raw);
+        assert!(output.actions.is_empty());
+        assert_eq!(output.citations.len(), 1);
+        assert_eq!(output.citations[0].token_type, "Character");
+    }
+
+    #[test]
+    fn test_take_actions_or_fallback() {
+        let mut output = parse_structured_output("plain", &AgentContext::empty());
+        let actions = output.take_actions_or(vec![AgentAction::Generate3D {
+            prompt: "a chair".into(),
+            model: "trellis".into(),
+        }]);
+        assert_eq!(actions.len(), 1);
+    }
+}
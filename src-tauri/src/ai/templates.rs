@@ -36,6 +36,13 @@ Always check the Vault before generating new content to maintain consistency.
 pub fn inject_context(base_prompt: &str, context: &AgentContext) -> String {
     let mut prompt = base_prompt.to_string();
 
+    // Inject the project's established facts
+    if let Some(memory) = context.project_memory.as_ref().filter(|m| !m.is_empty()) {
+        prompt.push_str("\n\n");
+        prompt.push_str(&memory.to_prompt_section());
+        prompt.push('\n');
+    }
+
     // Inject Vault tokens
     if let Some(vault) = &context.vault {
         if !vault.characters.is_empty() || !vault.locations.is_empty() || !vault.props.is_empty() {
@@ -88,10 +95,12 @@ You generate high-quality still images for the project.
 - Concept art & storyboards
 - Reference images for shots
 - Character reference sheets
+- Image edits ("change the sky to sunset") via the EditImage action
+- Removing objects ("remove the car from this shot") or extending a frame via the FillImage action
 
 ## Models Available
-- **Local**: FLUX Schnell (4-step, fast previews)
-- **Cloud**: FLUX 2 Pro, Kling Image O1, Imagen 4
+- **Local**: FLUX Schnell (4-step, fast previews), FLUX Fill (masked edits)
+- **Cloud**: FLUX 2 Pro, Kling Image O1, Imagen 4, FLUX Kontext (whole-image edits), FLUX Fill (inpainting/outpainting)
 
 ## Workflow
 1. Parse user intent (what image to generate)
@@ -0,0 +1,214 @@
+//! Token Budget - Keep prompts inside the model's context window
+//!
+//! Uses a simple ~4 characters per token heuristic (close to tiktoken for
+//! English prose) to estimate prompt size. When a request would overflow the
+//! model's window minus the reserved output tokens, the oldest history is
+//! dropped first, then the script context is shortened.
+
+use crate::ai::llm_client::LLMMessage;
+use crate::ai::models::get_all_models;
+
+/// Fallback window for models missing from the Model Matrix
+pub const DEFAULT_CONTEXT_WINDOW: u32 = 32_768;
+
+/// Approximate characters per token
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Per-message framing overhead (role markers, separators)
+const MESSAGE_OVERHEAD_TOKENS: u32 = 4;
+
+/// Marker inserted where script context was cut
+const OMITTED_MARKER: &str =
+    "\n\n[... script context omitted to fit the model's context window ...]\n\n";
+
+/// Estimate the token count of a piece of text
+pub fn estimate_tokens(text: &str) -> u32 {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN) as u32
+}
+
+fn estimate_message(message: &LLMMessage) -> u32 {
+    estimate_tokens(&message.content) + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// Context window for a model id, falling back to [`DEFAULT_CONTEXT_WINDOW`]
+pub fn context_window_for(model: &str) -> u32 {
+    get_all_models()
+        .into_iter()
+        .find(|m| m.id == model)
+        .map(|m| m.context_window)
+        .filter(|w| *w > 0)
+        .unwrap_or(DEFAULT_CONTEXT_WINDOW)
+}
+
+/// Prompt budget: the model's window minus tokens reserved for the reply
+pub fn prompt_budget(model: &str, max_tokens: u32) -> u32 {
+    context_window_for(model).saturating_sub(max_tokens)
+}
+
+/// A prompt after it has been fitted to the budget
+#[derive(Debug, Clone)]
+pub struct BudgetedPrompt {
+    pub history: Vec<LLMMessage>,
+    pub context: Option<String>,
+    /// Oldest history messages that were dropped
+    pub dropped_messages: usize,
+    /// Whether the script context had to be shortened
+    pub context_truncated: bool,
+    /// Estimated prompt tokens after trimming
+    pub estimated_tokens: u32,
+}
+
+impl BudgetedPrompt {
+    pub fn was_trimmed(&self) -> bool {
+        self.dropped_messages > 0 || self.context_truncated
+    }
+}
+
+/// Fit system prompt, context, history and the user message into `budget` tokens.
+///
+/// The system prompt and the user message are never trimmed.
+pub fn fit_to_budget(
+    system_prompt: &str,
+    context: Option<String>,
+    history: Vec<LLMMessage>,
+    user_message: &str,
+    budget: u32,
+) -> BudgetedPrompt {
+    let fixed =
+        estimate_tokens(system_prompt) + estimate_tokens(user_message) + MESSAGE_OVERHEAD_TOKENS;
+    let context_tokens = context.as_deref().map(estimate_tokens).unwrap_or(0);
+    let mut history_tokens: u32 = history.iter().map(estimate_message).sum();
+
+    // 1. Drop oldest history until it fits (or history is gone)
+    let mut dropped_messages = 0;
+    while fixed + context_tokens + history_tokens > budget && dropped_messages < history.len() {
+        history_tokens -= estimate_message(&history[dropped_messages]);
+        dropped_messages += 1;
+    }
+    let history: Vec<LLMMessage> = history.into_iter().skip(dropped_messages).collect();
+
+    // 2. Shorten the script context, keeping its beginning and end
+    let mut context_truncated = false;
+    let context = match context {
+        Some(ctx) if fixed + context_tokens + history_tokens > budget => {
+            context_truncated = true;
+            let available =
+                budget.saturating_sub(fixed + history_tokens + estimate_tokens(OMITTED_MARKER));
+            let keep_chars = available as usize * CHARS_PER_TOKEN;
+            if keep_chars == 0 {
+                None
+            } else {
+                let chars: Vec<char> = ctx.chars().collect();
+                let head: String = chars[..keep_chars / 2].iter().collect();
+                let tail: String = chars[chars.len() - keep_chars / 2..].iter().collect();
+                Some(format!("{}{}{}", head, OMITTED_MARKER, tail))
+            }
+        }
+        other => other,
+    };
+
+    if dropped_messages > 0 {
+        tracing::warn!(
+            "Prompt over budget ({} tokens): dropped {} oldest history message(s)",
+            budget,
+            dropped_messages
+        );
+    }
+    if context_truncated {
+        tracing::warn!(
+            "Prompt over budget ({} tokens): shortened script context from ~{} tokens",
+            budget,
+            context_tokens
+        );
+    }
+
+    let estimated_tokens =
+        fixed + history_tokens + context.as_deref().map(estimate_tokens).unwrap_or(0);
+
+    BudgetedPrompt {
+        history,
+        context,
+        dropped_messages,
+        context_truncated,
+        estimated_tokens,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(content: &str) -> LLMMessage {
+        LLMMessage {
+            role: "user".into(),
+            content: content.into(),
+            images: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+
+    #[test]
+    fn test_context_window_lookup() {
+        assert_eq!(context_window_for("claude-4.5-sonnet"), 500_000);
+        assert_eq!(context_window_for("unknown-model"), DEFAULT_CONTEXT_WINDOW);
+        // Image models report 0 and fall back to the default
+        assert_eq!(context_window_for("flux-pro-2.0"), DEFAULT_CONTEXT_WINDOW);
+        assert_eq!(prompt_budget("qwen-3-max", 4096), 32_768 - 4096);
+    }
+
+    #[test]
+    fn test_fits_exactly_at_budget() {
+        let history = vec![msg(&"a".repeat(40))]; // 10 + 4 tokens
+        let system = "s".repeat(40); // 10 tokens
+        let user = "u".repeat(40); // 10 + 4 tokens
+        let budget = 10 + 14 + 14;
+
+        let fitted = fit_to_budget(&system, None, history.clone(), &user, budget);
+        assert!(!fitted.was_trimmed());
+        assert_eq!(fitted.history.len(), 1);
+        assert_eq!(fitted.estimated_tokens, budget);
+
+        // One token less forces the history out
+        let fitted = fit_to_budget(&system, None, history, &user, budget - 1);
+        assert!(fitted.was_trimmed());
+        assert_eq!(fitted.dropped_messages, 1);
+        assert!(fitted.history.is_empty());
+    }
+
+    #[test]
+    fn test_drops_oldest_history_first() {
+        let history = vec![
+            msg("oldest message"),
+            msg("middle message"),
+            msg("newest message"),
+        ];
+        let full: u32 = history.iter().map(estimate_message).sum();
+        let budget = full - 1;
+
+        let fitted = fit_to_budget("", None, history, "", budget);
+        assert_eq!(fitted.dropped_messages, 1);
+        assert_eq!(fitted.history[0].content, "middle message");
+        assert!(!fitted.context_truncated);
+        assert!(fitted.estimated_tokens <= budget);
+    }
+
+    #[test]
+    fn test_truncates_context_when_history_is_not_enough() {
+        let context = format!("{}{}", "A".repeat(2000), "Z".repeat(2000));
+        let fitted = fit_to_budget("", Some(context), vec![msg("hi")], "question", 200);
+
+        assert_eq!(fitted.dropped_messages, 1);
+        assert!(fitted.context_truncated);
+        let ctx = fitted.context.unwrap();
+        assert!(ctx.starts_with('A'));
+        assert!(ctx.ends_with('Z'));
+        assert!(ctx.contains("omitted"));
+        assert!(fitted.estimated_tokens <= 200);
+    }
+}
@@ -0,0 +1,155 @@
+//! Token Extractor - Characters, locations and props read by an LLM
+//!
+//! The scene parser only sees characters once they speak and props from a
+//! keyword list. The LLM reads the whole script and also catches characters
+//! introduced in action and props the list doesn't know. Callers fall back to
+//! the parser when no key is set or the reply isn't usable.
+
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::env;
+
+use crate::ai::llm_client::{get_llm_client, LLMMessage, LLMProvider, LLMRequest};
+use crate::ai::structured_output::extract_json;
+use crate::vault::tokens::{ExtractedEntity, ExtractedTokens};
+
+/// Long-context model, cheap enough for a whole script
+const EXTRACTOR_MODEL: &str = "gemini-2.5-flash";
+
+const EXTRACTOR_SYSTEM_PROMPT: &str = r#"You are a script supervisor breaking down a screenplay.
+List every character (including those introduced only in action), every location and every prop that matters to the story or the shot.
+Use the name as written in the script (characters in capitals). Describe each in one short visual sentence.
+
+Reply with a single JSON object and nothing else:
+{
+  "characters": [{ "name": "ANNA", "description": "...", "first_appearance": "the line where it first appears" }],
+  "locations": [{ "name": "...", "description": "...", "first_appearance": "..." }],
+  "props": [{ "name": "...", "description": "...", "first_appearance": "..." }]
+}"#;
+
+#[derive(Debug, Deserialize)]
+struct LlmEntity {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    first_appearance: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LlmTokens {
+    #[serde(default)]
+    characters: Vec<LlmEntity>,
+    #[serde(default)]
+    locations: Vec<LlmEntity>,
+    #[serde(default)]
+    props: Vec<LlmEntity>,
+}
+
+/// Whether the extractor's provider has an API key
+pub fn llm_available() -> bool {
+    ["GOOGLE_API_KEY", "GEMINI_API_KEY"]
+        .iter()
+        .any(|var| env::var(var).is_ok_and(|key| !key.trim().is_empty()))
+}
+
+/// Ask the LLM for the script's tokens
+pub async fn extract_tokens_with_llm(script: &str) -> Result<ExtractedTokens, String> {
+    if script.trim().is_empty() {
+        return Err("Nothing to extract: the script is empty".to_string());
+    }
+
+    let response = get_llm_client()
+        .chat(LLMRequest {
+            provider: LLMProvider::Gemini,
+            model: EXTRACTOR_MODEL.to_string(),
+            messages: vec![LLMMessage {
+                role: "user".to_string(),
+                content: script.to_string(),
+                images: Vec::new(),
+            }],
+            temperature: Some(0.0),
+            max_tokens: Some(4000),
+            system_prompt: Some(EXTRACTOR_SYSTEM_PROMPT.to_string()),
+        })
+        .await?;
+
+    parse_tokens(&response.content, script)
+        .ok_or_else(|| "The model did not return the token JSON".to_string())
+}
+
+/// Validated tokens from the completion, with mentions counted in `script`
+fn parse_tokens(raw: &str, script: &str) -> Option<ExtractedTokens> {
+    let tokens: LlmTokens = serde_json::from_str(extract_json(raw)?).ok()?;
+    let script = script.to_lowercase();
+
+    let entities = |list: Vec<LlmEntity>| -> Vec<ExtractedEntity> {
+        let mut seen = HashSet::new();
+        list.into_iter()
+            .filter_map(|entity| {
+                let name = entity.name.trim().to_string();
+                if name.is_empty() || !seen.insert(name.to_lowercase()) {
+                    return None;
+                }
+                let mentions = script.matches(&name.to_lowercase()).count().max(1);
+                Some(ExtractedEntity {
+                    description: entity.description.trim().to_string(),
+                    mentions: u32::try_from(mentions).unwrap_or(u32::MAX),
+                    first_appearance: entity.first_appearance.trim().to_string(),
+                    name,
+                    aliases: Vec::new(),
+                })
+            })
+            .collect()
+    };
+
+    let extracted = ExtractedTokens {
+        characters: entities(tokens.characters),
+        locations: entities(tokens.locations),
+        props: entities(tokens.props),
+        proposed_merges: Vec::new(),
+    };
+
+    // A script with nothing in it is more likely a reply we misread
+    let empty = extracted.characters.is_empty()
+        && extracted.locations.is_empty()
+        && extracted.props.is_empty();
+    (!empty).then_some(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "INT. BAR - NIGHT\n\nMARLOWE, 40s, nurses a whiskey. \
+                          VIVIAN slides a photograph across the bar.\n\nMARLOWE\nWho is she?";
+
+    #[test]
+    fn test_parse_tokens_validates_and_counts() {
+        let raw = r#"Here you go:
+```json
+{"characters": [
+   {"name": "MARLOWE", "description": "A tired detective", "first_appearance": "MARLOWE, 40s, nurses a whiskey."},
+   {"name": "VIVIAN", "description": "Elegant, guarded"},
+   {"name": "Marlowe", "description": "duplicate"},
+   {"name": "  ", "description": "nameless"}
+ ],
+ "locations": [{"name": "BAR", "description": "Dim and smoky"}],
+ "props": [{"name": "photograph", "description": "A creased black-and-white photo"}]}
+```"#;
+        let tokens = parse_tokens(raw, SCRIPT).unwrap();
+
+        let names: Vec<_> = tokens.characters.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["MARLOWE", "VIVIAN"]);
+        assert_eq!(tokens.characters[0].mentions, 2);
+        assert_eq!(tokens.characters[1].mentions, 1);
+        assert_eq!(tokens.props[0].name, "photograph");
+    }
+
+    #[test]
+    fn test_unusable_replies_are_rejected() {
+        assert!(parse_tokens("I can't help with that.", SCRIPT).is_none());
+        assert!(parse_tokens(r#"{"characters": "MARLOWE"}"#, SCRIPT).is_none());
+        assert!(parse_tokens(r#"{"characters": [], "props": []}"#, SCRIPT).is_none());
+    }
+}
@@ -0,0 +1,334 @@
+//! Transcription - Local Whisper speech-to-text for imported media
+//!
+//! Audio is extracted to 16 kHz mono WAV with ffmpeg, then transcribed by a
+//! whisper.cpp binary using a ggml model from the model downloader
+//! (`whisper-*` sources, stored under `models/audio`). Nothing leaves the
+//! machine.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+use crate::ai::dialogue::ffmpeg_binary;
+use crate::installer::{get_cinema_os_dir, get_model_path, get_model_sources};
+
+/// Model used when the caller doesn't pick one
+pub const DEFAULT_WHISPER_MODEL: &str = "whisper-base";
+
+/// Share of the progress bar taken by audio extraction
+const EXTRACT_WEIGHT: f32 = 0.05;
+
+/// stderr lines kept for error messages
+const STDERR_TAIL: usize = 10;
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct TranscriptSegment {
+    #[specta(type = f64)]
+    pub start_ms: u64,
+    #[specta(type = f64)]
+    pub end_ms: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct Transcript {
+    pub source_path: String,
+    pub model: String,
+    /// Detected language code ("en", "es", ...)
+    pub language: Option<String>,
+    pub segments: Vec<TranscriptSegment>,
+    /// End of the last segment
+    #[specta(type = f64)]
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct TranscriptionProgress {
+    pub source_path: String,
+    /// 0.0 - 1.0
+    pub progress: f32,
+    pub message: String,
+}
+
+/// whisper.cpp `-oj` output
+#[derive(Deserialize)]
+struct WhisperOutput {
+    #[serde(default)]
+    result: Option<WhisperResult>,
+    transcription: Vec<WhisperSegment>,
+}
+
+#[derive(Deserialize)]
+struct WhisperResult {
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WhisperSegment {
+    offsets: WhisperOffsets,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct WhisperOffsets {
+    from: u64,
+    to: u64,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TRANSCRIPTION
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Transcribe an audio or video file with a downloaded Whisper model
+pub async fn transcribe_media(
+    path: &str,
+    model: Option<&str>,
+    on_progress: impl Fn(TranscriptionProgress),
+) -> Result<Transcript, String> {
+    let input = Path::new(path);
+    if !input.is_file() {
+        return Err(format!("File not found: {}", path));
+    }
+
+    let model_id = model.unwrap_or(DEFAULT_WHISPER_MODEL);
+    let model_path = whisper_model_path(model_id)?;
+    let report = |progress: f32, message: &str| {
+        on_progress(TranscriptionProgress {
+            source_path: path.to_string(),
+            progress,
+            message: message.to_string(),
+        })
+    };
+
+    let work_dir = get_cinema_os_dir().join("transcripts");
+    std::fs::create_dir_all(&work_dir).map_err(|e| e.to_string())?;
+    let job = uuid::Uuid::new_v4().to_string();
+    let wav = work_dir.join(format!("{}.wav", job));
+    let output_base = work_dir.join(&job);
+
+    report(0.0, "Extracting audio");
+    extract_audio(input, &wav).await?;
+
+    report(EXTRACT_WEIGHT, "Transcribing");
+    let result = run_whisper(&model_path, &wav, &output_base, |percent| {
+        let progress = EXTRACT_WEIGHT + (1.0 - EXTRACT_WEIGHT) * percent / 100.0;
+        report(progress, &format!("Transcribing ({:.0}%)", percent));
+    })
+    .await;
+    std::fs::remove_file(&wav).ok();
+    result?;
+
+    let json_path = work_dir.join(format!("{}.json", job));
+    let raw = std::fs::read_to_string(&json_path)
+        .map_err(|e| format!("Whisper produced no transcript: {}", e))?;
+    std::fs::remove_file(&json_path).ok();
+
+    let (language, segments) = parse_whisper_output(&raw)?;
+    report(1.0, "Transcription complete");
+
+    Ok(Transcript {
+        source_path: path.to_string(),
+        model: model_id.to_string(),
+        language,
+        duration_ms: segments.last().map(|s| s.end_ms).unwrap_or(0),
+        segments,
+    })
+}
+
+/// Path of a downloaded Whisper model
+fn whisper_model_path(model_id: &str) -> Result<PathBuf, String> {
+    let source = get_model_sources()
+        .into_iter()
+        .find(|s| s.id == model_id && s.id.starts_with("whisper"))
+        .ok_or_else(|| format!("Unknown Whisper model: {}", model_id))?;
+
+    let path = get_model_path(&source.id, &source.filename);
+    if !path.exists() {
+        return Err(format!(
+            "{} is not downloaded yet; download it from the model manager first",
+            source.name
+        ));
+    }
+    Ok(path)
+}
+
+/// whisper.cpp CLI: `WHISPER_CPP_PATH`, the bundled binary, or `whisper-cli` on PATH
+fn whisper_binary() -> PathBuf {
+    if let Ok(path) = std::env::var("WHISPER_CPP_PATH") {
+        return PathBuf::from(path);
+    }
+
+    let name = if cfg!(windows) {
+        "whisper-cli.exe"
+    } else {
+        "whisper-cli"
+    };
+    let bundled = get_cinema_os_dir().join("bin").join(name);
+    if bundled.exists() {
+        bundled
+    } else {
+        PathBuf::from(name)
+    }
+}
+
+/// Convert any media file to the 16 kHz mono PCM WAV whisper.cpp expects
+async fn extract_audio(input: &Path, wav: &Path) -> Result<(), String> {
+    let output = Command::new(ffmpeg_binary("FFMPEG_PATH", "ffmpeg"))
+        .args(["-y", "-i"])
+        .arg(input)
+        .args(["-vn", "-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le"])
+        .arg(wav)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Could not extract audio: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+/// Run whisper.cpp, reporting its progress percentage as it goes
+async fn run_whisper(
+    model: &Path,
+    wav: &Path,
+    output_base: &Path,
+    on_percent: impl Fn(f32),
+) -> Result<(), String> {
+    let mut child = Command::new(whisper_binary())
+        .arg("-m")
+        .arg(model)
+        .arg("-f")
+        .arg(wav)
+        // JSON output with print-progress on stderr
+        .args(["-oj", "-pp", "-of"])
+        .arg(output_base)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to run whisper.cpp (is it installed?): {}", e))?;
+
+    let mut tail = Vec::new();
+    if let Some(stderr) = child.stderr.take() {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(percent) = parse_progress(&line) {
+                on_percent(percent);
+            }
+            tail.push(line);
+            if tail.len() > STDERR_TAIL {
+                tail.remove(0);
+            }
+        }
+    }
+
+    let status = child.wait().await.map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("whisper.cpp failed: {}", tail.join("\n")));
+    }
+    Ok(())
+}
+
+/// Percentage from a whisper.cpp progress line
+/// ("whisper_print_progress_callback: progress =  42%")
+fn parse_progress(line: &str) -> Option<f32> {
+    let (_, rest) = line.split_once("progress =")?;
+    rest.trim().trim_end_matches('%').trim().parse().ok()
+}
+
+/// Detected language and non-empty segments from whisper.cpp JSON output
+fn parse_whisper_output(raw: &str) -> Result<(Option<String>, Vec<TranscriptSegment>), String> {
+    let output: WhisperOutput =
+        serde_json::from_str(raw).map_err(|e| format!("Invalid whisper.cpp output: {}", e))?;
+
+    let segments = output
+        .transcription
+        .into_iter()
+        .map(|s| TranscriptSegment {
+            start_ms: s.offsets.from,
+            end_ms: s.offsets.to,
+            text: s.text.trim().to_string(),
+        })
+        .filter(|s| !s.text.is_empty())
+        .collect();
+
+    Ok((output.result.and_then(|r| r.language), segments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::installer::is_model_downloaded;
+
+    #[test]
+    fn test_parse_whisper_output() {
+        let raw = r#"{
+            "result": { "language": "en" },
+            "transcription": [
+                { "timestamps": { "from": "00:00:00,000", "to": "00:00:02,480" },
+                  "offsets": { "from": 0, "to": 2480 },
+                  "text": " And so my fellow Americans," },
+                { "timestamps": { "from": "00:00:02,480", "to": "00:00:02,500" },
+                  "offsets": { "from": 2480, "to": 2500 }, "text": " " },
+                { "timestamps": { "from": "00:00:02,500", "to": "00:00:07,900" },
+                  "offsets": { "from": 2500, "to": 7900 },
+                  "text": " ask not what your country can do for you." }
+            ]
+        }"#;
+
+        let (language, segments) = parse_whisper_output(raw).unwrap();
+        assert_eq!(language.as_deref(), Some("en"));
+        assert_eq!(segments.len(), 2);
+        assert_eq!(
+            segments[0],
+            TranscriptSegment {
+                start_ms: 0,
+                end_ms: 2480,
+                text: "And so my fellow Americans,".into(),
+            }
+        );
+        assert_eq!(segments[1].end_ms, 7900);
+    }
+
+    #[test]
+    fn test_parse_progress() {
+        assert_eq!(
+            parse_progress("whisper_print_progress_callback: progress =  42%"),
+            Some(42.0)
+        );
+        assert_eq!(
+            parse_progress("whisper_init_from_file: loading model"),
+            None
+        );
+    }
+
+    /// Needs ffmpeg, whisper.cpp and a downloaded `whisper-base`; point
+    /// `WHISPER_SAMPLE` at a short speech clip to run it
+    #[tokio::test]
+    async fn test_transcribe_sample() {
+        let Ok(sample) = std::env::var("WHISPER_SAMPLE") else {
+            return;
+        };
+        if !is_model_downloaded(DEFAULT_WHISPER_MODEL) {
+            return;
+        }
+
+        let transcript = transcribe_media(&sample, None, |_| {}).await.unwrap();
+        assert!(!transcript.segments.is_empty());
+        assert!(transcript
+            .segments
+            .iter()
+            .all(|s| s.end_ms >= s.start_ms && !s.text.is_empty()));
+    }
+}
@@ -3,12 +3,17 @@
 //! Generates ComfyUI workflow JSON by injecting values into templates.
 //! Defines the "Neuro-System" payload structure.
 
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use specta::Type;
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+use crate::ai::comfyui::CinemaOSNode;
+use crate::ai::model_params::ModelParams;
+use crate::ai::resolution::{validate_dimensions, ValidatedSize};
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // TYPES
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -19,6 +24,54 @@ pub enum WorkflowType {
     ImageToImage,
     TextToVideo,
     ImageToVideo,
+    /// Repaint the masked area of `input_image`
+    Inpaint,
+    /// Extend `input_image` by `expand_pixels` on every side
+    Outpaint,
+}
+
+/// ControlNet conditioning applied to image generation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub enum ControlType {
+    Canny,
+    Depth,
+    /// Body, hands and face (DWPose)
+    Pose,
+    /// Body keypoints only (classic OpenPose)
+    OpenPose,
+}
+
+/// How strongly a control map steers the image when a request doesn't say
+pub const DEFAULT_CONTROL_STRENGTH: f32 = 0.8;
+
+fn default_control_strength() -> f32 {
+    DEFAULT_CONTROL_STRENGTH
+}
+
+/// Structure (edges, depth, pose) taken from a reference image
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct ControlNetConfig {
+    pub control_type: ControlType,
+    /// Conditioning image (file name in ComfyUI's input folder)
+    pub image: String,
+    /// 0.0 ignores the control map, 1.0 follows it closely (up to 2.0)
+    #[serde(default = "default_control_strength")]
+    pub strength: f32,
+}
+
+/// Largest batch a single execution may produce
+pub const MAX_BATCH_SIZE: u32 = 8;
+
+/// Largest seed that survives the round trip through JavaScript numbers
+const MAX_SEED: i64 = (1 << 53) - 1;
+
+pub(crate) fn default_batch_size() -> u32 {
+    1
+}
+
+/// The request's seed, or a fresh random one
+fn resolve_seed(seed: Option<i64>) -> i64 {
+    seed.unwrap_or_else(|| rand::thread_rng().gen_range(0..=MAX_SEED))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -30,9 +83,25 @@ pub struct WorkflowRequest {
     pub width: u32,
     pub height: u32,
     pub steps: Option<u32>,
+    /// Sampler seed; a random one is picked (and returned) when omitted
+    #[specta(type = Option<f64>)]
     pub seed: Option<i64>,
+    /// Images generated in one execution
+    #[serde(default = "default_batch_size")]
+    pub batch_size: u32,
     pub input_image: Option<String>,
     pub force_local: Option<bool>,
+    #[serde(default)]
+    pub control: Option<ControlNetConfig>,
+    /// Inpaint: white areas are repainted (file name in ComfyUI's input folder)
+    #[serde(default)]
+    pub mask_image: Option<String>,
+    /// Outpaint: pixels added on each side of `input_image`
+    #[serde(default)]
+    pub expand_pixels: Option<u32>,
+    /// Model-specific params (FLUX guidance, ...); defaults when omitted
+    #[serde(default)]
+    pub params: Option<ModelParams>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -40,6 +109,15 @@ pub struct GeneratedWorkflow {
     pub workflow_json: String,
     pub estimated_cost: f64,
     pub is_local: bool,
+    /// Output size after snapping to the model's valid dimensions
+    pub width: u32,
+    pub height: u32,
+    /// Seed the sampler runs with; pass it back to reproduce the output
+    #[specta(type = f64)]
+    pub seed: i64,
+    /// Adjustments made to the requested size
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -51,13 +129,49 @@ pub fn generate_workflow(request: &WorkflowRequest) -> Result<GeneratedWorkflow,
     // 1. Determine Execution Mode (Local/Cloud)
     // In a real implementation this would call `router.rs`
     let is_local = request.force_local.unwrap_or(false);
+    let seed = resolve_seed(request.seed);
+
+    if request.batch_size == 0 || request.batch_size > MAX_BATCH_SIZE {
+        return Err(format!(
+            "batch_size must be between 1 and {}, got {}",
+            MAX_BATCH_SIZE, request.batch_size
+        ));
+    }
+    if request.batch_size > 1
+        && !matches!(
+            request.workflow_type,
+            WorkflowType::TextToImage | WorkflowType::ImageToImage
+        )
+    {
+        return Err(format!(
+            "{:?} workflows generate one output at a time",
+            request.workflow_type
+        ));
+    }
+
+    let fill = fill_inputs(request)?;
+    let start_image = match request.workflow_type {
+        WorkflowType::ImageToImage => Some(
+            request
+                .input_image
+                .as_deref()
+                .ok_or("ImageToImage needs an input_image")?,
+        ),
+        _ => None,
+    };
+    if let (Some(fill), false) = (&fill, is_local) {
+        return fill_cloud_workflow(request, fill, seed);
+    }
 
     // 2. Select Template File
     let template_name = match request.workflow_type {
         WorkflowType::TextToImage => "t2i_flux.json",
-        WorkflowType::ImageToImage => "t2i_flux.json", // Reuse with adapters? Or i2i.json
+        // The text-to-image graph with the empty latent swapped for the input
+        WorkflowType::ImageToImage => "t2i_flux.json",
         WorkflowType::TextToVideo => "start_frame_init.json",
         WorkflowType::ImageToVideo => "i2v.json",
+        // The text-to-image graph with the empty latent swapped for the source
+        WorkflowType::Inpaint | WorkflowType::Outpaint => "t2i_flux.json",
     };
 
     // 3. Load Template String
@@ -67,28 +181,41 @@ pub fn generate_workflow(request: &WorkflowRequest) -> Result<GeneratedWorkflow,
     let template_str = std::fs::read_to_string(&template_path)
         .map_err(|e| format!("Failed to read template {}: {}", template_name, e))?;
 
-    // 4. Prepare Variables
+    // 4. Prepare Variables (dimensions snapped to what the model accepts;
+    // fills keep the source image's size)
+    let size = match &fill {
+        Some(fill) => fill.output_size(request),
+        None => validate_dimensions(&request.model, request.width, request.height),
+    };
+    for warning in &size.warnings {
+        tracing::warn!("{}", warning);
+    }
+
     let mut variables = HashMap::new();
     variables.insert("{{PROMPT}}".to_string(), request.prompt.clone());
     variables.insert(
         "{{NEGATIVE_PROMPT}}".to_string(),
         request.negative_prompt.clone().unwrap_or_default(),
     );
-    variables.insert("{{WIDTH}}".to_string(), request.width.to_string());
-    variables.insert("{{HEIGHT}}".to_string(), request.height.to_string());
-    variables.insert(
-        "{{SEED}}".to_string(),
-        request.seed.unwrap_or(0).to_string(),
-    );
-    variables.insert(
-        "{{STEPS}}".to_string(),
-        request.steps.unwrap_or(20).to_string(),
-    );
+    variables.insert("{{WIDTH}}".to_string(), size.width.to_string());
+    variables.insert("{{HEIGHT}}".to_string(), size.height.to_string());
+    variables.insert("{{SEED}}".to_string(), seed.to_string());
+    let flux = match &request.params {
+        Some(ModelParams::Flux(flux)) => Some(flux),
+        _ => None,
+    };
+    let steps = request
+        .steps
+        .or(flux.map(|f| f.num_inference_steps))
+        .unwrap_or(20);
+    variables.insert("{{STEPS}}".to_string(), steps.to_string());
 
     // Model Filename Mapping (Should come from models.rs in strict mode)
     let model_filename = match request.model.as_str() {
         "flux-schnell" => "flux1-schnell.safetensors",
         "flux-dev" => "flux1-dev.safetensors",
+        "flux-canny" => "flux1-canny-dev.safetensors",
+        "flux-depth" => "flux1-depth-dev.safetensors",
         "sdxl" => "sd_xl_base_1.0.safetensors",
         _ => "flux1-schnell.safetensors",
     };
@@ -105,13 +232,685 @@ pub fn generate_workflow(request: &WorkflowRequest) -> Result<GeneratedWorkflow,
     }
 
     // 6. Validate JSON
-    let _valid_json: Value = serde_json::from_str(&final_json)
+    let mut workflow: Value = serde_json::from_str(&final_json)
         .map_err(|e| format!("Template injection produced invalid JSON: {}", e))?;
 
+    // 7. Seed and batch size as numbers (templates carry them as strings)
+    apply_seed_and_batch(&mut workflow, seed, request.batch_size)?;
+    final_json = workflow.to_string();
+
+    // 8. Inpaint/outpaint/img2img: encode the source instead of an empty latent
+    if let Some(fill) = &fill {
+        apply_fill(&mut workflow, fill)?;
+        final_json = workflow.to_string();
+    }
+    if let Some(image) = start_image {
+        apply_image_to_image(&mut workflow, image, request.batch_size)?;
+        final_json = workflow.to_string();
+    }
+
+    // 9. ControlNet conditioning, chained in before the sampler
+    if let Some(control) = &request.control {
+        if !matches!(
+            request.workflow_type,
+            WorkflowType::TextToImage | WorkflowType::ImageToImage
+        ) {
+            return Err("ControlNet conditioning is only supported for image workflows".into());
+        }
+        if !(0.0..=2.0).contains(&control.strength) {
+            return Err(format!(
+                "ControlNet strength must be between 0 and 2, got {}",
+                control.strength
+            ));
+        }
+        if native_control(&request.model) == Some(control.control_type) {
+            if start_image.is_some() {
+                return Err(format!(
+                    "{} starts from the control map and cannot take an input_image",
+                    request.model
+                ));
+            }
+            apply_native_control(&mut workflow, control)?;
+        } else {
+            let controlnet =
+                controlnet_filename(&request.model, control.control_type).ok_or_else(|| {
+                    format!(
+                        "Model {} does not support {:?} conditioning",
+                        request.model, control.control_type
+                    )
+                })?;
+            apply_controlnet(&mut workflow, control, controlnet)?;
+        }
+        final_json = workflow.to_string();
+    }
+
+    // 10. Model-specific params
+    if let Some(params) = &request.params {
+        params.validate()?;
+    }
+    if let Some(flux) = flux {
+        for node in workflow
+            .as_object_mut()
+            .into_iter()
+            .flat_map(|n| n.values_mut())
+        {
+            if node["class_type"] == "KSampler" {
+                node["inputs"]["cfg"] = flux.guidance_scale.into();
+            }
+        }
+        final_json = workflow.to_string();
+    }
+
     Ok(GeneratedWorkflow {
         workflow_json: final_json,
         estimated_cost: 0.0, // TODO: Implement cost calculator
         is_local,
+        width: size.width,
+        height: size.height,
+        seed,
+        warnings: size.warnings,
+    })
+}
+
+/// Set every sampler's seed and the empty latent's batch dimension
+fn apply_seed_and_batch(workflow: &mut Value, seed: i64, batch_size: u32) -> Result<(), String> {
+    let mut batched = false;
+    for node in workflow
+        .as_object_mut()
+        .into_iter()
+        .flat_map(|n| n.values_mut())
+    {
+        if node["class_type"] == "KSampler" {
+            node["inputs"]["seed"] = seed.into();
+        } else if node["class_type"] == "EmptyLatentImage" {
+            node["inputs"]["batch_size"] = batch_size.into();
+            batched = true;
+        }
+    }
+
+    if batch_size > 1 && !batched {
+        return Err("Workflow has no EmptyLatentImage to batch".into());
+    }
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// CONTROLNET
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Node ids for the inserted ControlNet nodes (templates use ids below 20)
+const CONTROL_IMAGE_NODE: &str = "20";
+const CONTROL_PREPROCESS_NODE: &str = "21";
+const CONTROLNET_LOADER_NODE: &str = "22";
+const CONTROLNET_APPLY_NODE: &str = "23";
+
+/// FLUX.1 Canny/Depth Dev take their control map directly, without a ControlNet
+fn native_control(model: &str) -> Option<ControlType> {
+    match model {
+        "flux-canny" => Some(ControlType::Canny),
+        "flux-depth" => Some(ControlType::Depth),
+        _ => None,
+    }
+}
+
+/// ControlNet weights for a model, or `None` if it doesn't support `control_type`
+pub fn controlnet_filename(model: &str, control_type: ControlType) -> Option<&'static str> {
+    match (model, control_type) {
+        ("flux-schnell" | "flux-dev", ControlType::Canny) => {
+            Some("flux-canny-controlnet-v3.safetensors")
+        }
+        ("flux-schnell" | "flux-dev", ControlType::Depth) => {
+            Some("flux-depth-controlnet-v3.safetensors")
+        }
+        ("sdxl", ControlType::Canny) => Some("controlnet-canny-sdxl-1.0.safetensors"),
+        ("sdxl", ControlType::Depth) => Some("controlnet-depth-sdxl-1.0.safetensors"),
+        ("sdxl", ControlType::Pose | ControlType::OpenPose) => {
+            Some("controlnet-openpose-sdxl-1.0.safetensors")
+        }
+        _ => None,
+    }
+}
+
+/// Preprocessor node turning the conditioning image into a control map
+fn preprocessor_node(control_type: ControlType) -> Value {
+    let image = serde_json::json!([CONTROL_IMAGE_NODE, 0]);
+    match control_type {
+        ControlType::Canny => serde_json::json!({
+            "class_type": "Canny",
+            "inputs": { "image": image, "low_threshold": 0.4, "high_threshold": 0.8 }
+        }),
+        ControlType::Depth => serde_json::json!({
+            "class_type": "DepthAnythingV2Preprocessor",
+            "inputs": {
+                "image": image,
+                "ckpt_name": "depth_anything_v2_vitl.pth",
+                "resolution": 1024
+            }
+        }),
+        ControlType::Pose => serde_json::json!({
+            "class_type": "DWPreprocessor",
+            "inputs": {
+                "image": image,
+                "detect_hand": "enable",
+                "detect_body": "enable",
+                "detect_face": "enable",
+                "resolution": 1024,
+                "bbox_detector": "yolox_l.onnx",
+                "pose_estimator": "dw-ll_ucoco_384_bs5.torchscript.pt"
+            }
+        }),
+        ControlType::OpenPose => serde_json::json!({
+            "class_type": "OpenposePreprocessor",
+            "inputs": {
+                "image": image,
+                "detect_hand": "disable",
+                "detect_body": "enable",
+                "detect_face": "disable",
+                "resolution": 1024
+            }
+        }),
+    }
+}
+
+/// Id of the workflow's KSampler and the inputs it is conditioned on
+fn sampler_conditioning(
+    nodes: &serde_json::Map<String, Value>,
+) -> Result<(String, Value, Value), String> {
+    let (sampler_id, sampler) = nodes
+        .iter()
+        .find(|(_, node)| node["class_type"] == "KSampler")
+        .ok_or("Workflow has no KSampler to condition")?;
+    Ok((
+        sampler_id.clone(),
+        sampler["inputs"]["positive"].clone(),
+        sampler["inputs"]["negative"].clone(),
+    ))
+}
+
+/// The VAE comes from the checkpoint loader the decoder uses
+fn decoder_vae(nodes: &serde_json::Map<String, Value>) -> Option<Value> {
+    nodes
+        .values()
+        .find(|node| node["class_type"] == "VAEDecode")
+        .map(|node| node["inputs"]["vae"].clone())
+}
+
+/// Add the conditioning image and its preprocessor
+fn insert_control_map(nodes: &mut serde_json::Map<String, Value>, control: &ControlNetConfig) {
+    nodes.insert(
+        CONTROL_IMAGE_NODE.into(),
+        serde_json::json!({ "class_type": "LoadImage", "inputs": { "image": control.image } }),
+    );
+    nodes.insert(
+        CONTROL_PREPROCESS_NODE.into(),
+        preprocessor_node(control.control_type),
+    );
+}
+
+/// Insert ControlNet nodes between the prompt encoders and the KSampler
+fn apply_controlnet(
+    workflow: &mut Value,
+    control: &ControlNetConfig,
+    controlnet: &str,
+) -> Result<(), String> {
+    let nodes = workflow
+        .as_object_mut()
+        .ok_or("Workflow is not a node map")?;
+
+    let (sampler_id, positive, negative) = sampler_conditioning(nodes)?;
+    let vae = decoder_vae(nodes);
+
+    insert_control_map(nodes, control);
+    nodes.insert(
+        CONTROLNET_LOADER_NODE.into(),
+        serde_json::json!({
+            "class_type": "ControlNetLoader",
+            "inputs": { "control_net_name": controlnet }
+        }),
+    );
+
+    let mut apply_inputs = serde_json::json!({
+        "positive": positive,
+        "negative": negative,
+        "control_net": [CONTROLNET_LOADER_NODE, 0],
+        "image": [CONTROL_PREPROCESS_NODE, 0],
+        "strength": control.strength,
+        "start_percent": 0.0,
+        "end_percent": 1.0
+    });
+    if let Some(vae) = vae {
+        apply_inputs["vae"] = vae;
+    }
+    nodes.insert(
+        CONTROLNET_APPLY_NODE.into(),
+        serde_json::json!({ "class_type": "ControlNetApplyAdvanced", "inputs": apply_inputs }),
+    );
+
+    let sampler = &mut nodes
+        .get_mut(&sampler_id)
+        .ok_or("Workflow has no KSampler to condition")?["inputs"];
+    sampler["positive"] = serde_json::json!([CONTROLNET_APPLY_NODE, 0]);
+    sampler["negative"] = serde_json::json!([CONTROLNET_APPLY_NODE, 1]);
+
+    Ok(())
+}
+
+/// Condition a FLUX.1 Canny/Depth model on the control map directly
+///
+/// These models were trained on control maps, so there is no ControlNet and
+/// no strength; the conditioning node also supplies the sampler's latent.
+fn apply_native_control(workflow: &mut Value, control: &ControlNetConfig) -> Result<(), String> {
+    let nodes = workflow
+        .as_object_mut()
+        .ok_or("Workflow is not a node map")?;
+
+    let (sampler_id, positive, negative) = sampler_conditioning(nodes)?;
+    let vae = decoder_vae(nodes).ok_or("Workflow has no VAEDecode to take the VAE from")?;
+
+    insert_control_map(nodes, control);
+    nodes.insert(
+        CONTROLNET_APPLY_NODE.into(),
+        serde_json::json!({
+            "class_type": "InstructPixToPixConditioning",
+            "inputs": {
+                "positive": positive,
+                "negative": negative,
+                "vae": vae,
+                "pixels": [CONTROL_PREPROCESS_NODE, 0]
+            }
+        }),
+    );
+
+    let sampler = &mut nodes
+        .get_mut(&sampler_id)
+        .ok_or("Workflow has no KSampler to condition")?["inputs"];
+    sampler["positive"] = serde_json::json!([CONTROLNET_APPLY_NODE, 0]);
+    sampler["negative"] = serde_json::json!([CONTROLNET_APPLY_NODE, 1]);
+    sampler["latent_image"] = serde_json::json!([CONTROLNET_APPLY_NODE, 2]);
+
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// IMAGE TO IMAGE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// How much of the input image is repainted; lower keeps more of it
+pub const IMAGE_TO_IMAGE_DENOISE: f32 = 0.75;
+
+/// Node ids added to the text-to-image template for img2img
+const START_IMAGE_NODE: &str = "27";
+const START_ENCODE_NODE: &str = "28";
+const START_BATCH_NODE: &str = "29";
+
+/// Replace the template's empty latent with the VAE-encoded input image
+///
+/// Batches repeat the encoded latent; each copy still gets its own noise.
+fn apply_image_to_image(workflow: &mut Value, image: &str, batch_size: u32) -> Result<(), String> {
+    let nodes = workflow
+        .as_object_mut()
+        .ok_or("Workflow is not a node map")?;
+
+    let (sampler_id, _, _) = sampler_conditioning(nodes)?;
+    let vae = decoder_vae(nodes).ok_or("Workflow has no VAEDecode to take the VAE from")?;
+
+    nodes.insert(
+        START_IMAGE_NODE.into(),
+        serde_json::json!({ "class_type": "LoadImage", "inputs": { "image": image } }),
+    );
+    nodes.insert(
+        START_ENCODE_NODE.into(),
+        serde_json::json!({
+            "class_type": "VAEEncode",
+            "inputs": { "pixels": [START_IMAGE_NODE, 0], "vae": vae }
+        }),
+    );
+    let latent = if batch_size > 1 {
+        nodes.insert(
+            START_BATCH_NODE.into(),
+            serde_json::json!({
+                "class_type": "RepeatLatentBatch",
+                "inputs": { "samples": [START_ENCODE_NODE, 0], "amount": batch_size }
+            }),
+        );
+        serde_json::json!([START_BATCH_NODE, 0])
+    } else {
+        serde_json::json!([START_ENCODE_NODE, 0])
+    };
+
+    let sampler = &mut nodes
+        .get_mut(&sampler_id)
+        .ok_or("Workflow has no KSampler to condition")?["inputs"];
+    let empty_latent = sampler["latent_image"][0].as_str().map(str::to_string);
+    sampler["latent_image"] = latent;
+    sampler["denoise"] = IMAGE_TO_IMAGE_DENOISE.into();
+    if let Some(id) = empty_latent {
+        nodes.remove(&id);
+    }
+
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// INPAINT / OUTPAINT
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Largest outpaint border ComfyUI's ImagePadForOutpaint accepts
+pub const MAX_EXPAND_PIXELS: u32 = 8192;
+
+/// FLUX Fill Pro price per image
+const FLUX_FILL_COST: f64 = 0.03;
+
+/// Node ids added to the text-to-image template for fills
+const FILL_SOURCE_NODE: &str = "24";
+const FILL_MASK_NODE: &str = "25";
+const FILL_ENCODE_NODE: &str = "26";
+
+/// What an Inpaint or Outpaint request repaints
+#[derive(Debug, Clone, PartialEq)]
+enum Fill {
+    Inpaint { source: String, mask: String },
+    Outpaint { source: String, expand: u32 },
+}
+
+impl Fill {
+    /// Outpainting grows the source by the border on every side
+    fn output_size(&self, request: &WorkflowRequest) -> ValidatedSize {
+        let border = match self {
+            Fill::Inpaint { .. } => 0,
+            Fill::Outpaint { expand, .. } => expand * 2,
+        };
+        ValidatedSize {
+            width: request.width + border,
+            height: request.height + border,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Check the inputs of a fill request (`None` for other workflow types)
+fn fill_inputs(request: &WorkflowRequest) -> Result<Option<Fill>, String> {
+    let source = || {
+        request
+            .input_image
+            .clone()
+            .ok_or_else(|| format!("{:?} needs an input_image", request.workflow_type))
+    };
+
+    match request.workflow_type {
+        WorkflowType::Inpaint => {
+            let mask = request
+                .mask_image
+                .clone()
+                .ok_or("Inpaint needs a mask_image marking the area to repaint")?;
+            Ok(Some(Fill::Inpaint {
+                source: source()?,
+                mask,
+            }))
+        }
+        WorkflowType::Outpaint => {
+            if request.mask_image.is_some() {
+                return Err("Outpaint masks the new border itself; use Inpaint with a mask".into());
+            }
+            let expand = request.expand_pixels.unwrap_or(0);
+            if expand == 0 || expand > MAX_EXPAND_PIXELS {
+                return Err(format!(
+                    "Outpaint needs expand_pixels between 1 and {}",
+                    MAX_EXPAND_PIXELS
+                ));
+            }
+            Ok(Some(Fill::Outpaint {
+                source: source()?,
+                expand,
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Replace the template's empty latent with the VAE-encoded source and mask
+fn apply_fill(workflow: &mut Value, fill: &Fill) -> Result<(), String> {
+    let nodes = workflow
+        .as_object_mut()
+        .ok_or("Workflow is not a node map")?;
+
+    let (sampler_id, _, _) = sampler_conditioning(nodes)?;
+    let vae = decoder_vae(nodes).ok_or("Workflow has no VAEDecode to take the VAE from")?;
+
+    let (source, pixels, mask) = match fill {
+        Fill::Inpaint { source, mask } => {
+            nodes.insert(
+                FILL_MASK_NODE.into(),
+                serde_json::json!({
+                    "class_type": "LoadImageMask",
+                    "inputs": { "image": mask, "channel": "red" }
+                }),
+            );
+            (
+                source,
+                serde_json::json!([FILL_SOURCE_NODE, 0]),
+                serde_json::json!([FILL_MASK_NODE, 0]),
+            )
+        }
+        Fill::Outpaint { source, expand } => {
+            nodes.insert(
+                FILL_MASK_NODE.into(),
+                serde_json::json!({
+                    "class_type": "ImagePadForOutpaint",
+                    "inputs": {
+                        "image": [FILL_SOURCE_NODE, 0],
+                        "left": expand,
+                        "top": expand,
+                        "right": expand,
+                        "bottom": expand,
+                        "feathering": 40
+                    }
+                }),
+            );
+            (
+                source,
+                serde_json::json!([FILL_MASK_NODE, 0]),
+                serde_json::json!([FILL_MASK_NODE, 1]),
+            )
+        }
+    };
+    nodes.insert(
+        FILL_SOURCE_NODE.into(),
+        serde_json::json!({ "class_type": "LoadImage", "inputs": { "image": source } }),
+    );
+    nodes.insert(
+        FILL_ENCODE_NODE.into(),
+        serde_json::json!({
+            "class_type": "VAEEncodeForInpaint",
+            "inputs": { "pixels": pixels, "vae": vae, "mask": mask, "grow_mask_by": 6 }
+        }),
+    );
+
+    let sampler = &mut nodes
+        .get_mut(&sampler_id)
+        .ok_or("Workflow has no KSampler to condition")?["inputs"];
+    let empty_latent = sampler["latent_image"][0].as_str().map(str::to_string);
+    sampler["latent_image"] = serde_json::json!([FILL_ENCODE_NODE, 0]);
+    // VAEEncodeForInpaint only works at full denoise
+    sampler["denoise"] = 1.0.into();
+    if let Some(id) = empty_latent {
+        nodes.remove(&id);
+    }
+
+    Ok(())
+}
+
+/// Cloud fills always run on FLUX Fill Pro
+fn fill_cloud_workflow(
+    request: &WorkflowRequest,
+    fill: &Fill,
+    seed: i64,
+) -> Result<GeneratedWorkflow, String> {
+    let node = match fill {
+        Fill::Inpaint { source, mask } => CinemaOSNode::FalFluxFill {
+            prompt: request.prompt.clone(),
+            image_url: source.clone(),
+            mask_url: Some(mask.clone()),
+            expand_pixels: None,
+            seed,
+        },
+        Fill::Outpaint { source, expand } => CinemaOSNode::FalFluxFill {
+            prompt: request.prompt.clone(),
+            image_url: source.clone(),
+            mask_url: None,
+            expand_pixels: Some(*expand),
+            seed,
+        },
+    };
+    let size = fill.output_size(request);
+
+    Ok(GeneratedWorkflow {
+        workflow_json: serde_json::to_value(node)
+            .map_err(|e| e.to_string())?
+            .to_string(),
+        estimated_cost: FLUX_FILL_COST,
+        is_local: false,
+        width: size.width,
+        height: size.height,
+        seed,
+        warnings: size.warnings,
+    })
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// IMAGE EDITING
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Denoise strength when an edit doesn't set one: repaint the masked area fully
+pub const DEFAULT_EDIT_STRENGTH: f32 = 1.0;
+
+/// Node id of the edit workflow's SaveImage
+const EDIT_SAVE_NODE: &str = "13";
+
+/// Edit an existing image: repaint the masked area (FLUX Fill, local) or
+/// follow an instruction across the whole image (FLUX Kontext, cloud)
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct EditRequest {
+    pub prompt: String,
+    /// Image to edit (file name in ComfyUI's input folder)
+    pub source_image: String,
+    /// White areas are repainted (file name in ComfyUI's input folder)
+    pub mask: Option<String>,
+    /// Denoise strength, 0.0 (keep the source) to 1.0 (repaint fully)
+    pub strength: f32,
+    /// "flux-fill" or "flux-kontext"
+    pub model: String,
+    /// Size of the source image; edits keep it
+    pub width: u32,
+    pub height: u32,
+    #[specta(type = Option<f64>)]
+    pub seed: Option<i64>,
+}
+
+/// Build the workflow for an image edit
+pub fn generate_edit_workflow(request: &EditRequest) -> Result<GeneratedWorkflow, String> {
+    if !(0.0..=1.0).contains(&request.strength) {
+        return Err(format!(
+            "strength must be between 0 and 1, got {}",
+            request.strength
+        ));
+    }
+
+    let seed = resolve_seed(request.seed);
+    let (workflow, is_local, estimated_cost) = match request.model.as_str() {
+        "flux-fill" => {
+            let mask = request
+                .mask
+                .as_deref()
+                .ok_or("flux-fill needs a mask; use flux-kontext to edit the whole image")?;
+            (flux_fill_workflow(request, mask, seed), true, 0.0)
+        }
+        "flux-kontext" => {
+            if request.mask.is_some() {
+                return Err(
+                    "flux-kontext edits the whole image; use flux-fill for masked edits".into(),
+                );
+            }
+            let node = CinemaOSNode::FalFluxKontext {
+                prompt: request.prompt.clone(),
+                reference_images: vec![request.source_image.clone()],
+            };
+            let workflow = serde_json::to_value(node).map_err(|e| e.to_string())?;
+            (workflow, false, 0.04)
+        }
+        other => return Err(format!("Model {} does not support image editing", other)),
+    };
+
+    Ok(GeneratedWorkflow {
+        workflow_json: workflow.to_string(),
+        estimated_cost,
+        is_local,
+        width: request.width,
+        height: request.height,
+        seed,
+        warnings: Vec::new(),
+    })
+}
+
+/// Local FLUX Fill inpainting graph
+fn flux_fill_workflow(request: &EditRequest, mask: &str, seed: i64) -> Value {
+    serde_json::json!({
+        "1": {
+            "class_type": "UNETLoader",
+            "inputs": { "unet_name": "flux1-fill-dev.safetensors", "weight_dtype": "default" }
+        },
+        "2": {
+            "class_type": "DualCLIPLoader",
+            "inputs": {
+                "clip_name1": "clip_l.safetensors",
+                "clip_name2": "t5xxl_fp16.safetensors",
+                "type": "flux"
+            }
+        },
+        "3": { "class_type": "VAELoader", "inputs": { "vae_name": "ae.safetensors" } },
+        "4": { "class_type": "LoadImage", "inputs": { "image": request.source_image } },
+        "5": { "class_type": "LoadImageMask", "inputs": { "image": mask, "channel": "red" } },
+        "6": {
+            "class_type": "CLIPTextEncode",
+            "inputs": { "text": request.prompt, "clip": ["2", 0] }
+        },
+        "7": {
+            "class_type": "FluxGuidance",
+            "inputs": { "conditioning": ["6", 0], "guidance": 30.0 }
+        },
+        "8": { "class_type": "CLIPTextEncode", "inputs": { "text": "", "clip": ["2", 0] } },
+        "9": {
+            "class_type": "InpaintModelConditioning",
+            "inputs": {
+                "positive": ["7", 0],
+                "negative": ["8", 0],
+                "vae": ["3", 0],
+                "pixels": ["4", 0],
+                "mask": ["5", 0],
+                "noise_mask": true
+            }
+        },
+        "10": { "class_type": "DifferentialDiffusion", "inputs": { "model": ["1", 0] } },
+        "11": {
+            "class_type": "KSampler",
+            "inputs": {
+                "seed": seed,
+                "steps": 20,
+                "cfg": 1.0,
+                "sampler_name": "euler",
+                "scheduler": "normal",
+                "denoise": request.strength,
+                "model": ["10", 0],
+                "positive": ["9", 0],
+                "negative": ["9", 1],
+                "latent_image": ["9", 2]
+            }
+        },
+        "12": { "class_type": "VAEDecode", "inputs": { "samples": ["11", 0], "vae": ["3", 0] } },
+        EDIT_SAVE_NODE: {
+            "class_type": "SaveImage",
+            "inputs": { "images": ["12", 0], "filename_prefix": "CinemaOS_Edit" }
+        }
     })
 }
 
@@ -120,3 +919,325 @@ pub fn generate_workflow(request: &WorkflowRequest) -> Result<GeneratedWorkflow,
 // ═══════════════════════════════════════════════════════════════════════════════
 
 // Placeholder for resource path - should exist in lib.rs or utils.rs
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> Value {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets/workflows/t2i_flux.json");
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_controlnet_rewires_sampler() {
+        let mut workflow = template();
+        apply_controlnet(
+            &mut workflow,
+            &ControlNetConfig {
+                control_type: ControlType::Depth,
+                image: "composition.png".into(),
+                strength: DEFAULT_CONTROL_STRENGTH,
+            },
+            "flux-depth-controlnet-v3.safetensors",
+        )
+        .unwrap();
+
+        assert_eq!(
+            workflow["3"]["inputs"]["positive"],
+            serde_json::json!(["23", 0])
+        );
+        assert_eq!(
+            workflow["3"]["inputs"]["negative"],
+            serde_json::json!(["23", 1])
+        );
+        assert_eq!(
+            workflow["23"]["inputs"]["positive"],
+            serde_json::json!(["6", 0])
+        );
+        assert_eq!(workflow["23"]["inputs"]["vae"], serde_json::json!(["4", 2]));
+        assert_eq!(workflow["21"]["class_type"], "DepthAnythingV2Preprocessor");
+        assert_eq!(workflow["20"]["inputs"]["image"], "composition.png");
+    }
+
+    #[test]
+    fn test_fill_edit_workflow() {
+        let mut request = EditRequest {
+            prompt: "Change the sky to sunset".into(),
+            source_image: "shot_012.png".into(),
+            mask: Some("shot_012_sky.png".into()),
+            strength: 0.9,
+            model: "flux-fill".into(),
+            width: 1280,
+            height: 720,
+            seed: Some(7),
+        };
+
+        let generated = generate_edit_workflow(&request).unwrap();
+        assert!(generated.is_local);
+        assert_eq!((generated.width, generated.height), (1280, 720));
+
+        let workflow: Value = serde_json::from_str(&generated.workflow_json).unwrap();
+        assert_eq!(workflow["4"]["inputs"]["image"], "shot_012.png");
+        assert_eq!(workflow["5"]["inputs"]["image"], "shot_012_sky.png");
+        assert_eq!(workflow["9"]["inputs"]["mask"], serde_json::json!(["5", 0]));
+        assert_eq!(
+            workflow["11"]["inputs"]["denoise"].as_f64(),
+            Some(0.9f32 as f64)
+        );
+        assert_eq!(
+            workflow[EDIT_SAVE_NODE]["inputs"]["images"],
+            serde_json::json!(["12", 0])
+        );
+
+        // Kontext takes an instruction, not a mask
+        assert!(generate_edit_workflow(&EditRequest {
+            model: "flux-kontext".into(),
+            ..request.clone()
+        })
+        .is_err());
+        request.mask = None;
+        assert!(generate_edit_workflow(&request).is_err());
+
+        let kontext = generate_edit_workflow(&EditRequest {
+            model: "flux-kontext".into(),
+            ..request
+        })
+        .unwrap();
+        assert!(!kontext.is_local);
+        assert!(kontext
+            .workflow_json
+            .contains(r#""type":"fal_flux_kontext""#));
+    }
+
+    #[test]
+    fn test_control_type_support() {
+        assert!(controlnet_filename("flux-dev", ControlType::Canny).is_some());
+        assert!(controlnet_filename("flux-dev", ControlType::Pose).is_none());
+        assert!(controlnet_filename("sdxl", ControlType::Pose).is_some());
+        assert!(controlnet_filename("sdxl", ControlType::OpenPose).is_some());
+        assert!(controlnet_filename("veo-3.1", ControlType::Depth).is_none());
+        assert_eq!(native_control("flux-canny"), Some(ControlType::Canny));
+        assert_eq!(native_control("flux-dev"), None);
+    }
+
+    fn control_request(model: &str, control_type: ControlType) -> WorkflowRequest {
+        WorkflowRequest {
+            workflow_type: WorkflowType::TextToImage,
+            prompt: "Anna on the rooftop at dawn".into(),
+            negative_prompt: None,
+            model: model.into(),
+            width: 1024,
+            height: 1024,
+            steps: None,
+            seed: Some(7),
+            batch_size: 1,
+            input_image: None,
+            force_local: Some(true),
+            control: Some(ControlNetConfig {
+                control_type,
+                image: "blocking.png".into(),
+                strength: 0.55,
+            }),
+            mask_image: None,
+            expand_pixels: None,
+            params: None,
+        }
+    }
+
+    #[test]
+    fn test_generate_workflow_with_controlnet() {
+        let generated = generate_workflow(&control_request("sdxl", ControlType::OpenPose)).unwrap();
+        assert!(generated
+            .workflow_json
+            .contains(r#""class_type":"ControlNetApplyAdvanced""#));
+
+        let workflow: Value = serde_json::from_str(&generated.workflow_json).unwrap();
+        assert_eq!(workflow["21"]["class_type"], "OpenposePreprocessor");
+        assert_eq!(
+            workflow["22"]["inputs"]["control_net_name"],
+            "controlnet-openpose-sdxl-1.0.safetensors"
+        );
+        assert_eq!(
+            workflow["23"]["inputs"]["strength"].as_f64(),
+            Some(0.55f32 as f64)
+        );
+        assert_eq!(
+            workflow["3"]["inputs"]["positive"],
+            serde_json::json!(["23", 0])
+        );
+
+        // FLUX.1 Canny Dev reads the edge map itself
+        let generated =
+            generate_workflow(&control_request("flux-canny", ControlType::Canny)).unwrap();
+        let workflow: Value = serde_json::from_str(&generated.workflow_json).unwrap();
+        assert_eq!(workflow["23"]["class_type"], "InstructPixToPixConditioning");
+        assert_eq!(workflow["22"], Value::Null);
+        assert_eq!(
+            workflow["3"]["inputs"]["latent_image"],
+            serde_json::json!(["23", 2])
+        );
+
+        assert!(generate_workflow(&control_request("flux-canny", ControlType::Depth)).is_err());
+        assert!(generate_workflow(&control_request("flux-dev", ControlType::Pose)).is_err());
+    }
+
+    #[test]
+    fn test_seed_and_batch_size() {
+        let mut request = WorkflowRequest {
+            control: None,
+            batch_size: 4,
+            ..control_request("flux-dev", ControlType::Canny)
+        };
+        let generated = generate_workflow(&request).unwrap();
+        assert_eq!(generated.seed, 7);
+        let workflow: Value = serde_json::from_str(&generated.workflow_json).unwrap();
+        assert_eq!(workflow["3"]["inputs"]["seed"], 7);
+        assert_eq!(workflow["5"]["inputs"]["batch_size"], 4);
+
+        // A random seed is returned so the run can be repeated
+        request.seed = None;
+        let random = generate_workflow(&request).unwrap();
+        assert!((0..=MAX_SEED).contains(&random.seed));
+        let workflow: Value = serde_json::from_str(&random.workflow_json).unwrap();
+        assert_eq!(workflow["3"]["inputs"]["seed"], random.seed);
+        request.seed = Some(random.seed);
+        assert_eq!(
+            generate_workflow(&request).unwrap().workflow_json,
+            random.workflow_json
+        );
+
+        request.batch_size = MAX_BATCH_SIZE + 1;
+        assert!(generate_workflow(&request).is_err());
+        request.batch_size = 2;
+        request.workflow_type = WorkflowType::ImageToVideo;
+        assert!(generate_workflow(&request).is_err());
+    }
+
+    #[test]
+    fn test_image_to_image_encodes_uploaded_input() {
+        let uploaded = "cinemaos/cinemaos_input_0b1f.png";
+        let mut request = WorkflowRequest {
+            workflow_type: WorkflowType::ImageToImage,
+            control: None,
+            ..control_request("flux-dev", ControlType::Canny)
+        };
+        assert!(generate_workflow(&request).is_err());
+
+        request.input_image = Some(uploaded.into());
+        let generated = generate_workflow(&request).unwrap();
+        let workflow: Value = serde_json::from_str(&generated.workflow_json).unwrap();
+        assert_eq!(workflow[START_IMAGE_NODE]["class_type"], "LoadImage");
+        assert_eq!(workflow[START_IMAGE_NODE]["inputs"]["image"], uploaded);
+        assert_eq!(workflow[START_ENCODE_NODE]["class_type"], "VAEEncode");
+        assert_eq!(
+            workflow[START_ENCODE_NODE]["inputs"]["vae"],
+            serde_json::json!(["4", 2])
+        );
+        assert_eq!(
+            workflow["3"]["inputs"]["latent_image"],
+            serde_json::json!([START_ENCODE_NODE, 0])
+        );
+        let denoise = workflow["3"]["inputs"]["denoise"].as_f64().unwrap();
+        assert!(denoise > 0.0 && denoise < 1.0);
+        // The empty latent is gone
+        assert_eq!(workflow["5"], Value::Null);
+
+        // Batches repeat the encoded input
+        request.batch_size = 3;
+        let batched = generate_workflow(&request).unwrap();
+        let workflow: Value = serde_json::from_str(&batched.workflow_json).unwrap();
+        assert_eq!(workflow[START_BATCH_NODE]["inputs"]["amount"], 3);
+        assert_eq!(
+            workflow["3"]["inputs"]["latent_image"],
+            serde_json::json!([START_BATCH_NODE, 0])
+        );
+    }
+
+    fn fill_request(workflow_type: WorkflowType, force_local: bool) -> WorkflowRequest {
+        WorkflowRequest {
+            workflow_type,
+            prompt: "Empty wet street at night".into(),
+            negative_prompt: None,
+            model: "sdxl".into(),
+            width: 1344,
+            height: 768,
+            steps: None,
+            seed: Some(7),
+            batch_size: 1,
+            input_image: Some("shot_012.png".into()),
+            force_local: Some(force_local),
+            control: None,
+            mask_image: Some("shot_012_car.png".into()),
+            expand_pixels: None,
+            params: None,
+        }
+    }
+
+    #[test]
+    fn test_inpaint_workflow_has_mask_input() {
+        let generated = generate_workflow(&fill_request(WorkflowType::Inpaint, true)).unwrap();
+        assert!(generated.is_local);
+        assert_eq!((generated.width, generated.height), (1344, 768));
+
+        let workflow: Value = serde_json::from_str(&generated.workflow_json).unwrap();
+        assert_eq!(workflow[FILL_MASK_NODE]["class_type"], "LoadImageMask");
+        assert_eq!(
+            workflow[FILL_MASK_NODE]["inputs"]["image"],
+            "shot_012_car.png"
+        );
+        assert_eq!(
+            workflow[FILL_SOURCE_NODE]["inputs"]["image"],
+            "shot_012.png"
+        );
+        assert_eq!(
+            workflow[FILL_ENCODE_NODE]["class_type"],
+            "VAEEncodeForInpaint"
+        );
+        assert_eq!(
+            workflow[FILL_ENCODE_NODE]["inputs"]["mask"],
+            serde_json::json!([FILL_MASK_NODE, 0])
+        );
+        assert_eq!(
+            workflow["3"]["inputs"]["latent_image"],
+            serde_json::json!([FILL_ENCODE_NODE, 0])
+        );
+        // The empty latent is gone
+        assert_eq!(workflow["5"], Value::Null);
+
+        let mut unmasked = fill_request(WorkflowType::Inpaint, true);
+        unmasked.mask_image = None;
+        assert!(generate_workflow(&unmasked).is_err());
+
+        // Cloud fills go to FLUX Fill
+        let cloud = generate_workflow(&fill_request(WorkflowType::Inpaint, false)).unwrap();
+        assert!(!cloud.is_local);
+        assert!(cloud.workflow_json.contains(r#""type":"fal_flux_fill""#));
+        assert!(cloud
+            .workflow_json
+            .contains(r#""mask_url":"shot_012_car.png""#));
+    }
+
+    #[test]
+    fn test_outpaint_pads_source() {
+        let mut request = fill_request(WorkflowType::Outpaint, true);
+        assert!(generate_workflow(&request).is_err());
+        request.mask_image = None;
+        assert!(generate_workflow(&request).is_err());
+
+        request.expand_pixels = Some(128);
+        let generated = generate_workflow(&request).unwrap();
+        assert_eq!((generated.width, generated.height), (1600, 1024));
+
+        let workflow: Value = serde_json::from_str(&generated.workflow_json).unwrap();
+        assert_eq!(
+            workflow[FILL_MASK_NODE]["class_type"],
+            "ImagePadForOutpaint"
+        );
+        assert_eq!(workflow[FILL_MASK_NODE]["inputs"]["left"], 128);
+        assert_eq!(
+            workflow[FILL_ENCODE_NODE]["inputs"]["mask"],
+            serde_json::json!([FILL_MASK_NODE, 1])
+        );
+    }
+}
@@ -19,6 +19,19 @@ impl ComfyUIClient {
         }
     }
 
+    /// Client for the server described by `config`
+    pub fn from_config(config: &crate::ai::comfyui_client::ComfyUIConfig) -> Self {
+        let scheme = if config.use_ssl { "https" } else { "http" };
+        Self {
+            base_url: format!("{}://{}:{}", scheme, config.host, config.port),
+        }
+    }
+
+    /// Client for the ComfyUI the app uses, on whichever port it started on
+    pub fn active() -> Self {
+        Self::from_config(crate::ai::comfyui_client::get_client().config())
+    }
+
     /// Queue a workflow for execution
     pub async fn queue_prompt(&self, workflow: Value) -> Result<QueueResponse, AppError> {
         let client = reqwest::Client::new();
@@ -116,32 +129,70 @@ pub struct ImageOutput {
     pub output_type: String,
 }
 
-/// System stats
-#[derive(Debug, Serialize, Deserialize)]
+/// System stats reported by ComfyUI's `/system_stats` endpoint
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
 pub struct SystemStats {
     pub system: System,
     pub devices: Vec<Device>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
 pub struct System {
     pub os: String,
     pub python_version: String,
+    #[serde(default)]
+    pub pytorch_version: Option<String>,
+    #[serde(default)]
+    pub comfyui_version: Option<String>,
+    #[serde(default)]
+    #[specta(type = Option<f64>)]
+    pub ram_total: Option<u64>,
+    #[serde(default)]
+    #[specta(type = Option<f64>)]
+    pub ram_free: Option<u64>,
+    #[serde(default)]
     pub embedded_python: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, specta::Type)]
 pub struct Device {
     pub name: String,
     #[serde(rename = "type")]
     pub device_type: String,
-    pub index: u32,
+    /// `null` for CPU devices
+    pub index: Option<u32>,
+    #[specta(type = Option<f64>)]
     pub vram_total: Option<u64>,
+    #[specta(type = Option<f64>)]
     pub vram_free: Option<u64>,
+    #[specta(type = Option<f64>)]
     pub torch_vram_total: Option<u64>,
+    #[specta(type = Option<f64>)]
     pub torch_vram_free: Option<u64>,
 }
 
+impl SystemStats {
+    /// The device ComfyUI will run workflows on (first reported)
+    pub fn primary_device(&self) -> Option<&Device> {
+        self.devices.first()
+    }
+
+    /// Free VRAM on the primary device, in bytes
+    pub fn vram_free(&self) -> Option<u64> {
+        self.primary_device().and_then(|d| d.vram_free)
+    }
+
+    /// Total VRAM on the primary device, in bytes
+    pub fn vram_total(&self) -> Option<u64> {
+        self.primary_device().and_then(|d| d.vram_total)
+    }
+
+    /// Whether a workflow needing `required_bytes` of VRAM should fit right now
+    pub fn fits_in_vram(&self, required_bytes: u64) -> bool {
+        self.vram_free().is_some_and(|free| free >= required_bytes)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,5 +201,66 @@ mod tests {
     fn test_client_creation() {
         let client = ComfyUIClient::new("127.0.0.1", 8188);
         assert_eq!(client.base_url, "http://127.0.0.1:8188");
+
+        // ComfyUI moved off a taken port
+        let config = crate::ai::comfyui_client::ComfyUIConfig {
+            port: 8190,
+            ..Default::default()
+        };
+        let client = ComfyUIClient::from_config(&config);
+        assert_eq!(client.base_url, "http://127.0.0.1:8190");
+    }
+
+    #[test]
+    fn test_parse_system_stats() {
+        let sample = r#"{
+            "system": {
+                "os": "posix",
+                "ram_total": 67108864000,
+                "ram_free": 50331648000,
+                "comfyui_version": "0.3.10",
+                "python_version": "3.12.3 (main, Apr 10 2024, 05:33:47) [GCC 13.2.0]",
+                "pytorch_version": "2.5.1+cu124",
+                "embedded_python": false,
+                "argv": ["main.py", "--listen", "127.0.0.1"]
+            },
+            "devices": [
+                {
+                    "name": "cuda:0 NVIDIA GeForce RTX 4070 : cudaMallocAsync",
+                    "type": "cuda",
+                    "index": 0,
+                    "vram_total": 12878086144,
+                    "vram_free": 12000000000,
+                    "torch_vram_total": 0,
+                    "torch_vram_free": 0
+                }
+            ]
+        }"#;
+
+        let stats: SystemStats = serde_json::from_str(sample).unwrap();
+        assert_eq!(stats.system.pytorch_version.as_deref(), Some("2.5.1+cu124"));
+        assert!(stats.system.python_version.starts_with("3.12.3"));
+
+        let device = stats.primary_device().unwrap();
+        assert!(device.name.contains("RTX 4070"));
+        assert_eq!(device.device_type, "cuda");
+        assert_eq!(stats.vram_total(), Some(12878086144));
+        assert!(stats.fits_in_vram(8_000_000_000));
+        assert!(!stats.fits_in_vram(16_000_000_000));
+    }
+
+    #[test]
+    fn test_parse_cpu_only_stats() {
+        let sample = r#"{
+            "system": {"os": "nt", "python_version": "3.11.9", "embedded_python": true},
+            "devices": [{"name": "cpu", "type": "cpu", "index": null,
+                         "vram_total": 0, "vram_free": 0,
+                         "torch_vram_total": 0, "torch_vram_free": 0}]
+        }"#;
+
+        let stats: SystemStats = serde_json::from_str(sample).unwrap();
+        assert_eq!(stats.primary_device().unwrap().index, None);
+        assert!(stats.system.pytorch_version.is_none());
+        assert!(!stats.fits_in_vram(1));
     }
 }
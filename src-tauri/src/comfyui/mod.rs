@@ -9,10 +9,12 @@
 //! - `client`: WebSocket communication with ComfyUI API
 //! - `workflows`: Predefined generation workflows
 //! - `models`: Model management and download
+//! - `output`: Output directory settings and asset ingest
 
 pub mod client;
 pub mod installer;
 pub mod models;
+pub mod output;
 pub mod process;
 pub mod workflows;
 
@@ -25,6 +27,8 @@ pub struct ComfyUIConfig {
     pub host: String,
     pub port: u16,
     pub auto_start: bool,
+    /// Passed to ComfyUI as `--output-directory`
+    pub output_dir: PathBuf,
 }
 
 impl Default for ComfyUIConfig {
@@ -34,6 +38,7 @@ impl Default for ComfyUIConfig {
             host: "127.0.0.1".to_string(),
             port: 8188,
             auto_start: true,
+            output_dir: PathBuf::from(output::output_settings().output_dir),
         }
     }
 }
@@ -65,6 +70,7 @@ pub fn get_default_install_path() -> PathBuf {
 pub struct ComfyUIStatus {
     pub installed: bool,
     pub running: bool,
+    pub port: u16,
     pub version: Option<String>,
     pub install_path: String,
 }
@@ -182,12 +182,16 @@ impl CloudModels {
 
     /// FLUX.2 Pro - High-quality with 8 reference images
     pub const FLUX_2_PRO: &'static str = "black-forest-labs/flux-2-pro";
+    pub const FLUX_2_PRO_FAL: &'static str = "fal-ai/flux-2-pro";
 
     /// FLUX Kontext Pro - Multi-image editing
     pub const FLUX_KONTEXT_PRO: &'static str = "fal-ai/flux-pro/kontext";
     pub const FLUX_KONTEXT_MAX: &'static str = "fal-ai/flux-pro/kontext/max";
     pub const FLUX_KONTEXT_FAST: &'static str = "prunaai/flux-kontext-fast";
 
+    /// FLUX Fill Pro - Inpainting/outpainting
+    pub const FLUX_FILL_PRO: &'static str = "fal-ai/flux-pro/v1/fill";
+
     /// Seedream 4.5 - ByteDance (spatial understanding + world knowledge)
     pub const SEEDREAM_45: &'static str = "bytedance/seedream-4.5";
     pub const SEEDREAM_4_EDIT: &'static str = "fal-ai/bytedance/seedream/v4/edit";
@@ -217,6 +221,7 @@ impl CloudModels {
     /// Veo 3.1 - Google DeepMind (best quality)
     pub const VEO_31: &'static str = "fal-ai/veo3.1";
     pub const VEO_31_FAST: &'static str = "fal-ai/veo3.1/fast";
+    pub const VEO_31_FAST_I2V: &'static str = "fal-ai/veo3.1/fast/image-to-video";
     pub const VEO_31_I2V: &'static str = "fal-ai/veo3.1/image-to-video";
     pub const VEO_31_FIRST_LAST: &'static str = "fal-ai/veo3.1/first-last-frame-to-video";
     pub const VEO_31_REF: &'static str = "fal-ai/veo3.1/reference-to-video";
@@ -225,6 +230,7 @@ impl CloudModels {
     pub const SORA_2: &'static str = "fal-ai/sora-2/text-to-video";
     pub const SORA_2_PRO: &'static str = "fal-ai/sora-2/text-to-video/pro";
     pub const SORA_2_I2V: &'static str = "fal-ai/sora-2/image-to-video";
+    pub const SORA_2_PRO_I2V: &'static str = "fal-ai/sora-2/image-to-video/pro";
 
     /// Kling v2.6 - Kuaishou (cinematic)
     pub const KLING_V26_T2V: &'static str = "fal-ai/kling-video/v2.6/pro/text-to-video";
@@ -232,6 +238,8 @@ impl CloudModels {
 
     /// Kling v2.5 Turbo Pro - Fast cinematic
     pub const KLING_V25_TURBO: &'static str = "fal-ai/kling-video/v2.5-turbo/pro/text-to-video";
+    pub const KLING_V25_TURBO_I2V: &'static str =
+        "fal-ai/kling-video/v2.5-turbo/pro/image-to-video";
 
     /// Kling v2.1 Master - Premium
     pub const KLING_V21_MASTER: &'static str = "fal-ai/kling-video/v2.1/master/image-to-video";
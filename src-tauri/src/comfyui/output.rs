@@ -0,0 +1,337 @@
+//! ComfyUI output handling
+//!
+//! ComfyUI is launched with `--output-directory` pointing at a CinemaOS-managed
+//! folder. After a workflow finishes, the files it saved are copied into the
+//! project's asset directory and recorded as Vault assets, so generations show
+//! up in the gallery without a manual `get_image` fetch.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tokio::sync::mpsc;
+
+use crate::ai::comfyui_client::{ComfyUIClient, ExecutionResult, OutputData, ProgressUpdate};
+use crate::installer::get_cinema_os_dir;
+use crate::settings::{load_json, save_json};
+use crate::vault::{
+    self,
+    assets::{project_assets_dir, Asset, AssetKind},
+};
+
+/// Output keys ComfyUI uses for saved files
+const FILE_OUTPUT_KEYS: &[&str] = &["images", "gifs", "videos", "audio"];
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// SETTINGS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Where ComfyUI writes its outputs and whether they are ingested
+///
+/// `output_dir` is passed on launch, so changes apply on the next start.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct OutputSettings {
+    pub output_dir: String,
+    /// Copy finished outputs into the project's assets
+    pub auto_ingest: bool,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            output_dir: get_default_output_dir().display().to_string(),
+            auto_ingest: true,
+        }
+    }
+}
+
+/// Where the output settings are persisted
+const OUTPUT_SETTINGS_FILE: &str = "comfyui_output.json";
+
+static OUTPUT_SETTINGS: Lazy<RwLock<OutputSettings>> =
+    Lazy::new(|| RwLock::new(load_json(OUTPUT_SETTINGS_FILE)));
+
+/// Default ComfyUI output folder, inside the CinemaOS data directory
+pub fn get_default_output_dir() -> PathBuf {
+    get_cinema_os_dir().join("outputs").join("comfyui")
+}
+
+/// Current output settings
+pub fn output_settings() -> OutputSettings {
+    OUTPUT_SETTINGS
+        .read()
+        .map(|s| s.clone())
+        .unwrap_or_default()
+}
+
+/// Replace the output settings (persisted across restarts)
+pub fn set_output_settings(settings: OutputSettings) -> Result<(), String> {
+    if settings.output_dir.trim().is_empty() {
+        return Err("Output directory cannot be empty".to_string());
+    }
+    let mut current = OUTPUT_SETTINGS.write().map_err(|e| e.to_string())?;
+    save_json(OUTPUT_SETTINGS_FILE, &settings)?;
+    *current = settings;
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PARSING
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// A file saved by a workflow node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct OutputFile {
+    pub node_id: String,
+    pub filename: String,
+    pub subfolder: String,
+    /// "output" for saved files, "temp" for previews
+    pub folder_type: String,
+}
+
+impl OutputFile {
+    /// Location of the file under ComfyUI's output directory
+    pub fn source_path(&self, output_dir: &Path) -> PathBuf {
+        let dir = if self.subfolder.is_empty() {
+            output_dir.to_path_buf()
+        } else {
+            output_dir.join(&self.subfolder)
+        };
+        dir.join(&self.filename)
+    }
+}
+
+/// Saved files listed in `ExecutionResult::outputs_json`, ordered by node
+///
+/// Previews (`type: "temp"`) are skipped; they aren't written to the output
+/// directory.
+pub fn parse_outputs(outputs_json: &str) -> Result<Vec<OutputFile>, String> {
+    let outputs: HashMap<String, OutputData> = serde_json::from_str(outputs_json)
+        .map_err(|e| format!("Failed to parse ComfyUI outputs: {}", e))?;
+
+    let mut nodes: Vec<&OutputData> = outputs.values().collect();
+    nodes.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+
+    let mut files = Vec::new();
+    for node in nodes {
+        for key in FILE_OUTPUT_KEYS {
+            let Some(entries) = node.data.get(*key).and_then(|v| v.as_array()) else {
+                continue;
+            };
+            for entry in entries {
+                let field = |name: &str| {
+                    entry
+                        .get(name)
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                        .to_string()
+                };
+                let file = OutputFile {
+                    node_id: node.node_id.clone(),
+                    filename: field("filename"),
+                    subfolder: field("subfolder"),
+                    folder_type: field("type"),
+                };
+                if !file.filename.is_empty() && file.folder_type != "temp" {
+                    files.push(file);
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// INGEST
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Asset records for a run's output files, each paired with its source file
+///
+/// Files are prefixed with the run id so ComfyUI's counter-based names
+/// (`ComfyUI_00001_.png`) can't collide across runs.
+pub fn to_asset_records(
+    project_id: &str,
+    execution_id: &str,
+    files: &[OutputFile],
+    output_dir: &Path,
+    assets_dir: &Path,
+) -> Vec<(OutputFile, PathBuf, Asset)> {
+    let run: String = execution_id.chars().take(8).collect();
+    let created_at = chrono::Utc::now().to_rfc3339();
+
+    files
+        .iter()
+        .map(|file| {
+            let file_name = format!("{}_{}", run, file.filename);
+            let asset = Asset {
+                id: None,
+                project_id: project_id.to_string(),
+                kind: AssetKind::from_file_name(&file.filename),
+                path: assets_dir.join(&file_name).display().to_string(),
+                file_name,
+                source: "comfyui".to_string(),
+                execution_id: Some(execution_id.to_string()),
+                node_id: Some(file.node_id.clone()),
+                token_ids: Vec::new(),
+                created_at: created_at.clone(),
+            };
+            (file.clone(), file.source_path(output_dir), asset)
+        })
+        .collect()
+}
+
+/// Copy a finished run's outputs into the project and create asset records
+///
+/// Files missing from the output directory (ComfyUI started elsewhere) are
+/// fetched over HTTP instead.
+pub async fn ingest_outputs(
+    client: &ComfyUIClient,
+    project_id: &str,
+    result: &ExecutionResult,
+) -> Result<Vec<Asset>, String> {
+    let files = parse_outputs(&result.outputs_json)?;
+    if files.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let db = vault::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    let output_dir = PathBuf::from(output_settings().output_dir);
+    let assets_dir = project_assets_dir(project_id);
+    std::fs::create_dir_all(&assets_dir).map_err(|e| e.to_string())?;
+
+    let mut assets = Vec::new();
+    for (file, source, asset) in to_asset_records(
+        project_id,
+        &result.execution_id,
+        &files,
+        &output_dir,
+        &assets_dir,
+    ) {
+        if source.exists() {
+            std::fs::copy(&source, &asset.path).map_err(|e| e.to_string())?;
+        } else {
+            let bytes = client
+                .get_image(&file.filename, &file.subfolder, &file.folder_type)
+                .await?;
+            std::fs::write(&asset.path, bytes).map_err(|e| e.to_string())?;
+        }
+
+        let created: Option<Asset> = db
+            .create("asset")
+            .content(asset)
+            .await
+            .map_err(|e| e.to_string())?;
+        assets.extend(created);
+    }
+
+    Ok(assets)
+}
+
+/// A workflow run and the assets ingested from it
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct IngestedExecution {
+    pub result: ExecutionResult,
+    pub assets: Vec<Asset>,
+}
+
+/// Run a workflow and, when auto-ingest is on, ingest its outputs into the project
+pub async fn execute_and_ingest(
+    client: &ComfyUIClient,
+    project_id: &str,
+    prompt: serde_json::Value,
+    progress_tx: Option<mpsc::Sender<ProgressUpdate>>,
+) -> Result<IngestedExecution, String> {
+    let result = client.execute(prompt, progress_tx, None).await?;
+
+    let assets = if result.success && output_settings().auto_ingest {
+        ingest_outputs(client, project_id, &result).await?
+    } else {
+        Vec::new()
+    };
+
+    Ok(IngestedExecution { result, assets })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUTS: &str = r#"{
+        "9": {
+            "node_id": "9",
+            "output_type": "image",
+            "data": {"images": [
+                {"filename": "ComfyUI_00001_.png", "subfolder": "", "type": "output"},
+                {"filename": "ComfyUI_00002_.png", "subfolder": "shots", "type": "output"}
+            ]}
+        },
+        "12": {
+            "node_id": "12",
+            "output_type": "image",
+            "data": {
+                "images": [{"filename": "preview_0001.png", "subfolder": "", "type": "temp"}],
+                "gifs": [{"filename": "clip_00001.mp4", "subfolder": "", "type": "output"}]
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_outputs_skips_previews() {
+        let files = parse_outputs(OUTPUTS).unwrap();
+
+        let names: Vec<&str> = files.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(
+            names,
+            ["clip_00001.mp4", "ComfyUI_00001_.png", "ComfyUI_00002_.png"]
+        );
+        assert_eq!(files[0].node_id, "12");
+        assert_eq!(files[2].subfolder, "shots");
+    }
+
+    #[test]
+    fn test_ingest_mapping_to_asset_records() {
+        let files = parse_outputs(OUTPUTS).unwrap();
+        let output_dir = Path::new("/data/outputs");
+        let assets_dir = Path::new("/data/projects/p1/assets");
+
+        let records = to_asset_records(
+            "project:p1",
+            "3f2a9c1e-0000-4000-8000-000000000000",
+            &files,
+            output_dir,
+            assets_dir,
+        );
+
+        assert_eq!(records.len(), 3);
+
+        let (_, source, asset) = &records[0];
+        assert_eq!(source, &output_dir.join("clip_00001.mp4"));
+        assert_eq!(asset.kind, AssetKind::Video);
+        assert_eq!(asset.file_name, "3f2a9c1e_clip_00001.mp4");
+        assert_eq!(
+            asset.path,
+            assets_dir
+                .join("3f2a9c1e_clip_00001.mp4")
+                .display()
+                .to_string()
+        );
+        assert_eq!(asset.node_id.as_deref(), Some("12"));
+
+        let (_, source, asset) = &records[2];
+        assert_eq!(source, &output_dir.join("shots").join("ComfyUI_00002_.png"));
+        assert_eq!(asset.kind, AssetKind::Image);
+        assert_eq!(asset.project_id, "project:p1");
+        assert_eq!(asset.source, "comfyui");
+        assert_eq!(
+            asset.execution_id.as_deref(),
+            Some("3f2a9c1e-0000-4000-8000-000000000000")
+        );
+    }
+}
@@ -15,6 +15,7 @@ static COMFYUI_PROCESS: Lazy<Arc<Mutex<Option<Child>>>> = Lazy::new(|| Arc::new(
 /// Start ComfyUI headless server
 pub async fn start_comfyui(
     install_path: std::path::PathBuf,
+    output_dir: &std::path::Path,
     host: &str,
     port: u16,
 ) -> Result<(), AppError> {
@@ -25,6 +26,9 @@ pub async fn start_comfyui(
         return Ok(());
     }
 
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| AppError::ProcessStart(format!("Failed to create output directory: {}", e)))?;
+
     // Start ComfyUI via comfy-cli
     let child = Command::new("uv")
         .args([
@@ -38,6 +42,8 @@ pub async fn start_comfyui(
             "--disable-auto-launch", // No browser
             "--preview-method",
             "none", // No preview images
+            "--output-directory",
+            &output_dir.display().to_string(),
         ])
         .current_dir(&install_path)
         .stdout(Stdio::inherit())
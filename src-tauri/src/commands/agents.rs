@@ -2,15 +2,33 @@
 //!
 //! Replaces the basic agent_chat with full context and action support.
 
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use specta::Type;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
 
 use crate::ai::{
     actions::{parse_actions_from_response, ActionExecutor, ActionResult, AgentAction},
-    agent_executor::{get_agent_executor, ChatMessage},
+    agent_executor::{
+        self, get_agent_executor, AgentChatError, ChatMessage, ResponseCleanup, AGENT_CHAT_DEADLINE,
+    },
+    agent_memory::{self, AgentMemory, AgentMemoryConfig},
+    agents::routing::{self, RoutingTable},
     context::AgentContext,
+    mcp::{
+        registry::{self, McpSettings, McpToolInfo},
+        server::{self, McpServerInfo},
+    },
+    model_selection::{self, FallbackConfig, ModelSubstitution},
+    project_memory::{self, ProjectMemory},
 };
 
+/// Cancel senders for in-flight chats, keyed by the frontend's request id
+static ACTIVE_CHATS: Lazy<Mutex<HashMap<String, oneshot::Sender<()>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // REQUEST/RESPONSE TYPES
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -32,6 +50,9 @@ pub struct FullAgentRequest {
     pub model: Option<String>,
     /// Auto-execute actions?
     pub auto_execute: bool,
+    /// Client-chosen id used to cancel the chat via `cancel_agent_chat`
+    #[serde(default)]
+    pub request_id: Option<String>,
 }
 
 /// Full agent response with actions
@@ -49,6 +70,10 @@ pub struct FullAgentResponse {
     pub action_results: Vec<ActionResult>,
     /// Token usage
     pub tokens_used: Option<u32>,
+    /// History or script context was trimmed to fit the context window
+    pub context_trimmed: bool,
+    /// Set when the requested model failed and a fallback answered
+    pub substitution: Option<ModelSubstitution>,
 }
 
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -56,15 +81,51 @@ pub struct FullAgentResponse {
 // ═══════════════════════════════════════════════════════════════════════════════
 
 /// Chat with an agent with full context support
+///
+/// Fails with `Timeout` after [`AGENT_CHAT_DEADLINE`] or `Cancelled` when
+/// `cancel_agent_chat` is called with the request's `request_id`.
 #[tauri::command]
 #[specta::specta]
-pub async fn agent_chat_full(request: FullAgentRequest) -> Result<FullAgentResponse, String> {
+pub async fn agent_chat_full(
+    mut request: FullAgentRequest,
+) -> Result<FullAgentResponse, AgentChatError> {
+    if let Some(context) = request.context.as_mut() {
+        project_memory::attach(context).await;
+    }
+
+    // The agent's stored conversation replaces the frontend's history, which
+    // only covers the current session
+    let project_id = request.context.as_ref().and_then(|c| c.project_id.clone());
+    let mut memory_section = None;
+    if let Some(project_id) = &project_id {
+        match agent_memory::recall(project_id, &request.agent_role).await {
+            Ok(memory) if !memory.is_empty() => {
+                memory_section = Some(memory.to_prompt_section()).filter(|s| !s.is_empty());
+                request.history = memory.messages;
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Could not load agent memory for {}: {}", project_id, e),
+        }
+    }
+
     // Build context string
-    let context_str = request
-        .context
-        .as_ref()
-        .map(|c| c.to_prompt_context())
-        .filter(|s| !s.is_empty());
+    let context_str = [
+        memory_section,
+        request.context.as_ref().map(|c| c.to_prompt_context()),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|s| !s.is_empty())
+    .reduce(|a, b| format!("{}\n\n{}", a, b));
+    let user_message = request.message.clone();
+
+    let cancel = request.request_id.as_ref().map(|id| {
+        let (tx, rx) = oneshot::channel();
+        if let Ok(mut chats) = ACTIVE_CHATS.lock() {
+            chats.insert(id.clone(), tx);
+        }
+        rx
+    });
 
     // Call the agent executor
     let executor = get_agent_executor();
@@ -75,18 +136,55 @@ pub async fn agent_chat_full(request: FullAgentRequest) -> Result<FullAgentRespo
         history: request.history,
         provider: request.provider,
         model: request.model,
+        preferences: request
+            .context
+            .as_ref()
+            .and_then(|c| c.preferences.clone())
+            .unwrap_or_default(),
     };
 
-    let response = executor.chat(chat_request).await?;
+    // The deadline covers the LLM call and response parsing, not action execution
+    let result = tokio::time::timeout(AGENT_CHAT_DEADLINE, async {
+        let response = executor.chat_cancellable(chat_request, cancel).await?;
+        let actions = parse_actions_from_response(&response.message);
+        Ok::<_, AgentChatError>((response, actions))
+    })
+    .await
+    .unwrap_or(Err(AgentChatError::Timeout {
+        after_secs: AGENT_CHAT_DEADLINE.as_secs(),
+    }));
+
+    if let Some(id) = &request.request_id {
+        if let Ok(mut chats) = ACTIVE_CHATS.lock() {
+            chats.remove(id);
+        }
+    }
+
+    let (response, actions) = result?;
 
-    // Parse actions from response
-    let actions = parse_actions_from_response(&response.message);
+    if let Some(project_id) = &project_id {
+        if let Err(e) = agent_memory::record_turn(
+            project_id,
+            &request.agent_role,
+            &user_message,
+            &response.message,
+        )
+        .await
+        {
+            tracing::warn!("Could not save agent memory for {}: {}", project_id, e);
+        }
+    }
 
-    // Execute actions if requested
+    // Execute actions if requested (never past the user's credit cap)
+    let max_credits = request
+        .context
+        .as_ref()
+        .and_then(|c| c.preferences.as_ref())
+        .map(|p| p.max_credits_per_request);
     let action_results = if request.auto_execute && !actions.is_empty() {
         let mut results = Vec::new();
         for action in &actions {
-            let result = ActionExecutor::execute(action.clone()).await;
+            let result = ActionExecutor::execute_with_cap(action.clone(), max_credits, false).await;
             results.push(result);
         }
         results
@@ -101,23 +199,47 @@ pub async fn agent_chat_full(request: FullAgentRequest) -> Result<FullAgentRespo
         actions,
         action_results,
         tokens_used: response.tokens_used,
+        context_trimmed: response.context_trimmed,
+        substitution: response.substitution,
     })
 }
 
+/// Cancel an in-flight `agent_chat_full` call; returns false if it already finished
+#[tauri::command]
+#[specta::specta]
+pub fn cancel_agent_chat(request_id: String) -> bool {
+    ACTIVE_CHATS
+        .lock()
+        .ok()
+        .and_then(|mut chats| chats.remove(&request_id))
+        .is_some_and(|tx| tx.send(()).is_ok())
+}
+
 /// Execute a single action
+///
+/// Actions estimated above `max_credits` are blocked unless `confirmed`.
 #[tauri::command]
 #[specta::specta]
-pub async fn execute_agent_action(action: AgentAction) -> Result<ActionResult, String> {
-    Ok(ActionExecutor::execute(action).await)
+pub async fn execute_agent_action(
+    action: AgentAction,
+    max_credits: Option<f32>,
+    confirmed: Option<bool>,
+) -> Result<ActionResult, String> {
+    Ok(ActionExecutor::execute_with_cap(action, max_credits, confirmed.unwrap_or(false)).await)
 }
 
 /// Execute multiple actions
 #[tauri::command]
 #[specta::specta]
-pub async fn execute_agent_actions(actions: Vec<AgentAction>) -> Result<Vec<ActionResult>, String> {
+pub async fn execute_agent_actions(
+    actions: Vec<AgentAction>,
+    max_credits: Option<f32>,
+    confirmed: Option<bool>,
+) -> Result<Vec<ActionResult>, String> {
+    let confirmed = confirmed.unwrap_or(false);
     let mut results = Vec::new();
     for action in actions {
-        results.push(ActionExecutor::execute(action).await);
+        results.push(ActionExecutor::execute_with_cap(action, max_credits, confirmed).await);
     }
     Ok(results)
 }
@@ -125,12 +247,162 @@ pub async fn execute_agent_actions(actions: Vec<AgentAction>) -> Result<Vec<Acti
 /// Route a message to the best agent
 #[tauri::command]
 #[specta::specta]
-pub fn route_message_to_agent(message: String) -> String {
+pub async fn route_message_to_agent(message: String) -> String {
     let executor = get_agent_executor();
-    let role = executor.route_request(&message);
+    let role = executor.route_request_llm(&message).await;
     format!("{:?}", role).to_lowercase()
 }
 
+/// Get the keyword routing table shared by all agent routers
+#[tauri::command]
+#[specta::specta]
+pub fn get_routing_table() -> RoutingTable {
+    routing::routing_table()
+}
+
+/// Replace the routing table (e.g. to add domain-specific keywords)
+#[tauri::command]
+#[specta::specta]
+pub fn set_routing_table(table: RoutingTable) -> Result<(), String> {
+    tracing::info!("Updating agent routing table ({} rules)", table.rules.len());
+    routing::set_routing_table(table)
+}
+
+/// Get the model fallback chains used when a provider fails
+#[tauri::command]
+#[specta::specta]
+pub fn get_fallback_config() -> FallbackConfig {
+    model_selection::fallback_config()
+}
+
+/// Replace the model fallback chains
+#[tauri::command]
+#[specta::specta]
+pub fn set_fallback_config(config: FallbackConfig) -> Result<(), String> {
+    model_selection::set_fallback_config(config)
+}
+
+/// Get the agent reply cleanup settings
+#[tauri::command]
+#[specta::specta]
+pub fn get_response_cleanup() -> ResponseCleanup {
+    agent_executor::response_cleanup()
+}
+
+/// Toggle stripping of leaked instructions and role preambles, globally or per agent
+#[tauri::command]
+#[specta::specta]
+pub fn set_response_cleanup(config: ResponseCleanup) -> Result<(), String> {
+    agent_executor::set_response_cleanup(config)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PROJECT MEMORY
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Get the established facts (the Showrunner's Bible) for a project
+#[tauri::command]
+#[specta::specta]
+pub async fn get_project_memory(project_id: String) -> Result<ProjectMemory, String> {
+    project_memory::recall(&project_id).await
+}
+
+/// Add or update an established fact for a project
+#[tauri::command]
+#[specta::specta]
+pub async fn remember_project_fact(
+    project_id: String,
+    key: String,
+    value: String,
+) -> Result<ProjectMemory, String> {
+    project_memory::remember(&project_id, &key, &value).await
+}
+
+/// Remove an established fact from a project
+#[tauri::command]
+#[specta::specta]
+pub async fn forget_project_fact(project_id: String, key: String) -> Result<ProjectMemory, String> {
+    project_memory::forget(&project_id, &key).await
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// AGENT MEMORY
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Get what an agent remembers of its conversations about a project
+#[tauri::command]
+#[specta::specta]
+pub async fn get_agent_memory(project_id: String, role: String) -> Result<AgentMemory, String> {
+    agent_memory::recall(&project_id, &role).await
+}
+
+/// Make an agent forget its conversations about a project
+#[tauri::command]
+#[specta::specta]
+pub async fn clear_agent_memory(project_id: String, role: String) -> Result<(), String> {
+    agent_memory::clear(&project_id, &role).await
+}
+
+/// Get how many messages agents keep verbatim before summarizing
+#[tauri::command]
+#[specta::specta]
+pub fn get_agent_memory_config() -> AgentMemoryConfig {
+    agent_memory::memory_config()
+}
+
+/// Change how many messages agents keep verbatim before summarizing
+#[tauri::command]
+#[specta::specta]
+pub fn set_agent_memory_config(config: AgentMemoryConfig) -> Result<(), String> {
+    agent_memory::set_memory_config(config)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// MCP TOOLS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Get the MCP servers agents may call tools on
+#[tauri::command]
+#[specta::specta]
+pub fn get_mcp_servers() -> McpSettings {
+    registry::mcp_settings()
+}
+
+/// Replace the MCP servers agents may call tools on
+#[tauri::command]
+#[specta::specta]
+pub async fn set_mcp_servers(settings: McpSettings) -> Result<(), String> {
+    registry::set_mcp_settings(settings).await
+}
+
+/// List the tools the enabled MCP servers offer
+#[tauri::command]
+#[specta::specta]
+pub async fn list_mcp_tools() -> Vec<McpToolInfo> {
+    registry::available_tools()
+        .await
+        .into_iter()
+        .map(McpToolInfo::from)
+        .collect()
+}
+
+/// Serve the Vault and generation as MCP tools on `ws://127.0.0.1:<port>`
+///
+/// Returns the URL and the bearer token clients must send; fails while a
+/// server is already running.
+#[tauri::command]
+#[specta::specta]
+pub async fn start_mcp_server(port: u16) -> Result<McpServerInfo, String> {
+    server::start(port).await
+}
+
+/// Stop the MCP server; false if it wasn't running
+#[tauri::command]
+#[specta::specta]
+pub async fn stop_mcp_server() -> bool {
+    server::stop().await
+}
+
 /// Get list of agent roles
 #[tauri::command]
 #[specta::specta]
@@ -174,15 +446,46 @@ mod tests {
                 vault: None,
                 mode: "writer".into(),
                 project_name: Some("Test".into()),
+                project_id: None,
+                project_memory: None,
                 preferences: None,
             }),
             history: vec![],
             provider: None,
             model: None,
             auto_execute: false,
+            request_id: None,
         };
 
         assert_eq!(request.agent_role, "scriptwriter");
         assert!(request.context.is_some());
     }
+
+    #[test]
+    fn test_request_id_is_optional() {
+        let request = |extra: serde_json::Value| {
+            let mut json = serde_json::json!({
+                "agent_role": "scriptwriter",
+                "message": "Hello",
+                "context": null,
+                "history": [],
+                "provider": null,
+                "model": null,
+                "auto_execute": false,
+            });
+            json.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            serde_json::from_value::<FullAgentRequest>(json).unwrap()
+        };
+
+        // Older frontends don't send one; those chats just can't be cancelled
+        assert_eq!(request(serde_json::json!({})).request_id, None);
+        assert_eq!(
+            request(serde_json::json!({ "request_id": "chat-1" }))
+                .request_id
+                .as_deref(),
+            Some("chat-1")
+        );
+    }
 }
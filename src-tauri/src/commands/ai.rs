@@ -4,13 +4,33 @@
 
 use crate::ai::{
     agents::traits::AgentRole,
+    cost::{self, PricingConfig},
+    dialogue::{self, DialogueLine, DialogueOptions, DialogueTrack},
+    elevenlabs_client::{
+        self, ElevenLabsClient, TtsCache, TtsRequest, TtsResult, TtsStreamChunk, Voice,
+    },
+    generate::{self, GenerateRequest, GenerationHandle},
+    generation_queue::{self, generation_queue, QueueLimits, QueueStatus},
+    llm_client::{get_llm_client, LLMRequest, LLMResponse},
+    llm_stream::{ChatStreamChunk, StreamCollector},
     local::{detect_hardware, HardwareCapabilities},
+    local_models::{discover_local_models, LocalModelDiscovery},
     models::{
         get_all_models, get_local_models, get_models_by_capability, ModelCapability,
         ModelDefinition,
     },
+    prompt_enhancer::{self, EnhancedPrompt, PromptKind},
     router::{route_model_request, RouterDecision},
+    storyboard::{self, StoryboardOptions, StoryboardResult},
+    transcription::{self, Transcript},
 };
+use crate::comfyui::models::CloudModels;
+use crate::request_log::{self, RequestLogEntry};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use bytes::Bytes;
+use futures_util::StreamExt;
+use std::time::Instant;
+use tauri::Emitter;
 
 // ═══════════════════════════════════════════════════════════════════════════════
 // MODEL COMMANDS
@@ -88,16 +108,296 @@ pub fn route_request(model_id: String, prefer_local: bool) -> Result<RouterDecis
     route_model_request(cap, Some(model_id), prefer_local)
 }
 
-/// Get models that can run on current hardware
+/// Get the models served by the running local runtimes (Ollama, Llama Stack)
+///
+/// Results are cached for a few seconds; pass `refresh` to query again.
 #[tauri::command]
 #[specta::specta]
-pub fn get_available_local_models() -> Vec<ModelDefinition> {
-    tracing::debug!("Fetching available local models");
-    let hw = detect_hardware();
-    get_local_models()
-        .into_iter()
-        .filter(|m| crate::ai::local::can_run_locally(&m.id, &hw))
-        .collect()
+pub async fn get_available_local_models(refresh: bool) -> LocalModelDiscovery {
+    tracing::debug!("Discovering local models");
+    discover_local_models(refresh).await
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PRICING COMMANDS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Get the credit conversion rate and markups used for cost estimates
+#[tauri::command]
+#[specta::specta]
+pub fn get_pricing_config() -> PricingConfig {
+    cost::pricing()
+}
+
+/// Apply the pricing reported by the backend's `/api/credits`
+#[tauri::command]
+#[specta::specta]
+pub fn set_pricing_config(config: PricingConfig) {
+    cost::set_pricing(config);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// VOICE COMMANDS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Delete all cached text-to-speech audio
+#[tauri::command]
+#[specta::specta]
+pub fn clear_tts_cache() -> Result<(), String> {
+    tracing::info!("Clearing TTS cache");
+    elevenlabs_client::clear_tts_cache()
+}
+
+/// Voices on the ElevenLabs account, for the voice picker
+#[tauri::command]
+#[specta::specta]
+pub async fn list_voices() -> Result<Vec<Voice>, String> {
+    ElevenLabsClient::new()?.list_voices().await
+}
+
+/// Speak a line while it is generated, emitting audio as `tts-stream` events
+///
+/// Events carry the caller's `stream_id`; the last is `done`. The finished
+/// audio is cached like other TTS output and returned. Cached lines are
+/// returned at once without events. `model` defaults to Flash v2.5.
+#[tauri::command]
+#[specta::specta]
+pub async fn stream_tts(
+    window: tauri::Window,
+    stream_id: String,
+    text: String,
+    voice_id: Option<String>,
+    model: Option<String>,
+) -> Result<TtsResult, String> {
+    let request = TtsRequest::new(
+        &text,
+        voice_id,
+        model.as_deref().unwrap_or(CloudModels::ELEVENLABS_FLASH),
+    );
+    let client = ElevenLabsClient::new()?;
+
+    TtsCache::default_cache()
+        .get_or_fetch(&request, || async {
+            let mut chunks = std::pin::pin!(client.stream_tts(
+                &request.text,
+                &request.voice_id,
+                &request.model_id
+            ));
+            let mut audio = Vec::new();
+            while let Some(chunk) = chunks.next().await {
+                let chunk = chunk?;
+                audio.extend_from_slice(&chunk);
+                window
+                    .emit(
+                        "tts-stream",
+                        TtsStreamChunk {
+                            stream_id: stream_id.clone(),
+                            audio: STANDARD.encode(&chunk),
+                            done: false,
+                        },
+                    )
+                    .ok();
+            }
+            window
+                .emit(
+                    "tts-stream",
+                    TtsStreamChunk {
+                        stream_id: stream_id.clone(),
+                        audio: String::new(),
+                        done: true,
+                    },
+                )
+                .ok();
+            Ok(Bytes::from(audio))
+        })
+        .await
+}
+
+/// Synthesize a multi-speaker dialogue into one track with per-line timings
+///
+/// Lines without an explicit voice use their character token's voice from
+/// `project_id`'s Vault.
+#[tauri::command]
+#[specta::specta]
+pub async fn synthesize_dialogue(
+    project_id: Option<String>,
+    lines: Vec<DialogueLine>,
+    gap_ms: Option<u32>,
+) -> Result<DialogueTrack, String> {
+    tracing::info!("Synthesizing dialogue ({} lines)", lines.len());
+    let options = DialogueOptions {
+        project_id,
+        gap_ms: gap_ms.unwrap_or(dialogue::DEFAULT_GAP_MS),
+        ..Default::default()
+    };
+    dialogue::synthesize_dialogue(lines, options).await
+}
+
+/// Generate one storyboard frame per scene of the project's script
+///
+/// Emits `storyboard-progress` events; stop early with `cancel_storyboard`.
+#[tauri::command]
+#[specta::specta]
+pub async fn generate_storyboard(
+    window: tauri::Window,
+    project_id: String,
+    options: Option<StoryboardOptions>,
+) -> Result<StoryboardResult, String> {
+    let options = options.unwrap_or_default();
+    tracing::info!(
+        "Generating storyboard {} for project {}",
+        options.storyboard_id,
+        project_id
+    );
+
+    storyboard::generate_storyboard(&project_id, options, |progress| {
+        window.emit("storyboard-progress", progress).ok();
+    })
+    .await
+}
+
+/// Single entry point for generation: routes to chat, local ComfyUI or Fal.ai
+///
+/// Emits `generate-progress` events. Cloud and local jobs return a `Queued`
+/// handle; requests over the credit cap return `NeedsConfirmation`.
+#[tauri::command]
+#[specta::specta]
+pub async fn generate(
+    window: tauri::Window,
+    request: GenerateRequest,
+) -> Result<GenerationHandle, String> {
+    tracing::info!("Generating {} ({:?})", request.task_type, request.model_id);
+
+    generate::generate(request, |progress| {
+        window.emit("generate-progress", progress).ok();
+    })
+    .await
+}
+
+/// Stream a chat response, emitting every delta as an `llm-stream` event
+///
+/// Events carry the caller's `stream_id`; the last is `done` with the finish
+/// reason and token usage. The collected response is returned as well.
+#[tauri::command]
+#[specta::specta]
+pub async fn chat_stream(
+    window: tauri::Window,
+    stream_id: String,
+    request: LLMRequest,
+) -> Result<LLMResponse, String> {
+    let started = Instant::now();
+    let provider = request.provider.clone();
+    let model = request.model.clone();
+
+    let result = async move {
+        let mut events = std::pin::pin!(get_llm_client().chat_stream(request));
+        let mut collector = StreamCollector::default();
+        while let Some(event) = events.next().await {
+            let event = event?;
+            collector.push(&event);
+            window
+                .emit(
+                    "llm-stream",
+                    ChatStreamChunk {
+                        stream_id: stream_id.clone(),
+                        event,
+                    },
+                )
+                .ok();
+        }
+        collector.into_response()
+    }
+    .await;
+
+    request_log::record(RequestLogEntry::llm(
+        &provider,
+        &model,
+        None,
+        result.as_ref().ok(),
+        started.elapsed(),
+    ))
+    .await;
+
+    result
+}
+
+/// Running and waiting generations with the current concurrency caps
+#[tauri::command]
+#[specta::specta]
+pub fn get_generation_queue() -> QueueStatus {
+    generation_queue().status()
+}
+
+/// Set the local and cloud concurrency caps (persisted across restarts)
+#[tauri::command]
+#[specta::specta]
+pub fn set_generation_queue_limits(limits: QueueLimits) -> Result<QueueStatus, String> {
+    tracing::info!(
+        "Generation queue limits: {} local, {} cloud",
+        limits.max_local,
+        limits.max_cloud
+    );
+    generation_queue::set_queue_limits(limits)
+}
+
+/// Change the priority of a waiting generation (higher starts first)
+#[tauri::command]
+#[specta::specta]
+pub fn reprioritize_generation(job_id: String, priority: i32) -> bool {
+    generation_queue().reprioritize(&job_id, priority)
+}
+
+/// Cancel a generation that has not started yet
+#[tauri::command]
+#[specta::specta]
+pub fn cancel_queued_generation(job_id: String) -> bool {
+    generation_queue().cancel(&job_id)
+}
+
+/// Expand a short idea into a structured, model-ready prompt
+///
+/// `token_ids` are Vault tokens to inject (descriptions and LoRA triggers).
+#[tauri::command]
+#[specta::specta]
+pub async fn enhance_prompt(
+    kind: PromptKind,
+    idea: String,
+    token_ids: Vec<String>,
+    style: Option<String>,
+) -> Result<EnhancedPrompt, String> {
+    let tokens = if token_ids.is_empty() {
+        Vec::new()
+    } else {
+        crate::commands::tokens::get_token_contexts(token_ids).await?
+    };
+
+    prompt_enhancer::enhance_prompt(kind, &idea, &tokens, style.as_deref()).await
+}
+
+/// Transcribe an audio/video file locally with Whisper
+///
+/// `model` is a downloaded `whisper-*` model (default `whisper-base`).
+/// Emits `transcription-progress` events.
+#[tauri::command]
+#[specta::specta]
+pub async fn transcribe_media(
+    window: tauri::Window,
+    path: String,
+    model: Option<String>,
+) -> Result<Transcript, String> {
+    tracing::info!("Transcribing {}", path);
+
+    transcription::transcribe_media(&path, model.as_deref(), |progress| {
+        window.emit("transcription-progress", progress).ok();
+    })
+    .await
+}
+
+/// Cancel a running storyboard; scenes already generating still finish
+#[tauri::command]
+#[specta::specta]
+pub fn cancel_storyboard(storyboard_id: String) -> bool {
+    storyboard::cancel_storyboard(&storyboard_id)
 }
 
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -2,20 +2,41 @@
 //!
 //! Exposes ComfyUI installation, process management, and execution to the frontend
 
-use crate::comfyui::{self, ComfyUIConfig, ComfyUIStatus};
+use crate::ai::actions::ActionExecutor;
+use crate::ai::comfyui_client::{ProgressUpdate, QueueStatus};
+use crate::ai::cost::usd_to_credits;
+use crate::ai::generation_queue::{generation_queue, GenerationLane, DEFAULT_PRIORITY};
+use crate::ai::hybrid::{HybridExecutor, HybridRequest, HybridResult};
+use crate::ai::workflow_generator::{generate_workflow, WorkflowRequest};
+use crate::comfyui::{
+    self,
+    client::SystemStats,
+    output::{IngestedExecution, OutputSettings},
+    ComfyUIConfig, ComfyUIStatus,
+};
+use crate::vault::{
+    self,
+    assets::{Asset, AssetFilter},
+    history::{self, GenerationRecord, HistoryFilter},
+    Page,
+};
 use tauri::Emitter;
+use tokio::sync::mpsc;
 
 /// Get ComfyUI status (installation + running state)
 #[tauri::command]
 #[specta::specta]
 pub async fn get_comfyui_status() -> Result<ComfyUIStatus, String> {
-    let config = ComfyUIConfig::default();
+    // Follows the port ComfyUI actually started on
+    let client = crate::ai::comfyui_client::get_client();
+    let config = client.config();
 
     Ok(ComfyUIStatus {
         installed: comfyui::installer::is_installed(),
         running: comfyui::process::is_running(&config.host, config.port),
+        port: config.port,
         version: comfyui::installer::get_version(),
-        install_path: config.install_path.display().to_string(),
+        install_path: comfyui::get_default_install_path().display().to_string(),
     })
 }
 
@@ -58,9 +79,14 @@ pub async fn install_comfyui(window: tauri::Window) -> Result<String, String> {
 pub async fn start_comfyui() -> Result<(), String> {
     let config = ComfyUIConfig::default();
 
-    comfyui::process::start_comfyui(config.install_path, &config.host, config.port)
-        .await
-        .map_err(|e| e.to_string())
+    comfyui::process::start_comfyui(
+        config.install_path,
+        &config.output_dir,
+        &config.host,
+        config.port,
+    )
+    .await
+    .map_err(|e| e.to_string())
 }
 
 /// Stop ComfyUI server
@@ -77,15 +103,15 @@ pub async fn stop_comfyui() -> Result<(), String> {
 #[specta::specta]
 pub async fn generate_image(
     prompt: String,
-    seed: Option<u64>,
+    seed: Option<u32>,
     width: Option<u32>,
     height: Option<u32>,
 ) -> Result<String, String> {
-    let config = ComfyUIConfig::default();
-    let client = comfyui::client::ComfyUIClient::new(&config.host, config.port);
+    let client = comfyui::client::ComfyUIClient::active();
 
     // Create FLUX Schnell workflow
-    let workflow = comfyui::workflows::flux_schnell_text2img(&prompt, seed, width, height);
+    let workflow =
+        comfyui::workflows::flux_schnell_text2img(&prompt, seed.map(u64::from), width, height);
 
     // Queue workflow for execution
     let response = client
@@ -96,14 +122,255 @@ pub async fn generate_image(
     Ok(response.prompt_id)
 }
 
-/// Get system stats from ComfyUI
+/// Channel whose updates are emitted as `comfyui-progress` events
+///
+/// Updates carry the prompt id, so concurrent runs get separate progress bars.
+fn progress_events(window: tauri::Window) -> mpsc::Sender<ProgressUpdate> {
+    let (tx, mut rx) = mpsc::channel::<ProgressUpdate>(32);
+    tauri::async_runtime::spawn(async move {
+        while let Some(update) = rx.recv().await {
+            window.emit("comfyui-progress", update).ok();
+        }
+    });
+    tx
+}
+
+/// Run a workflow and ingest its outputs into the project's assets
+///
+/// Emits `comfyui-progress` events.
 #[tauri::command]
 #[specta::specta]
-pub async fn get_comfyui_stats() -> Result<String, String> {
-    let config = ComfyUIConfig::default();
-    let client = comfyui::client::ComfyUIClient::new(&config.host, config.port);
+pub async fn run_comfyui_workflow(
+    window: tauri::Window,
+    project_id: String,
+    workflow: serde_json::Value,
+) -> Result<IngestedExecution, String> {
+    let client = crate::ai::comfyui_client::get_client();
+    let progress = progress_events(window);
+    comfyui::output::execute_and_ingest(&client, &project_id, workflow, Some(progress)).await
+}
+
+/// Stop a ComfyUI prompt: dequeue it if it hasn't started, interrupt it if running
+#[tauri::command]
+#[specta::specta]
+pub async fn comfyui_interrupt(prompt_id: String) -> Result<(), String> {
+    crate::ai::comfyui_client::get_client()
+        .interrupt(&prompt_id)
+        .await
+}
+
+/// Prompts running and waiting in ComfyUI, from any client
+#[tauri::command]
+#[specta::specta]
+pub async fn comfyui_get_queue() -> Result<QueueStatus, String> {
+    crate::ai::comfyui_client::get_client().get_queue().await
+}
+
+/// Checkpoints, LoRAs, VAEs and ControlNets installed in ComfyUI
+#[tauri::command]
+#[specta::specta]
+pub async fn get_all_model_types() -> Result<crate::ai::comfyui_client::ModelInventory, String> {
+    crate::ai::comfyui_client::get_client()
+        .get_all_model_types()
+        .await
+}
+
+/// Point the ComfyUI client at a new host/port; running executions keep the old one
+#[tauri::command]
+#[specta::specta]
+pub async fn reconfigure_comfyui(
+    config: crate::ai::comfyui_client::ComfyUIConfig,
+) -> Result<(), String> {
+    crate::ai::comfyui_client::reconfigure_client(config);
+    Ok(())
+}
+
+/// Get the ComfyUI output directory and auto-ingest settings
+#[tauri::command]
+#[specta::specta]
+pub async fn get_comfyui_output_settings() -> Result<OutputSettings, String> {
+    Ok(comfyui::output::output_settings())
+}
+
+/// Update the ComfyUI output settings (the directory applies on next start)
+#[tauri::command]
+#[specta::specta]
+pub async fn set_comfyui_output_settings(settings: OutputSettings) -> Result<(), String> {
+    comfyui::output::set_output_settings(settings)
+}
+
+/// Get all assets of a project, newest first
+#[tauri::command]
+#[specta::specta]
+pub async fn get_project_assets(project_id: String) -> Result<Vec<Asset>, String> {
+    let db = vault::wait_for_db(vault::DB_WAIT_TIMEOUT)
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    let mut result = db
+        .query("SELECT * FROM asset WHERE project_id = $pid ORDER BY created_at DESC")
+        .bind(("pid", project_id))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    vault::or_empty(result.take(0))
+}
+
+/// Get one page of a project's assets, newest first, for the gallery
+#[tauri::command]
+#[specta::specta]
+pub async fn list_assets(
+    project_id: String,
+    cursor: Option<String>,
+    limit: Option<u32>,
+    filter: Option<AssetFilter>,
+) -> Result<Page<Asset>, String> {
+    let db = vault::wait_for_db(vault::DB_WAIT_TIMEOUT)
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    vault::assets::list_assets(
+        &db,
+        &project_id,
+        cursor.as_deref(),
+        limit,
+        filter.unwrap_or_default(),
+    )
+    .await
+}
 
-    let stats = client.get_system_stats().await.map_err(|e| e.to_string())?;
+/// Run a generation request for a project and record it in the generation history
+///
+/// Local runs finish and have their outputs ingested; cloud runs are recorded
+/// once submitted, with the Fal.ai request id and no assets yet. Local runs
+/// emit `comfyui-progress` events.
+#[tauri::command]
+#[specta::specta]
+pub async fn run_generation(
+    window: tauri::Window,
+    project_id: String,
+    request: WorkflowRequest,
+) -> Result<GenerationRecord, String> {
+    let workflow = generate_workflow(&request)?;
+    // Keep the seed that was drawn so the cloud run and the history entry match it
+    let request = WorkflowRequest {
+        seed: Some(workflow.seed),
+        ..request
+    };
+
+    let (execution_id, asset_ids, credits) = if workflow.is_local {
+        let prompt: serde_json::Value = serde_json::from_str(&workflow.workflow_json)
+            .map_err(|e| format!("Invalid generated workflow JSON: {}", e))?;
+        let client = crate::ai::comfyui_client::get_client();
+        if request.control.is_some() {
+            client.check_requirements(&prompt).await?;
+        }
+        let ingested = generation_queue()
+            .run(
+                GenerationLane::Local,
+                &request.model,
+                DEFAULT_PRIORITY,
+                comfyui::output::execute_and_ingest(
+                    &client,
+                    &project_id,
+                    prompt,
+                    Some(progress_events(window)),
+                ),
+            )
+            .await??;
+        if !ingested.result.success {
+            return Err(ingested
+                .result
+                .error
+                .unwrap_or_else(|| "Workflow failed".to_string()));
+        }
+        (
+            Some(ingested.result.execution_id),
+            ingested.assets.into_iter().filter_map(|a| a.id).collect(),
+            usd_to_credits(workflow.estimated_cost as f32, &request.model),
+        )
+    } else {
+        let result = ActionExecutor::execute_image_workflow(&request).await;
+        if !result.success {
+            return Err(result
+                .error
+                .unwrap_or_else(|| "Generation failed".to_string()));
+        }
+        (
+            result.execution_id,
+            Vec::new(),
+            result.credits_used.unwrap_or_default(),
+        )
+    };
 
-    serde_json::to_string(&stats).map_err(|e| format!("Failed to serialize stats: {}", e))
+    let db = vault::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+    history::record_generation(
+        &db,
+        GenerationRecord::new(&project_id, &request, asset_ids, execution_id, credits),
+    )
+    .await
+}
+
+/// Get one page of a project's generation history, newest first
+#[tauri::command]
+#[specta::specta]
+pub async fn get_generation_history(
+    project_id: String,
+    filter: Option<HistoryFilter>,
+    cursor: Option<String>,
+    limit: Option<u32>,
+) -> Result<Page<GenerationRecord>, String> {
+    let db = vault::wait_for_db(vault::DB_WAIT_TIMEOUT)
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    history::list_history(
+        &db,
+        &project_id,
+        cursor.as_deref(),
+        limit,
+        filter.unwrap_or_default(),
+    )
+    .await
+}
+
+/// The request behind a history entry, to re-run it with the same settings
+#[tauri::command]
+#[specta::specta]
+pub async fn reuse_generation_settings(entry_id: String) -> Result<WorkflowRequest, String> {
+    let db = vault::wait_for_db(vault::DB_WAIT_TIMEOUT)
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    Ok(history::get_entry(&db, &entry_id).await?.to_request())
+}
+
+/// Get system stats (GPU, VRAM, torch/python versions) from ComfyUI
+#[tauri::command]
+#[specta::specta]
+pub async fn get_comfyui_stats() -> Result<SystemStats, String> {
+    let client = comfyui::client::ComfyUIClient::active();
+
+    client.get_system_stats().await.map_err(|e| e.to_string())
+}
+
+/// Hybrid generation: heavy compute on Fal.ai, post-processing on local ComfyUI.
+///
+/// Emits `hybrid-progress` events; falls back to the cloud output when local
+/// ComfyUI isn't running.
+#[tauri::command]
+#[specta::specta]
+pub async fn execute_hybrid_generation(
+    window: tauri::Window,
+    request: HybridRequest,
+) -> Result<HybridResult, String> {
+    let executor = HybridExecutor::from_env()?;
+
+    executor
+        .execute(request, |progress| {
+            window.emit("hybrid-progress", progress).ok();
+        })
+        .await
 }
@@ -2,7 +2,10 @@
 
 use crate::ai::actions::AgentAction;
 use crate::ai::crew::MainAgent;
-use crate::ai::{model_selection::ModelSelection, Agent, AgentContext, UserPreferences};
+use crate::ai::{
+    model_selection::ModelSelection, project_memory, Agent, AgentContext, TokenReference,
+    UserPreferences,
+};
 use serde::{Deserialize, Serialize};
 
 /// Request for crew chat
@@ -25,6 +28,8 @@ pub struct CrewChatResponse {
     pub agent: String,
     pub content: String,
     pub actions: Vec<AgentAction>,
+    /// Vault tokens the agent relied on
+    pub citations: Vec<TokenReference>,
     pub cost: f32,
     pub model: String,
     pub provider: String,
@@ -36,13 +41,15 @@ pub struct CrewChatResponse {
 pub async fn chat_with_crew(request: CrewChatRequest) -> Result<CrewChatResponse, String> {
     let main_agent = MainAgent::new();
 
-    let context = request.context.unwrap_or_else(|| AgentContext {
+    let mut context = request.context.unwrap_or_else(|| AgentContext {
         script: None,
         canvas: None,
         timeline: None,
         vault: None,
         mode: "default".to_string(),
         project_name: None,
+        project_id: None,
+        project_memory: None,
         preferences: Some(UserPreferences {
             prefer_local: request.prefer_local,
             max_credits_per_request: request.max_credits,
@@ -54,6 +61,9 @@ pub async fn chat_with_crew(request: CrewChatRequest) -> Result<CrewChatResponse
         }),
     });
 
+    // Every crew member sees the project's established facts
+    project_memory::attach(&mut context).await;
+
     let response = main_agent
         .route(&request.message, context)
         .await
@@ -80,6 +90,7 @@ pub async fn chat_with_crew(request: CrewChatRequest) -> Result<CrewChatResponse
         agent: response.agent,
         content: response.content,
         actions: response.actions.clone(),
+        citations: response.citations,
         cost: response.cost.unwrap_or(0.0),
         model: response.metadata.model,
         provider,
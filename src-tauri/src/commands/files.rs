@@ -2,7 +2,11 @@
 //!
 //! Native file dialog operations for Open/Save/Export
 
+use crate::vault::backups::RestoreReport;
+use crate::vault::bundle::{self, BundleManifest};
+use crate::vault::models::Project;
 use std::fs;
+use std::path::{Path, PathBuf};
 use tauri::AppHandle;
 
 /// Open a file using native dialog
@@ -96,3 +100,151 @@ pub async fn export_pdf_dialog(
         None => Ok(None),
     }
 }
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PROJECT BUNDLES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Export a project (script, tokens, assets, sync snapshot) to a zip bundle
+#[tauri::command]
+#[specta::specta]
+pub async fn export_project_bundle(
+    project_id: String,
+    path: String,
+) -> Result<BundleManifest, String> {
+    bundle::export_project(&project_id, Path::new(&path)).await
+}
+
+/// Import a zip bundle as a new project
+#[tauri::command]
+#[specta::specta]
+pub async fn import_project_bundle(path: String) -> Result<Project, String> {
+    bundle::import_project(Path::new(&path)).await
+}
+
+/// Export a project to a `.cinemaos` bundle in the exports folder
+#[tauri::command]
+#[specta::specta]
+pub async fn export_project(project_id: String) -> Result<PathBuf, String> {
+    let safe_id: String = project_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let dir = crate::installer::get_cinema_os_dir().join("exports");
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!(
+        "{}-{}.{}",
+        safe_id,
+        chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+        bundle::BUNDLE_EXTENSION
+    ));
+
+    bundle::export_project(&project_id, &path).await?;
+    Ok(path)
+}
+
+/// Import a `.cinemaos` bundle as a new project (new ids, no collisions)
+#[tauri::command]
+#[specta::specta]
+pub async fn import_project(path: PathBuf) -> Result<Project, String> {
+    import_project_bundle(path.to_string_lossy().into_owned()).await
+}
+
+/// Export a project bundle using native save dialog
+#[tauri::command]
+#[specta::specta]
+pub async fn export_project_bundle_dialog(
+    app: AppHandle,
+    project_id: String,
+    default_name: Option<String>,
+) -> Result<Option<BundleManifest>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let mut dialog = app.dialog().file().add_filter(
+        "CinemaOS Project Bundle",
+        &[bundle::BUNDLE_EXTENSION, "zip"],
+    );
+
+    if let Some(name) = default_name {
+        dialog = dialog.set_file_name(&name);
+    }
+
+    match dialog.blocking_save_file() {
+        Some(path) => export_project_bundle(project_id, path.to_string())
+            .await
+            .map(Some),
+        None => Ok(None), // User cancelled
+    }
+}
+
+/// Import a project bundle using native open dialog
+#[tauri::command]
+#[specta::specta]
+pub async fn import_project_bundle_dialog(app: AppHandle) -> Result<Option<Project>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let file_path = app
+        .dialog()
+        .file()
+        .add_filter(
+            "CinemaOS Project Bundle",
+            &[bundle::BUNDLE_EXTENSION, "zip"],
+        )
+        .blocking_pick_file();
+
+    match file_path {
+        Some(path) => import_project_bundle(path.to_string()).await.map(Some),
+        None => Ok(None), // User cancelled
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// VAULT BACKUPS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Back up the whole Vault (every project) using native save dialog
+#[tauri::command]
+#[specta::specta]
+pub async fn backup_vault(app: AppHandle) -> Result<Option<String>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let file_name = format!(
+        "cinemaos-vault-{}.surql",
+        chrono::Utc::now().format("%Y-%m-%d")
+    );
+    let file_path = app
+        .dialog()
+        .file()
+        .add_filter("CinemaOS Vault Backup", &["surql"])
+        .set_file_name(&file_name)
+        .blocking_save_file();
+
+    match file_path {
+        Some(path) => {
+            let path = path.to_string();
+            crate::vault::backup(&path).await?;
+            Ok(Some(path))
+        }
+        None => Ok(None), // User cancelled
+    }
+}
+
+/// Replace the whole Vault with a backup picked in native open dialog
+///
+/// The current Vault is backed up automatically first.
+#[tauri::command]
+#[specta::specta]
+pub async fn restore_vault(app: AppHandle) -> Result<Option<RestoreReport>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let file_path = app
+        .dialog()
+        .file()
+        .add_filter("CinemaOS Vault Backup", &["surql"])
+        .blocking_pick_file();
+
+    match file_path {
+        Some(path) => crate::vault::restore(&path.to_string()).await.map(Some),
+        None => Ok(None), // User cancelled
+    }
+}
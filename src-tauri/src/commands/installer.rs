@@ -2,20 +2,30 @@
 //!
 //! Exposes installation, hardware detection, and model downloads to the frontend
 
+use crate::ai::local_models::list_ollama_models;
+use crate::errors::InstallFailure;
+use crate::installer::logs::ProcessLogs;
+use crate::installer::preflight::{self, PreflightReport};
 use crate::installer::{
-    detect_hardware, download_model, download_via_ollama, get_downloaded_models,
-    get_installation_state, get_model_recommendations, get_model_sources, get_ollama_models,
+    detect_hardware, download_model, download_models_parallel, download_via_ollama,
+    get_downloaded_models, get_installation_state, get_model_recommendations, get_model_sources,
     get_recommended_models, get_runnable_models, install_all, is_model_downloaded,
-    is_ollama_installed, ComfyUIProcess, HardwareInfo, InstallationState, ModelRecommendation,
-    ModelSource,
+    is_ollama_installed, ComfyUIProcess, HardwareInfo, InstallationState, ModelDownloadResult,
+    ModelRecommendation, ModelSource, DEFAULT_MAX_CONCURRENT_DOWNLOADS,
 };
 use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use tauri::Emitter;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::RwLock;
 
+// ComfyUI output, readable while `start` holds the process lock
+static COMFYUI_LOGS: Lazy<ProcessLogs> = Lazy::new(ProcessLogs::new);
+
 // Global ComfyUI process handle
 static COMFYUI_PROCESS: Lazy<Arc<RwLock<ComfyUIProcess>>> =
-    Lazy::new(|| Arc::new(RwLock::new(ComfyUIProcess::new())));
+    Lazy::new(|| Arc::new(RwLock::new(ComfyUIProcess::with_logs(COMFYUI_LOGS.clone()))));
 
 // ═══════════════════════════════════════════════════════════════════════════════
 // INSTALLATION COMMANDS
@@ -36,9 +46,11 @@ pub async fn is_system_ready() -> bool {
 }
 
 /// Run full installation
+///
+/// Failures come back as an `InstallFailure` with a remediation hint.
 #[tauri::command]
 #[specta::specta]
-pub async fn run_installation() -> Result<String, String> {
+pub async fn run_installation() -> Result<String, InstallFailure> {
     install_all(|progress| {
         tracing::info!(
             "Install progress: {:?} - {} ({}%)",
@@ -52,6 +64,85 @@ pub async fn run_installation() -> Result<String, String> {
     Ok("Installation complete".into())
 }
 
+/// Update ComfyUI to the latest upstream commit, emitting `comfyui-update-progress` events
+///
+/// Fails with `ComfyUILocalChanges` if the checkout has been modified.
+#[tauri::command]
+#[specta::specta]
+pub async fn update_comfyui(window: tauri::Window) -> Result<String, InstallFailure> {
+    crate::installer::update_comfyui(move |progress| {
+        window.emit("comfyui-update-progress", progress).ok();
+    })
+    .await?;
+
+    Ok("ComfyUI updated".into())
+}
+
+/// Start the installed ComfyUI, emitting each output line as a `comfyui-log` event
+///
+/// On failure the error ends with ComfyUI's last stderr lines.
+#[tauri::command]
+#[specta::specta]
+pub async fn start_installed_comfyui(window: tauri::Window) -> Result<(), String> {
+    // One forwarder for the app's lifetime, however often ComfyUI restarts
+    static FORWARDING_LOGS: AtomicBool = AtomicBool::new(false);
+    if !FORWARDING_LOGS.swap(true, Ordering::SeqCst) {
+        forward_logs(window);
+    }
+
+    let mut process = COMFYUI_PROCESS.write().await;
+    process.start().await?;
+
+    // `start` moves to the next free port if the preferred one is taken
+    if let Some(port) = process.active_port() {
+        let config = crate::ai::comfyui_client::get_client().config().clone();
+        let local = config.host == "127.0.0.1" || config.host == "localhost";
+        if local && config.port != port {
+            crate::ai::comfyui_client::reconfigure_client(
+                crate::ai::comfyui_client::ComfyUIConfig { port, ..config },
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn forward_logs(window: tauri::Window) {
+    let mut lines = COMFYUI_LOGS.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match lines.recv().await {
+                Ok(line) => {
+                    window.emit("comfyui-log", line).ok();
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Stop the ComfyUI started by `start_installed_comfyui`
+#[tauri::command]
+#[specta::specta]
+pub async fn stop_installed_comfyui() -> Result<(), String> {
+    COMFYUI_PROCESS.write().await.stop().await
+}
+
+/// The last lines ComfyUI printed, oldest first
+#[tauri::command]
+#[specta::specta]
+pub fn comfyui_logs() -> Vec<String> {
+    COMFYUI_LOGS.recent()
+}
+
+/// Dry run of `run_installation`: per-step readiness and download estimates
+#[tauri::command]
+#[specta::specta]
+pub async fn preflight_install() -> PreflightReport {
+    preflight::preflight_install().await
+}
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // HARDWARE DETECTION COMMANDS
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -130,6 +221,42 @@ pub async fn download_model_by_id(model_id: String) -> Result<String, String> {
     Ok(path.to_string_lossy().to_string())
 }
 
+/// Check that a model fits on disk before offering to download it
+#[tauri::command]
+#[specta::specta]
+pub async fn check_disk_space_for(model_id: String) -> Result<(), String> {
+    Ok(crate::installer::check_disk_space_for(&model_id).await?)
+}
+
+/// Download several models in parallel, emitting `model-download-progress` events
+///
+/// Each event carries one model's progress and the batch totals; one failing
+/// doesn't stop the rest. Fails up front if the whole batch won't fit on disk.
+#[tauri::command]
+#[specta::specta]
+pub async fn download_models_by_id(
+    window: tauri::Window,
+    model_ids: Vec<String>,
+    max_concurrent: Option<u32>,
+) -> Result<Vec<ModelDownloadResult>, String> {
+    let max_concurrent = max_concurrent
+        .map(|n| n as usize)
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_DOWNLOADS);
+    download_models_parallel(model_ids, max_concurrent, move |batch| {
+        tracing::info!(
+            "Download {}: {}% (batch {}%, {}/{})",
+            batch.model.model_id,
+            batch.model.percent,
+            batch.percent,
+            batch.downloaded_bytes,
+            batch.total_bytes
+        );
+        window.emit("model-download-progress", batch).ok();
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // OLLAMA COMMANDS (for LLMs)
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -145,7 +272,8 @@ pub async fn check_ollama_installed() -> bool {
 #[tauri::command]
 #[specta::specta]
 pub async fn get_ollama_model_list() -> Result<Vec<String>, String> {
-    get_ollama_models().await
+    let models = list_ollama_models().await?;
+    Ok(models.into_iter().map(|model| model.id).collect())
 }
 
 /// Pull a model via Ollama
@@ -9,6 +9,8 @@ pub mod crew;
 pub mod files;
 pub mod installer;
 pub mod settings;
+pub mod sync;
+pub mod telemetry;
 pub mod tokens;
 pub mod workflow;
 
@@ -16,6 +18,7 @@ pub mod workflow;
 use crate::vault::{
     self,
     models::{Character, Project, Script},
+    script_patch::{self, PatchResult, ScriptMatch, ScriptPatch, ScriptRevision},
 };
 use surrealdb::engine::any::Any;
 use surrealdb::Surreal;
@@ -27,6 +30,13 @@ async fn get_db() -> Result<Surreal<Any>, String> {
         .ok_or_else(|| "Vault not initialized".to_string())
 }
 
+// Reads wait briefly for `vault::init`, which may still be running at startup
+async fn read_db() -> Result<Surreal<Any>, String> {
+    vault::wait_for_db(vault::DB_WAIT_TIMEOUT)
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn create_project(title: String, author: String) -> Result<Project, String> {
@@ -50,8 +60,8 @@ pub async fn create_project(title: String, author: String) -> Result<Project, St
 #[tauri::command]
 #[specta::specta]
 pub async fn get_projects() -> Result<Vec<Project>, String> {
-    let db = get_db().await?;
-    let projects: Vec<Project> = db.select("project").await.map_err(|e| e.to_string())?;
+    let db = read_db().await?;
+    let projects: Vec<Project> = vault::or_empty(db.select("project").await)?;
     Ok(projects)
 }
 
@@ -83,7 +93,7 @@ pub async fn save_script(script: Script) -> Result<Script, String> {
 #[tauri::command]
 #[specta::specta]
 pub async fn load_script(project_id: String) -> Result<Option<Script>, String> {
-    let db = get_db().await?;
+    let db = read_db().await?;
 
     let mut result = db
         .query("SELECT * FROM script WHERE project_id = type::thing($pid)")
@@ -91,15 +101,52 @@ pub async fn load_script(project_id: String) -> Result<Option<Script>, String> {
         .await
         .map_err(|e| e.to_string())?;
 
-    let script: Option<Script> = result.take(0).map_err(|e| e.to_string())?;
+    let script: Option<Script> = vault::or_empty(result.take(0))?;
 
     Ok(script)
 }
 
+/// Find the lines of a project's script that contain `query`
+#[tauri::command]
+#[specta::specta]
+pub async fn search_script(project_id: String, query: String) -> Result<Vec<ScriptMatch>, String> {
+    let script = load_script(project_id)
+        .await?
+        .ok_or("This project has no script yet")?;
+    script_patch::search_lines(&script.content, &query)
+}
+
+/// Validate patches against the stored script and return the result without saving
+#[tauri::command]
+#[specta::specta]
+pub async fn preview_script_patch(
+    project_id: String,
+    patches: Vec<ScriptPatch>,
+) -> Result<PatchResult, String> {
+    script_patch::preview_patches(&project_id, &patches).await
+}
+
+/// Apply patches to the stored script (undo with `undo_script_patch`)
+#[tauri::command]
+#[specta::specta]
+pub async fn apply_script_patch(
+    project_id: String,
+    patches: Vec<ScriptPatch>,
+) -> Result<PatchResult, String> {
+    script_patch::apply_patches(&project_id, &patches).await
+}
+
+/// Restore the script as it was before the most recent patch
+#[tauri::command]
+#[specta::specta]
+pub async fn undo_script_patch(project_id: String) -> Result<Option<ScriptRevision>, String> {
+    script_patch::undo_last_patch(&project_id).await
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn get_characters(project_id: String) -> Result<Vec<Character>, String> {
-    let db = get_db().await?;
+    let db = read_db().await?;
 
     let mut result = db
         .query("SELECT * FROM character WHERE project_id = type::thing($pid)")
@@ -107,7 +154,7 @@ pub async fn get_characters(project_id: String) -> Result<Vec<Character>, String
         .await
         .map_err(|e| e.to_string())?;
 
-    let characters: Vec<Character> = result.take(0).map_err(|e| e.to_string())?;
+    let characters: Vec<Character> = vault::or_empty(result.take(0))?;
 
     Ok(characters)
 }
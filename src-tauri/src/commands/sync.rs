@@ -0,0 +1,180 @@
+//! Sync Commands
+//!
+//! Autosave configuration, manual saves, cloud sync, script and canvas edits,
+//! undo and history of the sync document.
+
+use crate::sync::{self, AutosaveSettings, CloudSyncReport, CloudSyncSettings, VersionInfo};
+use crate::vault::Page;
+
+/// Get the current autosave settings
+#[tauri::command]
+#[specta::specta]
+pub fn get_autosave_settings() -> AutosaveSettings {
+    sync::autosave_settings()
+}
+
+/// Set how long edits must be idle before they are saved (0 disables idle saves)
+#[tauri::command]
+#[specta::specta]
+pub fn set_autosave_interval(interval_ms: u32) -> Result<AutosaveSettings, String> {
+    let settings = AutosaveSettings {
+        interval_ms: interval_ms.into(),
+        ..sync::autosave_settings()
+    };
+    sync::set_autosave_settings(settings.clone())?;
+    Ok(settings)
+}
+
+/// Set how often edits are saved while the user keeps typing (0 disables periodic saves)
+#[tauri::command]
+#[specta::specta]
+pub fn set_periodic_save_interval(periodic_ms: u32) -> Result<AutosaveSettings, String> {
+    let settings = AutosaveSettings {
+        periodic_ms: periodic_ms.into(),
+        ..sync::autosave_settings()
+    };
+    sync::set_autosave_settings(settings.clone())?;
+    Ok(settings)
+}
+
+/// Save the sync document now; returns whether there was anything to write
+#[tauri::command]
+#[specta::specta]
+pub async fn force_save() -> Result<bool, String> {
+    sync::force_save().await
+}
+
+/// Get the cloud sync settings
+#[tauri::command]
+#[specta::specta]
+pub fn get_cloud_sync_settings() -> CloudSyncSettings {
+    sync::cloud_sync_settings()
+}
+
+/// Set the endpoint the document syncs with (`None` turns cloud sync off)
+#[tauri::command]
+#[specta::specta]
+pub fn set_cloud_sync_endpoint(endpoint: Option<String>) -> Result<CloudSyncSettings, String> {
+    let settings = CloudSyncSettings { endpoint };
+    sync::set_cloud_sync_settings(settings.clone())?;
+    Ok(settings)
+}
+
+/// Exchange updates with the sync endpoint now
+#[tauri::command]
+#[specta::specta]
+pub async fn sync_with_cloud() -> Result<CloudSyncReport, String> {
+    sync::sync_now().await
+}
+
+/// Replace `delete` characters of the script at `pos` with `insert`, as one
+/// undo step; returns the resulting document snapshot
+#[tauri::command]
+#[specta::specta]
+pub async fn edit_script(pos: u32, delete: u32, insert: String) -> Result<Vec<u8>, String> {
+    let mut guard = sync::SYNC_ENGINE.lock().await;
+    let engine = guard.as_mut().ok_or("Sync engine not initialized")?;
+    engine.edit_script(pos as usize, delete as usize, &insert)?;
+    engine.snapshot()
+}
+
+/// Add or replace a canvas node (JSON), as one undo step; returns the
+/// resulting document snapshot
+#[tauri::command]
+#[specta::specta]
+pub async fn set_canvas_node(node_id: String, node_json: String) -> Result<Vec<u8>, String> {
+    let mut guard = sync::SYNC_ENGINE.lock().await;
+    let engine = guard.as_mut().ok_or("Sync engine not initialized")?;
+    engine.set_canvas_node(&node_id, &node_json)?;
+    engine.snapshot()
+}
+
+/// Remove a canvas node, as one undo step; returns the resulting document snapshot
+#[tauri::command]
+#[specta::specta]
+pub async fn remove_canvas_node(node_id: String) -> Result<Vec<u8>, String> {
+    let mut guard = sync::SYNC_ENGINE.lock().await;
+    let engine = guard.as_mut().ok_or("Sync engine not initialized")?;
+    engine.remove_canvas_node(&node_id)?;
+    engine.snapshot()
+}
+
+/// Undo the last script or canvas edit; returns the resulting document snapshot
+#[tauri::command]
+#[specta::specta]
+pub async fn script_undo() -> Result<Vec<u8>, String> {
+    let mut guard = sync::SYNC_ENGINE.lock().await;
+    let engine = guard.as_mut().ok_or("Sync engine not initialized")?;
+    engine.undo()?;
+    engine.snapshot()
+}
+
+/// Redo the last undone script or canvas edit; returns the resulting document snapshot
+#[tauri::command]
+#[specta::specta]
+pub async fn script_redo() -> Result<Vec<u8>, String> {
+    let mut guard = sync::SYNC_ENGINE.lock().await;
+    let engine = guard.as_mut().ok_or("Sync engine not initialized")?;
+    engine.redo()?;
+    engine.snapshot()
+}
+
+/// One page of the document's past states, newest first
+#[tauri::command]
+#[specta::specta]
+pub async fn list_document_versions(
+    cursor: Option<String>,
+    limit: Option<u32>,
+) -> Result<Page<VersionInfo>, String> {
+    let guard = sync::SYNC_ENGINE.lock().await;
+    let engine = guard.as_ref().ok_or("Sync engine not initialized")?;
+    engine.list_versions(cursor.as_deref(), limit)
+}
+
+/// Named and daily snapshots, newest first
+#[tauri::command]
+#[specta::specta]
+pub async fn list_document_snapshots() -> Result<Vec<VersionInfo>, String> {
+    let guard = sync::SYNC_ENGINE.lock().await;
+    let engine = guard.as_ref().ok_or("Sync engine not initialized")?;
+    Ok(engine.list_snapshots())
+}
+
+/// Keep the current state as a named snapshot
+#[tauri::command]
+#[specta::specta]
+pub async fn save_document_snapshot(name: String) -> Result<VersionInfo, String> {
+    let guard = sync::SYNC_ENGINE.lock().await;
+    let engine = guard.as_ref().ok_or("Sync engine not initialized")?;
+    engine.save_named_snapshot(&name)
+}
+
+/// The script at `version`, for previewing before a restore
+#[tauri::command]
+#[specta::specta]
+pub async fn preview_document_version(version: String) -> Result<String, String> {
+    let mut guard = sync::SYNC_ENGINE.lock().await;
+    let engine = guard.as_mut().ok_or("Sync engine not initialized")?;
+    engine.checkout(&version)
+}
+
+/// Bring the script back to `version` (undoable); returns the restored script
+#[tauri::command]
+#[specta::specta]
+pub async fn restore_document_version(version: String) -> Result<String, String> {
+    let mut guard = sync::SYNC_ENGINE.lock().await;
+    let engine = guard.as_mut().ok_or("Sync engine not initialized")?;
+    engine.restore(&version)
+}
+
+/// Snapshot of a branch starting at `version`, independent of the document
+#[tauri::command]
+#[specta::specta]
+pub async fn fork_document_at(version: String) -> Result<Vec<u8>, String> {
+    let guard = sync::SYNC_ENGINE.lock().await;
+    let engine = guard.as_ref().ok_or("Sync engine not initialized")?;
+    engine
+        .fork_at(&version)?
+        .export(loro::ExportMode::Snapshot)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,60 @@
+//! Telemetry Commands
+//!
+//! Opt-in control, local generation analytics and the request cost ledger.
+
+use crate::request_log::{self, CostPeriod, CostSummary, RequestLogEntry, RequestLogFilter};
+use crate::telemetry::{self, GenerationEvent, ModelGenerationStats, TelemetrySettings};
+
+/// Get the current telemetry opt-in settings
+#[tauri::command]
+#[specta::specta]
+pub fn get_telemetry_settings() -> TelemetrySettings {
+    telemetry::get_settings()
+}
+
+/// Opt in or out of local generation analytics
+#[tauri::command]
+#[specta::specta]
+pub fn set_telemetry_enabled(enabled: bool) -> Result<TelemetrySettings, String> {
+    telemetry::set_local_enabled(enabled)
+}
+
+/// Record a generation outcome reported by the frontend (e.g. a regeneration)
+#[tauri::command]
+#[specta::specta]
+pub async fn record_generation_outcome(
+    model_id: String,
+    success: bool,
+    duration_ms: u32,
+    retry: bool,
+) -> Result<(), String> {
+    telemetry::record_generation(GenerationEvent::new(
+        model_id,
+        success,
+        duration_ms.into(),
+        retry,
+    ))
+    .await;
+    Ok(())
+}
+
+/// Summarize recorded success rates per model
+#[tauri::command]
+#[specta::specta]
+pub async fn get_generation_stats() -> Result<Vec<ModelGenerationStats>, String> {
+    telemetry::get_generation_stats().await
+}
+
+/// Logged provider requests matching `filter`, newest first
+#[tauri::command]
+#[specta::specta]
+pub async fn get_request_log(filter: RequestLogFilter) -> Result<Vec<RequestLogEntry>, String> {
+    request_log::get_request_log(filter).await
+}
+
+/// Credits spent over `period`, by provider and by day
+#[tauri::command]
+#[specta::specta]
+pub async fn get_cost_summary(period: CostPeriod) -> Result<CostSummary, String> {
+    request_log::get_cost_summary(period).await
+}
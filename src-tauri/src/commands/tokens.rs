@@ -1,13 +1,21 @@
 //! Token Commands — CRUD and AI Extraction for Vault Tokens
 //!
 //! Commands:
-//! - create_token, get_tokens, update_token, delete_token
-//! - extract_tokens_from_script (AI-powered)
+//! - create_token, get_tokens, list_tokens, search_tokens, update_token, delete_token
+//! - extract_tokens_from_script (LLM, or the scene parser offline)
+//! - link_tokens, get_related_tokens, get_token_graph (relations between tokens)
 //! - get_token_context (for prompt enhancement)
+//! - export_training_dataset, submit_lora_training (Fal LoRA trainer)
 
+use crate::ai::token_extractor;
 use crate::vault::{
     self,
-    tokens::{ExtractedTokens, Token, TokenContext, TokenType},
+    tokens::{
+        AliasMerge, ExtractedTokens, Token, TokenContext, TokenEdge, TokenGraph, TokenRelation,
+        TokenType,
+    },
+    training::{self, TrainingDataset},
+    Page,
 };
 use surrealdb::engine::any::Any;
 use surrealdb::Surreal;
@@ -19,6 +27,13 @@ async fn get_db() -> Result<Surreal<Any>, String> {
         .ok_or_else(|| "Vault not initialized".to_string())
 }
 
+// Reads wait briefly for `vault::init`, which may still be running at startup
+async fn read_db() -> Result<Surreal<Any>, String> {
+    vault::wait_for_db(vault::DB_WAIT_TIMEOUT)
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())
+}
+
 /// Create a new token in the Vault
 #[tauri::command]
 #[specta::specta]
@@ -31,30 +46,59 @@ pub async fn create_token(
     let db = get_db().await?;
 
     let token = Token::new(project_id, token_type, name, description);
-
-    let created: Option<Token> = db
-        .create("token")
-        .content(token)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    created.ok_or_else(|| "Failed to create token".to_string())
+    vault::tokens::insert_token(&db, token).await
 }
 
 /// Get all tokens for a project
 #[tauri::command]
 #[specta::specta]
 pub async fn get_tokens(project_id: String) -> Result<Vec<Token>, String> {
-    let db = get_db().await?;
+    let db = read_db().await?;
+    vault::tokens::project_tokens(&db, &project_id).await
+}
+
+/// Get one page of a project's tokens, optionally of a single type
+#[tauri::command]
+#[specta::specta]
+pub async fn list_tokens(
+    project_id: String,
+    cursor: Option<String>,
+    limit: Option<u32>,
+    token_type: Option<TokenType>,
+) -> Result<Page<Token>, String> {
+    let db = read_db().await?;
+    let (start, limit) = vault::page_window(cursor.as_deref(), limit)?;
+
+    // LIMIT/START are validated integers; everything else is bound
+    let query = format!(
+        "SELECT * FROM token WHERE project_id = $pid \
+         AND ($ttype = NONE OR token_type = $ttype) \
+         ORDER BY token_type, name, id LIMIT {} START {}",
+        limit + 1,
+        start
+    );
 
     let mut result = db
-        .query("SELECT * FROM token WHERE project_id = $pid ORDER BY token_type, name")
+        .query(query)
         .bind(("pid", project_id))
+        .bind(("ttype", token_type.map(|t| format!("{:?}", t))))
         .await
         .map_err(|e| e.to_string())?;
 
-    let tokens: Vec<Token> = result.take(0).map_err(|e| e.to_string())?;
-    Ok(tokens)
+    let tokens: Vec<Token> = vault::or_empty(result.take(0))?;
+    Ok(vault::into_page(tokens, start, limit))
+}
+
+/// Search a project's tokens by name and description, name matches first
+#[tauri::command]
+#[specta::specta]
+pub async fn search_tokens(
+    project_id: String,
+    query: String,
+    token_type: Option<TokenType>,
+) -> Result<Vec<Token>, String> {
+    let db = read_db().await?;
+    vault::tokens::search_tokens(&db, &project_id, &query, token_type).await
 }
 
 /// Get tokens by type
@@ -64,7 +108,7 @@ pub async fn get_tokens_by_type(
     project_id: String,
     token_type: TokenType,
 ) -> Result<Vec<Token>, String> {
-    let db = get_db().await?;
+    let db = read_db().await?;
 
     let type_str = format!("{:?}", token_type);
 
@@ -75,7 +119,7 @@ pub async fn get_tokens_by_type(
         .await
         .map_err(|e| e.to_string())?;
 
-    let tokens: Vec<Token> = result.take(0).map_err(|e| e.to_string())?;
+    let tokens: Vec<Token> = vault::or_empty(result.take(0))?;
     Ok(tokens)
 }
 
@@ -115,6 +159,37 @@ pub async fn delete_token(token_id: String) -> Result<(), String> {
     Ok(())
 }
 
+/// Link two tokens, e.g. a character that appears in a location
+#[tauri::command]
+#[specta::specta]
+pub async fn link_tokens(
+    from_id: String,
+    relation: TokenRelation,
+    to_id: String,
+) -> Result<TokenEdge, String> {
+    let db = get_db().await?;
+    vault::tokens::link_tokens(&db, &from_id, relation, &to_id).await
+}
+
+/// Tokens linked to a token, optionally by one relation only
+#[tauri::command]
+#[specta::specta]
+pub async fn get_related_tokens(
+    token_id: String,
+    relation: Option<TokenRelation>,
+) -> Result<Vec<Token>, String> {
+    let db = read_db().await?;
+    vault::tokens::related_tokens(&db, &token_id, relation).await
+}
+
+/// A project's tokens and their links, for the graph view
+#[tauri::command]
+#[specta::specta]
+pub async fn get_token_graph(project_id: String) -> Result<TokenGraph, String> {
+    let db = read_db().await?;
+    vault::tokens::token_graph(&db, &project_id).await
+}
+
 /// Add a visual reference to a token
 #[tauri::command]
 #[specta::specta]
@@ -151,11 +226,41 @@ pub async fn set_token_lora(token_id: String, lora_id: String) -> Result<Token,
     updated.ok_or_else(|| "Failed to set LoRA ID".to_string())
 }
 
+async fn get_token(token_id: String) -> Result<Token, String> {
+    let db = read_db().await?;
+
+    let mut result = db
+        .query("SELECT * FROM $id")
+        .bind(("id", token_id.clone()))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let token: Option<Token> = result.take(0).map_err(|e| e.to_string())?;
+    token.ok_or_else(|| format!("Token not found: {}", token_id))
+}
+
+/// Zip a token's visual references with captions for LoRA training
+#[tauri::command]
+#[specta::specta]
+pub async fn export_training_dataset(token_id: String) -> Result<TrainingDataset, String> {
+    let token = get_token(token_id).await?;
+    training::export_training_dataset(&token).await
+}
+
+/// Train a LoRA on Fal from the token's references and store it on the token
+#[tauri::command]
+#[specta::specta]
+pub async fn submit_lora_training(token_id: String) -> Result<Token, String> {
+    let dataset = export_training_dataset(token_id.clone()).await?;
+    let lora_id = training::train_lora(&dataset).await?;
+    set_token_lora(token_id, lora_id).await
+}
+
 /// Get token context for prompt enhancement in Studio
 #[tauri::command]
 #[specta::specta]
 pub async fn get_token_contexts(token_ids: Vec<String>) -> Result<Vec<TokenContext>, String> {
-    let db = get_db().await?;
+    let db = read_db().await?;
 
     let mut contexts = Vec::new();
 
@@ -174,157 +279,151 @@ pub async fn get_token_contexts(token_ids: Vec<String>) -> Result<Vec<TokenConte
     Ok(contexts)
 }
 
-/// Extract tokens from script using AI (placeholder - needs ComfyUI integration)
+/// Extract tokens from script
+///
+/// With `use_ai`, an LLM reads the script; without an API key, or when its
+/// reply can't be used, the offline scene parser runs instead. Likely
+/// character aliases are listed in `proposed_merges` but not applied; pass
+/// the ones the user accepts to `save_extracted_tokens`.
 #[tauri::command]
 #[specta::specta]
 pub async fn extract_tokens_from_script(
     _project_id: String,
     script_content: String,
+    use_ai: bool,
 ) -> Result<ExtractedTokens, String> {
-    // Improved extraction: NER-style regex-based
-    // Fast path using regex patterns (future: integrate Gemini/Claude for deep analysis)
+    let mut tokens = None;
+    if use_ai && token_extractor::llm_available() {
+        match token_extractor::extract_tokens_with_llm(&script_content).await {
+            Ok(extracted) => tokens = Some(extracted),
+            Err(e) => tracing::warn!("LLM token extraction failed, using the parser: {}", e),
+        }
+    }
 
+    let mut tokens = tokens.unwrap_or_else(|| extract_tokens_offline(&script_content));
+    tokens.proposed_merges = tokens.propose_alias_merges();
+    Ok(tokens)
+}
+
+/// Fast path over the parsed scenes: speaking characters, heading locations
+/// and props from a keyword list
+fn extract_tokens_offline(script_content: &str) -> ExtractedTokens {
+    use crate::scenes::{elements_from_text, parse_scenes};
     use crate::vault::tokens::ExtractedEntity;
     use std::collections::HashMap;
 
+    // Common prop indicators in action lines
+    let prop_keywords = [
+        "gun",
+        "weapon",
+        "phone",
+        "car",
+        "door",
+        "key",
+        "letter",
+        "photo",
+        "photograph",
+        "ring",
+        "watch",
+        "briefcase",
+        "suitcase",
+        "laptop",
+        "computer",
+        "bottle",
+        "glass",
+        "knife",
+        "sword",
+        "camera",
+        "book",
+    ];
+
     let mut characters: HashMap<String, ExtractedEntity> = HashMap::new();
     let mut locations: HashMap<String, ExtractedEntity> = HashMap::new();
     let mut props: HashMap<String, ExtractedEntity> = HashMap::new();
 
-    let lines: Vec<&str> = script_content.lines().collect();
-
-    for (i, line) in lines.iter().enumerate() {
-        let trimmed = line.trim();
-
-        // Character detection (ALL CAPS name, 2-30 chars, followed by dialogue or parenthetical)
-        if trimmed
-            .chars()
-            .all(|c| c.is_uppercase() || c.is_whitespace() || c == '.')
-            && trimmed.len() > 1
-            && trimmed.len() < 35
-            && !trimmed.starts_with("INT")
-            && !trimmed.starts_with("EXT")
-            && !trimmed.starts_with("FADE")
-            && !trimmed.ends_with(':')
-        {
-            let name = trimmed.replace('.', "").trim().to_string();
-            if !name.is_empty() && name.split_whitespace().count() <= 3 {
-                characters
-                    .entry(name.clone())
-                    .and_modify(|e| e.mentions += 1)
-                    .or_insert(ExtractedEntity {
-                        name: name.clone(),
-                        description: "Character appearing in the script".to_string(),
-                        mentions: 1,
-                        first_appearance: format!("Line {}", i + 1),
-                    });
-            }
+    let mention = |entities: &mut HashMap<String, ExtractedEntity>,
+                   name: String,
+                   description: &str,
+                   scene: &str| {
+        entities
+            .entry(name.clone())
+            .and_modify(|e| e.mentions += 1)
+            .or_insert(ExtractedEntity {
+                name,
+                description: description.to_string(),
+                mentions: 1,
+                first_appearance: scene.to_string(),
+                aliases: Vec::new(),
+            });
+    };
+
+    for scene in parse_scenes(&elements_from_text(script_content)) {
+        if !scene.location.is_empty() {
+            mention(
+                &mut locations,
+                scene.location.clone(),
+                "Location in the script",
+                &scene.heading,
+            );
         }
 
-        // Location detection (INT. or EXT.)
-        if trimmed.starts_with("INT.") || trimmed.starts_with("EXT.") {
-            let location = trimmed
-                .replace("INT.", "")
-                .replace("EXT.", "")
-                .split('-')
-                .next()
-                .unwrap_or("")
-                .split('.')
-                .next()
-                .unwrap_or("")
-                .trim()
-                .to_string();
-
-            if !location.is_empty() {
-                locations
-                    .entry(location.clone())
-                    .and_modify(|e| e.mentions += 1)
-                    .or_insert(ExtractedEntity {
-                        name: location.clone(),
-                        description: "Location in the script".to_string(),
-                        mentions: 1,
-                        first_appearance: trimmed.to_string(),
-                    });
-            }
+        // One mention per dialogue block
+        for block in &scene.dialogue_blocks {
+            mention(
+                &mut characters,
+                block.character.to_uppercase(),
+                "Character appearing in the script",
+                &scene.heading,
+            );
         }
 
-        // Prop detection (simple heuristic: objects in action lines)
-        // Look for common prop keywords in action lines
-        if !trimmed.is_empty()
-            && !trimmed
-                .chars()
-                .all(|c| c.is_uppercase() || c.is_whitespace())
-            && !trimmed.starts_with("INT")
-            && !trimmed.starts_with("EXT")
-            && !trimmed.starts_with('(')
-        {
-            // Common prop indicators
-            let prop_keywords = [
-                "gun",
-                "weapon",
-                "phone",
-                "car",
-                "door",
-                "key",
-                "letter",
-                "photo",
-                "photograph",
-                "ring",
-                "watch",
-                "briefcase",
-                "suitcase",
-                "laptop",
-                "computer",
-                "bottle",
-                "glass",
-                "knife",
-                "sword",
-                "camera",
-                "book",
-            ];
-
+        for line in &scene.action_lines {
+            let line = line.to_lowercase();
             for keyword in &prop_keywords {
-                if trimmed.to_lowercase().contains(keyword) {
-                    let prop_name = keyword.to_uppercase();
-                    props
-                        .entry(prop_name.clone())
-                        .and_modify(|e| e.mentions += 1)
-                        .or_insert(ExtractedEntity {
-                            name: prop_name.clone(),
-                            description: "Prop mentioned in action".to_string(),
-                            mentions: 1,
-                            first_appearance: format!("Line {}", i + 1),
-                        });
+                if line.contains(keyword) {
+                    mention(
+                        &mut props,
+                        keyword.to_uppercase(),
+                        "Prop mentioned in action",
+                        &scene.heading,
+                    );
                 }
             }
         }
     }
 
-    Ok(ExtractedTokens {
+    ExtractedTokens {
         characters: characters.into_values().collect(),
         locations: locations.into_values().collect(),
         props: props.into_values().collect(),
-    })
+        proposed_merges: Vec::new(),
+    }
 }
 
 /// Save extracted tokens to Vault (user confirms first)
+///
+/// `accepted_merges` are the `proposed_merges` the user agreed to; each folds
+/// its aliases into one character token.
 #[tauri::command]
 #[specta::specta]
 pub async fn save_extracted_tokens(
     project_id: String,
-    extracted: ExtractedTokens,
+    mut extracted: ExtractedTokens,
+    accepted_merges: Vec<AliasMerge>,
 ) -> Result<Vec<Token>, String> {
+    extracted.apply_merges(&accepted_merges);
     let db = get_db().await?;
     let mut saved_tokens = Vec::new();
 
     // Save characters
     for entity in extracted.characters {
-        let token = Token::new(
+        let mut token = Token::new(
             project_id.clone(),
             TokenType::Character,
             entity.name,
             entity.description,
         );
+        token.aliases = entity.aliases;
 
         if let Ok(Some(created)) = db.create::<Option<Token>>("token").content(token).await {
             saved_tokens.push(created);
@@ -361,3 +460,51 @@ pub async fn save_extracted_tokens(
 
     Ok(saved_tokens)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::tokens::ExtractedEntity;
+
+    const SCRIPT: &str = "\
+INT./EXT. MARLOWE'S CAR - NIGHT
+
+Marlowe drives one-handed. A gun rides on the seat.
+
+MARLOWE
+Where to?
+
+VERA (O.S.)
+The docks.
+
+EXT. DOCKS - CONTINUOUS
+
+MARLOWE (CONT'D)
+Here.
+";
+
+    #[test]
+    fn test_offline_extraction_reads_parsed_scenes() {
+        let tokens = extract_tokens_offline(SCRIPT);
+        let find = |entities: &[ExtractedEntity], name: &str| {
+            entities
+                .iter()
+                .find(|e| e.name == name)
+                .cloned()
+                .unwrap_or_else(|| panic!("{} not extracted", name))
+        };
+
+        // Cue extensions and CONT'D don't make new characters
+        assert_eq!(tokens.characters.len(), 2);
+        assert_eq!(find(&tokens.characters, "MARLOWE").mentions, 2);
+        assert_eq!(find(&tokens.characters, "VERA").mentions, 1);
+
+        // INT./EXT. and the time of day are stripped from locations
+        let car = find(&tokens.locations, "MARLOWE'S CAR");
+        assert_eq!(car.first_appearance, "INT./EXT. MARLOWE'S CAR - NIGHT");
+        assert_eq!(find(&tokens.locations, "DOCKS").mentions, 1);
+
+        assert_eq!(tokens.props.len(), 1);
+        assert_eq!(find(&tokens.props, "GUN").mentions, 1);
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! Exposes workflow generation to frontend
 
+use crate::ai::model_params::ModelParams;
 use crate::ai::{
     generate_workflow, parse_agent_request, GeneratedWorkflow, WorkflowRequest, WorkflowType,
 };
@@ -47,3 +48,10 @@ pub fn generate_comfyui_workflow(
 pub fn generate_workflow_from_agent(agent_output: String) -> Option<GeneratedWorkflow> {
     parse_agent_request(&agent_output).map(|req| generate_workflow(&req))
 }
+
+/// Default model-specific params for a workflow node type (e.g. "FalVeo31")
+#[tauri::command]
+#[specta::specta]
+pub fn get_model_params_defaults(node_type: String) -> Option<ModelParams> {
+    ModelParams::for_node_type(&node_type)
+}
@@ -4,6 +4,8 @@
 
 pub mod codes;
 
+use serde::{Deserialize, Serialize};
+use specta::Type;
 use thiserror::Error;
 
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -27,6 +29,9 @@ pub enum LLMError {
     #[error("Network error: {0}")]
     NetworkError(#[from] reqwest::Error),
 
+    #[error("Invalid request to {provider}: {message}")]
+    InvalidRequest { provider: String, message: String },
+
     #[error("Invalid response from {provider}: {message}")]
     InvalidResponse { provider: String, message: String },
 
@@ -46,10 +51,14 @@ pub enum LLMError {
 
 impl LLMError {
     pub fn is_retryable(&self) -> bool {
-        matches!(
-            self,
-            LLMError::RateLimited { .. } | LLMError::Timeout { .. } | LLMError::NetworkError(_)
-        )
+        match self {
+            LLMError::RateLimited { .. } | LLMError::Timeout { .. } | LLMError::NetworkError(_) => {
+                true
+            }
+            // Overloaded or failing upstream; client errors won't fix themselves
+            LLMError::ProviderError { status_code, .. } => *status_code >= 500,
+            _ => false,
+        }
     }
 
     pub fn retry_delay(&self) -> Option<u64> {
@@ -59,6 +68,7 @@ impl LLMError {
             } => Some(*retry_after_secs),
             LLMError::Timeout { .. } => Some(5),
             LLMError::NetworkError(_) => Some(2),
+            LLMError::ProviderError { status_code, .. } if *status_code >= 500 => Some(2),
             _ => None,
         }
     }
@@ -85,6 +95,12 @@ pub enum InstallerError {
     #[error("Failed to install dependencies: {message}")]
     DependencyInstallFailed { message: String },
 
+    #[error("ComfyUI has local modifications: {}", .files.join(", "))]
+    ComfyUILocalChanges { path: String, files: Vec<String> },
+
+    #[error("Failed to update ComfyUI: {message}")]
+    ComfyUIUpdateFailed { message: String },
+
     #[error("ComfyUI failed to start: {message}")]
     ComfyUIStartFailed { message: String },
 
@@ -101,6 +117,133 @@ pub enum InstallerError {
     CommandFailed { command: String, stderr: String },
 }
 
+impl InstallerError {
+    /// Variant name, stable for the UI to switch on
+    pub fn kind(&self) -> &'static str {
+        match self {
+            InstallerError::GitNotInstalled => "GitNotInstalled",
+            InstallerError::UVInstallFailed { .. } => "UVInstallFailed",
+            InstallerError::PythonInstallFailed { .. } => "PythonInstallFailed",
+            InstallerError::ComfyUICloneFailed { .. } => "ComfyUICloneFailed",
+            InstallerError::DependencyInstallFailed { .. } => "DependencyInstallFailed",
+            InstallerError::ComfyUILocalChanges { .. } => "ComfyUILocalChanges",
+            InstallerError::ComfyUIUpdateFailed { .. } => "ComfyUIUpdateFailed",
+            InstallerError::ComfyUIStartFailed { .. } => "ComfyUIStartFailed",
+            InstallerError::DirectoryNotWritable { .. } => "DirectoryNotWritable",
+            InstallerError::InsufficientDiskSpace { .. } => "InsufficientDiskSpace",
+            InstallerError::IoError(_) => "IoError",
+            InstallerError::CommandFailed { .. } => "CommandFailed",
+        }
+    }
+
+    /// What the user can do about it
+    pub fn remediation(&self) -> String {
+        if self.is_network_failure() {
+            return "Check your internet connection and try again. Large downloads \
+                    (PyTorch is several GB) can time out on slow connections."
+                .into();
+        }
+
+        match self {
+            InstallerError::GitNotInstalled => {
+                "Install Git from git-scm.com, then restart CinemaOS.".into()
+            }
+            InstallerError::UVInstallFailed { .. } => {
+                "Install uv manually from docs.astral.sh/uv, then try again.".into()
+            }
+            InstallerError::PythonInstallFailed { .. } => {
+                "Run `uv python install 3.11` in a terminal to see the full error, then try again."
+                    .into()
+            }
+            InstallerError::ComfyUICloneFailed { .. } => {
+                "Make sure github.com is reachable, then try again.".into()
+            }
+            InstallerError::DependencyInstallFailed { .. } => {
+                "Check your internet connection and free disk space, then try again.".into()
+            }
+            InstallerError::ComfyUILocalChanges { path, .. } => format!(
+                "Stash or discard the changes with `git -C {} stash`, then update again.",
+                path
+            ),
+            InstallerError::ComfyUIUpdateFailed { .. } => {
+                "Make sure github.com is reachable. If the checkout has diverged, reinstall ComfyUI."
+                    .into()
+            }
+            InstallerError::ComfyUIStartFailed { .. } => {
+                "Check that port 8188 is free and no other ComfyUI is running.".into()
+            }
+            InstallerError::DirectoryNotWritable { path } => {
+                format!("Make sure you have write permission to {}.", path)
+            }
+            InstallerError::InsufficientDiskSpace { needed_gb, .. } => {
+                format!(
+                    "Free up at least {}GB of disk space, then try again.",
+                    needed_gb
+                )
+            }
+            InstallerError::IoError(_) => {
+                "Check free disk space and permissions for the CinemaOS folder.".into()
+            }
+            InstallerError::CommandFailed { command, .. } => {
+                format!("Run `{}` in a terminal to see the full error.", command)
+            }
+        }
+    }
+
+    /// Whether the underlying failure was a timeout or unreachable host
+    fn is_network_failure(&self) -> bool {
+        let detail = match self {
+            InstallerError::UVInstallFailed { message }
+            | InstallerError::PythonInstallFailed { message }
+            | InstallerError::ComfyUICloneFailed { message }
+            | InstallerError::DependencyInstallFailed { message }
+            | InstallerError::ComfyUIUpdateFailed { message } => message,
+            InstallerError::CommandFailed { stderr, .. } => stderr,
+            _ => return false,
+        }
+        .to_lowercase();
+
+        [
+            "timed out",
+            "timeout",
+            "could not resolve",
+            "failed to resolve",
+            "dns",
+            "connection refused",
+            "connection reset",
+            "network is unreachable",
+            "error sending request",
+        ]
+        .iter()
+        .any(|needle| detail.contains(needle))
+    }
+}
+
+/// Install failure as sent to the UI
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct InstallFailure {
+    /// `InstallerError` variant name
+    pub kind: String,
+    pub message: String,
+    pub remediation: String,
+}
+
+impl From<&InstallerError> for InstallFailure {
+    fn from(err: &InstallerError) -> Self {
+        Self {
+            kind: err.kind().to_string(),
+            message: err.to_string(),
+            remediation: err.remediation(),
+        }
+    }
+}
+
+impl From<InstallerError> for InstallFailure {
+    fn from(err: InstallerError) -> Self {
+        Self::from(&err)
+    }
+}
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // COMFYUI ERRORS
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -289,6 +432,12 @@ impl From<ComfyUIError> for String {
     }
 }
 
+impl From<DownloadError> for String {
+    fn from(err: DownloadError) -> String {
+        err.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -308,6 +457,14 @@ mod tests {
         };
         assert!(!auth_error.is_retryable());
         assert_eq!(auth_error.retry_delay(), None);
+
+        let provider_error = |status_code| LLMError::ProviderError {
+            provider: "gemini".into(),
+            status_code,
+            message: String::new(),
+        };
+        assert!(provider_error(503).is_retryable());
+        assert!(!provider_error(400).is_retryable());
     }
 
     #[test]
@@ -126,6 +126,7 @@ pub struct UIError {
     pub retryable: bool,
 
     /// Suggested retry delay in seconds
+    #[specta(type = Option<f64>)]
     pub retry_after_secs: Option<u64>,
 
     /// Timestamp
@@ -3,11 +3,27 @@
 //! Downloads AI models with progress tracking
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use specta::Type;
-use std::path::PathBuf;
-use tokio::io::AsyncWriteExt;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Semaphore;
 
+use crate::errors::DownloadError;
 use crate::installer::get_models_dir;
+use crate::installer::preflight::free_disk_bytes;
+
+/// Suffix for downloads in progress; renamed to the final name once verified
+const PART_SUFFIX: &str = ".part";
+
+/// Room left free on top of a download, for the `.part` file's filesystem
+/// overhead and whatever else writes to the volume meanwhile
+const DISK_SAFETY_MARGIN_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Parallel downloads at once by default; more tends to get throttled by HuggingFace
+pub const DEFAULT_MAX_CONCURRENT_DOWNLOADS: usize = 2;
 
 // ═══════════════════════════════════════════════════════════════════════════════
 // DOWNLOAD STATUS
@@ -17,6 +33,8 @@ use crate::installer::get_models_dir;
 pub enum DownloadStatus {
     NotStarted,
     Downloading,
+    /// Hashing the file against `checksum_sha256`
+    Verifying,
     Extracting,
     Completed,
     Failed(String),
@@ -26,11 +44,34 @@ pub enum DownloadStatus {
 pub struct DownloadProgress {
     pub model_id: String,
     pub status: DownloadStatus,
+    #[specta(type = f64)]
+    pub downloaded_bytes: u64,
+    #[specta(type = f64)]
+    pub total_bytes: u64,
+    pub percent: f32,
+}
+
+/// Progress of one model in a parallel batch, with totals for the whole batch
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct BatchDownloadProgress {
+    /// The update this event is about, tagged by `model_id`
+    pub model: DownloadProgress,
+    /// Bytes downloaded across every model in the batch
+    #[specta(type = f64)]
     pub downloaded_bytes: u64,
+    #[specta(type = f64)]
     pub total_bytes: u64,
     pub percent: f32,
 }
 
+/// How one model of a parallel batch ended
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ModelDownloadResult {
+    pub model_id: String,
+    pub path: Option<String>,
+    pub error: Option<String>,
+}
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // MODEL SOURCES
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -48,6 +89,7 @@ pub struct ModelSource {
     pub name: String,
     pub download_url: String,
     pub filename: String,
+    #[specta(type = f64)]
     pub size_bytes: u64,
     pub checksum_sha256: Option<String>,
     pub requires_auth: bool,
@@ -71,7 +113,7 @@ fn get_hardcoded_sources() -> Vec<ModelSource> {
             download_url: "https://huggingface.co/stabilityai/stable-diffusion-xl-base-1.0/resolve/main/sd_xl_base_1.0.safetensors".into(),
             filename: "sd_xl_base_1.0.safetensors".into(),
             size_bytes: 6_938_040_682,
-            checksum_sha256: None,
+            checksum_sha256: Some("31e35c80fc4829d14f90153f4c74cd59c90b779f6afe05a74cd6120b893f7e5b".into()),
             requires_auth: false,
         },
         ModelSource {
@@ -79,8 +121,8 @@ fn get_hardcoded_sources() -> Vec<ModelSource> {
             name: "FLUX.1 Schnell".into(),
             download_url: "https://huggingface.co/black-forest-labs/FLUX.1-schnell/resolve/main/flux1-schnell.safetensors".into(),
             filename: "flux1-schnell.safetensors".into(),
-            size_bytes: 23_800_000_000,
-            checksum_sha256: None,
+            size_bytes: 23_782_506_688,
+            checksum_sha256: Some("9403429e0052277ac2a87ad800adece5481eecefd9ed334e1f348723621d2a0a".into()),
             requires_auth: false,
         },
         // ── Wan 2.1 (Alibaba) ──
@@ -122,6 +164,25 @@ fn get_hardcoded_sources() -> Vec<ModelSource> {
             checksum_sha256: None,
             requires_auth: false,
         },
+        // ── Whisper (whisper.cpp ggml) ──
+        ModelSource {
+            id: "whisper-base".into(),
+            name: "Whisper Base".into(),
+            download_url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.bin".into(),
+            filename: "ggml-base.bin".into(),
+            size_bytes: 147_951_465,
+            checksum_sha256: None,
+            requires_auth: false,
+        },
+        ModelSource {
+            id: "whisper-large-v3-turbo".into(),
+            name: "Whisper Large v3 Turbo".into(),
+            download_url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo.bin".into(),
+            filename: "ggml-large-v3-turbo.bin".into(),
+            size_bytes: 1_624_555_275,
+            checksum_sha256: None,
+            requires_auth: false,
+        },
         // ── Llama 4 (Meta) - REQUIRES AUTH ──
         ModelSource {
             id: "llama-4-70b-quant".into(),
@@ -245,19 +306,15 @@ pub async fn download_model(
                 });
                 return Ok(dest_path);
             } else {
-                // File exists but wrong size, likely corrupt or partial. Proceed to re-download.
-                // Ideally logging here would be good.
+                // Wrong size (a partial written in place by older versions);
+                // replaced once the new download is verified
+                tracing::warn!("{} has the wrong size, re-downloading", dest_path.display());
             }
         }
     }
 
-    progress_callback(DownloadProgress {
-        model_id: model_id.to_string(),
-        status: DownloadStatus::Downloading,
-        downloaded_bytes: 0,
-        total_bytes: source.size_bytes,
-        percent: 0.0,
-    });
+    // Fail now rather than at byte 38GB
+    ensure_disk_space(source, &dest_path).await?;
 
     // Download with progress
     let client = reqwest::Client::new();
@@ -277,39 +334,98 @@ pub async fn download_model(
         }
     }
 
+    // Resume a previous partial download when there is one worth keeping
+    let part_path = get_partial_path(&dest_path);
+    let existing = std::fs::metadata(&part_path).map(|m| m.len()).unwrap_or(0);
+    let resume_from = if keep_partial(existing, source.size_bytes) {
+        existing
+    } else {
+        0
+    };
+    if resume_from > 0 {
+        tracing::info!("Resuming {} from {} bytes", model_id, resume_from);
+        request = request.header("Range", format!("bytes={}-", resume_from));
+    }
+
     let response = request
         .send()
         .await
         .map_err(|e| format!("Download failed: {}", e))?;
 
-    let total_size = response.content_length().unwrap_or(source.size_bytes);
-
-    let mut file = tokio::fs::File::create(&dest_path)
-        .await
-        .map_err(|e| format!("Failed to create file: {}", e))?;
+    if !response.status().is_success() {
+        // A rejected range means the partial can't be resumed; start clean next time
+        if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            let _ = std::fs::remove_file(&part_path);
+        }
+        return Err(format!(
+            "Download failed with status: {}",
+            response.status()
+        ));
+    }
 
-    let mut downloaded: u64 = 0;
-    let mut stream = response.bytes_stream();
+    // Start the bar where the partial left off rather than at 0
+    let start = start_progress(
+        model_id,
+        response.status(),
+        response.content_length(),
+        resume_from,
+        source.size_bytes,
+    );
+    let (resume_from, total_size) = (start.downloaded_bytes, start.total_bytes);
+    progress_callback(start);
 
-    use futures_util::StreamExt;
+    // Hash while streaming; a resumed download first hashes what's on disk
+    let mut hasher = match &source.checksum_sha256 {
+        Some(_) => Some(
+            hash_existing(&part_path, resume_from, |hashed| {
+                progress_callback(DownloadProgress {
+                    model_id: model_id.to_string(),
+                    status: DownloadStatus::Verifying,
+                    downloaded_bytes: hashed,
+                    total_bytes: resume_from,
+                    percent: (hashed as f32 / resume_from as f32) * 100.0,
+                });
+            })
+            .await?,
+        ),
+        None => None,
+    };
 
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|e| format!("Download error: {}", e))?;
-        file.write_all(&chunk)
-            .await
-            .map_err(|e| format!("Write error: {}", e))?;
+    let result = write_stream(
+        response.bytes_stream(),
+        &part_path,
+        resume_from,
+        hasher.as_mut(),
+        |downloaded| {
+            progress_callback(DownloadProgress {
+                model_id: model_id.to_string(),
+                status: DownloadStatus::Downloading,
+                downloaded_bytes: downloaded,
+                total_bytes: total_size,
+                percent: (downloaded as f32 / total_size as f32) * 100.0,
+            });
+        },
+    )
+    .await;
 
-        downloaded += chunk.len() as u64;
-        let percent = (downloaded as f32 / total_size as f32) * 100.0;
+    let checksum = match (&source.checksum_sha256, hasher) {
+        (Some(expected), Some(hasher)) if result.is_ok() => {
+            progress_callback(DownloadProgress {
+                model_id: model_id.to_string(),
+                status: DownloadStatus::Verifying,
+                downloaded_bytes: total_size,
+                total_bytes: total_size,
+                percent: 100.0,
+            });
+            Some(Checksum {
+                expected: expected.to_lowercase(),
+                actual: format!("{:x}", hasher.finalize()),
+            })
+        }
+        _ => None,
+    };
 
-        progress_callback(DownloadProgress {
-            model_id: model_id.to_string(),
-            status: DownloadStatus::Downloading,
-            downloaded_bytes: downloaded,
-            total_bytes: total_size,
-            percent,
-        });
-    }
+    finish_download(&part_path, &dest_path, total_size, checksum, result)?;
 
     progress_callback(DownloadProgress {
         model_id: model_id.to_string(),
@@ -322,6 +438,395 @@ pub async fn download_model(
     Ok(dest_path)
 }
 
+/// Whether the model's volume has room for it, so the UI can disable models
+/// that won't fit
+pub async fn check_disk_space_for(model_id: &str) -> Result<(), DownloadError> {
+    let sources = get_model_sources();
+    let source =
+        sources
+            .iter()
+            .find(|s| s.id == model_id)
+            .ok_or_else(|| DownloadError::ModelNotFound {
+                model_id: model_id.to_string(),
+            })?;
+    ensure_disk_space(source, &get_model_path(&source.id, &source.filename)).await
+}
+
+/// `DiskFull` when the volume can't hold what is left to download
+///
+/// An unreadable free-space figure doesn't block the download.
+async fn ensure_disk_space(source: &ModelSource, dest_path: &Path) -> Result<(), DownloadError> {
+    let partial = std::fs::metadata(get_partial_path(dest_path))
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let needed_bytes = disk_bytes_needed(source.size_bytes, partial);
+
+    match free_disk_bytes(dest_path).await {
+        Some(free) if free < needed_bytes => Err(DownloadError::DiskFull { needed_bytes }),
+        _ => Ok(()),
+    }
+}
+
+/// Free space a download needs, given the partial already on disk
+fn disk_bytes_needed(size: u64, partial: u64) -> u64 {
+    bytes_remaining(size, partial) + DISK_SAFETY_MARGIN_BYTES
+}
+
+/// Bytes still to fetch, given the partial already on disk
+fn bytes_remaining(size: u64, partial: u64) -> u64 {
+    if keep_partial(partial, size) {
+        size - partial
+    } else {
+        size
+    }
+}
+
+/// Free space a batch needs, given each pending model's size and partial
+///
+/// The models all land on the models volume, so one margin covers the lot.
+fn batch_disk_bytes_needed(pending: &[(u64, u64)]) -> u64 {
+    pending
+        .iter()
+        .map(|&(size, partial)| bytes_remaining(size, partial))
+        .sum::<u64>()
+        + DISK_SAFETY_MARGIN_BYTES
+}
+
+/// `DiskFull` when the models volume can't hold every model of the batch
+///
+/// Checking each model as it starts would let the first few fill the disk
+/// and fail the rest halfway. Models already on disk don't count.
+async fn ensure_batch_disk_space(
+    ids: &[String],
+    sources: &[ModelSource],
+) -> Result<(), DownloadError> {
+    let file_len = |path: &Path| std::fs::metadata(path).map(|m| m.len()).ok();
+    let pending: Vec<(u64, u64)> = ids
+        .iter()
+        .filter_map(|id| sources.iter().find(|s| &s.id == id))
+        .filter_map(|source| {
+            let dest_path = get_model_path(&source.id, &source.filename);
+            if file_len(&dest_path) == Some(source.size_bytes) {
+                return None;
+            }
+            let partial = file_len(&get_partial_path(&dest_path)).unwrap_or(0);
+            Some((source.size_bytes, partial))
+        })
+        .collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let needed_bytes = batch_disk_bytes_needed(&pending);
+    match free_disk_bytes(&get_models_dir()).await {
+        Some(free) if free < needed_bytes => Err(DownloadError::DiskFull { needed_bytes }),
+        _ => Ok(()),
+    }
+}
+
+/// Download several models, at most `max_concurrent` at a time
+///
+/// Each update is reported with batch-wide totals, which count queued models
+/// at their expected size. A failed download doesn't stop the others; the
+/// results come back in the order of `ids`, with repeated ids downloaded once.
+/// Nothing starts unless the whole batch fits on disk.
+pub async fn download_models_parallel(
+    ids: Vec<String>,
+    max_concurrent: usize,
+    progress: impl Fn(BatchDownloadProgress) + Send + Sync + 'static,
+) -> Result<Vec<ModelDownloadResult>, DownloadError> {
+    // Two tasks on one id would write the same `.part` file
+    let ids = dedupe_ids(ids);
+    let sources = get_model_sources();
+    ensure_batch_disk_space(&ids, &sources).await?;
+
+    let semaphore = Arc::new(Semaphore::new(max_concurrent.max(1)));
+    let totals = Arc::new(Mutex::new(BatchTotals::new(&ids, &sources)));
+    let progress = Arc::new(progress);
+
+    let downloads = ids.into_iter().map(|model_id| {
+        let semaphore = semaphore.clone();
+        let report = {
+            let totals = totals.clone();
+            let progress = progress.clone();
+            move |update: DownloadProgress| {
+                let batch = match totals.lock() {
+                    Ok(mut totals) => totals.record(update),
+                    Err(poisoned) => poisoned.into_inner().record(update),
+                };
+                progress(batch);
+            }
+        };
+
+        async move {
+            // The semaphore is never closed
+            let _permit = semaphore.acquire().await.ok();
+            match download_model(&model_id, report.clone()).await {
+                Ok(path) => ModelDownloadResult {
+                    model_id,
+                    path: Some(path.to_string_lossy().to_string()),
+                    error: None,
+                },
+                Err(error) => {
+                    tracing::warn!("Download of {} failed: {}", model_id, error);
+                    report(DownloadProgress {
+                        model_id: model_id.clone(),
+                        status: DownloadStatus::Failed(error.clone()),
+                        downloaded_bytes: 0,
+                        total_bytes: 0,
+                        percent: 0.0,
+                    });
+                    ModelDownloadResult {
+                        model_id,
+                        path: None,
+                        error: Some(error),
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(futures_util::future::join_all(downloads).await)
+}
+
+/// `ids` without repeats, keeping the first occurrence of each
+fn dedupe_ids(ids: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    ids.into_iter()
+        .filter(|id| seen.insert(id.clone()))
+        .collect()
+}
+
+/// Downloaded and expected bytes per model of a parallel batch
+struct BatchTotals {
+    models: HashMap<String, (u64, u64)>,
+}
+
+impl BatchTotals {
+    fn new(ids: &[String], sources: &[ModelSource]) -> Self {
+        let models = ids
+            .iter()
+            .map(|id| {
+                let size = sources
+                    .iter()
+                    .find(|s| &s.id == id)
+                    .map_or(0, |s| s.size_bytes);
+                (id.clone(), (0, size))
+            })
+            .collect();
+        Self { models }
+    }
+
+    fn record(&mut self, update: DownloadProgress) -> BatchDownloadProgress {
+        let entry = self.models.entry(update.model_id.clone()).or_insert((0, 0));
+        match update.status {
+            DownloadStatus::Downloading | DownloadStatus::Completed => {
+                *entry = (update.downloaded_bytes, update.total_bytes);
+            }
+            // Nothing more is coming; count what it got as done
+            DownloadStatus::Failed(_) => entry.1 = entry.0,
+            // Hashing a resumed partial isn't download progress
+            _ => {}
+        }
+
+        let (downloaded_bytes, total_bytes) = self
+            .models
+            .values()
+            .fold((0, 0), |(done, total), (d, t)| (done + d, total + t));
+        BatchDownloadProgress {
+            model: update,
+            downloaded_bytes,
+            total_bytes,
+            percent: if total_bytes > 0 {
+                (downloaded_bytes as f32 / total_bytes as f32) * 100.0
+            } else {
+                100.0
+            },
+        }
+    }
+}
+
+/// Path a download is written to until it has been verified
+pub fn get_partial_path(dest_path: &Path) -> PathBuf {
+    let mut name = dest_path.file_name().unwrap_or_default().to_os_string();
+    name.push(PART_SUFFIX);
+    dest_path.with_file_name(name)
+}
+
+/// Whether a partial file should be kept for a ranged resume
+///
+/// Empty files carry nothing to resume, and files at or past the expected size
+/// can't be a prefix of it, so both are discarded.
+fn keep_partial(len: u64, expected: u64) -> bool {
+    len > 0 && len < expected
+}
+
+/// First progress of a successful response: the bytes of the partial it
+/// continues from, out of the whole file
+///
+/// Servers that ignore the range answer 200 with the whole file again, so
+/// the download restarts at 0.
+fn start_progress(
+    model_id: &str,
+    status: reqwest::StatusCode,
+    content_length: Option<u64>,
+    requested: u64,
+    expected_size: u64,
+) -> DownloadProgress {
+    let resume_from = if status == reqwest::StatusCode::PARTIAL_CONTENT {
+        requested
+    } else {
+        0
+    };
+    let total_size = content_length
+        .map(|len| len + resume_from)
+        .unwrap_or(expected_size);
+
+    DownloadProgress {
+        model_id: model_id.to_string(),
+        status: DownloadStatus::Downloading,
+        downloaded_bytes: resume_from,
+        total_bytes: total_size,
+        percent: (resume_from as f32 / total_size as f32) * 100.0,
+    }
+}
+
+/// SHA-256 state over the first `len` bytes of a partial download
+async fn hash_existing(path: &Path, len: u64, on_progress: impl Fn(u64)) -> Result<Sha256, String> {
+    let mut hasher = Sha256::new();
+    if len == 0 {
+        return Ok(hasher);
+    }
+
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| format!("Failed to open partial download: {}", e))?
+        .take(len);
+    let mut buffer = vec![0; 1 << 20];
+    let mut hashed = 0;
+    loop {
+        let n = file
+            .read(&mut buffer)
+            .await
+            .map_err(|e| format!("Failed to read partial download: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        hashed += n as u64;
+        on_progress(hashed);
+    }
+    Ok(hasher)
+}
+
+/// Append `stream` to the partial file, returning its length once the stream ends
+///
+/// Written bytes are also fed to `hasher`, so large files needn't be re-read
+/// to verify them.
+async fn write_stream<S, E>(
+    mut stream: S,
+    part_path: &Path,
+    resume_from: u64,
+    mut hasher: Option<&mut Sha256>,
+    on_progress: impl Fn(u64),
+) -> Result<u64, String>
+where
+    S: futures_util::Stream<Item = Result<bytes::Bytes, E>> + Unpin,
+    E: std::fmt::Display,
+{
+    use futures_util::StreamExt;
+
+    let mut file = if resume_from > 0 {
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(part_path)
+            .await
+    } else {
+        tokio::fs::File::create(part_path).await
+    }
+    .map_err(|e| format!("Failed to create file: {}", e))?;
+
+    let mut downloaded = resume_from;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Download error: {}", e))?;
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Write error: {}", e))?;
+        if let Some(hasher) = hasher.as_deref_mut() {
+            hasher.update(&chunk);
+        }
+
+        downloaded += chunk.len() as u64;
+        on_progress(downloaded);
+    }
+
+    file.flush()
+        .await
+        .map_err(|e| format!("Write error: {}", e))?;
+    Ok(downloaded)
+}
+
+/// Expected and computed SHA-256 of a download, as lowercase hex
+struct Checksum {
+    expected: String,
+    actual: String,
+}
+
+/// Move a verified download into place, or settle the partial file after a failure
+///
+/// On error the partial is kept only if it can be resumed; a stream that ends
+/// short or long of `expected`, or doesn't match its checksum, is treated as
+/// corrupt and deleted.
+fn finish_download(
+    part_path: &Path,
+    dest_path: &Path,
+    expected: u64,
+    checksum: Option<Checksum>,
+    result: Result<u64, String>,
+) -> Result<(), String> {
+    let error = match result {
+        Ok(len) if len == expected => {
+            if let Some(Checksum { expected, actual }) = checksum {
+                if actual != expected {
+                    let _ = std::fs::remove_file(part_path);
+                    return Err(DownloadError::ChecksumMismatch {
+                        filename: dest_path
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .to_string(),
+                        expected,
+                        actual,
+                    }
+                    .into());
+                }
+            }
+            return std::fs::rename(part_path, dest_path)
+                .map_err(|e| format!("Failed to move download into place: {}", e));
+        }
+        Ok(len) => {
+            let _ = std::fs::remove_file(part_path);
+            return Err(format!(
+                "Download incomplete: got {} of {} bytes",
+                len, expected
+            ));
+        }
+        Err(e) => e,
+    };
+
+    let len = std::fs::metadata(part_path).map(|m| m.len()).unwrap_or(0);
+    if keep_partial(len, expected) {
+        tracing::warn!(
+            "Download of {} failed at {} bytes, keeping partial for resume",
+            dest_path.display(),
+            len
+        );
+    } else {
+        let _ = std::fs::remove_file(part_path);
+    }
+    Err(error)
+}
+
 /// Download a model via Ollama (for LLMs)
 pub async fn download_via_ollama(model_name: &str) -> Result<(), String> {
     // Sanitize input to prevent injection
@@ -366,33 +871,6 @@ pub async fn is_ollama_installed() -> bool {
         .unwrap_or(false)
 }
 
-/// Get Ollama models list
-pub async fn get_ollama_models() -> Result<Vec<String>, String> {
-    use std::process::Stdio;
-    use tokio::process::Command;
-
-    let output = Command::new("ollama")
-        .args(["list"])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| format!("Failed to run ollama: {}", e))?;
-
-    if output.status.success() {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let models: Vec<String> = stdout
-            .lines()
-            .skip(1) // Skip header
-            .filter_map(|line| line.split_whitespace().next())
-            .map(String::from)
-            .collect();
-        Ok(models)
-    } else {
-        Err("Failed to list Ollama models".into())
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -408,4 +886,202 @@ mod tests {
         let sources = get_model_sources();
         assert!(!sources.is_empty());
     }
+
+    fn stream_of(
+        chunks: Vec<Result<&'static str, &'static str>>,
+    ) -> impl futures_util::Stream<Item = Result<bytes::Bytes, &'static str>> + Unpin {
+        futures_util::stream::iter(
+            chunks
+                .into_iter()
+                .map(|chunk| chunk.map(bytes::Bytes::from)),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_mid_stream_error_keeps_resumable_partial() {
+        let dir = std::env::temp_dir().join(format!("cinemaos-download-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("model.safetensors");
+        let part = get_partial_path(&dest);
+        assert_eq!(part.file_name().unwrap(), "model.safetensors.part");
+
+        // Fails after 4 of 8 bytes: the partial is kept and the model isn't "downloaded"
+        let result = write_stream(
+            stream_of(vec![Ok("abcd"), Err("connection reset")]),
+            &part,
+            0,
+            None,
+            |_| {},
+        )
+        .await;
+        let error = finish_download(&part, &dest, 8, None, result).unwrap_err();
+        assert!(error.contains("connection reset"));
+        assert_eq!(std::fs::read(&part).unwrap(), b"abcd");
+        assert!(!dest.exists());
+
+        // Resuming appends the rest and moves the verified file into place
+        let result = write_stream(stream_of(vec![Ok("efgh")]), &part, 4, None, |_| {}).await;
+        finish_download(&part, &dest, 8, None, result).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"abcdefgh");
+        assert!(!part.exists());
+
+        // Failing before any bytes arrive leaves nothing behind
+        let dest = dir.join("empty.safetensors");
+        let part = get_partial_path(&dest);
+        let result = write_stream(stream_of(vec![Err("timed out")]), &part, 0, None, |_| {}).await;
+        assert!(finish_download(&part, &dest, 8, None, result).is_err());
+        assert!(!part.exists());
+        assert!(!dest.exists());
+
+        // A stream longer than expected is corrupt
+        let result = write_stream(stream_of(vec![Ok("abcdefghij")]), &part, 0, None, |_| {}).await;
+        assert!(finish_download(&part, &dest, 8, None, result).is_err());
+        assert!(!part.exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_start_progress_follows_the_response() {
+        // The server honoured the range: continue after the partial
+        let resumed = start_progress(
+            "m",
+            reqwest::StatusCode::PARTIAL_CONTENT,
+            Some(768),
+            256,
+            1024,
+        );
+        assert_eq!((resumed.downloaded_bytes, resumed.total_bytes), (256, 1024));
+        assert_eq!(resumed.percent, 25.0);
+
+        // It ignored the range and sends everything: the bar starts at 0
+        let restarted = start_progress("m", reqwest::StatusCode::OK, Some(1024), 256, 1024);
+        assert_eq!(
+            (restarted.downloaded_bytes, restarted.total_bytes),
+            (0, 1024)
+        );
+        assert_eq!(restarted.percent, 0.0);
+
+        // Without a length the expected size is the total
+        let unknown_length = start_progress("m", reqwest::StatusCode::OK, None, 0, 2048);
+        assert_eq!(unknown_length.total_bytes, 2048);
+    }
+
+    #[test]
+    fn test_disk_bytes_needed() {
+        let gb = 1024 * 1024 * 1024;
+        assert_eq!(disk_bytes_needed(40 * gb, 0), 41 * gb);
+        // Only the rest of a resumable partial
+        assert_eq!(disk_bytes_needed(40 * gb, 30 * gb), 11 * gb);
+        // An oversized partial is discarded and downloaded again
+        assert_eq!(disk_bytes_needed(40 * gb, 50 * gb), 41 * gb);
+    }
+
+    #[test]
+    fn test_batch_disk_bytes_needed() {
+        let gb = 1024 * 1024 * 1024;
+        // One margin for the batch, not one per model
+        assert_eq!(
+            batch_disk_bytes_needed(&[(40 * gb, 0), (20 * gb, 0)]),
+            61 * gb
+        );
+        // Resumable partials only need their remainder
+        assert_eq!(
+            batch_disk_bytes_needed(&[(40 * gb, 30 * gb), (20 * gb, 0)]),
+            31 * gb
+        );
+        assert_eq!(batch_disk_bytes_needed(&[]), gb);
+    }
+
+    #[test]
+    fn test_dedupe_ids_keeps_first_occurrence() {
+        let ids = ["b", "a", "b", "c", "a"].map(String::from).to_vec();
+        assert_eq!(dedupe_ids(ids), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_batch_totals() {
+        let source = |id: &str, size_bytes| ModelSource {
+            id: id.into(),
+            name: id.into(),
+            download_url: String::new(),
+            filename: format!("{}.safetensors", id),
+            size_bytes,
+            checksum_sha256: None,
+            requires_auth: false,
+        };
+        let update = |id: &str, status, downloaded_bytes, total_bytes| DownloadProgress {
+            model_id: id.into(),
+            status,
+            downloaded_bytes,
+            total_bytes,
+            percent: 0.0,
+        };
+        let mut totals = BatchTotals::new(
+            &["a".into(), "b".into()],
+            &[source("a", 100), source("b", 300)],
+        );
+
+        // "b" is still queued but counts toward the total
+        let batch = totals.record(update("a", DownloadStatus::Downloading, 50, 100));
+        assert_eq!(batch.model.model_id, "a");
+        assert_eq!((batch.downloaded_bytes, batch.total_bytes), (50, 400));
+
+        let batch = totals.record(update("b", DownloadStatus::Downloading, 100, 300));
+        assert_eq!((batch.downloaded_bytes, batch.total_bytes), (150, 400));
+
+        // A failed model stops holding the batch back
+        let batch = totals.record(update("a", DownloadStatus::Failed("reset".into()), 0, 0));
+        assert_eq!((batch.downloaded_bytes, batch.total_bytes), (150, 350));
+
+        let batch = totals.record(update("b", DownloadStatus::Completed, 300, 300));
+        assert_eq!(batch.percent, 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_checksum_covers_resumed_download() {
+        let dir = std::env::temp_dir().join(format!("cinemaos-checksum-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("model.safetensors");
+        let part = get_partial_path(&dest);
+        let expected = format!("{:x}", Sha256::digest(b"abcdefgh"));
+
+        // Resume after "abcd": the hash spans the bytes already on disk
+        std::fs::write(&part, b"abcd").unwrap();
+        let mut hasher = hash_existing(&part, 4, |_| {}).await.unwrap();
+        let result = write_stream(
+            stream_of(vec![Ok("efgh")]),
+            &part,
+            4,
+            Some(&mut hasher),
+            |_| {},
+        )
+        .await;
+        let checksum = Checksum {
+            expected: expected.clone(),
+            actual: format!("{:x}", hasher.finalize()),
+        };
+        finish_download(&part, &dest, 8, Some(checksum), result).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"abcdefgh");
+
+        // Same length, different bytes: rejected and the partial deleted
+        let mut hasher = Sha256::new();
+        let result = write_stream(
+            stream_of(vec![Ok("abcdXXXX")]),
+            &part,
+            0,
+            Some(&mut hasher),
+            |_| {},
+        )
+        .await;
+        let checksum = Checksum {
+            expected,
+            actual: format!("{:x}", hasher.finalize()),
+        };
+        let error = finish_download(&part, &dest, 8, Some(checksum), result).unwrap_err();
+        assert!(error.contains("Checksum mismatch"));
+        assert!(!part.exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
@@ -1,21 +1,30 @@
 //! GPU Detection using WGPU
 //!
-//! Detects GPU hardware information using WGPU adapters.
-//! Falls back to nvidia-smi/rocm-smi if WGPU fails.
+//! Enumerates every GPU in the machine. nvidia-smi is preferred because it
+//! reports real VRAM, driver and CUDA versions for each card; rocm-smi and
+//! WGPU adapter enumeration are used when it is unavailable.
 
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::{HashMap, HashSet};
 use tracing::{debug, warn};
 
 /// GPU information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 pub struct GpuInfo {
     pub name: String,
     pub vendor: String,
     pub vram_gb: u32,
     pub backend: GpuBackend,
+    pub driver_version: Option<String>,
+    /// Highest CUDA version supported by the driver (NVIDIA only)
+    pub cuda_version: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 pub enum GpuBackend {
+    Cuda,
+    Rocm,
     Vulkan,
     Metal,
     DirectX12,
@@ -50,15 +59,40 @@ pub async fn detect_gpu_async() -> Option<GpuInfo> {
     let vram_bytes = limits.max_buffer_size;
     let vram_gb = (vram_bytes / (1024 * 1024 * 1024)) as u32;
 
-    // Determine vendor from device type and name
-    let vendor = match info.vendor {
+    let vendor = vendor_name(info.vendor, &info.name);
+
+    let backend = match info.backend {
+        wgpu::Backend::Vulkan => GpuBackend::Vulkan,
+        wgpu::Backend::Metal => GpuBackend::Metal,
+        wgpu::Backend::Dx12 => GpuBackend::DirectX12,
+        wgpu::Backend::Gl => GpuBackend::OpenGL,
+        _ => GpuBackend::Unknown,
+    };
+
+    debug!(
+        "Detected GPU: {} ({}), VRAM: {} GB, Backend: {:?}",
+        info.name, vendor, vram_gb, backend
+    );
+
+    Some(GpuInfo {
+        name: info.name.clone(),
+        vendor: vendor.to_string(),
+        vram_gb,
+        backend,
+        driver_version: (!info.driver_info.is_empty()).then(|| info.driver_info.clone()),
+        cuda_version: None,
+    })
+}
+
+/// Vendor from the PCI vendor id, falling back to the adapter name
+fn vendor_name(vendor_id: u32, name: &str) -> &'static str {
+    match vendor_id {
         0x1002 => "AMD",
         0x10DE => "NVIDIA",
         0x8086 => "Intel",
         0x106B => "Apple",
         _ => {
-            // Try to infer from name
-            let name_lower = info.name.to_lowercase();
+            let name_lower = name.to_lowercase();
             if name_lower.contains("nvidia")
                 || name_lower.contains("geforce")
                 || name_lower.contains("rtx")
@@ -77,91 +111,187 @@ pub async fn detect_gpu_async() -> Option<GpuInfo> {
                 "Unknown"
             }
         }
-    };
-
-    let backend = match info.backend {
-        wgpu::Backend::Vulkan => GpuBackend::Vulkan,
-        wgpu::Backend::Metal => GpuBackend::Metal,
-        wgpu::Backend::Dx12 => GpuBackend::DirectX12,
-        wgpu::Backend::Gl => GpuBackend::OpenGL,
-        _ => GpuBackend::Unknown,
-    };
-
-    debug!(
-        "Detected GPU: {} ({}), VRAM: {} GB, Backend: {:?}",
-        info.name, vendor, vram_gb, backend
-    );
-
-    Some(GpuInfo {
-        name: info.name.clone(),
-        vendor: vendor.to_string(),
-        vram_gb,
-        backend,
-    })
-}
-
-/// Synchronous wrapper for GPU detection
-pub fn detect_gpu() -> (Option<String>, Option<String>, u32) {
-    // Try WGPU detection first (async)
-    let result = tokio::runtime::Runtime::new()
-        .ok()
-        .and_then(|rt| rt.block_on(detect_gpu_async()));
-
-    if let Some(gpu) = result {
-        return (Some(gpu.name), Some(gpu.vendor), gpu.vram_gb);
     }
+}
 
-    // Fallback to nvidia-smi on Windows/Linux
+/// Enumerate all GPUs (empty on CPU-only machines)
+pub fn detect_gpus() -> Vec<GpuInfo> {
+    // nvidia-smi reports real VRAM and driver/CUDA versions per card
     #[cfg(any(windows, target_os = "linux"))]
     {
-        if let Some((name, vram)) = try_nvidia_smi() {
-            return (Some(name), Some("NVIDIA".to_string()), vram);
+        let gpus = try_nvidia_smi();
+        if !gpus.is_empty() {
+            return gpus;
         }
     }
 
     // Fallback to rocm-smi on Linux
     #[cfg(target_os = "linux")]
     {
-        if let Some((name, vram)) = try_rocm_smi() {
-            return (Some(name), Some("AMD".to_string()), vram);
+        if let Some(gpu) = try_rocm_smi() {
+            return vec![gpu];
         }
     }
 
-    warn!("No GPU detected");
-    (None, None, 0)
+    let gpus = enumerate_wgpu_adapters();
+    if gpus.is_empty() {
+        warn!("No GPU detected");
+    }
+    gpus
 }
 
-/// Try detecting NVIDIA GPU via nvidia-smi
-#[cfg(any(windows, target_os = "linux"))]
-fn try_nvidia_smi() -> Option<(String, u32)> {
-    let output = std::process::Command::new("nvidia-smi")
-        .args([
-            "--query-gpu=name,memory.total",
-            "--format=csv,noheader,nounits",
-        ])
-        .output()
-        .ok()?;
+/// Synchronous wrapper for GPU detection (the GPU with the most VRAM)
+pub fn detect_gpu() -> (Option<String>, Option<String>, u32) {
+    match detect_gpus().into_iter().max_by_key(|gpu| gpu.vram_gb) {
+        Some(gpu) => (Some(gpu.name), Some(gpu.vendor), gpu.vram_gb),
+        None => (None, None, 0),
+    }
+}
 
-    if !output.status.success() {
-        return None;
+/// Hardware GPUs visible to WGPU (software and CPU adapters are skipped)
+fn enumerate_wgpu_adapters() -> Vec<GpuInfo> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+
+    let adapters: Vec<_> = instance
+        .enumerate_adapters(wgpu::Backends::all())
+        .into_iter()
+        .filter(|adapter| {
+            matches!(
+                adapter.get_info().device_type,
+                wgpu::DeviceType::DiscreteGpu | wgpu::DeviceType::IntegratedGpu
+            )
+        })
+        .collect();
+    let infos: Vec<_> = adapters.iter().map(|adapter| adapter.get_info()).collect();
+    let cards: Vec<_> = infos
+        .iter()
+        .map(|info| {
+            (
+                info.backend,
+                info.name.as_str(),
+                info.device_pci_bus_id.as_str(),
+            )
+        })
+        .collect();
+
+    let mut gpus: Vec<GpuInfo> = Vec::new();
+    for i in distinct_cards(&cards) {
+        let (adapter, info) = (&adapters[i], infos[i].clone());
+        let backend = match info.backend {
+            wgpu::Backend::Vulkan => GpuBackend::Vulkan,
+            wgpu::Backend::Metal => GpuBackend::Metal,
+            wgpu::Backend::Dx12 => GpuBackend::DirectX12,
+            wgpu::Backend::Gl => GpuBackend::OpenGL,
+            _ => GpuBackend::Unknown,
+        };
+
+        gpus.push(GpuInfo {
+            vendor: vendor_name(info.vendor, &info.name).to_string(),
+            vram_gb: (adapter.limits().max_buffer_size / (1024 * 1024 * 1024)) as u32,
+            backend,
+            driver_version: (!info.driver_info.is_empty()).then(|| info.driver_info.clone()),
+            cuda_version: None,
+            name: info.name,
+        });
     }
+    gpus
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let parts: Vec<&str> = stdout.trim().split(',').collect();
+/// Indices of the adapters (backend, name, PCI bus id) that are distinct cards
+///
+/// A card shows up once per backend (Vulkan, GL, ...). Its PCI bus id tells
+/// copies apart; where a backend doesn't report one, the nth adapter of a
+/// name is taken to be the nth card of that name, so identical cards in one
+/// machine are all kept.
+fn distinct_cards(adapters: &[(wgpu::Backend, &str, &str)]) -> Vec<usize> {
+    // Adapters with a bus id first, so those without can be matched to them
+    let mut order: Vec<usize> = (0..adapters.len()).collect();
+    order.sort_by_key(|&i| adapters[i].2.is_empty());
 
-    if parts.len() >= 2 {
-        let name = parts[0].trim().to_string();
-        let vram_mb: u32 = parts[1].trim().parse().ok()?;
-        debug!("nvidia-smi detected: {} with {} MB VRAM", name, vram_mb);
-        return Some((name, vram_mb / 1024));
+    let mut kept: Vec<usize> = Vec::new();
+    let mut buses = HashSet::new();
+    let mut ordinals: HashMap<(wgpu::Backend, &str), usize> = HashMap::new();
+    for i in order {
+        let (backend, name, bus_id) = adapters[i];
+        let new_card = if bus_id.is_empty() {
+            let ordinal = ordinals.entry((backend, name)).or_default();
+            *ordinal += 1;
+            kept.iter().filter(|&&k| adapters[k].1 == name).count() < *ordinal
+        } else {
+            buses.insert(bus_id)
+        };
+        if new_card {
+            kept.push(i);
+        }
     }
+    kept.sort_unstable();
+    kept
+}
+
+/// Try detecting NVIDIA GPUs via nvidia-smi
+#[cfg(any(windows, target_os = "linux"))]
+fn try_nvidia_smi() -> Vec<GpuInfo> {
+    let run = |args: &[&str]| {
+        std::process::Command::new("nvidia-smi")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+    };
+
+    let Some(csv) = run(&[
+        "--query-gpu=name,memory.total,driver_version",
+        "--format=csv,noheader,nounits",
+    ]) else {
+        return Vec::new();
+    };
 
-    None
+    // The CUDA version is only printed in the summary header
+    let cuda_version = run(&[]).and_then(|summary| parse_cuda_version(&summary));
+
+    let gpus = parse_nvidia_smi(&csv, cuda_version.as_deref());
+    debug!("nvidia-smi detected {} GPU(s)", gpus.len());
+    gpus
+}
+
+/// Parse `nvidia-smi --query-gpu=name,memory.total,driver_version` CSV output
+/// (one line per GPU, memory in MiB)
+pub fn parse_nvidia_smi(csv: &str, cuda_version: Option<&str>) -> Vec<GpuInfo> {
+    csv.lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split(',').map(str::trim).collect();
+            let vram_mb: u32 = parts.get(1)?.parse().ok()?;
+            Some(GpuInfo {
+                name: parts[0].to_string(),
+                vendor: "NVIDIA".to_string(),
+                vram_gb: vram_mb / 1024,
+                backend: GpuBackend::Cuda,
+                driver_version: parts
+                    .get(2)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| v.to_string()),
+                cuda_version: cuda_version.map(String::from),
+            })
+        })
+        .collect()
+}
+
+/// Extract "12.4" from the `CUDA Version: 12.4` field of nvidia-smi's header
+pub fn parse_cuda_version(summary: &str) -> Option<String> {
+    let (_, rest) = summary.split_once("CUDA Version:")?;
+    rest.split_whitespace()
+        .next()
+        .map(|v| v.trim_end_matches('|').to_string())
+        .filter(|v| !v.is_empty())
 }
 
 /// Try detecting AMD GPU via rocm-smi
 #[cfg(target_os = "linux")]
-fn try_rocm_smi() -> Option<(String, u32)> {
+fn try_rocm_smi() -> Option<GpuInfo> {
     let output = std::process::Command::new("rocm-smi")
         .args(["--showproductname", "--showmeminfo", "vram"])
         .output()
@@ -183,7 +313,103 @@ fn try_rocm_smi() -> Option<(String, u32)> {
 
     // Default VRAM (rocm-smi output parsing is complex)
     debug!("rocm-smi detected: {}", name);
-    Some((name, 8)) // Conservative default
+    Some(GpuInfo {
+        name,
+        vendor: "AMD".to_string(),
+        vram_gb: 8, // Conservative default
+        backend: GpuBackend::Rocm,
+        driver_version: None,
+        cuda_version: None,
+    })
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PYTORCH BUILD
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// PyTorch wheel build matching the machine's GPUs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub enum TorchBackend {
+    Cuda124,
+    Cuda121,
+    Rocm,
+    /// The default PyPI wheel, which includes MPS on Apple Silicon
+    Mps,
+    #[default]
+    Cpu,
+}
+
+impl TorchBackend {
+    /// Index to pass to `pip install --index-url`; `None` uses PyPI
+    pub fn index_url(self) -> Option<&'static str> {
+        match self {
+            TorchBackend::Cuda124 => Some("https://download.pytorch.org/whl/cu124"),
+            TorchBackend::Cuda121 => Some("https://download.pytorch.org/whl/cu121"),
+            TorchBackend::Rocm => Some("https://download.pytorch.org/whl/rocm6.2"),
+            TorchBackend::Mps => None,
+            TorchBackend::Cpu => Some("https://download.pytorch.org/whl/cpu"),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TorchBackend::Cuda124 => "CUDA 12.4",
+            TorchBackend::Cuda121 => "CUDA 12.1",
+            TorchBackend::Rocm => "ROCm",
+            TorchBackend::Mps => "MPS",
+            TorchBackend::Cpu => "CPU",
+        }
+    }
+}
+
+/// Pick the PyTorch build for `gpus` on `os` (as in `std::env::consts::OS`)
+pub fn select_torch_backend(gpus: &[GpuInfo], os: &str) -> TorchBackend {
+    if os == "macos" {
+        return TorchBackend::Mps;
+    }
+
+    let nvidia: Vec<&GpuInfo> = gpus
+        .iter()
+        .filter(|gpu| gpu.vendor == "NVIDIA" || gpu.backend == GpuBackend::Cuda)
+        .collect();
+    if !nvidia.is_empty() {
+        // cu124 wheels need a driver that supports CUDA 12.4
+        let cuda_124 = nvidia
+            .iter()
+            .any(|gpu| cuda_at_least(gpu.cuda_version.as_deref(), (12, 4)));
+        return if cuda_124 {
+            TorchBackend::Cuda124
+        } else {
+            TorchBackend::Cuda121
+        };
+    }
+
+    // ROCm wheels are only published for Linux
+    let amd = gpus
+        .iter()
+        .any(|gpu| gpu.vendor == "AMD" || gpu.backend == GpuBackend::Rocm);
+    if amd && os == "linux" {
+        return TorchBackend::Rocm;
+    }
+
+    TorchBackend::Cpu
+}
+
+/// The PyTorch build for this machine
+pub fn detect_torch_backend() -> TorchBackend {
+    select_torch_backend(&detect_gpus(), std::env::consts::OS)
+}
+
+fn cuda_at_least(version: Option<&str>, minimum: (u32, u32)) -> bool {
+    let Some(version) = version else {
+        return false;
+    };
+    let mut parts = version.split('.').map(|p| p.trim().parse::<u32>());
+    match (parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor))) => (major, minor) >= minimum,
+        (Some(Ok(major)), None) => (major, 0) >= minimum,
+        _ => false,
+    }
 }
 
 #[cfg(test)]
@@ -197,6 +423,104 @@ mod tests {
         assert!(result.0.is_some() || result.0.is_none());
     }
 
+    #[test]
+    fn test_parse_nvidia_smi_multi_gpu() {
+        let csv = "NVIDIA GeForce RTX 4090, 24564, 550.54.14\nNVIDIA RTX A4000, 16376, 550.54.14\n";
+        let gpus = parse_nvidia_smi(csv, Some("12.4"));
+
+        assert_eq!(gpus.len(), 2);
+        assert_eq!(gpus[0].name, "NVIDIA GeForce RTX 4090");
+        assert_eq!(gpus[0].vram_gb, 23);
+        assert_eq!(gpus[1].vram_gb, 15);
+        assert_eq!(gpus[1].driver_version.as_deref(), Some("550.54.14"));
+        assert_eq!(gpus[1].cuda_version.as_deref(), Some("12.4"));
+        assert!(parse_nvidia_smi("", None).is_empty());
+    }
+
+    #[test]
+    fn test_distinct_cards() {
+        use wgpu::Backend::{Gl, Vulkan};
+
+        // Two identical cards, each also listed by GL without a bus id
+        let adapters = [
+            (Vulkan, "RTX 4090", "0000:01:00.0"),
+            (Vulkan, "RTX 4090", "0000:02:00.0"),
+            (Gl, "RTX 4090", ""),
+            (Gl, "RTX 4090", ""),
+        ];
+        assert_eq!(distinct_cards(&adapters), [0, 1]);
+
+        // No bus ids anywhere: matched by position within each backend
+        let adapters = [
+            (Vulkan, "RTX 4090", ""),
+            (Vulkan, "RTX 4090", ""),
+            (Gl, "RTX 4090", ""),
+            (Vulkan, "Iris Xe", ""),
+            (Gl, "Iris Xe", ""),
+        ];
+        assert_eq!(distinct_cards(&adapters), [0, 1, 3]);
+    }
+
+    #[test]
+    fn test_parse_cuda_version() {
+        let summary =
+            "| NVIDIA-SMI 550.54.14   Driver Version: 550.54.14   CUDA Version: 12.4     |";
+        assert_eq!(parse_cuda_version(summary).as_deref(), Some("12.4"));
+        assert_eq!(parse_cuda_version("No devices were found"), None);
+    }
+
+    #[test]
+    fn test_select_torch_backend() {
+        let gpu = |vendor: &str, backend: GpuBackend, cuda: Option<&str>| GpuInfo {
+            name: format!("{} GPU", vendor),
+            vendor: vendor.to_string(),
+            vram_gb: 16,
+            backend,
+            driver_version: None,
+            cuda_version: cuda.map(String::from),
+        };
+        let url = |gpus: &[GpuInfo], os: &str| select_torch_backend(gpus, os).index_url();
+
+        let nvidia = |cuda: &str| gpu("NVIDIA", GpuBackend::Cuda, Some(cuda));
+        let amd = || gpu("AMD", GpuBackend::Rocm, None);
+
+        assert_eq!(
+            url(&[nvidia("12.4")], "windows"),
+            Some("https://download.pytorch.org/whl/cu124")
+        );
+        assert_eq!(
+            url(&[nvidia("12.2")], "linux"),
+            Some("https://download.pytorch.org/whl/cu121")
+        );
+        assert_eq!(
+            url(&[gpu("NVIDIA", GpuBackend::Vulkan, None)], "linux"),
+            Some("https://download.pytorch.org/whl/cu121")
+        );
+        assert_eq!(
+            url(&[amd()], "linux"),
+            Some("https://download.pytorch.org/whl/rocm6.2")
+        );
+        assert_eq!(
+            url(&[amd()], "windows"),
+            Some("https://download.pytorch.org/whl/cpu")
+        );
+        assert_eq!(url(&[gpu("Apple", GpuBackend::Metal, None)], "macos"), None);
+        assert_eq!(
+            url(&[gpu("Intel", GpuBackend::Vulkan, None)], "linux"),
+            Some("https://download.pytorch.org/whl/cpu")
+        );
+        assert_eq!(
+            url(&[], "linux"),
+            Some("https://download.pytorch.org/whl/cpu")
+        );
+
+        // Any NVIDIA card wins over an AMD one
+        assert_eq!(
+            select_torch_backend(&[amd(), nvidia("12.2"), nvidia("12.6")], "linux"),
+            TorchBackend::Cuda124
+        );
+    }
+
     #[tokio::test]
     async fn test_async_detection() {
         let result = detect_gpu_async().await;
@@ -5,18 +5,25 @@
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+use crate::installer::gpu_detector::{self, GpuInfo};
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // HARDWARE INFO
 // ═══════════════════════════════════════════════════════════════════════════════
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
 pub struct HardwareInfo {
+    /// Name of the GPU with the most VRAM
     pub gpu_name: Option<String>,
     pub gpu_vendor: Option<String>,
+    /// VRAM of the largest single GPU (a model must fit on one card)
     pub vram_gb: u32,
     pub ram_gb: u32,
     pub cpu_cores: u32,
     pub os: String,
+    /// Every detected GPU (empty on CPU-only machines)
+    #[serde(default)]
+    pub gpus: Vec<GpuInfo>,
 }
 
 impl Default for HardwareInfo {
@@ -28,10 +35,41 @@ impl Default for HardwareInfo {
             ram_gb: 8,
             cpu_cores: 4,
             os: std::env::consts::OS.to_string(),
+            gpus: Vec::new(),
         }
     }
 }
 
+impl HardwareInfo {
+    /// Build from detected parts; the summary fields describe the largest GPU
+    pub fn from_parts(gpus: Vec<GpuInfo>, ram_gb: u32, cpu_cores: u32) -> Self {
+        let largest = gpus.iter().max_by_key(|gpu| gpu.vram_gb);
+
+        Self {
+            gpu_name: largest.map(|gpu| gpu.name.clone()),
+            gpu_vendor: largest.map(|gpu| gpu.vendor.clone()),
+            vram_gb: largest.map(|gpu| gpu.vram_gb).unwrap_or(0),
+            ram_gb,
+            cpu_cores,
+            os: std::env::consts::OS.to_string(),
+            gpus,
+        }
+    }
+
+    pub fn is_cpu_only(&self) -> bool {
+        self.gpus.is_empty() && self.vram_gb == 0
+    }
+
+    /// Combined VRAM across all GPUs (informational; models can't span cards)
+    pub fn total_vram_gb(&self) -> u32 {
+        self.gpus
+            .iter()
+            .map(|gpu| gpu.vram_gb)
+            .sum::<u32>()
+            .max(self.vram_gb)
+    }
+}
+
 /// Detect hardware
 pub fn detect_hardware() -> HardwareInfo {
     let ram_gb = sys_info::mem_info()
@@ -40,22 +78,8 @@ pub fn detect_hardware() -> HardwareInfo {
 
     let cpu_cores = sys_info::cpu_num().unwrap_or(4);
 
-    // GPU detection using WGPU with fallbacks
-    let (gpu_name, gpu_vendor, vram_gb) = detect_gpu();
-
-    HardwareInfo {
-        gpu_name,
-        gpu_vendor,
-        vram_gb,
-        ram_gb,
-        cpu_cores,
-        os: std::env::consts::OS.to_string(),
-    }
-}
-
-fn detect_gpu() -> (Option<String>, Option<String>, u32) {
-    use crate::installer::gpu_detector;
-    gpu_detector::detect_gpu()
+    // GPU detection using nvidia-smi/rocm-smi with a WGPU fallback
+    HardwareInfo::from_parts(gpu_detector::detect_gpus(), ram_gb, cpu_cores)
 }
 
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -85,6 +109,8 @@ pub enum ModelCategory {
 }
 
 /// Get model recommendations based on hardware
+///
+/// VRAM requirements are checked against the largest single GPU.
 pub fn get_model_recommendations(hardware: &HardwareInfo) -> Vec<ModelRecommendation> {
     let vram = hardware.vram_gb;
 
@@ -219,4 +245,64 @@ mod tests {
         let recs = get_runnable_models(&hw);
         assert!(recs.len() > 5); // Should run most models
     }
+
+    fn gpu(name: &str, vram_gb: u32) -> GpuInfo {
+        GpuInfo {
+            name: name.into(),
+            vendor: "NVIDIA".into(),
+            vram_gb,
+            backend: gpu_detector::GpuBackend::Cuda,
+            driver_version: Some("550.54.14".into()),
+            cuda_version: Some("12.4".into()),
+        }
+    }
+
+    fn runnable_ids(hw: &HardwareInfo) -> Vec<String> {
+        get_runnable_models(hw).into_iter().map(|m| m.id).collect()
+    }
+
+    #[test]
+    fn test_single_gpu() {
+        let hw = HardwareInfo::from_parts(vec![gpu("RTX 4070", 12)], 32, 16);
+        assert_eq!(hw.gpu_name.as_deref(), Some("RTX 4070"));
+        assert_eq!(hw.vram_gb, 12);
+        assert!(!hw.is_cpu_only());
+
+        let ids = runnable_ids(&hw);
+        assert!(ids.contains(&"flux-schnell".to_string()));
+        assert!(!ids.contains(&"flux-dev".to_string()));
+    }
+
+    #[test]
+    fn test_multi_gpu_uses_largest_card() {
+        let hw = HardwareInfo::from_parts(
+            vec![
+                gpu("RTX A4000", 16),
+                gpu("RTX 4090", 24),
+                gpu("RTX 4090", 24),
+            ],
+            128,
+            32,
+        );
+        assert_eq!(hw.gpus.len(), 3);
+        assert_eq!(hw.gpu_name.as_deref(), Some("RTX 4090"));
+        assert_eq!(hw.vram_gb, 24);
+        assert_eq!(hw.total_vram_gb(), 64);
+
+        // 64 GB in total, but a 70B model needs 48 GB on one card
+        let ids = runnable_ids(&hw);
+        assert!(ids.contains(&"flux-dev".to_string()));
+        assert!(!ids.contains(&"llama-3.1-70b".to_string()));
+    }
+
+    #[test]
+    fn test_cpu_only() {
+        let hw = HardwareInfo::from_parts(Vec::new(), 16, 8);
+        assert!(hw.is_cpu_only());
+        assert_eq!(hw.gpu_name, None);
+        assert_eq!(hw.vram_gb, 0);
+
+        // Only CPU-capable models remain
+        assert_eq!(runnable_ids(&hw), ["whisper-large"]);
+    }
 }
@@ -0,0 +1,146 @@
+//! Process Logs - Captured stdout/stderr of the ComfyUI server
+//!
+//! Lines are kept in a ring buffer for late readers and broadcast to live
+//! subscribers (the boot log in the UI).
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Lines kept for `recent`
+pub const MAX_LOG_LINES: usize = 500;
+
+/// Lines a slow subscriber may fall behind before it skips ahead
+const BROADCAST_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Type)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+/// One line of process output
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct LogLine {
+    pub stream: LogStream,
+    pub line: String,
+}
+
+/// Recent output of a process; clones share the same buffer and channel
+#[derive(Clone)]
+pub struct ProcessLogs {
+    recent: Arc<Mutex<VecDeque<LogLine>>>,
+    sender: broadcast::Sender<LogLine>,
+}
+
+impl ProcessLogs {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(BROADCAST_CAPACITY);
+        Self {
+            recent: Arc::new(Mutex::new(VecDeque::with_capacity(MAX_LOG_LINES))),
+            sender,
+        }
+    }
+
+    /// Live lines from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<LogLine> {
+        self.sender.subscribe()
+    }
+
+    /// The last `MAX_LOG_LINES` lines, oldest first
+    pub fn recent(&self) -> Vec<String> {
+        self.buffer().iter().map(|l| l.line.clone()).collect()
+    }
+
+    /// The last `count` lines of `stream`, oldest first
+    pub fn tail(&self, stream: LogStream, count: usize) -> Vec<String> {
+        let buffer = self.buffer();
+        let mut lines: Vec<String> = buffer
+            .iter()
+            .rev()
+            .filter(|l| l.stream == stream)
+            .take(count)
+            .map(|l| l.line.clone())
+            .collect();
+        lines.reverse();
+        lines
+    }
+
+    pub fn push(&self, stream: LogStream, line: String) {
+        let line = LogLine { stream, line };
+        {
+            let mut buffer = self.buffer();
+            if buffer.len() == MAX_LOG_LINES {
+                buffer.pop_front();
+            }
+            buffer.push_back(line.clone());
+        }
+        // No subscribers is fine
+        let _ = self.sender.send(line);
+    }
+
+    /// Read `reader` line by line into the log until it closes
+    pub fn capture<R>(&self, stream: LogStream, reader: R) -> JoinHandle<()>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let logs = self.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                tracing::debug!(target: "comfyui", "{}", line);
+                logs.push(stream, line);
+            }
+        })
+    }
+
+    fn buffer(&self) -> MutexGuard<'_, VecDeque<LogLine>> {
+        match self.recent.lock() {
+            Ok(buffer) => buffer,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Default for ProcessLogs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ring_buffer_and_tail() {
+        let logs = ProcessLogs::new();
+        let mut live = logs.subscribe();
+
+        let stderr: &[u8] =
+            b"Traceback (most recent call last):\nModuleNotFoundError: No module named 'torch'\n";
+        logs.capture(LogStream::Stderr, stderr).await.unwrap();
+        logs.push(LogStream::Stdout, "Starting server".into());
+
+        assert_eq!(
+            live.recv().await.unwrap().line,
+            "Traceback (most recent call last):"
+        );
+        assert_eq!(
+            logs.tail(LogStream::Stderr, 1),
+            ["ModuleNotFoundError: No module named 'torch'"]
+        );
+
+        for i in 0..MAX_LOG_LINES {
+            logs.push(LogStream::Stdout, format!("line {}", i));
+        }
+        let recent = logs.recent();
+        assert_eq!(recent.len(), MAX_LOG_LINES);
+        assert_eq!(recent[0], "line 0");
+        assert!(logs.tail(LogStream::Stderr, 5).is_empty());
+    }
+}
@@ -3,14 +3,17 @@
 pub mod downloader;
 pub mod gpu_detector;
 pub mod hardware;
+pub mod logs;
+pub mod preflight;
 
 pub use downloader::*;
 pub use hardware::*;
 
 // Re-export from main installer module
+use crate::errors::InstallerError;
 use serde::{Deserialize, Serialize};
 use specta::Type;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::process::Command;
 
@@ -27,6 +30,7 @@ pub enum InstallStatus {
     CreatingVenv,
     InstallingComfyUI,
     InstallingDependencies,
+    Updating,
     Completed,
     Failed(String),
 }
@@ -42,12 +46,17 @@ pub struct InstallProgress {
 
 impl InstallProgress {
     pub fn new(status: InstallStatus, step: u8, message: &str) -> Self {
+        Self::of(status, step, 6, message)
+    }
+
+    /// Progress through a run of `total_steps` steps
+    pub fn of(status: InstallStatus, step: u8, total_steps: u8, message: &str) -> Self {
         Self {
             status,
             step,
-            total_steps: 6,
+            total_steps,
             message: message.to_string(),
-            percent: (step as f32 / 6.0) * 100.0,
+            percent: (step as f32 / total_steps as f32) * 100.0,
         }
     }
 }
@@ -209,7 +218,6 @@ pub async fn install_python() -> Result<(), String> {
 
 pub async fn install_comfyui() -> Result<(), String> {
     let comfyui_dir = get_comfyui_dir();
-    let venv_dir = get_venv_dir();
 
     if !comfyui_dir.exists() {
         run_command(
@@ -224,6 +232,13 @@ pub async fn install_comfyui() -> Result<(), String> {
         .await?;
     }
 
+    install_requirements().await
+}
+
+/// Install ComfyUI's `requirements.txt` into the venv
+pub async fn install_requirements() -> Result<(), String> {
+    let comfyui_dir = get_comfyui_dir();
+    let venv_dir = get_venv_dir();
     let python_path = if cfg!(windows) {
         venv_dir.join("Scripts").join("python.exe")
     } else {
@@ -244,22 +259,25 @@ pub async fn install_comfyui() -> Result<(), String> {
     )
     .await?;
 
-    run_command(
-        "uv",
-        &[
-            "pip",
-            "install",
-            "torch",
-            "torchvision",
-            "torchaudio",
-            "--index-url",
-            "https://download.pytorch.org/whl/cu121",
-            "--python",
-            python_path.to_str().unwrap(),
-        ],
-        None,
-    )
-    .await?;
+    Ok(())
+}
+
+/// Install the PyTorch build for `backend` into the venv
+pub async fn install_torch(backend: gpu_detector::TorchBackend) -> Result<(), String> {
+    let venv_dir = get_venv_dir();
+    let python_path = if cfg!(windows) {
+        venv_dir.join("Scripts").join("python.exe")
+    } else {
+        venv_dir.join("bin").join("python")
+    };
+
+    let mut args = vec!["pip", "install", "torch", "torchvision", "torchaudio"];
+    if let Some(index_url) = backend.index_url() {
+        args.extend(["--index-url", index_url]);
+    }
+    args.extend(["--python", python_path.to_str().unwrap()]);
+
+    run_command("uv", &args, None).await?;
 
     Ok(())
 }
@@ -284,18 +302,32 @@ pub async fn install_custom_nodes() -> Result<(), String> {
         .join("comfyui_nodes")
         .join("cinemaos"); // NEW: Point to the package folder
 
-    // If source exists, copy files
     if source_dir.exists() {
-        for entry in std::fs::read_dir(&source_dir).map_err(|e| e.to_string())? {
-            let entry = entry.map_err(|e| e.to_string())?;
+        copy_node_tree(&source_dir, &custom_nodes_target)
+            .map_err(|e| format!("Failed to copy CinemaOS nodes: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Copy a node package, keeping its folder structure but not Python bytecode
+pub fn copy_node_tree(source: &Path, target: &Path) -> std::io::Result<()> {
+    let mut pending = vec![(source.to_path_buf(), target.to_path_buf())];
+
+    while let Some((from, to)) = pending.pop() {
+        std::fs::create_dir_all(&to)?;
+        for entry in std::fs::read_dir(&from)? {
+            let entry = entry?;
             let path = entry.path();
-            // Copy everything (__init__.py, nodes.py, etc.)
-            let filename = path.file_name().unwrap();
-            let dest = custom_nodes_target.join(filename);
-            if path.is_file() {
-                std::fs::copy(&path, &dest).map_err(|e| e.to_string())?;
+            let dest = to.join(entry.file_name());
+
+            if entry.file_type()?.is_dir() {
+                if entry.file_name() != "__pycache__" {
+                    pending.push((path, dest));
+                }
+            } else if path.extension().and_then(|ext| ext.to_str()) != Some("pyc") {
+                std::fs::copy(&path, &dest)?;
             }
-            // Note: If we add subfolders later, we need recursive copy here.
         }
     }
 
@@ -304,7 +336,18 @@ pub async fn install_custom_nodes() -> Result<(), String> {
 
 pub async fn install_all(
     progress_callback: impl Fn(InstallProgress) + Send + 'static,
-) -> Result<(), String> {
+) -> Result<(), InstallerError> {
+    // Report a failed step as `Failed` with the same detail the caller gets
+    let fail = |step: u8, status: InstallStatus, error: String| {
+        let error = step_error(&status, error);
+        progress_callback(InstallProgress::new(
+            InstallStatus::Failed(error.to_string()),
+            step,
+            &error.remediation(),
+        ));
+        error
+    };
+
     progress_callback(InstallProgress::new(
         InstallStatus::CheckingPrerequisites,
         1,
@@ -313,21 +356,25 @@ pub async fn install_all(
 
     run_command("git", &["--version"], None)
         .await
-        .map_err(|_| "Git is required but not installed")?;
+        .map_err(|e| fail(1, InstallStatus::CheckingPrerequisites, e))?;
 
     progress_callback(InstallProgress::new(
         InstallStatus::InstallingUV,
         2,
         "Installing UV package manager...",
     ));
-    install_uv().await?;
+    install_uv()
+        .await
+        .map_err(|e| fail(2, InstallStatus::InstallingUV, e))?;
 
     progress_callback(InstallProgress::new(
         InstallStatus::InstallingPython,
         3,
         "Installing Python 3.11...",
     ));
-    install_python().await?;
+    install_python()
+        .await
+        .map_err(|e| fail(3, InstallStatus::InstallingPython, e))?;
 
     progress_callback(InstallProgress::new(
         InstallStatus::CreatingVenv,
@@ -340,7 +387,22 @@ pub async fn install_all(
         5,
         "Installing ComfyUI...",
     ));
-    install_comfyui().await?;
+    install_comfyui()
+        .await
+        .map_err(|e| fail(5, InstallStatus::InstallingComfyUI, e))?;
+
+    // nvidia-smi and rocm-smi block while they run
+    let torch_backend = tokio::task::spawn_blocking(gpu_detector::detect_torch_backend)
+        .await
+        .unwrap_or_default();
+    progress_callback(InstallProgress::new(
+        InstallStatus::InstallingDependencies,
+        5,
+        &format!("Installing PyTorch ({})...", torch_backend.label()),
+    ));
+    install_torch(torch_backend)
+        .await
+        .map_err(|e| fail(5, InstallStatus::InstallingDependencies, e))?;
 
     // Install custom nodes
     progress_callback(InstallProgress::new(
@@ -348,7 +410,9 @@ pub async fn install_all(
         5,
         "Installing CinemaOS nodes...",
     ));
-    install_custom_nodes().await?;
+    install_custom_nodes()
+        .await
+        .map_err(|e| fail(5, InstallStatus::InstallingDependencies, e))?;
 
     progress_callback(InstallProgress::new(
         InstallStatus::Completed,
@@ -359,23 +423,200 @@ pub async fn install_all(
     Ok(())
 }
 
+/// Map a step's raw error to the `InstallerError` the UI can act on
+pub fn step_error(step: &InstallStatus, error: String) -> InstallerError {
+    let lower = error.to_lowercase();
+    if lower.contains("permission denied") || lower.contains("read-only file system") {
+        return InstallerError::DirectoryNotWritable {
+            path: get_cinema_os_dir().to_string_lossy().to_string(),
+        };
+    }
+
+    match step {
+        InstallStatus::CheckingPrerequisites => InstallerError::GitNotInstalled,
+        InstallStatus::InstallingUV => InstallerError::UVInstallFailed { message: error },
+        InstallStatus::InstallingPython | InstallStatus::CreatingVenv => {
+            InstallerError::PythonInstallFailed { message: error }
+        }
+        // `run_command` errors name the command first: the clone runs git,
+        // the requirements install runs uv
+        InstallStatus::InstallingComfyUI
+            if error.starts_with("git ") || error.starts_with("Failed to execute git") =>
+        {
+            InstallerError::ComfyUICloneFailed { message: error }
+        }
+        InstallStatus::InstallingComfyUI | InstallStatus::InstallingDependencies => {
+            InstallerError::DependencyInstallFailed { message: error }
+        }
+        _ => InstallerError::CommandFailed {
+            command: "install".into(),
+            stderr: error,
+        },
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// UPDATE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+const UPDATE_STEPS: u8 = 4;
+
+/// Pull the latest ComfyUI, then reinstall its requirements and the CinemaOS nodes
+///
+/// Refuses to touch a checkout with local modifications instead of leaving
+/// it mid-merge.
+pub async fn update_comfyui(
+    progress_callback: impl Fn(InstallProgress) + Send + 'static,
+) -> Result<(), InstallerError> {
+    let comfyui_dir = get_comfyui_dir();
+    let dir = comfyui_dir.to_string_lossy().to_string();
+
+    let fail = |step: u8, error: InstallerError| {
+        progress_callback(InstallProgress::of(
+            InstallStatus::Failed(error.to_string()),
+            step,
+            UPDATE_STEPS,
+            &error.remediation(),
+        ));
+        error
+    };
+    let update_failed = |message: String| InstallerError::ComfyUIUpdateFailed { message };
+
+    progress_callback(InstallProgress::of(
+        InstallStatus::Updating,
+        1,
+        UPDATE_STEPS,
+        "Checking for local changes...",
+    ));
+    if !is_comfyui_installed().await {
+        return Err(fail(1, update_failed("ComfyUI is not installed".into())));
+    }
+    let status = run_command(
+        "git",
+        &["-C", &dir, "status", "--porcelain", "--untracked-files=no"],
+        None,
+    )
+    .await
+    .map_err(|e| fail(1, update_failed(e)))?;
+    let files = modified_files(&status);
+    if !files.is_empty() {
+        return Err(fail(
+            1,
+            InstallerError::ComfyUILocalChanges { path: dir, files },
+        ));
+    }
+
+    progress_callback(InstallProgress::of(
+        InstallStatus::Updating,
+        2,
+        UPDATE_STEPS,
+        "Pulling the latest ComfyUI...",
+    ));
+    run_command("git", &["-C", &dir, "pull", "--ff-only"], None)
+        .await
+        .map_err(|e| fail(2, update_failed(e)))?;
+
+    progress_callback(InstallProgress::of(
+        InstallStatus::Updating,
+        3,
+        UPDATE_STEPS,
+        "Updating requirements...",
+    ));
+    install_requirements()
+        .await
+        .map_err(|e| fail(3, step_error(&InstallStatus::InstallingDependencies, e)))?;
+
+    progress_callback(InstallProgress::of(
+        InstallStatus::Updating,
+        4,
+        UPDATE_STEPS,
+        "Reinstalling CinemaOS nodes...",
+    ));
+    install_custom_nodes()
+        .await
+        .map_err(|e| fail(4, step_error(&InstallStatus::InstallingDependencies, e)))?;
+
+    progress_callback(InstallProgress::of(
+        InstallStatus::Completed,
+        UPDATE_STEPS,
+        UPDATE_STEPS,
+        "ComfyUI is up to date",
+    ));
+
+    Ok(())
+}
+
+/// Paths with uncommitted changes in `git status --porcelain` output
+pub fn modified_files(porcelain: &str) -> Vec<String> {
+    porcelain
+        .lines()
+        .filter(|line| line.len() > 3)
+        .map(|line| line[3..].to_string())
+        .collect()
+}
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // COMFYUI PROCESS MANAGEMENT
 // ═══════════════════════════════════════════════════════════════════════════════
 
+/// Stderr lines quoted in a startup error
+const STARTUP_ERROR_LINES: usize = 10;
+
+pub const DEFAULT_COMFYUI_PORT: u16 = 8188;
+
+/// Ports tried, starting at the configured one, before `start` gives up
+const PORT_ATTEMPTS: u16 = 10;
+
 pub struct ComfyUIProcess {
     process: Option<tokio::process::Child>,
+    /// Preferred port; `start` moves up from it if it is taken
     port: u16,
+    /// Port the running process listens on
+    active_port: Option<u16>,
+    logs: logs::ProcessLogs,
+    log_readers: Vec<tokio::task::JoinHandle<()>>,
 }
 
 impl ComfyUIProcess {
     pub fn new() -> Self {
+        Self::with_logs(logs::ProcessLogs::new())
+    }
+
+    /// Capture output into `logs`, which callers can keep reading while
+    /// `start` holds the process
+    pub fn with_logs(logs: logs::ProcessLogs) -> Self {
         Self {
             process: None,
-            port: 8188,
+            port: DEFAULT_COMFYUI_PORT,
+            active_port: None,
+            logs,
+            log_readers: Vec::new(),
         }
     }
 
+    /// Prefer `port` over `DEFAULT_COMFYUI_PORT`
+    pub fn with_port(port: u16) -> Self {
+        Self {
+            port,
+            ..Self::new()
+        }
+    }
+
+    /// Port of the running ComfyUI, which may differ from the preferred one
+    pub fn active_port(&self) -> Option<u16> {
+        self.active_port
+    }
+
+    /// Output lines as they are printed
+    pub fn subscribe_logs(&self) -> tokio::sync::broadcast::Receiver<logs::LogLine> {
+        self.logs.subscribe()
+    }
+
+    /// The last `logs::MAX_LOG_LINES` lines of output, oldest first
+    pub fn get_recent_logs(&self) -> Vec<String> {
+        self.logs.recent()
+    }
+
     pub async fn start(&mut self) -> Result<(), String> {
         if self.process.is_some() {
             return Ok(());
@@ -390,31 +631,79 @@ impl ComfyUIProcess {
             venv_dir.join("bin").join("python")
         };
 
-        let child = Command::new(python)
+        // Another ComfyUI or CinemaOS instance may already hold the port
+        let port = find_free_port(self.port, PORT_ATTEMPTS).ok_or_else(|| {
+            format!(
+                "Ports {}-{} are all in use; stop the other ComfyUI or choose another port",
+                self.port,
+                self.port.saturating_add(PORT_ATTEMPTS - 1)
+            )
+        })?;
+        if port != self.port {
+            tracing::info!("Port {} is taken, starting ComfyUI on {}", self.port, port);
+        }
+
+        let mut child = Command::new(python)
             .arg("main.py")
             .arg("--listen")
             .arg("127.0.0.1")
             .arg("--port")
-            .arg(self.port.to_string())
+            .arg(port.to_string())
             .current_dir(&comfyui_dir)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
             .map_err(|e| format!("Failed to start ComfyUI: {}", e))?;
 
+        // Unread pipes would also stall ComfyUI once their buffers fill
+        if let Some(stdout) = child.stdout.take() {
+            self.log_readers
+                .push(self.logs.capture(logs::LogStream::Stdout, stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            self.log_readers
+                .push(self.logs.capture(logs::LogStream::Stderr, stderr));
+        }
         self.process = Some(child);
+        self.active_port = Some(port);
 
         let client = reqwest::Client::new();
-        let url = format!("http://127.0.0.1:{}/system_stats", self.port);
+        let url = format!("http://127.0.0.1:{}/system_stats", port);
 
         for _ in 0..30 {
             tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
             if client.get(&url).send().await.is_ok() {
                 return Ok(());
             }
+
+            let exited = self
+                .process
+                .as_mut()
+                .and_then(|child| child.try_wait().ok().flatten());
+            if let Some(status) = exited {
+                self.process = None;
+                self.active_port = None;
+                // Let the readers drain what the process printed before exiting
+                for reader in self.log_readers.drain(..) {
+                    let _ = tokio::time::timeout(tokio::time::Duration::from_secs(1), reader).await;
+                }
+                return Err(
+                    self.startup_error(&format!("ComfyUI exited during startup ({})", status))
+                );
+            }
         }
 
-        Err("ComfyUI failed to start within 30 seconds".into())
+        Err(self.startup_error("ComfyUI failed to start within 30 seconds"))
+    }
+
+    /// `message` followed by the last lines ComfyUI wrote to stderr
+    fn startup_error(&self, message: &str) -> String {
+        let stderr = self.logs.tail(logs::LogStream::Stderr, STARTUP_ERROR_LINES);
+        if stderr.is_empty() {
+            message.to_string()
+        } else {
+            format!("{}:\n{}", message, stderr.join("\n"))
+        }
     }
 
     pub async fn stop(&mut self) -> Result<(), String> {
@@ -424,6 +713,9 @@ impl ComfyUIProcess {
                 .await
                 .map_err(|e| format!("Failed to stop ComfyUI: {}", e))?;
         }
+        self.active_port = None;
+        // The readers end on their own once the pipes close
+        self.log_readers.clear();
         Ok(())
     }
 
@@ -437,3 +729,10 @@ impl Default for ComfyUIProcess {
         Self::new()
     }
 }
+
+/// First of `attempts` ports from `preferred` up that nothing listens on
+pub fn find_free_port(preferred: u16, attempts: u16) -> Option<u16> {
+    (0..attempts)
+        .filter_map(|offset| preferred.checked_add(offset))
+        .find(|port| std::net::TcpListener::bind(("127.0.0.1", *port)).is_ok())
+}
@@ -0,0 +1,523 @@
+//! Installer Preflight — What `install_all` would do, without doing it
+//!
+//! `gather_facts` runs the read-only checks (git, free disk, network, existing
+//! install); `build_report` turns them into per-step readiness and download
+//! estimates. Nothing is created, downloaded or removed.
+
+use super::gpu_detector::{detect_torch_backend, TorchBackend};
+use super::{
+    get_cinema_os_dir, get_comfyui_dir, get_venv_dir, is_comfyui_installed, is_python_installed,
+    is_uv_installed, run_command, InstallStatus,
+};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Hosts each step downloads from
+pub const UV_INSTALL_URL: &str = "https://astral.sh/uv/install.sh";
+pub const PYTHON_DOWNLOAD_URL: &str = "https://github.com/astral-sh/python-build-standalone";
+pub const COMFYUI_REPO_URL: &str = "https://github.com/comfyanonymous/ComfyUI.git";
+pub const PYPI_INDEX_URL: &str = "https://pypi.org/simple/";
+
+// Approximate download sizes
+const UV_DOWNLOAD_BYTES: u64 = 20 * MB;
+const PYTHON_DOWNLOAD_BYTES: u64 = 45 * MB;
+const COMFYUI_CLONE_BYTES: u64 = 60 * MB;
+const REQUIREMENTS_DOWNLOAD_BYTES: u64 = 400 * MB;
+const TORCH_DOWNLOAD_BYTES: u64 = 2600 * MB;
+const MB: u64 = 1024 * 1024;
+
+/// Unpacked wheels plus uv's cache take about twice the download
+const DISK_PER_DOWNLOAD_BYTE: u64 = 2;
+
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
+pub enum StepReadiness {
+    /// Already installed; the step will be skipped or is a no-op
+    Done,
+    /// The step will run and should succeed
+    Ready,
+    /// The step would fail; `detail` says why
+    Blocked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct PreflightStep {
+    pub status: InstallStatus,
+    pub name: String,
+    pub readiness: StepReadiness,
+    pub detail: String,
+    #[specta(type = f64)]
+    pub download_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct PreflightReport {
+    pub steps: Vec<PreflightStep>,
+    #[specta(type = f64)]
+    pub total_download_bytes: u64,
+    #[specta(type = f64)]
+    pub required_disk_bytes: u64,
+    /// `None` when free space could not be determined
+    #[specta(type = Option<f64>)]
+    pub free_disk_bytes: Option<u64>,
+    /// No step is blocked
+    pub can_install: bool,
+}
+
+/// Results of the read-only checks a report is built from
+#[derive(Debug, Clone, Default)]
+pub struct PreflightFacts {
+    /// `git --version` output, if git runs
+    pub git_version: Option<String>,
+    pub free_disk_bytes: Option<u64>,
+    /// URLs that did not answer
+    pub unreachable: Vec<String>,
+    pub uv_installed: bool,
+    pub python_installed: bool,
+    /// A venv directory without a usable interpreter
+    pub partial_venv: bool,
+    pub comfyui_installed: bool,
+    /// A ComfyUI directory without `main.py`, e.g. an interrupted clone
+    pub partial_comfyui: bool,
+    /// PyTorch build the install would pick
+    pub torch_backend: TorchBackend,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// REPORT
+// ═══════════════════════════════════════════════════════════════════════════════
+
+fn step(
+    status: InstallStatus,
+    name: &str,
+    readiness: StepReadiness,
+    detail: impl Into<String>,
+    download_bytes: u64,
+) -> PreflightStep {
+    PreflightStep {
+        status,
+        name: name.to_string(),
+        readiness,
+        detail: detail.into(),
+        download_bytes,
+    }
+}
+
+/// A step that downloads from `urls`: blocked if any of them is unreachable
+fn download_step(
+    facts: &PreflightFacts,
+    status: InstallStatus,
+    name: &str,
+    urls: &[&str],
+    download_bytes: u64,
+    detail: &str,
+) -> PreflightStep {
+    let unreachable: Vec<&str> = urls
+        .iter()
+        .copied()
+        .filter(|url| facts.unreachable.iter().any(|u| u == url))
+        .collect();
+
+    if unreachable.is_empty() {
+        step(status, name, StepReadiness::Ready, detail, download_bytes)
+    } else {
+        step(
+            status,
+            name,
+            StepReadiness::Blocked,
+            format!("Cannot reach {}", unreachable.join(", ")),
+            download_bytes,
+        )
+    }
+}
+
+/// Per-step readiness for `install_all`, in the order it runs them
+pub fn build_report(facts: &PreflightFacts) -> PreflightReport {
+    let mut steps = Vec::new();
+
+    steps.push(match &facts.git_version {
+        Some(version) => step(
+            InstallStatus::CheckingPrerequisites,
+            "Git",
+            StepReadiness::Done,
+            version.trim(),
+            0,
+        ),
+        None => step(
+            InstallStatus::CheckingPrerequisites,
+            "Git",
+            StepReadiness::Blocked,
+            "Git is not installed or not on PATH",
+            0,
+        ),
+    });
+
+    steps.push(if facts.uv_installed {
+        step(
+            InstallStatus::InstallingUV,
+            "UV",
+            StepReadiness::Done,
+            "Already installed",
+            0,
+        )
+    } else {
+        download_step(
+            facts,
+            InstallStatus::InstallingUV,
+            "UV",
+            &[UV_INSTALL_URL],
+            UV_DOWNLOAD_BYTES,
+            "Will install the uv package manager",
+        )
+    });
+
+    steps.push(if facts.python_installed {
+        step(
+            InstallStatus::InstallingPython,
+            "Python 3.11",
+            StepReadiness::Done,
+            "Virtual environment already exists",
+            0,
+        )
+    } else {
+        let detail = if facts.partial_venv {
+            "Will recreate the incomplete virtual environment"
+        } else {
+            "Will install Python 3.11 and create a virtual environment"
+        };
+        download_step(
+            facts,
+            InstallStatus::InstallingPython,
+            "Python 3.11",
+            &[PYTHON_DOWNLOAD_URL],
+            PYTHON_DOWNLOAD_BYTES,
+            detail,
+        )
+    });
+
+    steps.push(if facts.comfyui_installed {
+        step(
+            InstallStatus::InstallingComfyUI,
+            "ComfyUI",
+            StepReadiness::Done,
+            "Already cloned",
+            0,
+        )
+    } else if facts.partial_comfyui {
+        // `install_comfyui` skips the clone when the directory exists
+        step(
+            InstallStatus::InstallingComfyUI,
+            "ComfyUI",
+            StepReadiness::Blocked,
+            format!(
+                "Incomplete ComfyUI checkout at {}; remove it before installing",
+                get_comfyui_dir().display()
+            ),
+            COMFYUI_CLONE_BYTES,
+        )
+    } else {
+        download_step(
+            facts,
+            InstallStatus::InstallingComfyUI,
+            "ComfyUI",
+            &[COMFYUI_REPO_URL],
+            COMFYUI_CLONE_BYTES,
+            "Will clone ComfyUI",
+        )
+    });
+
+    // Requirements are re-checked on every run; only a fresh install downloads them
+    steps.push(if facts.comfyui_installed && facts.python_installed {
+        step(
+            InstallStatus::InstallingDependencies,
+            "Dependencies",
+            StepReadiness::Done,
+            "Already installed; requirements are re-checked",
+            0,
+        )
+    } else {
+        let mut urls = vec![PYPI_INDEX_URL];
+        urls.extend(facts.torch_backend.index_url());
+        download_step(
+            facts,
+            InstallStatus::InstallingDependencies,
+            "Dependencies",
+            &urls,
+            REQUIREMENTS_DOWNLOAD_BYTES + TORCH_DOWNLOAD_BYTES,
+            &format!(
+                "Will install ComfyUI requirements and PyTorch ({})",
+                facts.torch_backend.label()
+            ),
+        )
+    });
+
+    let total_download_bytes: u64 = steps.iter().map(|s| s.download_bytes).sum();
+    let required_disk_bytes = total_download_bytes * DISK_PER_DOWNLOAD_BYTE;
+
+    let disk = match facts.free_disk_bytes {
+        Some(free) if free < required_disk_bytes => step(
+            InstallStatus::CheckingPrerequisites,
+            "Disk space",
+            StepReadiness::Blocked,
+            format!(
+                "Not enough disk space: {} MB free, {} MB needed",
+                free / MB,
+                required_disk_bytes / MB
+            ),
+            0,
+        ),
+        Some(free) => step(
+            InstallStatus::CheckingPrerequisites,
+            "Disk space",
+            StepReadiness::Done,
+            format!(
+                "{} MB free, {} MB needed",
+                free / MB,
+                required_disk_bytes / MB
+            ),
+            0,
+        ),
+        // Not a blocker: the install itself reports a full disk
+        None => step(
+            InstallStatus::CheckingPrerequisites,
+            "Disk space",
+            StepReadiness::Ready,
+            "Could not determine free disk space",
+            0,
+        ),
+    };
+    steps.insert(1, disk);
+
+    let can_install = steps.iter().all(|s| s.readiness != StepReadiness::Blocked);
+
+    PreflightReport {
+        steps,
+        total_download_bytes,
+        required_disk_bytes,
+        free_disk_bytes: facts.free_disk_bytes,
+        can_install,
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// CHECKS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Closest existing ancestor, so free space can be read before the install dir exists
+fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    path.ancestors().find(|p| p.exists()).map(Path::to_path_buf)
+}
+
+/// Free bytes on the volume holding `path`
+pub(crate) async fn free_disk_bytes(path: &Path) -> Option<u64> {
+    let dir = existing_ancestor(path)?;
+    let dir = dir.to_str()?;
+
+    #[cfg(windows)]
+    {
+        let script = format!(
+            "([System.IO.DriveInfo][System.IO.Path]::GetPathRoot('{}')).AvailableFreeSpace",
+            dir.replace('\'', "''")
+        );
+        let out = run_command("powershell", &["-NoProfile", "-Command", &script], None)
+            .await
+            .ok()?;
+        out.trim().parse().ok()
+    }
+
+    #[cfg(not(windows))]
+    {
+        // POSIX output: Filesystem 1024-blocks Used Available Capacity Mounted
+        let out = run_command("df", &["-Pk", dir], None).await.ok()?;
+        let kb: u64 = out
+            .lines()
+            .nth(1)?
+            .split_whitespace()
+            .nth(3)?
+            .parse()
+            .ok()?;
+        Some(kb * 1024)
+    }
+}
+
+/// URLs that do not answer; any HTTP response counts as reachable
+async fn unreachable_urls(urls: &[&str]) -> Vec<String> {
+    let client = match reqwest::Client::builder()
+        .timeout(REACHABILITY_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(_) => return urls.iter().map(|u| u.to_string()).collect(),
+    };
+
+    let checks = urls.iter().map(|url| {
+        let client = client.clone();
+        async move { (url, client.head(*url).send().await.is_ok()) }
+    });
+
+    futures_util::future::join_all(checks)
+        .await
+        .into_iter()
+        .filter(|(_, ok)| !ok)
+        .map(|(url, _)| url.to_string())
+        .collect()
+}
+
+/// Run the read-only checks for a preflight report
+pub async fn gather_facts() -> PreflightFacts {
+    let comfyui_dir = get_comfyui_dir();
+    let uv_installed = is_uv_installed().await;
+    let python_installed = is_python_installed().await;
+    let comfyui_installed = is_comfyui_installed().await;
+
+    let mut urls = Vec::new();
+    if !uv_installed {
+        urls.push(UV_INSTALL_URL);
+    }
+    if !python_installed {
+        urls.push(PYTHON_DOWNLOAD_URL);
+    }
+    if !comfyui_installed {
+        urls.push(COMFYUI_REPO_URL);
+    }
+    let torch_backend = tokio::task::spawn_blocking(detect_torch_backend)
+        .await
+        .unwrap_or_default();
+    if !(comfyui_installed && python_installed) {
+        urls.push(PYPI_INDEX_URL);
+        urls.extend(torch_backend.index_url());
+    }
+
+    PreflightFacts {
+        git_version: run_command("git", &["--version"], None).await.ok(),
+        free_disk_bytes: free_disk_bytes(&get_cinema_os_dir()).await,
+        unreachable: unreachable_urls(&urls).await,
+        uv_installed,
+        python_installed,
+        partial_venv: !python_installed && get_venv_dir().exists(),
+        comfyui_installed,
+        partial_comfyui: !comfyui_installed && comfyui_dir.exists(),
+        torch_backend,
+    }
+}
+
+/// Check what an installation would do, without changing anything
+pub async fn preflight_install() -> PreflightReport {
+    build_report(&gather_facts().await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fresh_machine() -> PreflightFacts {
+        PreflightFacts {
+            git_version: Some("git version 2.43.0\n".into()),
+            free_disk_bytes: Some(100 * 1024 * MB),
+            ..Default::default()
+        }
+    }
+
+    fn readiness(report: &PreflightReport) -> Vec<StepReadiness> {
+        report.steps.iter().map(|s| s.readiness.clone()).collect()
+    }
+
+    #[test]
+    fn test_fresh_install_downloads_everything() {
+        let report = build_report(&fresh_machine());
+
+        assert!(report.can_install);
+        assert_eq!(report.steps[0].detail, "git version 2.43.0");
+        assert_eq!(
+            readiness(&report),
+            [
+                StepReadiness::Done,
+                StepReadiness::Done,
+                StepReadiness::Ready,
+                StepReadiness::Ready,
+                StepReadiness::Ready,
+                StepReadiness::Ready,
+            ]
+        );
+        assert_eq!(
+            report.total_download_bytes,
+            UV_DOWNLOAD_BYTES
+                + PYTHON_DOWNLOAD_BYTES
+                + COMFYUI_CLONE_BYTES
+                + REQUIREMENTS_DOWNLOAD_BYTES
+                + TORCH_DOWNLOAD_BYTES
+        );
+        assert_eq!(report.required_disk_bytes, report.total_download_bytes * 2);
+    }
+
+    #[test]
+    fn test_complete_install_downloads_nothing() {
+        let report = build_report(&PreflightFacts {
+            uv_installed: true,
+            python_installed: true,
+            comfyui_installed: true,
+            ..fresh_machine()
+        });
+
+        assert!(report.can_install);
+        assert!(readiness(&report).iter().all(|r| *r == StepReadiness::Done));
+        assert_eq!(report.total_download_bytes, 0);
+    }
+
+    #[test]
+    fn test_blockers() {
+        let torch_url = TorchBackend::Rocm.index_url().unwrap();
+        let report = build_report(&PreflightFacts {
+            git_version: None,
+            unreachable: vec![torch_url.into()],
+            torch_backend: TorchBackend::Rocm,
+            partial_comfyui: true,
+            ..fresh_machine()
+        });
+
+        assert!(!report.can_install);
+        assert_eq!(
+            readiness(&report),
+            [
+                StepReadiness::Blocked,
+                StepReadiness::Done,
+                StepReadiness::Ready,
+                StepReadiness::Ready,
+                StepReadiness::Blocked,
+                StepReadiness::Blocked,
+            ]
+        );
+        assert!(report.steps[4]
+            .detail
+            .contains("Incomplete ComfyUI checkout"));
+        assert_eq!(
+            report.steps[5].detail,
+            format!("Cannot reach {}", torch_url)
+        );
+    }
+
+    #[test]
+    fn test_low_disk_space_blocks() {
+        let report = build_report(&PreflightFacts {
+            free_disk_bytes: Some(500 * MB),
+            ..fresh_machine()
+        });
+
+        assert!(!report.can_install);
+        assert_eq!(report.steps[1].name, "Disk space");
+        assert_eq!(report.steps[1].readiness, StepReadiness::Blocked);
+        assert!(report.steps[1]
+            .detail
+            .starts_with("Not enough disk space: 500 MB free"));
+
+        // Unknown free space is not a blocker
+        let report = build_report(&PreflightFacts {
+            free_disk_bytes: None,
+            ..fresh_machine()
+        });
+        assert!(report.can_install);
+    }
+}
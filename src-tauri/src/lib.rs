@@ -5,12 +5,18 @@ pub mod db;
 pub mod errors;
 pub mod graphics;
 pub mod installer;
+pub mod normalize;
 pub mod observability;
 pub mod pagination;
+pub mod request_log;
+pub mod scenes;
+pub mod settings;
 pub mod sync;
+pub mod telemetry;
 pub mod utils;
 pub mod vault;
 
+use crate::normalize::NormalizedScript;
 use crate::pagination::{PaginationResult, ScriptElement};
 
 #[tauri::command]
@@ -19,95 +25,250 @@ fn calculate_pagination(elements: Vec<ScriptElement>) -> PaginationResult {
     pagination::paginate_script(elements)
 }
 
+/// Paginate for A4, stage play or custom page geometry
+#[tauri::command]
+#[specta::specta]
+fn calculate_pagination_with_format(
+    elements: Vec<ScriptElement>,
+    format: pagination::PageFormat,
+) -> PaginationResult {
+    pagination::paginate_script_with_format(elements, &format)
+}
+
+/// Scene lengths in eighths of a page with runtime estimates (for scheduling)
+#[tauri::command]
+#[specta::specta]
+fn get_scene_breakdown(elements: Vec<ScriptElement>) -> Vec<pagination::SceneLength> {
+    pagination::page_metrics(&elements).scenes
+}
+
+/// Page count, scene lengths and runtime estimate (for the editor's stats)
+#[tauri::command]
+#[specta::specta]
+fn get_page_metrics(elements: Vec<ScriptElement>) -> pagination::PageMetrics {
+    pagination::page_metrics(&elements)
+}
+
+/// Split script elements into typed scenes (for scene reports and continuity)
+#[tauri::command]
+#[specta::specta]
+fn parse_scenes(elements: Vec<ScriptElement>) -> Vec<scenes::Scene> {
+    scenes::parse_scenes(&elements)
+}
+
+/// Canonicalize scene headings and transitions, returning a change log
+#[tauri::command]
+#[specta::specta]
+fn normalize_script(elements: Vec<ScriptElement>) -> NormalizedScript {
+    normalize::normalize_script(elements)
+}
+
 #[cfg(test)]
 mod tests;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
+/// Every command exposed to the frontend (the whole IPC surface)
+///
+/// `tests::bindings_tests` exports these to TypeScript so a type that specta
+/// can't represent fails `cargo test` instead of the frontend build.
+pub fn specta_builder() -> tauri_specta::Builder<tauri::Wry> {
+    tauri_specta::Builder::<tauri::Wry>::new().commands(tauri_specta::collect_commands![
+        commands::create_project,
+        commands::get_projects,
+        commands::save_script,
+        commands::load_script,
+        commands::search_script,
+        commands::preview_script_patch,
+        commands::apply_script_patch,
+        commands::undo_script_patch,
+        commands::get_characters,
+        commands::chat_with_agent,
+        calculate_pagination,
+        calculate_pagination_with_format,
+        get_scene_breakdown,
+        get_page_metrics,
+        parse_scenes,
+        normalize_script,
+        // AI Model Matrix commands
+        commands::ai::get_models,
+        commands::ai::get_models_for_task,
+        commands::ai::get_free_models,
+        commands::ai::get_hardware_capabilities,
+        commands::ai::route_request,
+        commands::ai::get_available_local_models,
+        commands::ai::get_pricing_config,
+        commands::ai::set_pricing_config,
+        commands::ai::clear_tts_cache,
+        commands::ai::list_voices,
+        commands::ai::stream_tts,
+        commands::ai::synthesize_dialogue,
+        commands::ai::generate_storyboard,
+        commands::ai::cancel_storyboard,
+        commands::ai::generate,
+        commands::ai::chat_stream,
+        commands::ai::get_generation_queue,
+        commands::ai::set_generation_queue_limits,
+        commands::ai::reprioritize_generation,
+        commands::ai::cancel_queued_generation,
+        commands::ai::enhance_prompt,
+        commands::ai::transcribe_media,
+        // Token/Vault commands
+        commands::tokens::create_token,
+        commands::tokens::get_tokens,
+        commands::tokens::list_tokens,
+        commands::tokens::search_tokens,
+        commands::tokens::link_tokens,
+        commands::tokens::get_related_tokens,
+        commands::tokens::get_token_graph,
+        commands::tokens::get_tokens_by_type,
+        commands::tokens::update_token,
+        commands::tokens::delete_token,
+        commands::tokens::add_token_visual,
+        commands::tokens::set_token_lora,
+        commands::tokens::export_training_dataset,
+        commands::tokens::submit_lora_training,
+        commands::tokens::get_token_contexts,
+        commands::tokens::extract_tokens_from_script,
+        commands::tokens::save_extracted_tokens,
+        // File I/O commands
+        commands::files::open_file_dialog,
+        commands::files::save_file_dialog,
+        commands::files::save_file_to_path,
+        commands::files::export_pdf_dialog,
+        commands::files::export_project_bundle,
+        commands::files::import_project_bundle,
+        commands::files::export_project_bundle_dialog,
+        commands::files::import_project_bundle_dialog,
+        commands::files::export_project,
+        commands::files::import_project,
+        commands::files::backup_vault,
+        commands::files::restore_vault,
+        // ComfyUI commands
+        commands::comfyui::get_comfyui_status,
+        commands::comfyui::install_comfyui,
+        commands::comfyui::start_comfyui,
+        commands::comfyui::stop_comfyui,
+        commands::comfyui::generate_image,
+        commands::comfyui::run_comfyui_workflow,
+        commands::comfyui::comfyui_interrupt,
+        commands::comfyui::comfyui_get_queue,
+        commands::comfyui::get_all_model_types,
+        commands::comfyui::reconfigure_comfyui,
+        commands::comfyui::get_comfyui_output_settings,
+        commands::comfyui::set_comfyui_output_settings,
+        commands::comfyui::get_project_assets,
+        commands::comfyui::list_assets,
+        commands::comfyui::run_generation,
+        commands::comfyui::get_generation_history,
+        commands::comfyui::reuse_generation_settings,
+        commands::comfyui::get_comfyui_stats,
+        commands::comfyui::execute_hybrid_generation,
+        //Installer commands
+        commands::installer::get_install_state,
+        commands::installer::is_system_ready,
+        commands::installer::run_installation,
+        commands::installer::preflight_install,
+        commands::installer::update_comfyui,
+        commands::installer::start_installed_comfyui,
+        commands::installer::stop_installed_comfyui,
+        commands::installer::comfyui_logs,
+        // Hardware detection
+        commands::installer::get_hardware_info,
+        commands::installer::get_all_model_recommendations,
+        commands::installer::get_recommended_models_for_hardware,
+        commands::installer::get_runnable_models_for_hardware,
+        // Model downloads
+        commands::installer::get_available_model_sources,
+        commands::installer::check_model_downloaded,
+        commands::installer::get_downloaded_model_ids,
+        commands::installer::download_model_by_id,
+        commands::installer::download_models_by_id,
+        commands::installer::check_disk_space_for,
+        commands::installer::check_ollama_installed,
+        commands::installer::get_ollama_model_list,
+        commands::installer::pull_ollama_model,
+        // Workflow generation
+        commands::workflow::generate_comfyui_workflow,
+        commands::workflow::generate_workflow_from_agent,
+        commands::workflow::get_model_params_defaults,
+        // Agent chat (full context + actions)
+        commands::agents::agent_chat_full,
+        commands::agents::cancel_agent_chat,
+        commands::agents::execute_agent_action,
+        commands::agents::execute_agent_actions,
+        commands::agents::route_message_to_agent,
+        commands::agents::get_routing_table,
+        commands::agents::set_routing_table,
+        commands::agents::get_fallback_config,
+        commands::agents::set_fallback_config,
+        commands::agents::get_response_cleanup,
+        commands::agents::set_response_cleanup,
+        commands::agents::get_project_memory,
+        commands::agents::remember_project_fact,
+        commands::agents::forget_project_fact,
+        commands::agents::get_agent_memory,
+        commands::agents::clear_agent_memory,
+        commands::agents::get_agent_memory_config,
+        commands::agents::set_agent_memory_config,
+        commands::agents::get_mcp_servers,
+        commands::agents::set_mcp_servers,
+        commands::agents::list_mcp_tools,
+        commands::agents::start_mcp_server,
+        commands::agents::stop_mcp_server,
+        commands::agents::get_agent_roles,
+        // AI Crew (new)
+        commands::crew::chat_with_crew,
+        commands::crew::get_crew_agents,
+        commands::crew::get_available_models,
+        // Settings
+        commands::settings::save_api_key,
+        commands::settings::get_api_key_status,
+        commands::settings::delete_api_key,
+        // Telemetry (opt-in, local only)
+        commands::telemetry::get_telemetry_settings,
+        commands::telemetry::set_telemetry_enabled,
+        commands::telemetry::record_generation_outcome,
+        commands::telemetry::get_generation_stats,
+        commands::telemetry::get_request_log,
+        commands::telemetry::get_cost_summary,
+        commands::sync::get_autosave_settings,
+        commands::sync::set_autosave_interval,
+        commands::sync::set_periodic_save_interval,
+        commands::sync::force_save,
+        commands::sync::get_cloud_sync_settings,
+        commands::sync::set_cloud_sync_endpoint,
+        commands::sync::sync_with_cloud,
+        commands::sync::edit_script,
+        commands::sync::set_canvas_node,
+        commands::sync::remove_canvas_node,
+        commands::sync::script_undo,
+        commands::sync::script_redo,
+        commands::sync::list_document_versions,
+        commands::sync::list_document_snapshots,
+        commands::sync::save_document_snapshot,
+        commands::sync::preview_document_version,
+        commands::sync::restore_document_version,
+        commands::sync::fork_document_at,
+    ])
+}
+
+/// TypeScript exporter settings for `src/bindings.ts`
+///
+/// 64-bit integers fail the export unless their field is exported as
+/// `#[specta(type = f64)]`; the values involved stay well below 2^53.
+pub fn typescript_exporter() -> specta_typescript::Typescript {
+    specta_typescript::Typescript::default()
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let builder =
-        tauri_specta::Builder::<tauri::Wry>::new().commands(tauri_specta::collect_commands![
-            commands::create_project,
-            commands::get_projects,
-            commands::save_script,
-            commands::load_script,
-            commands::get_characters,
-            commands::chat_with_agent,
-            calculate_pagination,
-            // AI Model Matrix commands
-            commands::ai::get_models,
-            commands::ai::get_models_for_task,
-            commands::ai::get_free_models,
-            commands::ai::get_hardware_capabilities,
-            commands::ai::route_request,
-            commands::ai::get_available_local_models,
-            // Token/Vault commands
-            commands::tokens::create_token,
-            commands::tokens::get_tokens,
-            commands::tokens::get_tokens_by_type,
-            commands::tokens::update_token,
-            commands::tokens::delete_token,
-            commands::tokens::add_token_visual,
-            commands::tokens::set_token_lora,
-            commands::tokens::get_token_contexts,
-            commands::tokens::extract_tokens_from_script,
-            commands::tokens::save_extracted_tokens,
-            // File I/O commands
-            commands::files::open_file_dialog,
-            commands::files::save_file_dialog,
-            commands::files::save_file_to_path,
-            commands::files::export_pdf_dialog,
-            // ComfyUI commands
-            commands::comfyui::get_comfyui_status,
-            commands::comfyui::install_comfyui,
-            commands::comfyui::start_comfyui,
-            commands::comfyui::stop_comfyui,
-            commands::comfyui::generate_image,
-            commands::comfyui::get_comfyui_stats,
-            //Installer commands
-            commands::installer::get_install_state,
-            commands::installer::is_system_ready,
-            commands::installer::run_installation,
-            // Hardware detection
-            commands::installer::get_hardware_info,
-            commands::installer::get_all_model_recommendations,
-            commands::installer::get_recommended_models_for_hardware,
-            commands::installer::get_runnable_models_for_hardware,
-            // Model downloads
-            commands::installer::get_available_model_sources,
-            commands::installer::check_model_downloaded,
-            commands::installer::get_downloaded_model_ids,
-            commands::installer::download_model_by_id,
-            commands::installer::check_ollama_installed,
-            commands::installer::get_ollama_model_list,
-            commands::installer::pull_ollama_model,
-            // Workflow generation
-            commands::workflow::generate_comfyui_workflow,
-            commands::workflow::generate_workflow_from_agent,
-            // Agent chat (full context + actions)
-            commands::agents::agent_chat_full,
-            commands::agents::execute_agent_action,
-            commands::agents::execute_agent_actions,
-            commands::agents::route_message_to_agent,
-            commands::agents::get_agent_roles,
-            // AI Crew (new)
-            commands::crew::chat_with_crew,
-            commands::crew::get_crew_agents,
-            commands::crew::get_available_models,
-            // Settings
-            commands::settings::save_api_key,
-            commands::settings::get_api_key_status,
-            commands::settings::delete_api_key,
-        ]);
+    let builder = specta_builder();
 
     #[cfg(debug_assertions)]
     // builder
-    //    .export(
-    //        specta_typescript::Typescript::default(),
-    //        "../src/bindings.ts",
-    //    )
+    //    .export(typescript_exporter(), "../src/bindings.ts")
     //    .expect("Failed to export typescript bindings");
     tauri::Builder::default()
         .setup(|_app| {
@@ -133,6 +294,25 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .invoke_handler(builder.invoke_handler())
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| match event {
+            // Persist the sync document when the app goes to the background or quits
+            tauri::RunEvent::WindowEvent {
+                event: tauri::WindowEvent::Focused(false),
+                ..
+            } => {
+                tauri::async_runtime::spawn(save_sync_document());
+            }
+            tauri::RunEvent::ExitRequested { .. } => {
+                tauri::async_runtime::block_on(save_sync_document());
+            }
+            _ => {}
+        });
+}
+
+async fn save_sync_document() {
+    if let Err(e) = sync::force_save().await {
+        eprintln!("❌ Failed to save sync document: {}", e);
+    }
 }
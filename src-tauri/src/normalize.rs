@@ -0,0 +1,256 @@
+//! Script normalization - canonical scene headings and transitions
+//!
+//! Imported and agent-written scripts mix heading styles ("Int office - day",
+//! "INT. OFFICE—DAY"). Consistent headings keep pagination and token
+//! extraction accurate, so this rewrites them to `INT./EXT. LOCATION - TIME`
+//! and transitions to uppercase `... TO:`, logging every change.
+
+use serde::{Deserialize, Serialize};
+
+use crate::pagination::ScriptElement;
+
+/// Heading prefixes as written, longest first, with their canonical form
+const HEADING_PREFIXES: &[(&str, &str)] = &[
+    ("INT./EXT.", "INT./EXT."),
+    ("INT./EXT", "INT./EXT."),
+    ("INT/EXT.", "INT./EXT."),
+    ("INT/EXT", "INT./EXT."),
+    ("EXT./INT.", "EXT./INT."),
+    ("EXT./INT", "EXT./INT."),
+    ("EXT/INT.", "EXT./INT."),
+    ("EXT/INT", "EXT./INT."),
+    ("INTERIOR", "INT."),
+    ("EXTERIOR", "EXT."),
+    ("I/E.", "INT./EXT."),
+    ("I/E", "INT./EXT."),
+    ("INT.", "INT."),
+    ("EXT.", "EXT."),
+    ("INT", "INT."),
+    ("EXT", "EXT."),
+];
+
+/// Transitions that take a `TO:` even when written without one
+const BARE_TRANSITIONS: &[&str] = &["CUT", "DISSOLVE", "SMASH CUT", "MATCH CUT", "JUMP CUT"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, specta::Type)]
+pub struct ScriptChange {
+    /// Index of the element in the input
+    #[specta(type = f64)]
+    pub index: usize,
+    pub element_type: String,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, specta::Type)]
+pub struct NormalizedScript {
+    pub elements: Vec<ScriptElement>,
+    pub changes: Vec<ScriptChange>,
+    #[specta(type = f64)]
+    pub change_count: usize,
+}
+
+pub(crate) fn is_scene_heading(element_type: &str) -> bool {
+    matches!(
+        element_type,
+        "scene-heading" | "scene_heading" | "screenplay-scene-heading"
+    )
+}
+
+/// Normalize scene headings and transitions, leaving other elements untouched
+pub fn normalize_script(elements: Vec<ScriptElement>) -> NormalizedScript {
+    let mut changes = Vec::new();
+
+    let elements: Vec<ScriptElement> = elements
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut element)| {
+            let normalized = match element.r#type.as_str() {
+                t if is_scene_heading(t) => normalize_heading(&element.text),
+                "transition" => normalize_transition(&element.text),
+                _ => return element,
+            };
+
+            if normalized != element.text {
+                changes.push(ScriptChange {
+                    index,
+                    element_type: element.r#type.clone(),
+                    before: std::mem::replace(&mut element.text, normalized.clone()),
+                    after: normalized,
+                });
+            }
+            element
+        })
+        .collect();
+
+    NormalizedScript {
+        elements,
+        change_count: changes.len(),
+        changes,
+    }
+}
+
+/// Rewrite a heading as `INT./EXT. LOCATION - TIME`
+///
+/// Headings without a recognizable prefix are uppercased with their dashes
+/// fixed but otherwise left as written.
+pub fn normalize_heading(text: &str) -> String {
+    let upper = collapse_whitespace(text).to_uppercase();
+
+    let (prefix, rest) = match split_prefix(&upper) {
+        Some((prefix, rest)) => (Some(prefix), rest),
+        None => (None, upper.as_str()),
+    };
+
+    let body = split_on_dashes(rest).join(" - ");
+    match (prefix, body.is_empty()) {
+        (Some(prefix), true) => prefix.to_string(),
+        (Some(prefix), false) => format!("{} {}", prefix, body),
+        (None, _) => body,
+    }
+}
+
+/// Uppercase a transition, ending `CUT TO`-style transitions in `TO:`
+pub fn normalize_transition(text: &str) -> String {
+    let upper = collapse_whitespace(text).to_uppercase();
+    let bare = upper.trim_end_matches(|c: char| c == ':' || c == '.' || c.is_whitespace());
+
+    if bare.ends_with(" TO") || bare == "TO" {
+        format!("{}:", bare)
+    } else if BARE_TRANSITIONS.contains(&bare) {
+        format!("{} TO:", bare)
+    } else {
+        upper
+    }
+}
+
+/// Split a known INT/EXT prefix off an uppercased heading
+pub(crate) fn split_prefix(heading: &str) -> Option<(&'static str, &str)> {
+    HEADING_PREFIXES.iter().find_map(|(raw, canonical)| {
+        let rest = heading.strip_prefix(raw)?;
+        // "INT" must not match the start of "INTERNATIONAL"
+        if rest.starts_with(|c: char| c.is_alphanumeric()) {
+            return None;
+        }
+        Some((
+            *canonical,
+            rest.trim_start_matches(|c: char| c == '.' || c.is_whitespace()),
+        ))
+    })
+}
+
+/// Split location/time segments on em/en dashes, `--`, or a hyphen next to a space
+///
+/// Hyphens inside words ("SEMI-TRUCK") are kept.
+pub(crate) fn split_on_dashes(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut segments = Vec::new();
+    let mut current = String::new();
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let is_separator = match c {
+            '—' | '–' => true,
+            '-' => {
+                let doubled = chars.get(i + 1) == Some(&'-');
+                let spaced = i == 0
+                    || chars[i - 1].is_whitespace()
+                    || chars.get(i + 1).is_none_or(|n| n.is_whitespace());
+                doubled || spaced
+            }
+            _ => false,
+        };
+
+        if is_separator {
+            segments.push(std::mem::take(&mut current));
+            while chars
+                .get(i + 1)
+                .is_some_and(|n| matches!(n, '-' | '—' | '–'))
+            {
+                i += 1;
+            }
+        } else {
+            current.push(c);
+        }
+        i += 1;
+    }
+    segments.push(current);
+
+    segments
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+pub(crate) fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(r#type: &str, text: &str) -> ScriptElement {
+        ScriptElement {
+            r#type: r#type.into(),
+            text: text.into(),
+            scene_number: None,
+        }
+    }
+
+    #[test]
+    fn test_malformed_headings() {
+        let cases = [
+            ("Int office - day", "INT. OFFICE - DAY"),
+            ("INT. OFFICE—DAY", "INT. OFFICE - DAY"),
+            ("int.office -- night", "INT. OFFICE - NIGHT"),
+            ("I/E car – moving", "INT./EXT. CAR - MOVING"),
+            (
+                "int/ext  Kitchen -  Continuous",
+                "INT./EXT. KITCHEN - CONTINUOUS",
+            ),
+            (
+                "Exterior semi-truck stop - day",
+                "EXT. SEMI-TRUCK STOP - DAY",
+            ),
+            ("EXT. ROOF - NIGHT -", "EXT. ROOF - NIGHT"),
+            ("exterior beach — dawn — later", "EXT. BEACH - DAWN - LATER"),
+            ("International airport - day", "INTERNATIONAL AIRPORT - DAY"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(normalize_heading(input), expected, "input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_transitions() {
+        assert_eq!(normalize_transition("cut to"), "CUT TO:");
+        assert_eq!(normalize_transition("Dissolve to."), "DISSOLVE TO:");
+        assert_eq!(normalize_transition("smash cut"), "SMASH CUT TO:");
+        assert_eq!(normalize_transition("fade out."), "FADE OUT.");
+    }
+
+    #[test]
+    fn test_normalize_script_logs_changes() {
+        let result = normalize_script(vec![
+            element("scene-heading", "Int office - day"),
+            element("action", "int office - day is not a heading here."),
+            element("scene-heading", "EXT. PARK - NIGHT"),
+            element("transition", "cut to"),
+        ]);
+
+        assert_eq!(result.change_count, 2);
+        assert_eq!(result.changes[0].index, 0);
+        assert_eq!(result.changes[0].before, "Int office - day");
+        assert_eq!(result.changes[1].after, "CUT TO:");
+        assert_eq!(result.elements[0].text, "INT. OFFICE - DAY");
+        assert_eq!(
+            result.elements[1].text,
+            "int office - day is not a heading here."
+        );
+        assert_eq!(result.elements[2].text, "EXT. PARK - NIGHT");
+    }
+}
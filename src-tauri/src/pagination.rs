@@ -1,17 +1,48 @@
+//! Screenplay pagination
+//!
+//! A character cue and the parentheticals and dialogue under it form one
+//! block. Blocks only break across pages between dialogue lines, with
+//! "(MORE)" at the bottom and "NAME (CONT'D)" at the top of the next page. A
+//! cue ending in `^` (Fountain) is the right column of a dual dialogue with
+//! the block before it; dual dialogue never breaks.
+
 use serde::{Deserialize, Serialize};
 
+use crate::normalize::is_scene_heading;
+use crate::scenes::parse_cue;
+
 // Constants mirroring the industry constraints (Courier Prime 12pt @ 72dpi equivalent)
 // 10 chars per inch. 6 lines per inch.
 const LINES_PER_PAGE: usize = 54;
 // const PAGE_WIDTH_CHARS: usize = 60; // Standard Action width roughly (unused)
+const A4_LINES_PER_PAGE: usize = 58; // 0.7" taller than Letter
 
 // Element Margins/Widths (in characters, based on left-margin + width)
 // Simplification: We only care about the max characters per line for wrapping.
 const ACTION_WIDTH: usize = 60;
-const DIALOGUE_WIDTH: usize = 35;
-const PARENTHETICAL_WIDTH: usize = 20; // 2.0" width strict
-const CHARACTER_WIDTH: usize = 38;
-const TRANSITION_WIDTH: usize = 15; // Right aligned usually, but width constraint applies
+const A4_ACTION_WIDTH: usize = 58; // 0.2" narrower than Letter
+const SCREENPLAY_MARGINS: ElementMargins = ElementMargins {
+    dialogue: 25,      // 3.5" wide
+    parenthetical: 40, // 2.0" width strict
+    character: 22,
+    transition: 45, // Right aligned usually, but width constraint applies
+};
+// Stage plays run dialogue across the page and indent directions instead
+const STAGE_PLAY_MARGINS: ElementMargins = ElementMargins {
+    dialogue: 0,
+    parenthetical: 30,
+    character: 22,
+    transition: 45,
+};
+
+/// Dialogue lines a split must leave on each page
+const MIN_SPLIT_LINES: usize = 2;
+
+/// Lines on a US Letter page as schedules count them
+const SCHEDULE_LINES_PER_PAGE: f64 = 55.0;
+// One page per minute, slower for action and faster for dialogue
+const ACTION_MINUTES_PER_PAGE: f64 = 1.2;
+const DIALOGUE_MINUTES_PER_PAGE: f64 = 0.85;
 
 #[derive(Serialize, Deserialize, Debug, Clone, specta::Type)]
 pub struct ScriptElement {
@@ -22,115 +53,618 @@ pub struct ScriptElement {
 
 #[derive(Serialize, Deserialize, Debug, Clone, specta::Type)]
 pub struct PageBreak {
-    pub line_index: usize, // Index in the list of calculated lines where the break occurs
+    #[specta(type = f64)]
+    pub line_index: usize, // Index of the element the new page starts with
+    #[specta(type = f64)]
     pub page_number: usize,
     pub scene_split: bool, // If a scene was split across pages
+    /// Lines of that element already on the previous page (0 unless a dialogue split)
+    #[specta(type = f64)]
+    pub element_line: usize,
+    /// Speaker of a dialogue split by this break: the previous page ends with
+    /// "(MORE)" and this one starts with "NAME (CONT'D)"
+    pub continued_speaker: Option<String>,
+}
+
+/// Length of one scene for scheduling
+#[derive(Serialize, Deserialize, Debug, Clone, specta::Type)]
+pub struct SceneLength {
+    /// 1-based scene number
+    #[specta(type = f64)]
+    pub number: usize,
+    pub heading: String,
+    /// Rounded to the nearest eighth of a page, at least 1
+    #[specta(type = f64)]
+    pub eighths: usize,
+    /// e.g. "2 3/8"
+    pub page_count: String,
+    pub runtime_estimate_minutes: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, specta::Type)]
+pub struct PageMetrics {
+    #[specta(type = f64)]
+    pub total_pages: usize,
+    pub scenes: Vec<SceneLength>,
+    pub runtime_estimate_minutes: f64,
+}
+
+/// Characters an element gives up from the action width (left plus right margin)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, specta::Type)]
+pub struct ElementMargins {
+    #[specta(type = f64)]
+    pub dialogue: usize,
+    #[specta(type = f64)]
+    pub parenthetical: usize,
+    #[specta(type = f64)]
+    pub character: usize,
+    #[specta(type = f64)]
+    pub transition: usize,
+}
+
+/// Page geometry in Courier lines and characters
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, specta::Type)]
+pub struct PageFormat {
+    #[specta(type = f64)]
+    pub lines_per_page: usize,
+    /// Width of action lines
+    #[specta(type = f64)]
+    pub chars_per_line: usize,
+    pub margins: ElementMargins,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, specta::Type)]
+pub enum PageFormatPreset {
+    #[default]
+    UsLetterScreenplay,
+    A4Screenplay,
+    StagePlay,
+}
+
+impl PageFormatPreset {
+    pub fn format(self) -> PageFormat {
+        match self {
+            Self::UsLetterScreenplay => PageFormat {
+                lines_per_page: LINES_PER_PAGE,
+                chars_per_line: ACTION_WIDTH,
+                margins: SCREENPLAY_MARGINS,
+            },
+            Self::A4Screenplay => PageFormat {
+                lines_per_page: A4_LINES_PER_PAGE,
+                chars_per_line: A4_ACTION_WIDTH,
+                margins: SCREENPLAY_MARGINS,
+            },
+            Self::StagePlay => PageFormat {
+                lines_per_page: LINES_PER_PAGE,
+                chars_per_line: ACTION_WIDTH,
+                margins: STAGE_PLAY_MARGINS,
+            },
+        }
+    }
+}
+
+impl Default for PageFormat {
+    fn default() -> Self {
+        PageFormatPreset::default().format()
+    }
+}
+
+impl PageFormat {
+    /// Characters per line for an element type
+    pub fn width(&self, element_type: &str) -> usize {
+        let margin = match element_type {
+            "dialogue" => self.margins.dialogue,
+            "parenthetical" => self.margins.parenthetical,
+            "character" => self.margins.character,
+            "transition" => self.margins.transition,
+            _ => 0,
+        };
+        // A margin wider than the page still leaves room for a word
+        self.chars_per_line.saturating_sub(margin).max(1)
+    }
+
+    /// Lines an element takes once soft-wrapped to its width
+    pub fn lines_for(&self, element: &ScriptElement) -> usize {
+        // Strict wrapping logic
+        // We treat newlines in text as forced breaks
+
+        // We also need to add top-margin based on element type (e.g. Dialogue has 0 if prev is Character, etc.)
+        // But this function just calculates the "visual height" of the element itself text-wise.
+        // The spacer logic will handle the gaps between elements.
+
+        // Textwrap is great, but we want strict character count wrapping for Courier.
+        // textwrap::wrap uses sophisticated algos, but basic is fine.
+        let wrapped = textwrap::wrap(element.text.as_str(), self.width(&element.r#type));
+
+        // Ensure at least 1 line if empty
+        wrapped.len().max(1)
+    }
+}
+
+/// Where an element's first line is printed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, specta::Type)]
+pub struct ElementPosition {
+    #[specta(type = f64)]
+    pub page: usize,
+    /// Line on the page, from 0
+    #[specta(type = f64)]
+    pub line: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, specta::Type)]
 pub struct PaginationResult {
     pub pages: Vec<PageBreak>,
+    #[specta(type = f64)]
     pub total_pages: usize,
+    /// One per input element, in order
+    pub positions: Vec<ElementPosition>,
 }
 
+/// Lines an element takes on a US Letter screenplay page
 pub fn calculate_lines_for_element(element: &ScriptElement) -> usize {
-    let width = match element.r#type.as_str() {
-        "dialogue" => DIALOGUE_WIDTH,
-        "parenthetical" => PARENTHETICAL_WIDTH,
-        "character" => CHARACTER_WIDTH,
-        "transition" => TRANSITION_WIDTH,
-        _ => ACTION_WIDTH,
-    };
+    PageFormat::default().lines_for(element)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RowKind {
+    Cue,
+    Parenthetical,
+    Dialogue,
+    Other,
+}
 
-    // Strict wrapping logic
-    // We treat newlines in text as forced breaks
+/// One printed line of an element
+#[derive(Debug, Clone, Copy)]
+struct Row {
+    element: usize,
+    /// Line within the element
+    line: usize,
+    kind: RowKind,
+}
 
-    // We also need to add top-margin based on element type (e.g. Dialogue has 0 if prev is Character, etc.)
-    // But this function just calculates the "visual height" of the element itself text-wise.
-    // The spacer logic will handle the gaps between elements.
+/// Elements that go on a page together
+struct Block {
+    rows: Vec<Row>,
+    /// Right column of a dual dialogue
+    right: Vec<Row>,
+    /// Set for dialogue blocks
+    speaker: Option<String>,
+    heading: bool,
+}
+
+impl Block {
+    fn height(&self) -> usize {
+        self.rows.len().max(self.right.len())
+    }
 
-    // Textwrap is great, but we want strict character count wrapping for Courier.
-    // textwrap::wrap uses sophisticated algos, but basic is fine.
-    let wrapped = textwrap::wrap(element.text.as_str(), width);
-    let mut total_lines = wrapped.len();
+    /// Rows to keep on this page before "(MORE)" when `available` lines are left
+    fn split_point(&self, available: usize) -> Option<usize> {
+        if self.speaker.is_none() || !self.right.is_empty() {
+            return None;
+        }
+        let dialogue_lines =
+            |rows: &[Row]| rows.iter().filter(|r| r.kind == RowKind::Dialogue).count();
 
-    // Ensure at least 1 line if empty?
-    if total_lines == 0 {
-        total_lines = 1;
+        // "(MORE)" takes a line
+        let max = available.checked_sub(1)?.min(self.rows.len());
+        (1..max).rev().find(|&split| {
+            let (before, after) = self.rows.split_at(split);
+            // A parenthetical never ends a page; it moves with its dialogue
+            before.last().map(|r| r.kind) == Some(RowKind::Dialogue)
+                && dialogue_lines(before) >= MIN_SPLIT_LINES
+                && dialogue_lines(after) >= MIN_SPLIT_LINES
+        })
     }
+}
 
-    total_lines
+fn element_rows(
+    elements: &[ScriptElement],
+    range: std::ops::Range<usize>,
+    format: &PageFormat,
+) -> Vec<Row> {
+    range
+        .flat_map(|element| {
+            let kind = match elements[element].r#type.as_str() {
+                "character" => RowKind::Cue,
+                "parenthetical" => RowKind::Parenthetical,
+                "dialogue" => RowKind::Dialogue,
+                _ => RowKind::Other,
+            };
+            (0..format.lines_for(&elements[element])).map(move |line| Row {
+                element,
+                line,
+                kind,
+            })
+        })
+        .collect()
 }
 
-pub fn paginate_script(elements: Vec<ScriptElement>) -> PaginationResult {
-    let mut current_line = 0;
-    let mut current_page = 1;
-    let mut page_breaks = Vec::new();
-
-    // Basic heuristic:
-    // Page 1 starts at line 1.
-    // We break at line 54.
-
-    let mut prev_type = "";
-
-    for (i, element) in elements.iter().enumerate() {
-        // 1. Calculate spacing before this element
-        let mut spacing = 1; // Default double space (1 empty line)
-        if i == 0 {
-            spacing = 0;
-        } // Top of script
-
-        // Compact rules
-        if prev_type == "character" && element.r#type == "dialogue" {
-            spacing = 0;
+fn build_blocks(elements: &[ScriptElement], format: &PageFormat) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut i = 0;
+
+    while i < elements.len() {
+        let element = &elements[i];
+        if element.r#type != "character" {
+            blocks.push(Block {
+                rows: element_rows(elements, i..i + 1, format),
+                right: Vec::new(),
+                speaker: None,
+                heading: is_scene_heading(&element.r#type),
+            });
+            i += 1;
+            continue;
         }
-        if prev_type == "dialogue" && element.r#type == "parenthetical" {
-            spacing = 0;
+
+        let start = i;
+        i += 1;
+        while i < elements.len()
+            && matches!(elements[i].r#type.as_str(), "parenthetical" | "dialogue")
+        {
+            i += 1;
         }
-        if prev_type == "parenthetical" && element.r#type == "dialogue" {
-            spacing = 0;
+        let rows = element_rows(elements, start..i, format);
+
+        let dual = element.text.trim_end().ends_with('^');
+        match blocks.last_mut() {
+            Some(left) if dual && left.speaker.is_some() && left.right.is_empty() => {
+                left.right = rows;
+            }
+            _ => blocks.push(Block {
+                rows,
+                right: Vec::new(),
+                speaker: Some(parse_cue(&element.text).0),
+                heading: false,
+            }),
         }
+    }
 
-        // Apply spacing
-        current_line += spacing;
-
-        // Check for page break during spacing
-        if current_line >= LINES_PER_PAGE {
-            current_page += 1;
-            current_line = 0; // Reset to top
-            page_breaks.push(PageBreak {
-                line_index: i, // Roughly breaking before this element
-                page_number: current_page,
-                scene_split: false,
-            });
+    blocks
+}
+
+/// Pages laid out so far
+struct Layout {
+    page: usize,
+    line: usize,
+    breaks: Vec<PageBreak>,
+    positions: Vec<ElementPosition>,
+}
+
+impl Layout {
+    /// Print `rows` from the current line, which stays put
+    fn place(&mut self, rows: &[Row]) {
+        for (offset, row) in rows.iter().enumerate() {
+            if row.line == 0 {
+                self.positions[row.element] = ElementPosition {
+                    page: self.page,
+                    line: self.line + offset,
+                };
+            }
+        }
+    }
+
+    fn place_block(&mut self, block: &Block) {
+        self.place(&block.rows);
+        self.place(&block.right);
+        self.line += block.height();
+    }
+
+    /// Start a new page with `first`
+    fn break_before(&mut self, first: Row, scene_split: bool, continued_speaker: Option<String>) {
+        self.page += 1;
+        self.line = 0;
+        self.breaks.push(PageBreak {
+            line_index: first.element,
+            page_number: self.page,
+            scene_split,
+            element_line: first.line,
+            continued_speaker,
+        });
+    }
+}
+
+/// Paginate for US Letter screenplay pages
+pub fn paginate_script(elements: Vec<ScriptElement>) -> PaginationResult {
+    paginate_script_with_format(elements, &PageFormat::default())
+}
+
+pub fn paginate_script_with_format(
+    elements: Vec<ScriptElement>,
+    format: &PageFormat,
+) -> PaginationResult {
+    // A break before anything but a heading splits the scene it falls in
+    let scenes = crate::scenes::parse_scenes(&elements);
+    let splits_scene = |row: Row| {
+        scenes.iter().any(|scene| {
+            let after_start = scene.start_index < row.element
+                || (scene.start_index == row.element && row.line > 0);
+            after_start && row.element < scene.end_index
+        })
+    };
+
+    let blocks = build_blocks(&elements, format);
+    let mut layout = Layout {
+        page: 1,
+        line: 0,
+        breaks: Vec::new(),
+        positions: vec![ElementPosition { page: 1, line: 0 }; elements.len()],
+    };
+
+    for (i, block) in blocks.iter().enumerate() {
+        // One blank line between blocks, none at the top of a page
+        let spacing = usize::from(layout.line > 0);
+        let available = format.lines_per_page.saturating_sub(layout.line + spacing);
+
+        // A heading keeps the start of what follows it on its page
+        let keep_with_next = match blocks.get(i + 1) {
+            Some(next) if block.heading => 1 + next.height().min(MIN_SPLIT_LINES),
+            _ => 0,
+        };
+        if block.height() + keep_with_next <= available {
+            layout.line += spacing;
+            layout.place_block(block);
+            continue;
         }
 
-        // 2. Calculate element height
-        let lines = calculate_lines_for_element(element);
-
-        // Check if element fits
-        if current_line + lines > LINES_PER_PAGE {
-            // Element doesn't fit mostly.
-            // Rule: Scene Headers should NOT be at the very bottom (orphan).
-            // Rule: Character names should NOT be at the bottom without dialogue.
-
-            // Force break
-            current_page += 1;
-            current_line = lines; // Starts fresh on next page
-            page_breaks.push(PageBreak {
-                line_index: i,
-                page_number: current_page,
-                scene_split: false, // simplified
-            });
-        } else {
-            current_line += lines;
+        if let Some(split) = block.split_point(available) {
+            let (before, after) = block.rows.split_at(split);
+            layout.line += spacing;
+            layout.place(before);
+            layout.break_before(after[0], splits_scene(after[0]), block.speaker.clone());
+            // "NAME (CONT'D)"
+            layout.line = 1;
+            layout.place(after);
+            layout.line += after.len();
+            continue;
         }
 
-        prev_type = &element.r#type;
+        // A block taller than a page overflows rather than breaking forever
+        if layout.line > 0 {
+            layout.break_before(block.rows[0], splits_scene(block.rows[0]), None);
+        }
+        layout.place_block(block);
     }
 
     PaginationResult {
-        pages: page_breaks,
-        total_pages: current_page,
+        pages: layout.breaks,
+        total_pages: layout.page,
+        positions: layout.positions,
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PAGE METRICS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Eighths as a schedule writes them: "3/8", "1", "2 3/8"
+pub fn format_eighths(eighths: usize) -> String {
+    match (eighths / 8, eighths % 8) {
+        (0, rest) => format!("{}/8", rest),
+        (pages, 0) => pages.to_string(),
+        (pages, rest) => format!("{} {}/8", pages, rest),
+    }
+}
+
+/// Lines and dialogue lines of `elements` on US Letter (as schedules count), without page breaks
+fn measure(elements: &[ScriptElement]) -> (usize, usize) {
+    let blocks = build_blocks(elements, &PageFormat::default());
+    let spacing = blocks.len().saturating_sub(1);
+    let lines = blocks.iter().map(Block::height).sum::<usize>() + spacing;
+    let dialogue = blocks
+        .iter()
+        .filter(|block| block.speaker.is_some())
+        .map(Block::height)
+        .sum();
+    (lines, dialogue)
+}
+
+/// Minutes for `lines` of which `dialogue` are dialogue blocks
+fn runtime_minutes(lines: usize, dialogue: usize) -> f64 {
+    if lines == 0 {
+        return 0.0;
+    }
+    let pages = lines as f64 / SCHEDULE_LINES_PER_PAGE;
+    let dialogue_share = dialogue as f64 / lines as f64;
+    pages
+        * (DIALOGUE_MINUTES_PER_PAGE * dialogue_share
+            + ACTION_MINUTES_PER_PAGE * (1.0 - dialogue_share))
+}
+
+/// Page count, scene lengths in eighths and runtime estimate
+pub fn page_metrics(elements: &[ScriptElement]) -> PageMetrics {
+    let scenes = crate::scenes::parse_scenes(elements)
+        .into_iter()
+        .map(|scene| {
+            let (lines, dialogue) = measure(&elements[scene.start_index..scene.end_index]);
+            let eighths = (lines as f64 * 8.0 / SCHEDULE_LINES_PER_PAGE).round() as usize;
+            SceneLength {
+                number: scene.number,
+                heading: scene.heading,
+                eighths: eighths.max(1),
+                page_count: format_eighths(eighths.max(1)),
+                runtime_estimate_minutes: runtime_minutes(lines, dialogue),
+            }
+        })
+        .collect();
+
+    let (lines, dialogue) = measure(elements);
+    PageMetrics {
+        total_pages: paginate_script(elements.to_vec()).total_pages,
+        scenes,
+        runtime_estimate_minutes: runtime_minutes(lines, dialogue),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(r#type: &str, text: &str) -> ScriptElement {
+        ScriptElement {
+            r#type: r#type.into(),
+            text: text.into(),
+            scene_number: None,
+        }
+    }
+
+    #[test]
+    fn test_breaks_inside_scenes_are_scene_splits() {
+        // 30 one-line actions per scene: the first break lands mid-scene
+        let mut elements = Vec::new();
+        for heading in ["INT. BAR - NIGHT", "EXT. ROOF - NIGHT"] {
+            elements.push(element("scene-heading", heading));
+            elements.extend((0..30).map(|_| element("action", "Rain.")));
+        }
+
+        let result = paginate_script(elements);
+        assert_eq!(result.total_pages, 3);
+        assert!(result.pages.iter().all(|page| page.scene_split));
+        assert!(result.pages.iter().all(|page| page.line_index != 31));
+    }
+
+    /// Actions filling a page down to `line`, which must be even
+    fn filler(line: usize) -> Vec<ScriptElement> {
+        let mut elements = vec![element(
+            "action",
+            "The bar is empty except for the piano player and a man in a wet coat.",
+        )];
+        elements.extend((0..(line - 2) / 2).map(|_| element("action", "Rain.")));
+        elements
+    }
+
+    #[test]
+    fn test_dialogue_split_keeps_parenthetical_with_its_line() {
+        let mut elements = filler(48);
+        let cue = elements.len();
+        elements.extend([
+            element("character", "MARLOWE (V.O.)"),
+            element(
+                "dialogue",
+                "I told you already, twice tonight, and I won't say it again.",
+            ),
+            element("parenthetical", "(beat)"),
+            element(
+                "dialogue",
+                "She never came back to the bar. Not that night, not the next, not in all the \
+                 years the piano kept playing the same sad song for nobody.",
+            ),
+        ]);
+
+        let result = paginate_script(elements);
+        assert_eq!(result.total_pages, 2);
+        // "(MORE)" would fit after the parenthetical, but it moves down instead
+        let split = &result.pages[0];
+        assert_eq!(split.line_index, cue + 2);
+        assert_eq!(split.element_line, 0);
+        assert_eq!(split.continued_speaker.as_deref(), Some("MARLOWE"));
+        assert_eq!(result.positions[cue], ElementPosition { page: 1, line: 49 });
+        assert_eq!(
+            result.positions[cue + 1],
+            ElementPosition { page: 1, line: 50 }
+        );
+        assert_eq!(
+            result.positions[cue + 2],
+            ElementPosition { page: 2, line: 1 }
+        );
+        assert_eq!(
+            result.positions[cue + 3],
+            ElementPosition { page: 2, line: 2 }
+        );
+    }
+
+    #[test]
+    fn test_dual_dialogue_moves_as_one_block() {
+        let mut elements = filler(48);
+        let left = elements.len();
+        elements.extend([
+            element("character", "MARLOWE"),
+            element(
+                "dialogue",
+                "Where were you? I waited all night, and the piano player asked about you \
+                 twice before closing.",
+            ),
+            element("character", "VIVIAN ^"),
+            element(
+                "dialogue",
+                "Out. Walking. Thinking about you, about us, about the rain and whether \
+                 it will ever stop falling on this town. You never asked me where I go when \
+                 the music stops.",
+            ),
+        ]);
+
+        let result = paginate_script(elements);
+        assert_eq!(result.total_pages, 2);
+        assert_eq!(result.pages[0].line_index, left);
+        assert_eq!(result.pages[0].continued_speaker, None);
+        // Both columns start on the same line
+        assert_eq!(result.positions[left], ElementPosition { page: 2, line: 0 });
+        assert_eq!(
+            result.positions[left + 2],
+            ElementPosition { page: 2, line: 0 }
+        );
+        assert_eq!(
+            result.positions[left + 3],
+            ElementPosition { page: 2, line: 1 }
+        );
+    }
+
+    #[test]
+    fn test_scene_lengths_in_eighths() {
+        assert_eq!(format_eighths(3), "3/8");
+        assert_eq!(format_eighths(16), "2");
+        assert_eq!(format_eighths(19), "2 3/8");
+
+        let mut elements = vec![element("scene-heading", "INT. BAR - NIGHT")];
+        elements.extend((0..27).map(|_| element("action", "Rain.")));
+        elements.push(element("scene-heading", "EXT. ROOF - NIGHT"));
+        elements.push(element("action", "Wind."));
+        for _ in 0..9 {
+            elements.push(element("character", "MARLOWE"));
+            elements.push(element("dialogue", "Cold."));
+        }
+
+        let metrics = page_metrics(&elements);
+        assert_eq!(metrics.total_pages, 2);
+        // 55 lines: heading, 27 actions and the blank lines between them
+        assert_eq!(metrics.scenes[0].eighths, 8);
+        assert_eq!(metrics.scenes[0].page_count, "1");
+        assert!(
+            (metrics.scenes[0].runtime_estimate_minutes - ACTION_MINUTES_PER_PAGE).abs() < 1e-9
+        );
+        // 3 + 9 * 3 = 30 lines
+        assert_eq!(metrics.scenes[1].eighths, 4);
+        assert_eq!(metrics.scenes[1].page_count, "4/8");
+        // Mostly dialogue plays faster than a page of action
+        let roof = &metrics.scenes[1];
+        assert!(roof.runtime_estimate_minutes < 30.0 / SCHEDULE_LINES_PER_PAGE);
+    }
+
+    #[test]
+    fn test_page_formats_wrap_and_break_differently() {
+        let long_action = element(
+            "action",
+            "Marlowe crosses the empty bar, past the piano and the sleeping bartender.",
+        );
+        let letter = PageFormatPreset::UsLetterScreenplay.format();
+        let narrow = PageFormat {
+            chars_per_line: 30,
+            ..letter
+        };
+        assert_eq!(letter.lines_for(&long_action), 2);
+        assert_eq!(narrow.lines_for(&long_action), 3);
+
+        let speech = element(
+            "dialogue",
+            "I told you already, twice tonight, and I won't say it again.",
+        );
+        let stage = PageFormatPreset::StagePlay.format();
+        assert_eq!(letter.lines_for(&speech), 2);
+        assert_eq!(stage.lines_for(&speech), 1);
+
+        // 28 one-line actions take 55 lines: one more than a Letter page
+        let elements: Vec<_> = (0..28).map(|_| element("action", "Rain.")).collect();
+        assert_eq!(paginate_script(elements.clone()).total_pages, 2);
+        let a4 = PageFormatPreset::A4Screenplay.format();
+        assert_eq!(paginate_script_with_format(elements, &a4).total_pages, 1);
     }
 }
@@ -0,0 +1,408 @@
+//! Request Log - Local ledger of every provider call for cost auditing
+//!
+//! Each LLM call and generation action is recorded with its provider, model,
+//! token usage and credit cost, so users can see exactly where their credits
+//! went. Like generation telemetry, nothing is recorded unless the user has
+//! opted in, and prompts are never stored.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::ai::llm_client::{LLMProvider, LLMResponse};
+use crate::telemetry;
+use crate::vault;
+
+/// SurrealDB table holding request log entries
+const LOG_TABLE: &str = "request_log";
+
+/// Default number of entries returned by `get_request_log`
+const DEFAULT_LIMIT: u32 = 200;
+
+/// Columns selected when reading entries back
+const LOG_FIELDS: &str = "timestamp, kind, provider, model, agent_role, prompt_tokens, \
+     completion_tokens, estimated_credits, actual_credits, success, duration_ms";
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// ENTRIES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub enum RequestKind {
+    Llm,
+    Generation,
+}
+
+/// One provider call
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct RequestLogEntry {
+    /// RFC 3339 UTC timestamp
+    pub timestamp: String,
+    pub kind: RequestKind,
+    pub provider: String,
+    pub model: String,
+    /// Agent that made the call, when known
+    pub agent_role: Option<String>,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    /// Credits estimated before the call
+    pub estimated_credits: Option<f32>,
+    /// Credits actually charged (0 for cache hits)
+    pub actual_credits: Option<f32>,
+    pub success: bool,
+    #[specta(type = f64)]
+    pub duration_ms: u64,
+}
+
+impl RequestLogEntry {
+    /// Entry for an LLM call (`response` is `None` when the call failed)
+    pub fn llm(
+        provider: &LLMProvider,
+        model: &str,
+        agent_role: Option<&str>,
+        response: Option<&LLMResponse>,
+        elapsed: Duration,
+    ) -> Self {
+        let model = response.map(|r| r.model.as_str()).unwrap_or(model);
+        let usage = response.and_then(|r| r.usage.as_ref());
+        let credits = usage.map(|u| {
+            crate::ai::cost::CostCalculator::estimate_llm(
+                model,
+                u.prompt_tokens,
+                u.completion_tokens,
+            )
+            .credits
+        });
+        let cached = response.is_some_and(|r| r.cached);
+
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            kind: RequestKind::Llm,
+            provider: format!("{:?}", provider).to_lowercase(),
+            model: model.to_string(),
+            agent_role: agent_role.map(String::from),
+            prompt_tokens: usage.map(|u| u.prompt_tokens),
+            completion_tokens: usage.map(|u| u.completion_tokens),
+            estimated_credits: credits,
+            actual_credits: if cached { Some(0.0) } else { credits },
+            success: response.is_some(),
+            duration_ms: elapsed.as_millis() as u64,
+        }
+    }
+
+    /// Entry for a generation action
+    pub fn generation(
+        model: &str,
+        estimated_credits: f32,
+        actual_credits: Option<f32>,
+        success: bool,
+        elapsed: Duration,
+    ) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            kind: RequestKind::Generation,
+            provider: crate::ai::providers::get_provider_for_model(model)
+                .pricing_key()
+                .to_string(),
+            model: model.to_string(),
+            agent_role: None,
+            prompt_tokens: None,
+            completion_tokens: None,
+            estimated_credits: Some(estimated_credits),
+            actual_credits,
+            success,
+            duration_ms: elapsed.as_millis() as u64,
+        }
+    }
+
+    /// Credits to account for: the actual charge, else the estimate; failed
+    /// requests are not billed
+    pub fn credits(&self) -> f32 {
+        if !self.success {
+            return 0.0;
+        }
+        self.actual_credits
+            .or(self.estimated_credits)
+            .unwrap_or(0.0)
+    }
+
+    /// Calendar day (UTC) of the entry, `YYYY-MM-DD`
+    fn day(&self) -> &str {
+        self.timestamp.get(..10).unwrap_or(&self.timestamp)
+    }
+}
+
+/// Record a provider call if the user has opted in to telemetry.
+///
+/// Failures are logged and swallowed; logging must never break a request.
+pub async fn record(entry: RequestLogEntry) {
+    if !telemetry::is_enabled() {
+        return;
+    }
+
+    let Some(db) = vault::get_db().await else {
+        return;
+    };
+
+    let result: Result<Option<RequestLogEntry>, _> = db.create(LOG_TABLE).content(entry).await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to record request log entry: {}", e);
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// QUERIES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Filter for `get_request_log` (all fields optional)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct RequestLogFilter {
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub agent_role: Option<String>,
+    #[serde(default)]
+    pub success: Option<bool>,
+    /// RFC 3339 lower bound (inclusive)
+    #[serde(default)]
+    pub since: Option<String>,
+    /// Maximum entries returned, newest first
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+/// Logged requests matching `filter`, newest first
+pub async fn get_request_log(filter: RequestLogFilter) -> Result<Vec<RequestLogEntry>, String> {
+    let db = vault::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    let mut conditions = Vec::new();
+    if filter.provider.is_some() {
+        conditions.push("provider = $provider");
+    }
+    if filter.model.is_some() {
+        conditions.push("model = $model");
+    }
+    if filter.agent_role.is_some() {
+        conditions.push("agent_role = $agent_role");
+    }
+    if filter.success.is_some() {
+        conditions.push("success = $success");
+    }
+    if filter.since.is_some() {
+        conditions.push("timestamp >= $since");
+    }
+
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", conditions.join(" AND "))
+    };
+
+    let mut result = db
+        .query(format!(
+            "SELECT {} FROM {}{} ORDER BY timestamp DESC LIMIT $limit",
+            LOG_FIELDS, LOG_TABLE, where_clause
+        ))
+        .bind(("provider", filter.provider))
+        .bind(("model", filter.model))
+        .bind(("agent_role", filter.agent_role))
+        .bind(("success", filter.success))
+        .bind(("since", filter.since))
+        .bind(("limit", filter.limit.unwrap_or(DEFAULT_LIMIT)))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    result.take(0).map_err(|e| e.to_string())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// COST SUMMARY
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Time window for a cost summary, ending now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub enum CostPeriod {
+    Day,
+    Week,
+    Month,
+    All,
+}
+
+impl CostPeriod {
+    /// RFC 3339 start of the window (`None` for `All`)
+    fn since(&self) -> Option<String> {
+        let days = match self {
+            CostPeriod::Day => 1,
+            CostPeriod::Week => 7,
+            CostPeriod::Month => 30,
+            CostPeriod::All => return None,
+        };
+        Some((chrono::Utc::now() - chrono::Duration::days(days)).to_rfc3339())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct ProviderCost {
+    pub provider: String,
+    pub requests: u32,
+    pub credits: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct DailyCost {
+    /// `YYYY-MM-DD` (UTC)
+    pub day: String,
+    pub requests: u32,
+    pub credits: f32,
+}
+
+/// Spend aggregated by provider and by day
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct CostSummary {
+    pub period: CostPeriod,
+    pub total_requests: u32,
+    pub failed_requests: u32,
+    pub total_credits: f32,
+    /// Most expensive provider first
+    pub by_provider: Vec<ProviderCost>,
+    /// Oldest day first
+    pub by_day: Vec<DailyCost>,
+}
+
+/// Aggregate log entries into a cost summary
+pub fn summarize_costs(period: CostPeriod, entries: &[RequestLogEntry]) -> CostSummary {
+    let mut by_provider: BTreeMap<&str, (u32, f32)> = BTreeMap::new();
+    let mut by_day: BTreeMap<&str, (u32, f32)> = BTreeMap::new();
+
+    for entry in entries {
+        let credits = entry.credits();
+        for (map, key) in [
+            (&mut by_provider, entry.provider.as_str()),
+            (&mut by_day, entry.day()),
+        ] {
+            let bucket = map.entry(key).or_default();
+            bucket.0 += 1;
+            bucket.1 += credits;
+        }
+    }
+
+    let mut by_provider: Vec<ProviderCost> = by_provider
+        .into_iter()
+        .map(|(provider, (requests, credits))| ProviderCost {
+            provider: provider.to_string(),
+            requests,
+            credits,
+        })
+        .collect();
+    by_provider.sort_by(|a, b| b.credits.total_cmp(&a.credits));
+
+    CostSummary {
+        period,
+        total_requests: entries.len() as u32,
+        failed_requests: entries.iter().filter(|e| !e.success).count() as u32,
+        total_credits: entries.iter().map(RequestLogEntry::credits).sum(),
+        by_provider,
+        by_day: by_day
+            .into_iter()
+            .map(|(day, (requests, credits))| DailyCost {
+                day: day.to_string(),
+                requests,
+                credits,
+            })
+            .collect(),
+    }
+}
+
+/// Summarize logged spend over `period`
+pub async fn get_cost_summary(period: CostPeriod) -> Result<CostSummary, String> {
+    let entries = get_request_log(RequestLogFilter {
+        since: period.since(),
+        limit: Some(u32::MAX),
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(summarize_costs(period, &entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        timestamp: &str,
+        provider: &str,
+        credits: Option<f32>,
+        success: bool,
+    ) -> RequestLogEntry {
+        RequestLogEntry {
+            timestamp: timestamp.into(),
+            kind: RequestKind::Generation,
+            provider: provider.into(),
+            model: "model".into(),
+            agent_role: None,
+            prompt_tokens: None,
+            completion_tokens: None,
+            estimated_credits: Some(10.0),
+            actual_credits: credits,
+            success,
+            duration_ms: 100,
+        }
+    }
+
+    #[test]
+    fn test_summary_math() {
+        let entries = vec![
+            entry("2026-03-01T10:00:00+00:00", "fal", Some(5.0), true),
+            entry("2026-03-01T12:00:00+00:00", "vertex", Some(120.0), true),
+            entry("2026-03-02T09:00:00+00:00", "fal", Some(2.5), true),
+            // Falls back to the estimate when the actual charge is unknown
+            entry("2026-03-02T11:00:00+00:00", "fal", None, true),
+            // Cache hit
+            entry("2026-03-02T11:30:00+00:00", "vertex", Some(0.0), true),
+            // Failed: counted as a request, not as spend
+            entry("2026-03-02T11:45:00+00:00", "fal", Some(4.0), false),
+        ];
+
+        let summary = summarize_costs(CostPeriod::All, &entries);
+        assert_eq!(summary.total_requests, 6);
+        assert_eq!(summary.failed_requests, 1);
+        assert!((summary.total_credits - 137.5).abs() < 1e-4);
+
+        assert_eq!(
+            summary.by_provider,
+            vec![
+                ProviderCost {
+                    provider: "vertex".into(),
+                    requests: 2,
+                    credits: 120.0
+                },
+                ProviderCost {
+                    provider: "fal".into(),
+                    requests: 4,
+                    credits: 17.5
+                },
+            ]
+        );
+        assert_eq!(
+            summary.by_day,
+            vec![
+                DailyCost {
+                    day: "2026-03-01".into(),
+                    requests: 2,
+                    credits: 125.0
+                },
+                DailyCost {
+                    day: "2026-03-02".into(),
+                    requests: 4,
+                    credits: 12.5
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,536 @@
+//! Scene parsing - the one screenplay parser everything else builds on
+//!
+//! `parse_scenes` turns script elements into typed scenes (heading parts,
+//! speaking characters, action lines, dialogue blocks). Token extraction,
+//! pagination and storyboards all read scenes from here instead of parsing
+//! the script themselves. Plain-text scripts go through `elements_from_text`
+//! first.
+
+use serde::{Deserialize, Serialize};
+
+use crate::normalize::{collapse_whitespace, is_scene_heading, split_on_dashes, split_prefix};
+use crate::pagination::ScriptElement;
+
+/// Words that make the last heading segment a time of day
+const TIME_WORDS: &[&str] = &[
+    "DAY",
+    "NIGHT",
+    "MORNING",
+    "AFTERNOON",
+    "EVENING",
+    "DAWN",
+    "DUSK",
+    "SUNRISE",
+    "SUNSET",
+    "NOON",
+    "MIDNIGHT",
+    "LATER",
+    "CONTINUOUS",
+    "SAME",
+    "MAGIC HOUR",
+];
+
+/// Times meaning "straight on from the previous scene"
+const CONTINUOUS_TIMES: &[&str] = &["CONTINUOUS", "SAME", "SAME TIME"];
+
+/// Heading suffixes marking a scene continued from an earlier one
+const CONTINUED_MARKERS: &[&str] = &["(CONT'D)", "(CONTD)", "(CONTINUED)", "(CONT.)"];
+
+/// Page-break markers that are not part of the story
+const PAGE_MARKERS: &[&str] = &["CONTINUED:", "(CONTINUED)", "(MORE)"];
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, specta::Type)]
+pub enum IntExt {
+    Int,
+    Ext,
+    /// INT./EXT., EXT./INT. and I/E
+    IntExt,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, specta::Type)]
+pub struct DialogueBlock {
+    /// Cue name without extensions
+    pub character: String,
+    /// Cue extension such as `V.O.` or `O.S.` (`CONT'D` is dropped)
+    pub extension: Option<String>,
+    pub parentheticals: Vec<String>,
+    pub lines: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, specta::Type)]
+pub struct Scene {
+    /// 1-based scene number
+    #[specta(type = f64)]
+    pub number: usize,
+    pub heading: String,
+    /// `None` for headings without an INT/EXT prefix (e.g. forced `.FLASHBACK`)
+    pub int_ext: Option<IntExt>,
+    pub location: String,
+    /// For continuous scenes, inherited from the previous scene
+    pub time_of_day: Option<String>,
+    /// CONTINUOUS/SAME time or a `(CONT'D)` heading
+    pub continued: bool,
+    /// Speaking characters in order of first cue
+    pub characters: Vec<String>,
+    pub action_lines: Vec<String>,
+    pub dialogue_blocks: Vec<DialogueBlock>,
+    /// Element range of the scene in the input, heading included
+    #[specta(type = f64)]
+    pub start_index: usize,
+    #[specta(type = f64)]
+    pub end_index: usize,
+}
+
+/// Parts of a scene heading
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadingParts {
+    pub int_ext: Option<IntExt>,
+    pub location: String,
+    pub time_of_day: Option<String>,
+    pub continued: bool,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// HEADINGS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Drop scene numbers (`12 INT. …`, `12A. INT. …`, `… #12#`) around a heading
+fn strip_scene_numbers(heading: &str) -> &str {
+    let mut text = heading.trim();
+    if let Some(start) = text.find('#').filter(|_| text.ends_with('#')) {
+        text = text[..start].trim_end();
+    }
+
+    let number_len = text
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(text.len());
+    let (number, rest) = text.split_at(number_len);
+    let is_number = number.starts_with(|c: char| c.is_ascii_digit())
+        && number.chars().filter(|c| c.is_ascii_alphabetic()).count() <= 1;
+    if is_number {
+        let rest = rest.trim_start_matches(|c: char| c == '.' || c.is_whitespace());
+        if split_prefix(&rest.to_uppercase()).is_some() {
+            return rest;
+        }
+    }
+    text
+}
+
+fn is_time_of_day(segment: &str) -> bool {
+    TIME_WORDS
+        .iter()
+        .any(|word| segment == *word || segment.split_whitespace().any(|w| w == *word))
+}
+
+/// Split a heading into INT/EXT, location and time of day
+pub fn parse_heading(heading: &str) -> HeadingParts {
+    let mut upper = collapse_whitespace(strip_scene_numbers(heading)).to_uppercase();
+
+    let mut continued = false;
+    for marker in CONTINUED_MARKERS {
+        if let Some(stripped) = upper.strip_suffix(marker) {
+            upper = stripped.trim_end().to_string();
+            continued = true;
+        }
+    }
+
+    let (int_ext, rest) = match split_prefix(&upper) {
+        Some((prefix, rest)) => {
+            let int_ext = match prefix {
+                "INT." => IntExt::Int,
+                "EXT." => IntExt::Ext,
+                _ => IntExt::IntExt,
+            };
+            (Some(int_ext), rest.to_string())
+        }
+        // Fountain forces headings with a leading dot
+        None => (None, upper.trim_start_matches('.').to_string()),
+    };
+
+    let mut segments = split_on_dashes(&rest);
+    let time_of_day = match segments.last() {
+        Some(last) if segments.len() > 1 && is_time_of_day(last) => segments.pop(),
+        _ => None,
+    };
+    continued |= time_of_day
+        .as_deref()
+        .is_some_and(|time| CONTINUOUS_TIMES.contains(&time));
+
+    HeadingParts {
+        int_ext,
+        location: segments.join(" - "),
+        time_of_day,
+        continued,
+    }
+}
+
+/// Split a character cue into name and extension, e.g. `ANNA (V.O.)`
+pub fn parse_cue(cue: &str) -> (String, Option<String>) {
+    let cue = cue.trim().trim_end_matches('^').trim_end();
+    let (name, extensions) = cue.split_once('(').unwrap_or((cue, ""));
+
+    let extension = extensions
+        .split('(')
+        .map(|ext| ext.trim().trim_end_matches(')').trim())
+        .find(|ext| {
+            let upper = ext.to_uppercase();
+            !ext.is_empty() && !matches!(upper.as_str(), "CONT'D" | "CONTD" | "CONT’D")
+        })
+        .map(str::to_string);
+
+    (collapse_whitespace(name), extension)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// SCENES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Group script elements into scenes
+///
+/// Each scene heading starts a scene; elements before the first heading are
+/// not part of any scene.
+pub fn parse_scenes(elements: &[ScriptElement]) -> Vec<Scene> {
+    let mut scenes: Vec<Scene> = Vec::new();
+
+    for (index, element) in elements.iter().enumerate() {
+        let element_type = element.r#type.as_str();
+        let text = element.text.trim();
+
+        if is_scene_heading(element_type) {
+            let parts = parse_heading(text);
+            let time_of_day = match (&parts.time_of_day, scenes.last()) {
+                (Some(time), Some(previous)) if CONTINUOUS_TIMES.contains(&time.as_str()) => {
+                    previous.time_of_day.clone()
+                }
+                (time, _) => time.clone(),
+            };
+            if let Some(previous) = scenes.last_mut() {
+                previous.end_index = index;
+            }
+            scenes.push(Scene {
+                number: scenes.len() + 1,
+                heading: collapse_whitespace(text),
+                int_ext: parts.int_ext,
+                location: parts.location,
+                time_of_day,
+                continued: parts.continued,
+                characters: Vec::new(),
+                action_lines: Vec::new(),
+                dialogue_blocks: Vec::new(),
+                start_index: index,
+                end_index: elements.len(),
+            });
+            continue;
+        }
+
+        let Some(scene) = scenes.last_mut() else {
+            continue;
+        };
+        if text.is_empty() || PAGE_MARKERS.contains(&text.to_uppercase().as_str()) {
+            continue;
+        }
+
+        match element_type {
+            "character" => {
+                let (character, extension) = parse_cue(text);
+                if character.is_empty() {
+                    continue;
+                }
+                if !scene
+                    .characters
+                    .iter()
+                    .any(|c| c.eq_ignore_ascii_case(&character))
+                {
+                    scene.characters.push(character.clone());
+                }
+                scene.dialogue_blocks.push(DialogueBlock {
+                    character,
+                    extension,
+                    parentheticals: Vec::new(),
+                    lines: Vec::new(),
+                });
+            }
+            "dialogue" | "parenthetical" => {
+                let Some(block) = scene.dialogue_blocks.last_mut() else {
+                    // Dialogue without a cue reads as action
+                    scene.action_lines.push(text.to_string());
+                    continue;
+                };
+                if element_type == "dialogue" {
+                    block.lines.push(text.to_string());
+                } else {
+                    block.parentheticals.push(text.to_string());
+                }
+            }
+            "transition" => {}
+            _ => scene.action_lines.push(text.to_string()),
+        }
+    }
+
+    scenes
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PLAIN TEXT
+// ═══════════════════════════════════════════════════════════════════════════════
+
+fn is_all_caps(text: &str) -> bool {
+    text.chars().any(|c| c.is_alphabetic()) && !text.chars().any(|c| c.is_lowercase())
+}
+
+fn is_heading_line(line: &str) -> bool {
+    // Forced heading (".FLASHBACK"), but not an ellipsis
+    if line.starts_with('.') && !line.starts_with("..") {
+        return true;
+    }
+    is_all_caps(line) && split_prefix(strip_scene_numbers(line)).is_some()
+}
+
+fn is_transition_line(line: &str) -> bool {
+    is_all_caps(line)
+        && (line.ends_with("TO:") || matches!(line, "FADE IN:" | "FADE OUT." | "FADE TO BLACK."))
+}
+
+/// Classify a plain-text screenplay into elements
+///
+/// Uses screenplay conventions: uppercase INT/EXT lines are headings, an
+/// uppercase line after a blank line with text right below is a character
+/// cue, and the lines after a cue are dialogue up to the next blank line.
+pub fn elements_from_text(text: &str) -> Vec<ScriptElement> {
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    let mut elements = Vec::new();
+    let mut in_dialogue = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            in_dialogue = false;
+            continue;
+        }
+
+        let after_blank = i == 0 || lines[i - 1].is_empty();
+        let before_text = lines.get(i + 1).is_some_and(|next| !next.is_empty());
+
+        let element_type = if in_dialogue {
+            if line.starts_with('(') {
+                "parenthetical"
+            } else {
+                "dialogue"
+            }
+        } else if is_heading_line(line) {
+            "scene-heading"
+        } else if is_transition_line(line) {
+            "transition"
+        } else if after_blank
+            && before_text
+            && is_all_caps(line)
+            && !line.ends_with(':')
+            && parse_cue(line).0.split_whitespace().count() <= 4
+        {
+            in_dialogue = true;
+            "character"
+        } else {
+            "action"
+        };
+
+        let text = match element_type {
+            "scene-heading" if line.starts_with('.') => line[1..].trim(),
+            _ => line,
+        };
+        elements.push(ScriptElement {
+            r#type: element_type.to_string(),
+            text: text.to_string(),
+            scene_number: None,
+        });
+    }
+
+    elements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(r#type: &str, text: &str) -> ScriptElement {
+        ScriptElement {
+            r#type: r#type.into(),
+            text: text.into(),
+            scene_number: None,
+        }
+    }
+
+    #[test]
+    fn test_odd_headings() {
+        let cases = [
+            (
+                "INT. HOUSE - KITCHEN - NIGHT",
+                Some(IntExt::Int),
+                "HOUSE - KITCHEN",
+                Some("NIGHT"),
+            ),
+            ("ext park -- day", Some(IntExt::Ext), "PARK", Some("DAY")),
+            (
+                "I/E CAR – MOVING",
+                Some(IntExt::IntExt),
+                "CAR - MOVING",
+                None,
+            ),
+            (
+                "EXT./INT. TRUCK — DUSK",
+                Some(IntExt::IntExt),
+                "TRUCK",
+                Some("DUSK"),
+            ),
+            (
+                "12A. INT. LAB - LATE NIGHT #12A#",
+                Some(IntExt::Int),
+                "LAB",
+                Some("LATE NIGHT"),
+            ),
+            (
+                "14 EXT. ROOF - DAWN",
+                Some(IntExt::Ext),
+                "ROOF",
+                Some("DAWN"),
+            ),
+            (
+                "Interior semi-truck stop - day",
+                Some(IntExt::Int),
+                "SEMI-TRUCK STOP",
+                Some("DAY"),
+            ),
+            (".FLASHBACK - 1985", None, "FLASHBACK - 1985", None),
+            (
+                "INTERNATIONAL AIRPORT - DAY",
+                None,
+                "INTERNATIONAL AIRPORT",
+                Some("DAY"),
+            ),
+        ];
+
+        for (heading, int_ext, location, time) in cases {
+            let parts = parse_heading(heading);
+            assert_eq!(parts.int_ext, int_ext, "heading: {:?}", heading);
+            assert_eq!(parts.location, location, "heading: {:?}", heading);
+            assert_eq!(parts.time_of_day.as_deref(), time, "heading: {:?}", heading);
+        }
+    }
+
+    #[test]
+    fn test_missing_time_of_day() {
+        let parts = parse_heading("INT. OFFICE");
+        assert_eq!(parts.location, "OFFICE");
+        assert_eq!(parts.time_of_day, None);
+        assert!(!parts.continued);
+
+        // A second segment that isn't a time stays part of the location
+        let parts = parse_heading("EXT. STADIUM - PARKING LOT");
+        assert_eq!(parts.location, "STADIUM - PARKING LOT");
+        assert_eq!(parts.time_of_day, None);
+    }
+
+    #[test]
+    fn test_cues() {
+        assert_eq!(parse_cue("ANNA"), ("ANNA".to_string(), None));
+        assert_eq!(
+            parse_cue("MARC (V.O.)"),
+            ("MARC".to_string(), Some("V.O.".to_string()))
+        );
+        assert_eq!(parse_cue("ANNA (CONT'D)"), ("ANNA".to_string(), None));
+        assert_eq!(
+            parse_cue("DR.  LEE (O.S.) (CONT'D) ^"),
+            ("DR. LEE".to_string(), Some("O.S.".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_scenes() {
+        let elements = vec![
+            element("transition", "FADE IN:"),
+            element("scene-heading", "INT. BAR - NIGHT"),
+            element("action", "Anna nurses a whiskey."),
+            element("character", "MARC (V.O.)"),
+            element("parenthetical", "(quietly)"),
+            element("dialogue", "You came back."),
+            element("action", "(CONTINUED)"),
+            element("action", "CONTINUED:"),
+            element("character", "ANNA"),
+            element("dialogue", "Don't."),
+            element("character", "MARC (CONT'D)"),
+            element("dialogue", "Anna..."),
+            element("transition", "CUT TO:"),
+            element("scene-heading", "EXT. BAR - CONTINUOUS"),
+            element("action", "Rain."),
+            element("scene-heading", "INT. BAR - LATER (CONT'D)"),
+        ];
+
+        let scenes = parse_scenes(&elements);
+        assert_eq!(scenes.len(), 3);
+
+        let bar = &scenes[0];
+        assert_eq!(bar.number, 1);
+        assert_eq!((bar.start_index, bar.end_index), (1, 13));
+        assert_eq!(bar.int_ext, Some(IntExt::Int));
+        assert_eq!(bar.location, "BAR");
+        assert_eq!(bar.characters, ["MARC", "ANNA"]);
+        assert_eq!(bar.action_lines, ["Anna nurses a whiskey."]);
+        assert_eq!(bar.dialogue_blocks.len(), 3);
+        assert_eq!(
+            bar.dialogue_blocks[0],
+            DialogueBlock {
+                character: "MARC".into(),
+                extension: Some("V.O.".into()),
+                parentheticals: vec!["(quietly)".into()],
+                lines: vec!["You came back.".into()],
+            }
+        );
+        assert_eq!(bar.dialogue_blocks[2].extension, None);
+
+        // Continuous scenes keep the previous time of day
+        let outside = &scenes[1];
+        assert!(outside.continued);
+        assert_eq!(outside.time_of_day.as_deref(), Some("NIGHT"));
+        assert_eq!(outside.action_lines, ["Rain."]);
+
+        let back_inside = &scenes[2];
+        assert!(back_inside.continued);
+        assert_eq!(back_inside.time_of_day.as_deref(), Some("LATER"));
+        assert_eq!(back_inside.end_index, elements.len());
+    }
+
+    #[test]
+    fn test_elements_from_text() {
+        let text = "FADE IN:\n\n\
+            INT. BAR - NIGHT\n\n\
+            Anna nurses a whiskey. A GUN sits on the bar.\n\n\
+            MARC (V.O.)\n\
+            (quietly)\n\
+            You came back.\n\n\
+            Interior design magazines litter the bar.\n\n\
+            CUT TO:\n\n\
+            .FLASHBACK\n";
+
+        let types: Vec<(String, String)> = elements_from_text(text)
+            .into_iter()
+            .map(|e| (e.r#type, e.text))
+            .collect();
+        let expected = [
+            ("transition", "FADE IN:"),
+            ("scene-heading", "INT. BAR - NIGHT"),
+            ("action", "Anna nurses a whiskey. A GUN sits on the bar."),
+            ("character", "MARC (V.O.)"),
+            ("parenthetical", "(quietly)"),
+            ("dialogue", "You came back."),
+            ("action", "Interior design magazines litter the bar."),
+            ("transition", "CUT TO:"),
+            ("scene-heading", "FLASHBACK"),
+        ];
+        assert_eq!(
+            types,
+            expected.map(|(t, text)| (t.to_string(), text.to_string()))
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! JSON settings files in the CinemaOS data directory
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::Path;
+
+use crate::installer::get_cinema_os_dir;
+
+/// Load the settings file `name`, or the defaults if it is missing or invalid
+pub fn load_json<T: DeserializeOwned + Default>(name: &str) -> T {
+    read_json(&get_cinema_os_dir().join(name))
+}
+
+/// Save settings as pretty-printed JSON to the file `name`
+pub fn save_json<T: Serialize>(name: &str, value: &T) -> Result<(), String> {
+    write_json(&get_cinema_os_dir().join(name), value)
+}
+
+/// [`load_json`] for a file outside the data directory
+pub fn read_json<T: DeserializeOwned + Default>(path: &Path) -> T {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// [`save_json`] for a file outside the data directory
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Limits {
+        max_local: u32,
+    }
+
+    #[test]
+    fn test_round_trip_and_defaults() {
+        let dir = std::env::temp_dir().join(format!("cinemaos-settings-{}", std::process::id()));
+        let path = dir.join("nested").join("limits.json");
+
+        // Missing file: defaults
+        assert_eq!(read_json::<Limits>(&path), Limits::default());
+
+        write_json(&path, &Limits { max_local: 2 }).unwrap();
+        assert_eq!(read_json::<Limits>(&path), Limits { max_local: 2 });
+
+        // Unreadable file: defaults rather than an error
+        std::fs::write(&path, "{not json").unwrap();
+        assert_eq!(read_json::<Limits>(&path), Limits::default());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
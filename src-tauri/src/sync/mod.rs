@@ -1,14 +1,201 @@
-use loro::LoroDoc;
+//! Sync Engine - Loro CRDT document with debounced autosave and cloud sync
+//!
+//! Saves append the updates since the last save to `<doc>.updates`; every
+//! [`COMPACT_AFTER_UPDATES`] saves the log is folded into a fresh snapshot,
+//! and the snapshot it replaces is kept as `<doc>.bak`.
+//! Cloud syncs exchange the updates since the last sync with the configured
+//! endpoint, whose version is kept in `<doc>.synced`. Undo and redo only
+//! cover edits made through [`SyncEngine::edit`], never imported ones.
+//! Named snapshots, including one per day, live in `<doc>.snapshots/`.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use loro::{Frontiers, LoroDoc, UndoManager, VersionVector, ID};
 use once_cell::sync::Lazy;
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
+use crate::installer::get_cinema_os_dir;
+use crate::settings::{load_json, read_json, save_json, write_json};
+use crate::vault::{into_page, page_window, Page};
+
+/// Update saves between full snapshots
+const COMPACT_AFTER_UPDATES: u32 = 50;
+
+/// Idle time before an edit is saved, unless configured
+pub const DEFAULT_AUTOSAVE_INTERVAL_MS: u64 = 2_000;
+
+/// Longest edits stay unsaved while the user keeps typing, unless configured
+pub const DEFAULT_PERIODIC_SAVE_MS: u64 = 30_000;
+
+const CLOUD_SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub type SharedEngine = Arc<Mutex<Option<SyncEngine>>>;
+
 // Global Sync Engine instance
-pub static SYNC_ENGINE: Lazy<Arc<Mutex<Option<SyncEngine>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(None)));
+pub static SYNC_ENGINE: Lazy<SharedEngine> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+/// Held for the whole export-and-write of a save
+static SAVE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Held for the whole exchange of a cloud sync
+static CLOUD_SYNC_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Text container holding the screenplay
+pub const SCRIPT_TEXT: &str = "script";
+
+/// Map container holding canvas nodes (JSON) by node id
+pub const CANVAS_NODES: &str = "canvas";
+
+/// Where the sync document is stored
+pub fn sync_doc_path() -> PathBuf {
+    get_cinema_os_dir().join("sync").join("document.loro")
+}
+
+/// Where a project's own document is stored (bundles export and import it)
+pub fn project_doc_path(project_id: &str) -> PathBuf {
+    let safe_id: String = project_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    get_cinema_os_dir()
+        .join("sync")
+        .join("projects")
+        .join(format!("{}.loro", safe_id))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// SETTINGS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct AutosaveSettings {
+    /// Idle time before edits are saved; 0 turns idle saves off
+    #[specta(type = f64)]
+    pub interval_ms: u64,
+    /// Save at least this often while edits keep coming; 0 turns periodic saves off
+    #[specta(type = f64)]
+    pub periodic_ms: u64,
+}
+
+impl Default for AutosaveSettings {
+    fn default() -> Self {
+        Self {
+            interval_ms: DEFAULT_AUTOSAVE_INTERVAL_MS,
+            periodic_ms: DEFAULT_PERIODIC_SAVE_MS,
+        }
+    }
+}
+
+/// Where the autosave settings are persisted
+const AUTOSAVE_FILE: &str = "autosave.json";
+
+static AUTOSAVE_SETTINGS: Lazy<RwLock<AutosaveSettings>> =
+    Lazy::new(|| RwLock::new(load_json(AUTOSAVE_FILE)));
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct CloudSyncSettings {
+    /// URL updates are POSTed to; `None` turns cloud sync off
+    pub endpoint: Option<String>,
+}
+
+/// Where the cloud sync settings are persisted
+const CLOUD_SYNC_FILE: &str = "cloud_sync.json";
+
+static CLOUD_SYNC_SETTINGS: Lazy<RwLock<CloudSyncSettings>> =
+    Lazy::new(|| RwLock::new(load_json(CLOUD_SYNC_FILE)));
+
+/// Current autosave settings
+pub fn autosave_settings() -> AutosaveSettings {
+    AUTOSAVE_SETTINGS
+        .read()
+        .map(|s| s.clone())
+        .unwrap_or_default()
+}
+
+/// Replace the autosave settings (persisted across restarts)
+pub fn set_autosave_settings(settings: AutosaveSettings) -> Result<(), String> {
+    let mut current = AUTOSAVE_SETTINGS.write().map_err(|e| e.to_string())?;
+    save_json(AUTOSAVE_FILE, &settings)?;
+    *current = settings;
+    Ok(())
+}
+
+/// Current cloud sync settings
+pub fn cloud_sync_settings() -> CloudSyncSettings {
+    CLOUD_SYNC_SETTINGS
+        .read()
+        .map(|s| s.clone())
+        .unwrap_or_default()
+}
+
+/// Replace the cloud sync settings (persisted across restarts)
+pub fn set_cloud_sync_settings(settings: CloudSyncSettings) -> Result<(), String> {
+    let mut current = CLOUD_SYNC_SETTINGS.write().map_err(|e| e.to_string())?;
+    save_json(CLOUD_SYNC_FILE, &settings)?;
+    *current = settings;
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// ENGINE
+// ═══════════════════════════════════════════════════════════════════════════════
 
 pub struct SyncEngine {
     pub doc: LoroDoc,
+    /// Document file; updates go to the `.updates` file next to it
+    path: PathBuf,
+    /// Version last written to disk
+    saved_version: VersionVector,
+    /// Update saves since the last snapshot
+    pending_updates: u32,
+    /// Version the sync endpoint is known to have
+    synced_version: VersionVector,
+    /// Local edits only; imports from other peers are skipped
+    undo: UndoManager,
+    /// Day of the last daily snapshot
+    daily_snapshot: Option<chrono::NaiveDate>,
+}
+
+/// Bytes to write for one save
+pub enum SaveBatch {
+    Snapshot(Vec<u8>),
+    Updates(Vec<u8>),
+}
+
+/// Outcome of one `sync_engine`
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct CloudSyncReport {
+    #[specta(type = f64)]
+    pub bytes_sent: u64,
+    #[specta(type = f64)]
+    pub bytes_received: u64,
+    /// Remote peers whose edits were concurrent with unsynced local ones;
+    /// Loro merged them
+    pub conflicts_resolved: u32,
+}
+
+/// Body POSTed to the sync endpoint
+#[derive(Serialize)]
+struct CloudSyncRequest {
+    /// Base64 encoded `VersionVector` of this document
+    version: String,
+    /// Base64 encoded Loro updates since the last sync
+    updates: String,
+}
+
+/// The endpoint's reply: updates it has beyond `CloudSyncRequest::version`
+#[derive(Deserialize)]
+struct CloudSyncResponse {
+    #[serde(default)]
+    updates: String,
 }
 
 impl Default for SyncEngine {
@@ -19,38 +206,934 @@ impl Default for SyncEngine {
 
 impl SyncEngine {
     pub fn new() -> Self {
+        Self::at(sync_doc_path())
+    }
+
+    /// An empty engine persisting to `path`
+    pub fn at(path: PathBuf) -> Self {
+        let doc = LoroDoc::new();
+        // Timestamps date the entries of `list_versions`
+        doc.set_record_timestamp(true);
+        let mut undo = UndoManager::new(&doc);
+        // Each `edit` is its own step, however quickly they follow each other
+        undo.set_merge_interval(0);
+
         Self {
-            doc: LoroDoc::new(),
+            doc,
+            path,
+            saved_version: VersionVector::default(),
+            pending_updates: 0,
+            synced_version: VersionVector::default(),
+            undo,
+            daily_snapshot: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Current version, including edits not yet committed
+    pub fn version(&self) -> VersionVector {
+        self.doc.commit();
+        self.doc.oplog_vv()
+    }
+
+    /// Apply a user edit as one undo step
+    pub fn edit<T>(&mut self, f: impl FnOnce(&LoroDoc) -> T) -> Result<T, String> {
+        // Changes made outside `edit` stay a step of their own
+        self.doc.commit();
+        let result = f(&self.doc);
+        self.doc.commit();
+        self.undo
+            .record_new_checkpoint()
+            .map_err(|e| e.to_string())?;
+        Ok(result)
+    }
+
+    /// Revert the last local edit; `false` if there was nothing to undo
+    pub fn undo(&mut self) -> Result<bool, String> {
+        self.doc.commit();
+        self.undo.undo().map_err(|e| e.to_string())
+    }
+
+    /// Reapply the last undone edit; a new edit after an undo clears this
+    pub fn redo(&mut self) -> Result<bool, String> {
+        self.doc.commit();
+        self.undo.redo().map_err(|e| e.to_string())
+    }
+
+    /// Replace `delete` characters of the script at `pos` with `insert`
+    pub fn edit_script(&mut self, pos: usize, delete: usize, insert: &str) -> Result<(), String> {
+        self.edit(|doc| {
+            let text = doc.get_text(SCRIPT_TEXT);
+            if delete > 0 {
+                text.delete(pos, delete)?;
+            }
+            text.insert(pos, insert)
+        })?
+        .map_err(|e| e.to_string())
+    }
+
+    /// Add or replace a canvas node
+    pub fn set_canvas_node(&mut self, node_id: &str, node_json: &str) -> Result<(), String> {
+        serde_json::from_str::<serde_json::Value>(node_json)
+            .map_err(|e| format!("Invalid canvas node {}: {}", node_id, e))?;
+        self.edit(|doc| doc.get_map(CANVAS_NODES).insert(node_id, node_json))?
+            .map_err(|e| e.to_string())
+    }
+
+    /// Remove a canvas node
+    pub fn remove_canvas_node(&mut self, node_id: &str) -> Result<(), String> {
+        self.edit(|doc| doc.get_map(CANVAS_NODES).delete(node_id))?
+            .map_err(|e| e.to_string())
+    }
+
+    /// The whole document, as a frontend `LoroDoc` imports it
+    pub fn snapshot(&self) -> Result<Vec<u8>, String> {
+        self.doc
+            .export(loro::ExportMode::Snapshot)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.version() != self.saved_version
+    }
+
+    /// What to write to persist unsaved changes, and the version it covers
+    pub fn unsaved_batch(&self) -> Result<Option<(SaveBatch, VersionVector)>, String> {
+        let version = self.version();
+        if version == self.saved_version {
+            return Ok(None);
+        }
+
+        let compact = !self.path.exists() || self.pending_updates >= COMPACT_AFTER_UPDATES;
+        let batch = if compact {
+            SaveBatch::Snapshot(
+                self.doc
+                    .export(loro::ExportMode::Snapshot)
+                    .map_err(|e| e.to_string())?,
+            )
+        } else {
+            SaveBatch::Updates(
+                self.doc
+                    .export(loro::ExportMode::updates(&self.saved_version))
+                    .map_err(|e| e.to_string())?,
+            )
+        };
+        Ok(Some((batch, version)))
+    }
+
+    /// Record that a batch covering `version` reached the disk
+    pub fn mark_saved(&mut self, batch: &SaveBatch, version: VersionVector) {
+        self.saved_version = version;
+        match batch {
+            SaveBatch::Snapshot(_) => self.pending_updates = 0,
+            SaveBatch::Updates(_) => self.pending_updates += 1,
         }
     }
 
+    /// Write a full snapshot to `path` now
     pub fn save_to_disk(&self, path: &str) -> std::io::Result<()> {
-        let bytes = self.doc.export(loro::ExportMode::Snapshot).unwrap();
-        std::fs::write(path, bytes)
+        let bytes = self
+            .doc
+            .export(loro::ExportMode::Snapshot)
+            .map_err(std::io::Error::other)?;
+        write_snapshot(Path::new(path), &bytes)
     }
 
+    /// Load the snapshot at `path` and any updates saved after it
     pub fn load_from_disk(&mut self, path: &str) -> std::io::Result<()> {
+        let path = Path::new(path);
         if let Ok(bytes) = std::fs::read(path) {
-            self.doc
-                .import(&bytes)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+            if let Err(e) = self.doc.import(&bytes) {
+                tracing::warn!("{} is corrupt ({}), loading its backup", path.display(), e);
+                let backup = std::fs::read(backup_path(path))?;
+                self.doc.import(&backup).map_err(std::io::Error::other)?;
+            }
+        }
+        let updates = read_updates(&updates_path(path))?;
+        for update in &updates {
+            self.doc.import(update).map_err(std::io::Error::other)?;
+        }
+
+        self.path = path.to_path_buf();
+        self.saved_version = self.version();
+        self.pending_updates = updates.len() as u32;
+        // Without a recorded version the next sync sends everything, which
+        // the endpoint imports idempotently
+        self.synced_version = std::fs::read(synced_path(path))
+            .ok()
+            .and_then(|bytes| VersionVector::decode(&bytes).ok())
+            .unwrap_or_default();
+        Ok(())
+    }
+
+    /// Updates since the last sync, and the version they bring the endpoint to
+    pub fn unsynced_updates(&self) -> Result<(Vec<u8>, VersionVector), String> {
+        let version = self.version();
+        let updates = self
+            .doc
+            .export(loro::ExportMode::updates(&self.synced_version))
+            .map_err(|e| e.to_string())?;
+        Ok((updates, version))
+    }
+
+    /// Merge the endpoint's reply to a sync that sent everything up to `sent`
+    ///
+    /// Edits made while the request was in flight aren't on the endpoint
+    /// yet; they go out with the next sync.
+    pub fn finish_cloud_sync(&mut self, sent: VersionVector, remote: &[u8]) -> Result<u32, String> {
+        let before = self.version();
+        let conflicts_resolved = self.merge_remote(remote)?;
+
+        // The endpoint has what was sent plus what it sent back
+        let mut synced = sent;
+        for (peer, counter) in self.version().iter() {
+            if before.get(peer).is_none_or(|seen| seen < counter) {
+                synced.insert(*peer, *counter);
+            }
+        }
+        self.mark_synced(synced)
+            .map_err(|e| format!("Failed to record sync state: {}", e))?;
+        Ok(conflicts_resolved)
+    }
+
+    /// Import updates from the endpoint; returns how many remote peers edited
+    /// concurrently with unsynced local changes
+    pub fn merge_remote(&mut self, updates: &[u8]) -> Result<u32, String> {
+        if updates.is_empty() {
+            return Ok(0);
+        }
+
+        let before = self.version();
+        self.doc.import(updates).map_err(|e| e.to_string())?;
+        if before == self.synced_version {
+            return Ok(0);
+        }
+
+        let after = self.version();
+        let concurrent = after
+            .iter()
+            .filter(|&(peer, counter)| before.get(peer).is_none_or(|seen| seen < counter))
+            .count();
+        Ok(concurrent as u32)
+    }
+
+    /// Record that the endpoint now has everything up to `version`
+    fn mark_synced(&mut self, version: VersionVector) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(synced_path(&self.path), version.encode())?;
+        self.synced_version = version;
+        Ok(())
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// HISTORY
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// A past state of the document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct VersionInfo {
+    /// Base64 encoded Loro frontiers; pass back to `checkout` or `fork_at`
+    pub version: String,
+    /// Unix seconds
+    #[specta(type = f64)]
+    pub timestamp: i64,
+    /// Set for named snapshots
+    pub name: Option<String>,
+}
+
+/// `snapshots/index.json` entry
+#[derive(Serialize, Deserialize)]
+struct SnapshotEntry {
+    #[serde(flatten)]
+    info: VersionInfo,
+    /// Snapshot file in the snapshots directory
+    file: String,
+}
+
+impl SyncEngine {
+    /// One page of the history, one entry per change, newest first
+    pub fn list_versions(
+        &self,
+        cursor: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<Page<VersionInfo>, String> {
+        let (start, limit) = page_window(cursor, limit)?;
+        self.doc.commit();
+        let heads: Vec<ID> = self.doc.oplog_frontiers().iter().collect();
+
+        let mut changes = Vec::new();
+        let travelled = self.doc.travel_change_ancestors(&heads, &mut |change| {
+            let last = ID::new(change.id.peer, change.id.counter + change.len as i32 - 1);
+            changes.push((change.lamport, change.timestamp, Frontiers::from(last)));
+            ControlFlow::Continue(())
+        });
+        if let Err(e) = travelled {
+            tracing::warn!("Failed to read document history: {}", e);
+        }
+
+        changes.sort_by(|a, b| b.0.cmp(&a.0));
+        let rows = changes
+            .into_iter()
+            .skip(start as usize)
+            .take(limit as usize + 1)
+            .map(|(_, timestamp, frontiers)| VersionInfo {
+                version: encode_version(&frontiers),
+                timestamp,
+                name: None,
+            })
+            .collect();
+        Ok(into_page(rows, start, limit))
+    }
+
+    /// The script at `version`; the document itself stays at the latest state
+    pub fn checkout(&mut self, version: &str) -> Result<String, String> {
+        let frontiers = decode_version(version)?;
+        self.doc.commit();
+        self.doc.checkout(&frontiers).map_err(|e| e.to_string())?;
+        let script = self.doc.get_text(SCRIPT_TEXT).to_string();
+        self.doc.checkout_to_latest();
+        Ok(script)
+    }
+
+    /// Replace the script with its content at `version`, as an undoable edit
+    pub fn restore(&mut self, version: &str) -> Result<String, String> {
+        let script = self.checkout(version)?;
+        self.edit(|doc| {
+            let text = doc.get_text(SCRIPT_TEXT);
+            text.delete(0, text.len_unicode())?;
+            text.insert(0, &script)
+        })?
+        .map_err(|e| e.to_string())?;
+        Ok(script)
+    }
+
+    /// A separate document starting from `version`; edits to it don't touch this one
+    pub fn fork_at(&self, version: &str) -> Result<LoroDoc, String> {
+        let frontiers = decode_version(version)?;
+        self.doc.commit();
+        Ok(self.doc.fork_at(&frontiers))
+    }
+
+    /// Keep a named snapshot of the current state next to the document
+    pub fn save_named_snapshot(&self, name: &str) -> Result<VersionInfo, String> {
+        let now = chrono::Utc::now();
+        let info = VersionInfo {
+            version: encode_version(&self.doc.oplog_frontiers()),
+            timestamp: now.timestamp(),
+            name: Some(name.to_string()),
+        };
+        let file = format!("{}.loro", now.timestamp_millis());
+
+        let dir = snapshots_dir(&self.path);
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        std::fs::write(dir.join(&file), self.snapshot()?).map_err(|e| e.to_string())?;
+
+        let mut index = read_snapshot_index(&dir);
+        index.push(SnapshotEntry {
+            info: info.clone(),
+            file,
+        });
+        write_json(&dir.join("index.json"), &index)?;
+        Ok(info)
+    }
+
+    /// Named snapshots, newest first
+    pub fn list_snapshots(&self) -> Vec<VersionInfo> {
+        let mut snapshots: Vec<VersionInfo> = read_snapshot_index(&snapshots_dir(&self.path))
+            .into_iter()
+            .map(|entry| entry.info)
+            .collect();
+        snapshots.reverse();
+        snapshots
+    }
+
+    /// Take today's snapshot unless it exists already
+    fn snapshot_daily(&mut self) -> Result<(), String> {
+        let today = chrono::Local::now().date_naive();
+        if self.daily_snapshot == Some(today) {
+            return Ok(());
+        }
+
+        let name = today.format("%Y-%m-%d").to_string();
+        let taken = self
+            .list_snapshots()
+            .iter()
+            .any(|s| s.name.as_deref() == Some(name.as_str()));
+        if !taken {
+            self.save_named_snapshot(&name)?;
         }
+        self.daily_snapshot = Some(today);
         Ok(())
     }
+}
+
+fn encode_version(frontiers: &Frontiers) -> String {
+    STANDARD.encode(frontiers.encode())
+}
+
+fn decode_version(version: &str) -> Result<Frontiers, String> {
+    let bytes = STANDARD
+        .decode(version)
+        .map_err(|e| format!("Invalid version: {}", e))?;
+    Frontiers::decode(&bytes).map_err(|e| format!("Invalid version: {}", e))
+}
+
+fn read_snapshot_index(dir: &Path) -> Vec<SnapshotEntry> {
+    read_json(&dir.join("index.json"))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// FILES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+fn snapshots_dir(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".snapshots");
+    PathBuf::from(name)
+}
+
+fn updates_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".updates");
+    PathBuf::from(name)
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+fn synced_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".synced");
+    PathBuf::from(name)
+}
+
+/// Replace the snapshot atomically and drop the updates it now contains
+///
+/// The previous snapshot is kept as `.bak` in case the new one turns out
+/// unreadable.
+fn write_snapshot(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, bytes)?;
+    std::fs::File::open(&tmp)?.sync_all()?;
+    if path.exists() {
+        std::fs::copy(path, backup_path(path))?;
+    }
+    std::fs::rename(&tmp, path)?;
+
+    match std::fs::remove_file(updates_path(path)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Append one length-prefixed update to the log
+fn append_update(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(updates_path(path))?;
+    file.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    file.write_all(bytes)?;
+    file.sync_data()
+}
+
+/// Updates in the log; a record cut short by a crash is ignored
+fn read_updates(path: &Path) -> std::io::Result<Vec<Vec<u8>>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut updates = Vec::new();
+    let mut rest = bytes.as_slice();
+    while rest.len() >= 4 {
+        let len = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let Some(update) = rest.get(4..4 + len) else {
+            tracing::warn!("Ignoring truncated update at the end of {}", path.display());
+            break;
+        };
+        updates.push(update.to_vec());
+        rest = &rest[4 + len..];
+    }
+    Ok(updates)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// AUTOSAVE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Persist unsaved changes of `engine`; returns whether anything was written
+pub async fn save_engine(engine: &SharedEngine) -> Result<bool, String> {
+    let _writing = SAVE_LOCK.lock().await;
+
+    let (batch, version, path) = {
+        let guard = engine.lock().await;
+        let Some(engine) = guard.as_ref() else {
+            return Ok(false);
+        };
+        match engine.unsaved_batch()? {
+            Some((batch, version)) => (batch, version, engine.path().to_path_buf()),
+            None => return Ok(false),
+        }
+    };
+
+    // Write without holding the engine, so edits continue during the IO
+    let written = match &batch {
+        SaveBatch::Snapshot(bytes) => write_snapshot(&path, bytes),
+        SaveBatch::Updates(bytes) => append_update(&path, bytes),
+    };
+    written.map_err(|e| format!("Failed to save {}: {}", path.display(), e))?;
+    match &batch {
+        SaveBatch::Snapshot(bytes) => {
+            tracing::debug!(
+                "Saved snapshot of {} ({} bytes)",
+                path.display(),
+                bytes.len()
+            )
+        }
+        SaveBatch::Updates(bytes) => {
+            tracing::debug!(
+                "Saved updates to {} ({} bytes)",
+                path.display(),
+                bytes.len()
+            )
+        }
+    }
+
+    if let Some(engine) = engine.lock().await.as_mut() {
+        engine.mark_saved(&batch, version);
+        if let Err(e) = engine.snapshot_daily() {
+            tracing::warn!("Failed to take the daily snapshot: {}", e);
+        }
+    }
+    Ok(true)
+}
+
+/// Persist the global document now
+pub async fn force_save() -> Result<bool, String> {
+    save_engine(&SYNC_ENGINE).await
+}
+
+/// Send `engine`'s updates since the last sync to `endpoint` and merge the
+/// ones it sends back
+pub async fn sync_engine(engine: &SharedEngine, endpoint: &str) -> Result<CloudSyncReport, String> {
+    let _syncing = CLOUD_SYNC_LOCK.lock().await;
+
+    let (updates, version) = engine
+        .lock()
+        .await
+        .as_ref()
+        .ok_or("Sync engine not initialized")?
+        .unsynced_updates()?;
+
+    // Post without holding the engine, so edits continue during the request
+    let remote = post_updates(endpoint, &updates, &version).await?;
+
+    let conflicts_resolved = engine
+        .lock()
+        .await
+        .as_mut()
+        .ok_or("Sync engine not initialized")?
+        .finish_cloud_sync(version, &remote)?;
+
+    Ok(CloudSyncReport {
+        bytes_sent: updates.len() as u64,
+        bytes_received: remote.len() as u64,
+        conflicts_resolved,
+    })
+}
+
+/// POST updates to the sync endpoint; returns the updates it has beyond `version`
+async fn post_updates(
+    endpoint: &str,
+    updates: &[u8],
+    version: &VersionVector,
+) -> Result<Vec<u8>, String> {
+    let response = reqwest::Client::new()
+        .post(endpoint)
+        .timeout(CLOUD_SYNC_TIMEOUT)
+        .json(&CloudSyncRequest {
+            version: STANDARD.encode(version.encode()),
+            updates: STANDARD.encode(updates),
+        })
+        .send()
+        .await
+        .map_err(|e| format!("Cloud sync failed: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("Cloud sync failed ({}): {}", status, body));
+    }
+    let response: CloudSyncResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid cloud sync response: {}", e))?;
+    STANDARD
+        .decode(response.updates)
+        .map_err(|e| format!("Invalid cloud sync response: {}", e))
+}
+
+/// Sync the global document with the configured endpoint
+pub async fn sync_now() -> Result<CloudSyncReport, String> {
+    let endpoint = cloud_sync_settings()
+        .endpoint
+        .filter(|e| !e.is_empty())
+        .ok_or("Cloud sync is not configured")?;
+
+    sync_engine(&SYNC_ENGINE, &endpoint).await
+}
+
+/// Save `engine` whenever it has been idle for `interval_ms`, and at least
+/// every `periodic_ms` while it keeps changing (zero turns either off)
+pub async fn autosave_loop(engine: SharedEngine, settings: impl Fn() -> AutosaveSettings) {
+    let mut last_version: Option<VersionVector> = None;
+    let mut changed_at = Instant::now();
+    // When the document was last saved or had nothing to save
+    let mut clean_at = Instant::now();
+
+    loop {
+        let settings = settings();
+        let idle = Duration::from_millis(settings.interval_ms);
+        let periodic = Duration::from_millis(settings.periodic_ms);
+        let shortest = [idle, periodic].into_iter().filter(|d| !d.is_zero()).min();
+        let tick = match shortest {
+            Some(shortest) => {
+                (shortest / 4).clamp(Duration::from_millis(25), Duration::from_millis(500))
+            }
+            None => Duration::from_secs(1),
+        };
+        tokio::time::sleep(tick).await;
+        if shortest.is_none() {
+            continue;
+        }
+
+        let (version, unsaved) = match engine.lock().await.as_ref() {
+            Some(engine) => (engine.version(), engine.has_unsaved_changes()),
+            None => continue,
+        };
+        if !unsaved {
+            clean_at = Instant::now();
+            continue;
+        }
+
+        // Any new edit restarts the idle window
+        if last_version.as_ref() != Some(&version) {
+            last_version = Some(version);
+            changed_at = Instant::now();
+        }
 
-    pub async fn sync_with_cloud(&self) {
-        // Placeholder for SurrealDB sync
-        println!("Syncing with cloud...");
+        let idle_due = !idle.is_zero() && changed_at.elapsed() >= idle;
+        // Continuous typing never goes idle
+        let periodic_due = !periodic.is_zero() && clean_at.elapsed() >= periodic;
+        if idle_due || periodic_due {
+            match save_engine(&engine).await {
+                Ok(_) => clean_at = Instant::now(),
+                Err(e) => tracing::warn!("Autosave failed: {}", e),
+            }
+        }
     }
 }
 
 pub async fn init() -> Result<(), Box<dyn std::error::Error>> {
-    let engine = SyncEngine::new();
+    let mut engine = SyncEngine::new();
+    let path = engine.path().display().to_string();
+    engine.load_from_disk(&path)?;
 
     let mut global_engine = SYNC_ENGINE.lock().await;
     *global_engine = Some(engine);
+    drop(global_engine);
+
+    tauri::async_runtime::spawn(autosave_loop(SYNC_ENGINE.clone(), autosave_settings));
 
     println!("✅ Sync Engine Initialized: Loro CRDT ready");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_change_persisted_after_debounce() {
+        let dir = std::env::temp_dir().join(format!("cinemaos-sync-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("document.loro");
+        let engine: SharedEngine = Arc::new(Mutex::new(Some(SyncEngine::at(path.clone()))));
+        let task = tokio::spawn(autosave_loop(engine.clone(), || AutosaveSettings {
+            interval_ms: 100,
+            periodic_ms: 0,
+        }));
+
+        let edit = |text: &'static str| {
+            let engine = engine.clone();
+            async move {
+                let guard = engine.lock().await;
+                let doc = &guard.as_ref().unwrap().doc;
+                let script = doc.get_text("script");
+                script.insert(script.len_unicode(), text).unwrap();
+            }
+        };
+        let load = || {
+            let mut loaded = SyncEngine::at(path.clone());
+            loaded.load_from_disk(path.to_str().unwrap()).unwrap();
+            loaded.doc.get_text("script").to_string()
+        };
+
+        edit("INT. LAB - NIGHT").await;
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(!path.exists(), "saved inside the debounce window");
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(load(), "INT. LAB - NIGHT");
+
+        // The next save only appends the new edit
+        edit("\nANNA enters.").await;
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(updates_path(&path).exists());
+        assert_eq!(load(), "INT. LAB - NIGHT\nANNA enters.");
+
+        task.abort();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_periodic_save_while_typing() {
+        let dir = std::env::temp_dir().join(format!("cinemaos-sync-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("document.loro");
+        let engine: SharedEngine = Arc::new(Mutex::new(Some(SyncEngine::at(path.clone()))));
+        let task = tokio::spawn(autosave_loop(engine.clone(), || AutosaveSettings {
+            interval_ms: 200,
+            periodic_ms: 300,
+        }));
+
+        // Edits every 50ms never leave the document idle for 200ms
+        for _ in 0..12 {
+            if let Some(engine) = engine.lock().await.as_ref() {
+                let script = engine.doc.get_text("script");
+                script.insert(script.len_unicode(), "x").unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(path.exists(), "not saved while edits kept coming");
+
+        task.abort();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_checkout_restore_and_fork() {
+        let dir = std::env::temp_dir().join(format!("cinemaos-sync-{}", uuid::Uuid::new_v4()));
+        let mut engine = SyncEngine::at(dir.join("document.loro"));
+        let script = |doc: &LoroDoc| doc.get_text(SCRIPT_TEXT).to_string();
+
+        engine
+            .edit(|doc| doc.get_text(SCRIPT_TEXT).insert(0, "INT. LAB").unwrap())
+            .unwrap();
+        let draft = engine.save_named_snapshot("First draft").unwrap();
+        engine
+            .edit(|doc| doc.get_text(SCRIPT_TEXT).insert(8, " - NIGHT").unwrap())
+            .unwrap();
+
+        // Previewing leaves the document at its latest state
+        assert_eq!(engine.checkout(&draft.version).unwrap(), "INT. LAB");
+        assert_eq!(script(&engine.doc), "INT. LAB - NIGHT");
+        assert_eq!(engine.list_snapshots(), [draft.clone()]);
+
+        let versions = engine.list_versions(None, None).unwrap().items;
+        assert!(!versions.is_empty());
+
+        // Pages of one walk the same history without gaps
+        let mut paged = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = engine.list_versions(cursor.as_deref(), Some(1)).unwrap();
+            assert_eq!(page.items.len(), 1);
+            paged.extend(page.items);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(paged, versions);
+        assert_eq!(
+            engine.checkout(&versions[0].version).unwrap(),
+            "INT. LAB - NIGHT"
+        );
+
+        let fork = engine.fork_at(&draft.version).unwrap();
+        fork.get_text(SCRIPT_TEXT).insert(0, "ALT: ").unwrap();
+        assert_eq!(script(&fork), "ALT: INT. LAB");
+        assert_eq!(script(&engine.doc), "INT. LAB - NIGHT");
+
+        // A restore is an edit like any other, so it can be undone
+        assert_eq!(engine.restore(&draft.version).unwrap(), "INT. LAB");
+        assert_eq!(script(&engine.doc), "INT. LAB");
+        engine.undo().unwrap();
+        assert_eq!(script(&engine.doc), "INT. LAB - NIGHT");
+
+        assert!(engine.checkout("not a version").is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_corrupt_snapshot_loads_backup() {
+        let dir = std::env::temp_dir().join(format!("cinemaos-sync-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("document.loro");
+        let path_str = path.to_str().unwrap();
+        let mut engine = SyncEngine::at(path.clone());
+
+        engine
+            .edit(|doc| doc.get_text("script").insert(0, "INT. LAB").unwrap())
+            .unwrap();
+        engine.save_to_disk(path_str).unwrap();
+        engine
+            .edit(|doc| doc.get_text("script").insert(8, " - NIGHT").unwrap())
+            .unwrap();
+        engine.save_to_disk(path_str).unwrap();
+
+        // A snapshot cut short mid-write
+        std::fs::write(&path, b"loro").unwrap();
+        let mut loaded = SyncEngine::at(path.clone());
+        loaded.load_from_disk(path_str).unwrap();
+        assert_eq!(loaded.doc.get_text("script").to_string(), "INT. LAB");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_merge_remote_reports_concurrent_edits() {
+        let mut local = SyncEngine::at(PathBuf::from("local.loro"));
+        let mut remote = SyncEngine::at(PathBuf::from("remote.loro"));
+        local.doc.get_text("script").insert(0, "INT. LAB").unwrap();
+
+        // Nothing unsynced on the receiving side, so nothing to resolve
+        let initial = local.doc.export(loro::ExportMode::Snapshot).unwrap();
+        assert_eq!(remote.merge_remote(&initial).unwrap(), 0);
+        local.synced_version = local.version();
+        remote.synced_version = remote.version();
+
+        local.doc.get_text("script").insert(8, " - NIGHT").unwrap();
+        remote.doc.get_text("script").insert(0, "1. ").unwrap();
+
+        let from_remote = remote
+            .doc
+            .export(loro::ExportMode::updates(&remote.synced_version))
+            .unwrap();
+        assert_eq!(local.merge_remote(&from_remote).unwrap(), 1);
+        assert_eq!(
+            local.doc.get_text("script").to_string(),
+            "1. INT. LAB - NIGHT"
+        );
+        assert_eq!(local.merge_remote(&[]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_edits_during_a_sync_go_out_next_time() {
+        let dir = std::env::temp_dir().join(format!("cinemaos-sync-{}", uuid::Uuid::new_v4()));
+        let mut local = SyncEngine::at(dir.join("local.loro"));
+        let mut remote = SyncEngine::at(dir.join("remote.loro"));
+        local.doc.get_text("script").insert(0, "INT. LAB").unwrap();
+
+        let (sent, version) = local.unsynced_updates().unwrap();
+        remote.merge_remote(&sent).unwrap();
+        remote.doc.get_text("script").insert(0, "1. ").unwrap();
+        let reply = remote
+            .doc
+            .export(loro::ExportMode::updates(&version))
+            .unwrap();
+
+        // Typed while the request was in flight
+        local.doc.get_text("script").insert(8, " - NIGHT").unwrap();
+        local.finish_cloud_sync(version, &reply).unwrap();
+        assert_eq!(
+            local.doc.get_text("script").to_string(),
+            "1. INT. LAB - NIGHT"
+        );
+
+        // The edit the endpoint hasn't seen is sent next
+        let (next, _) = local.unsynced_updates().unwrap();
+        remote.merge_remote(&next).unwrap();
+        assert_eq!(
+            remote.doc.get_text("script").to_string(),
+            "1. INT. LAB - NIGHT"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_undo_redo_local_edits() {
+        let mut engine = SyncEngine::at(PathBuf::from("undo.loro"));
+        let mut peer = SyncEngine::at(PathBuf::from("peer.loro"));
+        let append = |text: &'static str| {
+            move |doc: &LoroDoc| {
+                let script = doc.get_text("script");
+                script.insert(script.len_unicode(), text).unwrap();
+            }
+        };
+        let script = |engine: &SyncEngine| engine.doc.get_text("script").to_string();
+
+        engine.edit(append("INT. LAB")).unwrap();
+        engine.edit(append(" - NIGHT")).unwrap();
+        assert!(engine.undo().unwrap());
+        assert_eq!(script(&engine), "INT. LAB");
+        assert!(engine.redo().unwrap());
+        assert_eq!(script(&engine), "INT. LAB - NIGHT");
+
+        // A new edit after an undo drops the undone one
+        engine.undo().unwrap();
+        engine.edit(append(" - DAY")).unwrap();
+        assert!(!engine.redo().unwrap());
+        assert_eq!(script(&engine), "INT. LAB - DAY");
+
+        // A collaborator's change survives undoing our own edits
+        peer.merge_remote(&engine.snapshot().unwrap()).unwrap();
+        peer.edit(|doc| doc.get_text("script").insert(0, "1. ").unwrap())
+            .unwrap();
+        engine.merge_remote(&peer.snapshot().unwrap()).unwrap();
+        assert!(engine.undo().unwrap());
+        assert!(engine.undo().unwrap());
+        assert!(!engine.undo().unwrap());
+        assert_eq!(script(&engine), "1. ");
+    }
+
+    #[test]
+    fn test_script_and_canvas_edits_are_undoable() {
+        let mut engine = SyncEngine::at(PathBuf::from("edits.loro"));
+        let script = |engine: &SyncEngine| engine.doc.get_text(SCRIPT_TEXT).to_string();
+        let node = |engine: &SyncEngine| engine.doc.get_map(CANVAS_NODES).get("shot_1").is_some();
+
+        engine.edit_script(0, 0, "INT. LAB - DAY").unwrap();
+        engine.edit_script(11, 3, "NIGHT").unwrap();
+        assert_eq!(script(&engine), "INT. LAB - NIGHT");
+        assert!(engine.edit_script(100, 1, "").is_err());
+
+        engine
+            .set_canvas_node("shot_1", r#"{"type":"image","x":0,"y":0}"#)
+            .unwrap();
+        assert!(node(&engine));
+        assert!(engine.set_canvas_node("shot_2", "not json").is_err());
+        engine.remove_canvas_node("shot_1").unwrap();
+        assert!(!node(&engine));
+
+        assert!(engine.undo().unwrap());
+        assert!(node(&engine));
+        assert!(engine.undo().unwrap());
+        assert!(!node(&engine));
+        assert!(engine.undo().unwrap());
+        assert_eq!(script(&engine), "INT. LAB - DAY");
+    }
+}
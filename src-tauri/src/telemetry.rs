@@ -0,0 +1,204 @@
+//! Telemetry - Opt-in, local-only analytics for generation outcomes
+//!
+//! Records anonymized generation events (model id, success, duration, retry)
+//! to a local SurrealDB table so model selection can learn what works on the
+//! user's hardware. Nothing is recorded unless the user opts in, and nothing
+//! leaves the machine unless `cloud_enabled` is set by a future cloud opt-in.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::settings::{load_json, save_json};
+use crate::vault;
+
+/// SurrealDB table holding generation events
+const EVENTS_TABLE: &str = "generation_event";
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// SETTINGS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Telemetry opt-in flags (both off by default)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct TelemetrySettings {
+    /// Record generation events to the local Vault
+    pub local_enabled: bool,
+    /// Reserved for a future cloud opt-in; events never leave the machine while false
+    pub cloud_enabled: bool,
+}
+
+/// Where the telemetry settings are persisted
+const SETTINGS_FILE: &str = "telemetry.json";
+
+static SETTINGS: Lazy<RwLock<TelemetrySettings>> =
+    Lazy::new(|| RwLock::new(load_json(SETTINGS_FILE)));
+
+/// Current telemetry settings
+pub fn get_settings() -> TelemetrySettings {
+    SETTINGS.read().map(|s| s.clone()).unwrap_or_default()
+}
+
+/// Opt in or out of local telemetry (persisted across restarts)
+pub fn set_local_enabled(enabled: bool) -> Result<TelemetrySettings, String> {
+    let mut settings = SETTINGS.write().map_err(|e| e.to_string())?;
+    settings.local_enabled = enabled;
+    save_json(SETTINGS_FILE, &*settings)?;
+    Ok(settings.clone())
+}
+
+pub fn is_enabled() -> bool {
+    get_settings().local_enabled
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// EVENTS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Anonymized generation outcome (no prompts, no user data)
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct GenerationEvent {
+    pub model_id: String,
+    pub success: bool,
+    #[specta(type = f64)]
+    pub duration_ms: u64,
+    /// Whether this was a regeneration of a previous result
+    pub retry: bool,
+    pub timestamp: String,
+}
+
+impl GenerationEvent {
+    pub fn new(model_id: impl Into<String>, success: bool, duration_ms: u64, retry: bool) -> Self {
+        Self {
+            model_id: model_id.into(),
+            success,
+            duration_ms,
+            retry,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Record a generation event if the user has opted in.
+///
+/// Failures are logged and swallowed; telemetry must never break generation.
+pub async fn record_generation(event: GenerationEvent) {
+    if !is_enabled() {
+        return;
+    }
+
+    let Some(db) = vault::get_db().await else {
+        return;
+    };
+
+    let result: Result<Option<GenerationEvent>, _> = db.create(EVENTS_TABLE).content(event).await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to record generation event: {}", e);
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// STATS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Per-model success summary
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ModelGenerationStats {
+    pub model_id: String,
+    pub total: u32,
+    pub successes: u32,
+    pub failures: u32,
+    pub retries: u32,
+    pub success_rate: f32,
+    #[specta(type = f64)]
+    pub avg_duration_ms: u64,
+}
+
+/// Summarize events into per-model stats, sorted by usage (most used first)
+pub fn summarize(events: &[GenerationEvent]) -> Vec<ModelGenerationStats> {
+    let mut by_model: HashMap<&str, (u32, u32, u32, u64)> = HashMap::new();
+
+    for event in events {
+        let entry = by_model.entry(event.model_id.as_str()).or_default();
+        entry.0 += 1;
+        if event.success {
+            entry.1 += 1;
+        }
+        if event.retry {
+            entry.2 += 1;
+        }
+        entry.3 += event.duration_ms;
+    }
+
+    let mut stats: Vec<ModelGenerationStats> = by_model
+        .into_iter()
+        .map(
+            |(model_id, (total, successes, retries, duration))| ModelGenerationStats {
+                model_id: model_id.to_string(),
+                total,
+                successes,
+                failures: total - successes,
+                retries,
+                success_rate: successes as f32 / total as f32,
+                avg_duration_ms: duration / total as u64,
+            },
+        )
+        .collect();
+
+    stats.sort_by(|a, b| b.total.cmp(&a.total).then(a.model_id.cmp(&b.model_id)));
+    stats
+}
+
+/// Load all recorded events and summarize them per model
+pub async fn get_generation_stats() -> Result<Vec<ModelGenerationStats>, String> {
+    let db = vault::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    let mut result = db
+        .query(format!(
+            "SELECT model_id, success, duration_ms, retry, timestamp FROM {}",
+            EVENTS_TABLE
+        ))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let events: Vec<GenerationEvent> = result.take(0).map_err(|e| e.to_string())?;
+    Ok(summarize(&events))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_default_to_opted_out() {
+        let settings = TelemetrySettings::default();
+        assert!(!settings.local_enabled);
+        assert!(!settings.cloud_enabled);
+    }
+
+    #[test]
+    fn test_summarize_per_model() {
+        let events = vec![
+            GenerationEvent::new("flux-schnell", true, 1000, false),
+            GenerationEvent::new("flux-schnell", false, 3000, false),
+            GenerationEvent::new("flux-schnell", true, 2000, true),
+            GenerationEvent::new("kling-v2.6", true, 60000, false),
+        ];
+
+        let stats = summarize(&events);
+        assert_eq!(stats.len(), 2);
+
+        let flux = &stats[0];
+        assert_eq!(flux.model_id, "flux-schnell");
+        assert_eq!(flux.total, 3);
+        assert_eq!(flux.successes, 2);
+        assert_eq!(flux.failures, 1);
+        assert_eq!(flux.retries, 1);
+        assert_eq!(flux.avg_duration_ms, 2000);
+        assert!((flux.success_rate - 2.0 / 3.0).abs() < f32::EPSILON);
+    }
+}
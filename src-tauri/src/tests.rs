@@ -11,6 +11,7 @@ mod llm_tests {
         let msg = LLMMessage {
             role: "user".into(),
             content: "Hello, world!".into(),
+            images: Vec::new(),
         };
         assert_eq!(msg.role, "user");
         assert_eq!(msg.content, "Hello, world!");
@@ -24,6 +25,7 @@ mod llm_tests {
             messages: vec![LLMMessage {
                 role: "user".into(),
                 content: "Test".into(),
+                images: Vec::new(),
             }],
             temperature: Some(0.7),
             max_tokens: Some(4096),
@@ -63,6 +65,7 @@ mod llm_tests {
 #[cfg(test)]
 mod agent_tests {
     use crate::ai::agents::crew::VirtualCrew;
+    use crate::ai::agents::routing::RoutingTable;
     use crate::ai::agents::traits::AgentRole;
 
     #[test]
@@ -89,28 +92,31 @@ mod agent_tests {
     #[test]
     fn test_intent_routing_image() {
         let crew = VirtualCrew::new();
-        let role = crew.route_by_intent("Generate an image of a sunset");
+        let role = crew.route_with(&RoutingTable::default(), "Generate an image of a sunset");
         assert_eq!(role, AgentRole::PhotographyDirector);
     }
 
     #[test]
     fn test_intent_routing_video() {
         let crew = VirtualCrew::new();
-        let role = crew.route_by_intent("Create a video of the hero walking");
+        let role = crew.route_with(
+            &RoutingTable::default(),
+            "Create a video of the hero walking",
+        );
         assert_eq!(role, AgentRole::CameraDirector);
     }
 
     #[test]
     fn test_intent_routing_script() {
         let crew = VirtualCrew::new();
-        let role = crew.route_by_intent("Write dialogue for this scene");
+        let role = crew.route_with(&RoutingTable::default(), "Write dialogue for this scene");
         assert_eq!(role, AgentRole::Scriptwriter);
     }
 
     #[test]
     fn test_intent_routing_default() {
         let crew = VirtualCrew::new();
-        let role = crew.route_by_intent("What should we do?");
+        let role = crew.route_with(&RoutingTable::default(), "What should we do?");
         assert_eq!(role, AgentRole::Showrunner);
     }
 }
@@ -130,8 +136,13 @@ mod workflow_tests {
             height: 1024,
             steps: None,
             seed: None,
+            batch_size: 1,
             input_image: None,
             force_local: None,
+            control: None,
+            mask_image: None,
+            expand_pixels: None,
+            params: None,
         };
 
         let result = generate_workflow(&request).unwrap();
@@ -151,8 +162,13 @@ mod workflow_tests {
             height: 1024,
             steps: None,
             seed: None,
+            batch_size: 1,
             input_image: None,
             force_local: None,
+            control: None,
+            mask_image: None,
+            expand_pixels: None,
+            params: None,
         };
 
         // Note: In strict mode this might fail if model ID isn't in models.rs,
@@ -258,6 +274,164 @@ mod installer_tests {
         ];
         assert_eq!(statuses.len(), 8);
     }
+
+    #[test]
+    fn test_step_errors_map_to_variants() {
+        use crate::errors::{InstallFailure, InstallerError};
+
+        let git = step_error(
+            &InstallStatus::CheckingPrerequisites,
+            "Failed to execute git: No such file or directory (os error 2)".into(),
+        );
+        assert!(matches!(git, InstallerError::GitNotInstalled));
+        assert!(git.remediation().contains("git-scm.com"));
+
+        let uv = step_error(&InstallStatus::InstallingUV, "sh failed: curl: (22)".into());
+        assert!(matches!(uv, InstallerError::UVInstallFailed { .. }));
+
+        let python = step_error(
+            &InstallStatus::InstallingPython,
+            "uv failed: no python found".into(),
+        );
+        assert!(matches!(python, InstallerError::PythonInstallFailed { .. }));
+
+        let clone = step_error(
+            &InstallStatus::InstallingComfyUI,
+            "git failed: fatal: unable to access 'https://github.com/'".into(),
+        );
+        assert!(matches!(clone, InstallerError::ComfyUICloneFailed { .. }));
+
+        let torch = step_error(
+            &InstallStatus::InstallingComfyUI,
+            "uv failed: Failed to download `torch`: operation timed out".into(),
+        );
+        assert!(matches!(
+            torch,
+            InstallerError::DependencyInstallFailed { .. }
+        ));
+        assert!(torch.remediation().contains("internet connection"));
+
+        let nodes = step_error(
+            &InstallStatus::InstallingDependencies,
+            "Permission denied (os error 13)".into(),
+        );
+        assert!(matches!(nodes, InstallerError::DirectoryNotWritable { .. }));
+
+        let failure = InstallFailure::from(&torch);
+        assert_eq!(failure.kind, "DependencyInstallFailed");
+        assert_eq!(failure.message, torch.to_string());
+        assert_eq!(failure.remediation, torch.remediation());
+    }
+
+    #[test]
+    fn test_update_refuses_local_changes() {
+        use crate::errors::InstallerError;
+
+        let porcelain = " M nodes.py\nM  comfy/model_management.py\n";
+        let files = modified_files(porcelain);
+        assert_eq!(files, ["nodes.py", "comfy/model_management.py"]);
+        assert!(modified_files("").is_empty());
+
+        let error = InstallerError::ComfyUILocalChanges {
+            path: "/data/CinemaOS/comfyui".into(),
+            files,
+        };
+        assert_eq!(error.kind(), "ComfyUILocalChanges");
+        assert_eq!(
+            error.to_string(),
+            "ComfyUI has local modifications: nodes.py, comfy/model_management.py"
+        );
+        assert!(error
+            .remediation()
+            .contains("git -C /data/CinemaOS/comfyui stash"));
+
+        let progress = InstallProgress::of(InstallStatus::Updating, 2, 4, "Pulling...");
+        assert_eq!(progress.percent, 50.0);
+    }
+
+    #[test]
+    fn test_find_free_port_skips_taken_ports() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        let free = find_free_port(port, 10).unwrap();
+        assert!(free > port && free - port < 10);
+        assert_eq!(find_free_port(port, 1), None);
+        assert_eq!(ComfyUIProcess::with_port(port).active_port(), None);
+    }
+
+    #[test]
+    fn test_copy_node_tree_is_recursive() {
+        use std::fs;
+
+        let root = std::env::temp_dir().join(format!("cinemaos-nodes-{}", std::process::id()));
+        let source = root.join("cinemaos");
+        let target = root.join("custom_nodes").join("CinemaOS");
+        for (file, contents) in [
+            ("__init__.py", "from .nodes import *"),
+            ("nodes/video.py", "class Video: pass"),
+            ("nodes/util/io.py", "def load(): pass"),
+            ("web/js/widgets.js", "export {}"),
+            ("__pycache__/nodes.cpython-311.pyc", "bytecode"),
+            ("nodes/__pycache__/video.cpython-311.pyc", "bytecode"),
+            ("nodes/stale.pyc", "bytecode"),
+        ] {
+            let path = source.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        copy_node_tree(&source, &target).unwrap();
+
+        let mut copied = Vec::new();
+        let mut pending = vec![target.clone()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else {
+                    let relative = path.strip_prefix(&target).unwrap();
+                    copied.push(relative.to_string_lossy().replace('\\', "/"));
+                }
+            }
+        }
+        copied.sort();
+        assert_eq!(
+            copied,
+            [
+                "__init__.py",
+                "nodes/util/io.py",
+                "nodes/video.py",
+                "web/js/widgets.js"
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(target.join("nodes/util/io.py")).unwrap(),
+            "def load(): pass"
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod bindings_tests {
+    use crate::{specta_builder, typescript_exporter};
+
+    #[test]
+    fn test_typescript_bindings_export() {
+        let path = std::env::temp_dir().join("cinemaos-bindings-test.ts");
+
+        // The error names the type specta could not represent
+        if let Err(e) = specta_builder().export(typescript_exporter(), &path) {
+            panic!("TypeScript bindings cannot be generated: {}", e);
+        }
+
+        let bindings = std::fs::read_to_string(&path).unwrap();
+        assert!(bindings.contains("agentChatFull"));
+        let _ = std::fs::remove_file(path);
+    }
 }
 
 // Downloader tests require additional setup - covered in integration tests
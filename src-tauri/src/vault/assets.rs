@@ -0,0 +1,223 @@
+//! Assets — Generated and imported media owned by a project
+//!
+//! Files live under the project's asset directory; the Vault stores one
+//! `asset` record per file so the gallery can list them without scanning disk.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::path::PathBuf;
+use surrealdb::engine::any::Any;
+use surrealdb::Surreal;
+
+use super::{into_page, or_empty, page_window, Page};
+use crate::installer::get_cinema_os_dir;
+
+/// Kind of media an asset holds
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Type)]
+pub enum AssetKind {
+    Image,
+    Video,
+    Audio,
+    Other,
+}
+
+impl AssetKind {
+    /// Guess the kind from a file extension
+    pub fn from_file_name(file_name: &str) -> Self {
+        let ext = file_name
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match ext.as_str() {
+            "png" | "jpg" | "jpeg" | "webp" | "gif" | "bmp" | "tiff" => Self::Image,
+            "mp4" | "webm" | "mov" | "mkv" | "avi" => Self::Video,
+            "wav" | "mp3" | "flac" | "ogg" | "m4a" => Self::Audio,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// An asset record stored in the Vault
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct Asset {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub project_id: String,
+    pub kind: AssetKind,
+    pub file_name: String,
+    /// Absolute path inside the project's asset directory
+    pub path: String,
+    /// Where the asset came from ("comfyui", "import", ...)
+    pub source: String,
+    /// Generation run that produced it (ComfyUI prompt id)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub execution_id: Option<String>,
+    /// Workflow node that produced it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<String>,
+    /// Tokens the asset depicts (`token:…` ids)
+    #[serde(default)]
+    pub token_ids: Vec<String>,
+    pub created_at: String,
+}
+
+/// Narrows an asset listing
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct AssetFilter {
+    pub kind: Option<AssetKind>,
+    /// Only assets linked to this token
+    pub token_id: Option<String>,
+}
+
+/// Directory holding a project's asset files
+pub fn project_assets_dir(project_id: &str) -> PathBuf {
+    let safe_id: String = project_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    get_cinema_os_dir()
+        .join("projects")
+        .join(safe_id)
+        .join("assets")
+}
+
+/// One page of a project's assets, newest first
+pub async fn list_assets(
+    db: &Surreal<Any>,
+    project_id: &str,
+    cursor: Option<&str>,
+    limit: Option<u32>,
+    filter: AssetFilter,
+) -> Result<Page<Asset>, String> {
+    let (start, limit) = page_window(cursor, limit)?;
+
+    // LIMIT/START are validated integers; everything else is bound
+    let query = format!(
+        "SELECT * FROM asset WHERE project_id = $pid \
+         AND ($kind = NONE OR kind = $kind) \
+         AND ($token = NONE OR $token IN token_ids) \
+         ORDER BY created_at DESC, id LIMIT {} START {}",
+        limit + 1,
+        start
+    );
+
+    let mut result = db
+        .query(query)
+        .bind(("pid", project_id.to_string()))
+        .bind(("kind", filter.kind))
+        .bind(("token", filter.token_id))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let rows: Vec<Asset> = or_empty(result.take(0))?;
+    Ok(into_page(rows, start, limit))
+}
+
+/// A single asset by record id
+pub async fn get_asset(db: &Surreal<Any>, asset_id: &str) -> Result<Asset, String> {
+    let mut result = db
+        .query("SELECT * FROM type::thing($id)")
+        .bind(("id", asset_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let asset: Option<Asset> = or_empty(result.take(0))?;
+    asset.ok_or_else(|| format!("Asset not found: {}", asset_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    async fn seeded_db(count: usize) -> Surreal<Any> {
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+
+        for i in 0..count {
+            let file_name = if i % 4 == 0 {
+                format!("clip_{:03}.mp4", i)
+            } else {
+                format!("frame_{:03}.png", i)
+            };
+            let asset = Asset {
+                id: None,
+                project_id: "project:p1".into(),
+                kind: AssetKind::from_file_name(&file_name),
+                path: format!("/assets/{}", file_name),
+                file_name,
+                source: "comfyui".into(),
+                execution_id: None,
+                node_id: None,
+                token_ids: if i % 3 == 0 {
+                    vec!["token:anna".into()]
+                } else {
+                    Vec::new()
+                },
+                // Pairs share a timestamp, as outputs of one run do
+                created_at: format!("2025-12-01T10:{:02}:00Z", i / 2),
+            };
+            let _: Option<Asset> = db.create("asset").content(asset).await.unwrap();
+        }
+        db
+    }
+
+    async fn all_pages(db: &Surreal<Any>, limit: u32, filter: AssetFilter) -> Vec<Asset> {
+        let mut assets = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = list_assets(
+                db,
+                "project:p1",
+                cursor.as_deref(),
+                Some(limit),
+                filter.clone(),
+            )
+            .await
+            .unwrap();
+            assert!(page.items.len() <= limit as usize);
+            assets.extend(page.items);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return assets,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paging_has_no_duplicates_or_gaps() {
+        let db = seeded_db(23).await;
+
+        let assets = all_pages(&db, 5, AssetFilter::default()).await;
+        let names: HashSet<&str> = assets.iter().map(|a| a.file_name.as_str()).collect();
+        assert_eq!(assets.len(), 23);
+        assert_eq!(names.len(), 23);
+        assert!(assets
+            .windows(2)
+            .all(|w| w[0].created_at >= w[1].created_at));
+
+        let videos = all_pages(
+            &db,
+            2,
+            AssetFilter {
+                kind: Some(AssetKind::Video),
+                token_id: None,
+            },
+        )
+        .await;
+        assert_eq!(videos.len(), 6);
+        assert!(videos.iter().all(|a| a.kind == AssetKind::Video));
+
+        let anna = all_pages(
+            &db,
+            3,
+            AssetFilter {
+                kind: None,
+                token_id: Some("token:anna".into()),
+            },
+        )
+        .await;
+        assert_eq!(anna.len(), 8);
+    }
+}
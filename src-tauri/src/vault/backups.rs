@@ -0,0 +1,210 @@
+//! Vault Backups — The whole database in one SurrealQL file
+//!
+//! A backup is SurrealDB's `EXPORT` with a comment header carrying the backup
+//! format version, so restores can refuse files written by a newer schema.
+//! Restores first apply the file to a throwaway in-memory database, then save
+//! the current Vault next to the app data before replacing it.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::path::{Path, PathBuf};
+use surrealdb::engine::any::Any;
+use surrealdb::Surreal;
+
+use crate::installer::get_cinema_os_dir;
+
+/// Current backup format (bumped when the Vault schema changes incompatibly)
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+/// First line of every backup, followed by " v<format> (app <version>)"
+const BACKUP_HEADER: &str = "-- CinemaOS Vault backup";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct RestoreReport {
+    pub format_version: u32,
+    /// Backup of the Vault as it was before the restore
+    pub pre_restore_backup: String,
+}
+
+fn header() -> String {
+    format!(
+        "{} v{} (app {})\n",
+        BACKUP_HEADER,
+        BACKUP_FORMAT_VERSION,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// Format version of a backup, if it can be restored here
+pub fn check_backup(contents: &str) -> Result<u32, String> {
+    let first_line = contents.lines().next().unwrap_or_default();
+    let version = first_line
+        .strip_prefix(BACKUP_HEADER)
+        .and_then(|rest| rest.trim_start().strip_prefix('v'))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|v| v.parse::<u32>().ok())
+        .ok_or("Not a CinemaOS Vault backup")?;
+
+    if version > BACKUP_FORMAT_VERSION {
+        return Err(format!(
+            "Backup format v{} is newer than supported v{}",
+            version, BACKUP_FORMAT_VERSION
+        ));
+    }
+    Ok(version)
+}
+
+/// Where the automatic pre-restore backup goes
+fn pre_restore_path() -> PathBuf {
+    get_cinema_os_dir().join("backups").join(format!(
+        "pre-restore-{}.surql",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    ))
+}
+
+async fn db() -> Result<Surreal<Any>, String> {
+    super::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())
+}
+
+/// Export the whole Vault to `path`
+pub async fn backup(path: &str) -> Result<(), String> {
+    export_to(&db().await?, Path::new(path)).await
+}
+
+/// Replace the whole Vault with the backup at `path`
+///
+/// The current Vault is backed up first and put back if the import fails.
+pub async fn restore(path: &str) -> Result<RestoreReport, String> {
+    let path = Path::new(path);
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read backup: {}", e))?;
+    let format_version = check_backup(&contents)?;
+
+    // A file that doesn't import cleanly must not wipe anything
+    let scratch = surrealdb::engine::any::connect("mem://")
+        .await
+        .map_err(|e| e.to_string())?;
+    scratch
+        .use_ns(super::NAMESPACE)
+        .use_db(super::DATABASE)
+        .await
+        .map_err(|e| e.to_string())?;
+    scratch
+        .import(path)
+        .await
+        .map_err(|e| format!("Backup does not import cleanly: {}", e))?;
+
+    let db = db().await?;
+    let pre_restore = pre_restore_path();
+    if let Some(dir) = pre_restore.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    export_to(&db, &pre_restore).await?;
+
+    if let Err(e) = replace_database(&db, path).await {
+        tracing::error!(
+            "Vault restore failed, putting the previous data back: {}",
+            e
+        );
+        replace_database(&db, &pre_restore).await.map_err(|undo| {
+            format!(
+                "Restore failed ({}) and so did rolling back ({}); the previous Vault is at {}",
+                e,
+                undo,
+                pre_restore.display()
+            )
+        })?;
+        return Err(format!("Restore failed, nothing was changed: {}", e));
+    }
+
+    // Older backups come in at their own schema version
+    super::migrate(&db).await?;
+    if let Err(e) = super::tokens::define_search_index(&db).await {
+        tracing::warn!("Token search index unavailable after restore: {}", e);
+    }
+
+    Ok(RestoreReport {
+        format_version,
+        pre_restore_backup: pre_restore.display().to_string(),
+    })
+}
+
+/// `EXPORT` with the backup header in front
+async fn export_to(db: &Surreal<Any>, path: &Path) -> Result<(), String> {
+    db.export(path)
+        .await
+        .map_err(|e| format!("Failed to export Vault: {}", e))?;
+
+    let export = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    std::fs::write(path, header() + &export).map_err(|e| format!("Failed to write backup: {}", e))
+}
+
+/// Drop the selected database and import `path` in its place
+async fn replace_database(db: &Surreal<Any>, path: &Path) -> Result<(), String> {
+    db.query(format!("REMOVE DATABASE IF EXISTS {}", super::DATABASE))
+        .await
+        .and_then(|response| response.check())
+        .map_err(|e| e.to_string())?;
+    db.use_ns(super::NAMESPACE)
+        .use_db(super::DATABASE)
+        .await
+        .map_err(|e| e.to_string())?;
+    db.import(path).await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::{DATABASE, NAMESPACE};
+
+    #[test]
+    fn test_check_backup_header() {
+        assert_eq!(check_backup(&(header() + "OPTION IMPORT;")), Ok(1));
+        assert_eq!(
+            check_backup("-- CinemaOS Vault backup v1 (app 0.1.0)\n"),
+            Ok(1)
+        );
+        assert_eq!(
+            check_backup("-- CinemaOS Vault backup v9 (app 3.0.0)\n"),
+            Err(format!(
+                "Backup format v9 is newer than supported v{}",
+                BACKUP_FORMAT_VERSION
+            ))
+        );
+        assert!(check_backup("OPTION IMPORT;\nDEFINE TABLE token;").is_err());
+        assert!(check_backup("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_and_replace_round_trip() {
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        db.use_ns(NAMESPACE).use_db(DATABASE).await.unwrap();
+        db.query("CREATE project:noir SET title = 'Noir'")
+            .await
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "cinemaos-vault-backup-{}.surql",
+            uuid::Uuid::new_v4()
+        ));
+        export_to(&db, &path).await.unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(check_backup(&contents), Ok(BACKUP_FORMAT_VERSION));
+
+        db.query("CREATE project:western SET title = 'Western'")
+            .await
+            .unwrap();
+        replace_database(&db, &path).await.unwrap();
+
+        let titles: Vec<String> = db
+            .query("SELECT VALUE title FROM project")
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap();
+        assert_eq!(titles, ["Noir"]);
+        std::fs::remove_file(&path).ok();
+    }
+}
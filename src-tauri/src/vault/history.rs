@@ -0,0 +1,276 @@
+//! Generation History — What a project generated, and with which settings
+//!
+//! Every run recorded here keeps the request that produced it, the assets it
+//! created and what it cost, so an entry can be turned back into a
+//! `WorkflowRequest` and run again.
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use surrealdb::engine::any::Any;
+use surrealdb::Surreal;
+
+use super::{into_page, or_empty, page_window, Page};
+use crate::ai::model_params::ModelParams;
+use crate::ai::workflow_generator::{
+    default_batch_size, ControlNetConfig, WorkflowRequest, WorkflowType,
+};
+
+/// Request settings besides the prompt, model and seed
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct GenerationParameters {
+    pub negative_prompt: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub steps: Option<u32>,
+    #[serde(default = "default_batch_size")]
+    pub batch_size: u32,
+    pub input_image: Option<String>,
+    pub force_local: Option<bool>,
+    #[serde(default)]
+    pub control: Option<ControlNetConfig>,
+    #[serde(default)]
+    pub mask_image: Option<String>,
+    #[serde(default)]
+    pub expand_pixels: Option<u32>,
+    #[serde(default)]
+    pub params: Option<ModelParams>,
+}
+
+/// A `generation_history` record
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct GenerationRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub project_id: String,
+    pub workflow_type: WorkflowType,
+    pub prompt: String,
+    pub model: String,
+    #[specta(type = Option<f64>)]
+    pub seed: Option<i64>,
+    pub parameters: GenerationParameters,
+    /// Assets the run produced (`asset:…` ids)
+    #[serde(default)]
+    pub asset_ids: Vec<String>,
+    /// ComfyUI prompt id or Fal.ai request id
+    pub execution_id: Option<String>,
+    pub credits: f32,
+    pub created_at: String,
+}
+
+/// Narrows a history listing
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct HistoryFilter {
+    pub model: Option<String>,
+    pub workflow_type: Option<WorkflowType>,
+    /// Case-insensitive substring of the prompt
+    pub search: Option<String>,
+    /// Only the run that produced this asset
+    pub asset_id: Option<String>,
+}
+
+impl GenerationRecord {
+    /// Record of a finished run of `request`
+    pub fn new(
+        project_id: &str,
+        request: &WorkflowRequest,
+        asset_ids: Vec<String>,
+        execution_id: Option<String>,
+        credits: f32,
+    ) -> Self {
+        Self {
+            id: None,
+            project_id: project_id.to_string(),
+            workflow_type: request.workflow_type.clone(),
+            prompt: request.prompt.clone(),
+            model: request.model.clone(),
+            seed: request.seed,
+            parameters: GenerationParameters {
+                negative_prompt: request.negative_prompt.clone(),
+                width: request.width,
+                height: request.height,
+                steps: request.steps,
+                batch_size: request.batch_size,
+                input_image: request.input_image.clone(),
+                force_local: request.force_local,
+                control: request.control.clone(),
+                mask_image: request.mask_image.clone(),
+                expand_pixels: request.expand_pixels,
+                params: request.params.clone(),
+            },
+            asset_ids,
+            execution_id,
+            credits,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// The request that reproduces this run (same seed included)
+    pub fn to_request(&self) -> WorkflowRequest {
+        let parameters = self.parameters.clone();
+        WorkflowRequest {
+            workflow_type: self.workflow_type.clone(),
+            prompt: self.prompt.clone(),
+            negative_prompt: parameters.negative_prompt,
+            model: self.model.clone(),
+            width: parameters.width,
+            height: parameters.height,
+            steps: parameters.steps,
+            seed: self.seed,
+            batch_size: parameters.batch_size,
+            input_image: parameters.input_image,
+            force_local: parameters.force_local,
+            control: parameters.control,
+            mask_image: parameters.mask_image,
+            expand_pixels: parameters.expand_pixels,
+            params: parameters.params,
+        }
+    }
+}
+
+/// Store a history entry
+pub async fn record_generation(
+    db: &Surreal<Any>,
+    record: GenerationRecord,
+) -> Result<GenerationRecord, String> {
+    let created: Option<GenerationRecord> = db
+        .create("generation_history")
+        .content(record)
+        .await
+        .map_err(|e| e.to_string())?;
+    created.ok_or_else(|| "Failed to record generation".to_string())
+}
+
+/// One page of a project's history, newest first
+pub async fn list_history(
+    db: &Surreal<Any>,
+    project_id: &str,
+    cursor: Option<&str>,
+    limit: Option<u32>,
+    filter: HistoryFilter,
+) -> Result<Page<GenerationRecord>, String> {
+    let (start, limit) = page_window(cursor, limit)?;
+
+    // LIMIT/START are validated integers; everything else is bound
+    let query = format!(
+        "SELECT * FROM generation_history WHERE project_id = $pid \
+         AND ($model = NONE OR model = $model) \
+         AND ($wtype = NONE OR workflow_type = $wtype) \
+         AND ($search = NONE OR string::contains(string::lowercase(prompt), $search)) \
+         AND ($asset = NONE OR $asset IN asset_ids) \
+         ORDER BY created_at DESC, id LIMIT {} START {}",
+        limit + 1,
+        start
+    );
+
+    let mut result = db
+        .query(query)
+        .bind(("pid", project_id.to_string()))
+        .bind(("model", filter.model))
+        .bind(("wtype", filter.workflow_type))
+        .bind(("search", filter.search.map(|s| s.to_lowercase())))
+        .bind(("asset", filter.asset_id))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let rows: Vec<GenerationRecord> = or_empty(result.take(0))?;
+    Ok(into_page(rows, start, limit))
+}
+
+/// A single history entry
+pub async fn get_entry(db: &Surreal<Any>, entry_id: &str) -> Result<GenerationRecord, String> {
+    let mut result = db
+        .query("SELECT * FROM type::thing($id)")
+        .bind(("id", entry_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let entry: Option<GenerationRecord> = result.take(0).map_err(|e| e.to_string())?;
+    entry.ok_or_else(|| format!("History entry not found: {}", entry_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::model_params::FluxParams;
+    use crate::ai::workflow_generator::ControlType;
+
+    fn request(prompt: &str, seed: i64) -> WorkflowRequest {
+        WorkflowRequest {
+            workflow_type: WorkflowType::TextToImage,
+            prompt: prompt.into(),
+            negative_prompt: Some("blurry".into()),
+            model: "flux-dev".into(),
+            width: 1344,
+            height: 768,
+            steps: Some(28),
+            seed: Some(seed),
+            batch_size: 2,
+            input_image: None,
+            force_local: Some(true),
+            control: Some(ControlNetConfig {
+                control_type: ControlType::Depth,
+                image: "depth.png".into(),
+                strength: 0.6,
+            }),
+            mask_image: None,
+            expand_pixels: None,
+            params: Some(ModelParams::Flux(FluxParams::default())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_rehydrates_request() {
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+
+        let original = request("Anna on the rooftop at dawn", 42);
+        let recorded = record_generation(
+            &db,
+            GenerationRecord::new(
+                "project:p1",
+                &original,
+                vec!["asset:frame1".into()],
+                Some("prompt-1".into()),
+                12.5,
+            ),
+        )
+        .await
+        .unwrap();
+        record_generation(
+            &db,
+            GenerationRecord::new(
+                "project:p1",
+                &request("Street at night", 7),
+                vec![],
+                None,
+                3.0,
+            ),
+        )
+        .await
+        .unwrap();
+
+        let page = list_history(
+            &db,
+            "project:p1",
+            None,
+            None,
+            HistoryFilter {
+                search: Some("ROOFTOP".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].asset_ids, ["asset:frame1"]);
+        assert_eq!(page.items[0].credits, 12.5);
+
+        let entry = get_entry(&db, recorded.id.as_deref().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(entry.to_request()).unwrap(),
+            serde_json::to_value(&original).unwrap()
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Vault Migrations — Bring an older `cinema_os.db` up to the current models
+//!
+//! The database stores its version in `schema_version:current`; a database
+//! without one is version 1 (the baseline). Migrations run in order, each in
+//! its own transaction, and are written so running one twice changes nothing.
+
+use surrealdb::engine::any::Any;
+use surrealdb::Surreal;
+
+use super::or_empty;
+
+/// One step from `version - 1` to `version`
+struct Migration {
+    version: u32,
+    description: &'static str,
+    statements: &'static str,
+}
+
+/// Ordered by version; append new steps, never edit shipped ones
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        description: "Backfill fields the models now require",
+        statements: "\
+            UPDATE token SET slug = \
+                (IF token_type = 'Character' THEN '@' \
+                 ELSE IF token_type = 'Location' THEN '/' \
+                 ELSE IF token_type = 'Prop' THEN '#' \
+                 ELSE '//' END) \
+                + string::lowercase(string::replace(name, ' ', '-')) \
+                WHERE slug = NONE; \
+            UPDATE token SET metadata = {} WHERE metadata = NONE; \
+            UPDATE token SET visual_refs = [] WHERE visual_refs = NONE; \
+            UPDATE token SET updated_at = created_at WHERE updated_at = NONE; \
+            UPDATE project SET updated_at = created_at WHERE updated_at = NONE; \
+            UPDATE script SET version = 1 WHERE version = NONE;",
+    },
+    Migration {
+        version: 3,
+        description: "Move character aliases out of token metadata",
+        statements: "\
+            UPDATE token SET aliases = string::split(metadata.aliases, ', ') \
+                WHERE metadata.aliases != NONE; \
+            UPDATE token UNSET metadata.aliases WHERE metadata.aliases != NONE; \
+            UPDATE token SET aliases = [] WHERE aliases = NONE;",
+    },
+    Migration {
+        version: 4,
+        description: "Group ControlNet settings in generation history",
+        statements: "\
+            UPDATE generation_history SET parameters.control = { \
+                control_type: parameters.control_type, \
+                image: parameters.control_image, \
+                strength: 0.8 \
+            } WHERE parameters.control_image != NONE AND parameters.control_type != NONE; \
+            UPDATE generation_history UNSET parameters.control_image, parameters.control_type \
+                WHERE parameters.control_image != NONE OR parameters.control_type != NONE;",
+    },
+];
+
+/// Version a database has once every migration ran
+pub fn current_version() -> u32 {
+    MIGRATIONS.last().map_or(1, |m| m.version)
+}
+
+async fn stored_version(db: &Surreal<Any>) -> Result<u32, String> {
+    let mut result = db
+        .query("SELECT VALUE version FROM schema_version:current")
+        .await
+        .map_err(|e| e.to_string())?;
+    let versions: Vec<u32> = or_empty(result.take(0))?;
+    Ok(versions.first().copied().unwrap_or(1))
+}
+
+/// Apply the migrations `db` is missing, returning its new version
+pub async fn migrate(db: &Surreal<Any>) -> Result<u32, String> {
+    let mut version = stored_version(db).await?;
+    if version > current_version() {
+        return Err(format!(
+            "Vault schema v{} is newer than this app supports (v{})",
+            version,
+            current_version()
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        tracing::info!(
+            "Migrating Vault to v{}: {}",
+            migration.version,
+            migration.description
+        );
+        let transaction = format!(
+            "BEGIN TRANSACTION; {} \
+             UPSERT schema_version:current SET version = {}, migrated_at = time::now(); \
+             COMMIT TRANSACTION;",
+            migration.statements, migration.version
+        );
+        db.query(transaction)
+            .await
+            .and_then(|response| response.check())
+            .map_err(|e| format!("Migration to v{} failed: {}", migration.version, e))?;
+        version = migration.version;
+    }
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::models::{Project, Script};
+    use crate::vault::tokens::{Token, TokenType};
+
+    /// A database as version 1 wrote it
+    async fn v1_fixture() -> Surreal<Any> {
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+        db.query(
+            "CREATE project:noir SET title = 'Noir', author = 'Mozzzie', \
+                 created_at = '2025-01-01T00:00:00Z'; \
+             CREATE script:draft SET project_id = project:noir, title = 'Noir', \
+                 content = '{}'; \
+             CREATE token:sarah SET project_id = 'project:noir', token_type = 'Character', \
+                 name = 'Det. Sarah Jones', description = 'Tired detective', \
+                 metadata = { aliases: 'SARAH, JONES', age: '40s' }, \
+                 created_at = '2025-01-02T00:00:00Z'; \
+             CREATE generation_history:first SET project_id = 'project:noir', \
+                 parameters = { width: 1024, height: 1024, control_image: 'depth.png', \
+                     control_type: 'Depth' };",
+        )
+        .await
+        .unwrap()
+        .check()
+        .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_v1_database_migrates_without_data_loss() {
+        let db = v1_fixture().await;
+        assert_eq!(stored_version(&db).await.unwrap(), 1);
+
+        assert_eq!(migrate(&db).await.unwrap(), current_version());
+        // Running again is a no-op
+        assert_eq!(migrate(&db).await.unwrap(), current_version());
+        assert_eq!(stored_version(&db).await.unwrap(), current_version());
+
+        let token: Option<Token> = db.select(("token", "sarah")).await.unwrap();
+        let token = token.unwrap();
+        assert_eq!(token.token_type, TokenType::Character);
+        assert_eq!(token.slug, "@det.-sarah-jones");
+        assert_eq!(token.description, "Tired detective");
+        assert_eq!(token.aliases, ["SARAH", "JONES"]);
+        assert_eq!(token.metadata.get("age").map(String::as_str), Some("40s"));
+        assert!(!token.metadata.contains_key("aliases"));
+        assert_eq!(token.updated_at, "2025-01-02T00:00:00Z");
+
+        let project: Option<Project> = db.select(("project", "noir")).await.unwrap();
+        assert_eq!(project.unwrap().updated_at, "2025-01-01T00:00:00Z");
+        let script: Option<Script> = db.select(("script", "draft")).await.unwrap();
+        assert_eq!(script.unwrap().version, 1);
+
+        let parameters: Vec<serde_json::Value> = db
+            .query("SELECT VALUE parameters FROM generation_history:first")
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap();
+        assert_eq!(
+            parameters[0],
+            serde_json::json!({
+                "width": 1024,
+                "height": 1024,
+                "control": { "control_type": "Depth", "image": "depth.png", "strength": 0.8 }
+            })
+        );
+    }
+}
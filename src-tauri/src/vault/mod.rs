@@ -1,13 +1,33 @@
 pub mod api;
+pub mod assets;
+pub mod backups;
+pub mod bundle;
+pub mod history;
+pub mod migrations;
 pub mod models;
+pub mod script_patch;
 pub mod tokens;
+pub mod training;
+
+pub use backups::{backup, restore};
+pub use migrations::migrate;
 
 use once_cell::sync::Lazy;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use surrealdb::engine::any::Any;
 use surrealdb::Surreal;
 use tokio::sync::Mutex;
 
+/// Namespace and database every table lives in
+pub const NAMESPACE: &str = "cinema_os";
+pub const DATABASE: &str = "production";
+
+/// How long read commands wait for `init` before reporting the Vault as missing
+pub const DB_WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const DB_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 // Global database instance using Any engine
 pub static DB: Lazy<Arc<Mutex<Option<Surreal<Any>>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
 
@@ -21,12 +41,23 @@ pub async fn init() -> Result<(), Box<dyn std::error::Error>> {
     db.connect(db_path).await?;
 
     // Select a namespace and database
-    db.use_ns("cinema_os").use_db("production").await?;
+    db.use_ns(NAMESPACE).use_db(DATABASE).await?;
+
+    let version = migrate(&db)
+        .await
+        .map_err(|e| format!("Vault migration failed: {}", e))?;
+
+    if let Err(e) = tokens::define_search_index(&db).await {
+        eprintln!("⚠️ Token search index unavailable, using substring search: {}", e);
+    }
 
     let mut global_db = DB.lock().await;
     *global_db = Some(db);
 
-    println!("✅ Vault Initialized: SurrealDB connected at {}", db_path);
+    println!(
+        "✅ Vault Initialized: SurrealDB connected at {} (schema v{})",
+        db_path, version
+    );
 
     // Start the Vault HTTP API in background
     let port = 8080;
@@ -43,3 +74,130 @@ pub async fn get_db() -> Option<Surreal<Any>> {
     let global_db = DB.lock().await;
     global_db.clone()
 }
+
+/// Wait up to `timeout` for `init` to connect.
+///
+/// `init` runs in a spawned task, so the UI can mount and issue reads before
+/// the database is ready.
+pub async fn wait_for_db(timeout: Duration) -> Option<Surreal<Any>> {
+    wait_for_value(&DB, timeout).await
+}
+
+async fn wait_for_value<T: Clone>(slot: &Mutex<Option<T>>, timeout: Duration) -> Option<T> {
+    let started = Instant::now();
+    loop {
+        if let Some(value) = slot.lock().await.clone() {
+            return Some(value);
+        }
+        if started.elapsed() >= timeout {
+            return None;
+        }
+        tokio::time::sleep(DB_POLL_INTERVAL).await;
+    }
+}
+
+/// Query result for a read, where a missing table just means nothing has
+/// been saved yet (a new project has no tokens, characters, ...)
+pub fn or_empty<T: Default>(result: Result<T, surrealdb::Error>) -> Result<T, String> {
+    result.or_else(|e| {
+        let message = e.to_string();
+        if is_missing_table(&message) {
+            Ok(T::default())
+        } else {
+            Err(message)
+        }
+    })
+}
+
+/// SurrealDB reports "The table 'token' does not exist" in strict mode
+fn is_missing_table(error: &str) -> bool {
+    error.contains("table") && error.contains("does not exist")
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PAGINATION
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Page size when the caller doesn't pass a limit
+pub const DEFAULT_PAGE_LIMIT: u32 = 50;
+
+/// Largest page a caller may ask for
+pub const MAX_PAGE_LIMIT: u32 = 200;
+
+/// One page of a listing; pass `next_cursor` back to get the following page
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, specta::Type)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// `START` and `LIMIT` for a page request
+///
+/// Cursors are opaque to callers; they hold the offset of the next row.
+pub fn page_window(cursor: Option<&str>, limit: Option<u32>) -> Result<(u32, u32), String> {
+    let start = match cursor {
+        Some(c) => c
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid page cursor: {}", c))?,
+        None => 0,
+    };
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+    Ok((start, limit))
+}
+
+/// Build a page from rows fetched with `LIMIT limit + 1`
+///
+/// The extra row only tells whether another page follows; it is dropped.
+pub fn into_page<T>(mut rows: Vec<T>, start: u32, limit: u32) -> Page<T> {
+    let has_more = rows.len() > limit as usize;
+    rows.truncate(limit as usize);
+    Page {
+        items: rows,
+        next_cursor: has_more.then(|| (start + limit).to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wait_for_delayed_init() {
+        let slot = Arc::new(Mutex::new(None));
+
+        let init = slot.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            *init.lock().await = Some("connected");
+        });
+
+        // Too short for the delayed init
+        assert_eq!(wait_for_value(&slot, Duration::from_millis(20)).await, None);
+
+        let started = Instant::now();
+        assert_eq!(
+            wait_for_value(&slot, Duration::from_secs(2)).await,
+            Some("connected")
+        );
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_missing_table_is_empty() {
+        assert!(is_missing_table("The table 'token' does not exist"));
+        assert!(!is_missing_table("Parse error: unexpected token"));
+    }
+
+    #[test]
+    fn test_page_window() {
+        assert_eq!(page_window(None, None).unwrap(), (0, DEFAULT_PAGE_LIMIT));
+        assert_eq!(page_window(Some("40"), Some(20)).unwrap(), (40, 20));
+        assert_eq!(page_window(None, Some(0)).unwrap(), (0, 1));
+        assert_eq!(
+            page_window(None, Some(10_000)).unwrap(),
+            (0, MAX_PAGE_LIMIT)
+        );
+        assert!(page_window(Some("asset:abc"), None).is_err());
+    }
+}
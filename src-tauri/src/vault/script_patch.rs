@@ -0,0 +1,514 @@
+//! Script Patches — Validated line edits against the stored script
+//!
+//! A line is one top-level screenplay element (scene heading, action,
+//! character, dialogue...), numbered from 1 as in the Navigator. Patches are
+//! bounds-checked and must not overlap; applying them snapshots the previous
+//! content into `script_revision` so the change can be undone.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use specta::Type;
+
+use super::models::Script;
+
+/// SurrealDB table holding pre-patch snapshots
+const REVISION_TABLE: &str = "script_revision";
+
+/// Unchanged lines shown around each change in the diff
+const CONTEXT_LINES: usize = 3;
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// TYPES
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Replace lines `line_start..=line_end` with `content` (one line per element)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct ScriptPatch {
+    pub line_start: u32,
+    pub line_end: u32,
+    /// Replacement text; empty deletes the range
+    pub content: String,
+}
+
+impl ScriptPatch {
+    /// Patch from an `UpdateScript` action's optional range
+    pub fn from_range(
+        line_start: Option<u32>,
+        line_end: Option<u32>,
+        content: &str,
+    ) -> Result<Self, String> {
+        let line_start = line_start.ok_or("Patch requires line_start")?;
+        let patch = Self {
+            line_start,
+            line_end: line_end.unwrap_or(line_start),
+            content: content.to_string(),
+        };
+        patch.check_range()?;
+        Ok(patch)
+    }
+
+    fn check_range(&self) -> Result<(), String> {
+        if self.line_start == 0 || self.line_end < self.line_start {
+            return Err(format!(
+                "Invalid line range {}-{}",
+                self.line_start, self.line_end
+            ));
+        }
+        Ok(())
+    }
+
+    fn new_lines(&self) -> Vec<&str> {
+        self.content.lines().collect()
+    }
+}
+
+/// Outcome of patching a script
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct PatchResult {
+    /// Resulting script as plain text, one element per line
+    pub text: String,
+    /// Unified diff of the change
+    pub diff: String,
+    /// Resulting Lexical JSON
+    pub content: String,
+    /// Script version after the patch (unchanged for previews)
+    pub version: u32,
+}
+
+/// Snapshot of a script taken before a patch
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ScriptRevision {
+    pub project_id: String,
+    /// Script version the snapshot holds
+    pub version: u32,
+    /// Lexical JSON before the patch
+    pub content: String,
+    pub created_at: String,
+}
+
+/// A script line containing a search term
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct ScriptMatch {
+    /// Line number, from 1 as in the Navigator
+    pub line: u32,
+    pub text: String,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// PATCHING
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Check every patch against a script of `line_count` lines; returns them sorted
+pub fn validate_patches(
+    line_count: usize,
+    patches: &[ScriptPatch],
+) -> Result<Vec<ScriptPatch>, String> {
+    if patches.is_empty() {
+        return Err("No patches to apply".into());
+    }
+
+    for patch in patches {
+        patch.check_range()?;
+        if patch.line_end as usize > line_count {
+            return Err(format!(
+                "Line range {}-{} is outside the script ({} lines)",
+                patch.line_start, patch.line_end, line_count
+            ));
+        }
+    }
+
+    let mut sorted = patches.to_vec();
+    sorted.sort_by_key(|p| p.line_start);
+    for pair in sorted.windows(2) {
+        if pair[1].line_start <= pair[0].line_end {
+            return Err(format!(
+                "Patches {}-{} and {}-{} overlap",
+                pair[0].line_start, pair[0].line_end, pair[1].line_start, pair[1].line_end
+            ));
+        }
+    }
+
+    Ok(sorted)
+}
+
+/// Apply patches to a Lexical script, returning the new JSON, text and diff.
+///
+/// Replacement lines take the element type of the first line they replace;
+/// inline token nodes in replaced lines become plain text.
+pub fn patch_script(lexical_json: &str, patches: &[ScriptPatch]) -> Result<PatchResult, String> {
+    let mut doc: Value =
+        serde_json::from_str(lexical_json).map_err(|e| format!("Invalid script JSON: {}", e))?;
+    let nodes = doc["root"]["children"]
+        .as_array_mut()
+        .ok_or("Script has no root children")?;
+
+    let old_lines: Vec<String> = nodes.iter().map(node_text).collect();
+    let patches = validate_patches(old_lines.len(), patches)?;
+
+    // Bottom-up so earlier line numbers stay valid
+    for patch in patches.iter().rev() {
+        let start = patch.line_start as usize - 1;
+        let end = patch.line_end as usize;
+        let template = nodes[start].clone();
+        let replacement: Vec<Value> = patch
+            .new_lines()
+            .into_iter()
+            .map(|line| element_node(&template, line))
+            .collect();
+        nodes.splice(start..end, replacement);
+    }
+
+    let text = nodes.iter().map(node_text).collect::<Vec<_>>().join("\n");
+    Ok(PatchResult {
+        text,
+        diff: unified_diff(&old_lines, &patches),
+        content: doc.to_string(),
+        version: 0,
+    })
+}
+
+/// Lines of a Lexical script containing `query`, ignoring case
+pub fn search_lines(lexical_json: &str, query: &str) -> Result<Vec<ScriptMatch>, String> {
+    let doc: Value =
+        serde_json::from_str(lexical_json).map_err(|e| format!("Invalid script JSON: {}", e))?;
+    let nodes = doc["root"]["children"]
+        .as_array()
+        .ok_or("Script has no root children")?;
+
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Err("Nothing to search for".into());
+    }
+    Ok(nodes
+        .iter()
+        .map(node_text)
+        .zip(1..)
+        .filter(|(text, _)| text.to_lowercase().contains(&query))
+        .map(|(text, line)| ScriptMatch { line, text })
+        .collect())
+}
+
+/// Concatenated text of a node and its descendants
+fn node_text(node: &Value) -> String {
+    let mut text = node["text"].as_str().unwrap_or_default().to_string();
+    if let Some(children) = node["children"].as_array() {
+        for child in children {
+            text.push_str(&node_text(child));
+        }
+    }
+    text
+}
+
+/// Copy of `template` holding a single text node
+fn element_node(template: &Value, text: &str) -> Value {
+    let mut node = template.clone();
+    node["children"] = if text.is_empty() {
+        json!([])
+    } else {
+        json!([{
+            "type": "text",
+            "text": text,
+            "detail": 0,
+            "format": 0,
+            "mode": "normal",
+            "style": "",
+            "version": 1
+        }])
+    };
+    node
+}
+
+/// Unified diff for sorted, validated patches over `old`
+pub fn unified_diff(old: &[String], patches: &[ScriptPatch]) -> String {
+    let mut diff = String::from("--- a/script\n+++ b/script\n");
+
+    // Patches whose context windows touch share a hunk
+    let mut groups: Vec<Vec<&ScriptPatch>> = Vec::new();
+    for patch in patches {
+        match groups.last_mut() {
+            Some(group)
+                if patch.line_start as usize
+                    <= group.last().unwrap().line_end as usize + 2 * CONTEXT_LINES + 1 =>
+            {
+                group.push(patch)
+            }
+            _ => groups.push(vec![patch]),
+        }
+    }
+
+    let mut offset: isize = 0;
+    for group in groups {
+        let first = group[0].line_start as usize;
+        let last = group.last().unwrap().line_end as usize;
+        let old_start = first.saturating_sub(CONTEXT_LINES).max(1);
+        let old_end = (last + CONTEXT_LINES).min(old.len());
+
+        let mut body = Vec::new();
+        let mut removed = 0;
+        let mut added = 0;
+        let mut line = old_start;
+        let mut pending = group.iter().peekable();
+        while line <= old_end {
+            match pending.next_if(|p| p.line_start as usize == line) {
+                Some(patch) => {
+                    for old_line in &old[line - 1..patch.line_end as usize] {
+                        body.push(format!("-{}", old_line));
+                        removed += 1;
+                    }
+                    for new_line in patch.new_lines() {
+                        body.push(format!("+{}", new_line));
+                        added += 1;
+                    }
+                    line = patch.line_end as usize + 1;
+                }
+                None => {
+                    body.push(format!(" {}", old[line - 1]));
+                    line += 1;
+                }
+            }
+        }
+
+        let old_len = old_end - old_start + 1;
+        let new_len = old_len + added - removed;
+        let mut new_start = (old_start as isize + offset) as usize;
+        if new_len == 0 {
+            new_start -= 1;
+        }
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start, old_len, new_start, new_len
+        ));
+        for line in body {
+            diff.push_str(&line);
+            diff.push('\n');
+        }
+        offset += added as isize - removed as isize;
+    }
+
+    diff
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// STORED SCRIPT
+// ═══════════════════════════════════════════════════════════════════════════════
+
+async fn load_script(project_id: &str) -> Result<Script, String> {
+    let db = super::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    let mut result = db
+        .query("SELECT * FROM script WHERE project_id = type::thing($pid)")
+        .bind(("pid", project_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+    let script: Option<Script> = result.take(0).map_err(|e| e.to_string())?;
+    script.ok_or_else(|| format!("No script for project {}", project_id))
+}
+
+async fn write_script(script: &Script, content: String, version: u32) -> Result<(), String> {
+    let db = super::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+    let id = script.id.clone().ok_or("Script has no id")?;
+
+    db.query("UPDATE $id SET content = $content, version = $version")
+        .bind(("id", id))
+        .bind(("content", content))
+        .bind(("version", version))
+        .await
+        .map_err(|e| e.to_string())?
+        .check()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Patch the stored script without saving it
+pub async fn preview_patches(
+    project_id: &str,
+    patches: &[ScriptPatch],
+) -> Result<PatchResult, String> {
+    let script = load_script(project_id).await?;
+    let mut result = patch_script(&script.content, patches)?;
+    result.version = script.version;
+    Ok(result)
+}
+
+/// Patch and save the stored script, keeping the previous content as a revision
+pub async fn apply_patches(
+    project_id: &str,
+    patches: &[ScriptPatch],
+) -> Result<PatchResult, String> {
+    let script = load_script(project_id).await?;
+    let mut result = patch_script(&script.content, patches)?;
+
+    let db = super::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+    let _: Option<ScriptRevision> = db
+        .create(REVISION_TABLE)
+        .content(ScriptRevision {
+            project_id: project_id.to_string(),
+            version: script.version,
+            content: script.content.clone(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+    result.version = script.version + 1;
+    write_script(&script, result.content.clone(), result.version).await?;
+    Ok(result)
+}
+
+/// Restore the content saved before the most recent patch.
+///
+/// Returns the restored revision, or `None` when there is nothing to undo.
+pub async fn undo_last_patch(project_id: &str) -> Result<Option<ScriptRevision>, String> {
+    let db = super::get_db()
+        .await
+        .ok_or_else(|| "Vault not initialized".to_string())?;
+
+    let mut result = db
+        .query(format!(
+            "SELECT project_id, version, content, created_at FROM {} \
+             WHERE project_id = $pid ORDER BY version DESC LIMIT 1",
+            REVISION_TABLE
+        ))
+        .bind(("pid", project_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+    let revision: Option<ScriptRevision> = result.take(0).map_err(|e| e.to_string())?;
+    let Some(revision) = revision else {
+        return Ok(None);
+    };
+
+    let script = load_script(project_id).await?;
+    write_script(&script, revision.content.clone(), script.version + 1).await?;
+
+    db.query(format!(
+        "DELETE {} WHERE project_id = $pid AND version = $version",
+        REVISION_TABLE
+    ))
+    .bind(("pid", project_id.to_string()))
+    .bind(("version", revision.version))
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(Some(revision))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(lines: &[(&str, &str)]) -> String {
+        let children: Vec<Value> = lines
+            .iter()
+            .map(|(node_type, text)| {
+                json!({
+                    "type": node_type,
+                    "children": [{"type": "text", "text": text}]
+                })
+            })
+            .collect();
+        json!({"root": {"type": "root", "children": children}}).to_string()
+    }
+
+    fn patch(line_start: u32, line_end: u32, content: &str) -> ScriptPatch {
+        ScriptPatch {
+            line_start,
+            line_end,
+            content: content.into(),
+        }
+    }
+
+    #[test]
+    fn test_search_lines() {
+        let doc = script(&[
+            ("scene-heading", "INT. OFFICE - DAY"),
+            ("action", "John enters."),
+            ("character", "JOHN"),
+            ("dialogue", "Hello."),
+        ]);
+
+        let matches = search_lines(&doc, "john").unwrap();
+        assert_eq!(matches.iter().map(|m| m.line).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(matches[0].text, "John enters.");
+        assert!(search_lines(&doc, "  ").is_err());
+    }
+
+    #[test]
+    fn test_valid_patch() {
+        let doc = script(&[
+            ("scene-heading", "INT. OFFICE - DAY"),
+            ("action", "John enters."),
+            ("character", "JOHN"),
+            ("dialogue", "Hello."),
+        ]);
+
+        let result = patch_script(
+            &doc,
+            &[
+                patch(4, 4, "Good morning."),
+                patch(2, 2, "John storms in.\nHe slams the door."),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.text,
+            "INT. OFFICE - DAY\nJohn storms in.\nHe slams the door.\nJOHN\nGood morning."
+        );
+        assert_eq!(
+            result.diff,
+            "--- a/script\n+++ b/script\n@@ -1,4 +1,5 @@\n INT. OFFICE - DAY\n\
+             -John enters.\n+John storms in.\n+He slams the door.\n JOHN\n\
+             -Hello.\n+Good morning.\n"
+        );
+
+        // New lines keep the element type of the line they replace
+        let doc: Value = serde_json::from_str(&result.content).unwrap();
+        let types: Vec<_> = doc["root"]["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| n["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            ["scene-heading", "action", "action", "character", "dialogue"]
+        );
+    }
+
+    #[test]
+    fn test_delete_lines() {
+        let doc = script(&[("action", "One."), ("action", "Two."), ("action", "Three.")]);
+        let result = patch_script(&doc, &[patch(2, 3, "")]).unwrap();
+
+        assert_eq!(result.text, "One.");
+        assert!(result
+            .diff
+            .contains("@@ -1,3 +1,1 @@\n One.\n-Two.\n-Three.\n"));
+    }
+
+    #[test]
+    fn test_out_of_range_patch() {
+        let doc = script(&[("action", "One."), ("action", "Two.")]);
+
+        let err = patch_script(&doc, &[patch(2, 3, "Nope")]).unwrap_err();
+        assert!(err.contains("outside the script (2 lines)"));
+        assert!(patch_script(&doc, &[patch(0, 1, "Nope")]).is_err());
+        assert!(patch_script(&doc, &[patch(2, 1, "Nope")]).is_err());
+        assert!(ScriptPatch::from_range(None, Some(2), "Nope").is_err());
+    }
+
+    #[test]
+    fn test_overlapping_patches_rejected() {
+        let err = validate_patches(10, &[patch(5, 7, "a"), patch(2, 5, "b")]).unwrap_err();
+        assert_eq!(err, "Patches 2-5 and 5-7 overlap");
+
+        let sorted = validate_patches(10, &[patch(6, 7, "a"), patch(2, 5, "b")]).unwrap();
+        assert_eq!(sorted[0].line_start, 2);
+    }
+}
@@ -13,6 +13,43 @@
 
 use serde::{Deserialize, Serialize};
 use specta::Type;
+use std::sync::atomic::{AtomicBool, Ordering};
+use surrealdb::engine::any::Any;
+use surrealdb::Surreal;
+
+use super::or_empty;
+
+/// Most results a search returns
+const SEARCH_LIMIT: u32 = 50;
+
+/// Full-text indexes on token names and descriptions (word prefixes match)
+const DEFINE_SEARCH_INDEX: &str = "\
+    DEFINE ANALYZER IF NOT EXISTS token_search TOKENIZERS class \
+        FILTERS lowercase, ascii, edgengram(2, 10); \
+    DEFINE INDEX IF NOT EXISTS token_name_search ON TABLE token \
+        FIELDS name SEARCH ANALYZER token_search BM25; \
+    DEFINE INDEX IF NOT EXISTS token_description_search ON TABLE token \
+        FIELDS description SEARCH ANALYZER token_search BM25;";
+
+/// Set once `define_search_index` succeeds
+static SEARCH_INDEXED: AtomicBool = AtomicBool::new(false);
+
+/// Titles ignored when matching names, with their long forms
+const NAME_TITLES: &[(&str, &[&str])] = &[
+    ("DET", &["DETECTIVE"]),
+    ("DR", &["DOCTOR"]),
+    ("MR", &["MISTER"]),
+    ("MRS", &[]),
+    ("MS", &["MISS"]),
+    ("SGT", &["SERGEANT"]),
+    ("CAPT", &["CAPTAIN"]),
+    ("LT", &["LIEUTENANT"]),
+    ("PROF", &["PROFESSOR"]),
+    ("OFFICER", &[]),
+    ("AGENT", &[]),
+    ("SIR", &[]),
+    ("LADY", &[]),
+];
 
 /// Token type enum
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Type)]
@@ -62,6 +99,9 @@ pub struct Token {
     pub voice_id: Option<String>, // ElevenLabs voice (characters)
     #[serde(default)]
     pub metadata: std::collections::HashMap<String, String>,
+    /// Other names the script uses (characters)
+    #[serde(default)]
+    pub aliases: Vec<String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -92,6 +132,7 @@ impl Token {
             lora_id: None,
             voice_id: None,
             metadata: std::collections::HashMap::new(),
+            aliases: Vec::new(),
             created_at: now.clone(),
             updated_at: now,
         }
@@ -130,6 +171,9 @@ pub struct ExtractedTokens {
     pub characters: Vec<ExtractedEntity>,
     pub locations: Vec<ExtractedEntity>,
     pub props: Vec<ExtractedEntity>,
+    /// Character names that look like aliases, for the user to confirm
+    #[serde(default)]
+    pub proposed_merges: Vec<AliasMerge>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -138,6 +182,129 @@ pub struct ExtractedEntity {
     pub description: String,
     pub mentions: u32,            // How many times mentioned
     pub first_appearance: String, // Scene/line reference
+    /// Other names for the same entity (e.g. "SARAH" for "DET. SARAH JONES")
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+/// Names folded into `canonical`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct AliasMerge {
+    pub canonical: String,
+    pub aliases: Vec<String>,
+}
+
+/// A name split into titles and name words: "Det. Sarah Jones" is
+/// (["DET"], ["SARAH", "JONES"])
+fn split_name(name: &str) -> (Vec<&'static str>, Vec<String>) {
+    let mut titles = Vec::new();
+    let mut words = Vec::new();
+    for word in name.split_whitespace() {
+        let word = word
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .to_uppercase();
+        let title = NAME_TITLES
+            .iter()
+            .find(|(short, long)| *short == word || long.contains(&word.as_str()));
+        match title {
+            Some((short, _)) => titles.push(*short),
+            None if !word.is_empty() => words.push(word),
+            None => {}
+        }
+    }
+    // "THE DOCTOR" style names are all title
+    if words.is_empty() {
+        words = titles.iter().map(|t| t.to_string()).collect();
+        titles.clear();
+    }
+    (titles, words)
+}
+
+impl ExtractedTokens {
+    /// Propose folding each character whose name words all belong to exactly
+    /// one fuller name into it ("SARAH" and "DET. JONES" into
+    /// "DET. SARAH JONES"); nothing is merged until [`Self::apply_merges`]
+    ///
+    /// Different titles keep names apart (MR. JONES and MRS. JONES), and so
+    /// does a short name that fits several fuller ones.
+    pub fn propose_alias_merges(&self) -> Vec<AliasMerge> {
+        let mut characters: Vec<&ExtractedEntity> = self.characters.iter().collect();
+        // Fullest names first: most name words, then titles included
+        characters.sort_by_cached_key(|c| {
+            let (titles, words) = split_name(&c.name);
+            std::cmp::Reverse((words.len(), titles.len(), c.mentions))
+        });
+
+        let mut groups: Vec<(Vec<&'static str>, Vec<String>, AliasMerge)> = Vec::new();
+        for entity in characters {
+            let (titles, words) = split_name(&entity.name);
+            let owners: Vec<usize> = groups
+                .iter()
+                .enumerate()
+                .filter(|(_, (full_titles, full_words, _))| {
+                    let titles_agree = titles.is_empty()
+                        || full_titles.is_empty()
+                        || titles.iter().any(|t| full_titles.contains(t));
+                    titles_agree && words.iter().all(|w| full_words.contains(w))
+                })
+                .map(|(i, _)| i)
+                .collect();
+
+            match owners[..] {
+                [owner] => {
+                    let (full_titles, _, merge) = &mut groups[owner];
+                    if full_titles.is_empty() {
+                        full_titles.extend(titles);
+                    }
+                    merge.aliases.push(entity.name.clone());
+                }
+                _ => groups.push((
+                    titles,
+                    words,
+                    AliasMerge {
+                        canonical: entity.name.clone(),
+                        aliases: Vec::new(),
+                    },
+                )),
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(_, _, merge)| merge)
+            .filter(|merge| !merge.aliases.is_empty())
+            .collect()
+    }
+
+    /// Fold the characters each accepted merge names into its canonical one,
+    /// summing mentions and keeping the folded names as aliases
+    pub fn apply_merges(&mut self, merges: &[AliasMerge]) {
+        for merge in merges {
+            if !self.characters.iter().any(|c| c.name == merge.canonical) {
+                continue;
+            }
+            let (folded, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.characters)
+                .into_iter()
+                .partition(|c| c.name != merge.canonical && merge.aliases.contains(&c.name));
+            self.characters = kept;
+
+            let Some(canonical) = self
+                .characters
+                .iter_mut()
+                .find(|c| c.name == merge.canonical)
+            else {
+                continue;
+            };
+            for entity in folded {
+                canonical.mentions += entity.mentions;
+                if canonical.description.is_empty() {
+                    canonical.description = entity.description;
+                }
+                canonical.aliases.push(entity.name);
+                canonical.aliases.extend(entity.aliases);
+            }
+        }
+    }
 }
 
 /// For prompt enhancement in Studio
@@ -168,6 +335,303 @@ impl From<Token> for TokenContext {
     }
 }
 
+/// Inject Vault tokens into a generation prompt: each token's look is
+/// appended to `prompt`, and LoRA triggers go last so they all activate
+pub fn build_generation_prompt(prompt: &str, tokens: &[TokenContext]) -> String {
+    let mut parts = vec![prompt.to_string()];
+    let mut loras = Vec::new();
+
+    for token in tokens {
+        let name = token.display_name.trim_start_matches(['@', '/', '#']);
+        let look = token.visual_prompt.as_ref().unwrap_or(&token.description);
+        parts.push(format!("{}: {}", name, look));
+        loras.extend(token.lora_trigger.clone());
+    }
+
+    let mut prompt = parts.join(". ");
+    if !loras.is_empty() {
+        prompt.push(' ');
+        prompt.push_str(&loras.join(" "));
+    }
+    prompt
+}
+
+/// Store a new token
+pub async fn insert_token(db: &Surreal<Any>, token: Token) -> Result<Token, String> {
+    let created: Option<Token> = db
+        .create("token")
+        .content(token)
+        .await
+        .map_err(|e| e.to_string())?;
+    created.ok_or_else(|| "Failed to create token".to_string())
+}
+
+/// All of a project's tokens, by type then name
+pub async fn project_tokens(db: &Surreal<Any>, project_id: &str) -> Result<Vec<Token>, String> {
+    let mut result = db
+        .query("SELECT * FROM token WHERE project_id = $pid ORDER BY token_type, name")
+        .bind(("pid", project_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+    or_empty(result.take(0))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// SEARCH
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Create the full-text indexes `search_tokens` prefers
+pub async fn define_search_index(db: &Surreal<Any>) -> Result<(), String> {
+    db.query(DEFINE_SEARCH_INDEX)
+        .await
+        .and_then(|response| response.check())
+        .map_err(|e| e.to_string())?;
+    SEARCH_INDEXED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Tokens whose name or description matches `query`, name matches first
+///
+/// Uses the full-text index when there is one; substring matching covers a
+/// missing index and queries that start mid-word.
+pub async fn search_tokens(
+    db: &Surreal<Any>,
+    project_id: &str,
+    query: &str,
+    token_type: Option<TokenType>,
+) -> Result<Vec<Token>, String> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let filter = "project_id = $pid AND ($ttype = NONE OR token_type = $ttype)";
+    if SEARCH_INDEXED.load(Ordering::Relaxed) {
+        let full_text = format!(
+            "SELECT *, search::score(0) AS name_score, search::score(1) AS description_score \
+             FROM token WHERE {} AND (name @0@ $q OR description @1@ $q) \
+             ORDER BY name_score DESC, description_score DESC LIMIT {}",
+            filter, SEARCH_LIMIT
+        );
+        match run_search(db, &full_text, project_id, &query, token_type.clone()).await {
+            Ok(tokens) if !tokens.is_empty() => return Ok(tokens),
+            Ok(_) => {}
+            Err(e) => tracing::debug!("Full-text token search failed: {}", e),
+        }
+    }
+
+    let substring = format!(
+        "SELECT *, string::contains(string::lowercase(name), $q) AS name_match \
+         FROM token WHERE {} AND (string::contains(string::lowercase(name), $q) \
+         OR string::contains(string::lowercase(description), $q)) \
+         ORDER BY name_match DESC, name LIMIT {}",
+        filter, SEARCH_LIMIT
+    );
+    run_search(db, &substring, project_id, &query, token_type).await
+}
+
+async fn run_search(
+    db: &Surreal<Any>,
+    sql: &str,
+    project_id: &str,
+    query: &str,
+    token_type: Option<TokenType>,
+) -> Result<Vec<Token>, String> {
+    let mut result = db
+        .query(sql)
+        .bind(("pid", project_id.to_string()))
+        .bind(("q", query.to_string()))
+        .bind(("ttype", token_type.map(|t| format!("{:?}", t))))
+        .await
+        .map_err(|e| e.to_string())?;
+    or_empty(result.take(0))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// GRAPH
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Kind of link between two tokens, stored as a SurrealDB edge table
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Type)]
+pub enum TokenRelation {
+    /// Character or prop -> location or scene
+    AppearsIn,
+    /// Prop -> character
+    OwnedBy,
+    /// Character -> character
+    Knows,
+    /// Location -> location (a room in a building)
+    PartOf,
+}
+
+impl TokenRelation {
+    pub const ALL: [TokenRelation; 4] = [
+        TokenRelation::AppearsIn,
+        TokenRelation::OwnedBy,
+        TokenRelation::Knows,
+        TokenRelation::PartOf,
+    ];
+
+    /// Edge table name
+    pub fn table(self) -> &'static str {
+        match self {
+            TokenRelation::AppearsIn => "appears_in",
+            TokenRelation::OwnedBy => "owned_by",
+            TokenRelation::Knows => "knows",
+            TokenRelation::PartOf => "part_of",
+        }
+    }
+
+    pub fn from_table(table: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.table() == table)
+    }
+
+    /// Whether a `from` token may link to a `to` token with this relation
+    pub fn allows(self, from: &TokenType, to: &TokenType) -> bool {
+        use TokenType::*;
+        match self {
+            TokenRelation::AppearsIn => {
+                matches!(from, Character | Prop) && matches!(to, Location | Scene)
+            }
+            TokenRelation::OwnedBy => *from == Prop && *to == Character,
+            TokenRelation::Knows => *from == Character && *to == Character,
+            TokenRelation::PartOf => *from == Location && *to == Location,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct TokenEdge {
+    pub from: String,
+    pub relation: TokenRelation,
+    pub to: String,
+}
+
+/// A project's tokens and the links between them
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct TokenGraph {
+    pub nodes: Vec<Token>,
+    pub edges: Vec<TokenEdge>,
+}
+
+/// Every edge table, for queries over all relations
+fn edge_tables(relation: Option<TokenRelation>) -> String {
+    match relation {
+        Some(relation) => relation.table().to_string(),
+        None => TokenRelation::ALL.map(TokenRelation::table).join(", "),
+    }
+}
+
+async fn token_by_id(db: &Surreal<Any>, token_id: &str) -> Result<Token, String> {
+    let mut result = db
+        .query("SELECT * FROM type::thing($id)")
+        .bind(("id", token_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+    let token: Option<Token> = or_empty(result.take(0))?;
+    token.ok_or_else(|| format!("Token not found: {}", token_id))
+}
+
+/// Link two tokens of the same project (linking twice keeps one edge)
+pub async fn link_tokens(
+    db: &Surreal<Any>,
+    from_id: &str,
+    relation: TokenRelation,
+    to_id: &str,
+) -> Result<TokenEdge, String> {
+    let from = token_by_id(db, from_id).await?;
+    let to = token_by_id(db, to_id).await?;
+    if from.project_id != to.project_id {
+        return Err("Tokens belong to different projects".to_string());
+    }
+    if !relation.allows(&from.token_type, &to.token_type) {
+        return Err(format!(
+            "A {:?} can't be linked to a {:?} with {:?}",
+            from.token_type, to.token_type, relation
+        ));
+    }
+
+    // The edge table comes from the enum; ids are bound
+    let query = format!(
+        "LET $from = type::thing($from_id); LET $to = type::thing($to_id); \
+         IF (SELECT VALUE id FROM {table} WHERE in = $from AND out = $to) = [] {{ \
+             RELATE $from->{table}->$to; \
+         }};",
+        table = relation.table()
+    );
+    db.query(query)
+        .bind(("from_id", from_id.to_string()))
+        .bind(("to_id", to_id.to_string()))
+        .await
+        .and_then(|response| response.check())
+        .map_err(|e| e.to_string())?;
+
+    Ok(TokenEdge {
+        from: from_id.to_string(),
+        relation,
+        to: to_id.to_string(),
+    })
+}
+
+/// Tokens linked to `token_id` in either direction, optionally by one relation
+pub async fn related_tokens(
+    db: &Surreal<Any>,
+    token_id: &str,
+    relation: Option<TokenRelation>,
+) -> Result<Vec<Token>, String> {
+    let query = format!(
+        "LET $token = type::thing($id); \
+         SELECT * FROM token \
+         WHERE id IN (SELECT VALUE out FROM {tables} WHERE in = $token) \
+            OR id IN (SELECT VALUE in FROM {tables} WHERE out = $token) \
+         ORDER BY token_type, name;",
+        tables = edge_tables(relation)
+    );
+    let mut result = db
+        .query(query)
+        .bind(("id", token_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+    or_empty(result.take(1))
+}
+
+#[derive(Deserialize)]
+struct EdgeRow {
+    from: String,
+    relation: String,
+    to: String,
+}
+
+/// Nodes and edges of a project's token graph
+pub async fn token_graph(db: &Surreal<Any>, project_id: &str) -> Result<TokenGraph, String> {
+    let query = format!(
+        "SELECT * FROM token WHERE project_id = $pid ORDER BY token_type, name; \
+         SELECT type::string(in) AS from, meta::tb(id) AS relation, type::string(out) AS to \
+         FROM {} WHERE in.project_id = $pid;",
+        edge_tables(None)
+    );
+    let mut result = db
+        .query(query)
+        .bind(("pid", project_id.to_string()))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let nodes: Vec<Token> = or_empty(result.take(0))?;
+    let rows: Vec<EdgeRow> = or_empty(result.take(1))?;
+    let edges = rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(TokenEdge {
+                relation: TokenRelation::from_table(&row.relation)?,
+                from: row.from,
+                to: row.to,
+            })
+        })
+        .collect();
+
+    Ok(TokenGraph { nodes, edges })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -209,4 +673,256 @@ mod tests {
         assert_eq!(context.display_name, "/Bar De La Ciutat");
         assert_eq!(context.lora_trigger, Some("<lora:bar_lora_v1>".into()));
     }
+
+    #[test]
+    fn test_build_generation_prompt() {
+        let mut anna = Token::new(
+            "project:123".into(),
+            TokenType::Character,
+            "Anna".into(),
+            "A tired detective in her 30s".into(),
+        );
+        anna.lora_id = Some("anna_v2".into());
+        let bar = Token::new(
+            "project:123".into(),
+            TokenType::Location,
+            "Bar".into(),
+            "A dimly lit bar with neon signs".into(),
+        );
+
+        let prompt = build_generation_prompt(
+            "Rain on the window",
+            &[TokenContext::from(anna), TokenContext::from(bar)],
+        );
+        assert_eq!(
+            prompt,
+            "Rain on the window. Anna: A tired detective in her 30s. \
+             Bar: A dimly lit bar with neon signs <lora:anna_v2>"
+        );
+        assert_eq!(build_generation_prompt("Rain", &[]), "Rain");
+    }
+
+    fn character(name: &str, mentions: u32) -> ExtractedEntity {
+        ExtractedEntity {
+            name: name.into(),
+            description: String::new(),
+            mentions,
+            first_appearance: "INT. PRECINCT - DAY".into(),
+            aliases: Vec::new(),
+        }
+    }
+
+    fn merge(names: &[(&str, u32)]) -> ExtractedTokens {
+        let mut tokens = ExtractedTokens {
+            characters: names.iter().map(|(n, m)| character(n, *m)).collect(),
+            locations: Vec::new(),
+            props: Vec::new(),
+            proposed_merges: Vec::new(),
+        };
+        tokens.proposed_merges = tokens.propose_alias_merges();
+        let accepted = tokens.proposed_merges.clone();
+        tokens.apply_merges(&accepted);
+        tokens
+    }
+
+    #[test]
+    fn test_first_and_last_names_merge_into_full_name() {
+        let tokens = merge(&[("SARAH", 12), ("DET. SARAH JONES", 1), ("JONES", 4)]);
+
+        assert_eq!(tokens.characters.len(), 1);
+        let sarah = &tokens.characters[0];
+        assert_eq!(sarah.name, "DET. SARAH JONES");
+        assert_eq!(sarah.mentions, 17);
+        assert_eq!(
+            tokens.proposed_merges,
+            [AliasMerge {
+                canonical: "DET. SARAH JONES".into(),
+                aliases: vec!["SARAH".into(), "JONES".into()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_only_accepted_merges_are_applied() {
+        let mut tokens = ExtractedTokens {
+            characters: vec![
+                character("SARAH", 12),
+                character("DET. SARAH JONES", 1),
+                character("HANNAH MILLER", 2),
+                character("MILLER", 3),
+            ],
+            locations: Vec::new(),
+            props: Vec::new(),
+            proposed_merges: Vec::new(),
+        };
+
+        // Proposing leaves the characters alone
+        let proposed = tokens.propose_alias_merges();
+        assert_eq!(proposed.len(), 2);
+        assert_eq!(tokens.characters.len(), 4);
+
+        // The user rejects MILLER as Hannah
+        let accepted: Vec<_> = proposed
+            .into_iter()
+            .filter(|m| m.canonical == "DET. SARAH JONES")
+            .collect();
+        tokens.apply_merges(&accepted);
+        let names: Vec<_> = tokens.characters.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["DET. SARAH JONES", "HANNAH MILLER", "MILLER"]);
+        assert_eq!(tokens.characters[0].mentions, 13);
+        assert_eq!(tokens.characters[0].aliases, ["SARAH"]);
+    }
+
+    #[test]
+    fn test_titles_and_ambiguous_names_stay_apart() {
+        // Long and short titles are the same; a different title is someone else
+        let tokens = merge(&[
+            ("DR. MILLER", 3),
+            ("DOCTOR HANNAH MILLER", 1),
+            ("MR. PARK", 2),
+            ("MRS. PARK", 2),
+        ]);
+        let names: Vec<_> = tokens.characters.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["DOCTOR HANNAH MILLER", "MR. PARK", "MRS. PARK"]);
+        assert_eq!(tokens.characters[0].aliases, ["DR. MILLER"]);
+
+        // Two Sarahs: a bare SARAH could be either
+        let tokens = merge(&[("SARAH JONES", 1), ("SARAH CONNOR", 1), ("SARAH", 5)]);
+        assert_eq!(tokens.characters.len(), 3);
+        assert!(tokens.proposed_merges.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_ranks_name_matches_first() {
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+
+        let seed = [
+            (
+                TokenType::Character,
+                "Marlowe",
+                "A detective who loves Sarah",
+            ),
+            (TokenType::Character, "Sarah", "A singer at the bar"),
+            (TokenType::Location, "Sarah's Apartment", "Small and tidy"),
+            (TokenType::Prop, "Revolver", "Marlowe's old gun"),
+        ];
+        for (token_type, name, description) in seed {
+            let token = Token::new(
+                "project:p1".into(),
+                token_type,
+                name.into(),
+                description.into(),
+            );
+            let _: Option<Token> = db.create("token").content(token).await.unwrap();
+        }
+        let other = Token::new(
+            "project:p2".into(),
+            TokenType::Character,
+            "Sarah".into(),
+            String::new(),
+        );
+        let _: Option<Token> = db.create("token").content(other).await.unwrap();
+
+        let names = |tokens: Vec<Token>| tokens.into_iter().map(|t| t.name).collect::<Vec<_>>();
+
+        // Substring search before the index exists
+        let found = names(
+            search_tokens(&db, "project:p1", " SARAH", None)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(found.len(), 3);
+        assert_eq!(found.last().map(String::as_str), Some("Marlowe"));
+
+        define_search_index(&db).await.unwrap();
+        let found = names(
+            search_tokens(&db, "project:p1", "sarah", None)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(found.len(), 3);
+        assert_eq!(found.last().map(String::as_str), Some("Marlowe"));
+
+        let characters = search_tokens(&db, "project:p1", "sarah", Some(TokenType::Character))
+            .await
+            .unwrap();
+        assert_eq!(names(characters), ["Sarah", "Marlowe"]);
+
+        // Mid-word queries fall back to substring matching
+        let found = names(
+            search_tokens(&db, "project:p1", "olver", None)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(found, ["Revolver"]);
+        assert!(search_tokens(&db, "project:p1", "  ", None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_relations_check_token_types() {
+        use TokenType::*;
+        assert!(TokenRelation::AppearsIn.allows(&Character, &Location));
+        assert!(TokenRelation::OwnedBy.allows(&Prop, &Character));
+        assert!(!TokenRelation::OwnedBy.allows(&Character, &Prop));
+        assert!(!TokenRelation::Knows.allows(&Character, &Location));
+        for relation in TokenRelation::ALL {
+            assert_eq!(TokenRelation::from_table(relation.table()), Some(relation));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_graph() {
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+        db.query(
+            "CREATE token:sarah SET project_id = 'project:p1', token_type = 'Character', \
+                 name = 'Sarah', slug = '@sarah', description = '', \
+                 created_at = '', updated_at = ''; \
+             CREATE token:bar SET project_id = 'project:p1', token_type = 'Location', \
+                 name = 'Bar', slug = '/bar', description = '', \
+                 created_at = '', updated_at = ''; \
+             CREATE token:revolver SET project_id = 'project:p1', token_type = 'Prop', \
+                 name = 'Revolver', slug = '#revolver', description = '', \
+                 created_at = '', updated_at = '';",
+        )
+        .await
+        .unwrap();
+
+        link_tokens(&db, "token:sarah", TokenRelation::AppearsIn, "token:bar")
+            .await
+            .unwrap();
+        // Linking again doesn't duplicate the edge
+        link_tokens(&db, "token:sarah", TokenRelation::AppearsIn, "token:bar")
+            .await
+            .unwrap();
+        link_tokens(&db, "token:revolver", TokenRelation::OwnedBy, "token:sarah")
+            .await
+            .unwrap();
+        assert!(
+            link_tokens(&db, "token:bar", TokenRelation::OwnedBy, "token:sarah")
+                .await
+                .is_err()
+        );
+
+        let related = related_tokens(&db, "token:sarah", None).await.unwrap();
+        let names: Vec<_> = related.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["Bar", "Revolver"]);
+        let owners = related_tokens(&db, "token:revolver", Some(TokenRelation::OwnedBy))
+            .await
+            .unwrap();
+        assert_eq!(owners.len(), 1);
+
+        let graph = token_graph(&db, "project:p1").await.unwrap();
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.edges.len(), 2);
+        assert!(graph.edges.contains(&TokenEdge {
+            from: "token:sarah".into(),
+            relation: TokenRelation::AppearsIn,
+            to: "token:bar".into(),
+        }));
+    }
 }
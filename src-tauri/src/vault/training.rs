@@ -0,0 +1,262 @@
+//! LoRA Training Datasets — Package token references for Fal trainers
+//!
+//! A dataset is a zip archive with one caption file per image:
+//! - `00.png`, `01.jpg`, …: the token's visual references
+//! - `00.txt`, `01.txt`, …: caption built from the trigger word and description
+//!
+//! References may be local paths, `file://` paths, http(s) URLs or data URLs.
+//! Unreadable or non-image references are skipped before the count check.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::io::{Seek, Write};
+use zip::write::SimpleFileOptions;
+
+use super::assets::project_assets_dir;
+use super::tokens::Token;
+use crate::ai::fal_client::FalClient;
+use crate::comfyui::models::CloudModels;
+
+/// Fewest images the trainer gets reasonable likeness from
+pub const MIN_TRAINING_IMAGES: usize = 4;
+
+/// Portrait training usually finishes well within this
+const TRAINING_TIMEOUT_SECS: u64 = 60 * 60;
+
+/// A dataset archive ready to upload to a trainer
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct TrainingDataset {
+    pub token_id: String,
+    pub archive_path: String,
+    pub image_count: u32,
+    /// Word the captions (and later prompts) use for the subject
+    pub trigger_word: String,
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// CAPTIONS
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// Trigger word for a token, e.g. `anna` for `@anna`
+pub fn trigger_word(token: &Token) -> String {
+    let word = token.slug.trim_start_matches(['@', '/', '#']);
+    if word.is_empty() {
+        token.name.to_lowercase().replace(' ', "-")
+    } else {
+        word.to_string()
+    }
+}
+
+/// Caption written next to every image
+pub fn caption(token: &Token, trigger: &str) -> String {
+    let description = token
+        .description
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if description.is_empty() {
+        format!("a photo of {}", trigger)
+    } else {
+        format!("a photo of {}, {}", trigger, description)
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// ARCHIVE
+// ═══════════════════════════════════════════════════════════════════════════════
+
+/// File extension for supported image bytes
+pub fn image_extension(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0x89, b'P', b'N', b'G']) {
+        Some("png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpg")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("webp")
+    } else {
+        None
+    }
+}
+
+/// Write images and their captions; returns the writer and the image count
+pub fn write_dataset<W: Write + Seek>(
+    writer: W,
+    images: &[Vec<u8>],
+    caption: &str,
+) -> Result<(W, usize), String> {
+    let images: Vec<(&Vec<u8>, &str)> = images
+        .iter()
+        .filter_map(|bytes| image_extension(bytes).map(|ext| (bytes, ext)))
+        .collect();
+    if images.len() < MIN_TRAINING_IMAGES {
+        return Err(format!(
+            "LoRA training needs at least {} reference images, found {}",
+            MIN_TRAINING_IMAGES,
+            images.len()
+        ));
+    }
+
+    let mut zip = zip::ZipWriter::new(writer);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let mut add = |name: &str, bytes: &[u8]| -> Result<(), String> {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(bytes).map_err(|e| e.to_string())
+    };
+
+    for (i, (bytes, ext)) in images.iter().enumerate() {
+        add(&format!("{:02}.{}", i, ext), bytes)?;
+        add(&format!("{:02}.txt", i), caption.as_bytes())?;
+    }
+
+    let writer = zip.finish().map_err(|e| e.to_string())?;
+    Ok((writer, images.len()))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// EXPORT / TRAINING
+// ═══════════════════════════════════════════════════════════════════════════════
+
+async fn read_reference(source: &str) -> Result<Vec<u8>, String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let resp = reqwest::get(source)
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| e.to_string())?;
+        return Ok(resp.bytes().await.map_err(|e| e.to_string())?.to_vec());
+    }
+    if let Some(data) = source.strip_prefix("data:") {
+        let (_, encoded) = data.split_once(";base64,").ok_or("Unsupported data URL")?;
+        return STANDARD.decode(encoded).map_err(|e| e.to_string());
+    }
+    std::fs::read(source.strip_prefix("file://").unwrap_or(source)).map_err(|e| e.to_string())
+}
+
+/// Zip a token's references with captions into the project's training folder
+pub async fn export_training_dataset(token: &Token) -> Result<TrainingDataset, String> {
+    let token_id = token.id.clone().ok_or("Token has no id")?;
+
+    let mut images = Vec::new();
+    for source in &token.visual_refs {
+        match read_reference(source).await {
+            Ok(bytes) => images.push(bytes),
+            Err(e) => tracing::warn!("Skipping training image {}: {}", source, e),
+        }
+    }
+
+    let trigger = trigger_word(token);
+    let dir = project_assets_dir(&token.project_id).join("training");
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let file_name: String = trigger
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let path = dir.join(format!("{}.zip", file_name));
+
+    let (archive, image_count) = write_dataset(
+        std::io::Cursor::new(Vec::new()),
+        &images,
+        &caption(token, &trigger),
+    )?;
+    std::fs::write(&path, archive.into_inner()).map_err(|e| e.to_string())?;
+
+    Ok(TrainingDataset {
+        token_id,
+        archive_path: path.display().to_string(),
+        image_count: image_count as u32,
+        trigger_word: trigger,
+    })
+}
+
+/// Train a portrait LoRA on Fal from a dataset; returns the LoRA weights URL
+pub async fn train_lora(dataset: &TrainingDataset) -> Result<String, String> {
+    let api_key = std::env::var("FAL_KEY").map_err(|_| "FAL_KEY not set".to_string())?;
+    let archive = std::fs::read(&dataset.archive_path).map_err(|e| e.to_string())?;
+
+    let client = FalClient::new(api_key);
+    let queued = client
+        .submit(
+            CloudModels::FLUX_LORA_PORTRAIT_TRAINER,
+            serde_json::json!({
+                "images_data_url": format!("data:application/zip;base64,{}", STANDARD.encode(archive)),
+                "trigger_phrase": dataset.trigger_word,
+            }),
+        )
+        .await?;
+
+    let output = client
+        .poll_output(&queued.request_id, TRAINING_TIMEOUT_SECS)
+        .await?;
+    output["diffusers_lora_file"]["url"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "Trainer returned no LoRA file".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::tokens::TokenType;
+    use std::io::{Cursor, Read};
+
+    const PNG: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0];
+
+    fn anna() -> Token {
+        Token::new(
+            "project:film".into(),
+            TokenType::Character,
+            "Anna".into(),
+            "Woman in her 30s,\n  short red hair".into(),
+        )
+    }
+
+    #[test]
+    fn test_captions_use_trigger_word() {
+        let token = anna();
+        let trigger = trigger_word(&token);
+        assert_eq!(trigger, "anna");
+        assert_eq!(
+            caption(&token, &trigger),
+            "a photo of anna, Woman in her 30s, short red hair"
+        );
+    }
+
+    #[test]
+    fn test_dataset_pairs_images_with_captions() {
+        let images = vec![
+            PNG.to_vec(),
+            JPEG.to_vec(),
+            b"not an image".to_vec(),
+            PNG.to_vec(),
+            JPEG.to_vec(),
+        ];
+        let (cursor, count) =
+            write_dataset(Cursor::new(Vec::new()), &images, "a photo of anna").unwrap();
+        assert_eq!(count, 4);
+
+        let mut archive = zip::ZipArchive::new(cursor).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["00.png", "00.txt", "01.jpg", "01.txt", "02.png", "02.txt", "03.jpg", "03.txt"]
+        );
+
+        let mut caption = String::new();
+        archive
+            .by_name("01.txt")
+            .unwrap()
+            .read_to_string(&mut caption)
+            .unwrap();
+        assert_eq!(caption, "a photo of anna");
+    }
+
+    #[test]
+    fn test_dataset_requires_minimum_images() {
+        let images = vec![PNG.to_vec(); MIN_TRAINING_IMAGES - 1];
+        let err = write_dataset(Cursor::new(Vec::new()), &images, "x").unwrap_err();
+        assert!(err.contains("at least 4"));
+    }
+}
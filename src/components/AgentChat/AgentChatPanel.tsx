@@ -33,10 +33,13 @@ export const AgentChatPanel: FC<AgentChatPanelProps> = ({
     error,
     agentRole,
     pendingActions,
+    blockedActions,
     sendMessage,
     setAgentRole,
     clearHistory,
     executeAction,
+    confirmAction,
+    dismissAction,
   } = useAgentChat({
     autoExecute: false,
   });
@@ -77,6 +80,15 @@ export const AgentChatPanel: FC<AgentChatPanelProps> = ({
     }
   }, [executeAction, onUpdateScript]);
 
+  const handleConfirmClick = useCallback(async (action: typeof pendingActions[0]) => {
+    const result = await confirmAction(action);
+
+    if (result.success && result.data && action.type === 'UpdateScript') {
+      const data = JSON.parse(result.data);
+      onUpdateScript?.(data.content, data.mode);
+    }
+  }, [confirmAction, onUpdateScript]);
+
   const currentAgent = AGENT_ROLES[agentRole];
 
   if (isCollapsed) {
@@ -186,6 +198,32 @@ export const AgentChatPanel: FC<AgentChatPanelProps> = ({
           </div>
         )}
 
+        {/* Actions over the credit cap */}
+        {blockedActions.length > 0 && (
+          <div className="pending-actions">
+            {blockedActions.map((blocked, i) => (
+              <div key={i}>
+                <p className="actions-label">
+                  {blocked.action.type} needs ~{blocked.estimated_credits.toFixed(1)} credits
+                  (limit {blocked.credit_cap.toFixed(1)})
+                </p>
+                <button
+                  className="action-btn"
+                  onClick={() => handleConfirmClick(blocked.action)}
+                >
+                  ✅ Run Anyway
+                </button>
+                <button
+                  className="action-btn"
+                  onClick={() => dismissAction(blocked.action)}
+                >
+                  ✖️ Cancel
+                </button>
+              </div>
+            ))}
+          </div>
+        )}
+
         {error && (
           <div className="error-message">
             ⚠️ {error}
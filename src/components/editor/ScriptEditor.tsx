@@ -68,6 +68,7 @@ import AlternativeDialoguePlugin, { ADD_ALT_DIALOGUE_COMMAND, CYCLE_ALT_DIALOGUE
 import AlternativeDialogueDialog from "./AlternativeDialogueDialog";
 import PreferencesDialog, { usePreferences } from "../PreferencesDialog";
 import { calculatePagination, extractElementsFromRoot } from "../../lib/pagination";
+import { safeInvoke } from "../../utils/tauriMock";
 import type { ScriptContext, SceneNumbersConfig, MoresContinuedsConfig } from "../../types/scriptContext";
 
 const theme = {
@@ -79,23 +80,50 @@ function onError(error: Error) {
   console.error(error);
 }
 
-// Stats Plugin - Now uses true pagination
+interface PageMetrics {
+  total_pages: number;
+  scenes: unknown[];
+}
+
+// Stats Plugin - pages and scenes come from the backend scene parser
 function StatsPlugin({ onStatsChange }: { onStatsChange: (words: number, pages: number, scenes: number) => void }) {
   const [editor] = useLexicalComposerContext();
   useEffect(() => {
-    return editor.registerUpdateListener(({ editorState }) => {
+    let timer: ReturnType<typeof setTimeout> | undefined;
+    let latest = 0;
+
+    const unregister = editor.registerUpdateListener(({ editorState }) => {
       editorState.read(() => {
         const root = $getRoot();
         const text = root.getTextContent();
         const words = text.trim() ? text.trim().split(/\s+/).length : 0;
-        
-        // Use true pagination
         const elements = extractElementsFromRoot(root.getChildren());
-        const stats = calculatePagination(elements);
-        
-        onStatsChange(words, stats.pageCount, stats.sceneCount);
+
+        // Debounced: the parse runs once typing pauses
+        clearTimeout(timer);
+        timer = setTimeout(() => {
+          const request = ++latest;
+          safeInvoke<PageMetrics>("get_page_metrics", { elements })
+            .then((metrics) => {
+              if (request === latest) {
+                onStatsChange(words, metrics.total_pages, metrics.scenes.length);
+              }
+            })
+            .catch(() => {
+              // Browser-only mode: estimate locally
+              const stats = calculatePagination(elements);
+              if (request === latest) {
+                onStatsChange(words, stats.pageCount, stats.sceneCount);
+              }
+            });
+        }, 300);
       });
     });
+
+    return () => {
+      clearTimeout(timer);
+      unregister();
+    };
   }, [editor, onStatsChange]);
   return null;
 }
@@ -12,6 +12,7 @@
 
 import { useState, useEffect, useCallback, useMemo } from 'react';
 import { TokenType, Token, getTokenIcon } from '../../types/tokens';
+import type { AliasMerge } from '../../types/tokens';
 import TokenCard from './TokenCard';
 import TokenEditor from './TokenEditor';
 import { safeInvoke } from '../../utils/tauriMock';
@@ -232,12 +233,19 @@ export default function PreProductionLayout({ projectId }: PreProductionLayoutPr
 
       const extracted = await safeInvoke<any>('extract_tokens_from_script', {
         projectId,
-        scriptContent: scriptText
+        scriptContent: scriptText,
+        useAi: true
       });
 
+      // Aliases are only merged into one character when the user agrees
+      const acceptedMerges = (extracted.proposed_merges ?? []).filter((merge: AliasMerge) =>
+        confirm(`Treat ${merge.aliases.join(', ')} as ${merge.canonical}?`)
+      );
+
       const saved = await safeInvoke<Token[]>('save_extracted_tokens', {
         projectId,
-        extracted
+        extracted,
+        acceptedMerges
       });
 
       if (saved.length > 0) {
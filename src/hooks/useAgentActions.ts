@@ -5,8 +5,11 @@
 import { useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import type { AgentAction, ActionResult } from '../types/agents';
+import { DEFAULT_MAX_CREDITS } from '../types/agents';
 
 interface ActionHandler {
+  /** Credits an action may spend without confirmation (0 = no cap) */
+  maxCredits?: number;
   /** Handle UpdateScript action */
   onUpdateScript?: (content: string, mode: string, lineStart?: number, lineEnd?: number) => void;
   /** Handle AddToCanvas action */
@@ -15,29 +18,39 @@ interface ActionHandler {
   onShowMessage?: (title: string, content: string, suggestions: string[]) => void;
   /** Handle generation started */
   onGenerationStarted?: (executionId: string, type: 'image' | 'video' | 'audio') => void;
+  /** Handle an action blocked by the credit cap; call `executeAndHandle(action, true)` to run it anyway */
+  onConfirmationRequired?: (action: AgentAction, result: ActionResult) => void;
 }
 
 interface UseAgentActionsReturn {
-  /** Execute an action and handle the result */
-  executeAndHandle: (action: AgentAction) => Promise<ActionResult>;
+  /** Execute an action and handle the result (`confirmed` overrides the credit cap) */
+  executeAndHandle: (action: AgentAction, confirmed?: boolean) => Promise<ActionResult>;
   /** Execute a workflow in ComfyUI */
   executeWorkflow: (workflowJson: string) => Promise<string>;
 }
 
 export function useAgentActions(handlers: ActionHandler = {}): UseAgentActionsReturn {
   const {
+    maxCredits = DEFAULT_MAX_CREDITS,
     onUpdateScript,
     onAddToCanvas,
     onShowMessage,
     onGenerationStarted,
+    onConfirmationRequired,
   } = handlers;
 
   const executeAndHandle = useCallback(
-    async (action: AgentAction): Promise<ActionResult> => {
+    async (action: AgentAction, confirmed = false): Promise<ActionResult> => {
       try {
-        const result = await invoke<ActionResult>('execute_agent_action', { action });
+        const result = await invoke<ActionResult>('execute_agent_action', {
+          action,
+          maxCredits,
+          confirmed,
+        });
 
-        if (result.success && result.data) {
+        if (result.requires_confirmation) {
+          onConfirmationRequired?.(action, result);
+        } else if (result.success && result.data) {
           const data = JSON.parse(result.data);
 
           switch (action.type) {
@@ -74,7 +87,14 @@ export function useAgentActions(handlers: ActionHandler = {}): UseAgentActionsRe
         };
       }
     },
-    [onUpdateScript, onAddToCanvas, onShowMessage, onGenerationStarted]
+    [
+      maxCredits,
+      onUpdateScript,
+      onAddToCanvas,
+      onShowMessage,
+      onGenerationStarted,
+      onConfirmationRequired,
+    ]
   );
 
   const executeWorkflow = useCallback(async (workflowJson: string): Promise<string> => {
@@ -12,9 +12,11 @@ import type {
   FullAgentResponse,
   AgentAction,
   ActionResult,
+  BlockedAction,
   CrewChatRequest,
   CrewChatResponse,
 } from '../types/agents';
+import { DEFAULT_MAX_CREDITS } from '../types/agents';
 
 interface UseAgentChatOptions {
   /** Initial agent role */
@@ -25,6 +27,8 @@ interface UseAgentChatOptions {
   provider?: string;
   /** Model override */
   model?: string;
+  /** Credits one request or action may spend without confirmation (0 = no cap) */
+  maxCredits?: number;
 }
 
 interface UseAgentChatReturn {
@@ -42,6 +46,8 @@ interface UseAgentChatReturn {
   pendingActions: AgentAction[];
   /** Results from executed actions */
   actionResults: ActionResult[];
+  /** Actions over the credit cap, waiting for confirmation */
+  blockedActions: BlockedAction[];
   /** Send a message to the agent */
   sendMessage: (message: string, context?: AgentContext) => Promise<FullAgentResponse | null>;
   /** Change the active agent */
@@ -52,6 +58,10 @@ interface UseAgentChatReturn {
   executeAction: (action: AgentAction) => Promise<ActionResult>;
   /** Execute all pending actions */
   executeAllActions: () => Promise<ActionResult[]>;
+  /** Run a blocked action even though it exceeds the credit cap */
+  confirmAction: (action: AgentAction) => Promise<ActionResult>;
+  /** Drop a blocked action without running it */
+  dismissAction: (action: AgentAction) => void;
 }
 
 export function useAgentChat(options: UseAgentChatOptions = {}): UseAgentChatReturn {
@@ -60,6 +70,7 @@ export function useAgentChat(options: UseAgentChatOptions = {}): UseAgentChatRet
     autoExecute = false,
     provider,
     model,
+    maxCredits = DEFAULT_MAX_CREDITS,
   } = options;
 
   const [messages, setMessages] = useState<ChatMessage[]>([]);
@@ -69,6 +80,33 @@ export function useAgentChat(options: UseAgentChatOptions = {}): UseAgentChatRet
   const [lastResponse, setLastResponse] = useState<FullAgentResponse | null>(null);
   const [pendingActions, setPendingActions] = useState<AgentAction[]>([]);
   const [actionResults, setActionResults] = useState<ActionResult[]>([]);
+  const [blockedActions, setBlockedActions] = useState<BlockedAction[]>([]);
+
+  /** Run an action under the credit cap, parking it if the cap blocks it */
+  const runAction = useCallback(
+    async (action: AgentAction, confirmed = false): Promise<ActionResult> => {
+      const result = await invoke<ActionResult>('execute_agent_action', {
+        action,
+        maxCredits,
+        confirmed,
+      });
+
+      setBlockedActions((prev) => {
+        const others = prev.filter((blocked) => blocked.action !== action);
+        if (!result.requires_confirmation) return others;
+        return [
+          ...others,
+          {
+            action,
+            estimated_credits: result.estimated_credits ?? 0,
+            credit_cap: result.credit_cap ?? maxCredits,
+          },
+        ];
+      });
+      return result;
+    },
+    [maxCredits]
+  );
 
   const sendMessage = useCallback(
     async (message: string, context?: AgentContext): Promise<FullAgentResponse | null> => {
@@ -83,7 +121,7 @@ export function useAgentChat(options: UseAgentChatOptions = {}): UseAgentChatRet
         const request: CrewChatRequest = {
           message,
           prefer_local: true, // TODO: Get from preferences
-          max_credits: 10.0, // TODO: Get from preferences
+          max_credits: maxCredits,
           model_selection: provider ? { provider, model } : undefined,
           context,
         };
@@ -113,7 +151,7 @@ export function useAgentChat(options: UseAgentChatOptions = {}): UseAgentChatRet
             // Better to just call internal logic or let useEffect handle it if we had one.
             // For now, simple delay or call logic directly.
             response.actions.forEach(action => {
-                invoke<ActionResult>('execute_agent_action', { action }).then(result => {
+                runAction(action).then(result => {
                     setActionResults(prev => [...prev, result]);
                 });
             });
@@ -129,12 +167,12 @@ export function useAgentChat(options: UseAgentChatOptions = {}): UseAgentChatRet
         setIsLoading(false);
       }
     },
-    [agentRole, messages, provider, model, autoExecute]
+    [agentRole, messages, provider, model, autoExecute, maxCredits, runAction]
   );
   
   const executeAction = useCallback(async (action: AgentAction): Promise<ActionResult> => {
     try {
-      const result = await invoke<ActionResult>('execute_agent_action', { action });
+      const result = await runAction(action);
       setActionResults(prev => [...prev, result]);
       setPendingActions(prev => prev.filter(a => a !== action));
       return result;
@@ -146,12 +184,31 @@ export function useAgentChat(options: UseAgentChatOptions = {}): UseAgentChatRet
       };
       return result;
     }
+  }, [runAction]);
+
+  const confirmAction = useCallback(async (action: AgentAction): Promise<ActionResult> => {
+    try {
+      const result = await runAction(action, true);
+      setActionResults(prev => [...prev, result]);
+      return result;
+    } catch (err) {
+      const result: ActionResult = {
+        success: false,
+        action_type: action.type,
+        error: err instanceof Error ? err.message : String(err),
+      };
+      return result;
+    }
+  }, [runAction]);
+
+  const dismissAction = useCallback((action: AgentAction) => {
+    setBlockedActions(prev => prev.filter(blocked => blocked.action !== action));
   }, []);
 
   const executeAllActions = useCallback(async (): Promise<ActionResult[]> => {
     try {
       const promises = pendingActions.map(action => 
-        invoke<ActionResult>('execute_agent_action', { action })
+        runAction(action)
           .then(result => ({ ...result, originalAction: action }))
       );
       
@@ -169,13 +226,14 @@ export function useAgentChat(options: UseAgentChatOptions = {}): UseAgentChatRet
       };
       return [errorResult];
     }
-  }, [pendingActions]);
+  }, [pendingActions, runAction]);
 
   const clearHistory = useCallback(() => {
     setMessages([]);
     setLastResponse(null);
     setPendingActions([]);
     setActionResults([]);
+    setBlockedActions([]);
     setError(null);
   }, []);
 
@@ -187,11 +245,14 @@ export function useAgentChat(options: UseAgentChatOptions = {}): UseAgentChatRet
     lastResponse,
     pendingActions,
     actionResults,
+    blockedActions,
     sendMessage,
     setAgentRole,
     clearHistory,
     executeAction,
     executeAllActions,
+    confirmAction,
+    dismissAction,
   };
 }
 
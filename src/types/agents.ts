@@ -124,8 +124,23 @@ export interface ActionResult {
   data?: string; // JSON string, parse as needed
   error?: string;
   credits_used?: number;
+  /** Estimated cost, set when the action was checked against a cap */
+  estimated_credits?: number;
+  credit_cap?: number;
+  /** Blocked by the credit cap; run again with `confirmed` to go ahead */
+  requires_confirmation?: boolean;
 }
 
+/** An action the credit cap blocked, waiting for the user to confirm */
+export interface BlockedAction {
+  action: AgentAction;
+  estimated_credits: number;
+  credit_cap: number;
+}
+
+/** Credits a single request may spend unless the user confirms */
+export const DEFAULT_MAX_CREDITS = 10.0;
+
 export interface FullAgentResponse {
   message: string;
   agent_role: string;
@@ -40,6 +40,7 @@ export interface Token {
   lora_training_status?: 'none' | 'pending' | 'training' | 'completed' | 'failed';
   voice_id?: string;
   metadata: Record<string, string>;
+  aliases?: string[];
   
   // Advanced Context
   temporal_states?: TemporalState[];
@@ -89,12 +90,19 @@ export interface ExtractedEntity {
   description: string;
   mentions: number;
   first_appearance: string;
+  aliases?: string[];
+}
+
+export interface AliasMerge {
+  canonical: string;
+  aliases: string[];
 }
 
 export interface ExtractedTokens {
   characters: ExtractedEntity[];
   locations: ExtractedEntity[];
   props: ExtractedEntity[];
+  proposed_merges?: AliasMerge[];
 }
 
 export interface TokenContext {
@@ -5,7 +5,7 @@
  * This allows testing the UI without the Tauri desktop wrapper.
  */
 
-import type { Token, ExtractedTokens, ExtractedEntity } from '../types/tokens';
+import type { Token, ExtractedTokens, ExtractedEntity, AliasMerge } from '../types/tokens';
 
 // Check if we're running in Tauri
 const isTauri = () => {
@@ -87,7 +87,7 @@ const mockCommands: Record<string, (...args: any[]) => Promise<any>> = {
     return null;
   },
   
-  extract_tokens_from_script: async (args: { projectId: string; scriptContent: string }): Promise<ExtractedTokens> => {
+  extract_tokens_from_script: async (args: { projectId: string; scriptContent: string; useAi?: boolean }): Promise<ExtractedTokens> => {
     // Actually extract from the provided script content - no fake data!
     const { scriptContent } = args;
     
@@ -147,7 +147,7 @@ const mockCommands: Record<string, (...args: any[]) => Promise<any>> = {
     return { characters, locations, props: [] };
   },
   
-  save_extracted_tokens: async (args: { projectId: string; extracted: ExtractedTokens }) => {
+  save_extracted_tokens: async (args: { projectId: string; extracted: ExtractedTokens; acceptedMerges?: AliasMerge[] }) => {
     const saved: Token[] = [];
     const now = new Date().toISOString();
     